@@ -1,25 +1,84 @@
-var cacheName = 'egui-template-pwa';
-var filesToCache = [
-  // './',
-  // './index.html',
-  // './eframe_template.js',
-  // './eframe_template_bg.wasm',
+// Keeps the app shell and the fetched cache groups available offline.
+//
+// The shell (page, script, wasm and icons) is fetched network first so new builds show up on the
+// next load, falling back to the stored copy offline. OpenRS2 groups are immutable, so they're
+// served cache first from the caches the page stores them in, see src/test.js.
+var shellCacheName = 'rs-model-viewer-shell-v1';
+var groupCachePrefix = 'openrs2-';
+var groupUrlPrefix = 'https://archive.openrs2.org/caches/';
+var shellFiles = [
+  './',
+  './index.html',
+  './manifest.json',
+  './rs_model_viewer.js',
+  './rs_model_viewer_bg.wasm',
+  './assets/icon-256.png',
+  './assets/maskable_icon_x512.png',
 ];
 
-/* Start the service worker and cache all of the app's content */
 self.addEventListener('install', function (e) {
   e.waitUntil(
-    caches.open(cacheName).then(function (cache) {
-      return cache.addAll(filesToCache);
-    })
+    caches.open(shellCacheName)
+      .then(function (cache) {
+        return cache.addAll(shellFiles);
+      })
+      .then(function () {
+        return self.skipWaiting();
+      })
+  );
+});
+
+/* Drop the shells of older versions, the groups stay */
+self.addEventListener('activate', function (e) {
+  e.waitUntil(
+    caches.keys()
+      .then(function (names) {
+        return Promise.all(names
+          .filter(function (name) {
+            return name !== shellCacheName && !name.startsWith(groupCachePrefix);
+          })
+          .map(function (name) {
+            return caches.delete(name);
+          }));
+      })
+      .then(function () {
+        return self.clients.claim();
+      })
   );
 });
 
-/* Serve cached content when offline */
 self.addEventListener('fetch', function (e) {
+  var request = e.request;
+  if (request.method !== 'GET') {
+    return;
+  }
+  if (request.url.startsWith(groupUrlPrefix)) {
+    e.respondWith(
+      caches.match(request).then(function (response) {
+        return response || fetch(request);
+      })
+    );
+    return;
+  }
+  if (new URL(request.url).origin !== self.location.origin) {
+    return;
+  }
+  /* Also stores the files not in the list, like the hashed snippet paths */
   e.respondWith(
-    caches.match(e.request).then(function (response) {
-      return response || fetch(e.request);
-    })
+    fetch(request)
+      .then(function (response) {
+        if (response.ok) {
+          var copy = response.clone();
+          caches.open(shellCacheName).then(function (cache) {
+            return cache.put(request, copy);
+          });
+        }
+        return response;
+      })
+      .catch(function () {
+        return caches.match(request).then(function (response) {
+          return response || Promise.reject('offline');
+        });
+      })
   );
 });
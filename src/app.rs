@@ -1,124 +1,1193 @@
+mod animation_player;
+mod annotation;
+mod camera_path;
+mod dataset_export;
+mod definition_search;
+mod definitions;
+mod font_preview;
+mod frame_browser;
+mod gpu_timer;
+mod hex_inspector;
+mod hex_view;
+mod integrity;
+mod interface_viewer;
+mod item_browser;
+mod link;
+mod material_rules;
+mod model_format;
+pub mod model_image;
+mod model_info;
+mod music_browser;
+mod names;
+mod orbit_camera;
+pub mod plugin;
+mod post_process;
+mod profiler;
+mod save;
+mod script_console;
+mod sprite_browser;
+mod sprite_import;
+mod text_label;
+mod texture_browser;
+mod texture_uploads;
+mod triangle_order;
+
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     sync::Arc,
-    time::Instant,
+    time::Duration,
 };
 
 use eframe::{egui_glow, glow};
 use egui::mutex::Mutex;
-use wasm_bindgen::prelude::*;
 
+use crate::export::{png, ExportFile};
 use crate::runetek5::{
+    animation::seq::SeqType,
     graphics::{
-        model::{ModelFlags, ModelLit, ModelUnlit},
-        texture::TextureProvider,
+        model::{
+            FaceCulling, LightDetail, MergeSize, ModelFlags, ModelLit, ModelUnlit, TriangleShading,
+        },
+        orientation::Orientation,
+        rasterizer::Rasterizer,
+        texture::{TextureFilter, TextureProvider},
     },
-    js5::Js5,
+    js5::{net, Js5},
 };
 
+pub use link::linked_cache_id;
+#[cfg(not(target_arch = "wasm32"))]
+pub use save::pick_cache_dir;
+
+use animation_player::AnimationPlayerWindow;
+use camera_path::{CameraPathWindow, CameraPose};
+use dataset_export::DatasetExportWindow;
+use definition_search::DefinitionSearchWindow;
+use definitions::DefinitionScan;
+use font_preview::FontPreviewWindow;
+use frame_browser::FrameBrowserWindow;
+use gpu_timer::{GpuScope, GpuTimer};
+use hex_inspector::{HexInspectorWindow, InspectorArchive};
+use integrity::IntegrityWindow;
+use material_rules::MaterialRulesWindow;
+use model_format::ModelFormatWindow;
+use model_info::ModelInfoWindow;
+use names::{NameKind, NameTable, NamesWindow};
+use orbit_camera::OrbitCamera;
+use plugin::{PluginContext, PluginRegistry};
+use post_process::{PostProcessSettings, PostProcessor, ViewDepths};
+use profiler::{LoadStage, ModelLoadTimings, ProfilerWindow};
+use script_console::{ScriptCommand, ScriptConsoleWindow};
+use sprite_browser::SpriteBrowserWindow;
+use sprite_import::SpriteImportWindow;
+use text_label::TextLabelRenderer;
+use texture_browser::TextureBrowserWindow;
+use texture_uploads::TextureUploads;
+use triangle_order::TriangleOrderWindow;
+
 extern crate nalgebra_glm as glm;
 
-#[wasm_bindgen]
-extern "C" {
-    #[wasm_bindgen(js_namespace = performance)]
-    fn now() -> f64;
+/// Milliseconds since an arbitrary origin, `performance.now()` on the web.
+fn now() -> f64 {
+    crate::trace::timestamp() / 1000.0
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ColourPipeline {
+    /// Brightens palette colours with `pow(rgb, brightness)` like the client does.
+    Legacy,
+    /// Leaves palette colours unbrightened and multiplies textures into them in linear light,
+    /// converting both from sRGB and the product back. Lighting is still baked into the HSL
+    /// lightness.
+    LinearBlend,
+}
+
+impl ColourPipeline {
+    fn label(&self) -> &'static str {
+        match self {
+            ColourPipeline::Legacy => "Legacy (brightness)",
+            ColourPipeline::LinearBlend => "Linear texture blend",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RenderBackend {
+    Gl,
+    /// CPU reference renderer, drawn into an egui texture.
+    Software,
+}
+
+impl RenderBackend {
+    fn label(&self) -> &'static str {
+        match self {
+            RenderBackend::Gl => "OpenGL",
+            RenderBackend::Software => "Software (reference)",
+        }
+    }
+}
+
+/// Colour pipeline settings shared by the shader and texture decoding, so vertex colours and
+/// textures are always brightened the same way.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct RenderSettings {
+    backend: RenderBackend,
+    colour_pipeline: ColourPipeline,
+    brightness: f32,
+    /// Quantizes the output with an ordered dither to emulate the software renderer's banding.
+    dithering: bool,
+    texture_filter: TextureFilter,
+    face_culling: FaceCulling,
+    /// Draws triangles by priority and then back to front without the depth buffer, like the
+    /// client's software renderer, instead of depth testing them.
+    painter_order: bool,
+    camera: CameraSettings,
+    /// The light's direction in model space, lengths are in the client's units. The GL renderer
+    /// lights in the shader, so changing it doesn't upload the model again.
+    light_direction: (i32, i32, i32),
+    /// Effects applied after the GL renderer draws the viewport, off by default.
+    post_process: PostProcessSettings,
+}
+
+impl RenderSettings {
+    const DEFAULT_BRIGHTNESS: f32 = 0.7;
+    const MIN_BRIGHTNESS: f32 = 0.5;
+    const MAX_BRIGHTNESS: f32 = 1.0;
+
+    /// The brightness palette colours are raised to, which is identity when blending linearly.
+    fn effective_brightness(&self) -> f32 {
+        match self.colour_pipeline {
+            ColourPipeline::Legacy => self.brightness,
+            ColourPipeline::LinearBlend => 1.0,
+        }
+    }
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            backend: RenderBackend::Gl,
+            colour_pipeline: ColourPipeline::Legacy,
+            brightness: Self::DEFAULT_BRIGHTNESS,
+            dithering: false,
+            texture_filter: TextureFilter::default(),
+            face_culling: FaceCulling::default(),
+            painter_order: false,
+            camera: CameraSettings::default(),
+            light_direction: ModelViewer::LIGHT_DIRECTION,
+            post_process: PostProcessSettings::default(),
+        }
+    }
+}
+
+/// Clip planes and zoom limits of the main viewport.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CameraSettings {
+    /// Overrides the near plane derived from the camera distance and model radius.
+    near_plane: Option<f32>,
+    /// Overrides the far plane derived from the camera distance and model radius.
+    far_plane: Option<f32>,
+    min_zoom: f32,
+    max_zoom: f32,
+    /// Vertical field of view in degrees.
+    field_of_view: f32,
+    /// Degrees the view is turned around its direction, positive turns the image anticlockwise.
+    roll: f32,
+}
+
+impl CameraSettings {
+    const DEFAULT_NEAR_PLANE: f32 = 0.1;
+    const DEFAULT_FAR_PLANE: f32 = 100.0;
+    const DEFAULT_FIELD_OF_VIEW: f32 = 60.0;
+
+    /// The near and far planes for a camera `distance` away from a model of `model_radius`.
+    fn clip_planes(&self, distance: f32, model_radius: f32) -> (f32, f32) {
+        let far = self
+            .far_plane
+            .unwrap_or_else(|| (distance + model_radius * 2.0).max(Self::DEFAULT_FAR_PLANE));
+        // Keep the depth range within what a 24-bit depth buffer can resolve.
+        let near = self.near_plane.unwrap_or_else(|| {
+            ((distance - model_radius) * 0.5)
+                .max(far / 10000.0)
+                .max(0.001)
+        });
+        (near, far.max(near + 0.001))
+    }
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            near_plane: None,
+            far_plane: None,
+            min_zoom: 0.1,
+            max_zoom: 50.0,
+            field_of_view: Self::DEFAULT_FIELD_OF_VIEW,
+            roll: 0.0,
+        }
+    }
+}
+
+/// Screenshot request for the viewport, passed through [`egui::ViewportCommand::Screenshot`].
+struct ViewportScreenshot {
+    rect: egui::Rect,
+}
+
+/// A viewport screenshot to save, read back from the framebuffer right after the model is painted
+/// so the label and render stats drawn over it are left out.
+#[derive(Default)]
+struct ScreenshotCapture {
+    /// Whether the next paint should be captured, and with a transparent background.
+    pending: Option<bool>,
+    captured: Option<egui::ColorImage>,
 }
 
+/// What the software rasterizer clears to. Drawing never sets the top byte, so the background can
+/// be told apart from black parts of the model.
+const SOFTWARE_BACKGROUND: u32 = 0xff00_0000;
+
 struct ModelRenderContext {
     program: glow::Program,
     texture_array: glow::Texture,
+    /// Layers of the texture array waiting to be uploaded again.
+    texture_uploads: TextureUploads,
+    settings: RenderSettings,
+    /// Brightness the texture array layers are decoded with. Layers queued in `texture_uploads`
+    /// may still have an older one.
+    texture_brightness: f32,
+    /// Filter the texture array is currently sampled with.
+    texture_filter: TextureFilter,
     model_viewer: Arc<Mutex<ModelViewer>>,
+    gpu_timer: Arc<Mutex<GpuTimer>>,
+    screenshot: Arc<Mutex<ScreenshotCapture>>,
+    post_processor: Arc<Mutex<PostProcessor>>,
 }
 
 pub struct ModelViewerApp {
     gl: Arc<glow::Context>,
     render_ctx: ModelRenderContext,
     model_js5: Arc<Js5>,
+    frame_js5: Arc<Js5>,
+    framemap_js5: Arc<Js5>,
+    fontmetrics_js5: Arc<Js5>,
     texture_provider: TextureProvider,
     model_selector: ModelSelectorWindow,
+    texture_browser: TextureBrowserWindow,
+    dataset_export: DatasetExportWindow,
+    model_info: ModelInfoWindow,
+    definition_search: DefinitionSearchWindow,
+    /// NPC and item definitions, decoded while a window showing them is open.
+    definitions: DefinitionScan,
+    model_format: ModelFormatWindow,
+    frame_browser: FrameBrowserWindow,
+    animation_player: AnimationPlayerWindow,
+    hex_inspector: HexInspectorWindow,
+    sprite_browser: SpriteBrowserWindow,
+    sprite_import: SpriteImportWindow,
+    font_preview: FontPreviewWindow,
+    profiler: ProfilerWindow,
+    integrity: IntegrityWindow,
+    camera_path: CameraPathWindow,
+    script_console: ScriptConsoleWindow,
+    names: NamesWindow,
+    triangle_order: TriangleOrderWindow,
+    material_rules: MaterialRulesWindow,
+    /// HSL colours replaced in models shown in the viewport, set by scripts.
+    recolours: Vec<(u16, u16)>,
+    /// The model id being loaded and when it was selected, for timing the fetch.
+    load_started: Option<(u32, f64)>,
+    inspector_archives: Vec<InspectorArchive>,
+    plugins: PluginRegistry,
     selected_model_id: u32,
     current_model_id: u32,
-    yaw: f32,
-    pitch: f32,
-    zoom: f32,
+    current_model: Option<ModelLit>,
+    /// The shown model before it was posed, kept so it can be posed again by the animation player.
+    animation_base: Option<ModelUnlit>,
+    rasterizer: Rasterizer,
+    rasterizer_texture: Option<egui::TextureHandle>,
+    camera: OrbitCamera,
+    /// Mouse wheel scroll not yet turned into a model step.
+    wheel_scroll: f32,
+    /// Where the viewport was last drawn, in points.
+    viewport_rect: egui::Rect,
+    /// Models to merge into the viewport once all of their groups are loaded.
+    pending_merge: Option<Vec<u32>>,
+    /// Font the viewport is labelled with, loaded into `text_labels` once fetched.
+    label_font_id: Option<u32>,
+    text_labels: Option<TextLabelRenderer>,
+    /// Whether CPU and GPU frame times are shown over the viewport.
+    show_render_stats: bool,
+    /// CPU time of recent updates in milliseconds, averaged like the GPU timings.
+    cpu_frame_ms: f64,
+    /// Whether saved screenshots leave the background transparent.
+    screenshot_transparent: bool,
+    screenshot_status: Option<String>,
 }
 
 impl ModelViewerApp {
+    /// Points of wheel scrolling over the viewport per model step.
+    const WHEEL_STEP: f32 = 50.0;
+    /// How often to repaint while waiting on network fetches.
+    const FETCH_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
     pub fn new(
         cc: &eframe::CreationContext<'_>,
         model_js5: Arc<Js5>,
+        frame_js5: Arc<Js5>,
+        framemap_js5: Arc<Js5>,
+        fontmetrics_js5: Arc<Js5>,
         texture_provider: TextureProvider,
+        mut plugins: PluginRegistry,
     ) -> Self {
         let gl = cc.gl.as_ref().unwrap().clone();
-        let model_viewer = ModelViewer::new(6.0);
+        let model_viewer = ModelViewer::new();
         let program = Self::init_shader_program(&gl);
-        let texture_array = Self::init_texture_array(&gl, &texture_provider);
+        let settings = RenderSettings::default();
+        let mut texture_uploads =
+            TextureUploads::new(texture_provider.textures.len(), Self::TEXTURE_SIZE);
+        let texture_array = Self::init_texture_array(
+            &gl,
+            &texture_provider,
+            &mut texture_uploads,
+            settings.effective_brightness(),
+            settings.texture_filter,
+        );
+        let mut inspector_archives = vec![
+            InspectorArchive {
+                name: "Frames",
+                archive_id: 0,
+                js5: frame_js5.clone(),
+            },
+            InspectorArchive {
+                name: "Frame maps",
+                archive_id: 1,
+                js5: framemap_js5.clone(),
+            },
+            InspectorArchive {
+                name: "Models",
+                archive_id: 7,
+                js5: model_js5.clone(),
+            },
+            InspectorArchive {
+                name: "Sprites",
+                archive_id: 8,
+                js5: texture_provider.sprite_js5.clone(),
+            },
+            InspectorArchive {
+                name: "Font metrics",
+                archive_id: 13,
+                js5: fontmetrics_js5.clone(),
+            },
+        ];
+        for (archive_id, js5) in plugins.take_archives() {
+            inspector_archives.push(InspectorArchive {
+                name: plugins.archive_name(archive_id),
+                archive_id,
+                js5,
+            });
+        }
+        inspector_archives.sort_by_key(|archive| archive.archive_id);
+        let definitions = DefinitionScan::new(
+            &inspector_archives
+                .iter()
+                .map(|archive| (archive.archive_id, archive.js5.clone()))
+                .collect::<Vec<_>>(),
+        );
         let render_ctx = ModelRenderContext {
             program,
             texture_array,
+            texture_uploads,
+            settings,
+            texture_brightness: settings.effective_brightness(),
+            texture_filter: settings.texture_filter,
             model_viewer: Arc::new(Mutex::new(model_viewer)),
+            gpu_timer: Arc::new(Mutex::new(GpuTimer::new(&gl))),
+            screenshot: Arc::new(Mutex::new(ScreenshotCapture::default())),
+            post_processor: Arc::new(Mutex::new(PostProcessor::new(&gl))),
         };
         Self {
             gl: gl.clone(),
             render_ctx,
             model_js5,
+            frame_js5,
+            framemap_js5,
+            fontmetrics_js5,
             texture_provider,
             model_selector: ModelSelectorWindow::new(gl.clone()),
-            selected_model_id: 0,
+            texture_browser: TextureBrowserWindow::new(),
+            dataset_export: DatasetExportWindow::new(),
+            model_info: ModelInfoWindow::new(),
+            definition_search: DefinitionSearchWindow::new(),
+            definitions,
+            model_format: ModelFormatWindow::new(),
+            frame_browser: FrameBrowserWindow::new(),
+            animation_player: AnimationPlayerWindow::new(),
+            hex_inspector: HexInspectorWindow::new(),
+            sprite_browser: SpriteBrowserWindow::new(),
+            sprite_import: SpriteImportWindow::new(),
+            font_preview: FontPreviewWindow::new(),
+            profiler: ProfilerWindow::new(),
+            integrity: IntegrityWindow::new(),
+            camera_path: CameraPathWindow::new(),
+            script_console: ScriptConsoleWindow::new(),
+            names: NamesWindow::new(),
+            triangle_order: TriangleOrderWindow::new(),
+            material_rules: MaterialRulesWindow::new(),
+            recolours: Vec::new(),
+            load_started: None,
+            inspector_archives,
+            plugins,
+            selected_model_id: link::linked_model_id().unwrap_or(0),
             current_model_id: u32::MAX,
-            yaw: 90.0,
-            pitch: 0.0,
-            zoom: 1.0,
+            current_model: None,
+            animation_base: None,
+            rasterizer: Rasterizer::new(settings.brightness as f64),
+            rasterizer_texture: None,
+            camera: OrbitCamera::new(OrbitCamera::DEFAULT_YAW, 0.0, 1.0),
+            wheel_scroll: 0.0,
+            viewport_rect: egui::Rect::NOTHING,
+            pending_merge: None,
+            label_font_id: None,
+            text_labels: None,
+            show_render_stats: false,
+            cpu_frame_ms: 0.0,
+            screenshot_transparent: false,
+            screenshot_status: None,
+        }
+    }
+
+    /// Selects the model before or after the current one in the selector's list.
+    fn navigate(&mut self, forward: bool) {
+        if let Some(id) =
+            self.model_selector
+                .adjacent_id(&self.model_js5, self.selected_model_id, forward)
+        {
+            self.selected_model_id = id;
+        }
+    }
+
+    /// Merges the pending models into one and shows it, waiting for groups still being fetched.
+    fn merge_pending(&mut self) {
+        let Some(ids) = &self.pending_merge else {
+            return;
+        };
+        let mut models = Vec::with_capacity(ids.len());
+        let mut size = MergeSize::default();
+        let mut warning = None;
+        for (i, &id) in ids.iter().enumerate() {
+            // Leave out ids without a model and groups that never load, e.g. quarantined ones.
+            if self.model_js5.try_get_file(id, 0).is_err() {
+                continue;
+            }
+            let Some(model_unlit) = ModelUnlit::from_js5(&self.model_js5, id, 0) else {
+                return;
+            };
+            // The merged counts are 16 bit, so leave out models that no longer fit.
+            let merged_size = size.with(&model_unlit);
+            if !merged_size.fits() {
+                warning = Some(format!(
+                    "Left out the last {} of {} models, with model {id} the merge would have {merged_size}",
+                    ids.len() - i,
+                    ids.len()
+                ));
+                break;
+            }
+            size = merged_size;
+            models.push(model_unlit);
+        }
+        self.pending_merge = None;
+        self.model_selector.merge_warning = warning;
+        if models.is_empty() {
+            return;
+        }
+
+        let Ok(mut model_unlit) = ModelUnlit::merge(&models) else {
+            return;
+        };
+        self.apply_recolours(&mut model_unlit);
+        self.model_info
+            .set_model(self.selected_model_id, &model_unlit);
+        self.model_info.set_edited_model(&model_unlit);
+        // Scaled after merging, so the edited model above stays in the units of the format.
+        if model_unlit.version < 13 {
+            model_unlit.scale_log2(2);
+        }
+        self.triangle_order
+            .set_model(&self.texture_provider, &model_unlit);
+        self.animation_base = Some(model_unlit.clone());
+        self.animation_player.pose(&mut model_unlit);
+        let model = ModelLit::from_unlit_with_order(
+            &self.texture_provider,
+            &model_unlit,
+            ModelFlags::empty(),
+            64,
+            768,
+            LightDetail::Full,
+            self.triangle_order.order(),
+        );
+        self.render_ctx
+            .model_viewer
+            .lock()
+            .upload_model(&self.gl, &model);
+        self.current_model = Some(model);
+        // Keep showing the merged model until another model is selected.
+        self.current_model_id = self.selected_model_id;
+    }
+
+    /// Poses the shown model in the animation player's current frame and shows it again.
+    fn pose_current_model(&mut self) {
+        let Some(mut model_unlit) = self.animation_base.clone() else {
+            return;
+        };
+        self.animation_player.pose(&mut model_unlit);
+        let model = ModelLit::from_unlit_with_order(
+            &self.texture_provider,
+            &model_unlit,
+            ModelFlags::empty(),
+            64,
+            768,
+            LightDetail::Full,
+            self.triangle_order.order(),
+        );
+        self.render_ctx
+            .model_viewer
+            .lock()
+            .upload_model(&self.gl, &model);
+        self.current_model = Some(model);
+    }
+
+    /// Lists the models of an NPC or item in the selector and shows them recoloured like it, an
+    /// NPC's models merged into its body.
+    fn show_definition(&mut self, kind: NameKind, id: u32) {
+        let (label, model_ids, recolours, merge) = match kind {
+            NameKind::Npc => {
+                let Some(npc) = self.definitions.npcs.get(&id) else {
+                    return;
+                };
+                (
+                    format!("Models of NPC {id} ({})", npc.name),
+                    npc.models.clone(),
+                    npc.recolours.clone(),
+                    npc.models.len() > 1,
+                )
+            }
+            NameKind::Item => {
+                let Some(obj) = self.definitions.objs.get(&id) else {
+                    return;
+                };
+                let mut model_ids = Vec::new();
+                for &model_id in obj
+                    .model
+                    .iter()
+                    .chain(obj.male_models.iter().flatten())
+                    .chain(obj.female_models.iter().flatten())
+                {
+                    if !model_ids.contains(&model_id) {
+                        model_ids.push(model_id);
+                    }
+                }
+                (
+                    format!("Models of item {id} ({})", obj.name),
+                    model_ids,
+                    obj.recolours.clone(),
+                    false,
+                )
+            }
+            NameKind::Model | NameKind::Object => return,
+        };
+        let Some(&first_id) = model_ids.first() else {
+            return;
+        };
+        self.model_selector.set_filter(
+            label,
+            model_ids
+                .iter()
+                .map(|&model_id| model_id as usize)
+                .collect(),
+        );
+        self.selected_model_id = first_id;
+        self.pending_merge = merge.then_some(model_ids);
+        self.recolours = recolours;
+        // Reload even if the model is already shown, to apply the new colours.
+        self.current_model_id = u32::MAX;
+    }
+
+    fn apply_recolours(&self, model_unlit: &mut ModelUnlit) {
+        for &(old_colour, new_colour) in &self.recolours {
+            model_unlit.recolour(old_colour, new_colour);
+        }
+    }
+
+    /// Applies the commands of a script run, in order.
+    fn run_script_commands(&mut self) {
+        for command in std::mem::take(&mut self.script_console.commands) {
+            let result = match &command {
+                ScriptCommand::Cache(cache_id) => {
+                    link::open_cache(*cache_id, self.selected_model_id)
+                }
+                ScriptCommand::Model(id) if !self.model_js5.is_group_valid(*id) => {
+                    Err(format!("model {id} doesn't exist"))
+                }
+                ScriptCommand::Model(id) => {
+                    self.selected_model_id = *id;
+                    self.pending_merge = None;
+                    Ok(())
+                }
+                ScriptCommand::Merge(ids) => {
+                    self.pending_merge = Some(ids.clone());
+                    Ok(())
+                }
+                ScriptCommand::Recolour {
+                    old_colour,
+                    new_colour,
+                } => {
+                    self.recolours.push((*old_colour, *new_colour));
+                    Ok(())
+                }
+                ScriptCommand::Reset => {
+                    self.recolours.clear();
+                    Ok(())
+                }
+                ScriptCommand::Filter(ids) => {
+                    let ids = ids
+                        .iter()
+                        .filter(|&&id| self.model_js5.is_group_valid(id))
+                        .map(|&id| id as usize)
+                        .collect();
+                    self.model_selector
+                        .set_filter("Models from script".to_owned(), ids);
+                    Ok(())
+                }
+                ScriptCommand::Export(ids) => {
+                    self.dataset_export
+                        .export_ids(ids.clone(), self.render_ctx.settings.brightness);
+                    Ok(())
+                }
+            };
+            if matches!(
+                command,
+                ScriptCommand::Recolour { .. } | ScriptCommand::Reset
+            ) {
+                // Reload the shown model with the new colours.
+                self.current_model_id = u32::MAX;
+            }
+            self.script_console.log(match result {
+                Ok(()) => format!("> {}", command.describe()),
+                Err(err) => format!("> {}: {err}", command.describe()),
+            });
+        }
+    }
+
+    fn update_text_labels(&mut self) {
+        if let Some(font_id) = self.font_preview.label_font.take() {
+            self.label_font_id = Some(font_id);
+            self.text_labels = None;
+        }
+        if let (Some(font_id), None) = (self.label_font_id, &self.text_labels) {
+            self.text_labels = TextLabelRenderer::load(
+                font_id,
+                &self.texture_provider.sprite_js5,
+                &self.fontmetrics_js5,
+            );
+        }
+    }
+
+    /// Labels the viewport with the current model id in the chosen game font.
+    fn paint_label(&mut self, ui: &mut egui::Ui, rect: egui::Rect) {
+        const LABEL_SCALE: f32 = 2.0;
+        let Some(text_labels) = self.text_labels.as_mut() else {
+            return;
+        };
+        let text = format!("Model {}", self.selected_model_id);
+        let texture = text_labels.texture(ui.ctx(), &text, egui::Color32::YELLOW, true);
+        let label_rect = egui::Rect::from_min_size(
+            rect.min + egui::vec2(8.0, 8.0),
+            texture.size_vec2() * LABEL_SCALE,
+        );
+        ui.painter().image(
+            texture.id(),
+            label_rect,
+            egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+            egui::Color32::WHITE,
+        );
+    }
+
+    /// Shows the CPU time of updates and the GPU time of the viewport and previews in the top
+    /// right corner of the viewport.
+    fn paint_render_stats(&self, ui: &mut egui::Ui, rect: egui::Rect) {
+        if !self.show_render_stats {
+            return;
+        }
+        let mut lines = vec![format!("CPU update   {:6.2} ms", self.cpu_frame_ms)];
+        let gpu_timer = self.render_ctx.gpu_timer.lock();
+        if gpu_timer.is_supported() {
+            for scope in GpuScope::ALL {
+                lines.push(match gpu_timer.timing(scope) {
+                    Some(timing) if scope == GpuScope::Previews => format!(
+                        "GPU {:<8} {:6.2} ms ({} drawn)",
+                        scope.label().to_lowercase(),
+                        timing.ms,
+                        timing.passes
+                    ),
+                    Some(timing) => format!(
+                        "GPU {:<8} {:6.2} ms",
+                        scope.label().to_lowercase(),
+                        timing.ms
+                    ),
+                    None => format!("GPU {:<8}      - ms", scope.label().to_lowercase()),
+                });
+            }
+        } else {
+            lines.push("GPU timer queries unsupported".to_owned());
+        }
+        let painter = ui.painter_at(rect);
+        let galley = painter.layout_no_wrap(
+            lines.join("\n"),
+            egui::FontId::monospace(12.0),
+            egui::Color32::WHITE,
+        );
+        let text_pos = egui::pos2(rect.max.x - galley.size().x - 12.0, rect.min.y + 12.0);
+        painter.rect_filled(
+            egui::Rect::from_min_size(text_pos, galley.size()).expand(4.0),
+            4.0,
+            egui::Color32::from_black_alpha(160),
+        );
+        painter.galley(text_pos, galley, egui::Color32::WHITE);
+    }
+
+    /// Loads the selected model once its group is available, timing each stage.
+    fn load_selected_model(&mut self) {
+        let model_id = self.selected_model_id;
+        let started = match self.load_started {
+            Some((id, started)) if id == model_id => started,
+            _ => {
+                let started = now();
+                self.load_started = Some((model_id, started));
+                started
+            }
+        };
+        // Quarantined groups would never load, keep showing the previous model.
+        if self.model_js5.is_group_corrupt(model_id) {
+            self.load_started = None;
+            return;
+        }
+        if self.model_js5.get_packed_group(model_id).is_none() {
+            return;
+        }
+        let mut timings = ModelLoadTimings::new(model_id);
+        let mut stage_start = now();
+        timings.set(LoadStage::Fetch, stage_start - started);
+        let mut end_stage = |timings: &mut ModelLoadTimings, stage| {
+            let end = now();
+            timings.set(stage, end - stage_start);
+            stage_start = end;
+        };
+
+        let Some(model_data) = self.model_js5.get_file(model_id, 0) else {
+            return;
+        };
+        end_stage(&mut timings, LoadStage::Decompress);
+
+        let mut model_unlit = ModelUnlit::new();
+        // Shows nothing rather than retrying every frame.
+        if let Err(err) = model_unlit.decode(&model_data) {
+            log::warn!("Model {model_id} couldn't be decoded: {err}");
+            model_unlit = ModelUnlit::new();
+        }
+        end_stage(&mut timings, LoadStage::Decode);
+        self.model_info.set_model(model_id, &model_unlit);
+
+        self.apply_recolours(&mut model_unlit);
+        self.model_info.set_edited_model(&model_unlit);
+        if model_unlit.version < 13 {
+            model_unlit.scale_log2(2);
+        }
+        self.triangle_order
+            .set_model(&self.texture_provider, &model_unlit);
+        // Framing needs the bounds anyway. Cached before the copy, the base keeps them across
+        // poses and the lit model starts with them unless a pose moved the vertices.
+        model_unlit.get_bounds();
+        self.animation_base = Some(model_unlit.clone());
+        self.animation_player.pose(&mut model_unlit);
+
+        let model = ModelLit::from_unlit_with_order(
+            &self.texture_provider,
+            &model_unlit,
+            ModelFlags::empty(),
+            64,
+            768,
+            LightDetail::Full,
+            self.triangle_order.order(),
+        );
+        end_stage(&mut timings, LoadStage::Light);
+
+        self.render_ctx
+            .model_viewer
+            .lock()
+            .upload_model(&self.gl, &model);
+        end_stage(&mut timings, LoadStage::Upload);
+
+        self.profiler.record(timings);
+        self.load_started = None;
+        self.current_model = Some(model);
+        self.current_model_id = model_id;
+    }
+
+    fn handle_navigation_keys(&mut self, ctx: &egui::Context) {
+        if ctx.wants_keyboard_input() {
+            return;
+        }
+        let (next, previous) = ctx.input(|input| {
+            (
+                input.key_pressed(egui::Key::ArrowRight) || input.key_pressed(egui::Key::ArrowDown),
+                input.key_pressed(egui::Key::ArrowLeft) || input.key_pressed(egui::Key::ArrowUp),
+            )
+        });
+        if next {
+            self.navigate(true);
+        } else if previous {
+            self.navigate(false);
+        }
+        let (focus, reset) = ctx.input(|input| {
+            (
+                input.key_pressed(egui::Key::F),
+                input.key_pressed(egui::Key::Home),
+            )
+        });
+        if focus {
+            self.focus_on_model();
+        } else if reset {
+            self.reset_view();
         }
     }
 
+    /// The centre of the shown model's bounding box, in the units the camera works in.
+    fn model_centre(&mut self) -> Option<glm::Vec3> {
+        let (x, y, z) = self.current_model.as_mut()?.get_center();
+        Some(glm::vec3(
+            x as f32 / 512.0,
+            -y as f32 / 512.0,
+            -z as f32 / 512.0,
+        ))
+    }
+
+    /// Orbits the centre of the shown model, keeping the angles and zoom.
+    fn focus_on_model(&mut self) {
+        if let Some(centre) = self.model_centre() {
+            self.camera.focus(centre);
+        }
+    }
+
+    /// Faces the shown model from the default angle, orbiting its centre at the distance fitted
+    /// to its radius.
+    fn reset_view(&mut self) {
+        let centre = self.model_centre().unwrap_or_else(glm::Vec3::zeros);
+        self.camera.reset(centre);
+    }
+
     fn custom_painting(&mut self, ui: &mut egui::Ui) {
         let (rect, response) =
             ui.allocate_exact_size(ui.available_size(), egui::Sense::click_and_drag());
-
-        if response.dragged_by(egui::PointerButton::Secondary) {
-            // Add panning
+        self.viewport_rect = rect;
+        response.context_menu(|ui| self.viewport_menu(ui));
+
+        let shift = ui.ctx().input(|i| i.modifiers.shift);
+        if response.dragged_by(egui::PointerButton::Secondary)
+            || (shift && response.dragged_by(egui::PointerButton::Primary))
+        {
+            let distance = self.render_ctx.model_viewer.lock().radius * self.camera.zoom;
+            let camera = self.render_ctx.settings.camera;
+            self.camera.pan(
+                response.drag_motion(),
+                distance,
+                rect.height(),
+                camera.field_of_view,
+                camera.roll,
+            );
         } else {
-            self.yaw += response.drag_motion().x * 0.3;
-            self.pitch += response.drag_motion().y * 0.3;
-            if self.pitch > 89.0 {
-                self.pitch = 89.0;
-            } else if self.pitch < -89.0 {
-                self.pitch = -89.0;
-            }
+            self.camera.rotate(response.drag_motion());
+        }
+        if response.double_clicked() {
+            self.focus_on_model();
         }
         if response.contains_pointer() {
             let zoom_delta = ui.ctx().input(|i| i.zoom_delta());
-            self.zoom -= (zoom_delta - 1.0) * 0.3;
-            if self.zoom < 0.1 {
-                self.zoom = 0.1;
+            // Scale relative to the current zoom so zooming out stays usable on huge models.
+            self.camera.zoom -= (zoom_delta - 1.0) * 0.3 * self.camera.zoom.max(1.0);
+            let camera = self.render_ctx.settings.camera;
+            self.camera.zoom = self
+                .camera
+                .zoom
+                .clamp(camera.min_zoom, camera.max_zoom.max(camera.min_zoom));
+
+            // Plain wheel scrolling steps through models, ctrl+wheel zooms.
+            if zoom_delta == 1.0 {
+                self.wheel_scroll += ui.ctx().input(|i| i.raw_scroll_delta.y);
+                while self.wheel_scroll.abs() >= Self::WHEEL_STEP {
+                    let forward = self.wheel_scroll < 0.0;
+                    self.wheel_scroll -= Self::WHEEL_STEP.copysign(self.wheel_scroll);
+                    self.navigate(forward);
+                }
             }
+        } else {
+            self.wheel_scroll = 0.0;
+        }
+
+        if self.render_ctx.settings.backend == RenderBackend::Software {
+            self.paint_software(ui, rect);
+            self.paint_label(ui, rect);
+            self.paint_render_stats(ui, rect);
+            return;
         }
 
         // Clone locals so we can move them into the paint callback:
-        let yaw = self.yaw.to_radians();
-        let pitch = self.pitch.to_radians();
-        let zoom = self.zoom;
+        let camera = self.camera;
         let program = self.render_ctx.program;
         let texture_array = self.render_ctx.texture_array;
+        let settings = self.render_ctx.settings;
         let model_viewer = self.render_ctx.model_viewer.clone();
+        let gpu_timer = self.render_ctx.gpu_timer.clone();
+        let screenshot = self.render_ctx.screenshot.clone();
+        let post_processor = self.render_ctx.post_processor.clone();
 
         let callback = egui::PaintCallback {
             rect,
-            callback: std::sync::Arc::new(egui_glow::CallbackFn::new(move |_info, painter| {
-                model_viewer.lock().paint(
+            callback: std::sync::Arc::new(egui_glow::CallbackFn::new(move |info, painter| {
+                let capture = screenshot.lock().pending.take();
+                if capture == Some(true) {
+                    use glow::HasContext as _;
+                    // Only the viewport is cleared, egui scissors callbacks to their rect.
+                    unsafe {
+                        painter.gl().clear_color(0.0, 0.0, 0.0, 0.0);
+                        painter.gl().clear(glow::COLOR_BUFFER_BIT);
+                    }
+                }
+                gpu_timer.lock().begin(painter.gl(), GpuScope::Viewport);
+                let post_process = settings.post_process;
+                if post_process.enabled {
+                    post_processor.lock().begin(painter.gl(), &info);
+                }
+                let model_viewer = model_viewer.lock();
+                model_viewer.paint(
                     painter.gl(),
                     rect.width(),
                     rect.height(),
-                    yaw,
-                    pitch,
-                    zoom,
+                    &camera,
                     program,
                     texture_array,
+                    settings,
                 );
+                if post_process.enabled {
+                    post_processor.lock().finish(
+                        painter.gl(),
+                        &info,
+                        painter.intermediate_fbo(),
+                        &post_process,
+                        model_viewer.view_depths(&camera, settings.camera),
+                    );
+                }
+                gpu_timer.lock().end(painter.gl());
+                if let Some(transparent) = capture {
+                    screenshot.lock().captured =
+                        Some(read_viewport(painter.gl(), &info, transparent));
+                }
             })),
         };
         ui.painter().add(callback);
+        self.paint_label(ui, rect);
+        self.paint_render_stats(ui, rect);
+    }
+
+    /// Camera and clipboard actions for the current model, opened by right clicking the viewport.
+    fn viewport_menu(&mut self, ui: &mut egui::Ui) {
+        let model_id = self.current_model_id;
+        if ui
+            .button("Focus on model")
+            .on_hover_text("Orbit the centre of the model (F or double click)")
+            .clicked()
+        {
+            self.focus_on_model();
+            ui.close_menu();
+        }
+        if ui
+            .button("Reset view")
+            .on_hover_text("Face the model from the front, centred (Home)")
+            .clicked()
+        {
+            self.reset_view();
+            ui.close_menu();
+        }
+        ui.separator();
+        if ui.button("Copy model id").clicked() {
+            ui.ctx().copy_text(model_id.to_string());
+            ui.close_menu();
+        }
+        if let Some(json) = self.model_info.metadata_json() {
+            if ui.button("Copy metadata JSON").clicked() {
+                ui.ctx().copy_text(json);
+                ui.close_menu();
+            }
+        }
+        if let Some(link) = link::model_link(model_id) {
+            if ui.button("Copy link").clicked() {
+                ui.ctx().copy_text(link);
+                ui.close_menu();
+            }
+        }
+        if ui.button("Copy screenshot").clicked() {
+            ui.ctx()
+                .send_viewport_cmd(egui::ViewportCommand::Screenshot(egui::UserData::new(
+                    ViewportScreenshot {
+                        rect: self.viewport_rect,
+                    },
+                )));
+            ui.close_menu();
+        }
+        if ui.button("Save screenshot").clicked() {
+            self.render_ctx.screenshot.lock().pending = Some(self.screenshot_transparent);
+            ui.ctx().request_repaint();
+            ui.close_menu();
+        }
+        ui.checkbox(&mut self.screenshot_transparent, "Transparent background")
+            .on_hover_text("Leave the background out of saved screenshots");
+        if let Some(status) = &self.screenshot_status {
+            ui.label(status);
+        }
+    }
+
+    /// Annotates a captured screenshot and saves it as a PNG.
+    fn save_screenshot(&mut self, mut image: egui::ColorImage) {
+        self.camera_path
+            .annotation
+            .burn_colour_image(self.current_model_id, &mut image);
+        let rgba: Vec<u8> = image
+            .pixels
+            .iter()
+            .flat_map(|pixel| pixel.to_srgba_unmultiplied())
+            .collect();
+        let file = ExportFile {
+            path: format!("model_{}.png", self.current_model_id),
+            data: png::encode_rgba(image.size[0] as u32, image.size[1] as u32, &rgba),
+        };
+        self.screenshot_status = match save::save_file_as("Save screenshot", "PNG image", file) {
+            Some(Ok(location)) => Some(format!("Saved to {location}")),
+            Some(Err(err)) => Some(format!("Saving failed: {err}")),
+            None => None,
+        };
+    }
+
+    /// Crops replies to screenshot requests to the viewport, annotates them and copies them as an
+    /// image, and saves screenshots captured by the last paint.
+    fn handle_screenshots(&mut self, ctx: &egui::Context) {
+        let screenshots: Vec<(egui::Rect, Arc<egui::ColorImage>)> = ctx.input(|input| {
+            input
+                .raw
+                .events
+                .iter()
+                .filter_map(|event| match event {
+                    egui::Event::Screenshot {
+                        user_data, image, ..
+                    } => {
+                        let request = user_data
+                            .data
+                            .as_ref()?
+                            .downcast_ref::<ViewportScreenshot>()?;
+                        Some((request.rect, image.clone()))
+                    }
+                    _ => None,
+                })
+                .collect()
+        });
+        for (rect, image) in screenshots {
+            let mut image = image.region(&rect, Some(ctx.pixels_per_point()));
+            self.camera_path
+                .annotation
+                .burn_colour_image(self.current_model_id, &mut image);
+            ctx.copy_image(image);
+        }
+
+        let captured = self.render_ctx.screenshot.lock().captured.take();
+        if let Some(image) = captured {
+            self.save_screenshot(image);
+        }
+    }
+
+    fn paint_software(&mut self, ui: &mut egui::Ui, rect: egui::Rect) {
+        let pixels_per_point = ui.ctx().pixels_per_point();
+        let width = (rect.width() * pixels_per_point).round().max(1.0) as usize;
+        let height = (rect.height() * pixels_per_point).round().max(1.0) as usize;
+
+        let rasterizer = &mut self.rasterizer;
+        rasterizer.set_brightness(self.render_ctx.settings.brightness as f64);
+        rasterizer.face_culling = self.render_ctx.settings.face_culling;
+        rasterizer.resize(width, height);
+        rasterizer.clear(SOFTWARE_BACKGROUND);
+
+        if let Some(model) = &self.current_model {
+            let (view, projection) = self.render_ctx.model_viewer.lock().camera_matrices(
+                width as f32,
+                height as f32,
+                &self.camera,
+                self.render_ctx.settings.camera,
+            );
+            let (light_x, light_y, light_z) = self.render_ctx.settings.light_direction;
+            rasterizer.render_model(
+                &self.texture_provider,
+                model,
+                &(projection * view),
+                light_x,
+                light_y,
+                light_z,
+            );
+        }
+
+        if let Some(transparent) = self.render_ctx.screenshot.lock().pending.take() {
+            let pixels = rasterizer
+                .pixels
+                .iter()
+                .map(|&rgb| {
+                    let [_, r, g, b] = rgb.to_be_bytes();
+                    if transparent && rgb == SOFTWARE_BACKGROUND {
+                        egui::Color32::TRANSPARENT
+                    } else {
+                        egui::Color32::from_rgb(r, g, b)
+                    }
+                })
+                .collect();
+            self.render_ctx.screenshot.lock().captured = Some(egui::ColorImage {
+                size: [width, height],
+                pixels,
+            });
+        }
+
+        let pixels: Vec<egui::Color32> = rasterizer
+            .pixels
+            .iter()
+            .map(|&rgb| egui::Color32::from_rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8))
+            .collect();
+        let image = egui::ColorImage {
+            size: [width, height],
+            pixels,
+        };
+
+        let texture = match &mut self.rasterizer_texture {
+            Some(texture) => {
+                texture.set(image, egui::TextureOptions::NEAREST);
+                texture
+            }
+            None => self.rasterizer_texture.insert(ui.ctx().load_texture(
+                "software_viewport",
+                image,
+                egui::TextureOptions::NEAREST,
+            )),
+        };
+
+        ui.painter().image(
+            texture.id(),
+            rect,
+            egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+            egui::Color32::WHITE,
+        );
     }
 
     fn init_shader_program(gl: &Arc<glow::Context>) -> glow::Program {
@@ -141,12 +1210,21 @@ impl ModelViewerApp {
 
                     uniform mat4 u_view;
                     uniform mat4 u_projection;
+                    uniform vec3 u_light;
+                    // The light's length scaled by the model's contrast.
+                    uniform int u_light_scale;
+                    uniform int u_ambient;
 
                     layout (location = 0) in vec3 a_position;
                     layout (location = 1) in uint a_hsl;
                     layout (location = 2) in float a_alpha;
                     layout (location = 3) in vec2 a_texcoord;
                     layout (location = 4) in uint a_texture_id;
+                    // The summed normal with the number of faces summed, FLAT_SHADED for a face
+                    // normal or 0 for unlit triangles.
+                    layout (location = 5) in vec4 a_normal;
+
+                    const float FLAT_SHADED = 32767.0;
 
                     flat out int v_hs;
                     #ifdef GL_NV_shader_noperspective_interpolation
@@ -158,10 +1236,25 @@ impl ModelViewerApp {
                     out vec2 v_texcoord;
                     flat out int v_texture_id;
 
+                    // The client's integer lighting, see ModelLit::calc_lit_colours.
+                    int lightness(int hsl) {
+                        if (a_normal.w == 0.0) {
+                            return hsl & 0x7f;
+                        }
+                        int divisor = a_normal.w == FLAT_SHADED
+                            ? u_light_scale / 2 + u_light_scale
+                            : u_light_scale * int(a_normal.w);
+                        int light = int(dot(u_light, a_normal.xyz) / float(divisor)) + u_ambient;
+                        if (a_texture_id == 0u) {
+                            light = (hsl & 0x7f) * light >> 7;
+                        }
+                        return clamp(light, 2, 126);
+                    }
+
                     void main() {
                         int hsl = int(a_hsl);
                         v_hs = hsl & 0xff80;
-                        v_lightness = float(hsl & 0x7f);
+                        v_lightness = float(lightness(hsl));
                         v_alpha = a_alpha;
                         v_texcoord = a_texcoord;
                         v_texture_id = int(a_texture_id);
@@ -177,6 +1270,16 @@ impl ModelViewerApp {
                     precision mediump float;
 
                     uniform highp sampler2DArray u_texture_array;
+                    uniform float u_brightness;
+                    uniform bool u_linear_blend;
+                    uniform bool u_dither;
+
+                    const float bayer4x4[16] = float[16](
+                        0.0, 8.0, 2.0, 10.0,
+                        12.0, 4.0, 14.0, 6.0,
+                        3.0, 11.0, 1.0, 9.0,
+                        15.0, 7.0, 13.0, 5.0
+                    );
 
                     flat in int v_hs;
                     #ifdef GL_NV_shader_noperspective_interpolation
@@ -233,14 +1336,43 @@ impl ModelViewerApp {
                         return pow(rgb, vec3(brightness));
                     }
 
+                    vec3 srgbToLinear(vec3 rgb) {
+                        return mix(rgb / 12.92, pow((rgb + 0.055) / 1.055, vec3(2.4)), step(0.04045, rgb));
+                    }
+
+                    vec3 linearToSrgb(vec3 rgb) {
+                        return mix(rgb * 12.92, 1.055 * pow(rgb, vec3(1.0 / 2.4)) - 0.055, step(0.0031308, rgb));
+                    }
+
+                    // Quantizes to 5 bits per channel using a 4x4 ordered dither.
+                    vec3 dither(vec3 rgb) {
+                        ivec2 p = ivec2(gl_FragCoord.xy) & 3;
+                        float threshold = (bayer4x4[p.y * 4 + p.x] + 0.5) / 16.0 - 0.5;
+                        return clamp(floor(rgb * 31.0 + 0.5 + threshold) / 31.0, 0.0, 1.0);
+                    }
+
                     void main() {
-                        out_color = vec4(hslToRgb(v_hs | int(v_lightness), 0.7), v_alpha);
+                        vec4 colour = vec4(hslToRgb(v_hs | int(v_lightness), u_brightness), v_alpha);
+                        if (u_linear_blend) {
+                            colour.rgb = srgbToLinear(colour.rgb);
+                        }
                         if (v_texture_id > 0) {
-                            out_color *= texture(u_texture_array, vec3(v_texcoord, float(v_texture_id - 1))).bgra;
-                            if (out_color.a < 0.1) {
+                            vec4 texel = texture(u_texture_array, vec3(v_texcoord, float(v_texture_id - 1))).bgra;
+                            if (u_linear_blend) {
+                                texel.rgb = srgbToLinear(texel.rgb);
+                            }
+                            colour *= texel;
+                            if (colour.a < 0.1) {
                                 discard;
                             }
                         }
+                        if (u_linear_blend) {
+                            colour.rgb = linearToSrgb(colour.rgb);
+                        }
+                        if (u_dither) {
+                            colour.rgb = dither(colour.rgb);
+                        }
+                        out_color = colour;
                     }
                 "#,
             );
@@ -284,13 +1416,18 @@ impl ModelViewerApp {
         }
     }
 
+    const TEXTURE_SIZE: i32 = 128;
+
     fn init_texture_array(
         gl: &Arc<glow::Context>,
         texture_provider: &TextureProvider,
+        texture_uploads: &mut TextureUploads,
+        brightness: f32,
+        filter: TextureFilter,
     ) -> glow::Texture {
         use glow::HasContext as _;
 
-        let texture_size = 128;
+        let texture_size = Self::TEXTURE_SIZE;
         let texture_count = texture_provider.textures.len();
 
         unsafe {
@@ -306,40 +1443,9 @@ impl ModelViewerApp {
                 texture_count as i32,
             );
 
-            for &texture_id in texture_provider.get_texture_ids().iter() {
-                if let Some(pixels) = texture_provider.get_pixels_argb(
-                    texture_id,
-                    texture_size as u16,
-                    texture_size as u16,
-                    false,
-                    0.7,
-                ) {
-                    gl.tex_sub_image_3d(
-                        glow::TEXTURE_2D_ARRAY,
-                        0,
-                        0,
-                        0,
-                        texture_id as i32,
-                        texture_size,
-                        texture_size,
-                        1,
-                        glow::RGBA,
-                        glow::UNSIGNED_BYTE,
-                        glow::PixelUnpackData::Slice(Some(bytemuck::cast_slice(&pixels))),
-                    );
-                }
-            }
+            texture_uploads.upload_all(gl, texture_array, texture_provider, brightness);
+            Self::set_texture_filter(gl, texture_array, filter);
 
-            gl.tex_parameter_i32(
-                glow::TEXTURE_2D_ARRAY,
-                glow::TEXTURE_MIN_FILTER,
-                glow::LINEAR as i32,
-            );
-            gl.tex_parameter_i32(
-                glow::TEXTURE_2D_ARRAY,
-                glow::TEXTURE_MAG_FILTER,
-                glow::LINEAR as i32,
-            );
             gl.tex_parameter_i32(
                 glow::TEXTURE_2D_ARRAY,
                 glow::TEXTURE_WRAP_S,
@@ -354,64 +1460,535 @@ impl ModelViewerApp {
             texture_array
         }
     }
-}
 
-impl eframe::App for ModelViewerApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        egui::CentralPanel::default()
-            .frame(egui::Frame::new().fill(egui::Color32::BLACK))
-            .show(ctx, |ui| {
-                self.custom_painting(ui);
-            });
+    fn set_texture_filter(gl: &glow::Context, texture_array: glow::Texture, filter: TextureFilter) {
+        use glow::HasContext as _;
 
-        self.model_selector.show(
-            ctx,
-            &self.render_ctx,
-            &self.model_js5,
+        let filter = match filter {
+            TextureFilter::Nearest => glow::NEAREST,
+            TextureFilter::Linear => glow::LINEAR,
+        };
+        unsafe {
+            gl.active_texture(glow::TEXTURE0);
+            gl.bind_texture(glow::TEXTURE_2D_ARRAY, Some(texture_array));
+            gl.tex_parameter_i32(
+                glow::TEXTURE_2D_ARRAY,
+                glow::TEXTURE_MIN_FILTER,
+                filter as i32,
+            );
+            gl.tex_parameter_i32(
+                glow::TEXTURE_2D_ARRAY,
+                glow::TEXTURE_MAG_FILTER,
+                filter as i32,
+            );
+        }
+    }
+
+    fn camera_settings_ui(ui: &mut egui::Ui, camera: &mut CameraSettings) {
+        for (label, plane, default) in [
+            (
+                "Near plane",
+                &mut camera.near_plane,
+                CameraSettings::DEFAULT_NEAR_PLANE,
+            ),
+            (
+                "Far plane",
+                &mut camera.far_plane,
+                CameraSettings::DEFAULT_FAR_PLANE,
+            ),
+        ] {
+            ui.horizontal(|ui| {
+                let mut overridden = plane.is_some();
+                if ui.checkbox(&mut overridden, label).changed() {
+                    *plane = overridden.then_some(default);
+                }
+                match plane {
+                    Some(value) => {
+                        ui.add(
+                            egui::DragValue::new(value)
+                                .speed(0.01)
+                                .range(0.001..=100000.0),
+                        );
+                    }
+                    None => {
+                        ui.weak("Auto");
+                    }
+                }
+            });
+        }
+        ui.horizontal(|ui| {
+            ui.label("Zoom");
+            ui.add(
+                egui::DragValue::new(&mut camera.min_zoom)
+                    .speed(0.01)
+                    .range(0.01..=1.0)
+                    .prefix("min "),
+            );
+            ui.add(
+                egui::DragValue::new(&mut camera.max_zoom)
+                    .speed(0.1)
+                    .range(1.0..=1000.0)
+                    .prefix("max "),
+            );
+        });
+        ui.add(
+            egui::Slider::new(&mut camera.field_of_view, 10.0..=120.0)
+                .suffix("°")
+                .text("Field of view"),
+        );
+        ui.add(
+            egui::Slider::new(&mut camera.roll, -180.0..=180.0)
+                .suffix("°")
+                .text("Roll"),
+        )
+        .on_hover_text("Turns the view around its direction");
+    }
+
+    fn show_settings(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Settings")
+            .default_open(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let settings = &mut self.render_ctx.settings;
+                egui::ComboBox::from_label("Renderer")
+                    .selected_text(settings.backend.label())
+                    .show_ui(ui, |ui| {
+                        for backend in [RenderBackend::Gl, RenderBackend::Software] {
+                            ui.selectable_value(&mut settings.backend, backend, backend.label());
+                        }
+                    });
+                egui::ComboBox::from_label("Colour pipeline")
+                    .selected_text(settings.colour_pipeline.label())
+                    .show_ui(ui, |ui| {
+                        for pipeline in [ColourPipeline::Legacy, ColourPipeline::LinearBlend] {
+                            ui.selectable_value(
+                                &mut settings.colour_pipeline,
+                                pipeline,
+                                pipeline.label(),
+                            );
+                        }
+                    });
+                ui.add_enabled(
+                    settings.colour_pipeline == ColourPipeline::Legacy,
+                    egui::Slider::new(
+                        &mut settings.brightness,
+                        RenderSettings::MIN_BRIGHTNESS..=RenderSettings::MAX_BRIGHTNESS,
+                    )
+                    .text("Brightness"),
+                );
+                ui.checkbox(&mut settings.dithering, "Dithering");
+                ui.horizontal(|ui| {
+                    let (x, y, z) = &mut settings.light_direction;
+                    for (value, axis) in [(x, "x "), (y, "y "), (z, "z ")] {
+                        ui.add(egui::DragValue::new(value).range(-100..=100).prefix(axis));
+                    }
+                    // Lighting divides by the light's length.
+                    if settings.light_direction == (0, 0, 0) {
+                        settings.light_direction = ModelViewer::LIGHT_DIRECTION;
+                    }
+                    ui.label("Light direction");
+                });
+                ui.checkbox(&mut self.show_render_stats, "Show render stats")
+                    .on_hover_text("CPU and GPU frame times over the viewport");
+                egui::ComboBox::from_label("Texture filtering")
+                    .selected_text(settings.texture_filter.label())
+                    .show_ui(ui, |ui| {
+                        for filter in [TextureFilter::Nearest, TextureFilter::Linear] {
+                            ui.selectable_value(
+                                &mut settings.texture_filter,
+                                filter,
+                                filter.label(),
+                            );
+                        }
+                    })
+                    .response
+                    .on_hover_text("Also used for the textures of glTF exports");
+                egui::ComboBox::from_label("Face culling")
+                    .selected_text(settings.face_culling.label())
+                    .show_ui(ui, |ui| {
+                        for culling in
+                            [FaceCulling::All, FaceCulling::RenderType, FaceCulling::None]
+                        {
+                            ui.selectable_value(
+                                &mut settings.face_culling,
+                                culling,
+                                culling.label(),
+                            );
+                        }
+                    })
+                    .response
+                    .on_hover_text("Which faces are hidden when seen from behind");
+                ui.checkbox(&mut settings.painter_order, "Painter's order")
+                    .on_hover_text(
+                        "Draw triangles by priority and then back to front like the client's \
+                         software renderer, instead of with a depth buffer. The Triangle Order \
+                         window picks which priorities count.",
+                    );
+                ui.separator();
+                Self::camera_settings_ui(ui, &mut settings.camera);
+                ui.separator();
+                settings.post_process.ui(ui);
+                ui.separator();
+                self.model_selector.orientation_settings_ui(ui);
+                #[cfg(not(target_arch = "wasm32"))]
+                ui.horizontal(|ui| {
+                    ui.label(format!("Export folder: {}", save::export_root().display()));
+                    if ui.button("Choose…").clicked() {
+                        save::choose_export_root();
+                    }
+                });
+                #[cfg(target_arch = "wasm32")]
+                {
+                    let mut offline = net::is_offline_mode();
+                    if ui
+                        .checkbox(&mut offline, "Offline mode")
+                        .on_hover_text(
+                            "Load cache data only from what earlier visits stored, anything \
+                             missing fails at once. What failed loads after reloading the page.",
+                        )
+                        .changed()
+                    {
+                        net::set_offline_mode(offline);
+                    }
+                }
+                if let Some(font_id) = self.label_font_id {
+                    ui.horizontal(|ui| {
+                        ui.label(format!("Viewport label font {font_id}"));
+                        if ui.button("Remove").clicked() {
+                            self.label_font_id = None;
+                            self.text_labels = None;
+                        }
+                    });
+                }
+                if ui.button("Reset").clicked() {
+                    *settings = RenderSettings::default();
+                }
+            });
+
+        let brightness = self.render_ctx.settings.effective_brightness();
+        if self.render_ctx.texture_brightness != brightness {
+            // Spread over the next frames rather than decoding every layer in this one.
+            self.render_ctx.texture_uploads.mark_all_dirty();
+            self.render_ctx.texture_brightness = brightness;
+            self.texture_browser.clear_thumbnails();
+        }
+        if self.render_ctx.texture_uploads.is_pending() {
+            self.render_ctx.texture_uploads.upload_pending(
+                &self.gl,
+                self.render_ctx.texture_array,
+                &self.texture_provider,
+                brightness,
+            );
+            ctx.request_repaint();
+        }
+        let filter = self.render_ctx.settings.texture_filter;
+        if self.render_ctx.texture_filter != filter {
+            Self::set_texture_filter(&self.gl, self.render_ctx.texture_array, filter);
+            self.render_ctx.texture_filter = filter;
+        }
+    }
+}
+
+impl eframe::App for ModelViewerApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let update_started = now();
+        self.render_ctx.gpu_timer.lock().begin_frame(&self.gl);
+
+        egui::CentralPanel::default()
+            .frame(egui::Frame::new().fill(egui::Color32::BLACK))
+            .show(ctx, |ui| {
+                self.custom_painting(ui);
+            });
+
+        self.show_settings(ctx);
+        self.handle_navigation_keys(ctx);
+        self.handle_screenshots(ctx);
+        self.update_text_labels();
+
+        self.names.show(ctx, self.selected_model_id);
+        if self.names.take_changed() {
+            self.model_selector.refresh_search();
+        }
+
+        self.model_selector.show(
+            ctx,
+            &self.render_ctx,
+            &self.model_js5,
+            &self.texture_provider,
+            self.names.tables().get(NameKind::Model),
+            self.selected_model_id,
+        );
+
+        self.texture_browser.show(
+            ctx,
+            &self.model_js5,
+            &self.texture_provider,
+            self.render_ctx.texture_brightness,
+        );
+
+        self.dataset_export.show(
+            ctx,
+            &self.model_js5,
+            &self.texture_provider,
+            self.render_ctx.settings.brightness,
+        );
+
+        self.model_info.show(
+            ctx,
+            &self.model_js5,
+            &mut self.definitions,
+            &self.texture_provider,
+            self.current_model.as_ref(),
+            self.render_ctx.settings.brightness,
+            self.render_ctx.settings.texture_filter,
+        );
+
+        self.definition_search
+            .show(ctx, &mut self.definitions, self.names.tables());
+        if let Some((kind, id)) = self.definition_search.picked.take() {
+            self.show_definition(kind, id);
+        }
+
+        self.model_format
+            .show(ctx, &self.model_js5, self.selected_model_id);
+
+        self.frame_browser
+            .show(ctx, &self.frame_js5, &self.framemap_js5);
+
+        let config_js5 = self
+            .inspector_archives
+            .iter()
+            .find(|archive| archive.archive_id == SeqType::ARCHIVE_ID)
+            .map(|archive| archive.js5.clone());
+        self.animation_player.show(
+            ctx,
+            &self.frame_js5,
+            &self.framemap_js5,
+            config_js5.as_deref(),
+        );
+        if self.animation_player.take_changed() {
+            self.pose_current_model();
+        }
+
+        self.triangle_order.show(ctx);
+        if self.triangle_order.take_changed() {
+            self.pose_current_model();
+        }
+
+        self.material_rules
+            .show(ctx, self.texture_provider.material_rules());
+        if let Some(rules) = self.material_rules.applied.take() {
+            self.texture_provider.set_material_rules(rules);
+            self.model_selector.reload_previews();
+            // Reload even if the model is already shown, to light it with the new rules.
+            self.current_model_id = u32::MAX;
+        }
+
+        self.hex_inspector
+            .show(ctx, &self.inspector_archives, &self.plugins);
+
+        let plugin_ctx = PluginContext::new(
+            &self.inspector_archives,
+            &self.texture_provider,
+            self.names.tables(),
+            self.render_ctx.settings.brightness,
+        );
+        self.plugins.show_windows(ctx, &plugin_ctx);
+        if let Some(id) = plugin_ctx.selected_model_id() {
+            self.selected_model_id = id;
+            self.pending_merge = None;
+        }
+        if let Some(recolours) = plugin_ctx.take_recolours() {
+            self.recolours = recolours;
+            // Reload even if the model is already shown, to apply the new colours.
+            self.current_model_id = u32::MAX;
+        }
+
+        self.sprite_browser
+            .show(ctx, &self.texture_provider.sprite_js5);
+
+        self.sprite_import.show(ctx);
+
+        self.profiler.show(ctx);
+
+        self.integrity.show(ctx, &self.model_js5);
+
+        self.script_console.show(ctx, &self.model_js5);
+        self.run_script_commands();
+
+        let pose = CameraPose {
+            yaw: self.camera.yaw,
+            pitch: self.camera.pitch,
+            zoom: self.camera.zoom,
+        };
+        let settings = self.render_ctx.settings;
+        self.camera_path
+            .annotation
+            .load_font(&self.texture_provider.sprite_js5, &self.fontmetrics_js5);
+        self.camera_path.step_render(
+            self.current_model_id,
+            self.current_model.as_ref(),
             &self.texture_provider,
+            &self.render_ctx.model_viewer.lock(),
+            &self.camera,
+            settings.camera,
+        );
+        if let Some(pose) =
+            self.camera_path
+                .show(ctx, pose, self.current_model_id, settings.brightness)
+        {
+            let camera = settings.camera;
+            self.camera.yaw = pose.yaw;
+            self.camera.pitch = pose.pitch;
+            self.camera.zoom = pose
+                .zoom
+                .clamp(camera.min_zoom, camera.max_zoom.max(camera.min_zoom));
+        }
+
+        self.font_preview.show(
+            ctx,
+            &self.texture_provider.sprite_js5,
+            &self.fontmetrics_js5,
         );
 
+        if let Some((texture_id, model_ids)) = self.texture_browser.found_models.take() {
+            self.model_selector
+                .set_filter(format!("Models using texture {texture_id}"), model_ids);
+        }
+
+        if let Some((model_id, model_ids)) = self.model_info.found_models.take() {
+            self.model_selector
+                .set_filter(format!("Models identical to {model_id}"), model_ids);
+        }
+
+        if let Some(model_ids) = self.integrity.found_models.take() {
+            self.model_selector
+                .set_filter("Corrupt models".to_owned(), model_ids);
+        }
+
+        match self.model_selector.batch_action.take() {
+            Some(BatchAction::Export(ids)) => self
+                .dataset_export
+                .export_ids(ids, self.render_ctx.settings.brightness),
+            Some(BatchAction::Merge(ids)) => self.pending_merge = Some(ids),
+            None => {}
+        }
+
         if let Some(id) = self.model_selector.selected_id.take() {
             self.selected_model_id = id;
+            self.pending_merge = None;
         }
 
         if self.current_model_id != self.selected_model_id {
-            if let Some(model_data) = self.model_js5.get_file(self.selected_model_id, 0) {
-                let mut model_unlit = ModelUnlit::new();
-                model_unlit.decode(&model_data);
-
-                if model_unlit.version < 13 {
-                    model_unlit.scale_log2(2);
-                }
+            self.load_selected_model();
+        }
 
-                let model = ModelLit::from_unlit(
-                    &self.texture_provider,
-                    &model_unlit,
-                    ModelFlags::empty(),
-                    64,
-                    768,
-                );
+        self.merge_pending();
 
-                self.render_ctx
-                    .model_viewer
-                    .lock()
-                    .upload_model(&self.gl, model);
-                self.current_model_id = self.selected_model_id;
-            }
+        // Fetches complete outside of egui, so poll while any are in flight. Animations and
+        // incremental work request their own repaints, otherwise egui repaints on input. Local
+        // caches are read as groups are fetched, so they never have requests in flight.
+        if net::in_flight_request_count() > 0 {
+            ctx.request_repaint_after(Self::FETCH_POLL_INTERVAL);
         }
 
-        ctx.request_repaint(); // always repaint
+        let update_ms = now() - update_started;
+        self.cpu_frame_ms += (update_ms - self.cpu_frame_ms) * GpuTimer::SMOOTHING;
+        if self.show_render_stats {
+            ctx.request_repaint();
+        }
     }
 }
 
+/// Parses a pasted list of two or more ids separated by commas, semicolons or whitespace.
+fn parse_id_list(text: &str) -> Option<Vec<u32>> {
+    let ids: Vec<u32> = text
+        .split(|c: char| c == ',' || c == ';' || c.is_whitespace())
+        .filter(|part| !part.is_empty())
+        .map(|part| part.parse().ok())
+        .collect::<Option<_>>()?;
+    (ids.len() >= 2).then_some(ids)
+}
+
 struct ModelSelectorWindow {
     gl: Arc<glow::Context>,
-    start_time: f64,
+    /// Yaw of previews that were never hovered, advanced only while the selector is shown so new
+    /// previews join the others in phase after it was hidden.
+    spin_yaw: f32,
+    /// False while the window or the whole app is collapsed or minimized.
+    visible: bool,
     search_text: String,
+    /// The search text the current results were computed for.
+    searched_text: String,
+    search_changed_at: Option<f64>,
+    /// Describes results set from outside the search box, e.g. by the texture browser.
+    filter_label: Option<String>,
     selected_id: Option<u32>,
     model_viewers: HashMap<usize, Arc<Mutex<ModelViewer>>>,
+    preview_cameras: HashMap<usize, PreviewCamera>,
     active_preview_ids: HashSet<usize>,
-    search_results: Vec<usize>,
+    search_results: Option<Vec<usize>>,
+    /// Tiles picked with ctrl/shift click for batch actions.
+    multi_selection: BTreeSet<u32>,
+    /// The last clicked id, shift click selects the range from it.
+    selection_anchor: Option<u32>,
+    favorites: BTreeSet<u32>,
+    batch_action: Option<BatchAction>,
+    /// Why the last merge left models out.
+    merge_warning: Option<String>,
+    /// Turns previews upright and to the front with [`Orientation::estimate`].
+    auto_orient: bool,
+    /// Orientations picked by hand, used instead of the estimate.
+    orientation_overrides: BTreeMap<u32, Orientation>,
+    /// The orientation each loaded preview was turned by.
+    preview_orientations: HashMap<usize, Orientation>,
+}
+
+/// Orbit camera of one selector preview. Previews spin on their own and ease towards a fixed
+/// "hero" orientation while hovered.
+struct PreviewCamera {
+    /// Degrees, unbounded so easing back to the hero yaw takes the short way round.
+    yaw: f32,
+    /// How far the preview has eased towards the hero orientation, from 0 to 1.
+    hover: f32,
+}
+
+impl PreviewCamera {
+    const SPIN_SPEED: f32 = 60.0;
+    const EASE_SPEED: f32 = 8.0;
+    const HOVER_SPEED: f32 = 5.0;
+    const HERO_PITCH: f32 = 20.0;
+    const HERO_ZOOM: f32 = 0.8;
+
+    fn update(&mut self, hovered: bool, dt: f32, hero_yaw: f32) {
+        let target_hover = if hovered { 1.0 } else { 0.0 };
+        let step = Self::HOVER_SPEED * dt;
+        self.hover += (target_hover - self.hover).clamp(-step, step);
+
+        if hovered {
+            let hero_yaw = hero_yaw + 360.0 * ((self.yaw - hero_yaw) / 360.0).round();
+            self.yaw += (hero_yaw - self.yaw) * (1.0 - (-Self::EASE_SPEED * dt).exp());
+        } else {
+            self.yaw += Self::SPIN_SPEED * (1.0 - self.hover) * dt;
+        }
+        // Keep the angle small without changing which way the preview faces.
+        self.yaw %= 360.0;
+    }
+
+    /// The camera to draw the preview with.
+    fn view(&self, base_pitch: f32) -> OrbitCamera {
+        let t = self.hover * self.hover * (3.0 - 2.0 * self.hover);
+        let pitch = base_pitch + (Self::HERO_PITCH - base_pitch) * t;
+        let zoom = 1.0 + (Self::HERO_ZOOM - 1.0) * t;
+        OrbitCamera::new(self.yaw, pitch, zoom)
+    }
+}
+
+/// An action on the multi-selected models, handled by the app.
+enum BatchAction {
+    Export(Vec<u32>),
+    /// Merges the models into one and shows it in the main viewport.
+    Merge(Vec<u32>),
 }
 
 impl ModelSelectorWindow {
@@ -423,16 +2000,283 @@ impl ModelSelectorWindow {
     const CONTAINER_WIDTH_WITH_SPACING: f32 = Self::CONTAINER_WIDTH + 6.0;
     const CANVAS_SIZE: f32 = 128.0;
 
+    const SEARCH_DEBOUNCE_MS: f64 = 150.0;
+
     fn new(gl: Arc<glow::Context>) -> Self {
         Self {
             gl,
-            start_time: now(),
+            spin_yaw: 0.0,
+            visible: false,
             search_text: "".to_owned(),
+            searched_text: "".to_owned(),
+            search_changed_at: None,
+            filter_label: None,
             selected_id: None,
             model_viewers: HashMap::new(),
+            preview_cameras: HashMap::new(),
             active_preview_ids: HashSet::new(),
-            search_results: vec![],
+            search_results: None,
+            multi_selection: BTreeSet::new(),
+            selection_anchor: None,
+            favorites: BTreeSet::new(),
+            batch_action: None,
+            merge_warning: None,
+            auto_orient: true,
+            orientation_overrides: BTreeMap::new(),
+            preview_orientations: HashMap::new(),
+        }
+    }
+
+    /// Drops a loaded preview, so it's loaded again the next time it's shown.
+    fn reload_preview(&mut self, id: usize) {
+        if let Some(model_viewer) = self.model_viewers.remove(&id) {
+            model_viewer.lock().destroy(&self.gl);
+        }
+        self.preview_orientations.remove(&id);
+    }
+
+    /// Drops every loaded preview, e.g. after a setting they were lit with changed.
+    fn reload_previews(&mut self) {
+        let ids: Vec<usize> = self.model_viewers.keys().copied().collect();
+        for id in ids {
+            self.reload_preview(id);
+        }
+    }
+
+    fn set_orientation_override(&mut self, id: u32, orientation: Option<Orientation>) {
+        match orientation {
+            Some(orientation) => self.orientation_overrides.insert(id, orientation),
+            None => self.orientation_overrides.remove(&id),
+        };
+        self.reload_preview(id as usize);
+    }
+
+    fn orientation_settings_ui(&mut self, ui: &mut egui::Ui) {
+        if ui
+            .checkbox(&mut self.auto_orient, "Auto-orient previews")
+            .on_hover_text("Stands up models lying flat and turns their front to the camera")
+            .changed()
+        {
+            self.reload_previews();
+        }
+        let mut removed = None;
+        for (&id, orientation) in &self.orientation_overrides {
+            ui.horizontal(|ui| {
+                if ui.small_button("✖").on_hover_text("Remove").clicked() {
+                    removed = Some(id);
+                }
+                ui.label(format!("Model {id}: {}", orientation.label()));
+            });
+        }
+        if let Some(id) = removed {
+            self.set_orientation_override(id, None);
+        }
+    }
+
+    fn set_filter(&mut self, label: String, ids: Vec<usize>) {
+        self.search_text.clear();
+        self.searched_text.clear();
+        self.search_changed_at = None;
+        self.filter_label = Some(label);
+        self.search_results = Some(ids);
+    }
+
+    /// Runs the search again, e.g. after model names changed.
+    fn refresh_search(&mut self) {
+        if self.filter_label.is_none() && !self.search_text.trim().is_empty() {
+            self.searched_text.clear();
+            self.search_changed_at = Some(0.0);
+        }
+    }
+
+    fn update_search(&mut self, ctx: &egui::Context, model_js5: &Js5, names: &NameTable) {
+        let Some(changed_at) = self.search_changed_at else {
+            return;
+        };
+        let elapsed = now() - changed_at;
+        if elapsed < Self::SEARCH_DEBOUNCE_MS {
+            ctx.request_repaint_after(Duration::from_secs_f64(
+                (Self::SEARCH_DEBOUNCE_MS - elapsed) / 1000.0,
+            ));
+            return;
+        }
+        self.search_changed_at = None;
+
+        let search_text = self.search_text.trim().to_lowercase();
+        if search_text.is_empty() {
+            self.search_results = None;
+        } else if let Some(ids) = parse_id_list(&search_text) {
+            let mut seen = HashSet::new();
+            let results = ids
+                .into_iter()
+                .filter(|&id| model_js5.is_group_valid(id) && seen.insert(id))
+                .map(|id| id as usize)
+                .collect();
+            self.search_results = Some(results);
+            // Lists aren't substrings of each other, so don't narrow them later.
+            self.searched_text.clear();
+            return;
+        } else if !self.searched_text.is_empty()
+            && search_text.contains(&self.searched_text)
+            && model_js5.index.group_name_hashes.is_none()
+        {
+            // Narrowing the search can only remove matches, so filter the previous results. Not
+            // with name hashes, where a longer search can hash to a group the shorter one missed.
+            if let Some(results) = self.search_results.as_mut() {
+                results.retain(|&id| {
+                    let hash = model_js5.index.get_group_name_hash(id as u32);
+                    names.matches_group(id as u32, hash, &search_text)
+                });
+            }
+        } else {
+            let results = model_js5
+                .index
+                .group_ids
+                .iter()
+                .filter(|&&id| {
+                    let hash = model_js5.index.get_group_name_hash(id);
+                    names.matches_group(id, hash, &search_text)
+                })
+                .map(|&id| id as usize)
+                .collect();
+            self.search_results = Some(results);
+        }
+        self.searched_text = search_text;
+    }
+
+    /// The id after (or before) `id` in the search results, or in all groups without a search.
+    /// Ids missing from the list step to the nearest listed id in that direction.
+    fn adjacent_id(&self, model_js5: &Js5, id: u32, forward: bool) -> Option<u32> {
+        let count = self.result_count(model_js5);
+        let id_at = |index: usize| self.get_id(model_js5, index) as u32;
+        let index = match (0..count).position(|index| id_at(index) == id) {
+            Some(index) if forward => index + 1,
+            Some(index) => index.checked_sub(1)?,
+            None if forward => (0..count).position(|index| id_at(index) > id)?,
+            None => (0..count).rposition(|index| id_at(index) < id)?,
+        };
+        (index < count).then(|| id_at(index))
+    }
+
+    fn result_count(&self, model_js5: &Js5) -> usize {
+        match &self.search_results {
+            Some(results) => results.len(),
+            None => model_js5.index.group_ids.len(),
+        }
+    }
+
+    /// Updates the selection for a click on the tile at `index`, with ctrl toggling a tile and
+    /// shift selecting the range from the last clicked tile.
+    fn click_item(&mut self, model_js5: &Js5, index: usize, modifiers: egui::Modifiers) {
+        let id = self.get_id(model_js5, index) as u32;
+        if modifiers.shift {
+            let anchor_index = self.selection_anchor.and_then(|anchor| {
+                (0..self.result_count(model_js5))
+                    .position(|index| self.get_id(model_js5, index) as u32 == anchor)
+            });
+            let anchor_index = anchor_index.unwrap_or(index);
+            for index in anchor_index.min(index)..=anchor_index.max(index) {
+                self.multi_selection
+                    .insert(self.get_id(model_js5, index) as u32);
+            }
+        } else if modifiers.command {
+            if !self.multi_selection.remove(&id) {
+                self.multi_selection.insert(id);
+            }
+            self.selection_anchor = Some(id);
+        } else {
+            self.multi_selection.clear();
+            self.selection_anchor = Some(id);
+            self.selected_id = Some(id);
+        }
+    }
+
+    fn selection_ui(&mut self, ui: &mut egui::Ui, model_js5: &Js5) {
+        ui.horizontal(|ui| {
+            if self.search_results.is_some() && ui.button("Select all").clicked() {
+                for index in 0..self.result_count(model_js5) {
+                    self.multi_selection
+                        .insert(self.get_id(model_js5, index) as u32);
+                }
+            }
+            if !self.favorites.is_empty()
+                && ui
+                    .button(format!("Favorites ({})", self.favorites.len()))
+                    .clicked()
+            {
+                let ids = self.favorites.iter().map(|&id| id as usize).collect();
+                self.set_filter("Favorites".to_owned(), ids);
+            }
+            if self.multi_selection.is_empty() {
+                return;
+            }
+            ui.label(format!("{} selected", self.multi_selection.len()));
+            let ids: Vec<u32> = self.multi_selection.iter().copied().collect();
+            if ui.button("Export").clicked() {
+                self.batch_action = Some(BatchAction::Export(ids.clone()));
+            }
+            if ui.button("Merge").clicked() {
+                self.batch_action = Some(BatchAction::Merge(ids.clone()));
+            }
+            if ui.button("Copy ids").clicked() {
+                let ids: Vec<String> = ids.iter().map(u32::to_string).collect();
+                ui.ctx().copy_text(ids.join(", "));
+            }
+            if ui.button("Add to favorites").clicked() {
+                self.favorites.extend(ids);
+            }
+            if ui.button("Clear").clicked() {
+                self.multi_selection.clear();
+            }
+        });
+        if let Some(warning) = &self.merge_warning {
+            ui.colored_label(ui.visuals().warn_fg_color, warning);
+        }
+    }
+
+    fn get_id(&self, model_js5: &Js5, index: usize) -> usize {
+        match &self.search_results {
+            Some(results) => results[index],
+            None => model_js5.index.group_ids[index] as usize,
+        }
+    }
+
+    fn id_label(
+        &self,
+        id: usize,
+        name: Option<&str>,
+        text_color: egui::Color32,
+    ) -> egui::text::LayoutJob {
+        let text = match name {
+            Some(name) => format!("{id} {name}"),
+            None => id.to_string(),
+        };
+        let format = egui::TextFormat {
+            color: text_color,
+            ..Default::default()
+        };
+        let mut job = egui::text::LayoutJob::default();
+        // Searches are lowercase, ASCII lowercasing keeps the byte offsets of the text.
+        let matched = (!self.searched_text.is_empty())
+            .then(|| text.to_ascii_lowercase().find(&self.searched_text))
+            .flatten();
+        if let Some(start) = matched {
+            let end = start + self.searched_text.len();
+            job.append(&text[..start], 0.0, format.clone());
+            job.append(
+                &text[start..end],
+                0.0,
+                egui::TextFormat {
+                    color: egui::Color32::BLACK,
+                    background: egui::Color32::YELLOW,
+                    ..Default::default()
+                },
+            );
+            job.append(&text[end..], 0.0, format);
+        } else {
+            job.append(&text, 0.0, format);
         }
+        job
     }
 
     fn get_or_load_model(
@@ -451,18 +2295,31 @@ impl ModelSelectorWindow {
             model_unlit.scale_log2(2);
         }
 
-        let mut model =
-            ModelLit::from_unlit(texture_provider, &model_unlit, ModelFlags::empty(), 64, 768);
+        let orientation = match self.orientation_overrides.get(&(id as u32)) {
+            Some(&orientation) => orientation,
+            None if self.auto_orient => Orientation::estimate(&model_unlit),
+            None => Orientation::default(),
+        };
+        orientation.apply(&mut model_unlit);
+        self.preview_orientations.insert(id, orientation);
+        let (center_x, center_y, center_z) = model_unlit.get_center();
+
+        // Previews are too small to show smooth shading or texture detail, the main viewport
+        // lights the model in full once it's opened.
+        let mut model = ModelLit::from_unlit_with_detail(
+            texture_provider,
+            &model_unlit,
+            ModelFlags::empty(),
+            64,
+            768,
+            LightDetail::Preview,
+        );
 
         model = model.copy(ModelFlags::CHANGED_X | ModelFlags::CHANGED_Y | ModelFlags::CHANGED_Z);
-
-        let (center_x, center_y, center_z) = model.get_center();
         model.translate(-center_x, -center_y, -center_z);
 
-        let radius = model.get_xyz_radius() as f32 / 512.0 * 2.0;
-
-        let model_viewer = Arc::new(Mutex::new(ModelViewer::new(radius)));
-        model_viewer.lock().upload_model(&self.gl, model);
+        let model_viewer = Arc::new(Mutex::new(ModelViewer::new()));
+        model_viewer.lock().upload_model(&self.gl, &model);
 
         self.model_viewers.insert(id, model_viewer.clone());
 
@@ -475,14 +2332,32 @@ impl ModelSelectorWindow {
         render_ctx: &ModelRenderContext,
         model_js5: &Js5,
         texture_provider: &TextureProvider,
+        names: &NameTable,
+        current_id: u32,
     ) {
+        let minimized = ctx.input(|input| input.viewport().minimized.unwrap_or(false));
+        // Nothing below runs while the window is collapsed, which pauses the preview animation
+        // and model uploads until it's expanded again; the previews keep their GL buffers.
+        self.visible = false;
         egui::Window::new("Model Selector")
             .resizable(true)
             .scroll(false)
             .show(ctx, |ui| {
+                self.visible = !minimized;
+                if self.visible {
+                    let dt = ui.input(|input| input.stable_dt).min(0.1);
+                    self.spin_yaw = (self.spin_yaw + dt * PreviewCamera::SPIN_SPEED) % 360.0;
+                }
                 self.active_preview_ids.clear();
 
-                self.ui(ui, render_ctx, model_js5, texture_provider);
+                self.ui(
+                    ui,
+                    render_ctx,
+                    model_js5,
+                    texture_provider,
+                    names,
+                    current_id,
+                );
 
                 let mut to_remove = vec![];
                 for id in self.model_viewers.keys() {
@@ -491,6 +2366,10 @@ impl ModelSelectorWindow {
                     }
                 }
 
+                self.preview_cameras
+                    .retain(|id, _| self.active_preview_ids.contains(id));
+                self.preview_orientations
+                    .retain(|id, _| self.active_preview_ids.contains(id));
                 for id in to_remove {
                     let Some(model_viewer) = self.model_viewers.remove(&id) else {
                         continue;
@@ -506,31 +2385,65 @@ impl ModelSelectorWindow {
         render_ctx: &ModelRenderContext,
         model_js5: &Js5,
         texture_provider: &TextureProvider,
+        names: &NameTable,
+        current_id: u32,
     ) {
         let search_response = ui.add(egui::TextEdit::singleline(&mut self.search_text).hint_text(
             format!(
-                "Search models by id (0-{})...",
+                "Search models by id (0-{}) or name, or paste a list of ids...",
                 model_js5.get_last_group_id()
             ),
         ));
         if search_response.changed() {
-            self.search_results.clear();
-            if !self.search_text.is_empty() {
-                for index in 0..model_js5.get_group_count() as usize {
-                    let id = model_js5.index.group_ids[index];
-                    if id.to_string().contains(&self.search_text) {
-                        self.search_results.push(id as usize);
-                    }
-                }
+            self.search_changed_at = Some(now());
+            self.filter_label = None;
+        }
+        self.update_search(ui.ctx(), model_js5, names);
+
+        if let Some(label) = &self.filter_label {
+            let mut clear = false;
+            ui.horizontal(|ui| {
+                ui.label(label);
+                clear = ui.button("Clear").clicked();
+            });
+            if clear {
+                self.filter_label = None;
+                self.search_results = None;
             }
-            println!("Search text: {}", self.search_text);
         }
 
-        let count = if self.search_results.is_empty() {
-            model_js5.get_group_count() as usize
-        } else {
-            self.search_results.len()
-        };
+        let group_count = model_js5.get_group_count() as usize;
+        let count = ui
+            .horizontal(|ui| {
+                let previous = self.adjacent_id(model_js5, current_id, false);
+                if ui
+                    .add_enabled(previous.is_some(), egui::Button::new("\u{25C0}"))
+                    .on_hover_text("Previous model (left arrow)")
+                    .clicked()
+                {
+                    self.selected_id = previous;
+                }
+                let next = self.adjacent_id(model_js5, current_id, true);
+                if ui
+                    .add_enabled(next.is_some(), egui::Button::new("\u{25B6}"))
+                    .on_hover_text("Next model (right arrow)")
+                    .clicked()
+                {
+                    self.selected_id = next;
+                }
+                match &self.search_results {
+                    Some(results) => {
+                        ui.label(format!("{} of {} models", results.len(), group_count));
+                        results.len()
+                    }
+                    None => {
+                        ui.label(format!("{} models", group_count));
+                        group_count
+                    }
+                }
+            })
+            .inner;
+        self.selection_ui(ui, model_js5);
 
         ui.ctx().style_mut(|style| {
             style.interaction.selectable_labels = false;
@@ -559,6 +2472,7 @@ impl ModelSelectorWindow {
                     render_ctx,
                     model_js5,
                     texture_provider,
+                    names,
                     row_range,
                     count,
                     total_rows,
@@ -574,6 +2488,7 @@ impl ModelSelectorWindow {
         render_ctx: &ModelRenderContext,
         model_js5: &Js5,
         texture_provider: &TextureProvider,
+        names: &NameTable,
         row_range: std::ops::Range<usize>,
         total_items: usize,
         total_rows: usize,
@@ -586,12 +2501,7 @@ impl ModelSelectorWindow {
                 let item_start = row * items_per_row;
                 let item_end = (item_start + items_per_row).min(total_items);
                 for index in item_start..item_end {
-                    let id = if self.search_results.is_empty() {
-                        model_js5.index.group_ids[index] as usize
-                    } else {
-                        self.search_results[index]
-                    };
-                    self.add_item(ui, render_ctx, model_js5, texture_provider, id);
+                    self.add_item(ui, render_ctx, model_js5, texture_provider, names, index);
                 }
             });
 
@@ -608,9 +2518,12 @@ impl ModelSelectorWindow {
         render_ctx: &ModelRenderContext,
         model_js5: &Js5,
         texture_provider: &TextureProvider,
-        id: usize,
+        names: &NameTable,
+        index: usize,
     ) {
+        let id = self.get_id(model_js5, index);
         self.active_preview_ids.insert(id);
+        let multi_selected = self.multi_selection.contains(&(id as u32));
         let response = ui
             .scope_builder(
                 egui::UiBuilder::new()
@@ -622,8 +2535,12 @@ impl ModelSelectorWindow {
                     let visuals = ui.style().interact(&response);
                     let text_color = visuals.text_color();
 
+                    let hovered = response.hovered();
                     let mut stroke = ui.style().visuals.window_stroke();
-                    if response.hovered() {
+                    if multi_selected {
+                        stroke = ui.style().visuals.selection.stroke;
+                        stroke.width = stroke.width.max(2.0);
+                    } else if hovered {
                         stroke.color = egui::Color32::WHITE;
                     }
 
@@ -631,14 +2548,21 @@ impl ModelSelectorWindow {
                         egui::Frame::dark_canvas(ui.style())
                             .stroke(stroke)
                             .show(ui, |ui| {
-                                if let Some(model_viewer) =
+                                if model_js5.is_group_corrupt(id as u32) {
+                                    ui.set_width(128.0);
+                                    ui.set_height(128.0);
+                                    ui.centered_and_justified(|ui| {
+                                        ui.colored_label(ui.visuals().error_fg_color, "⚠ Corrupt")
+                                            .on_hover_text("The group failed its CRC check");
+                                    });
+                                } else if let Some(model_viewer) =
                                     self.get_or_load_model(model_js5, texture_provider, id)
                                 {
                                     let (rect, _response) = ui.allocate_exact_size(
                                         egui::Vec2::new(Self::CANVAS_SIZE, Self::CANVAS_SIZE),
                                         egui::Sense::empty(),
                                     );
-                                    self.add_model(ui, render_ctx, rect, model_viewer);
+                                    self.add_model(ui, render_ctx, rect, id, hovered, model_viewer);
                                 } else {
                                     ui.set_width(128.0);
                                     ui.set_height(128.0);
@@ -647,77 +2571,328 @@ impl ModelSelectorWindow {
                                     });
                                 }
                             });
-                        ui.colored_label(text_color, id.to_string());
-                        // ui.label("Long text that should wrap hopefully maybe");
+                        let hash = model_js5.index.get_group_name_hash(id as u32);
+                        let name = names.get_group(id as u32, hash);
+                        ui.add(egui::Label::new(self.id_label(id, name, text_color)).truncate());
                     });
                 },
             )
             .response;
 
         if response.clicked() {
-            self.selected_id = Some(id as u32);
+            let modifiers = ui.input(|input| input.modifiers);
+            self.click_item(model_js5, index, modifiers);
         }
-    }
+        response.context_menu(|ui| {
+            let orientation = self
+                .preview_orientations
+                .get(&id)
+                .copied()
+                .unwrap_or_default();
+            ui.label(format!("Orientation: {}", orientation.label()));
+            if ui.button("Turn 90° about y").clicked() {
+                self.set_orientation_override(id as u32, Some(orientation.turned(0, 1)));
+            }
+            if ui.button("Tip 90° about x").clicked() {
+                self.set_orientation_override(id as u32, Some(orientation.turned(1, 0)));
+            }
+            let has_override = self.orientation_overrides.contains_key(&(id as u32));
+            if ui
+                .add_enabled(has_override, egui::Button::new("Reset orientation"))
+                .clicked()
+            {
+                self.set_orientation_override(id as u32, None);
+                ui.close_menu();
+            }
+        });
+    }
 
     fn add_model(
         &mut self,
         ui: &mut egui::Ui,
         render_ctx: &ModelRenderContext,
         rect: egui::Rect,
+        id: usize,
+        hovered: bool,
         model_viewer: Arc<Mutex<ModelViewer>>,
     ) {
-        let yaw = ((now() - self.start_time) / 1000.0 * 60.0).to_radians() as f32;
-
-        // let yaw = Self::YAW.to_radians();
-        let pitch = Self::PITCH.to_radians();
-        let zoom = 1.0;
+        let camera = self.preview_cameras.entry(id).or_insert(PreviewCamera {
+            yaw: self.spin_yaw,
+            hover: 0.0,
+        });
+        // Previews scrolled out of view or covered by another window keep their last frame.
+        if self.visible && ui.is_rect_visible(rect) && !is_occluded(ui, rect) {
+            let dt = ui.input(|input| input.stable_dt).min(0.1);
+            camera.update(hovered, dt, Self::YAW);
+            ui.ctx().request_repaint();
+        }
+        let camera = camera.view(Self::PITCH);
         let program = render_ctx.program;
         let texture_array = render_ctx.texture_array;
+        let settings = render_ctx.settings;
+        let gpu_timer = render_ctx.gpu_timer.clone();
 
         let callback = egui::PaintCallback {
             rect,
             callback: std::sync::Arc::new(egui_glow::CallbackFn::new(move |_info, painter| {
+                gpu_timer.lock().begin(painter.gl(), GpuScope::Previews);
                 model_viewer.lock().paint(
                     painter.gl(),
                     rect.width(),
                     rect.height(),
-                    yaw,
-                    pitch,
-                    zoom,
+                    &camera,
                     program,
                     texture_array,
+                    settings,
                 );
+                gpu_timer.lock().end(painter.gl());
             })),
         };
         ui.painter().add(callback);
     }
 }
 
+/// Reads the viewport a paint callback drew into back from the framebuffer, top row first. Alpha is
+/// only kept for a transparent background, egui draws an opaque one otherwise.
+fn read_viewport(
+    gl: &glow::Context,
+    info: &egui::PaintCallbackInfo,
+    transparent: bool,
+) -> egui::ColorImage {
+    use glow::HasContext as _;
+
+    let viewport = info.viewport_in_pixels();
+    let width = viewport.width_px.max(1) as usize;
+    let height = viewport.height_px.max(1) as usize;
+    let mut rgba = vec![0u8; width * height * 4];
+    unsafe {
+        gl.read_pixels(
+            viewport.left_px,
+            viewport.from_bottom_px,
+            width as i32,
+            height as i32,
+            glow::RGBA,
+            glow::UNSIGNED_BYTE,
+            glow::PixelPackData::Slice(Some(&mut rgba)),
+        );
+    }
+    let pixels = rgba
+        .chunks_exact(width * 4)
+        .rev()
+        .flat_map(|row| row.chunks_exact(4))
+        .map(|p| {
+            if transparent {
+                egui::Color32::from_rgba_unmultiplied(p[0], p[1], p[2], p[3])
+            } else {
+                egui::Color32::from_rgb(p[0], p[1], p[2])
+            }
+        })
+        .collect();
+    egui::ColorImage {
+        size: [width, height],
+        pixels,
+    }
+}
+
+/// Whether other windows cover the centre and all corners of `rect`, going by the window layout
+/// of the previous frame.
+fn is_occluded(ui: &egui::Ui, rect: egui::Rect) -> bool {
+    let layer_id = ui.layer_id();
+    let points = [
+        rect.center(),
+        rect.left_top(),
+        rect.right_top(),
+        rect.left_bottom(),
+        rect.right_bottom(),
+    ];
+    points.into_iter().all(|point| {
+        ui.ctx()
+            .layer_id_at(point)
+            .is_some_and(|layer| layer != layer_id)
+    })
+}
+
+/// GL buffers for a model, sized for `capacity` triangles so they can be refilled with
+/// `buffer_sub_data` when switching to a model that fits.
 struct UploadedModel {
     triangle_count: i32,
+    /// Opaque triangles before this are one-sided, the rest are double-sided.
+    single_sided_count: i32,
+    /// Triangles before this are opaque, the rest are transparent and drawn through the index
+    /// buffer, sorted back to front each frame.
+    opaque_count: i32,
+    /// Every triangle in upload order, for sorting.
+    triangles: Vec<SortedTriangle>,
+    capacity: usize,
     vertex_array: glow::VertexArray,
+    index_buffer: glow::Buffer,
     position_buffer: glow::Buffer,
     colour_buffer: glow::Buffer,
+    alpha_buffer: glow::Buffer,
     texcoord_buffer: glow::Buffer,
     texture_id_buffer: glow::Buffer,
+    normal_buffer: glow::Buffer,
+    /// The model's lighting, for the shader's uniforms.
+    ambient: i32,
+    contrast: i32,
+}
+
+/// A triangle of an uploaded model, for sorting by priority and depth.
+struct SortedTriangle {
+    centre: glm::Vec3,
+    double_sided: bool,
+    priority: u8,
 }
 
 impl UploadedModel {
-    fn new(
-        triangle_count: i32,
-        vertex_array: glow::VertexArray,
-        position_buffer: glow::Buffer,
-        colour_buffer: glow::Buffer,
-        texcoord_buffer: glow::Buffer,
-        texture_id_buffer: glow::Buffer,
-    ) -> Self {
-        Self {
-            triangle_count,
-            vertex_array,
-            position_buffer,
-            colour_buffer,
-            texcoord_buffer,
-            texture_id_buffer,
+    const MIN_CAPACITY: usize = 256;
+
+    fn create(gl: &glow::Context, capacity: usize) -> Self {
+        use glow::HasContext as _;
+
+        let vertex_count = capacity * 3;
+        unsafe {
+            let vertex_array = gl
+                .create_vertex_array()
+                .expect("vertex array should be created");
+            let position_buffer = gl
+                .create_buffer()
+                .expect("position buffer should be created");
+            let colour_buffer = gl.create_buffer().expect("colour buffer should be created");
+            let alpha_buffer = gl.create_buffer().expect("alpha buffer should be created");
+            let texcoord_buffer = gl
+                .create_buffer()
+                .expect("texcoord buffer should be created");
+            let texture_id_buffer = gl
+                .create_buffer()
+                .expect("texture id buffer should be created");
+            let normal_buffer = gl.create_buffer().expect("normal buffer should be created");
+            let index_buffer = gl.create_buffer().expect("index buffer should be created");
+
+            gl.bind_vertex_array(Some(vertex_array));
+
+            // Part of the vertex array's state, so it stays bound with it.
+            gl.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(index_buffer));
+            gl.buffer_data_size(
+                glow::ELEMENT_ARRAY_BUFFER,
+                (vertex_count * std::mem::size_of::<u32>()) as i32,
+                glow::DYNAMIC_DRAW,
+            );
+
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(position_buffer));
+            gl.buffer_data_size(
+                glow::ARRAY_BUFFER,
+                (vertex_count * std::mem::size_of::<f32>() * 3) as i32,
+                glow::DYNAMIC_DRAW,
+            );
+            gl.vertex_attrib_pointer_f32(
+                0,
+                3,
+                glow::FLOAT,
+                false,
+                std::mem::size_of::<f32>() as i32 * 3,
+                0,
+            );
+            gl.enable_vertex_attrib_array(0);
+
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(colour_buffer));
+            gl.buffer_data_size(
+                glow::ARRAY_BUFFER,
+                (vertex_count * std::mem::size_of::<u16>()) as i32,
+                glow::DYNAMIC_DRAW,
+            );
+            gl.vertex_attrib_pointer_i32(
+                1,
+                1,
+                glow::UNSIGNED_SHORT,
+                std::mem::size_of::<u16>() as i32,
+                0,
+            );
+            gl.enable_vertex_attrib_array(1);
+
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(alpha_buffer));
+            gl.buffer_data_size(
+                glow::ARRAY_BUFFER,
+                (vertex_count * std::mem::size_of::<u8>()) as i32,
+                glow::DYNAMIC_DRAW,
+            );
+            gl.vertex_attrib_pointer_f32(
+                2,
+                1,
+                glow::UNSIGNED_BYTE,
+                true,
+                std::mem::size_of::<u8>() as i32,
+                0,
+            );
+            gl.enable_vertex_attrib_array(2);
+
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(texcoord_buffer));
+            gl.buffer_data_size(
+                glow::ARRAY_BUFFER,
+                (vertex_count * std::mem::size_of::<f32>() * 2) as i32,
+                glow::DYNAMIC_DRAW,
+            );
+            gl.vertex_attrib_pointer_f32(
+                3,
+                2,
+                glow::FLOAT,
+                false,
+                std::mem::size_of::<f32>() as i32 * 2,
+                0,
+            );
+            gl.enable_vertex_attrib_array(3);
+
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(texture_id_buffer));
+            gl.buffer_data_size(
+                glow::ARRAY_BUFFER,
+                (vertex_count * std::mem::size_of::<u16>()) as i32,
+                glow::DYNAMIC_DRAW,
+            );
+            gl.vertex_attrib_pointer_i32(
+                4,
+                1,
+                glow::UNSIGNED_SHORT,
+                std::mem::size_of::<u16>() as i32,
+                0,
+            );
+            gl.enable_vertex_attrib_array(4);
+
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(normal_buffer));
+            gl.buffer_data_size(
+                glow::ARRAY_BUFFER,
+                (vertex_count * std::mem::size_of::<i16>() * 4) as i32,
+                glow::DYNAMIC_DRAW,
+            );
+            gl.vertex_attrib_pointer_f32(
+                5,
+                4,
+                glow::SHORT,
+                false,
+                std::mem::size_of::<i16>() as i32 * 4,
+                0,
+            );
+            gl.enable_vertex_attrib_array(5);
+
+            gl.bind_vertex_array(None);
+            gl.bind_buffer(glow::ARRAY_BUFFER, None);
+
+            Self {
+                triangle_count: 0,
+                single_sided_count: 0,
+                opaque_count: 0,
+                triangles: Vec::new(),
+                capacity,
+                vertex_array,
+                index_buffer,
+                position_buffer,
+                colour_buffer,
+                alpha_buffer,
+                texcoord_buffer,
+                texture_id_buffer,
+                normal_buffer,
+                ambient: 0,
+                contrast: 0,
+            }
         }
     }
 
@@ -727,39 +2902,54 @@ impl UploadedModel {
             gl.delete_vertex_array(self.vertex_array);
             gl.delete_buffer(self.position_buffer);
             gl.delete_buffer(self.colour_buffer);
+            gl.delete_buffer(self.alpha_buffer);
             gl.delete_buffer(self.texcoord_buffer);
             gl.delete_buffer(self.texture_id_buffer);
+            gl.delete_buffer(self.normal_buffer);
+            gl.delete_buffer(self.index_buffer);
         }
     }
 }
 
 struct ModelViewer {
+    /// Camera distance at zoom 1, fitted to the uploaded model.
     radius: f32,
+    /// Radius of the uploaded model in view units, used to fit the clip planes around it.
+    model_radius: f32,
     uploaded_model: Option<UploadedModel>,
 }
 
 impl ModelViewer {
-    fn new(radius: f32) -> Self {
+    const LIGHT_DIRECTION: (i32, i32, i32) = ModelLit::DEFAULT_LIGHT_DIRECTION;
+    /// The normal's w of flat shaded triangles, see the vertex shader.
+    const FLAT_SHADED: i16 = i16::MAX;
+
+    const DEFAULT_RADIUS: f32 = 6.0;
+    /// Keeps the camera outside tiny or empty models.
+    const MIN_RADIUS: f32 = 0.1;
+
+    fn new() -> Self {
         Self {
-            radius,
+            radius: Self::DEFAULT_RADIUS,
+            model_radius: Self::DEFAULT_RADIUS,
             uploaded_model: None,
         }
     }
 
-    fn upload_model(&mut self, gl: &glow::Context, model: ModelLit) {
+    /// Converts a radius in model units to view units. Done in f64 so the radii of huge models
+    /// don't lose precision before the division.
+    fn view_radius(xyz_radius: i32) -> f32 {
+        (xyz_radius as f64 / 512.0 * 2.0) as f32
+    }
+
+    fn upload_model(&mut self, gl: &glow::Context, model: &ModelLit) {
         use glow::HasContext as _;
 
-        if let Some(uploaded_model) = self.uploaded_model.take() {
-            uploaded_model.destroy(gl);
-        }
+        let _span = tracing::info_span!("model_upload").entered();
 
-        let vertex_array = unsafe {
-            gl.create_vertex_array()
-                .expect("vertex array should be created")
-        };
-        let (triangle_colours_a, triangle_colours_b, triangle_colours_c) =
-            model.calc_lit_colours(-50, -10, -50);
-        // let (triangle_colours_a, triangle_colours_b, triangle_colours_c) = model.calc_lit_colours(-30, -50, -30);
+        let bounds = model.bounds.unwrap_or_else(|| model.calculate_bounds());
+        self.model_radius = Self::view_radius(bounds.xyz_radius);
+        self.radius = self.model_radius.max(Self::MIN_RADIUS);
 
         let mut vertex_x = vec![0; model.render_vertex_count as usize];
         let mut vertex_y = vec![0; model.render_vertex_count as usize];
@@ -781,31 +2971,63 @@ impl ModelViewer {
 
         let mut triangle_count = 0;
 
+        // Opaque triangles come first, one-sided then double-sided so both batches can be drawn
+        // with their own culling. Transparent triangles follow in the order the model has them.
+        let is_transparent = |t: usize| {
+            model.triangle_transparent.get(t).copied().unwrap_or(false)
+                || model.triangle_transparency[t] != 0
+        };
+        let (transparent, opaque): (Vec<usize>, Vec<usize>) =
+            (0..model.render_triangle_count as usize).partition(|&t| is_transparent(t));
+        let (single_sided, double_sided): (Vec<usize>, Vec<usize>) =
+            opaque.into_iter().partition(|&t| !model.is_double_sided(t));
+        let single_sided_count = single_sided.len();
+        let opaque_count = single_sided_count + double_sided.len();
+
         let mut positions: Vec<f32> = Vec::with_capacity(model.triangle_count as usize * 3 * 3);
         let mut colours: Vec<u16> = Vec::with_capacity(model.triangle_count as usize * 3);
         let mut alphas: Vec<u8> = Vec::with_capacity(model.triangle_count as usize * 3);
         let mut texcoords: Vec<f32> = Vec::with_capacity(model.triangle_count as usize * 3 * 2);
         let mut texture_ids: Vec<u16> = Vec::with_capacity(model.triangle_count as usize * 3);
-        for t in 0..model.render_triangle_count as usize {
+        let mut normals: Vec<i16> = Vec::with_capacity(model.triangle_count as usize * 3 * 4);
+        let mut single_sided_triangle_count = 0;
+        let mut opaque_triangle_count = 0;
+        let mut sorted_triangles = Vec::with_capacity(model.render_triangle_count as usize);
+        let order = single_sided
+            .into_iter()
+            .chain(double_sided)
+            .chain(transparent);
+        for (i, t) in order.enumerate() {
             let a = model.triangle_render_a[t] as usize;
             let b = model.triangle_render_b[t] as usize;
             let c = model.triangle_render_c[t] as usize;
 
-            let colour_a = triangle_colours_a[t];
-            let mut colour_b = triangle_colours_b[t];
-            let mut colour_c = triangle_colours_c[t];
+            let shading = model.shading(t);
+            // The shader lights the rest, textured triangles only need their lightness.
+            let colour = match shading {
+                TriangleShading::Hidden => continue,
+                TriangleShading::Unlit(colour) => colour,
+                _ if model.triangle_material[t] != -1 => 0,
+                _ => model.triangle_colour[t],
+            };
+            let normal = |index: usize, w: i16| {
+                [
+                    model.normal_x[index],
+                    model.normal_y[index],
+                    model.normal_z[index],
+                    w,
+                ]
+            };
+            let corner_normals = match shading {
+                TriangleShading::Smooth => {
+                    [a, b, c].map(|index| normal(index, model.normal_magnitude[index] as i16))
+                }
+                TriangleShading::Flat => [normal(a, Self::FLAT_SHADED); 3],
+                _ => [[0; 4]; 3],
+            };
 
             let alpha = 0xff - model.triangle_transparency[t];
 
-            if colour_c == -2 {
-                continue;
-            }
-
-            if colour_c == -1 {
-                colour_c = colour_a;
-                colour_b = colour_a;
-            }
-
             let texture_id = (model.triangle_material[t] + 1) as u16;
 
             positions.push(vertex_x[a] as f32 / 512.0);
@@ -820,12 +3042,11 @@ impl ModelViewer {
             positions.push(-vertex_y[c] as f32 / 512.0);
             positions.push(-vertex_z[c] as f32 / 512.0);
 
-            // colours.push(model.triangle_colours[t]);
-            // colours.push(model.triangle_colours[t]);
-            // colours.push(model.triangle_colours[t]);
-            colours.push(colour_a as u16);
-            colours.push(colour_b as u16);
-            colours.push(colour_c as u16);
+            colours.push(colour);
+            colours.push(colour);
+            colours.push(colour);
+
+            normals.extend(corner_normals.iter().flatten());
 
             alphas.push(alpha);
             alphas.push(alpha);
@@ -845,125 +3066,191 @@ impl ModelViewer {
             texture_ids.push(texture_id);
 
             triangle_count += 1;
+            if i < single_sided_count {
+                single_sided_triangle_count += 1;
+            }
+            if i < opaque_count {
+                opaque_triangle_count += 1;
+            }
+            let corners = &positions[positions.len() - 9..];
+            sorted_triangles.push(SortedTriangle {
+                centre: (glm::make_vec3(&corners[0..3])
+                    + glm::make_vec3(&corners[3..6])
+                    + glm::make_vec3(&corners[6..9]))
+                    / 3.0,
+                double_sided: model.is_double_sided(t),
+                priority: model.draw_priority(t),
+            });
         }
 
-        unsafe {
-            let position_buffer = gl
-                .create_buffer()
-                .expect("position buffer should be created");
-            let colour_buffer = gl.create_buffer().expect("colour buffer should be created");
-            let alpha_buffer = gl.create_buffer().expect("alpha buffer should be created");
-            let texcoord_buffer = gl
-                .create_buffer()
-                .expect("texcoord buffer should be created");
-            let texture_id_buffer = gl
-                .create_buffer()
-                .expect("texture id buffer should be created");
-
-            gl.bind_vertex_array(Some(vertex_array));
-
-            gl.bind_buffer(glow::ARRAY_BUFFER, Some(position_buffer));
-            gl.buffer_data_u8_slice(
-                glow::ARRAY_BUFFER,
-                bytemuck::cast_slice(&positions),
-                glow::STATIC_DRAW,
-            );
-
-            gl.vertex_attrib_pointer_f32(
-                0,
-                3,
-                glow::FLOAT,
-                false,
-                std::mem::size_of::<f32>() as i32 * 3, /* + std::mem::size_of::<u16>() as i32*/
-                0,
-            );
-
-            gl.enable_vertex_attrib_array(0);
-
-            gl.bind_buffer(glow::ARRAY_BUFFER, Some(colour_buffer));
-            gl.buffer_data_u8_slice(
-                glow::ARRAY_BUFFER,
-                bytemuck::cast_slice(&colours),
-                glow::STATIC_DRAW,
-            );
-
-            gl.vertex_attrib_pointer_i32(
-                1,
-                1,
-                glow::UNSIGNED_SHORT,
-                std::mem::size_of::<u16>() as i32,
-                0,
-            );
+        let capacity = triangle_count as usize;
+        let reusable = self
+            .uploaded_model
+            .as_ref()
+            .is_some_and(|uploaded_model| uploaded_model.capacity >= capacity);
+        if !reusable {
+            if let Some(uploaded_model) = self.uploaded_model.take() {
+                uploaded_model.destroy(gl);
+            }
+            // Leave headroom so browsing similar sized models keeps reusing the buffers.
+            let capacity = capacity
+                .next_power_of_two()
+                .max(UploadedModel::MIN_CAPACITY);
+            self.uploaded_model = Some(UploadedModel::create(gl, capacity));
+        }
+        let uploaded_model = self
+            .uploaded_model
+            .as_mut()
+            .expect("uploaded model should exist");
+        uploaded_model.triangle_count = triangle_count;
+        uploaded_model.single_sided_count = single_sided_triangle_count;
+        uploaded_model.opaque_count = opaque_triangle_count;
+        uploaded_model.triangles = sorted_triangles;
+        uploaded_model.ambient = model.ambient as i32;
+        uploaded_model.contrast = model.contrast as i32;
 
-            gl.enable_vertex_attrib_array(1);
+        unsafe {
+            for (buffer, data) in [
+                (
+                    uploaded_model.position_buffer,
+                    bytemuck::cast_slice(&positions),
+                ),
+                (uploaded_model.colour_buffer, bytemuck::cast_slice(&colours)),
+                (uploaded_model.alpha_buffer, alphas.as_slice()),
+                (
+                    uploaded_model.texcoord_buffer,
+                    bytemuck::cast_slice(&texcoords),
+                ),
+                (
+                    uploaded_model.texture_id_buffer,
+                    bytemuck::cast_slice(&texture_ids),
+                ),
+                (uploaded_model.normal_buffer, bytemuck::cast_slice(&normals)),
+            ] {
+                gl.bind_buffer(glow::ARRAY_BUFFER, Some(buffer));
+                gl.buffer_sub_data_u8_slice(glow::ARRAY_BUFFER, 0, data);
+            }
+            gl.bind_buffer(glow::ARRAY_BUFFER, None);
+        }
+    }
 
-            gl.bind_buffer(glow::ARRAY_BUFFER, Some(alpha_buffer));
-            gl.buffer_data_u8_slice(
-                glow::ARRAY_BUFFER,
-                bytemuck::cast_slice(&alphas),
-                glow::STATIC_DRAW,
-            );
+    fn destroy(&mut self, gl: &glow::Context) {
+        if let Some(uploaded_model) = self.uploaded_model.take() {
+            uploaded_model.destroy(gl);
+        }
+    }
 
-            gl.vertex_attrib_pointer_f32(
-                2,
-                1,
-                glow::UNSIGNED_BYTE,
-                true,
-                std::mem::size_of::<u8>() as i32,
-                0,
-            );
+    fn camera_matrices(
+        &self,
+        width: f32,
+        height: f32,
+        orbit: &OrbitCamera,
+        camera: CameraSettings,
+    ) -> (glm::Mat4, glm::Mat4) {
+        let aspect = width / height;
 
-            gl.enable_vertex_attrib_array(2);
+        let eye = orbit.eye(self.radius * orbit.zoom);
+        let view = glm::rotation(camera.roll.to_radians(), &glm::vec3(0.0, 0.0, 1.0))
+            * glm::look_at(&eye, &orbit.target, &glm::vec3(0.0, 1.0, 0.0));
 
-            gl.bind_buffer(glow::ARRAY_BUFFER, Some(texcoord_buffer));
-            gl.buffer_data_u8_slice(
-                glow::ARRAY_BUFFER,
-                bytemuck::cast_slice(&texcoords),
-                glow::STATIC_DRAW,
-            );
+        let depths = self.view_depths(orbit, camera);
+        let projection = glm::perspective(
+            aspect,
+            camera.field_of_view.to_radians(),
+            depths.near,
+            depths.far,
+        );
 
-            gl.vertex_attrib_pointer_f32(
-                3,
-                2,
-                glow::FLOAT,
-                false,
-                std::mem::size_of::<f32>() as i32 * 2,
-                0,
-            );
+        (view, projection)
+    }
 
-            gl.enable_vertex_attrib_array(3);
+    /// Draws the triangles from `first` on furthest first, blended over what's behind them
+    /// without hiding each other. With `by_priority` lower priorities are drawn first whatever
+    /// their depth.
+    unsafe fn draw_sorted(
+        gl: &glow::Context,
+        uploaded_model: &UploadedModel,
+        first: usize,
+        view: &glm::Mat4,
+        face_culling: FaceCulling,
+        by_priority: bool,
+    ) {
+        use glow::HasContext as _;
 
-            gl.bind_buffer(glow::ARRAY_BUFFER, Some(texture_id_buffer));
-            gl.buffer_data_u8_slice(
-                glow::ARRAY_BUFFER,
-                bytemuck::cast_slice(&texture_ids),
-                glow::STATIC_DRAW,
-            );
+        let triangles = &uploaded_model.triangles[first..];
+        if triangles.is_empty() {
+            return;
+        }
+        // Further away is more negative in view space, the stable sort keeps the model's order
+        // between triangles at the same depth.
+        let mut order: Vec<(u8, f32, usize)> = triangles
+            .iter()
+            .enumerate()
+            .map(|(i, triangle)| {
+                let priority = if by_priority { triangle.priority } else { 0 };
+                (priority, (view * triangle.centre.push(1.0)).z, i)
+            })
+            .collect();
+        order.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+        let indices: Vec<u32> = order
+            .iter()
+            .flat_map(|&(_, _, i)| {
+                let vertex = (first + i) as u32 * 3;
+                [vertex, vertex + 1, vertex + 2]
+            })
+            .collect();
+        gl.buffer_sub_data_u8_slice(
+            glow::ELEMENT_ARRAY_BUFFER,
+            0,
+            bytemuck::cast_slice(&indices),
+        );
 
-            gl.vertex_attrib_pointer_i32(
-                4,
-                1,
-                glow::UNSIGNED_SHORT,
-                std::mem::size_of::<u16>() as i32,
-                0,
+        gl.depth_mask(false);
+        // The shader's colours aren't premultiplied, unlike egui's.
+        gl.enable(glow::BLEND);
+        gl.blend_func_separate(
+            glow::SRC_ALPHA,
+            glow::ONE_MINUS_SRC_ALPHA,
+            glow::ONE,
+            glow::ONE_MINUS_SRC_ALPHA,
+        );
+        // Runs of triangles culled alike are drawn together, keeping the order.
+        let mut start = 0;
+        for run in order.chunk_by(|a, b| triangles[a.2].double_sided == triangles[b.2].double_sided)
+        {
+            let culled = match face_culling {
+                FaceCulling::All => true,
+                FaceCulling::RenderType => !triangles[run[0].2].double_sided,
+                FaceCulling::None => false,
+            };
+            if culled {
+                gl.enable(glow::CULL_FACE);
+            } else {
+                gl.disable(glow::CULL_FACE);
+            }
+            gl.draw_elements(
+                glow::TRIANGLES,
+                run.len() as i32 * 3,
+                glow::UNSIGNED_INT,
+                (start * 3 * std::mem::size_of::<u32>()) as i32,
             );
-
-            gl.enable_vertex_attrib_array(4);
-
-            self.uploaded_model = Some(UploadedModel::new(
-                triangle_count,
-                vertex_array,
-                position_buffer,
-                colour_buffer,
-                texcoord_buffer,
-                texture_id_buffer,
-            ));
+            start += run.len();
         }
+        gl.enable(glow::CULL_FACE);
+        gl.depth_mask(true);
+        gl.blend_func(glow::ONE, glow::ONE_MINUS_SRC_ALPHA);
     }
 
-    fn destroy(&mut self, gl: &glow::Context) {
-        if let Some(uploaded_model) = self.uploaded_model.take() {
-            uploaded_model.destroy(gl);
+    /// The clip planes [`ModelViewer::camera_matrices`] projects with and the distance to the
+    /// orbited target.
+    fn view_depths(&self, orbit: &OrbitCamera, camera: CameraSettings) -> ViewDepths {
+        let radius = self.radius * orbit.zoom;
+        // The model stays around the origin wherever the target is panned to.
+        let (near, far) = camera.clip_planes(glm::length(&orbit.eye(radius)), self.model_radius);
+        ViewDepths {
+            near,
+            far,
+            target: radius,
         }
     }
 
@@ -972,32 +3259,14 @@ impl ModelViewer {
         gl: &glow::Context,
         width: f32,
         height: f32,
-        yaw: f32,
-        pitch: f32,
-        zoom: f32,
+        camera: &OrbitCamera,
         program: glow::Program,
         texture_array: glow::Texture,
+        settings: RenderSettings,
     ) {
         use glow::HasContext as _;
 
-        let aspect = width / height;
-        let field_of_view = 60f32;
-
-        let radius: f32 = self.radius * zoom;
-
-        let camera_front = glm::normalize(&glm::vec3(
-            yaw.cos() * pitch.cos(),
-            pitch.sin(),
-            yaw.sin() * pitch.cos(),
-        ));
-
-        let view = glm::look_at(
-            &(camera_front * radius),
-            &glm::vec3(0.0, 0.0, 0.0),
-            &glm::vec3(0.0, 1.0, 0.0),
-        );
-
-        let projection = glm::perspective(aspect, field_of_view.to_radians(), 0.1f32, 100.0f32);
+        let (view, projection) = self.camera_matrices(width, height, camera, settings.camera);
 
         unsafe {
             gl.enable(glow::CULL_FACE);
@@ -1024,9 +3293,68 @@ impl ModelViewer {
                     gl.get_uniform_location(program, "u_texture_array").as_ref(),
                     0,
                 );
+                gl.uniform_1_f32(
+                    gl.get_uniform_location(program, "u_brightness").as_ref(),
+                    settings.effective_brightness(),
+                );
+                gl.uniform_1_i32(
+                    gl.get_uniform_location(program, "u_linear_blend").as_ref(),
+                    (settings.colour_pipeline == ColourPipeline::LinearBlend) as i32,
+                );
+                gl.uniform_1_i32(
+                    gl.get_uniform_location(program, "u_dither").as_ref(),
+                    settings.dithering as i32,
+                );
+                let (light_x, light_y, light_z) = settings.light_direction;
+                let light_mag =
+                    f64::sqrt((light_x * light_x + light_y * light_y + light_z * light_z) as f64)
+                        as i32;
+                gl.uniform_3_f32(
+                    gl.get_uniform_location(program, "u_light").as_ref(),
+                    light_x as f32,
+                    light_y as f32,
+                    light_z as f32,
+                );
+                gl.uniform_1_i32(
+                    gl.get_uniform_location(program, "u_light_scale").as_ref(),
+                    (light_mag * uploaded_model.contrast) >> 8,
+                );
+                gl.uniform_1_i32(
+                    gl.get_uniform_location(program, "u_ambient").as_ref(),
+                    uploaded_model.ambient,
+                );
 
                 gl.bind_vertex_array(Some(uploaded_model.vertex_array));
-                gl.draw_arrays(glow::TRIANGLES, 0, uploaded_model.triangle_count * 3);
+                if settings.painter_order {
+                    gl.disable(glow::DEPTH_TEST);
+                    Self::draw_sorted(gl, uploaded_model, 0, &view, settings.face_culling, true);
+                    gl.enable(glow::DEPTH_TEST);
+                    return;
+                }
+                let single_sided_count = uploaded_model.single_sided_count;
+                let double_sided_count = uploaded_model.opaque_count - single_sided_count;
+                if settings.face_culling == FaceCulling::None {
+                    gl.disable(glow::CULL_FACE);
+                }
+                gl.draw_arrays(glow::TRIANGLES, 0, single_sided_count * 3);
+                if settings.face_culling == FaceCulling::RenderType {
+                    gl.disable(glow::CULL_FACE);
+                }
+                gl.draw_arrays(
+                    glow::TRIANGLES,
+                    single_sided_count * 3,
+                    double_sided_count * 3,
+                );
+                gl.enable(glow::CULL_FACE);
+
+                Self::draw_sorted(
+                    gl,
+                    uploaded_model,
+                    uploaded_model.opaque_count as usize,
+                    &view,
+                    settings.face_culling,
+                    false,
+                );
             }
         }
     }
@@ -0,0 +1,460 @@
+use std::collections::{hash_map::Entry, HashMap};
+
+use nalgebra_glm as glm;
+
+use super::now;
+use crate::runetek5::{
+    animation::{animaya::AnimayaAnimation, frame::AnimFrame, framemap::FrameMap, seq::SeqType},
+    graphics::model::ModelUnlit,
+    js5::Js5,
+};
+
+/// What the player steps through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AnimationSource {
+    /// Every frame of a frame group in file order, at a chosen speed.
+    FrameSet(u32),
+    /// A sequence's frames at their own delays.
+    Sequence(u32),
+}
+
+impl AnimationSource {
+    fn label(&self) -> &'static str {
+        match self {
+            AnimationSource::FrameSet(_) => "Frame set",
+            AnimationSource::Sequence(_) => "Sequence",
+        }
+    }
+}
+
+/// Returned while groups are still being fetched.
+const LOADING: &str = "";
+
+struct PlayerFrame {
+    frame_set_id: u32,
+    file_id: u32,
+    /// Client cycles the frame is shown for, None to use the player's speed.
+    delay: Option<u16>,
+    frame: AnimFrame,
+}
+
+/// The frames of a source decoded against their frame maps.
+struct LoadedAnimation {
+    source: AnimationSource,
+    frames: Vec<PlayerFrame>,
+    framemaps: HashMap<u16, FrameMap>,
+    /// The frame to loop back to after the last one.
+    loop_start: usize,
+    /// The length of a sequence in client cycles.
+    duration: Option<u32>,
+    /// The animaya animation a sequence plays instead of frames, stepped through a cycle at a
+    /// time.
+    animaya: Option<AnimayaAnimation>,
+}
+
+impl LoadedAnimation {
+    fn load(
+        frame_js5: &Js5,
+        framemap_js5: &Js5,
+        config_js5: Option<&Js5>,
+        source: AnimationSource,
+    ) -> Result<Self, String> {
+        let mut loaded = Self {
+            source,
+            frames: Vec::new(),
+            framemaps: HashMap::new(),
+            loop_start: 0,
+            duration: None,
+            animaya: None,
+        };
+        match source {
+            AnimationSource::FrameSet(frame_set_id) => {
+                let file_ids = frame_js5
+                    .get_file_ids(frame_set_id)
+                    .ok_or_else(|| format!("Frame set {frame_set_id} doesn't exist"))?;
+                let mut datas = Vec::with_capacity(file_ids.len());
+                for &file_id in file_ids.iter() {
+                    let data = frame_js5
+                        .get_file(frame_set_id, file_id)
+                        .ok_or(LOADING.to_owned())?;
+                    datas.push((file_id, data));
+                }
+                // Frames using another frame map than the first are left out.
+                let framemap_id = datas
+                    .first()
+                    .and_then(|(_, data)| AnimFrame::peek_framemap_id(data))
+                    .ok_or_else(|| format!("Frame set {frame_set_id} has no frames"))?;
+                for (file_id, data) in &datas {
+                    if AnimFrame::peek_framemap_id(data) == Some(framemap_id) {
+                        loaded.push_frame(framemap_js5, frame_set_id, *file_id, data, None)?;
+                    }
+                }
+            }
+            AnimationSource::Sequence(seq_id) => {
+                let config_js5 = config_js5.ok_or("The cache has no config archive")?;
+                if !config_js5.is_group_valid(SeqType::GROUP_ID) {
+                    return Err("The config archive has no sequences".to_owned());
+                }
+                let seq = match SeqType::from_js5(config_js5, seq_id) {
+                    Some(seq) => seq,
+                    None if config_js5.get_packed_group(SeqType::GROUP_ID).is_none() => {
+                        return Err(LOADING.to_owned())
+                    }
+                    None => return Err(format!("Sequence {seq_id} doesn't exist")),
+                };
+                if let Some(animaya_id) = seq.animaya_id {
+                    let data = frame_js5
+                        .get_file(
+                            SeqType::frame_group(animaya_id),
+                            SeqType::frame_file(animaya_id),
+                        )
+                        .ok_or(LOADING.to_owned())?;
+                    let animaya = AnimayaAnimation::decode(&data).ok_or_else(|| {
+                        format!("Failed to decode the animaya animation of sequence {seq_id}")
+                    })?;
+                    let framemap = loaded.load_framemap(framemap_js5, animaya.framemap_id)?;
+                    if framemap.skeleton.is_none() {
+                        return Err(format!("Frame map {} has no skeleton", animaya.framemap_id));
+                    }
+                    loaded.duration = Some(animaya.duration as u32);
+                    loaded.animaya = Some(animaya);
+                    return Ok(loaded);
+                }
+                for (&frame_id, &delay) in seq.frame_ids.iter().zip(&seq.frame_delays) {
+                    let frame_set_id = SeqType::frame_group(frame_id);
+                    let file_id = SeqType::frame_file(frame_id);
+                    let data = frame_js5
+                        .get_file(frame_set_id, file_id)
+                        .ok_or(LOADING.to_owned())?;
+                    loaded.push_frame(framemap_js5, frame_set_id, file_id, &data, Some(delay))?;
+                }
+                loaded.duration = Some(seq.duration());
+                if let Some(replay_offset) = seq.replay_offset {
+                    loaded.loop_start = loaded.frames.len().saturating_sub(replay_offset as usize);
+                }
+            }
+        }
+        Ok(loaded)
+    }
+
+    /// Decodes a frame, fetching and decoding its frame map on first use. Frames that fail to
+    /// decode are left out.
+    fn push_frame(
+        &mut self,
+        framemap_js5: &Js5,
+        frame_set_id: u32,
+        file_id: u32,
+        data: &[u8],
+        delay: Option<u16>,
+    ) -> Result<(), String> {
+        let Some(framemap_id) = AnimFrame::peek_framemap_id(data) else {
+            return Ok(());
+        };
+        let framemap = self.load_framemap(framemap_js5, framemap_id)?;
+        if let Some(frame) = AnimFrame::decode(data, framemap) {
+            self.frames.push(PlayerFrame {
+                frame_set_id,
+                file_id,
+                delay,
+                frame,
+            });
+        }
+        Ok(())
+    }
+
+    /// Fetches and decodes a frame map on first use.
+    fn load_framemap(&mut self, framemap_js5: &Js5, framemap_id: u16) -> Result<&FrameMap, String> {
+        match self.framemaps.entry(framemap_id) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => {
+                let framemap_data = framemap_js5
+                    .get_file(framemap_id as u32, 0)
+                    .ok_or(LOADING.to_owned())?;
+                let framemap = FrameMap::decode(&framemap_data)
+                    .ok_or_else(|| format!("Failed to decode frame map {framemap_id}"))?;
+                Ok(entry.insert(framemap))
+            }
+        }
+    }
+
+    /// The number of steps on the timeline, cycles for animaya animations.
+    fn frame_count(&self) -> usize {
+        match &self.animaya {
+            Some(animaya) => animaya.duration as usize,
+            None => self.frames.len(),
+        }
+    }
+
+    /// Client cycles a step is shown for, frames without a delay use the player's speed.
+    fn frame_cycles(&self, index: usize, default: u16) -> u16 {
+        match &self.animaya {
+            Some(_) => 1,
+            None => self.frames[index].delay.unwrap_or(default),
+        }
+    }
+
+    fn frame_label(&self, index: usize) -> String {
+        if self.animaya.is_some() {
+            return format!("Cycle {index}");
+        }
+        let frame = &self.frames[index];
+        match frame.delay {
+            Some(delay) => format!(
+                "Frame {}:{} for {delay} cycles",
+                frame.frame_set_id, frame.file_id
+            ),
+            None => format!("Frame {}:{}", frame.frame_set_id, frame.file_id),
+        }
+    }
+}
+
+/// Plays a frame set or a sequence on the model in the viewport, a frame every few client
+/// cycles.
+pub struct AnimationPlayerWindow {
+    /// Whether the model is posed at all, the model is shown unposed otherwise.
+    enabled: bool,
+    source: AnimationSource,
+    loaded: Option<LoadedAnimation>,
+    error: Option<String>,
+    frame_index: usize,
+    playing: bool,
+    /// Client cycles each frame of a frame set is shown for.
+    frame_cycles: u16,
+    /// Time into the current frame, in seconds.
+    elapsed: f64,
+    last_time: Option<f64>,
+    changed: bool,
+}
+
+impl AnimationPlayerWindow {
+    /// The length of a client cycle in seconds.
+    const CYCLE_LENGTH: f64 = SeqType::CYCLE_MS as f64 / 1000.0;
+
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            source: AnimationSource::FrameSet(0),
+            loaded: None,
+            error: None,
+            frame_index: 0,
+            playing: true,
+            frame_cycles: 5,
+            elapsed: 0.0,
+            last_time: None,
+            changed: false,
+        }
+    }
+
+    /// Whether the pose changed since the last call, so the model has to be posed again.
+    pub fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+
+    /// Poses the model in the current frame, if the player is enabled.
+    pub fn pose(&self, model: &mut ModelUnlit) {
+        if !self.enabled {
+            return;
+        }
+        let Some(loaded) = &self.loaded else {
+            return;
+        };
+        if let Some(animaya) = &loaded.animaya {
+            let Some(skeleton) = &loaded.framemaps[&animaya.framemap_id].skeleton else {
+                return;
+            };
+            let mut transforms = animaya.skinning_transforms(skeleton, self.frame_index as f32);
+            // Skeletons are in the units of the model file, older models are shown scaled up.
+            if model.version < 13 {
+                let scale = glm::scaling(&glm::vec3(4.0, 4.0, 4.0));
+                let inverse_scale = glm::scaling(&glm::vec3(0.25, 0.25, 0.25));
+                for transform in &mut transforms {
+                    *transform = scale * *transform * inverse_scale;
+                }
+            }
+            model.apply_skinning(&transforms);
+        } else if let Some(frame) = loaded.frames.get(self.frame_index) {
+            let framemap = &loaded.framemaps[&frame.frame.framemap_id];
+            model.apply_frame(&frame.frame, framemap);
+        }
+    }
+
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        frame_js5: &Js5,
+        framemap_js5: &Js5,
+        config_js5: Option<&Js5>,
+    ) {
+        if self.enabled {
+            self.load(frame_js5, framemap_js5, config_js5);
+            self.advance(ctx);
+        }
+        egui::Window::new("Animation")
+            .default_open(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                self.ui(ui, frame_js5, config_js5);
+            });
+    }
+
+    fn load(&mut self, frame_js5: &Js5, framemap_js5: &Js5, config_js5: Option<&Js5>) {
+        let is_loaded = self
+            .loaded
+            .as_ref()
+            .is_some_and(|loaded| loaded.source == self.source);
+        if is_loaded {
+            return;
+        }
+        match LoadedAnimation::load(frame_js5, framemap_js5, config_js5, self.source) {
+            Ok(loaded) => {
+                self.loaded = Some(loaded);
+                self.error = None;
+                self.frame_index = 0;
+                self.elapsed = 0.0;
+                self.changed = true;
+            }
+            // Still being fetched.
+            Err(error) if error.is_empty() => {}
+            Err(error) => {
+                self.loaded = None;
+                self.error = Some(error);
+            }
+        }
+    }
+
+    fn advance(&mut self, ctx: &egui::Context) {
+        let time = now();
+        let delta = self.last_time.map_or(0.0, |last_time| time - last_time);
+        self.last_time = Some(time);
+        let Some(loaded) = &self.loaded else {
+            return;
+        };
+        let frame_count = loaded.frame_count();
+        if !self.playing || frame_count < 2 {
+            return;
+        }
+        self.elapsed += delta;
+        loop {
+            // The client shows frames without a delay for a cycle.
+            let frame_length = loaded
+                .frame_cycles(self.frame_index, self.frame_cycles)
+                .max(1) as f64
+                * Self::CYCLE_LENGTH;
+            if self.elapsed < frame_length {
+                break;
+            }
+            self.elapsed -= frame_length;
+            self.frame_index += 1;
+            if self.frame_index >= frame_count {
+                self.frame_index = loaded.loop_start;
+            }
+            self.changed = true;
+        }
+        ctx.request_repaint();
+    }
+
+    fn source_ui(&mut self, ui: &mut egui::Ui, frame_js5: &Js5, config_js5: Option<&Js5>) {
+        ui.horizontal(|ui| {
+            let last_frame_set_id = frame_js5.get_last_group_id();
+            let last_seq_id = config_js5
+                .and_then(|config_js5| config_js5.get_file_ids(SeqType::GROUP_ID))
+                .and_then(|file_ids| file_ids.last().copied());
+            egui::ComboBox::from_id_salt("animation_source")
+                .selected_text(self.source.label())
+                .show_ui(ui, |ui| {
+                    let is_frame_set = matches!(self.source, AnimationSource::FrameSet(_));
+                    if ui.selectable_label(is_frame_set, "Frame set").clicked() && !is_frame_set {
+                        self.source = AnimationSource::FrameSet(0);
+                    }
+                    let response = ui
+                        .add_enabled(
+                            last_seq_id.is_some(),
+                            egui::SelectableLabel::new(!is_frame_set, "Sequence"),
+                        )
+                        .on_disabled_hover_text("The cache has no sequences");
+                    if response.clicked() && is_frame_set {
+                        self.source = AnimationSource::Sequence(0);
+                    }
+                });
+            match &mut self.source {
+                AnimationSource::FrameSet(frame_set_id) => {
+                    ui.add(egui::DragValue::new(frame_set_id).range(0..=last_frame_set_id));
+                }
+                AnimationSource::Sequence(seq_id) => {
+                    ui.add(egui::DragValue::new(seq_id).range(0..=last_seq_id.unwrap_or(0)));
+                }
+            }
+        });
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, frame_js5: &Js5, config_js5: Option<&Js5>) {
+        if ui
+            .checkbox(&mut self.enabled, "Animate the model")
+            .changed()
+        {
+            self.changed = true;
+            self.last_time = None;
+        }
+        ui.add_enabled_ui(self.enabled, |ui| {
+            self.source_ui(ui, frame_js5, config_js5);
+            if let Some(error) = &self.error {
+                ui.label(error);
+                return;
+            }
+            let Some(loaded) = &self.loaded else {
+                if self.enabled {
+                    ui.spinner();
+                }
+                return;
+            };
+            let frame_count = loaded.frame_count();
+            if frame_count == 0 {
+                ui.label("No frame could be decoded");
+                return;
+            }
+            let frame_label = loaded.frame_label(self.frame_index.min(frame_count - 1));
+            if let Some(cycles) = loaded.duration {
+                let kind = if loaded.animaya.is_some() {
+                    "animaya cycles"
+                } else {
+                    "frames"
+                };
+                ui.label(format!(
+                    "{frame_count} {kind}, {:.2} s",
+                    (cycles * SeqType::CYCLE_MS) as f64 / 1000.0
+                ));
+            }
+
+            ui.horizontal(|ui| {
+                let label = if self.playing { "Pause" } else { "Play" };
+                if ui.button(label).clicked() {
+                    self.playing = !self.playing;
+                    self.last_time = None;
+                }
+                if ui.button("<").on_hover_text("Previous frame").clicked() {
+                    self.frame_index = (self.frame_index + frame_count - 1) % frame_count;
+                    self.changed = true;
+                }
+                if ui.button(">").on_hover_text("Next frame").clicked() {
+                    self.frame_index = (self.frame_index + 1) % frame_count;
+                    self.changed = true;
+                }
+                ui.label(frame_label);
+            });
+            let timeline = ui.add(
+                egui::Slider::new(&mut self.frame_index, 0..=frame_count - 1).text("Timeline"),
+            );
+            if timeline.changed() {
+                self.elapsed = 0.0;
+                self.changed = true;
+            }
+            if let AnimationSource::FrameSet(_) = loaded.source {
+                ui.add(
+                    egui::Slider::new(&mut self.frame_cycles, 1..=50)
+                        .text("Cycles per frame")
+                        .suffix(" × 20 ms"),
+                );
+            }
+        });
+    }
+}
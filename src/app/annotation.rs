@@ -0,0 +1,226 @@
+use super::{link, text_label::TextLabelRenderer};
+use crate::runetek5::{graphics::font::Font, js5::Js5};
+
+/// Milliseconds since the Unix epoch.
+#[cfg(target_arch = "wasm32")]
+fn unix_time_ms() -> f64 {
+    #[wasm_bindgen::prelude::wasm_bindgen]
+    extern "C" {
+        #[wasm_bindgen(js_namespace = Date)]
+        fn now() -> f64;
+    }
+
+    now()
+}
+
+/// Milliseconds since the Unix epoch.
+#[cfg(not(target_arch = "wasm32"))]
+fn unix_time_ms() -> f64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0.0, |duration| duration.as_secs_f64() * 1000.0)
+}
+
+/// Formats a Unix time as a UTC date and time, like `2024-05-01 13:37 UTC`.
+fn format_utc(unix_time_ms: f64) -> String {
+    let minutes = (unix_time_ms / 60_000.0).floor() as i64;
+    let (days, minute_of_day) = (minutes.div_euclid(1440), minutes.rem_euclid(1440));
+    // Converts days since the epoch to a proleptic Gregorian date, from 400 year eras starting
+    // on the 1st of March so leap days fall at the end of each year.
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02} UTC",
+        minute_of_day / 60,
+        minute_of_day % 60
+    )
+}
+
+/// Text burned into the corner of exported renders, so images keep saying what they show once
+/// they are shared. Drawn with a game font.
+pub struct Annotation {
+    pub enabled: bool,
+    pub model_id: bool,
+    pub cache: bool,
+    pub timestamp: bool,
+    /// Extra lines, e.g. a credit or a watermark.
+    pub custom_text: String,
+    /// Pixels per font pixel, the game fonts are small next to large renders.
+    pub scale: usize,
+    font: Option<TextLabelRenderer>,
+    font_missing: bool,
+}
+
+impl Annotation {
+    /// The sprite group of the font the client draws most interface text with.
+    const FONT_NAME: &'static str = "p12_full";
+    const MARGIN: usize = 4;
+    const TEXT_ARGB: u32 = 0xffffffff;
+    const MAX_SCALE: usize = 8;
+
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            model_id: true,
+            cache: true,
+            timestamp: true,
+            custom_text: String::new(),
+            scale: 1,
+            font: None,
+            font_missing: false,
+        }
+    }
+
+    /// Loads the font once annotations are enabled, waiting for its groups to be fetched.
+    pub fn load_font(&mut self, sprite_js5: &Js5, fontmetrics_js5: &Js5) {
+        if !self.enabled || self.font.is_some() || self.font_missing {
+            return;
+        }
+        let Some(font_id) = sprite_js5.index.get_group_id_by_name(Self::FONT_NAME) else {
+            self.font_missing = true;
+            return;
+        };
+        self.font = TextLabelRenderer::load(font_id, sprite_js5, fontmetrics_js5);
+    }
+
+    /// The annotation's lines of text, top to bottom.
+    pub fn lines(&self, model_id: u32) -> Vec<String> {
+        let mut lines = Vec::new();
+        if self.model_id {
+            lines.push(format!("Model {model_id}"));
+        }
+        if self.cache {
+            lines.push(match link::linked_cache_id() {
+                Some(cache_id) => format!("OpenRS2 cache {cache_id}"),
+                None => "Local cache".to_owned(),
+            });
+        }
+        if self.timestamp {
+            lines.push(format_utc(unix_time_ms()));
+        }
+        lines.extend(
+            self.custom_text
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(str::to_owned),
+        );
+        lines
+    }
+
+    fn font(&self) -> Option<&Font> {
+        if !self.enabled {
+            return None;
+        }
+        self.font.as_ref().map(TextLabelRenderer::font)
+    }
+
+    /// Draws the lines into the bottom left corner of an image at font scale, as 0xAARRGGBB
+    /// pixels that are 0 where there is no text. None if there is nothing to draw.
+    fn overlay(&self, model_id: u32, width: usize, height: usize) -> Option<Vec<u32>> {
+        let font = self.font()?;
+        let lines = self.lines(model_id);
+        if lines.is_empty() {
+            return None;
+        }
+        let (width, height) = (width / self.scale, height / self.scale);
+        let mut pixels = vec![0; width * height];
+        let line_height = font.line_height();
+        let mut y = height as i32 - Self::MARGIN as i32 - line_height * lines.len() as i32;
+        for line in &lines {
+            let x = Self::MARGIN as i32;
+            font.draw(
+                &mut pixels,
+                width,
+                line,
+                x + 1,
+                y + 1,
+                TextLabelRenderer::SHADOW_ARGB,
+            );
+            font.draw(&mut pixels, width, line, x, y, Self::TEXT_ARGB);
+            y += line_height;
+        }
+        Some(pixels)
+    }
+
+    /// Calls `blend` with the index and colour of every annotated pixel of an image.
+    fn for_each_pixel(
+        &self,
+        model_id: u32,
+        width: usize,
+        height: usize,
+        mut blend: impl FnMut(usize, u32),
+    ) {
+        let Some(overlay) = self.overlay(model_id, width, height) else {
+            return;
+        };
+        let overlay_width = width / self.scale;
+        // Scale up by repeating overlay pixels, leftover rows and columns stay unannotated.
+        for y in 0..height {
+            for x in 0..overlay_width * self.scale {
+                let overlay_index = x / self.scale + y / self.scale * overlay_width;
+                if let Some(&argb) = overlay.get(overlay_index).filter(|&&argb| argb != 0) {
+                    blend(x + y * width, argb);
+                }
+            }
+        }
+    }
+
+    /// Burns the annotation into 0xRRGGBB pixels, as rendered by the rasterizer.
+    pub fn burn_rgb(&self, model_id: u32, pixels: &mut [u32], width: usize) {
+        let height = pixels.len() / width.max(1);
+        self.for_each_pixel(model_id, width, height, |index, argb| {
+            pixels[index] = argb & 0xffffff;
+        });
+    }
+
+    /// Burns the annotation into an egui image, e.g. a screenshot of the viewport.
+    pub fn burn_colour_image(&self, model_id: u32, image: &mut egui::ColorImage) {
+        let [width, height] = image.size;
+        self.for_each_pixel(model_id, width, height, |index, argb| {
+            let [_, r, g, b] = argb.to_be_bytes();
+            image.pixels[index] = egui::Color32::from_rgb(r, g, b);
+        });
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.enabled, "Annotate renders")
+            .on_hover_text("Burn text into the corner of rendered frames and screenshots");
+        ui.add_enabled_ui(self.enabled, |ui| {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.model_id, "Model id");
+                ui.checkbox(&mut self.cache, "Cache");
+                ui.checkbox(&mut self.timestamp, "Timestamp");
+            });
+            ui.add(
+                egui::TextEdit::multiline(&mut self.custom_text)
+                    .desired_rows(2)
+                    .hint_text("Custom text..."),
+            );
+            ui.add(
+                egui::Slider::new(&mut self.scale, 1..=Self::MAX_SCALE)
+                    .text("Text scale")
+                    .suffix("x"),
+            );
+            if self.font_missing {
+                ui.label(format!("The cache has no {} font", Self::FONT_NAME));
+            } else if self.enabled && self.font.is_none() {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label("Loading font...");
+                });
+            }
+        });
+    }
+}
@@ -0,0 +1,408 @@
+use super::{
+    annotation::Annotation, now, orbit_camera::OrbitCamera, save::save_files, CameraSettings,
+    ModelViewer,
+};
+use crate::{
+    export::{png, ExportFile},
+    runetek5::graphics::{model::ModelLit, rasterizer::Rasterizer, texture::TextureProvider},
+};
+
+/// The angles and zoom of the main viewport's orbit camera, angles in degrees.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraPose {
+    pub yaw: f32,
+    pub pitch: f32,
+    pub zoom: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CameraKeyframe {
+    /// Seconds from the start of the path.
+    time: f32,
+    pose: CameraPose,
+}
+
+fn catmull_rom(p0: f32, p1: f32, p2: f32, p3: f32, t: f32) -> f32 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * (2.0 * p1
+        + (p2 - p0) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+}
+
+/// Keyframes sorted by time, interpolated with a Catmull-Rom spline.
+#[derive(Default)]
+struct CameraPath {
+    keyframes: Vec<CameraKeyframe>,
+}
+
+impl CameraPath {
+    fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |keyframe| keyframe.time)
+    }
+
+    fn insert(&mut self, keyframe: CameraKeyframe) {
+        let index = self
+            .keyframes
+            .partition_point(|other| other.time <= keyframe.time);
+        self.keyframes.insert(index, keyframe);
+    }
+
+    fn sample(&self, time: f32) -> Option<CameraPose> {
+        let last = self.keyframes.len().checked_sub(1)?;
+        let next = self
+            .keyframes
+            .partition_point(|keyframe| keyframe.time <= time);
+        if next == 0 {
+            return Some(self.keyframes[0].pose);
+        }
+        if next > last {
+            return Some(self.keyframes[last].pose);
+        }
+        let (i1, i2) = (next - 1, next);
+        let (k1, k2) = (&self.keyframes[i1], &self.keyframes[i2]);
+        let span = k2.time - k1.time;
+        let t = if span > 0.0 {
+            (time - k1.time) / span
+        } else {
+            1.0
+        };
+        let p0 = self.keyframes[i1.saturating_sub(1)].pose;
+        let p3 = self.keyframes[(i2 + 1).min(last)].pose;
+        let (p1, p2) = (k1.pose, k2.pose);
+        Some(CameraPose {
+            yaw: catmull_rom(p0.yaw, p1.yaw, p2.yaw, p3.yaw, t),
+            pitch: catmull_rom(p0.pitch, p1.pitch, p2.pitch, p3.pitch, t).clamp(-89.0, 89.0),
+            zoom: catmull_rom(p0.zoom, p1.zoom, p2.zoom, p3.zoom, t),
+        })
+    }
+}
+
+/// Renders the path with the software rasterizer into a PNG sequence, a few frames per call.
+struct PathRender {
+    model_id: u32,
+    frame_count: usize,
+    next_frame: usize,
+    fps: u32,
+    rasterizer: Rasterizer,
+    files: Vec<ExportFile>,
+}
+
+impl PathRender {
+    const FRAMES_PER_STEP: usize = 2;
+
+    /// Returns true once every frame has been rendered.
+    fn step(
+        &mut self,
+        path: &CameraPath,
+        model: &ModelLit,
+        texture_provider: &TextureProvider,
+        model_viewer: &ModelViewer,
+        orbit: &OrbitCamera,
+        camera: CameraSettings,
+        annotation: &Annotation,
+    ) -> bool {
+        let (width, height) = (self.rasterizer.width, self.rasterizer.height);
+        let end = (self.next_frame + Self::FRAMES_PER_STEP).min(self.frame_count);
+        for frame in self.next_frame..end {
+            let Some(pose) = path.sample(frame as f32 / self.fps as f32) else {
+                break;
+            };
+            let orbit = OrbitCamera {
+                yaw: pose.yaw,
+                pitch: pose.pitch,
+                zoom: pose
+                    .zoom
+                    .clamp(camera.min_zoom, camera.max_zoom.max(camera.min_zoom)),
+                ..*orbit
+            };
+            let (view, projection) =
+                model_viewer.camera_matrices(width as f32, height as f32, &orbit, camera);
+            let (light_x, light_y, light_z) = ModelViewer::LIGHT_DIRECTION;
+            self.rasterizer.clear(0);
+            self.rasterizer.render_model(
+                texture_provider,
+                model,
+                &(projection * view),
+                light_x,
+                light_y,
+                light_z,
+            );
+            annotation.burn_rgb(self.model_id, &mut self.rasterizer.pixels, width);
+            self.files.push(ExportFile {
+                path: format!("frame_{frame:05}.png"),
+                data: png::encode_rgb(width as u32, height as u32, &self.rasterizer.pixels, None),
+            });
+        }
+        self.next_frame = end;
+        self.next_frame >= self.frame_count
+    }
+}
+
+/// Keyframes camera poses of the main viewport, plays them back and renders the flythrough to an
+/// image sequence.
+pub struct CameraPathWindow {
+    path: CameraPath,
+    /// When playback started, from `now()`.
+    playing_since: Option<f64>,
+    /// When recording started and when the camera was last sampled, from `now()`.
+    recording: Option<(f64, f64)>,
+    fps: u32,
+    width: usize,
+    height: usize,
+    render: Option<PathRender>,
+    status: Option<String>,
+    /// Text burned into rendered frames, also used for screenshots of the viewport.
+    pub annotation: Annotation,
+}
+
+impl CameraPathWindow {
+    const RECORD_INTERVAL_MS: f64 = 250.0;
+    const KEYFRAME_STEP: f32 = 1.0;
+    const MAX_SIZE: usize = 1920;
+
+    pub fn new() -> Self {
+        Self {
+            path: CameraPath::default(),
+            playing_since: None,
+            recording: None,
+            fps: 30,
+            width: 640,
+            height: 480,
+            render: None,
+            status: None,
+            annotation: Annotation::new(),
+        }
+    }
+
+    /// Shows the window and returns the pose the viewport should take while the path plays.
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        pose: CameraPose,
+        model_id: u32,
+        brightness: f32,
+    ) -> Option<CameraPose> {
+        self.record(pose);
+        if self.recording.is_some() || self.render.is_some() || self.playing_since.is_some() {
+            ctx.request_repaint();
+        }
+
+        egui::Window::new("Camera Path")
+            .default_open(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                self.ui(ui, pose, model_id, brightness);
+            });
+
+        let started = self.playing_since?;
+        let time = ((now() - started) / 1000.0) as f32;
+        if time > self.path.duration() {
+            self.playing_since = None;
+        }
+        self.path.sample(time)
+    }
+
+    fn record(&mut self, pose: CameraPose) {
+        let Some((started, last_sample)) = self.recording.as_mut() else {
+            return;
+        };
+        let time = now();
+        if time - *last_sample < Self::RECORD_INTERVAL_MS {
+            return;
+        }
+        *last_sample = time;
+        let unchanged = self
+            .path
+            .keyframes
+            .last()
+            .is_some_and(|keyframe| keyframe.pose == pose);
+        if !unchanged {
+            self.path.insert(CameraKeyframe {
+                time: ((time - *started) / 1000.0) as f32,
+                pose,
+            });
+        }
+    }
+
+    /// Renders the next few frames of a running render of `model`, saving them once done. Frames
+    /// orbit the target of the viewport's camera.
+    pub fn step_render(
+        &mut self,
+        model_id: u32,
+        model: Option<&ModelLit>,
+        texture_provider: &TextureProvider,
+        model_viewer: &ModelViewer,
+        orbit: &OrbitCamera,
+        camera: CameraSettings,
+    ) {
+        let (Some(render), Some(model)) = (self.render.as_mut(), model) else {
+            return;
+        };
+        if render.model_id != model_id {
+            self.render = None;
+            self.status = Some("Render cancelled, the model changed".to_owned());
+            return;
+        }
+        if !render.step(
+            &self.path,
+            model,
+            texture_provider,
+            model_viewer,
+            orbit,
+            camera,
+            &self.annotation,
+        ) {
+            return;
+        }
+        let render = self.render.take().unwrap();
+        let count = render.files.len();
+        let dir_name = format!("camera_path_{}", render.model_id);
+        self.status = Some(match save_files(&dir_name, render.files) {
+            Ok(location) => format!("Rendered {count} frames to {location}"),
+            Err(err) => format!("Render failed: {err}"),
+        });
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, pose: CameraPose, model_id: u32, brightness: f32) {
+        let busy = self.render.is_some();
+        ui.horizontal(|ui| {
+            ui.add_enabled_ui(!busy && self.recording.is_none(), |ui| {
+                if ui.button("Add keyframe").clicked() {
+                    let time = match self.path.keyframes.last() {
+                        Some(keyframe) => keyframe.time + Self::KEYFRAME_STEP,
+                        None => 0.0,
+                    };
+                    self.path.insert(CameraKeyframe { time, pose });
+                }
+            });
+            let recording = self.recording.is_some();
+            if ui
+                .add_enabled(!busy, egui::SelectableLabel::new(recording, "⏺ Record"))
+                .on_hover_text("Sample the camera while you move it, replacing the keyframes")
+                .clicked()
+            {
+                if recording {
+                    self.recording = None;
+                } else {
+                    let time = now();
+                    self.path.keyframes.clear();
+                    self.path.insert(CameraKeyframe { time: 0.0, pose });
+                    self.recording = Some((time, time));
+                    self.playing_since = None;
+                }
+            }
+            let playing = self.playing_since.is_some();
+            let can_play = self.path.keyframes.len() >= 2 && self.recording.is_none();
+            if ui
+                .add_enabled(
+                    can_play,
+                    egui::Button::new(if playing { "⏹ Stop" } else { "▶ Play" }),
+                )
+                .clicked()
+            {
+                self.playing_since = if playing { None } else { Some(now()) };
+            }
+            if ui.add_enabled(!busy, egui::Button::new("Clear")).clicked() {
+                self.path.keyframes.clear();
+                self.recording = None;
+                self.playing_since = None;
+            }
+        });
+
+        ui.label(format!(
+            "{} keyframes, {:.1} s",
+            self.path.keyframes.len(),
+            self.path.duration()
+        ));
+        let mut remove = None;
+        let times: Vec<f32> = self.path.keyframes.iter().map(|k| k.time).collect();
+        egui::ScrollArea::vertical()
+            .max_height(160.0)
+            .show(ui, |ui| {
+                egui::Grid::new("camera_path_keyframes")
+                    .num_columns(5)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for header in ["Time", "Yaw", "Pitch", "Zoom", ""] {
+                            ui.strong(header);
+                        }
+                        ui.end_row();
+                        for (index, keyframe) in self.path.keyframes.iter_mut().enumerate() {
+                            // Keep retimed keyframes between their neighbours so the path stays sorted.
+                            let min = index.checked_sub(1).map_or(0.0, |i| times[i]);
+                            let max = times.get(index + 1).copied().unwrap_or(f32::MAX);
+                            ui.add(
+                                egui::DragValue::new(&mut keyframe.time)
+                                    .speed(0.05)
+                                    .range(min..=max)
+                                    .suffix(" s"),
+                            );
+                            ui.monospace(format!("{:.1}", keyframe.pose.yaw));
+                            ui.monospace(format!("{:.1}", keyframe.pose.pitch));
+                            ui.monospace(format!("{:.2}", keyframe.pose.zoom));
+                            if ui.small_button("✖").clicked() {
+                                remove = Some(index);
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+        if let Some(index) = remove {
+            self.path.keyframes.remove(index);
+        }
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.add(egui::DragValue::new(&mut self.width).range(16..=Self::MAX_SIZE));
+            ui.label("x");
+            ui.add(egui::DragValue::new(&mut self.height).range(16..=Self::MAX_SIZE));
+            ui.add(
+                egui::DragValue::new(&mut self.fps)
+                    .range(1..=60)
+                    .suffix(" fps"),
+            );
+        });
+        ui.add_enabled_ui(!busy, |ui| self.annotation.ui(ui));
+        match &self.render {
+            Some(render) => {
+                ui.add(
+                    egui::ProgressBar::new(render.next_frame as f32 / render.frame_count as f32)
+                        .text(format!(
+                            "{} / {} frames",
+                            render.next_frame, render.frame_count
+                        )),
+                );
+                if ui.button("Cancel").clicked() {
+                    self.render = None;
+                }
+            }
+            None => {
+                let can_render = self.path.keyframes.len() >= 2 && self.recording.is_none();
+                if ui
+                    .add_enabled(can_render, egui::Button::new("Render frames"))
+                    .on_hover_text("Renders a PNG sequence with the software renderer")
+                    .clicked()
+                {
+                    let frame_count = (self.path.duration() * self.fps as f32).ceil() as usize + 1;
+                    let mut rasterizer = Rasterizer::new(brightness as f64);
+                    rasterizer.resize(self.width, self.height);
+                    self.render = Some(PathRender {
+                        model_id,
+                        frame_count,
+                        next_frame: 0,
+                        fps: self.fps,
+                        rasterizer,
+                        files: Vec::with_capacity(frame_count),
+                    });
+                    self.playing_since = None;
+                    self.status = None;
+                }
+            }
+        }
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+    }
+}
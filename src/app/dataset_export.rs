@@ -0,0 +1,121 @@
+use super::save::save_files;
+use crate::{
+    export::dataset::DatasetExport,
+    runetek5::{graphics::texture::TextureProvider, js5::Js5},
+};
+
+/// Exports a JSON manifest and thumbnail PNGs for a range of model ids.
+pub struct DatasetExportWindow {
+    start_id: u32,
+    end_id: u32,
+    thumbnail_size: usize,
+    export: Option<DatasetExport>,
+    status: Option<String>,
+}
+
+impl DatasetExportWindow {
+    const THUMBNAIL_SIZES: [usize; 4] = [64, 128, 256, 512];
+
+    pub fn new() -> Self {
+        Self {
+            start_id: 0,
+            end_id: 99,
+            thumbnail_size: 128,
+            export: None,
+            status: None,
+        }
+    }
+
+    /// Starts exporting the given models, unless an export is already running.
+    pub fn export_ids(&mut self, ids: Vec<u32>, brightness: f32) {
+        if self.export.is_some() || ids.is_empty() {
+            return;
+        }
+        self.status = None;
+        self.export = Some(DatasetExport::from_ids(
+            ids,
+            self.thumbnail_size,
+            brightness as f64,
+        ));
+    }
+
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        model_js5: &Js5,
+        texture_provider: &TextureProvider,
+        brightness: f32,
+    ) {
+        if let Some(export) = self.export.as_mut() {
+            if export.step(model_js5, texture_provider) {
+                let export = self.export.take().unwrap();
+                let dir_name = format!("dataset_{}_{}", export.start_id, export.end_id);
+                let count = export.exported_count();
+                self.status = Some(match save_files(&dir_name, export.finish()) {
+                    Ok(location) => format!("Exported {count} models to {location}"),
+                    Err(err) => format!("Export failed: {err}"),
+                });
+            }
+        }
+        if self.export.is_some() {
+            ctx.request_repaint();
+        }
+
+        egui::Window::new("Dataset Export")
+            .default_open(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                self.ui(ui, model_js5, brightness);
+            });
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, model_js5: &Js5, brightness: f32) {
+        let last_id = model_js5.get_last_group_id();
+        let exporting = self.export.is_some();
+        ui.add_enabled_ui(!exporting, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Ids");
+                ui.add(egui::DragValue::new(&mut self.start_id).range(0..=last_id));
+                ui.label("to");
+                ui.add(egui::DragValue::new(&mut self.end_id).range(self.start_id..=last_id));
+            });
+            egui::ComboBox::from_label("Thumbnail size")
+                .selected_text(format!("{0}x{0}", self.thumbnail_size))
+                .show_ui(ui, |ui| {
+                    for size in Self::THUMBNAIL_SIZES {
+                        ui.selectable_value(
+                            &mut self.thumbnail_size,
+                            size,
+                            format!("{size}x{size}"),
+                        );
+                    }
+                });
+        });
+        self.end_id = self.end_id.max(self.start_id);
+
+        if let Some(export) = &self.export {
+            let (done, total) = export.progress();
+            let mut cancel = false;
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label(format!("Exporting... {done}/{total}"));
+                cancel = ui.button("Cancel").clicked();
+            });
+            if cancel {
+                self.export = None;
+            }
+        } else if ui.button("Export").clicked() {
+            self.status = None;
+            self.export = Some(DatasetExport::new(
+                self.start_id,
+                self.end_id,
+                self.thumbnail_size,
+                brightness as f64,
+            ));
+        }
+
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use super::{
+    definitions::DefinitionScan,
+    names::{NameKind, NameTables},
+};
+
+/// What a search ran with, to tell when the results are out of date.
+#[derive(Clone, PartialEq)]
+struct SearchQuery {
+    kind: NameKind,
+    text: String,
+    combat_levels: Option<(u16, u16)>,
+    /// Definitions decoded when it ran, results grow while more are.
+    decoded: usize,
+}
+
+/// Searches NPCs by name and combat level, items by name and examine text, and objects by their
+/// imported names. Picking an NPC or item shows its models.
+pub struct DefinitionSearchWindow {
+    kind: NameKind,
+    search_text: String,
+    filter_combat_level: bool,
+    combat_levels: (u16, u16),
+    query: Option<SearchQuery>,
+    results: Vec<u32>,
+    selected: Option<u32>,
+    /// The NPC or item picked last, taken by the app.
+    pub picked: Option<(NameKind, u32)>,
+}
+
+impl DefinitionSearchWindow {
+    const ROW_HEIGHT: f32 = 18.0;
+
+    pub fn new() -> Self {
+        Self {
+            kind: NameKind::Npc,
+            search_text: String::new(),
+            filter_combat_level: false,
+            combat_levels: (1, 126),
+            query: None,
+            results: Vec::new(),
+            selected: None,
+            picked: None,
+        }
+    }
+
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        definitions: &mut DefinitionScan,
+        names: &NameTables,
+    ) {
+        egui::Window::new("Definition Search")
+            .default_open(false)
+            .resizable(true)
+            .show(ctx, |ui| {
+                self.ui(ui, definitions, names);
+            });
+    }
+
+    /// The ids of the definitions of the searched kind matching the query.
+    fn search(query: &SearchQuery, definitions: &DefinitionScan, names: &NameTables) -> Vec<u32> {
+        let names = names.get(query.kind);
+        let contains = |text: &str| text.to_lowercase().contains(&query.text);
+        match query.kind {
+            NameKind::Npc => definitions
+                .npcs
+                .values()
+                .filter(|npc| match query.combat_levels {
+                    Some((min, max)) => npc
+                        .combat_level
+                        .is_some_and(|level| (min..=max).contains(&level)),
+                    None => true,
+                })
+                .filter(|npc| names.matches(npc.id, &query.text) || contains(&npc.name))
+                .map(|npc| npc.id)
+                .collect(),
+            NameKind::Item => definitions
+                .objs
+                .values()
+                .filter(|obj| {
+                    names.matches(obj.id, &query.text)
+                        || contains(&obj.name)
+                        || contains(&obj.examine)
+                })
+                .map(|obj| obj.id)
+                .collect(),
+            NameKind::Object | NameKind::Model => names
+                .ids()
+                .into_iter()
+                .filter(|&id| names.matches(id, &query.text))
+                .collect(),
+        }
+    }
+
+    /// The row of a result, with names from imported lists in place of the cache's.
+    fn label(&self, id: u32, definitions: &DefinitionScan, names: &NameTables) -> String {
+        let name = names.get(self.kind).get(id);
+        match self.kind {
+            NameKind::Npc => {
+                let npc = definitions.npcs.get(&id);
+                let name = name
+                    .or(npc.map(|npc| npc.name.as_str()))
+                    .unwrap_or_default();
+                match npc.and_then(|npc| npc.combat_level) {
+                    Some(level) => format!("{id} {name} (level {level})"),
+                    None => format!("{id} {name}"),
+                }
+            }
+            NameKind::Item => {
+                let obj = definitions.objs.get(&id);
+                let name = name
+                    .or(obj.map(|obj| obj.name.as_str()))
+                    .unwrap_or_default();
+                format!("{id} {name}")
+            }
+            NameKind::Object | NameKind::Model => format!("{id} {}", name.unwrap_or_default()),
+        }
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, definitions: &mut DefinitionScan, names: &NameTables) {
+        if !definitions.step() {
+            ui.ctx().request_repaint();
+        }
+
+        ui.horizontal(|ui| {
+            for kind in [NameKind::Npc, NameKind::Item, NameKind::Object] {
+                if ui
+                    .selectable_value(&mut self.kind, kind, kind.label())
+                    .clicked()
+                {
+                    self.selected = None;
+                }
+            }
+        });
+        let hint = match self.kind {
+            NameKind::Item => "Search id, name or examine...",
+            _ => "Search id or name...",
+        };
+        ui.add(egui::TextEdit::singleline(&mut self.search_text).hint_text(hint));
+        if self.kind == NameKind::Npc {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.filter_combat_level, "Combat level");
+                ui.add_enabled_ui(self.filter_combat_level, |ui| {
+                    let (min, max) = &mut self.combat_levels;
+                    ui.add(egui::DragValue::new(min).range(0..=*max));
+                    ui.label("to");
+                    ui.add(egui::DragValue::new(max).range(*min..=u16::MAX));
+                });
+            });
+        }
+        if self.kind == NameKind::Object {
+            ui.label("Object definitions aren't decoded, objects are searched by imported names.");
+        }
+        if let Some((decoded, total)) = definitions.progress() {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label(format!("Decoding definitions... {decoded}/{total}"));
+            });
+        } else if !definitions.has_definitions() && self.kind != NameKind::Object {
+            ui.label("The cache has no NPC or item definitions");
+        }
+
+        let query = SearchQuery {
+            kind: self.kind,
+            text: self.search_text.trim().to_lowercase(),
+            combat_levels: (self.kind == NameKind::Npc && self.filter_combat_level)
+                .then_some(self.combat_levels),
+            decoded: definitions.npcs.len() + definitions.objs.len(),
+        };
+        if self.query.as_ref() != Some(&query) {
+            self.results = Self::search(&query, definitions, names);
+            self.query = Some(query);
+        }
+
+        ui.label(format!("{} results", self.results.len()));
+        egui::ScrollArea::vertical()
+            .id_salt("definition_results")
+            .auto_shrink(false)
+            .show_rows(ui, Self::ROW_HEIGHT, self.results.len(), |ui, row_range| {
+                for &id in &self.results[row_range] {
+                    let label = self.label(id, definitions, names);
+                    let selected = self.selected == Some(id);
+                    let response = ui.selectable_label(selected, label);
+                    if self.kind == NameKind::Object {
+                        continue;
+                    }
+                    if response.on_hover_text("Show its models").clicked() {
+                        self.selected = Some(id);
+                        self.picked = Some((self.kind, id));
+                    }
+                }
+            });
+    }
+}
@@ -0,0 +1,117 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::Arc,
+};
+
+use crate::runetek5::{
+    js5::Js5,
+    model_users::{DefinitionKind, ModelUserIndex},
+    npc::{NpcArchive, NpcType},
+    obj::{ObjArchive, ObjType},
+};
+
+/// Every NPC and item definition of the cache, decoded a few groups per frame by the windows
+/// that need them. Nothing is fetched until a window first steps the scan.
+pub struct DefinitionScan {
+    npc_source: Option<(NpcArchive, Arc<Js5>)>,
+    obj_source: Option<(ObjArchive, Arc<Js5>)>,
+    /// Groups left to decode, NPCs first.
+    pending: VecDeque<(DefinitionKind, u32)>,
+    group_count: usize,
+    pub npcs: BTreeMap<u32, NpcType>,
+    pub objs: BTreeMap<u32, ObjType>,
+    pub model_users: ModelUserIndex,
+}
+
+impl DefinitionScan {
+    const GROUPS_PER_FRAME: usize = 16;
+
+    /// Reads definitions from the archives of their own if the cache has them, or else from the
+    /// config archive.
+    pub fn new(archives: &[(u8, Arc<Js5>)]) -> Self {
+        let archive = |archive_id: u8, group_id: u32| {
+            archives
+                .iter()
+                .find(|(id, _)| *id == archive_id)
+                .filter(|(_, js5)| js5.is_group_valid(group_id))
+                .map(|(_, js5)| js5.clone())
+        };
+        let npc_source = [NpcArchive::Npcs, NpcArchive::Config]
+            .into_iter()
+            .find_map(|source| Some((source, archive(source.archive_id(), source.location(0).0)?)));
+        let obj_source = [ObjArchive::Objs, ObjArchive::Config]
+            .into_iter()
+            .find_map(|source| Some((source, archive(source.archive_id(), source.location(0).0)?)));
+
+        let mut pending = VecDeque::new();
+        if let Some((source, js5)) = &npc_source {
+            pending.extend(
+                source
+                    .group_ids(js5)
+                    .into_iter()
+                    .map(|group_id| (DefinitionKind::Npc, group_id)),
+            );
+        }
+        if let Some((source, js5)) = &obj_source {
+            pending.extend(
+                source
+                    .group_ids(js5)
+                    .into_iter()
+                    .map(|group_id| (DefinitionKind::Item, group_id)),
+            );
+        }
+        Self {
+            npc_source,
+            obj_source,
+            group_count: pending.len(),
+            pending,
+            npcs: BTreeMap::new(),
+            objs: BTreeMap::new(),
+            model_users: ModelUserIndex::default(),
+        }
+    }
+
+    /// Whether the cache has NPC or item definitions at all.
+    pub fn has_definitions(&self) -> bool {
+        self.npc_source.is_some() || self.obj_source.is_some()
+    }
+
+    /// Groups decoded so far and in total, None once every group is.
+    pub fn progress(&self) -> Option<(usize, usize)> {
+        (!self.pending.is_empty())
+            .then(|| (self.group_count - self.pending.len(), self.group_count))
+    }
+
+    /// Decodes the next few groups, returns true once every group has been decoded.
+    pub fn step(&mut self) -> bool {
+        for _ in 0..Self::GROUPS_PER_FRAME {
+            let Some(&(kind, group_id)) = self.pending.front() else {
+                break;
+            };
+            // Stop at groups that are still being fetched.
+            match (kind, &self.npc_source, &self.obj_source) {
+                (DefinitionKind::Npc, Some((source, js5)), _) => {
+                    let Some(npcs) = source.load_group(js5, group_id) else {
+                        break;
+                    };
+                    for npc in npcs {
+                        self.model_users.add_npc(&npc);
+                        self.npcs.insert(npc.id, npc);
+                    }
+                }
+                (DefinitionKind::Item, _, Some((source, js5))) => {
+                    let Some(objs) = source.load_group(js5, group_id) else {
+                        break;
+                    };
+                    for obj in objs {
+                        self.model_users.add_obj(&obj);
+                        self.objs.insert(obj.id, obj);
+                    }
+                }
+                _ => {}
+            }
+            self.pending.pop_front();
+        }
+        self.pending.is_empty()
+    }
+}
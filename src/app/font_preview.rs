@@ -0,0 +1,223 @@
+use super::{
+    save::save_files,
+    text_label::{colour_to_argb, TextLabelRenderer},
+};
+use crate::{
+    export::{png, ExportFile},
+    runetek5::{
+        graphics::{font::Font, sprite::SpriteData},
+        js5::Js5,
+    },
+};
+
+/// Scans the sprite archive a few groups per frame for fonts, sprites with a glyph per byte.
+struct FontScan {
+    next_index: usize,
+    font_ids: Vec<u32>,
+}
+
+impl FontScan {
+    const GROUPS_PER_FRAME: usize = 256;
+
+    /// Returns true once every sprite group has been checked.
+    fn step(&mut self, sprite_js5: &Js5) -> bool {
+        let group_ids = &sprite_js5.index.group_ids;
+        let end = (self.next_index + Self::GROUPS_PER_FRAME).min(group_ids.len());
+        while self.next_index < end {
+            let group_id = group_ids[self.next_index];
+            match sprite_js5.try_get_file(group_id, 0) {
+                Ok(Some(data)) => {
+                    if SpriteData::peek_sprite_count(&data) == Some(Font::GLYPH_COUNT as u16) {
+                        self.font_ids.push(group_id);
+                    }
+                }
+                // Stop at groups that are still being fetched.
+                Ok(None) => break,
+                // Skip groups that never load, e.g. quarantined ones.
+                Err(_) => {}
+            }
+            self.next_index += 1;
+        }
+        self.next_index == group_ids.len()
+    }
+}
+
+/// Decodes font glyph sprites with their metrics and renders sample text.
+pub struct FontPreviewWindow {
+    scan: FontScan,
+    scan_done: bool,
+    selected_font: Option<u32>,
+    loaded: Option<TextLabelRenderer>,
+    sample_text: String,
+    colour: egui::Color32,
+    shadow: bool,
+    status: Option<String>,
+    /// A font picked for labelling the viewport, taken by the app.
+    pub label_font: Option<u32>,
+}
+
+impl FontPreviewWindow {
+    const SCALE: f32 = 2.0;
+
+    pub fn new() -> Self {
+        Self {
+            scan: FontScan {
+                next_index: 0,
+                font_ids: Vec::new(),
+            },
+            scan_done: false,
+            selected_font: None,
+            loaded: None,
+            sample_text: "The quick brown fox jumps over the lazy dog".to_owned(),
+            colour: egui::Color32::YELLOW,
+            shadow: true,
+            status: None,
+            label_font: None,
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, sprite_js5: &Js5, fontmetrics_js5: &Js5) {
+        egui::Window::new("Font Preview")
+            .default_open(false)
+            .resizable(true)
+            .show(ctx, |ui| {
+                // Only scan while the window is open.
+                if !self.scan_done {
+                    self.scan_done = self.scan.step(sprite_js5);
+                    ui.ctx().request_repaint();
+                }
+                self.ui(ui, sprite_js5, fontmetrics_js5);
+            });
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, sprite_js5: &Js5, fontmetrics_js5: &Js5) {
+        ui.horizontal(|ui| {
+            let selected_text = match self.selected_font {
+                Some(group_id) => format!("Font {group_id}"),
+                None => "Select a font".to_owned(),
+            };
+            egui::ComboBox::from_id_salt("font_select")
+                .selected_text(selected_text)
+                .show_ui(ui, |ui| {
+                    for &group_id in &self.scan.font_ids {
+                        ui.selectable_value(
+                            &mut self.selected_font,
+                            Some(group_id),
+                            format!("Font {group_id}"),
+                        );
+                    }
+                });
+            if !self.scan_done {
+                ui.spinner();
+                ui.label(format!(
+                    "Finding fonts... {}/{}",
+                    self.scan.next_index,
+                    sprite_js5.get_group_count()
+                ));
+            }
+        });
+        ui.horizontal(|ui| {
+            if ui.text_edit_singleline(&mut self.sample_text).changed() {
+                // Every edit renders a new texture, so don't keep the old ones around.
+                if let Some(loaded) = self.loaded.as_mut() {
+                    loaded.clear();
+                }
+            }
+            ui.color_edit_button_srgba(&mut self.colour);
+            ui.checkbox(&mut self.shadow, "Shadow");
+        });
+
+        let Some(group_id) = self.selected_font else {
+            return;
+        };
+        if !self
+            .loaded
+            .as_ref()
+            .is_some_and(|loaded| loaded.font_id == group_id)
+        {
+            if !sprite_js5.is_group_valid(group_id) {
+                return;
+            }
+            match TextLabelRenderer::load(group_id, sprite_js5, fontmetrics_js5) {
+                Some(loaded) => {
+                    self.loaded = Some(loaded);
+                    self.status = None;
+                }
+                None => {
+                    ui.spinner();
+                    return;
+                }
+            }
+        }
+        let loaded = self.loaded.as_mut().unwrap();
+        let font = loaded.font();
+        let metrics = &font.metrics;
+        ui.label(format!(
+            "Line height {}, ascent {}, {}{}",
+            font.line_height(),
+            metrics.ascent,
+            if loaded.has_metrics {
+                "metrics decoded"
+            } else {
+                "metrics approximated from glyphs"
+            },
+            if metrics.has_kerning {
+                ", has kerning"
+            } else {
+                ""
+            }
+        ));
+
+        ui.horizontal(|ui| {
+            if ui
+                .button("Use for viewport labels")
+                .on_hover_text("Label the main viewport with this font")
+                .clicked()
+            {
+                self.label_font = Some(group_id);
+            }
+            if ui.button("Export PNG").clicked() {
+                let shadow = self.shadow.then_some(TextLabelRenderer::SHADOW_ARGB);
+                let image = font.render(&self.sample_text, colour_to_argb(self.colour), shadow);
+                let rgba: Vec<u8> = image
+                    .pixels
+                    .iter()
+                    .flat_map(|&argb| {
+                        let [a, r, g, b] = argb.to_be_bytes();
+                        [r, g, b, a]
+                    })
+                    .collect();
+                let file_name: String = self
+                    .sample_text
+                    .chars()
+                    .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                    .take(32)
+                    .collect();
+                let file = ExportFile {
+                    path: format!("font_{group_id}_{file_name}.png"),
+                    data: png::encode_rgba(image.width as u32, image.height as u32, &rgba),
+                };
+                self.status = Some(match save_files("text", vec![file]) {
+                    Ok(location) => format!("Saved to {location}"),
+                    Err(err) => format!("Export failed: {err}"),
+                });
+            }
+        });
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+
+        let texture = loaded
+            .texture(ui.ctx(), &self.sample_text, self.colour, self.shadow)
+            .clone();
+        ui.separator();
+        egui::ScrollArea::horizontal().show(ui, |ui| {
+            egui::Frame::dark_canvas(ui.style()).show(ui, |ui| {
+                ui.add(egui::Image::new((
+                    texture.id(),
+                    texture.size_vec2() * Self::SCALE,
+                )));
+            });
+        });
+    }
+}
@@ -0,0 +1,189 @@
+use crate::runetek5::{
+    animation::{
+        frame::AnimFrame,
+        framemap::{FrameMap, TransformType},
+    },
+    js5::Js5,
+};
+
+/// Browses the raw frame (archive 0) and frame map (archive 1) data before animations are
+/// applied to models.
+pub struct FrameBrowserWindow {
+    search_text: String,
+    selected_group: Option<u32>,
+    selected_file: Option<u32>,
+}
+
+impl FrameBrowserWindow {
+    const ROW_HEIGHT: f32 = 18.0;
+
+    pub fn new() -> Self {
+        Self {
+            search_text: String::new(),
+            selected_group: None,
+            selected_file: None,
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, frame_js5: &Js5, framemap_js5: &Js5) {
+        egui::Window::new("Frame Browser")
+            .default_open(false)
+            .resizable(true)
+            .scroll(false)
+            .show(ctx, |ui| {
+                ui.horizontal_top(|ui| {
+                    ui.vertical(|ui| {
+                        ui.set_width(140.0);
+                        self.group_list(ui, frame_js5);
+                    });
+                    ui.separator();
+                    ui.vertical(|ui| {
+                        self.frame_details(ui, frame_js5, framemap_js5);
+                    });
+                });
+            });
+    }
+
+    fn group_list(&mut self, ui: &mut egui::Ui, frame_js5: &Js5) {
+        ui.add(egui::TextEdit::singleline(&mut self.search_text).hint_text("Search frame sets..."));
+        let search_text = self.search_text.trim();
+        let group_ids: Vec<u32> = frame_js5
+            .index
+            .group_ids
+            .iter()
+            .copied()
+            .filter(|id| search_text.is_empty() || id.to_string().contains(search_text))
+            .collect();
+        ui.label(format!("{} frame sets", group_ids.len()));
+        egui::ScrollArea::vertical()
+            .id_salt("frame_groups")
+            .auto_shrink(false)
+            .show_rows(ui, Self::ROW_HEIGHT, group_ids.len(), |ui, row_range| {
+                for &group_id in &group_ids[row_range] {
+                    let selected = self.selected_group == Some(group_id);
+                    if ui
+                        .selectable_label(selected, format!("Frame set {group_id}"))
+                        .clicked()
+                    {
+                        self.selected_group = Some(group_id);
+                        self.selected_file = None;
+                    }
+                }
+            });
+    }
+
+    fn frame_details(&mut self, ui: &mut egui::Ui, frame_js5: &Js5, framemap_js5: &Js5) {
+        let Some(group_id) = self.selected_group else {
+            ui.label("Select a frame set");
+            return;
+        };
+        let Some(file_ids) = frame_js5.get_file_ids(group_id) else {
+            return;
+        };
+
+        let selected_text = match self.selected_file {
+            Some(file_id) => format!("Frame {file_id}"),
+            None => "Select a frame".to_owned(),
+        };
+        egui::ComboBox::from_label(format!("{} frames", file_ids.len()))
+            .selected_text(selected_text)
+            .show_ui(ui, |ui| {
+                for &file_id in file_ids.iter() {
+                    ui.selectable_value(
+                        &mut self.selected_file,
+                        Some(file_id),
+                        format!("Frame {file_id}"),
+                    );
+                }
+            });
+
+        let Some(file_id) = self.selected_file else {
+            return;
+        };
+        let Some(data) = frame_js5.get_file(group_id, file_id) else {
+            ui.spinner();
+            return;
+        };
+        let Some(framemap_id) = AnimFrame::peek_framemap_id(&data) else {
+            ui.label(format!("Frame is too short ({} bytes)", data.len()));
+            return;
+        };
+        let Some(framemap_data) = framemap_js5.get_file(framemap_id as u32, 0) else {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label(format!("Loading frame map {framemap_id}"));
+            });
+            return;
+        };
+        let Some(framemap) = FrameMap::decode(&framemap_data) else {
+            ui.label(format!(
+                "Failed to decode frame map {framemap_id} ({} bytes)",
+                framemap_data.len()
+            ));
+            return;
+        };
+
+        match &framemap.skeleton {
+            Some(skeleton) => ui.label(format!(
+                "Frame map {framemap_id}: {} slots, skeleton of {} bones",
+                framemap.types.len(),
+                skeleton.bones.len()
+            )),
+            None => ui.label(format!(
+                "Frame map {framemap_id}: {} slots, {} trailing bytes",
+                framemap.types.len(),
+                framemap.trailing_bytes
+            )),
+        };
+
+        let Some(frame) = AnimFrame::decode(&data, &framemap) else {
+            ui.label(format!("Failed to decode frame ({} bytes)", data.len()));
+            return;
+        };
+        ui.label(format!(
+            "{} transforms, {} trailing bytes",
+            frame.transforms.len(),
+            frame.trailing_bytes
+        ));
+        ui.separator();
+
+        egui::ScrollArea::vertical()
+            .id_salt("frame_transforms")
+            .auto_shrink(false)
+            .show(ui, |ui| {
+                egui::Grid::new("frame_transform_grid")
+                    .num_columns(6)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for header in ["Slot", "Type", "Labels", "X", "Y", "Z"] {
+                            ui.strong(header);
+                        }
+                        ui.end_row();
+                        for transform in &frame.transforms {
+                            let slot = transform.slot as usize;
+                            let transform_type = framemap.types[slot];
+                            ui.label(slot.to_string());
+                            match transform_type {
+                                TransformType::Unknown(id) => ui.label(format!("Unknown ({id})")),
+                                _ => ui.label(transform_type.label()),
+                            };
+                            let labels: Vec<String> = framemap.labels[slot]
+                                .iter()
+                                .map(|label| label.to_string())
+                                .collect();
+                            ui.label(labels.join(", "));
+                            for (bit, value) in
+                                [(0x1, transform.x), (0x2, transform.y), (0x4, transform.z)]
+                            {
+                                if transform.flags & bit != 0 {
+                                    ui.monospace(value.to_string());
+                                } else {
+                                    ui.weak(value.to_string());
+                                }
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+    }
+}
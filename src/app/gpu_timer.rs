@@ -0,0 +1,200 @@
+//! GPU time of the viewport and model previews, measured with timer queries where the driver
+//! supports them (ARB_timer_query, or EXT_disjoint_timer_query on WebGL2 and GLES). Queries are
+//! read back a few frames late, once their results are available, so timing never stalls the GPU.
+
+use std::collections::VecDeque;
+
+use eframe::glow::{self, HasContext as _};
+
+/// What a timer query measures. Each scope can be timed several times a frame, the times of a
+/// frame are summed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GpuScope {
+    Viewport,
+    Previews,
+}
+
+impl GpuScope {
+    pub const ALL: [GpuScope; 2] = [GpuScope::Viewport, GpuScope::Previews];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            GpuScope::Viewport => "Viewport",
+            GpuScope::Previews => "Previews",
+        }
+    }
+}
+
+/// GPU time of a scope, averaged over recent frames.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GpuTiming {
+    pub ms: f64,
+    /// How many times the scope was drawn in the last frame it was drawn in.
+    pub passes: u32,
+}
+
+struct PendingQuery {
+    query: glow::Query,
+    scope: GpuScope,
+    frame: u64,
+}
+
+/// Sums of the frame whose query results are being read.
+struct FrameSums {
+    frame: u64,
+    ns: [u64; GpuScope::ALL.len()],
+    passes: [u32; GpuScope::ALL.len()],
+}
+
+pub struct GpuTimer {
+    supported: bool,
+    /// Whether the driver can report the timer was disturbed, see [`GpuTimer::GPU_DISJOINT`].
+    can_be_disjoint: bool,
+    frame: u64,
+    /// The scope currently being timed, queries of a target can't overlap.
+    active: Option<GpuScope>,
+    free: Vec<glow::Query>,
+    pending: VecDeque<PendingQuery>,
+    sums: Option<FrameSums>,
+    timings: [Option<GpuTiming>; GpuScope::ALL.len()],
+}
+
+impl GpuTimer {
+    /// `GPU_DISJOINT_EXT`, set when something like a clock change made recent timings invalid.
+    const GPU_DISJOINT: u32 = 0x8fbb;
+    /// Weight of the newest frame in the averaged timings.
+    pub const SMOOTHING: f64 = 0.1;
+    /// Queries left unread are dropped beyond this, e.g. when a driver never makes results
+    /// available.
+    const MAX_PENDING: usize = 1024;
+
+    pub fn new(gl: &glow::Context) -> Self {
+        let version = gl.version();
+        let extensions = gl.supported_extensions();
+        let supported = if cfg!(target_arch = "wasm32") {
+            extensions.contains("EXT_disjoint_timer_query_webgl2")
+        } else if version.is_embedded {
+            extensions.contains("GL_EXT_disjoint_timer_query")
+        } else {
+            (version.major, version.minor) >= (3, 3) || extensions.contains("GL_ARB_timer_query")
+        };
+        Self {
+            supported,
+            can_be_disjoint: version.is_embedded,
+            frame: 0,
+            active: None,
+            free: Vec::new(),
+            pending: VecDeque::new(),
+            sums: None,
+            timings: [None; GpuScope::ALL.len()],
+        }
+    }
+
+    pub fn is_supported(&self) -> bool {
+        self.supported
+    }
+
+    /// The averaged GPU time of a scope, None until a frame that drew it has been read back.
+    pub fn timing(&self, scope: GpuScope) -> Option<GpuTiming> {
+        self.timings[scope as usize]
+    }
+
+    /// Starts a new frame and reads back the results of earlier frames that are available.
+    pub fn begin_frame(&mut self, gl: &glow::Context) {
+        if !self.supported {
+            return;
+        }
+        self.frame += 1;
+        unsafe {
+            if self.can_be_disjoint && gl.get_parameter_i32(Self::GPU_DISJOINT) != 0 {
+                self.free
+                    .extend(self.pending.drain(..).map(|pending| pending.query));
+                self.sums = None;
+                return;
+            }
+            while let Some(pending) = self.pending.front() {
+                let available =
+                    gl.get_query_parameter_u32(pending.query, glow::QUERY_RESULT_AVAILABLE);
+                if available == 0 && self.pending.len() <= Self::MAX_PENDING {
+                    break;
+                }
+                let pending = self.pending.pop_front().unwrap();
+                if available != 0 {
+                    let ns = gl.get_query_parameter_u32(pending.query, glow::QUERY_RESULT);
+                    self.add_result(pending.frame, pending.scope, ns as u64);
+                }
+                self.free.push(pending.query);
+            }
+        }
+        // Queries are only made while painting, so all of a frame's results are in once nothing
+        // of it is left pending.
+        let complete = self.sums.as_ref().is_some_and(|sums| {
+            self.pending
+                .front()
+                .map_or(true, |pending| pending.frame > sums.frame)
+        });
+        if complete {
+            self.finish_frame();
+        }
+    }
+
+    /// Starts timing a scope, until [`GpuTimer::end`]. Does nothing while another scope is timed.
+    pub fn begin(&mut self, gl: &glow::Context, scope: GpuScope) {
+        if !self.supported || self.active.is_some() {
+            return;
+        }
+        let query = match self.free.pop() {
+            Some(query) => query,
+            None => match unsafe { gl.create_query() } {
+                Ok(query) => query,
+                Err(err) => {
+                    log::warn!("Failed to create a timer query, GPU timing is off: {err}");
+                    self.supported = false;
+                    return;
+                }
+            },
+        };
+        unsafe { gl.begin_query(glow::TIME_ELAPSED, query) };
+        self.pending.push_back(PendingQuery {
+            query,
+            scope,
+            frame: self.frame,
+        });
+        self.active = Some(scope);
+    }
+
+    pub fn end(&mut self, gl: &glow::Context) {
+        if self.active.take().is_some() {
+            unsafe { gl.end_query(glow::TIME_ELAPSED) };
+        }
+    }
+
+    fn add_result(&mut self, frame: u64, scope: GpuScope, ns: u64) {
+        if self.sums.as_ref().is_some_and(|sums| sums.frame != frame) {
+            self.finish_frame();
+        }
+        let sums = self.sums.get_or_insert(FrameSums {
+            frame,
+            ns: [0; GpuScope::ALL.len()],
+            passes: [0; GpuScope::ALL.len()],
+        });
+        sums.ns[scope as usize] += ns;
+        sums.passes[scope as usize] += 1;
+    }
+
+    fn finish_frame(&mut self) {
+        let Some(sums) = self.sums.take() else {
+            return;
+        };
+        for scope in GpuScope::ALL {
+            let passes = sums.passes[scope as usize];
+            if passes == 0 {
+                continue;
+            }
+            let ms = sums.ns[scope as usize] as f64 / 1_000_000.0;
+            let timing = self.timings[scope as usize].get_or_insert(GpuTiming { ms, passes });
+            timing.ms += (ms - timing.ms) * Self::SMOOTHING;
+            timing.passes = passes;
+        }
+    }
+}
@@ -0,0 +1,368 @@
+use std::sync::Arc;
+
+use bytes::Bytes;
+
+use super::{
+    hex_view::{to_hex, HexSection, HexView},
+    plugin::PluginRegistry,
+};
+use crate::runetek5::{
+    error::Runetek5Error,
+    js5::{decompress_with_key, xtea::XteaKey, Js5},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HexMode {
+    Packed,
+    Unpacked,
+}
+
+impl HexMode {
+    fn label(&self) -> &'static str {
+        match self {
+            HexMode::Packed => "Packed",
+            HexMode::Unpacked => "Unpacked",
+        }
+    }
+}
+
+/// An archive the inspector can browse.
+pub struct InspectorArchive {
+    pub name: &'static str,
+    pub archive_id: u8,
+    pub js5: Arc<Js5>,
+}
+
+struct LoadedGroup {
+    archive_index: usize,
+    group_id: u32,
+    mode: HexMode,
+    data: Bytes,
+    sections: Vec<HexSection>,
+}
+
+/// Hex inspector for any group of the loaded archives, before and after decompression.
+pub struct HexInspectorWindow {
+    archive_index: usize,
+    group_id: u32,
+    mode: HexMode,
+    loaded: Option<LoadedGroup>,
+    error: Option<String>,
+    search_text: String,
+    /// XTEA key to set on the group, as four integers.
+    key_text: String,
+    key_error: Option<String>,
+    hex_view: HexView,
+}
+
+impl HexInspectorWindow {
+    pub fn new() -> Self {
+        Self {
+            archive_index: 0,
+            group_id: 0,
+            mode: HexMode::Unpacked,
+            loaded: None,
+            error: None,
+            search_text: String::new(),
+            key_text: String::new(),
+            key_error: None,
+            hex_view: HexView::new(),
+        }
+    }
+
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        archives: &[InspectorArchive],
+        plugins: &PluginRegistry,
+    ) {
+        egui::Window::new("Hex Inspector")
+            .default_open(false)
+            .resizable(true)
+            .scroll(false)
+            .show(ctx, |ui| {
+                self.ui(ui, archives, plugins);
+            });
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, archives: &[InspectorArchive], plugins: &PluginRegistry) {
+        if archives.is_empty() {
+            return;
+        }
+        self.archive_index = self.archive_index.min(archives.len() - 1);
+        let archive = &archives[self.archive_index];
+
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_salt("hex_archive")
+                .selected_text(format!("{} ({})", archive.name, archive.archive_id))
+                .show_ui(ui, |ui| {
+                    for (index, archive) in archives.iter().enumerate() {
+                        ui.selectable_value(
+                            &mut self.archive_index,
+                            index,
+                            format!("{} ({})", archive.name, archive.archive_id),
+                        );
+                    }
+                });
+            ui.label("Group");
+            ui.add(
+                egui::DragValue::new(&mut self.group_id).range(0..=archive.js5.get_last_group_id()),
+            );
+            for mode in [HexMode::Packed, HexMode::Unpacked] {
+                ui.selectable_value(&mut self.mode, mode, mode.label());
+            }
+        });
+
+        let archive = &archives[self.archive_index];
+        if self.mode == HexMode::Unpacked {
+            self.key_ui(ui, archive);
+        }
+        let is_loaded = self.loaded.as_ref().is_some_and(|loaded| {
+            loaded.archive_index == self.archive_index
+                && loaded.group_id == self.group_id
+                && loaded.mode == self.mode
+        });
+        if !is_loaded {
+            self.load(archive, plugins);
+        }
+
+        if let Some(error) = &self.error {
+            ui.label(error);
+            return;
+        }
+        let Some(loaded) = &self.loaded else {
+            ui.spinner();
+            return;
+        };
+        let data = loaded.data.clone();
+        let sections = loaded.sections.clone();
+
+        ui.horizontal(|ui| {
+            ui.label(format!("{} bytes", data.len()));
+            if ui.button("Copy all").clicked() {
+                ui.ctx().copy_text(to_hex(&data));
+            }
+            if let Some(range) = &self.hex_view.highlight {
+                if ui.button("Copy match").clicked() {
+                    ui.ctx().copy_text(to_hex(&data[range.clone()]));
+                }
+            }
+        });
+
+        ui.horizontal(|ui| {
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.search_text)
+                    .hint_text("Search hex (0a ff) or text..."),
+            );
+            let submitted =
+                response.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter));
+            if ui.button("Find next").clicked() || submitted {
+                self.find_next(&data);
+            }
+        });
+
+        ui.separator();
+        egui::CollapsingHeader::new("Sections")
+            .default_open(false)
+            .show(ui, |ui| {
+                self.hex_view.legend_ui(ui, &data, &sections);
+            });
+        ui.separator();
+        self.hex_view.ui(ui, &data, &sections);
+    }
+
+    /// Sets the key encrypted groups like map squares are unpacked with.
+    fn key_ui(&mut self, ui: &mut egui::Ui, archive: &InspectorArchive) {
+        ui.horizontal(|ui| {
+            ui.label("XTEA key");
+            let hint = archive
+                .js5
+                .get_xtea_key(self.group_id)
+                .map_or_else(|| "k0, k1, k2, k3".to_owned(), |key| key.to_string());
+            let response = ui.add(egui::TextEdit::singleline(&mut self.key_text).hint_text(hint));
+            let submitted =
+                response.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter));
+            if ui.button("Set").clicked() || submitted {
+                match self.key_text.parse::<XteaKey>() {
+                    Ok(key) => {
+                        archive.js5.set_xtea_key(self.group_id, key);
+                        self.key_text.clear();
+                        self.key_error = None;
+                        self.loaded = None;
+                    }
+                    Err(err) => self.key_error = Some(err),
+                }
+            }
+            if let Some(error) = &self.key_error {
+                ui.label(error);
+            }
+        });
+    }
+
+    /// Parses the search text as hex byte pairs, falling back to the raw text.
+    fn search_pattern(&self) -> Vec<u8> {
+        let text = self.search_text.trim();
+        let hex: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+        let is_hex =
+            !hex.is_empty() && hex.len() % 2 == 0 && hex.chars().all(|c| c.is_ascii_hexdigit());
+        if is_hex {
+            (0..hex.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+                .collect()
+        } else {
+            text.as_bytes().to_vec()
+        }
+    }
+
+    fn find_next(&mut self, data: &[u8]) {
+        let pattern = self.search_pattern();
+        if pattern.is_empty() || pattern.len() > data.len() {
+            self.hex_view.highlight = None;
+            return;
+        }
+        let start = self
+            .hex_view
+            .highlight
+            .as_ref()
+            .map_or(0, |range| range.start + 1);
+        let find_from = |from: usize| {
+            data.windows(pattern.len())
+                .skip(from)
+                .position(|window| window == pattern.as_slice())
+                .map(|position| position + from)
+        };
+        // Wrap around to the start once the end is reached.
+        let found = find_from(start).or_else(|| find_from(0));
+        self.hex_view.highlight = found.map(|offset| offset..offset + pattern.len());
+        if let Some(offset) = found {
+            self.hex_view.scroll_to(offset);
+        }
+    }
+
+    fn load(&mut self, archive: &InspectorArchive, plugins: &PluginRegistry) {
+        self.loaded = None;
+        self.error = None;
+        self.hex_view.highlight = None;
+        if !archive.js5.is_group_valid(self.group_id) {
+            self.error = Some(format!("Group {} does not exist", self.group_id));
+            return;
+        }
+        let Some(packed) = archive.js5.get_packed_group(self.group_id) else {
+            return;
+        };
+        let (data, sections) = match self.mode {
+            HexMode::Packed => {
+                let sections = packed_sections(&packed);
+                (packed, sections)
+            }
+            HexMode::Unpacked => {
+                // Only gzip and bzip2 containers can be decompressed.
+                if packed.len() < 5 || packed[0] > 2 {
+                    self.error = Some(format!(
+                        "Unsupported compression type {}",
+                        packed.first().copied().unwrap_or(0)
+                    ));
+                    return;
+                }
+                let key = archive.js5.get_xtea_key(self.group_id);
+                let data = match decompress_with_key(&packed, key.as_ref()) {
+                    Ok(data) => data,
+                    Err(Runetek5Error::Decryption) => {
+                        self.error = Some(match key {
+                            Some(_) => "The group couldn't be decrypted with its key".to_owned(),
+                            None => "The group is encrypted, set its XTEA key".to_owned(),
+                        });
+                        return;
+                    }
+                    Err(err) => {
+                        self.error = Some(format!("The group couldn't be unpacked: {err}"));
+                        return;
+                    }
+                };
+                let data = Bytes::from(data);
+                let file_count = archive.js5.get_file_count(self.group_id) as usize;
+                let sections = if file_count > 1 {
+                    group_file_sections(&data, file_count)
+                } else {
+                    plugins
+                        .decoder(archive.archive_id)
+                        .map_or_else(Vec::new, |decoder| decoder.sections(&data))
+                };
+                (data, sections)
+            }
+        };
+        self.loaded = Some(LoadedGroup {
+            archive_index: self.archive_index,
+            group_id: self.group_id,
+            mode: self.mode,
+            data,
+            sections,
+        });
+    }
+}
+
+fn section(name: impl Into<String>, start: usize, end: usize) -> HexSection {
+    HexSection {
+        name: name.into(),
+        range: start..end,
+    }
+}
+
+/// Sections of a JS5 container: compression type, sizes, payload and the optional version.
+fn packed_sections(packed: &[u8]) -> Vec<HexSection> {
+    if packed.len() < 5 {
+        return Vec::new();
+    }
+    let compression_type = packed[0];
+    let compressed_size = u32::from_be_bytes([packed[1], packed[2], packed[3], packed[4]]) as usize;
+    let mut sections = vec![
+        section("Compression type", 0, 1),
+        section("Compressed size", 1, 5),
+    ];
+    let mut offset = 5;
+    if compression_type != 0 {
+        sections.push(section("Decompressed size", 5, 9));
+        offset = 9;
+    }
+    let payload_end = (offset + compressed_size).min(packed.len());
+    sections.push(section("Payload", offset, payload_end));
+    if payload_end + 2 <= packed.len() {
+        sections.push(section("Version", payload_end, payload_end + 2));
+    }
+    sections
+}
+
+/// Sections of the files in a decompressed multi-file group, with the trailing size table.
+fn group_file_sections(data: &[u8], file_count: usize) -> Vec<HexSection> {
+    let Some(&chunks) = data.last() else {
+        return Vec::new();
+    };
+    let chunks = chunks as usize;
+    let table_size = file_count * chunks * 4;
+    if data.len() < table_size + 1 {
+        return Vec::new();
+    }
+    let table_start = data.len() - 1 - table_size;
+    let mut sections = Vec::with_capacity(file_count * chunks + 2);
+    let mut offset = 0;
+    let mut table = &data[table_start..data.len() - 1];
+    for chunk in 0..chunks {
+        let mut file_size = 0i32;
+        for file in 0..file_count {
+            file_size += i32::from_be_bytes([table[0], table[1], table[2], table[3]]);
+            table = &table[4..];
+            let end = (offset + file_size.max(0) as usize).min(table_start);
+            let name = if chunks == 1 {
+                format!("File {file}")
+            } else {
+                format!("File {file} (chunk {chunk})")
+            };
+            sections.push(section(name, offset, end));
+            offset = end;
+        }
+    }
+    sections.push(section("Size table", table_start, data.len() - 1));
+    sections.push(section("Chunk count", data.len() - 1, data.len()));
+    sections
+}
@@ -0,0 +1,144 @@
+use std::ops::Range;
+
+/// A named byte range highlighted in a [`HexView`].
+#[derive(Debug, Clone)]
+pub struct HexSection {
+    pub name: String,
+    pub range: Range<usize>,
+}
+
+/// A virtualized hex dump with an offset column, ASCII column and coloured section overlay.
+pub struct HexView {
+    scroll_to_offset: Option<usize>,
+    /// Bytes drawn with a highlighted background, e.g. a search match.
+    pub highlight: Option<Range<usize>>,
+}
+
+impl HexView {
+    const BYTES_PER_ROW: usize = 16;
+
+    pub fn new() -> Self {
+        Self {
+            scroll_to_offset: None,
+            highlight: None,
+        }
+    }
+
+    /// Stable colour for a section name, so a section looks the same across files.
+    pub fn section_colour(name: &str) -> egui::Color32 {
+        if name == "Unaccounted" {
+            return egui::Color32::GRAY;
+        }
+        let hash = name.bytes().fold(0x811c9dc5u32, |hash, byte| {
+            (hash ^ byte as u32).wrapping_mul(0x01000193)
+        });
+        let hue = (hash % 360) as f32 / 360.0;
+        egui::ecolor::Hsva::new(hue, 0.6, 0.9, 1.0).into()
+    }
+
+    pub fn scroll_to(&mut self, offset: usize) {
+        self.scroll_to_offset = Some(offset);
+    }
+
+    /// Lists the sections with their ranges, clicking one scrolls the hex view to it.
+    pub fn legend_ui(&mut self, ui: &mut egui::Ui, data: &[u8], sections: &[HexSection]) {
+        egui::Grid::new("hex_legend").num_columns(3).show(ui, |ui| {
+            for section in sections {
+                let colour = Self::section_colour(&section.name);
+                let response = ui.add(
+                    egui::Label::new(egui::RichText::new(&section.name).color(colour))
+                        .sense(egui::Sense::click()),
+                );
+                if response.clicked() {
+                    self.scroll_to(section.range.start);
+                }
+                response.context_menu(|ui| {
+                    if ui.button("Copy bytes as hex").clicked() {
+                        if let Some(bytes) = data.get(section.range.clone()) {
+                            ui.ctx().copy_text(to_hex(bytes));
+                        }
+                        ui.close_menu();
+                    }
+                });
+                ui.monospace(format!(
+                    "{:06x}..{:06x}",
+                    section.range.start, section.range.end
+                ));
+                ui.label(format!("{} bytes", section.range.len()));
+                ui.end_row();
+            }
+        });
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, data: &[u8], sections: &[HexSection]) {
+        let font = egui::TextStyle::Monospace.resolve(ui.style());
+        let row_height = ui.fonts(|fonts| fonts.row_height(&font));
+        let total_rows = data.len().div_ceil(Self::BYTES_PER_ROW);
+
+        let mut scroll_area = egui::ScrollArea::vertical()
+            .id_salt("hex_view")
+            .auto_shrink(false);
+        if let Some(offset) = self.scroll_to_offset.take() {
+            let spacing = ui.spacing().item_spacing.y;
+            let row = offset / Self::BYTES_PER_ROW;
+            scroll_area = scroll_area.vertical_scroll_offset(row as f32 * (row_height + spacing));
+        }
+
+        let text_colour = ui.visuals().text_color();
+        let highlight = &self.highlight;
+        scroll_area.show_rows(ui, row_height, total_rows, |ui, row_range| {
+            for row in row_range {
+                let start = row * Self::BYTES_PER_ROW;
+                let end = (start + Self::BYTES_PER_ROW).min(data.len());
+                let mut job = egui::text::LayoutJob::default();
+                let format = |colour: egui::Color32| egui::TextFormat {
+                    font_id: font.clone(),
+                    color: colour,
+                    ..Default::default()
+                };
+                job.append(&format!("{start:06x}  "), 0.0, format(egui::Color32::GRAY));
+                for offset in start..start + Self::BYTES_PER_ROW {
+                    if offset < end {
+                        let colour = sections
+                            .iter()
+                            .find(|section| section.range.contains(&offset))
+                            .map_or(text_colour, |section| Self::section_colour(&section.name));
+                        let mut byte_format = format(colour);
+                        if highlight
+                            .as_ref()
+                            .is_some_and(|range| range.contains(&offset))
+                        {
+                            byte_format.color = egui::Color32::BLACK;
+                            byte_format.background = egui::Color32::YELLOW;
+                        }
+                        job.append(&format!("{:02x}", data[offset]), 0.0, byte_format);
+                        job.append(" ", 0.0, format(colour));
+                    } else {
+                        job.append("   ", 0.0, format(text_colour));
+                    }
+                }
+                let ascii: String = data[start..end]
+                    .iter()
+                    .map(|&byte| {
+                        if byte.is_ascii_graphic() || byte == b' ' {
+                            byte as char
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect();
+                job.append(&format!(" {ascii}"), 0.0, format(egui::Color32::GRAY));
+                ui.label(job);
+            }
+        });
+    }
+}
+
+/// Formats bytes as space separated hex pairs.
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,194 @@
+use crate::runetek5::js5::Js5;
+
+/// Which model groups a scan checks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanScope {
+    /// Groups evenly spread over the archive.
+    Sample,
+    All,
+}
+
+impl ScanScope {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ScanScope::Sample => "Sample",
+            ScanScope::All => "All groups",
+        }
+    }
+}
+
+/// Checks the group CRCs of the model archive a batch per frame, quarantining corrupt groups.
+struct IntegrityScan {
+    group_ids: Vec<u32>,
+    next_index: usize,
+    corrupt_count: usize,
+}
+
+impl IntegrityScan {
+    const GROUPS_PER_FRAME: usize = 256;
+
+    fn new(model_js5: &Js5, scope: ScanScope, sample_size: usize) -> Self {
+        let all = &model_js5.index.group_ids;
+        let group_ids = match scope {
+            ScanScope::All => all.clone(),
+            ScanScope::Sample => {
+                let step = (all.len() / sample_size.max(1)).max(1);
+                all.iter().step_by(step).copied().collect()
+            }
+        };
+        Self {
+            group_ids,
+            next_index: 0,
+            corrupt_count: 0,
+        }
+    }
+
+    /// Returns true once every group has been checked.
+    fn step(&mut self, model_js5: &Js5) -> bool {
+        let end = (self.next_index + Self::GROUPS_PER_FRAME).min(self.group_ids.len());
+        while self.next_index < end {
+            // Stop at groups that are still being fetched.
+            let Some(valid) = model_js5.verify_group(self.group_ids[self.next_index]) else {
+                break;
+            };
+            if !valid {
+                self.corrupt_count += 1;
+            }
+            self.next_index += 1;
+        }
+        self.next_index == self.group_ids.len()
+    }
+}
+
+/// Verifies model groups against the CRCs in the archive index. Groups failing the check are
+/// quarantined by [`Js5`], get an error badge in the selector and are never decoded.
+pub struct IntegrityWindow {
+    scope: ScanScope,
+    sample_size: usize,
+    scan: Option<IntegrityScan>,
+    status: Option<String>,
+    /// Groups quarantined so far.
+    corrupt_group_ids: Vec<u32>,
+    /// Set when the corrupt groups should be listed in the selector.
+    pub found_models: Option<Vec<usize>>,
+}
+
+impl IntegrityWindow {
+    pub fn new() -> Self {
+        Self {
+            scope: ScanScope::Sample,
+            sample_size: 1000,
+            scan: None,
+            status: None,
+            corrupt_group_ids: Vec::new(),
+            found_models: None,
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, model_js5: &Js5) {
+        if let Some(scan) = self.scan.as_mut() {
+            if scan.step(model_js5) {
+                let scan = self.scan.take().unwrap();
+                self.status = Some(format!(
+                    "Checked {} groups, {} corrupt",
+                    scan.group_ids.len(),
+                    scan.corrupt_count
+                ));
+            }
+        }
+        // Groups are also quarantined when they fail the check on fetch.
+        if model_js5.corrupt_group_count() as usize != self.corrupt_group_ids.len() {
+            self.corrupt_group_ids = model_js5.corrupt_group_ids();
+        }
+        if self.scan.is_some() {
+            ctx.request_repaint();
+        }
+
+        egui::Window::new("Integrity Check")
+            .default_open(false)
+            .show(ctx, |ui| {
+                self.ui(ui, model_js5);
+            });
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, model_js5: &Js5) {
+        let mut verify = model_js5.verifies_groups();
+        if ui
+            .checkbox(&mut verify, "Verify groups as they are fetched")
+            .changed()
+        {
+            model_js5.set_verify_groups(verify);
+        }
+
+        ui.separator();
+
+        ui.add_enabled_ui(self.scan.is_none(), |ui| {
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_salt("integrity_scope")
+                    .selected_text(self.scope.label())
+                    .show_ui(ui, |ui| {
+                        for scope in [ScanScope::Sample, ScanScope::All] {
+                            ui.selectable_value(&mut self.scope, scope, scope.label());
+                        }
+                    });
+                if self.scope == ScanScope::Sample {
+                    ui.add(
+                        egui::DragValue::new(&mut self.sample_size)
+                            .range(1..=model_js5.index.group_ids.len().max(1))
+                            .suffix(" groups"),
+                    );
+                }
+            });
+        });
+
+        if let Some(scan) = &self.scan {
+            let mut cancel = false;
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label(format!(
+                    "Verifying... {}/{}, {} corrupt",
+                    scan.next_index,
+                    scan.group_ids.len(),
+                    scan.corrupt_count
+                ));
+                cancel = ui.button("Cancel").clicked();
+            });
+            if cancel {
+                self.scan = None;
+            }
+        } else if ui
+            .button("Verify")
+            .on_hover_text("Check the model groups against the CRCs in the index")
+            .clicked()
+        {
+            self.status = None;
+            self.scan = Some(IntegrityScan::new(model_js5, self.scope, self.sample_size));
+        }
+
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+
+        if self.corrupt_group_ids.is_empty() {
+            return;
+        }
+        ui.separator();
+        ui.label(format!(
+            "{} corrupt groups: {}",
+            self.corrupt_group_ids.len(),
+            self.corrupt_group_ids
+                .iter()
+                .map(|id| id.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+        if ui.button("Show in selector").clicked() {
+            self.found_models = Some(
+                self.corrupt_group_ids
+                    .iter()
+                    .map(|&id| id as usize)
+                    .collect(),
+            );
+        }
+    }
+}
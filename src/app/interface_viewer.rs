@@ -0,0 +1,537 @@
+use std::collections::{hash_map::Entry, HashMap};
+
+use super::{
+    model_image::{ModelCamera, ModelImageRenderer},
+    plugin::{PluginContext, PluginWindow},
+    text_label::TextLabelRenderer,
+};
+use crate::runetek5::{
+    graphics::sprite::SpriteData,
+    interface::{layout, load_interface, Component, ComponentType},
+    js5::Js5,
+};
+
+const INTERFACE_ARCHIVE_ID: u8 = 3;
+const MODEL_ARCHIVE_ID: u8 = 7;
+const FONTMETRICS_ARCHIVE_ID: u8 = 13;
+
+/// Sizes interfaces are laid out in, as the client gives them to top level components.
+const ROOT_SIZES: [(&str, i32, i32); 4] = [
+    ("Game frame", 765, 503),
+    ("Main area", 512, 334),
+    ("Side panel", 190, 261),
+    ("Chatbox", 519, 142),
+];
+
+struct LoadedInterface {
+    interface_id: u32,
+    components: Vec<Component>,
+    /// Model renders by component index, None if the model couldn't be rendered.
+    model_textures: HashMap<usize, Option<egui::TextureHandle>>,
+}
+
+/// Draws the static layout of an interface: rectangles, text, graphics, models and lines as
+/// placed by their layout modes, without running any of the interface's scripts.
+pub struct InterfaceViewerWindow {
+    interface_id: u32,
+    root_size: usize,
+    scale: f32,
+    show_outlines: bool,
+    loaded: Option<LoadedInterface>,
+    selected: Option<usize>,
+    /// Sprite textures by id, None for sprites that failed to decode.
+    sprites: HashMap<u32, Option<egui::TextureHandle>>,
+    /// Fonts by id, None for sprites that aren't fonts.
+    fonts: HashMap<u32, Option<TextLabelRenderer>>,
+    model_renderer: ModelImageRenderer,
+}
+
+impl InterfaceViewerWindow {
+    pub fn new() -> Self {
+        Self {
+            interface_id: 0,
+            root_size: 0,
+            scale: 1.0,
+            show_outlines: false,
+            loaded: None,
+            selected: None,
+            sprites: HashMap::new(),
+            fonts: HashMap::new(),
+            model_renderer: ModelImageRenderer::new(0.8),
+        }
+    }
+
+    fn controls_ui(&mut self, ui: &mut egui::Ui, interface_js5: &Js5) {
+        ui.horizontal(|ui| {
+            ui.label("Interface");
+            ui.add(
+                egui::DragValue::new(&mut self.interface_id)
+                    .range(0..=interface_js5.get_last_group_id()),
+            );
+            let (name, width, height) = ROOT_SIZES[self.root_size];
+            egui::ComboBox::from_id_salt("interface_root_size")
+                .selected_text(format!("{name} {width}x{height}"))
+                .show_ui(ui, |ui| {
+                    for (index, (name, width, height)) in ROOT_SIZES.iter().enumerate() {
+                        ui.selectable_value(
+                            &mut self.root_size,
+                            index,
+                            format!("{name} {width}x{height}"),
+                        );
+                    }
+                });
+            ui.add(
+                egui::Slider::new(&mut self.scale, 0.5..=3.0)
+                    .step_by(0.25)
+                    .text("Scale"),
+            );
+            ui.checkbox(&mut self.show_outlines, "Outlines");
+        });
+    }
+
+    fn load(&mut self, interface_js5: &Js5) {
+        let is_loaded = self
+            .loaded
+            .as_ref()
+            .is_some_and(|loaded| loaded.interface_id == self.interface_id);
+        if is_loaded {
+            return;
+        }
+        if let Some(components) = load_interface(interface_js5, self.interface_id) {
+            self.selected = None;
+            self.loaded = Some(LoadedInterface {
+                interface_id: self.interface_id,
+                components,
+                model_textures: HashMap::new(),
+            });
+        }
+    }
+
+    fn sprite_texture(
+        &mut self,
+        ctx: &egui::Context,
+        sprite_js5: &Js5,
+        sprite_id: u32,
+    ) -> Option<egui::TextureHandle> {
+        if let Some(texture) = self.sprites.get(&sprite_id) {
+            return texture.clone();
+        }
+        if !sprite_js5.is_group_valid(sprite_id) {
+            self.sprites.insert(sprite_id, None);
+            return None;
+        }
+        let data = sprite_js5.get_file(sprite_id, 0)?;
+        let texture = SpriteData::peek_sprite_count(&data)
+            .filter(|&count| count > 0)
+            .and_then(|_| SpriteData::decode_into_pix8(&data).ok())
+            .map(|mut frame| {
+                frame.normalize();
+                let pixels = frame
+                    .pixels
+                    .iter()
+                    .map(|&index| match index {
+                        0 => egui::Color32::TRANSPARENT,
+                        index => {
+                            let rgb = frame.palette[index as usize];
+                            egui::Color32::from_rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+                        }
+                    })
+                    .collect();
+                let image = egui::ColorImage {
+                    size: [frame.width as usize, frame.height as usize],
+                    pixels,
+                };
+                ctx.load_texture(
+                    format!("interface_sprite_{sprite_id}"),
+                    image,
+                    egui::TextureOptions::NEAREST,
+                )
+            });
+        self.sprites.insert(sprite_id, texture.clone());
+        texture
+    }
+
+    /// The font, loading it once its groups are fetched.
+    fn font(&mut self, font_id: u32, sprite_js5: &Js5, fontmetrics_js5: Option<&Js5>) -> bool {
+        if let Some(font) = self.fonts.get(&font_id) {
+            return font.is_some();
+        }
+        let Some(fontmetrics_js5) = fontmetrics_js5 else {
+            return false;
+        };
+        let font = TextLabelRenderer::load(font_id, sprite_js5, fontmetrics_js5);
+        let fetched = sprite_js5.is_group_ready(font_id)
+            && (!fontmetrics_js5.is_group_valid(font_id)
+                || fontmetrics_js5.is_group_ready(font_id));
+        let loaded = font.is_some();
+        if loaded || fetched || !sprite_js5.is_group_valid(font_id) {
+            self.fonts.insert(font_id, font);
+        }
+        loaded
+    }
+
+    /// Renders a model component the way the client does, looking down the z axis from the
+    /// component's centre with the model pitched, turned and zoomed away.
+    fn render_model(
+        &mut self,
+        ctx: &egui::Context,
+        plugin_ctx: &PluginContext<'_>,
+        component: &Component,
+        width: i32,
+        height: i32,
+    ) -> Option<Option<egui::TextureHandle>> {
+        let model_js5 = plugin_ctx.archive(MODEL_ARCHIVE_ID)?;
+        let Some(model_id) = component.model else {
+            return Some(None);
+        };
+        if width <= 0
+            || height <= 0
+            || !model_js5.is_group_valid(model_id)
+            || model_js5.is_group_corrupt(model_id)
+        {
+            return Some(None);
+        }
+        let model =
+            ModelImageRenderer::load_model(model_js5, plugin_ctx.texture_provider, model_id)?;
+        let camera = ModelCamera {
+            zoom: component.model_zoom,
+            x_angle: component.model_x_angle,
+            y_angle: component.model_y_angle,
+            z_angle: component.model_z_angle,
+            x_offset: component.model_x_offset,
+            y_offset: component.model_y_offset,
+        };
+        self.model_renderer.set_brightness(plugin_ctx.brightness);
+        Some(Some(self.model_renderer.render(
+            ctx,
+            format!("interface_model_{}", component.id),
+            plugin_ctx.texture_provider,
+            &model,
+            &camera,
+            [width as usize, height as usize],
+        )))
+    }
+
+    fn canvas_ui(&mut self, ui: &mut egui::Ui, plugin_ctx: &PluginContext<'_>) {
+        let Some(mut loaded) = self
+            .loaded
+            .take_if(|loaded| loaded.interface_id == self.interface_id)
+        else {
+            ui.spinner();
+            return;
+        };
+        let (_, root_width, root_height) = ROOT_SIZES[self.root_size];
+        let scale = self.scale;
+        let (response, painter) = ui.allocate_painter(
+            egui::vec2(root_width as f32, root_height as f32) * scale,
+            egui::Sense::click(),
+        );
+        let origin = response.rect.min;
+        painter.rect_filled(response.rect, 0.0, egui::Color32::BLACK);
+        let to_rect = |x: i32, y: i32, width: i32, height: i32| {
+            egui::Rect::from_min_size(
+                origin + egui::vec2(x as f32, y as f32) * scale,
+                egui::vec2(width as f32, height as f32) * scale,
+            )
+        };
+
+        let bounds = layout(&loaded.components, root_width, root_height);
+        let sprite_js5 = plugin_ctx.texture_provider.sprite_js5.clone();
+        let fontmetrics_js5 = plugin_ctx.archive(FONTMETRICS_ARCHIVE_ID).cloned();
+        for &(index, component_bounds) in &bounds {
+            let component = &loaded.components[index];
+            let (min_x, min_y, max_x, max_y) = component_bounds.clip;
+            let clip = to_rect(min_x, min_y, max_x - min_x, max_y - min_y).intersect(response.rect);
+            let painter = painter.with_clip_rect(clip);
+            let rect = to_rect(
+                component_bounds.x,
+                component_bounds.y,
+                component_bounds.width,
+                component_bounds.height,
+            );
+            let alpha = 255 - component.transparency;
+            let colour = egui::Color32::from_rgba_unmultiplied(
+                (component.colour >> 16) as u8,
+                (component.colour >> 8) as u8,
+                component.colour as u8,
+                alpha,
+            );
+            match component.component_type {
+                ComponentType::Rectangle if component.filled => {
+                    painter.rect_filled(rect, 0.0, colour);
+                }
+                ComponentType::Rectangle => {
+                    painter.rect_stroke(
+                        rect,
+                        0.0,
+                        egui::Stroke::new(scale, colour),
+                        egui::StrokeKind::Inside,
+                    );
+                }
+                ComponentType::Line => {
+                    let (start, end) = if component.line_direction {
+                        (rect.left_bottom(), rect.right_top())
+                    } else {
+                        (rect.left_top(), rect.right_bottom())
+                    };
+                    let width = component.line_width.max(1) as f32 * scale;
+                    painter.line_segment([start, end], egui::Stroke::new(width, colour));
+                }
+                ComponentType::Graphic => {
+                    let Some(texture) = component
+                        .graphic
+                        .and_then(|id| self.sprite_texture(ui.ctx(), &sprite_js5, id))
+                    else {
+                        continue;
+                    };
+                    let tint = egui::Color32::from_white_alpha(alpha);
+                    let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+                    if component.graphic_tiling {
+                        let [sprite_width, sprite_height] = texture.size();
+                        let (sprite_width, sprite_height) =
+                            (sprite_width.max(1) as i32, sprite_height.max(1) as i32);
+                        let painter = painter.with_clip_rect(rect.intersect(clip));
+                        for tile_y in (0..component_bounds.height).step_by(sprite_height as usize) {
+                            for tile_x in (0..component_bounds.width).step_by(sprite_width as usize)
+                            {
+                                let tile = to_rect(
+                                    component_bounds.x + tile_x,
+                                    component_bounds.y + tile_y,
+                                    sprite_width,
+                                    sprite_height,
+                                );
+                                painter.image(texture.id(), tile, uv, tint);
+                            }
+                        }
+                    } else {
+                        painter.image(texture.id(), rect, uv, tint);
+                    }
+                }
+                ComponentType::Text => {
+                    let Some(font_id) = component.font else {
+                        continue;
+                    };
+                    if self.font(font_id, &sprite_js5, fontmetrics_js5.as_deref()) {
+                        let font = self.fonts.get_mut(&font_id).unwrap().as_mut().unwrap();
+                        draw_text(ui.ctx(), &painter, font, component, rect, scale);
+                    }
+                }
+                ComponentType::Model => {
+                    if let Entry::Vacant(entry) = loaded.model_textures.entry(index) {
+                        if let Some(texture) = self.render_model(
+                            ui.ctx(),
+                            plugin_ctx,
+                            component,
+                            component_bounds.width,
+                            component_bounds.height,
+                        ) {
+                            entry.insert(texture);
+                        }
+                    }
+                    if let Some(Some(texture)) = loaded.model_textures.get(&index) {
+                        let uv =
+                            egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+                        painter.image(texture.id(), rect, uv, egui::Color32::WHITE);
+                    }
+                }
+                _ => {}
+            }
+            if self.show_outlines {
+                painter.rect_stroke(
+                    rect,
+                    0.0,
+                    egui::Stroke::new(1.0, egui::Color32::from_white_alpha(64)),
+                    egui::StrokeKind::Inside,
+                );
+            }
+        }
+
+        // Pick the topmost component under the pointer.
+        let hovered = response.hover_pos().and_then(|pos| {
+            bounds.iter().rev().find(|(_, bounds)| {
+                to_rect(bounds.x, bounds.y, bounds.width, bounds.height).contains(pos)
+            })
+        });
+        if let Some(&(index, _)) = hovered {
+            let component = &loaded.components[index];
+            if response.clicked() {
+                self.selected = Some(index);
+            }
+            response.on_hover_text(format!(
+                "{} {}:{}",
+                component.component_type.label(),
+                component.id >> 16,
+                component.id & 0xffff
+            ));
+        }
+        if let Some((_, bounds)) = self
+            .selected
+            .and_then(|selected| bounds.iter().find(|(index, _)| *index == selected))
+        {
+            painter.rect_stroke(
+                to_rect(bounds.x, bounds.y, bounds.width, bounds.height),
+                0.0,
+                egui::Stroke::new(1.0, egui::Color32::YELLOW),
+                egui::StrokeKind::Outside,
+            );
+        }
+        self.loaded = Some(loaded);
+    }
+
+    fn details_ui(&self, ui: &mut egui::Ui, plugin_ctx: &PluginContext<'_>) {
+        let (Some(loaded), Some(selected)) = (&self.loaded, self.selected) else {
+            ui.label("Click a component to select it");
+            return;
+        };
+        let component = &loaded.components[selected];
+        egui::Grid::new("interface_component").show(ui, |ui| {
+            ui.label("Component");
+            ui.label(format!(
+                "{}:{} ({})",
+                component.id >> 16,
+                component.id & 0xffff,
+                if component.if3 {
+                    "new format"
+                } else {
+                    "old format"
+                }
+            ));
+            ui.end_row();
+            ui.label("Type");
+            ui.label(component.component_type.label());
+            ui.end_row();
+            ui.label("Position");
+            ui.label(format!(
+                "{}, {} ({}, {})",
+                component.x, component.y, component.modes.x, component.modes.y
+            ));
+            ui.end_row();
+            ui.label("Size");
+            ui.label(format!(
+                "{}x{} ({}, {})",
+                component.width, component.height, component.modes.width, component.modes.height
+            ));
+            ui.end_row();
+            if let Some(layer) = component.layer {
+                ui.label("Layer");
+                ui.label(format!("{}:{}", layer >> 16, layer & 0xffff));
+                ui.end_row();
+            }
+            if !component.text.is_empty() {
+                ui.label("Text");
+                ui.label(&component.text);
+                ui.end_row();
+            }
+            if let Some(graphic) = component.graphic {
+                ui.label("Graphic");
+                ui.label(graphic.to_string());
+                ui.end_row();
+            }
+            if let Some(font) = component.font {
+                ui.label("Font");
+                ui.label(font.to_string());
+                ui.end_row();
+            }
+        });
+        if let Some(model_id) = component.model {
+            if ui.button(format!("Show model {model_id}")).clicked() {
+                plugin_ctx.show_model(model_id);
+            }
+        }
+    }
+}
+
+/// Draws the component's text, split at `<br>` and with other tags left out, aligned in the
+/// component like the client does.
+fn draw_text(
+    ctx: &egui::Context,
+    painter: &egui::Painter,
+    font: &mut TextLabelRenderer,
+    component: &Component,
+    rect: egui::Rect,
+    scale: f32,
+) {
+    let lines: Vec<String> = component.text.split("<br>").map(strip_tags).collect();
+    let line_height = match component.line_height {
+        0 => font.font().line_height(),
+        line_height => line_height as i32,
+    } as f32
+        * scale;
+    let text_height = line_height * lines.len() as f32;
+    let top = match component.v_align {
+        1 => rect.center().y - text_height / 2.0,
+        2 => rect.bottom() - text_height,
+        _ => rect.top(),
+    };
+    let colour = egui::Color32::from_rgb(
+        (component.colour >> 16) as u8,
+        (component.colour >> 8) as u8,
+        component.colour as u8,
+    );
+    for (i, line) in lines.iter().enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+        let texture = font.texture(ctx, line, colour, component.text_shadowed);
+        let size = texture.size_vec2() * scale;
+        let left = match component.h_align {
+            1 => rect.center().x - size.x / 2.0,
+            2 => rect.right() - size.x,
+            _ => rect.left(),
+        };
+        let line_rect =
+            egui::Rect::from_min_size(egui::pos2(left, top + line_height * i as f32), size);
+        painter.image(
+            texture.id(),
+            line_rect,
+            egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+            egui::Color32::WHITE,
+        );
+    }
+}
+
+/// Leaves out tags like `<col=ff0000>`, which the fonts would draw as text.
+fn strip_tags(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            c if !in_tag => stripped.push(c),
+            _ => {}
+        }
+    }
+    stripped
+}
+
+impl PluginWindow for InterfaceViewerWindow {
+    fn title(&self) -> &'static str {
+        "Interfaces"
+    }
+
+    fn archive_ids(&self) -> Vec<u8> {
+        vec![INTERFACE_ARCHIVE_ID]
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, plugin_ctx: &PluginContext<'_>) {
+        let Some(interface_js5) = plugin_ctx.archive(INTERFACE_ARCHIVE_ID).cloned() else {
+            ui.label("This cache has no interface archive");
+            return;
+        };
+        self.controls_ui(ui, &interface_js5);
+        self.load(&interface_js5);
+        if !interface_js5.is_group_valid(self.interface_id) {
+            ui.label(format!("Interface {} does not exist", self.interface_id));
+            return;
+        }
+        ui.separator();
+        egui::ScrollArea::both()
+            .max_height(ui.available_height() - 140.0)
+            .show(ui, |ui| {
+                self.canvas_ui(ui, plugin_ctx);
+            });
+        ui.separator();
+        self.details_ui(ui, plugin_ctx);
+    }
+}
@@ -0,0 +1,516 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{
+    model_image::{ModelCamera, ModelImageRenderer},
+    plugin::{NameKind, NameTable, PluginContext, PluginWindow},
+    save::save_files,
+};
+use crate::{
+    export::{png, ExportFile},
+    runetek5::{
+        graphics::model::{ModelFlags, ModelLit, ModelUnlit},
+        js5::Js5,
+        obj::{ObjArchive, ObjType},
+    },
+};
+
+const MODEL_ARCHIVE_ID: u8 = 7;
+
+/// The size of an inventory slot, which icons are rendered to fill.
+const ICON_SIZE: [usize; 2] = [36, 32];
+/// The colour of the shadow icons cast to their bottom right.
+const ICON_SHADOW_RGB: u32 = 0x302020;
+
+/// An item's ground model with its recolours, retextures and resize applied, lit like the client
+/// lights item models. None while the model is being fetched or if it can't be decoded.
+fn load_obj_model(
+    model_js5: &Js5,
+    plugin_ctx: &PluginContext<'_>,
+    obj: &ObjType,
+    model_id: u32,
+) -> Option<ModelLit> {
+    if !model_js5.is_group_valid(model_id) || model_js5.is_group_corrupt(model_id) {
+        return None;
+    }
+    let mut model_unlit = ModelUnlit::from_js5(model_js5, model_id, 0)?;
+    if model_unlit.version < 13 {
+        model_unlit.scale_log2(2);
+    }
+    for &(old_colour, new_colour) in &obj.recolours {
+        model_unlit.recolour(old_colour, new_colour);
+    }
+    let (ambient, contrast) = obj.lighting();
+    let mut model = ModelLit::from_unlit(
+        plugin_ctx.texture_provider,
+        &model_unlit,
+        ModelFlags::empty(),
+        ambient,
+        contrast,
+    );
+    for &(old_texture, new_texture) in &obj.retextures {
+        model.replace_material(old_texture as i16, new_texture as i16);
+    }
+    let [x, y, z] = obj.resize.map(i32::from);
+    model.scale(x, y, z);
+    Some(model)
+}
+
+/// Adds the black outline and the shadow the client draws around item icons in inventories.
+fn outline_icon(image: &mut egui::ColorImage) {
+    let [width, height] = image.size;
+    let opaque: Vec<bool> = image.pixels.iter().map(|pixel| pixel.a() > 0).collect();
+    let is_opaque = |x: isize, y: isize| {
+        x >= 0
+            && y >= 0
+            && (x as usize) < width
+            && (y as usize) < height
+            && opaque[x as usize + y as usize * width]
+    };
+    for y in 0..height as isize {
+        for x in 0..width as isize {
+            if is_opaque(x, y) {
+                continue;
+            }
+            let pixel = &mut image.pixels[x as usize + y as usize * width];
+            if is_opaque(x - 1, y)
+                || is_opaque(x + 1, y)
+                || is_opaque(x, y - 1)
+                || is_opaque(x, y + 1)
+            {
+                *pixel = egui::Color32::BLACK;
+            } else if is_opaque(x - 1, y - 1) {
+                let [_, r, g, b] = ICON_SHADOW_RGB.to_be_bytes();
+                *pixel = egui::Color32::from_rgb(r, g, b);
+            }
+        }
+    }
+}
+
+struct ItemIcon {
+    obj_id: u32,
+    outlined: bool,
+    image: egui::ColorImage,
+    texture: egui::TextureHandle,
+}
+
+/// Lists item definitions with their details, and renders the inventory icon of the selected
+/// item from its ground model the way the client rasterizes it.
+pub struct ItemBrowserWindow {
+    search_text: String,
+    selected: Option<u32>,
+    /// Decoded items by id, filled in as their groups are fetched.
+    objs: HashMap<u32, ObjType>,
+    /// Groups whose items have all been decoded.
+    loaded_groups: HashSet<u32>,
+    /// Set while fetching every group for the item names.
+    loading_names: bool,
+    outline: bool,
+    icon_scale: f32,
+    icon: Option<ItemIcon>,
+    model_renderer: ModelImageRenderer,
+    status: Option<String>,
+}
+
+impl ItemBrowserWindow {
+    const ROW_HEIGHT: f32 = 18.0;
+    /// Groups decoded per frame while loading names, to keep the UI responsive.
+    const GROUPS_PER_FRAME: usize = 8;
+
+    pub fn new() -> Self {
+        Self {
+            search_text: String::new(),
+            selected: None,
+            objs: HashMap::new(),
+            loaded_groups: HashSet::new(),
+            loading_names: false,
+            outline: true,
+            icon_scale: 4.0,
+            icon: None,
+            model_renderer: ModelImageRenderer::new(0.8),
+            status: None,
+        }
+    }
+
+    /// Where the cache keeps its items, the obj archive if it has one.
+    fn source(plugin_ctx: &PluginContext<'_>) -> Option<(ObjArchive, std::sync::Arc<Js5>)> {
+        [ObjArchive::Objs, ObjArchive::Config]
+            .into_iter()
+            .find_map(|source| {
+                let js5 = plugin_ctx.archive(source.archive_id())?;
+                let (group_id, _) = source.location(0);
+                js5.is_group_valid(group_id).then(|| (source, js5.clone()))
+            })
+    }
+
+    /// Decodes every item of a group, returns false while it is being fetched.
+    fn load_group(&mut self, source: ObjArchive, js5: &Js5, group_id: u32) -> bool {
+        if self.loaded_groups.contains(&group_id) {
+            return true;
+        }
+        if js5.is_group_corrupt(group_id) {
+            self.loaded_groups.insert(group_id);
+            return true;
+        }
+        let Some(file_ids) = js5.get_file_ids(group_id) else {
+            self.loaded_groups.insert(group_id);
+            return true;
+        };
+        let mut objs = Vec::with_capacity(file_ids.len());
+        for &file_id in file_ids.iter() {
+            let Some(data) = js5.get_file(group_id, file_id) else {
+                return false;
+            };
+            if let Some(obj) = ObjType::decode(source.obj_id(group_id, file_id), &data) {
+                objs.push(obj);
+            }
+        }
+        self.objs.extend(objs.into_iter().map(|obj| (obj.id, obj)));
+        self.loaded_groups.insert(group_id);
+        true
+    }
+
+    fn load_names(&mut self, ctx: &egui::Context, source: ObjArchive, js5: &Js5) {
+        let missing: Vec<u32> = js5
+            .index
+            .group_ids
+            .iter()
+            .copied()
+            .filter(|group_id| match source {
+                ObjArchive::Objs => true,
+                ObjArchive::Config => *group_id == ObjArchive::CONFIG_GROUP_ID,
+            })
+            .filter(|group_id| !self.loaded_groups.contains(group_id))
+            .take(Self::GROUPS_PER_FRAME)
+            .collect();
+        if missing.is_empty() {
+            self.loading_names = false;
+            return;
+        }
+        for group_id in missing {
+            self.load_group(source, js5, group_id);
+        }
+        ctx.request_repaint();
+    }
+
+    fn obj(&mut self, source: ObjArchive, js5: &Js5, obj_id: u32) -> Option<&ObjType> {
+        let (group_id, _) = source.location(obj_id);
+        self.load_group(source, js5, group_id);
+        self.objs.get(&obj_id)
+    }
+
+    /// Starts fetching what showing the items needs in the background: the definitions not
+    /// decoded yet, and the models of those that are.
+    fn prefetch(&self, source: ObjArchive, js5: &Js5, model_js5: &Js5, obj_ids: &[u32]) {
+        for &obj_id in obj_ids {
+            match self.objs.get(&obj_id) {
+                Some(obj) => {
+                    if let Some(model_id) = obj.model {
+                        model_js5.prefetch_group(model_id);
+                    }
+                }
+                None => js5.prefetch_group(source.location(obj_id).0),
+            }
+        }
+    }
+
+    /// Lists the items, names from imported lists are shown in place of the cache's. The models
+    /// of the shown items and the page after them are prefetched, so selecting one shows its icon
+    /// right away.
+    fn item_list(
+        &mut self,
+        ui: &mut egui::Ui,
+        source: ObjArchive,
+        js5: &Js5,
+        model_js5: Option<&Js5>,
+        names: &NameTable,
+    ) {
+        ui.horizontal(|ui| {
+            if self.loading_names {
+                let total = match source {
+                    ObjArchive::Objs => js5.index.group_ids.len(),
+                    ObjArchive::Config => 1,
+                };
+                ui.spinner();
+                ui.label(format!("{}/{total}", self.loaded_groups.len().min(total)));
+                if ui.button("Stop").clicked() {
+                    self.loading_names = false;
+                }
+            } else if ui
+                .button("Load names")
+                .on_hover_text("Decode every item, so they can be searched by name")
+                .clicked()
+            {
+                self.loading_names = true;
+            }
+        });
+        ui.add(egui::TextEdit::singleline(&mut self.search_text).hint_text("Search id or name..."));
+        let search_text = self.search_text.trim().to_lowercase();
+        let obj_ids: Vec<u32> = (0..source.obj_count(js5))
+            .filter(|obj_id| {
+                search_text.is_empty()
+                    || names.matches(*obj_id, &search_text)
+                    || self
+                        .objs
+                        .get(obj_id)
+                        .is_some_and(|obj| obj.name.to_lowercase().contains(&search_text))
+            })
+            .collect();
+        ui.label(format!("{} items", obj_ids.len()));
+        egui::ScrollArea::vertical()
+            .id_salt("item_list")
+            .auto_shrink(false)
+            .show_rows(ui, Self::ROW_HEIGHT, obj_ids.len(), |ui, row_range| {
+                if let Some(model_js5) = model_js5 {
+                    let end = (row_range.end + row_range.len()).min(obj_ids.len());
+                    self.prefetch(source, js5, model_js5, &obj_ids[row_range.start..end]);
+                }
+                for &obj_id in &obj_ids[row_range] {
+                    let name = names.get(obj_id).map(str::to_owned);
+                    let label = match (name, self.obj(source, js5, obj_id)) {
+                        (Some(name), _) => format!("{obj_id} {name}"),
+                        (None, Some(obj)) => format!("{obj_id} {}", obj.name),
+                        (None, None) => obj_id.to_string(),
+                    };
+                    let selected = self.selected == Some(obj_id);
+                    if ui.selectable_label(selected, label).clicked() {
+                        self.selected = Some(obj_id);
+                        self.status = None;
+                    }
+                }
+            });
+    }
+
+    /// Renders the selected item's icon, None while its model is being fetched.
+    fn render_icon(
+        &mut self,
+        ctx: &egui::Context,
+        plugin_ctx: &PluginContext<'_>,
+        obj: &ObjType,
+    ) -> Option<()> {
+        if self
+            .icon
+            .as_ref()
+            .is_some_and(|icon| icon.obj_id == obj.id && icon.outlined == self.outline)
+        {
+            return Some(());
+        }
+        let model_js5 = plugin_ctx.archive(MODEL_ARCHIVE_ID)?;
+        let model = load_obj_model(model_js5, plugin_ctx, obj, obj.model?)?;
+        let camera = ModelCamera {
+            zoom: obj.zoom_2d,
+            x_angle: obj.x_angle_2d,
+            y_angle: obj.y_angle_2d,
+            z_angle: obj.z_angle_2d,
+            x_offset: obj.x_offset_2d,
+            y_offset: obj.y_offset_2d,
+        };
+        self.model_renderer.set_brightness(plugin_ctx.brightness);
+        let mut image = self.model_renderer.render_image(
+            plugin_ctx.texture_provider,
+            &model,
+            &camera,
+            ICON_SIZE,
+        );
+        if self.outline {
+            outline_icon(&mut image);
+        }
+        let texture = ctx.load_texture(
+            format!("item_icon_{}", obj.id),
+            image.clone(),
+            egui::TextureOptions::NEAREST,
+        );
+        self.icon = Some(ItemIcon {
+            obj_id: obj.id,
+            outlined: self.outline,
+            image,
+            texture,
+        });
+        Some(())
+    }
+
+    fn icon_ui(&mut self, ui: &mut egui::Ui, plugin_ctx: &PluginContext<'_>, obj: &ObjType) {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.outline, "Outline")
+                .on_hover_text("Draw the outline and shadow icons have in inventories");
+            ui.add(
+                egui::Slider::new(&mut self.icon_scale, 1.0..=8.0)
+                    .text("Scale")
+                    .suffix("x"),
+            );
+        });
+        let Some(model_id) = obj.model else {
+            ui.label("The item has no model");
+            return;
+        };
+        let model_exists = plugin_ctx
+            .archive(MODEL_ARCHIVE_ID)
+            .is_some_and(|model_js5| model_js5.is_group_valid(model_id));
+        if !model_exists {
+            ui.label(format!("Model {model_id} does not exist"));
+            return;
+        }
+        if self.render_icon(ui.ctx(), plugin_ctx, obj).is_none() {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Loading model...");
+            });
+            ui.ctx().request_repaint();
+            return;
+        }
+        let Some(icon) = &self.icon else {
+            return;
+        };
+        let size = egui::vec2(ICON_SIZE[0] as f32, ICON_SIZE[1] as f32) * self.icon_scale;
+        ui.image((icon.texture.id(), size));
+        if ui.button("Export icon").clicked() {
+            let [width, height] = icon.image.size;
+            let file = ExportFile {
+                path: format!("item_{}.png", obj.id),
+                data: png::encode_rgba(width as u32, height as u32, icon.image.as_raw()),
+            };
+            self.status = Some(match save_files("items", vec![file]) {
+                Ok(location) => format!("Saved to {location}"),
+                Err(err) => format!("Export failed: {err}"),
+            });
+        }
+    }
+
+    fn item_details(
+        &mut self,
+        ui: &mut egui::Ui,
+        plugin_ctx: &PluginContext<'_>,
+        source: ObjArchive,
+        js5: &Js5,
+    ) {
+        let Some(obj_id) = self.selected else {
+            ui.label("Select an item");
+            return;
+        };
+        let Some(obj) = self.obj(source, js5, obj_id).cloned() else {
+            ui.spinner();
+            return;
+        };
+        let model_label = |model: Option<u32>| match model {
+            Some(model_id) => model_id.to_string(),
+            None => "-".to_owned(),
+        };
+        egui::Grid::new("item_details")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Id");
+                ui.label(obj.id.to_string());
+                ui.end_row();
+                ui.label("Name");
+                ui.label(&obj.name);
+                ui.end_row();
+                if let Some(name) = plugin_ctx.names.get(NameKind::Item).get(obj.id) {
+                    ui.label("Listed name");
+                    ui.label(name);
+                    ui.end_row();
+                }
+                ui.label("Model");
+                ui.label(model_label(obj.model));
+                ui.end_row();
+                ui.label("Worn models");
+                ui.label(format!(
+                    "{} / {}",
+                    obj.male_models.map(model_label).join(", "),
+                    obj.female_models.map(model_label).join(", ")
+                ));
+                ui.end_row();
+                ui.label("Cost");
+                ui.label(obj.cost.to_string());
+                ui.end_row();
+                ui.label("Stackable");
+                ui.label(if obj.stackable { "Yes" } else { "No" });
+                ui.end_row();
+                ui.label("Members");
+                ui.label(if obj.members { "Yes" } else { "No" });
+                ui.end_row();
+                ui.label("Recolours");
+                ui.label(
+                    obj.recolours
+                        .iter()
+                        .map(|(old, new)| format!("{old}→{new}"))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                );
+                ui.end_row();
+                ui.label("Retextures");
+                ui.label(
+                    obj.retextures
+                        .iter()
+                        .map(|(old, new)| format!("{old}→{new}"))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                );
+                ui.end_row();
+                ui.label("Resize");
+                ui.label(format!(
+                    "{} × {} × {}",
+                    obj.resize[0], obj.resize[1], obj.resize[2]
+                ));
+                ui.end_row();
+                if let Some(cert_link) = obj.cert_link {
+                    ui.label("Note of");
+                    ui.label(cert_link.to_string());
+                    ui.end_row();
+                }
+            });
+        if let Some(opcode) = obj.unknown_opcode {
+            ui.label(format!(
+                "Stopped decoding at unknown opcode {opcode}, later fields are missing"
+            ));
+        }
+        if let Some(model_id) = obj.model {
+            if ui
+                .button("Show in viewport")
+                .on_hover_text("Show the item's model with its recolours")
+                .clicked()
+            {
+                plugin_ctx.show_recoloured_model(model_id, obj.recolours.clone());
+            }
+        }
+        ui.separator();
+        self.icon_ui(ui, plugin_ctx, &obj);
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+    }
+}
+
+impl PluginWindow for ItemBrowserWindow {
+    fn title(&self) -> &'static str {
+        "Items"
+    }
+
+    fn archive_ids(&self) -> Vec<u8> {
+        vec![ObjArchive::CONFIG_ARCHIVE_ID, ObjArchive::OBJ_ARCHIVE_ID]
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, plugin_ctx: &PluginContext<'_>) {
+        let Some((source, js5)) = Self::source(plugin_ctx) else {
+            ui.label("This cache has no item definitions");
+            return;
+        };
+        if self.loading_names {
+            self.load_names(ui.ctx(), source, &js5);
+        }
+        ui.horizontal_top(|ui| {
+            ui.vertical(|ui| {
+                ui.set_width(200.0);
+                self.item_list(
+                    ui,
+                    source,
+                    &js5,
+                    plugin_ctx.archive(MODEL_ARCHIVE_ID).map(|js5| &**js5),
+                    plugin_ctx.names.get(NameKind::Item),
+                );
+            });
+            ui.separator();
+            ui.vertical(|ui| {
+                self.item_details(ui, plugin_ctx, source, &js5);
+            });
+        });
+    }
+}
@@ -0,0 +1,76 @@
+//! Deep links to a model, as a `#model=<id>` fragment of the page URL. A `cache=<id>` parameter
+//! picks the OpenRS2 cache to open, e.g. `#cache=2064&model=123`.
+
+const MODEL_PARAM: &str = "model";
+const CACHE_PARAM: &str = "cache";
+
+fn parse_fragment(fragment: &str, param: &str) -> Option<u32> {
+    fragment
+        .strip_prefix('#')?
+        .split('&')
+        .find_map(|pair| pair.strip_prefix(param)?.strip_prefix('='))?
+        .parse()
+        .ok()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn fragment() -> Option<String> {
+    web_sys::window()?.location().hash().ok()
+}
+
+/// The fragment the app was opened with, read from the first argument starting with `#`, like
+/// `#model=123`.
+#[cfg(not(target_arch = "wasm32"))]
+fn fragment() -> Option<String> {
+    std::env::args().skip(1).find(|arg| arg.starts_with('#'))
+}
+
+/// The model id the app was opened with.
+pub fn linked_model_id() -> Option<u32> {
+    parse_fragment(&fragment()?, MODEL_PARAM)
+}
+
+/// The OpenRS2 cache id the app was opened with.
+pub fn linked_cache_id() -> Option<u32> {
+    parse_fragment(&fragment()?, CACHE_PARAM)
+}
+
+/// Links to `model_id` on the current page, in the cache the page was opened with.
+#[cfg(target_arch = "wasm32")]
+pub fn model_link(model_id: u32) -> Option<String> {
+    let location = web_sys::window()?.location();
+    let origin = location.origin().ok()?;
+    let path = location.pathname().ok()?;
+    let cache = linked_cache_id()
+        .map(|cache_id| format!("{CACHE_PARAM}={cache_id}&"))
+        .unwrap_or_default();
+    Some(format!("{origin}{path}#{cache}{MODEL_PARAM}={model_id}"))
+}
+
+/// The desktop build has no page to link to.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn model_link(_model_id: u32) -> Option<String> {
+    None
+}
+
+/// Reopens the page on another cache, keeping the model that is shown.
+#[cfg(target_arch = "wasm32")]
+pub fn open_cache(cache_id: u32, model_id: u32) -> Result<(), String> {
+    let location = web_sys::window().ok_or("no window")?.location();
+    location
+        .set_hash(&format!(
+            "{CACHE_PARAM}={cache_id}&{MODEL_PARAM}={model_id}"
+        ))
+        .and_then(|_| location.reload())
+        .map_err(|err| format!("{err:?}"))
+}
+
+/// The desktop build opens a local cache on startup only.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn open_cache(_cache_id: u32, _model_id: u32) -> Result<(), String> {
+    Err(
+        "the desktop viewer opens its cache on startup, restart it with #cache=<id> or another \
+         cache directory"
+            .to_owned(),
+    )
+}
@@ -0,0 +1,64 @@
+use crate::runetek5::graphics::material_rules::MaterialRules;
+
+const EXAMPLE: &str = "\
+# <materials> -> <material>, colour <hsl> or average
+# 40 -> 41
+# 1-3,24 -> colour 43968
+# * -> average
+";
+
+/// Edits the material substitutions applied to every model as it is lit.
+pub struct MaterialRulesWindow {
+    text: String,
+    /// Rules applied by the last click on Apply, taken by the app.
+    pub applied: Option<MaterialRules>,
+    status: Option<String>,
+}
+
+impl MaterialRulesWindow {
+    pub fn new() -> Self {
+        Self {
+            text: EXAMPLE.to_owned(),
+            applied: None,
+            status: None,
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, current: &MaterialRules) {
+        egui::Window::new("Material Rules")
+            .default_open(false)
+            .resizable(true)
+            .show(ctx, |ui| {
+                self.ui(ui, current);
+            });
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, current: &MaterialRules) {
+        ui.label("Replaces materials in every model and preview as it is lit.");
+        ui.add(
+            egui::TextEdit::multiline(&mut self.text)
+                .code_editor()
+                .desired_rows(6)
+                .desired_width(f32::INFINITY),
+        );
+        ui.horizontal(|ui| {
+            if ui.button("Apply").clicked() {
+                match self.text.parse::<MaterialRules>() {
+                    Ok(rules) => {
+                        self.status = Some(format!("{} rules applied", rules.rules().len()));
+                        self.applied = Some(rules);
+                    }
+                    Err(err) => self.status = Some(format!("Error on {err}")),
+                }
+            }
+            let clear = ui.add_enabled(!current.is_empty(), egui::Button::new("Clear"));
+            if clear.on_hover_text("Stop substituting materials").clicked() {
+                self.status = None;
+                self.applied = Some(MaterialRules::default());
+            }
+        });
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+    }
+}
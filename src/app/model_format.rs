@@ -0,0 +1,93 @@
+use bytes::Bytes;
+
+use super::hex_view::{HexSection, HexView};
+use crate::runetek5::{
+    error::Runetek5Error,
+    graphics::model::{ModelFormat, ModelUnlit},
+    js5::Js5,
+};
+
+/// Shows the selected model's encoded bytes, annotated with the sections the decoder read.
+pub struct ModelFormatWindow {
+    model_id: Option<u32>,
+    data: Option<Bytes>,
+    /// None when the data fits no format and decodes as nothing.
+    format: Option<ModelFormat>,
+    version: u8,
+    /// Why decoding stopped, the sections after it show as unaccounted.
+    error: Option<Runetek5Error>,
+    sections: Vec<HexSection>,
+    hex_view: HexView,
+}
+
+impl ModelFormatWindow {
+    pub fn new() -> Self {
+        Self {
+            model_id: None,
+            data: None,
+            format: None,
+            version: 0,
+            error: None,
+            sections: Vec::new(),
+            hex_view: HexView::new(),
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, model_js5: &Js5, model_id: u32) {
+        egui::Window::new("Model Format")
+            .default_open(false)
+            .resizable(true)
+            .scroll(false)
+            .show(ctx, |ui| {
+                // Only decode with annotations while the window is expanded.
+                if self.model_id != Some(model_id) {
+                    self.load(model_js5, model_id);
+                }
+                let Some(data) = &self.data else {
+                    ui.spinner();
+                    return;
+                };
+                let format = self.format.map_or("no known", |format| format.label());
+                ui.label(format!(
+                    "Model {model_id}, {format} format, version {}, {} bytes",
+                    self.version,
+                    data.len()
+                ));
+                if let Some(err) = &self.error {
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        format!("Decoding failed: {err}"),
+                    );
+                }
+                ui.separator();
+                egui::CollapsingHeader::new("Sections")
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        self.hex_view.legend_ui(ui, data, &self.sections);
+                    });
+                ui.separator();
+                self.hex_view.ui(ui, data, &self.sections);
+            });
+    }
+
+    fn load(&mut self, model_js5: &Js5, model_id: u32) {
+        let Some(data) = model_js5.get_file(model_id, 0) else {
+            self.data = None;
+            return;
+        };
+        let mut model = ModelUnlit::new();
+        let (sections, result) = model.decode_annotated(&data);
+        self.error = result.err();
+        self.sections = sections
+            .into_iter()
+            .map(|section| HexSection {
+                name: section.name.to_owned(),
+                range: section.range,
+            })
+            .collect();
+        self.format = ModelFormat::detect(&data);
+        self.version = model.version;
+        self.data = Some(data);
+        self.model_id = Some(model_id);
+    }
+}
@@ -0,0 +1,173 @@
+//! Renders models into egui textures the way the client draws item icons and interface models,
+//! for windows that show a model as a static image rather than in the viewport.
+
+use nalgebra_glm as glm;
+
+use crate::runetek5::{
+    graphics::{
+        model::{ModelFlags, ModelLit, ModelUnlit},
+        rasterizer::Rasterizer,
+        texture::TextureProvider,
+    },
+    js5::Js5,
+};
+
+/// Interface angles are in 2048ths of a turn.
+fn angle_to_radians(angle: u16) -> f32 {
+    (angle % 2048) as f32 * std::f32::consts::TAU / 2048.0
+}
+
+/// How a model is placed in front of the camera, in the client's units as stored on item and
+/// interface definitions. Angles are in 2048ths of a turn.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ModelCamera {
+    /// Distance of the model from the camera.
+    pub zoom: u16,
+    /// Pitch, tilting the top of the model away from the camera.
+    pub x_angle: u16,
+    /// Yaw, turning the model around its vertical axis.
+    pub y_angle: u16,
+    /// Roll, turning the model around the view direction.
+    pub z_angle: u16,
+    pub x_offset: i32,
+    pub y_offset: i32,
+}
+
+impl ModelCamera {
+    /// The view-projection matrix for an image of the given size. The model's centre height is
+    /// lifted to the middle of the image, like the client does.
+    pub fn view_projection(&self, model: &ModelLit, size: [usize; 2]) -> glm::Mat4 {
+        let [width, height] = size;
+        let zoom = self.zoom as f32;
+        let pitch = angle_to_radians(self.x_angle);
+        let bounds = model.calculate_bounds();
+        let model_height = (bounds.bounding_box.max_y - bounds.bounding_box.min_y) as f32;
+        let translation = glm::vec3(
+            self.x_offset as f32,
+            zoom * pitch.sin() + model_height / 2.0 + self.y_offset as f32,
+            zoom * pitch.cos() + self.y_offset as f32,
+        );
+        // Rotations in client space, where y points down and z into the screen.
+        let client_transform = glm::translation(&translation)
+            * glm::rotation(-pitch, &glm::vec3(1.0, 0.0, 0.0))
+            * glm::rotation(angle_to_radians(self.y_angle), &glm::vec3(0.0, 1.0, 0.0))
+            * glm::rotation(-angle_to_radians(self.z_angle), &glm::vec3(0.0, 0.0, 1.0));
+        // The rasterizer divides by 512 and flips y and z, undo that around the client transform.
+        let to_gl = glm::scaling(&glm::vec3(1.0 / 512.0, -1.0 / 512.0, -1.0 / 512.0));
+        let from_gl = glm::scaling(&glm::vec3(512.0, -512.0, -512.0));
+        // The client projects with a focal length of 512 pixels.
+        let fov_y = 2.0 * (height as f32 / 2.0 / 512.0).atan();
+        let projection = glm::perspective(width as f32 / height as f32, fov_y, 0.05, 100.0);
+        projection * to_gl * client_transform * from_gl
+    }
+}
+
+/// Renders models to textures with the software rasterizer, so images can be made outside the
+/// GL viewport and at any size. Pixels the model doesn't cover are transparent.
+pub struct ModelImageRenderer {
+    rasterizer: Rasterizer,
+}
+
+impl ModelImageRenderer {
+    /// The lighting the client gives item icons and interface models.
+    pub const AMBIENT: i16 = 64;
+    pub const CONTRAST: i16 = 768;
+
+    pub fn new(brightness: f32) -> Self {
+        Self {
+            rasterizer: Rasterizer::new(brightness as f64),
+        }
+    }
+
+    pub fn set_brightness(&mut self, brightness: f32) {
+        self.rasterizer.set_brightness(brightness as f64);
+    }
+
+    /// Decodes and lights a model for rendering, None if its group is missing, still being
+    /// fetched or can't be decoded.
+    pub fn load_model(
+        model_js5: &Js5,
+        texture_provider: &TextureProvider,
+        model_id: u32,
+    ) -> Option<ModelLit> {
+        if !model_js5.is_group_valid(model_id) || model_js5.is_group_corrupt(model_id) {
+            return None;
+        }
+        let mut model_unlit = ModelUnlit::from_js5(model_js5, model_id, 0)?;
+        if model_unlit.version < 13 {
+            model_unlit.scale_log2(2);
+        }
+        Some(Self::light(texture_provider, &model_unlit))
+    }
+
+    /// Lights a model like an item icon.
+    pub fn light(texture_provider: &TextureProvider, model_unlit: &ModelUnlit) -> ModelLit {
+        ModelLit::from_unlit(
+            texture_provider,
+            model_unlit,
+            ModelFlags::empty(),
+            Self::AMBIENT,
+            Self::CONTRAST,
+        )
+    }
+
+    /// Renders the model to an image of `[width, height]` pixels.
+    pub fn render_image(
+        &mut self,
+        texture_provider: &TextureProvider,
+        model: &ModelLit,
+        camera: &ModelCamera,
+        size: [usize; 2],
+    ) -> egui::ColorImage {
+        let size = size.map(|side| side.max(1));
+        self.rasterizer.resize(size[0], size[1]);
+        self.rasterizer.clear(0);
+        let (light_x, light_y, light_z) = ModelLit::DEFAULT_LIGHT_DIRECTION;
+        self.rasterizer.render_model(
+            texture_provider,
+            model,
+            &camera.view_projection(model, size),
+            light_x,
+            light_y,
+            light_z,
+        );
+
+        let pixels = self
+            .rasterizer
+            .pixels
+            .iter()
+            .map(|&rgb| match rgb {
+                0 => egui::Color32::TRANSPARENT,
+                rgb => egui::Color32::from_rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8),
+            })
+            .collect();
+        egui::ColorImage { size, pixels }
+    }
+
+    /// Renders the model to a new texture named `name`.
+    pub fn render(
+        &mut self,
+        ctx: &egui::Context,
+        name: impl Into<String>,
+        texture_provider: &TextureProvider,
+        model: &ModelLit,
+        camera: &ModelCamera,
+        size: [usize; 2],
+    ) -> egui::TextureHandle {
+        let image = self.render_image(texture_provider, model, camera, size);
+        ctx.load_texture(name, image, egui::TextureOptions::LINEAR)
+    }
+
+    /// Renders the model into an existing texture at its current size, e.g. to redraw an icon
+    /// after its camera changed.
+    pub fn render_into(
+        &mut self,
+        texture: &mut egui::TextureHandle,
+        texture_provider: &TextureProvider,
+        model: &ModelLit,
+        camera: &ModelCamera,
+    ) {
+        let image = self.render_image(texture_provider, model, camera, texture.size());
+        texture.set(image, egui::TextureOptions::LINEAR);
+    }
+}
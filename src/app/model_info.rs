@@ -0,0 +1,557 @@
+use super::{definitions::DefinitionScan, save::save_files};
+use crate::{
+    export::{
+        atlas::AtlasOptions,
+        gltf::UpAxis,
+        json::JsonObject,
+        preset::{ExportFormat, ExportPreset},
+        schema::SCHEMA_VERSION,
+        ExportFile,
+    },
+    runetek5::{
+        graphics::{
+            limits::{check_limits, LimitCheck},
+            model::{ModelLit, ModelUnlit},
+            reencode::{check_reencode, ReencodeReport},
+            texture::{TextureFilter, TextureProvider},
+        },
+        js5::Js5,
+    },
+};
+
+struct ModelInfo {
+    id: u32,
+    version: u8,
+    vertex_count: u16,
+    triangle_count: u16,
+    textured_triangle_count: u16,
+    priority: u8,
+    vertex_skins: Option<Vec<i32>>,
+    has_skins: bool,
+    fingerprint: u64,
+    limits: Vec<LimitCheck>,
+}
+
+/// Scans the model archive a few groups per frame for models with a matching fingerprint.
+struct DuplicateScan {
+    model_id: u32,
+    fingerprint: u64,
+    next_index: usize,
+    model_ids: Vec<usize>,
+}
+
+impl DuplicateScan {
+    const GROUPS_PER_FRAME: usize = 256;
+
+    /// Returns true once every model group has been checked.
+    fn step(&mut self, model_js5: &Js5) -> bool {
+        let group_ids = &model_js5.index.group_ids;
+        let end = (self.next_index + Self::GROUPS_PER_FRAME).min(group_ids.len());
+        let mut batch = Vec::with_capacity(end - self.next_index);
+        while self.next_index < end {
+            let group_id = group_ids[self.next_index];
+            match model_js5.try_get_file(group_id, 0) {
+                Ok(Some(data)) => batch.push((group_id, data)),
+                // Stop at groups that are still being fetched.
+                Ok(None) => break,
+                // Skip groups that never load, e.g. quarantined ones.
+                Err(_) => {}
+            }
+            self.next_index += 1;
+        }
+
+        let fingerprints = crate::parallel::map(&batch, |(_, data)| {
+            ModelUnlit::from_data(data)
+                .ok()
+                .map(|model| model.fingerprint())
+        });
+        for ((group_id, _), fingerprint) in batch.iter().zip(fingerprints) {
+            if fingerprint == Some(self.fingerprint) {
+                self.model_ids.push(*group_id as usize);
+            }
+        }
+        self.next_index == group_ids.len()
+    }
+}
+
+/// Shows metadata for the selected model and finds models with identical content.
+pub struct ModelInfoWindow {
+    info: Option<ModelInfo>,
+    duplicate_scan: Option<DuplicateScan>,
+    rig_skins: bool,
+    /// The built-in presets followed by the ones saved this session.
+    presets: Vec<ExportPreset>,
+    /// The options the next export uses, starting from a preset.
+    preset: ExportPreset,
+    /// Preset options pasted as `key=value` pairs.
+    preset_text: String,
+    export_status: Option<String>,
+    /// The shown model with its edits, in the units of the format, and how it re-encodes.
+    edited: Option<(ModelUnlit, ReencodeReport)>,
+    /// Models found by a finished duplicate scan, as (model id, model ids).
+    pub found_models: Option<(u32, Vec<usize>)>,
+}
+
+impl ModelInfoWindow {
+    pub fn new() -> Self {
+        Self {
+            info: None,
+            duplicate_scan: None,
+            rig_skins: false,
+            presets: ExportPreset::builtins(),
+            preset: ExportPreset::default(),
+            preset_text: String::new(),
+            export_status: None,
+            edited: None,
+            found_models: None,
+        }
+    }
+
+    /// Sets the model to show, `model` should be as decoded before any scaling.
+    /// The current model's header and fingerprint as a JSON object.
+    pub fn metadata_json(&self) -> Option<String> {
+        let info = self.info.as_ref()?;
+        Some(
+            JsonObject::new()
+                .number("schemaVersion", SCHEMA_VERSION)
+                .number("id", info.id)
+                .number("version", info.version)
+                .number("vertexCount", info.vertex_count)
+                .number("triangleCount", info.triangle_count)
+                .number("texturedTriangleCount", info.textured_triangle_count)
+                .number("priority", info.priority)
+                .bool("hasSkins", info.has_skins)
+                .string("fingerprint", &format!("{:016x}", info.fingerprint))
+                .finish(),
+        )
+    }
+
+    pub fn set_model(&mut self, id: u32, model: &ModelUnlit) {
+        self.info = Some(ModelInfo {
+            id,
+            version: model.version,
+            vertex_count: model.vertex_count,
+            triangle_count: model.triangle_count,
+            textured_triangle_count: model.textured_triangle_count,
+            priority: model.priority,
+            vertex_skins: model.vertex_skins.clone(),
+            has_skins: model.vertex_skins.is_some() || model.triangle_skins.is_some(),
+            fingerprint: model.fingerprint(),
+            limits: check_limits(model),
+        });
+        self.export_status = None;
+    }
+
+    /// Sets the model as edited for display, e.g. recoloured or merged, and checks it re-encodes
+    /// without losing anything.
+    pub fn set_edited_model(&mut self, model: &ModelUnlit) {
+        self.edited = Some((model.clone(), check_reencode(model)));
+    }
+
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        model_js5: &Js5,
+        definitions: &mut DefinitionScan,
+        texture_provider: &TextureProvider,
+        model: Option<&ModelLit>,
+        brightness: f32,
+        texture_filter: TextureFilter,
+    ) {
+        if let Some(scan) = self.duplicate_scan.as_mut() {
+            if scan.step(model_js5) {
+                let scan = self.duplicate_scan.take().unwrap();
+                self.found_models = Some((scan.model_id, scan.model_ids));
+            }
+        }
+        if self.duplicate_scan.is_some() {
+            ctx.request_repaint();
+        }
+
+        egui::Window::new("Model Info")
+            .default_open(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                self.ui(ui, model_js5, definitions);
+                if let Some(model) = model {
+                    ui.separator();
+                    self.export_ui(ui, texture_provider, model, brightness, texture_filter);
+                }
+            });
+    }
+
+    /// Lists the definitions using the model, and how far indexing them is.
+    fn users_ui(ui: &mut egui::Ui, definitions: &DefinitionScan, model_id: u32) {
+        const MAX_SHOWN: usize = 10;
+
+        if !definitions.has_definitions() {
+            ui.label("No NPC or item definitions in the cache");
+            return;
+        }
+        let users = definitions.model_users.users(model_id);
+        for user in users.iter().take(MAX_SHOWN) {
+            ui.label(user.to_string());
+        }
+        if users.len() > MAX_SHOWN {
+            ui.label(format!("and {} more", users.len() - MAX_SHOWN));
+        }
+        if let Some((decoded, total)) = definitions.progress() {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label(format!("Decoding definitions... {decoded}/{total}"));
+            });
+        } else if users.is_empty() {
+            ui.label("Nothing");
+        }
+    }
+
+    fn export_ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        texture_provider: &TextureProvider,
+        model: &ModelLit,
+        brightness: f32,
+        texture_filter: TextureFilter,
+    ) {
+        let Some(info) = &self.info else {
+            return;
+        };
+        let vertex_skins = info.vertex_skins.clone();
+        let name = format!("model_{}", info.id);
+        self.reencode_ui(ui, &name);
+        ui.separator();
+        self.preset_ui(ui);
+        ui.add_enabled(
+            vertex_skins.is_some(),
+            egui::Checkbox::new(&mut self.rig_skins, "Rig skins as joints"),
+        )
+        .on_hover_text("Export vertex skin labels as glTF joints");
+        if ui.button("Export glTF").clicked() {
+            let options =
+                self.preset
+                    .gltf_options(brightness as f64, texture_filter, self.rig_skins);
+            let files = self.preset.export(
+                texture_provider,
+                model,
+                vertex_skins.as_deref(),
+                options,
+                &name,
+            );
+            self.export_status = Some(match save_files("gltf", files) {
+                Ok(location) => format!("Exported to {location}"),
+                Err(err) => format!("Export failed: {err}"),
+            });
+        }
+        if let Some(status) = &self.export_status {
+            ui.label(status);
+        }
+    }
+
+    /// Shows what re-encoding the edited model would lose, opened by default if anything.
+    fn reencode_ui(&mut self, ui: &mut egui::Ui, name: &str) {
+        let Some((model, report)) = &self.edited else {
+            return;
+        };
+        let title = if report.is_lossless() {
+            egui::RichText::new(format!("Re-encodes losslessly ({} bytes)", report.size))
+        } else {
+            egui::RichText::new("Re-encoding loses data").color(ui.visuals().warn_fg_color)
+        };
+        egui::CollapsingHeader::new(title)
+            .id_salt("model_reencode")
+            .default_open(!report.is_lossless())
+            .show(ui, |ui| {
+                if !report.losses.is_empty() {
+                    egui::Grid::new("model_reencode_losses")
+                        .num_columns(2)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for loss in &report.losses {
+                                ui.label(loss.property).on_hover_text(loss.note);
+                                ui.label(format!("{} changed", loss.count));
+                                ui.end_row();
+                            }
+                        });
+                }
+                if !report.diffs.is_empty() {
+                    ui.label("Decoded differently:");
+                    egui::Grid::new("model_reencode_diffs")
+                        .num_columns(3)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for diff in &report.diffs {
+                                ui.label(diff.name);
+                                ui.label(format!("{}/{}", diff.changed, diff.total));
+                                ui.label(format!("first at {}", diff.first));
+                                ui.end_row();
+                            }
+                        });
+                }
+                if ui
+                    .button("Save model file")
+                    .on_hover_text("Save the re-encoded model as the client reads it")
+                    .clicked()
+                {
+                    let file = ExportFile {
+                        path: format!("{name}.dat"),
+                        data: model.encode(),
+                    };
+                    self.export_status = Some(match save_files("models", vec![file]) {
+                        Ok(location) => format!("Saved to {location}"),
+                        Err(err) => format!("Export failed: {err}"),
+                    });
+                }
+            });
+    }
+
+    /// Picks and edits the export options, and saves them as presets.
+    fn preset_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_label("Preset")
+                .selected_text(&self.preset.name)
+                .show_ui(ui, |ui| {
+                    for preset in &self.presets {
+                        if ui
+                            .selectable_label(*preset == self.preset, &preset.name)
+                            .clicked()
+                        {
+                            self.preset = preset.clone();
+                        }
+                    }
+                });
+            if ui
+                .button("Copy")
+                .on_hover_text("Copy the options as key=value pairs")
+                .clicked()
+            {
+                ui.ctx().copy_text(self.preset.to_string());
+            }
+        });
+        egui::Grid::new("export_options")
+            .num_columns(2)
+            .show(ui, |ui| {
+                let preset = &mut self.preset;
+                ui.label("Format");
+                egui::ComboBox::from_id_salt("export_format")
+                    .selected_text(preset.format.label())
+                    .show_ui(ui, |ui| {
+                        for format in ExportFormat::ALL {
+                            ui.selectable_value(&mut preset.format, format, format.label());
+                        }
+                    });
+                ui.end_row();
+                ui.label("Scale");
+                ui.add(
+                    egui::DragValue::new(&mut preset.scale)
+                        .range(0.001..=4096.0)
+                        .speed(0.01)
+                        .suffix(" per tile"),
+                );
+                ui.end_row();
+                ui.label("Axis");
+                ui.horizontal(|ui| {
+                    for up_axis in UpAxis::ALL {
+                        ui.radio_value(&mut preset.up_axis, up_axis, up_axis.label());
+                    }
+                });
+                ui.end_row();
+                ui.label("Textures");
+                ui.checkbox(&mut preset.textures, "Embed textures");
+                ui.end_row();
+                ui.label("Lighting");
+                ui.checkbox(&mut preset.baked_lighting, "Bake into colours");
+                ui.end_row();
+                ui.label("Colours");
+                ui.checkbox(&mut preset.bake_colours, "Bake into textures")
+                    .on_hover_text("For tools that ignore vertex colours");
+                ui.end_row();
+                ui.label("Size");
+                ui.checkbox(&mut preset.quantize, "Quantize")
+                    .on_hover_text("Smaller files using KHR_mesh_quantization");
+                ui.end_row();
+                ui.label("Normals");
+                ui.checkbox(&mut preset.normal_map, "Bake normal map")
+                    .on_hover_text("Smoothed normals, with flat shading kept in a normal map");
+                ui.end_row();
+                ui.label("Atlas");
+                ui.horizontal(|ui| {
+                    let mut packed = preset.atlas.is_some();
+                    if ui
+                        .checkbox(&mut packed, "Pack textures")
+                        .on_hover_text("One material per page instead of one per texture")
+                        .changed()
+                    {
+                        preset.atlas = packed.then(AtlasOptions::default);
+                    }
+                    if let Some(atlas) = &mut preset.atlas {
+                        ui.add(
+                            egui::DragValue::new(&mut atlas.size)
+                                .range(64..=16384)
+                                .suffix(" px"),
+                        );
+                        ui.add(
+                            egui::DragValue::new(&mut atlas.padding)
+                                .range(0..=64)
+                                .prefix("padding "),
+                        );
+                    }
+                });
+                ui.end_row();
+            });
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.preset_text)
+                    .desired_width(180.0)
+                    .hint_text("Preset name or key=value..."),
+            );
+            if ui
+                .button("Apply")
+                .on_hover_text("Use a built-in preset or pasted options")
+                .clicked()
+            {
+                match self.preset_text.parse::<ExportPreset>() {
+                    Ok(preset) => {
+                        self.preset = preset;
+                        self.export_status = None;
+                    }
+                    Err(err) => self.export_status = Some(format!("Invalid preset: {err}")),
+                }
+            }
+            if ui
+                .button("Save as")
+                .on_hover_text("Save the options as a preset named by the text")
+                .clicked()
+            {
+                let name = self.preset_text.trim();
+                if name.is_empty() || name.contains('=') {
+                    self.export_status = Some("Enter a name for the preset".to_owned());
+                } else {
+                    self.preset.name = name.to_owned();
+                    match self.presets.iter_mut().find(|preset| preset.name == name) {
+                        Some(preset) => *preset = self.preset.clone(),
+                        None => self.presets.push(self.preset.clone()),
+                    }
+                    self.preset_text.clear();
+                }
+            }
+        });
+    }
+
+    /// Compares the model against the format limits, opened by default if any are exceeded.
+    fn limits_ui(ui: &mut egui::Ui, limits: &[LimitCheck]) {
+        let exceeded = limits.iter().filter(|check| !check.passed()).count();
+        let title = if exceeded == 0 {
+            egui::RichText::new("Within format limits")
+        } else {
+            egui::RichText::new(format!("{exceeded} format limits exceeded"))
+                .color(ui.visuals().warn_fg_color)
+        };
+        egui::CollapsingHeader::new(title)
+            .id_salt("model_limits")
+            .default_open(exceeded > 0)
+            .show(ui, |ui| {
+                egui::Grid::new("model_limits_grid")
+                    .num_columns(3)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for check in limits {
+                            ui.label(check.name).on_hover_text(check.note);
+                            if check.passed() {
+                                ui.label(check.value.to_string());
+                            } else {
+                                ui.colored_label(
+                                    ui.visuals().warn_fg_color,
+                                    format!("{} \u{26A0}", check.value),
+                                );
+                            }
+                            ui.label(format!("max {}", check.limit));
+                            ui.end_row();
+                        }
+                    });
+            });
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, model_js5: &Js5, definitions: &mut DefinitionScan) {
+        if !definitions.step() {
+            ui.ctx().request_repaint();
+        }
+
+        let Some(info) = &self.info else {
+            ui.label("No model loaded");
+            return;
+        };
+
+        egui::Grid::new("model_info_grid")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Id");
+                ui.label(info.id.to_string());
+                ui.end_row();
+                ui.label("Version");
+                ui.label(info.version.to_string());
+                ui.end_row();
+                ui.label("Vertices");
+                ui.label(info.vertex_count.to_string());
+                ui.end_row();
+                ui.label("Triangles");
+                ui.label(info.triangle_count.to_string());
+                ui.end_row();
+                ui.label("Textured triangles");
+                ui.label(info.textured_triangle_count.to_string());
+                ui.end_row();
+                ui.label("Priority");
+                ui.label(info.priority.to_string());
+                ui.end_row();
+                ui.label("Skins");
+                ui.label(if info.has_skins { "Yes" } else { "No" });
+                ui.end_row();
+                ui.label("Fingerprint");
+                let fingerprint = format!("{:016x}", info.fingerprint);
+                if ui
+                    .add(
+                        egui::Label::new(egui::RichText::new(&fingerprint).monospace())
+                            .sense(egui::Sense::click()),
+                    )
+                    .on_hover_text("Click to copy")
+                    .clicked()
+                {
+                    ui.ctx().copy_text(fingerprint);
+                }
+                ui.end_row();
+                ui.label("Used by");
+                ui.vertical(|ui| Self::users_ui(ui, definitions, info.id));
+                ui.end_row();
+            });
+
+        Self::limits_ui(ui, &info.limits);
+
+        ui.separator();
+
+        if let Some(scan) = &self.duplicate_scan {
+            let mut cancel = false;
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label(format!(
+                    "Finding duplicates... {}/{}",
+                    scan.next_index,
+                    model_js5.get_group_count()
+                ));
+                cancel = ui.button("Cancel").clicked();
+            });
+            if cancel {
+                self.duplicate_scan = None;
+            }
+        } else if ui
+            .button("Find duplicates")
+            .on_hover_text("Find models with the same fingerprint")
+            .clicked()
+        {
+            self.duplicate_scan = Some(DuplicateScan {
+                model_id: info.id,
+                fingerprint: info.fingerprint,
+                next_index: 0,
+                model_ids: Vec::new(),
+            });
+        }
+    }
+}
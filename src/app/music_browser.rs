@@ -0,0 +1,350 @@
+use std::collections::HashMap;
+
+use super::{
+    plugin::{PluginContext, PluginWindow},
+    save::save_files,
+};
+use crate::{
+    export::ExportFile,
+    runetek5::js5::{name_hash, Js5},
+};
+
+/// The audio archives, listed by what they hold.
+const AUDIO_ARCHIVES: [(u8, &str); 4] = [
+    (6, "Music"),
+    (11, "Jingles"),
+    (14, "Vorbis"),
+    (15, "Instruments"),
+];
+
+/// Track names to resolve group name hashes with, groups are only named by hash in the index.
+const KNOWN_NAMES: [&str; 24] = [
+    "scape main",
+    "harmony",
+    "autumn voyage",
+    "newbie melody",
+    "flute salad",
+    "adventure",
+    "arrival",
+    "sea shanty 2",
+    "spirit",
+    "still night",
+    "garden",
+    "medieval",
+    "venture",
+    "fanfare",
+    "wander",
+    "dream",
+    "yesteryear",
+    "parade",
+    "book of spells",
+    "unknown land",
+    "expanse",
+    "greatness",
+    "lullaby",
+    "long way home",
+];
+
+/// Sizes of a fetched group.
+#[derive(Debug, Clone, Copy)]
+struct GroupSize {
+    packed: usize,
+    /// The summed size of the group's files, None if they couldn't be unpacked.
+    unpacked: Option<usize>,
+}
+
+/// Lists the music, jingle and instrument archives by group, with raw export of the files. The
+/// formats aren't decoded, there is no playback.
+pub struct MusicBrowserWindow {
+    archive_index: usize,
+    search_text: String,
+    selected_group: Option<u32>,
+    /// Sizes by archive and group id, filled in as groups are fetched.
+    sizes: HashMap<(u8, u32), GroupSize>,
+    /// Set while fetching every group of the archive for its size.
+    fetching_sizes: bool,
+    names: HashMap<i32, &'static str>,
+    status: Option<String>,
+}
+
+impl MusicBrowserWindow {
+    const ROW_HEIGHT: f32 = 18.0;
+    /// Groups fetched per frame while fetching sizes, to keep the UI responsive.
+    const SIZES_PER_FRAME: usize = 32;
+
+    pub fn new() -> Self {
+        Self {
+            archive_index: 0,
+            search_text: String::new(),
+            selected_group: None,
+            sizes: HashMap::new(),
+            fetching_sizes: false,
+            names: KNOWN_NAMES
+                .iter()
+                .map(|&name| (name_hash(name), name))
+                .collect(),
+            status: None,
+        }
+    }
+
+    /// Names the group by its hash, if the name is known.
+    fn group_name(&self, js5: &Js5, group_id: u32) -> Option<String> {
+        let hash = js5.index.get_group_name_hash(group_id)?;
+        Some(match self.names.get(&hash) {
+            Some(name) => (*name).to_owned(),
+            None => format!("#{hash:08x}"),
+        })
+    }
+
+    /// The group's sizes, fetching it if needed.
+    fn fetch_size(&mut self, archive_id: u8, js5: &Js5, group_id: u32) -> Option<GroupSize> {
+        if let Some(size) = self.sizes.get(&(archive_id, group_id)) {
+            return Some(*size);
+        }
+        let packed = js5.get_packed_group(group_id)?;
+        let unpacked =
+            group_files(js5, group_id).map(|files| files.iter().map(|(_, data)| data.len()).sum());
+        let size = GroupSize {
+            packed: packed.len(),
+            unpacked,
+        };
+        self.sizes.insert((archive_id, group_id), size);
+        Some(size)
+    }
+
+    fn fetch_sizes(&mut self, ctx: &egui::Context, archive_id: u8, js5: &Js5) {
+        let missing: Vec<u32> = js5
+            .index
+            .group_ids
+            .iter()
+            .copied()
+            .filter(|&group_id| !self.sizes.contains_key(&(archive_id, group_id)))
+            .filter(|&group_id| !js5.is_group_corrupt(group_id))
+            .take(Self::SIZES_PER_FRAME)
+            .collect();
+        if missing.is_empty() {
+            self.fetching_sizes = false;
+            return;
+        }
+        for group_id in missing {
+            self.fetch_size(archive_id, js5, group_id);
+        }
+        ctx.request_repaint();
+    }
+
+    fn archive_ui(&mut self, ui: &mut egui::Ui, plugin_ctx: &PluginContext<'_>) {
+        let (archive_id, name) = AUDIO_ARCHIVES[self.archive_index];
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_salt("music_archive")
+                .selected_text(format!("{name} ({archive_id})"))
+                .show_ui(ui, |ui| {
+                    for (index, (archive_id, name)) in AUDIO_ARCHIVES.iter().enumerate() {
+                        let enabled = plugin_ctx.archive(*archive_id).is_some();
+                        let response = ui.add_enabled(
+                            enabled,
+                            egui::SelectableLabel::new(
+                                self.archive_index == index,
+                                format!("{name} ({archive_id})"),
+                            ),
+                        );
+                        if response.clicked() {
+                            self.archive_index = index;
+                            self.selected_group = None;
+                            self.fetching_sizes = false;
+                            self.status = None;
+                        }
+                    }
+                });
+            let Some(js5) = plugin_ctx.archive(archive_id) else {
+                return;
+            };
+            if self.fetching_sizes {
+                let fetched = js5
+                    .index
+                    .group_ids
+                    .iter()
+                    .filter(|&&group_id| self.sizes.contains_key(&(archive_id, group_id)))
+                    .count();
+                ui.spinner();
+                ui.label(format!("{fetched}/{}", js5.index.group_ids.len()));
+                if ui.button("Stop").clicked() {
+                    self.fetching_sizes = false;
+                }
+            } else if ui
+                .button("Fetch sizes")
+                .on_hover_text("Fetch every group to list its size")
+                .clicked()
+            {
+                self.fetching_sizes = true;
+            }
+        });
+    }
+
+    fn group_list(&mut self, ui: &mut egui::Ui, archive_id: u8, js5: &Js5) {
+        ui.add(egui::TextEdit::singleline(&mut self.search_text).hint_text("Search id or name..."));
+        let search_text = self.search_text.trim();
+        // A name is found by its hash, like the client looks tracks up.
+        let search_hash = name_hash(search_text);
+        let group_ids: Vec<u32> = js5
+            .index
+            .group_ids
+            .iter()
+            .copied()
+            .filter(|&group_id| {
+                search_text.is_empty()
+                    || group_id.to_string().contains(search_text)
+                    || js5.index.get_group_name_hash(group_id) == Some(search_hash)
+                    || self
+                        .group_name(js5, group_id)
+                        .is_some_and(|name| name.contains(search_text))
+            })
+            .collect();
+        ui.label(format!("{} groups", group_ids.len()));
+        egui::ScrollArea::vertical()
+            .id_salt("music_groups")
+            .auto_shrink(false)
+            .show_rows(ui, Self::ROW_HEIGHT, group_ids.len(), |ui, row_range| {
+                for &group_id in &group_ids[row_range] {
+                    let mut label = format!("{group_id}");
+                    if let Some(name) = self.group_name(js5, group_id) {
+                        label.push_str(&format!(" {name}"));
+                    }
+                    if let Some(size) = self.sizes.get(&(archive_id, group_id)) {
+                        label.push_str(&format!(" ({} bytes)", size.packed));
+                    }
+                    let selected = self.selected_group == Some(group_id);
+                    if ui.selectable_label(selected, label).clicked() {
+                        self.selected_group = Some(group_id);
+                        self.status = None;
+                    }
+                }
+            });
+    }
+
+    fn group_details(&mut self, ui: &mut egui::Ui, archive_id: u8, archive_name: &str, js5: &Js5) {
+        let Some(group_id) = self.selected_group else {
+            ui.label("Select a group");
+            return;
+        };
+        egui::Grid::new("music_group")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Group");
+                ui.label(group_id.to_string());
+                ui.end_row();
+                if let Some(name) = self.group_name(js5, group_id) {
+                    ui.label("Name");
+                    ui.label(name);
+                    ui.end_row();
+                }
+                ui.label("Version");
+                ui.label(js5.index.get_group_version(group_id).to_string());
+                ui.end_row();
+                ui.label("CRC");
+                ui.monospace(format!("{:08x}", js5.index.get_group_crc(group_id)));
+                ui.end_row();
+                ui.label("Files");
+                ui.label(js5.get_file_count(group_id).to_string());
+                ui.end_row();
+            });
+
+        if js5.is_group_corrupt(group_id) {
+            ui.label("The group failed its CRC check");
+            return;
+        }
+        let Some(size) = self.fetch_size(archive_id, js5, group_id) else {
+            ui.spinner();
+            return;
+        };
+        ui.label(format!("Packed: {} bytes", size.packed));
+        match size.unpacked {
+            Some(unpacked) => ui.label(format!("Unpacked: {unpacked} bytes")),
+            None => ui.label("The group couldn't be unpacked"),
+        };
+
+        let Some(files) = group_files(js5, group_id) else {
+            return;
+        };
+        if files.len() > 1 {
+            egui::ScrollArea::vertical()
+                .id_salt("music_files")
+                .max_height(160.0)
+                .show(ui, |ui| {
+                    for (file_id, data) in &files {
+                        ui.label(format!("File {file_id}: {} bytes", data.len()));
+                    }
+                });
+        }
+        if ui
+            .button("Export raw files")
+            .on_hover_text("Save the unpacked files as they are stored")
+            .clicked()
+        {
+            let exported = files
+                .into_iter()
+                .map(|(file_id, data)| ExportFile {
+                    path: match js5.get_file_count(group_id) {
+                        1 => format!("{archive_name}/{group_id}.dat"),
+                        _ => format!("{archive_name}/{group_id}/{file_id}.dat"),
+                    },
+                    data,
+                })
+                .collect();
+            self.status = Some(match save_files("audio", exported) {
+                Ok(location) => format!("Saved to {location}"),
+                Err(err) => format!("Export failed: {err}"),
+            });
+        }
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+    }
+}
+
+impl PluginWindow for MusicBrowserWindow {
+    fn title(&self) -> &'static str {
+        "Music"
+    }
+
+    fn archive_ids(&self) -> Vec<u8> {
+        AUDIO_ARCHIVES
+            .iter()
+            .map(|(archive_id, _)| *archive_id)
+            .collect()
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, plugin_ctx: &PluginContext<'_>) {
+        self.archive_ui(ui, plugin_ctx);
+        let (archive_id, archive_name) = AUDIO_ARCHIVES[self.archive_index];
+        let Some(js5) = plugin_ctx.archive(archive_id) else {
+            ui.label("The cache has no such archive");
+            return;
+        };
+        if self.fetching_sizes {
+            self.fetch_sizes(ui.ctx(), archive_id, js5);
+        }
+        ui.separator();
+        ui.horizontal_top(|ui| {
+            ui.vertical(|ui| {
+                ui.set_width(180.0);
+                self.group_list(ui, archive_id, js5);
+            });
+            ui.separator();
+            ui.vertical(|ui| {
+                self.group_details(ui, archive_id, &archive_name.to_lowercase(), js5);
+            });
+        });
+    }
+}
+
+/// The unpacked files of a group by file id, None until all of them are fetched.
+fn group_files(js5: &Js5, group_id: u32) -> Option<Vec<(u32, Vec<u8>)>> {
+    let file_ids = match js5.get_file_ids(group_id) {
+        Some(file_ids) => file_ids.into_owned(),
+        None => (0..js5.get_file_count(group_id)).collect(),
+    };
+    file_ids
+        .into_iter()
+        .map(|file_id| Some((file_id, js5.get_file(group_id, file_id)?.to_vec())))
+        .collect()
+}
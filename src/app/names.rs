@@ -0,0 +1,440 @@
+use std::collections::{BTreeMap, HashMap};
+
+use super::{save::save_files, sprite_import::file_name};
+use crate::export::{
+    json::{self, JsonObject, JsonValue},
+    ExportFile,
+};
+use crate::runetek5::js5::name_hash;
+
+/// What the ids of a name table refer to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameKind {
+    Model,
+    Npc,
+    Item,
+    Object,
+}
+
+impl NameKind {
+    pub const ALL: [NameKind; 4] = [
+        NameKind::Model,
+        NameKind::Npc,
+        NameKind::Item,
+        NameKind::Object,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            NameKind::Model => "Models",
+            NameKind::Npc => "NPCs",
+            NameKind::Item => "Items",
+            NameKind::Object => "Objects",
+        }
+    }
+
+    /// The name exported tables are saved under.
+    fn file_stem(&self) -> &'static str {
+        match self {
+            NameKind::Model => "models",
+            NameKind::Npc => "npcs",
+            NameKind::Item => "items",
+            NameKind::Object => "objects",
+        }
+    }
+}
+
+/// Names of the ids of one kind, from imported lists and added by hand. Names added by hand take
+/// precedence.
+#[derive(Default)]
+pub struct NameTable {
+    imported: HashMap<u32, String>,
+    custom: BTreeMap<u32, String>,
+    /// Names from lists without ids, by [`name_hash`]. They name the groups of archives that
+    /// store the hashes of their group names.
+    hashed: HashMap<i32, String>,
+}
+
+impl NameTable {
+    pub fn get(&self, id: u32) -> Option<&str> {
+        self.custom
+            .get(&id)
+            .or_else(|| self.imported.get(&id))
+            .map(String::as_str)
+    }
+
+    /// Like [`NameTable::get`], falling back to the listed name with the group's name hash.
+    pub fn get_group(&self, id: u32, group_name_hash: Option<i32>) -> Option<&str> {
+        self.get(id)
+            .or_else(|| self.hashed.get(&group_name_hash?).map(String::as_str))
+    }
+
+    /// The ids with a name, in order.
+    pub fn ids(&self) -> Vec<u32> {
+        let mut ids: Vec<u32> = self
+            .imported
+            .keys()
+            .chain(self.custom.keys())
+            .copied()
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+
+    /// Whether the id or its name contains the search text, which has to be lowercase.
+    pub fn matches(&self, id: u32, search_text: &str) -> bool {
+        id.to_string().contains(search_text)
+            || self
+                .get(id)
+                .is_some_and(|name| name.to_lowercase().contains(search_text))
+    }
+
+    /// Like [`NameTable::matches`], also matching groups by their name hash: the hash of the
+    /// search text itself, or of a listed name containing it.
+    pub fn matches_group(&self, id: u32, group_name_hash: Option<i32>, search_text: &str) -> bool {
+        self.matches(id, search_text)
+            || group_name_hash.is_some_and(|hash| {
+                hash == name_hash(search_text)
+                    || self
+                        .hashed
+                        .get(&hash)
+                        .is_some_and(|name| name.to_lowercase().contains(search_text))
+            })
+    }
+
+    /// Adds the names of a CSV or JSON list, replacing earlier imported names of the same ids.
+    /// A `.txt` file lists names without ids, one per line, and is added by the names' hashes.
+    /// Returns how many names were read.
+    fn import(&mut self, file_name: &str, text: &str) -> Result<usize, String> {
+        let file_name = file_name.to_lowercase();
+        if file_name.ends_with(".txt") {
+            return Ok(self.import_hashed(text));
+        }
+        let names = if file_name.ends_with(".json") {
+            parse_json(text)?
+        } else {
+            parse_csv(text)?
+        };
+        let count = names.len();
+        self.imported.extend(names);
+        Ok(count)
+    }
+
+    fn import_hashed(&mut self, text: &str) -> usize {
+        let names: Vec<&str> = text
+            .lines()
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .collect();
+        self.hashed
+            .extend(names.iter().map(|&name| (name_hash(name), name.to_owned())));
+        names.len()
+    }
+
+    fn export_csv(&self) -> String {
+        let mut out = String::from("id,name\n");
+        for (id, name) in &self.custom {
+            out.push_str(&format!("{id},{}\n", csv_field(name)));
+        }
+        out
+    }
+
+    fn export_json(&self) -> String {
+        self.custom
+            .iter()
+            .fold(JsonObject::new(), |object, (id, name)| {
+                object.string(&id.to_string(), name)
+            })
+            .finish()
+    }
+}
+
+/// Reads `id,name` lines, names may be quoted. A first line without an id is taken as a header.
+fn parse_csv(text: &str) -> Result<Vec<(u32, String)>, String> {
+    let mut names = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (id, name) = line
+            .split_once([',', '\t'])
+            .ok_or_else(|| format!("Line {} has no name", index + 1))?;
+        let Ok(id) = id.trim().parse() else {
+            if index == 0 {
+                continue;
+            }
+            return Err(format!("Line {}: {id:?} isn't an id", index + 1));
+        };
+        let name = unquote_csv(name.trim());
+        if !name.is_empty() {
+            names.push((id, name));
+        }
+    }
+    Ok(names)
+}
+
+fn unquote_csv(field: &str) -> String {
+    match field
+        .strip_prefix('"')
+        .and_then(|field| field.strip_suffix('"'))
+    {
+        Some(quoted) => quoted.replace("\"\"", "\""),
+        None => field.to_owned(),
+    }
+}
+
+fn csv_field(name: &str) -> String {
+    if name.contains([',', '"', '\n']) || name.trim() != name {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    } else {
+        name.to_owned()
+    }
+}
+
+/// Reads an object of names keyed by id, or an array of objects with `id` and `name` keys.
+fn parse_json(text: &str) -> Result<Vec<(u32, String)>, String> {
+    let invalid_id = |id: &str| format!("{id:?} isn't an id");
+    match json::parse(text)? {
+        JsonValue::Object(entries) => entries
+            .into_iter()
+            .filter_map(|(id, name)| match name {
+                JsonValue::String(name) => Some((id, name)),
+                _ => None,
+            })
+            .map(|(id, name)| Ok((id.parse().map_err(|_| invalid_id(&id))?, name)))
+            .collect(),
+        JsonValue::Array(entries) => entries
+            .iter()
+            .map(|entry| {
+                let id = match entry.get("id") {
+                    Some(&JsonValue::Number(id)) if id >= 0.0 && id <= u32::MAX as f64 => id as u32,
+                    Some(JsonValue::String(id)) => id.parse().map_err(|_| invalid_id(id))?,
+                    _ => return Err("An entry has no id".to_owned()),
+                };
+                match entry.get("name") {
+                    Some(JsonValue::String(name)) => Ok((id, name.clone())),
+                    _ => Err(format!("Entry {id} has no name")),
+                }
+            })
+            .collect(),
+        _ => Err("Expected an object or an array of names".to_owned()),
+    }
+}
+
+/// The name tables of every kind.
+#[derive(Default)]
+pub struct NameTables {
+    tables: [NameTable; 4],
+}
+
+impl NameTables {
+    pub fn get(&self, kind: NameKind) -> &NameTable {
+        &self.tables[kind as usize]
+    }
+
+    fn get_mut(&mut self, kind: NameKind) -> &mut NameTable {
+        &mut self.tables[kind as usize]
+    }
+}
+
+/// Imports name lists for models, NPCs, items and objects, and edits and exports names added by
+/// hand.
+pub struct NamesWindow {
+    tables: NameTables,
+    kind: NameKind,
+    edit_id: u32,
+    edit_name: String,
+    status: Option<String>,
+    changed: bool,
+}
+
+impl NamesWindow {
+    pub fn new() -> Self {
+        Self {
+            tables: NameTables::default(),
+            kind: NameKind::Model,
+            edit_id: 0,
+            edit_name: String::new(),
+            status: None,
+            changed: false,
+        }
+    }
+
+    pub fn tables(&self) -> &NameTables {
+        &self.tables
+    }
+
+    /// Whether names changed since the last call, so searches have to run again.
+    pub fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, current_model_id: u32) {
+        self.handle_dropped_files(ctx);
+
+        egui::Window::new("Names")
+            .default_open(false)
+            .resizable(true)
+            .show(ctx, |ui| {
+                self.ui(ui, current_model_id);
+            });
+    }
+
+    /// Imports a dropped CSV, JSON or text list into the table of the selected kind.
+    fn handle_dropped_files(&mut self, ctx: &egui::Context) {
+        let dropped_files = ctx.input(|input| input.raw.dropped_files.clone());
+        let Some(file) = dropped_files.into_iter().find(|file| {
+            let name = file_name(file).to_lowercase();
+            name.ends_with(".csv") || name.ends_with(".json") || name.ends_with(".txt")
+        }) else {
+            return;
+        };
+        let name = file_name(&file);
+        let data = match (&file.bytes, &file.path) {
+            (Some(bytes), _) => Ok(bytes.to_vec()),
+            #[cfg(not(target_arch = "wasm32"))]
+            (None, Some(path)) => std::fs::read(path).map_err(|err| err.to_string()),
+            _ => Err("no file contents".to_owned()),
+        };
+        self.import(name, data);
+    }
+
+    /// Asks for a list with the native file dialog.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn open_file(&mut self) {
+        let Some(path) =
+            super::save::pick_file("Import names", "Name list", &["csv", "json", "txt"])
+        else {
+            return;
+        };
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let data = std::fs::read(&path).map_err(|err| err.to_string());
+        self.import(name, data);
+    }
+
+    fn import(&mut self, name: String, data: Result<Vec<u8>, String>) {
+        let kind = self.kind;
+        let result = data
+            .and_then(|data| String::from_utf8(data).map_err(|_| "not UTF-8 text".to_owned()))
+            .and_then(|text| self.tables.get_mut(kind).import(&name, &text));
+        self.status = Some(match result {
+            Ok(count) => {
+                self.changed = true;
+                format!("Imported {count} {} names from {name}", kind.label())
+            }
+            Err(err) => format!("Failed to import {name}: {err}"),
+        });
+    }
+
+    fn export(&self) -> Result<String, String> {
+        let table = self.tables.get(self.kind);
+        let stem = self.kind.file_stem();
+        save_files(
+            "names",
+            vec![
+                ExportFile {
+                    path: format!("{stem}.csv"),
+                    data: table.export_csv().into_bytes(),
+                },
+                ExportFile {
+                    path: format!("{stem}.json"),
+                    data: table.export_json().into_bytes(),
+                },
+            ],
+        )
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, current_model_id: u32) {
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_salt("name_kind")
+                .selected_text(self.kind.label())
+                .show_ui(ui, |ui| {
+                    for kind in NameKind::ALL {
+                        ui.selectable_value(&mut self.kind, kind, kind.label());
+                    }
+                });
+            let table = self.tables.get(self.kind);
+            ui.label(format!(
+                "{} imported, {} custom, {} by hash",
+                table.imported.len(),
+                table.custom.len(),
+                table.hashed.len()
+            ));
+        });
+        ui.horizontal(|ui| {
+            ui.label("Drop a CSV or JSON name list onto the window to import it.")
+                .on_hover_text(
+                    "A text file of names without ids, one per line, names model groups by the \
+                     hashes of their names",
+                );
+            #[cfg(not(target_arch = "wasm32"))]
+            if ui.button("Open…").clicked() {
+                self.open_file();
+            }
+        });
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            ui.label("Id");
+            ui.add(egui::DragValue::new(&mut self.edit_id));
+            if self.kind == NameKind::Model && ui.button("Current model").clicked() {
+                self.edit_id = current_model_id;
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.edit_name).hint_text("Name"));
+            let name = self.edit_name.trim();
+            if ui
+                .add_enabled(!name.is_empty(), egui::Button::new("Set"))
+                .clicked()
+            {
+                let name = name.to_owned();
+                self.tables
+                    .get_mut(self.kind)
+                    .custom
+                    .insert(self.edit_id, name);
+                self.changed = true;
+            }
+        });
+
+        let kind = self.kind;
+        let mut removed = None;
+        egui::ScrollArea::vertical()
+            .id_salt("custom_names")
+            .max_height(200.0)
+            .show(ui, |ui| {
+                for (&id, name) in &self.tables.get(kind).custom {
+                    ui.horizontal(|ui| {
+                        if ui.small_button("✖").on_hover_text("Remove").clicked() {
+                            removed = Some(id);
+                        }
+                        ui.label(format!("{id} {name}"));
+                    });
+                }
+            });
+        if let Some(id) = removed {
+            self.tables.get_mut(kind).custom.remove(&id);
+            self.changed = true;
+        }
+
+        ui.separator();
+        let has_custom = !self.tables.get(kind).custom.is_empty();
+        if ui
+            .add_enabled(has_custom, egui::Button::new("Export custom names"))
+            .on_hover_text("Saves them as CSV and JSON")
+            .clicked()
+        {
+            self.status = Some(match self.export() {
+                Ok(path) => format!("Saved to {path}"),
+                Err(err) => format!("Export failed: {err}"),
+            });
+        }
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+    }
+}
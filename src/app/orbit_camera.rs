@@ -0,0 +1,87 @@
+use nalgebra_glm as glm;
+
+/// A camera orbiting a target point, at a distance relative to the one fitted to the model.
+/// Angles are in degrees and positions in the units models are uploaded in, 512 model units to
+/// one with y up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrbitCamera {
+    pub yaw: f32,
+    pub pitch: f32,
+    pub zoom: f32,
+    pub target: glm::Vec3,
+}
+
+impl OrbitCamera {
+    pub const DEFAULT_YAW: f32 = 90.0;
+    /// Stops short of looking straight down or up, where the view would flip.
+    const MAX_PITCH: f32 = 89.0;
+    /// Degrees turned per point dragged.
+    const ROTATE_SPEED: f32 = 0.3;
+
+    /// A camera orbiting the model origin.
+    pub fn new(yaw: f32, pitch: f32, zoom: f32) -> Self {
+        Self {
+            yaw,
+            pitch,
+            zoom,
+            target: glm::Vec3::zeros(),
+        }
+    }
+
+    /// The direction from the target to the camera.
+    pub fn front(&self) -> glm::Vec3 {
+        let (yaw, pitch) = (self.yaw.to_radians(), self.pitch.to_radians());
+        glm::normalize(&glm::vec3(
+            yaw.cos() * pitch.cos(),
+            pitch.sin(),
+            yaw.sin() * pitch.cos(),
+        ))
+    }
+
+    /// The camera position for a distance from the target.
+    pub fn eye(&self, distance: f32) -> glm::Vec3 {
+        self.target + self.front() * distance
+    }
+
+    /// Turns the camera around the target by a drag in points.
+    pub fn rotate(&mut self, drag: egui::Vec2) {
+        self.yaw += drag.x * Self::ROTATE_SPEED;
+        self.pitch =
+            (self.pitch + drag.y * Self::ROTATE_SPEED).clamp(-Self::MAX_PITCH, Self::MAX_PITCH);
+    }
+
+    /// Moves the target across the view by a drag in points, so the point under the pointer
+    /// follows it. `distance` is how far the camera is from the target, `viewport_height` the
+    /// height of the view in points and `field_of_view` and `roll` the view's angles in degrees.
+    pub fn pan(
+        &mut self,
+        drag: egui::Vec2,
+        distance: f32,
+        viewport_height: f32,
+        field_of_view: f32,
+        roll: f32,
+    ) {
+        // Undo the roll, so the drag is along the unrolled view's axes.
+        let (sin, cos) = roll.to_radians().sin_cos();
+        let drag = egui::vec2(drag.x * cos - drag.y * sin, drag.x * sin + drag.y * cos);
+        let forward = -self.front();
+        let right = glm::normalize(&glm::cross(&forward, &glm::vec3(0.0, 1.0, 0.0)));
+        let up = glm::cross(&right, &forward);
+        let view_height = 2.0 * distance * (field_of_view.to_radians() / 2.0).tan();
+        let units_per_point = view_height / viewport_height.max(1.0);
+        self.target += (up * drag.y - right * drag.x) * units_per_point;
+    }
+
+    /// Orbits `centre` instead, keeping the angles and distance.
+    pub fn focus(&mut self, centre: glm::Vec3) {
+        self.target = centre;
+    }
+
+    /// Faces the model from the default angle at the fitted distance, orbiting `centre`.
+    pub fn reset(&mut self, centre: glm::Vec3) {
+        *self = Self {
+            target: centre,
+            ..Self::new(Self::DEFAULT_YAW, 0.0, 1.0)
+        };
+    }
+}
@@ -0,0 +1,257 @@
+//! Extension points for decoders and windows kept out of the core modules, whether built in or
+//! from another crate. Register them on a [`PluginRegistry`] passed to
+//! [`ModelViewerApp::new`](crate::ModelViewerApp::new); archives they read are opened on startup.
+
+use std::{cell::Cell, sync::Arc};
+
+pub use super::hex_view::HexSection;
+pub use super::names::{NameKind, NameTable, NameTables};
+use super::{
+    hex_inspector::InspectorArchive, interface_viewer::InterfaceViewerWindow,
+    item_browser::ItemBrowserWindow, music_browser::MusicBrowserWindow,
+};
+use crate::runetek5::{
+    animation::seq::SeqType,
+    graphics::{model::ModelUnlit, texture::TextureProvider},
+    js5::Js5,
+    npc::NpcArchive,
+    obj::ObjArchive,
+};
+
+/// Archives the viewer opens for itself.
+pub const BUILTIN_ARCHIVE_IDS: [u8; 6] = [0, 1, 7, 8, 9, 13];
+
+/// Decodes the files of an archive, for the hex inspector.
+pub trait ArchiveDecoder {
+    /// Names the archive in the hex inspector.
+    fn name(&self) -> &'static str;
+
+    fn archive_id(&self) -> u8;
+
+    /// Splits a decompressed file into named sections, or none if it can't be decoded.
+    fn sections(&self, data: &[u8]) -> Vec<HexSection>;
+}
+
+/// A window of its own, shown alongside the built-in ones.
+pub trait PluginWindow {
+    fn title(&self) -> &'static str;
+
+    /// Archives the window reads through [`PluginContext::archive`].
+    fn archive_ids(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, ctx: &PluginContext<'_>);
+}
+
+/// What plugin windows get to see of the app each frame.
+pub struct PluginContext<'a> {
+    archives: &'a [InspectorArchive],
+    pub texture_provider: &'a TextureProvider,
+    /// Imported and custom names of models, NPCs, items and objects.
+    pub names: &'a NameTables,
+    /// The brightness models are rendered with.
+    pub brightness: f32,
+    selected_model_id: Cell<Option<u32>>,
+    recolours: Cell<Option<Vec<(u16, u16)>>>,
+}
+
+impl<'a> PluginContext<'a> {
+    pub(super) fn new(
+        archives: &'a [InspectorArchive],
+        texture_provider: &'a TextureProvider,
+        names: &'a NameTables,
+        brightness: f32,
+    ) -> Self {
+        Self {
+            archives,
+            texture_provider,
+            names,
+            brightness,
+            selected_model_id: Cell::new(None),
+            recolours: Cell::new(None),
+        }
+    }
+
+    /// An opened archive, either built in or requested by a plugin.
+    pub fn archive(&self, archive_id: u8) -> Option<&Arc<Js5>> {
+        self.archives
+            .iter()
+            .find(|archive| archive.archive_id == archive_id)
+            .map(|archive| &archive.js5)
+    }
+
+    /// Shows a model in the main viewport.
+    pub fn show_model(&self, model_id: u32) {
+        self.selected_model_id.set(Some(model_id));
+    }
+
+    /// Shows a model in the main viewport with its colours replaced, as (old, new) pairs, e.g.
+    /// like an item recolours its model.
+    pub fn show_recoloured_model(&self, model_id: u32, recolours: Vec<(u16, u16)>) {
+        self.selected_model_id.set(Some(model_id));
+        self.recolours.set(Some(recolours));
+    }
+
+    pub(super) fn selected_model_id(&self) -> Option<u32> {
+        self.selected_model_id.get()
+    }
+
+    pub(super) fn take_recolours(&self) -> Option<Vec<(u16, u16)>> {
+        self.recolours.take()
+    }
+}
+
+/// Decoders and windows added to the viewer, with the extra archives they read.
+pub struct PluginRegistry {
+    decoders: Vec<Box<dyn ArchiveDecoder>>,
+    windows: Vec<Box<dyn PluginWindow>>,
+    /// Archives built-in windows read if the cache has them, with their names.
+    optional_archives: Vec<(u8, &'static str)>,
+    archives: Vec<(u8, Arc<Js5>)>,
+}
+
+impl PluginRegistry {
+    pub fn new() -> Self {
+        Self {
+            decoders: Vec::new(),
+            windows: Vec::new(),
+            optional_archives: Vec::new(),
+            archives: Vec::new(),
+        }
+    }
+
+    /// A registry with the built-in decoders and windows.
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::new();
+        registry.register_decoder(Box::new(ModelDecoder));
+        registry.register_window(Box::new(InterfaceViewerWindow::new()));
+        registry.register_window(Box::new(MusicBrowserWindow::new()));
+        registry.register_window(Box::new(ItemBrowserWindow::new()));
+        // The animation player picks sequences from the config archive.
+        registry
+            .optional_archives
+            .push((SeqType::ARCHIVE_ID, "Configs"));
+        // Model info lists the NPCs and items using a model.
+        registry
+            .optional_archives
+            .push((NpcArchive::NPC_ARCHIVE_ID, "NPCs"));
+        registry
+            .optional_archives
+            .push((ObjArchive::OBJ_ARCHIVE_ID, "Items"));
+        registry
+    }
+
+    /// Registers a decoder, replacing any earlier one for the same archive.
+    pub fn register_decoder(&mut self, decoder: Box<dyn ArchiveDecoder>) {
+        self.decoders
+            .retain(|registered| registered.archive_id() != decoder.archive_id());
+        self.decoders.push(decoder);
+    }
+
+    pub fn register_window(&mut self, window: Box<dyn PluginWindow>) {
+        self.windows.push(window);
+    }
+
+    /// Archives read by the registered plugins that still have to be opened.
+    pub fn missing_archive_ids(&self) -> Vec<u8> {
+        let mut archive_ids: Vec<u8> = self
+            .decoders
+            .iter()
+            .map(|decoder| decoder.archive_id())
+            .chain(self.windows.iter().flat_map(|window| window.archive_ids()))
+            .chain(
+                self.optional_archives
+                    .iter()
+                    .map(|(archive_id, _)| *archive_id),
+            )
+            .filter(|archive_id| {
+                !BUILTIN_ARCHIVE_IDS.contains(archive_id)
+                    && !self.archives.iter().any(|(id, _)| id == archive_id)
+            })
+            .collect();
+        archive_ids.sort_unstable();
+        archive_ids.dedup();
+        archive_ids
+    }
+
+    /// Adds an archive opened for the plugins.
+    pub fn add_archive(&mut self, archive_id: u8, js5: Arc<Js5>) {
+        self.archives.push((archive_id, js5));
+    }
+
+    pub(super) fn take_archives(&mut self) -> Vec<(u8, Arc<Js5>)> {
+        std::mem::take(&mut self.archives)
+    }
+
+    /// Names an archive after the plugin reading it.
+    pub(super) fn archive_name(&self, archive_id: u8) -> &'static str {
+        self.decoder(archive_id)
+            .map(|decoder| decoder.name())
+            .or_else(|| {
+                self.windows
+                    .iter()
+                    .find(|window| window.archive_ids().contains(&archive_id))
+                    .map(|window| window.title())
+            })
+            .or_else(|| {
+                self.optional_archives
+                    .iter()
+                    .find(|(id, _)| *id == archive_id)
+                    .map(|(_, name)| *name)
+            })
+            .unwrap_or("Plugin archive")
+    }
+
+    pub(super) fn decoder(&self, archive_id: u8) -> Option<&dyn ArchiveDecoder> {
+        self.decoders
+            .iter()
+            .find(|decoder| decoder.archive_id() == archive_id)
+            .map(|decoder| decoder.as_ref())
+    }
+
+    pub(super) fn show_windows(&mut self, ctx: &egui::Context, plugin_ctx: &PluginContext<'_>) {
+        for window in &mut self.windows {
+            egui::Window::new(window.title())
+                .default_open(false)
+                .resizable(true)
+                .show(ctx, |ui| {
+                    window.ui(ui, plugin_ctx);
+                });
+        }
+    }
+}
+
+impl Default for PluginRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Annotates model files with the sections of their format.
+struct ModelDecoder;
+
+impl ArchiveDecoder for ModelDecoder {
+    fn name(&self) -> &'static str {
+        "Models"
+    }
+
+    fn archive_id(&self) -> u8 {
+        7
+    }
+
+    fn sections(&self, data: &[u8]) -> Vec<HexSection> {
+        if data.len() < 2 {
+            return Vec::new();
+        }
+        ModelUnlit::new()
+            .decode_annotated(data)
+            .0
+            .into_iter()
+            .map(|section| HexSection {
+                name: section.name.to_owned(),
+                range: section.range,
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,485 @@
+//! Optional effects for presentation renders of the main viewport: depth of field, a vignette
+//! and tone mapping. The scene is drawn into an offscreen target, blurred in two passes between a
+//! pair of ping-pong targets and composited into egui's framebuffer. While disabled none of this
+//! runs and the viewport is drawn exactly as before.
+
+use eframe::glow::{self, HasContext as _};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToneMapping {
+    None,
+    Reinhard,
+    /// Narkowicz's fit of the ACES filmic curve.
+    Aces,
+}
+
+impl ToneMapping {
+    pub const ALL: [ToneMapping; 3] = [ToneMapping::None, ToneMapping::Reinhard, ToneMapping::Aces];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ToneMapping::None => "None",
+            ToneMapping::Reinhard => "Reinhard",
+            ToneMapping::Aces => "ACES",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PostProcessSettings {
+    pub enabled: bool,
+    pub depth_of_field: bool,
+    /// Distance in focus, relative to the distance to the orbited target.
+    pub focus: f32,
+    /// How far either side of the focus things stay sharp, relative to the same distance.
+    pub focus_range: f32,
+    /// Blur radius in pixels of what's furthest out of focus.
+    pub blur_radius: f32,
+    /// How much the corners are darkened, 0 leaves them alone.
+    pub vignette: f32,
+    pub tone_mapping: ToneMapping,
+    /// Scales colours before they're tone mapped.
+    pub exposure: f32,
+}
+
+impl Default for PostProcessSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            depth_of_field: true,
+            focus: 1.0,
+            focus_range: 0.25,
+            blur_radius: 6.0,
+            vignette: 0.3,
+            tone_mapping: ToneMapping::None,
+            exposure: 1.0,
+        }
+    }
+}
+
+impl PostProcessSettings {
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.enabled, "Post-processing")
+            .on_hover_text("Effects for presentation renders, OpenGL renderer only");
+        ui.add_enabled_ui(self.enabled, |ui| {
+            ui.checkbox(&mut self.depth_of_field, "Depth of field");
+            ui.add_enabled_ui(self.depth_of_field, |ui| {
+                ui.add(egui::Slider::new(&mut self.focus, 0.1..=4.0).text("Focus"))
+                    .on_hover_text("Distance in focus, 1 is the orbited point");
+                ui.add(egui::Slider::new(&mut self.focus_range, 0.01..=2.0).text("Focus range"));
+                ui.add(egui::Slider::new(&mut self.blur_radius, 1.0..=16.0).text("Blur radius"));
+            });
+            ui.add(egui::Slider::new(&mut self.vignette, 0.0..=1.0).text("Vignette"));
+            egui::ComboBox::from_label("Tone mapping")
+                .selected_text(self.tone_mapping.label())
+                .show_ui(ui, |ui| {
+                    for tone_mapping in ToneMapping::ALL {
+                        ui.selectable_value(
+                            &mut self.tone_mapping,
+                            tone_mapping,
+                            tone_mapping.label(),
+                        );
+                    }
+                });
+            ui.add(egui::Slider::new(&mut self.exposure, 0.25..=4.0).text("Exposure"));
+        });
+    }
+}
+
+/// The camera's clip planes and the distance to the orbited target, to turn depth buffer values
+/// back into distances.
+#[derive(Debug, Clone, Copy)]
+pub struct ViewDepths {
+    pub near: f32,
+    pub far: f32,
+    pub target: f32,
+}
+
+struct RenderTarget {
+    framebuffer: glow::Framebuffer,
+    colour: glow::Texture,
+}
+
+/// Offscreen targets sized to the viewport, recreated when it's resized.
+struct Targets {
+    width: i32,
+    height: i32,
+    scene: RenderTarget,
+    depth: glow::Texture,
+    ping_pong: [RenderTarget; 2],
+}
+
+pub struct PostProcessor {
+    blur_program: glow::Program,
+    composite_program: glow::Program,
+    /// Bound while drawing the fullscreen triangle, which has no vertex attributes.
+    vertex_array: glow::VertexArray,
+    targets: Option<Targets>,
+}
+
+impl PostProcessor {
+    /// Samples either side of a pixel in each blur pass.
+    const BLUR_TAPS: i32 = 8;
+
+    const VERTEX_SHADER: &'static str = r#"
+        out vec2 v_uv;
+
+        void main() {
+            vec2 position = vec2(float((gl_VertexID & 1) << 2), float((gl_VertexID & 2) << 1)) - 1.0;
+            v_uv = position * 0.5 + 0.5;
+            gl_Position = vec4(position, 0.0, 1.0);
+        }
+    "#;
+
+    const BLUR_SHADER: &'static str = r#"
+        precision highp float;
+
+        uniform sampler2D u_colour;
+        uniform sampler2D u_depth;
+        // One pixel along the blurred axis.
+        uniform vec2 u_direction;
+        uniform float u_near;
+        uniform float u_far;
+        uniform float u_focus;
+        uniform float u_focus_range;
+        uniform float u_blur_radius;
+        uniform int u_taps;
+
+        in vec2 v_uv;
+
+        out vec4 out_color;
+
+        float distanceAt(vec2 uv) {
+            float z = texture(u_depth, uv).r * 2.0 - 1.0;
+            return 2.0 * u_near * u_far / (u_far + u_near - z * (u_far - u_near));
+        }
+
+        float blurAt(vec2 uv) {
+            return clamp(abs(distanceAt(uv) - u_focus) / u_focus_range, 0.0, 1.0) * u_blur_radius;
+        }
+
+        void main() {
+            float radius = blurAt(v_uv);
+            vec4 sum = vec4(0.0);
+            float weight = 0.0;
+            for (int i = -u_taps; i <= u_taps; i++) {
+                float offset = radius * float(i) / float(u_taps);
+                vec2 uv = v_uv + u_direction * offset;
+                // Only samples blurred as far as this pixel reach it, so sharp edges don't bleed
+                // into what's behind them.
+                float w = clamp(blurAt(uv) - abs(offset) + 1.0, 0.0, 1.0);
+                sum += texture(u_colour, uv) * w;
+                weight += w;
+            }
+            out_color = sum / weight;
+        }
+    "#;
+
+    const COMPOSITE_SHADER: &'static str = r#"
+        precision highp float;
+
+        uniform sampler2D u_colour;
+        uniform float u_vignette;
+        uniform int u_tone_mapping;
+        uniform float u_exposure;
+
+        in vec2 v_uv;
+
+        out vec4 out_color;
+
+        vec3 toneMap(vec3 rgb) {
+            rgb *= u_exposure;
+            if (u_tone_mapping == 1) {
+                return rgb / (1.0 + rgb);
+            }
+            if (u_tone_mapping == 2) {
+                return clamp(rgb * (2.51 * rgb + 0.03) / (rgb * (2.43 * rgb + 0.59) + 0.14), 0.0, 1.0);
+            }
+            return min(rgb, 1.0);
+        }
+
+        void main() {
+            // Colours are premultiplied, the scene was blended onto a transparent background.
+            vec4 colour = texture(u_colour, v_uv);
+            if (colour.a > 0.0) {
+                colour.rgb = toneMap(colour.rgb / colour.a) * colour.a;
+            }
+            float shade = 1.0 - u_vignette * smoothstep(0.4, 1.0, length(v_uv - 0.5) * 1.4142);
+            // Darkens whatever egui drew behind the scene too.
+            out_color = vec4(colour.rgb * shade, 1.0 - (1.0 - colour.a) * shade);
+        }
+    "#;
+
+    pub fn new(gl: &glow::Context) -> Self {
+        unsafe {
+            Self {
+                blur_program: compile_program(gl, Self::VERTEX_SHADER, Self::BLUR_SHADER),
+                composite_program: compile_program(gl, Self::VERTEX_SHADER, Self::COMPOSITE_SHADER),
+                vertex_array: gl
+                    .create_vertex_array()
+                    .expect("Cannot create vertex array"),
+                targets: None,
+            }
+        }
+    }
+
+    /// Binds a cleared offscreen target the size of the callback's viewport, for the scene to be
+    /// drawn into instead of egui's framebuffer.
+    pub fn begin(&mut self, gl: &glow::Context, info: &egui::PaintCallbackInfo) {
+        let viewport = info.viewport_in_pixels();
+        let (width, height) = (viewport.width_px.max(1), viewport.height_px.max(1));
+        let resized = self
+            .targets
+            .as_ref()
+            .is_some_and(|targets| (targets.width, targets.height) != (width, height));
+        if resized {
+            unsafe { self.targets.take().unwrap().destroy(gl) };
+        }
+        let targets = self
+            .targets
+            .get_or_insert_with(|| unsafe { Targets::create(gl, width, height) });
+        unsafe {
+            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(targets.scene.framebuffer));
+            gl.viewport(0, 0, width, height);
+            // egui's scissor rect is in its own framebuffer's coordinates.
+            gl.disable(glow::SCISSOR_TEST);
+            gl.clear_color(0.0, 0.0, 0.0, 0.0);
+            gl.clear(glow::COLOR_BUFFER_BIT | glow::DEPTH_BUFFER_BIT);
+        }
+    }
+
+    /// Runs the enabled passes over the scene drawn since [`PostProcessor::begin`] and blends the
+    /// result into `target`, the framebuffer egui is painting, within the callback's viewport.
+    pub fn finish(
+        &self,
+        gl: &glow::Context,
+        info: &egui::PaintCallbackInfo,
+        target: Option<glow::Framebuffer>,
+        settings: &PostProcessSettings,
+        depths: ViewDepths,
+    ) {
+        let Some(targets) = &self.targets else {
+            return;
+        };
+        unsafe {
+            gl.disable(glow::DEPTH_TEST);
+            gl.disable(glow::CULL_FACE);
+            gl.bind_vertex_array(Some(self.vertex_array));
+
+            let mut colour = targets.scene.colour;
+            if settings.depth_of_field {
+                gl.disable(glow::BLEND);
+                let program = self.blur_program;
+                gl.use_program(Some(program));
+                let uniform = |name: &str| gl.get_uniform_location(program, name);
+                gl.uniform_1_i32(uniform("u_colour").as_ref(), 0);
+                gl.uniform_1_i32(uniform("u_depth").as_ref(), 1);
+                gl.uniform_1_f32(uniform("u_near").as_ref(), depths.near);
+                gl.uniform_1_f32(uniform("u_far").as_ref(), depths.far);
+                gl.uniform_1_f32(uniform("u_focus").as_ref(), depths.target * settings.focus);
+                gl.uniform_1_f32(
+                    uniform("u_focus_range").as_ref(),
+                    (depths.target * settings.focus_range).max(0.001),
+                );
+                gl.uniform_1_f32(uniform("u_blur_radius").as_ref(), settings.blur_radius);
+                gl.uniform_1_i32(uniform("u_taps").as_ref(), Self::BLUR_TAPS);
+                gl.active_texture(glow::TEXTURE1);
+                gl.bind_texture(glow::TEXTURE_2D, Some(targets.depth));
+                let directions = [
+                    (1.0 / targets.width as f32, 0.0),
+                    (0.0, 1.0 / targets.height as f32),
+                ];
+                for (pass, (x, y)) in targets.ping_pong.iter().zip(directions) {
+                    gl.bind_framebuffer(glow::FRAMEBUFFER, Some(pass.framebuffer));
+                    gl.active_texture(glow::TEXTURE0);
+                    gl.bind_texture(glow::TEXTURE_2D, Some(colour));
+                    gl.uniform_2_f32(uniform("u_direction").as_ref(), x, y);
+                    gl.draw_arrays(glow::TRIANGLES, 0, 3);
+                    colour = pass.colour;
+                }
+            }
+
+            let viewport = info.viewport_in_pixels();
+            let clip = info.clip_rect_in_pixels();
+            gl.bind_framebuffer(glow::FRAMEBUFFER, target);
+            gl.viewport(
+                viewport.left_px,
+                viewport.from_bottom_px,
+                viewport.width_px,
+                viewport.height_px,
+            );
+            gl.enable(glow::SCISSOR_TEST);
+            gl.scissor(
+                clip.left_px,
+                clip.from_bottom_px,
+                clip.width_px,
+                clip.height_px,
+            );
+            gl.enable(glow::BLEND);
+            gl.blend_func(glow::ONE, glow::ONE_MINUS_SRC_ALPHA);
+
+            let program = self.composite_program;
+            gl.use_program(Some(program));
+            let uniform = |name: &str| gl.get_uniform_location(program, name);
+            gl.uniform_1_i32(uniform("u_colour").as_ref(), 0);
+            gl.uniform_1_f32(uniform("u_vignette").as_ref(), settings.vignette);
+            gl.uniform_1_i32(
+                uniform("u_tone_mapping").as_ref(),
+                settings.tone_mapping as i32,
+            );
+            gl.uniform_1_f32(uniform("u_exposure").as_ref(), settings.exposure);
+            gl.active_texture(glow::TEXTURE0);
+            gl.bind_texture(glow::TEXTURE_2D, Some(colour));
+            gl.draw_arrays(glow::TRIANGLES, 0, 3);
+        }
+    }
+}
+
+impl RenderTarget {
+    unsafe fn create(gl: &glow::Context, width: i32, height: i32) -> Self {
+        let colour = create_texture(
+            gl,
+            width,
+            height,
+            glow::RGBA8,
+            glow::RGBA,
+            glow::UNSIGNED_BYTE,
+            glow::LINEAR,
+        );
+        let framebuffer = gl.create_framebuffer().expect("Cannot create framebuffer");
+        gl.bind_framebuffer(glow::FRAMEBUFFER, Some(framebuffer));
+        gl.framebuffer_texture_2d(
+            glow::FRAMEBUFFER,
+            glow::COLOR_ATTACHMENT0,
+            glow::TEXTURE_2D,
+            Some(colour),
+            0,
+        );
+        Self {
+            framebuffer,
+            colour,
+        }
+    }
+
+    unsafe fn destroy(self, gl: &glow::Context) {
+        gl.delete_framebuffer(self.framebuffer);
+        gl.delete_texture(self.colour);
+    }
+}
+
+impl Targets {
+    unsafe fn create(gl: &glow::Context, width: i32, height: i32) -> Self {
+        let scene = RenderTarget::create(gl, width, height);
+        // Depth textures can't be filtered in GLES and WebGL2.
+        let depth = create_texture(
+            gl,
+            width,
+            height,
+            glow::DEPTH_COMPONENT24,
+            glow::DEPTH_COMPONENT,
+            glow::UNSIGNED_INT,
+            glow::NEAREST,
+        );
+        gl.framebuffer_texture_2d(
+            glow::FRAMEBUFFER,
+            glow::DEPTH_ATTACHMENT,
+            glow::TEXTURE_2D,
+            Some(depth),
+            0,
+        );
+        Self {
+            width,
+            height,
+            scene,
+            depth,
+            ping_pong: [
+                RenderTarget::create(gl, width, height),
+                RenderTarget::create(gl, width, height),
+            ],
+        }
+    }
+
+    unsafe fn destroy(self, gl: &glow::Context) {
+        self.scene.destroy(gl);
+        gl.delete_texture(self.depth);
+        for target in self.ping_pong {
+            target.destroy(gl);
+        }
+    }
+}
+
+unsafe fn create_texture(
+    gl: &glow::Context,
+    width: i32,
+    height: i32,
+    internal_format: u32,
+    format: u32,
+    ty: u32,
+    filter: u32,
+) -> glow::Texture {
+    let texture = gl.create_texture().expect("Cannot create texture");
+    gl.active_texture(glow::TEXTURE0);
+    gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+    gl.tex_image_2d(
+        glow::TEXTURE_2D,
+        0,
+        internal_format as i32,
+        width,
+        height,
+        0,
+        format,
+        ty,
+        glow::PixelUnpackData::Slice(None),
+    );
+    gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MIN_FILTER, filter as i32);
+    gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MAG_FILTER, filter as i32);
+    gl.tex_parameter_i32(
+        glow::TEXTURE_2D,
+        glow::TEXTURE_WRAP_S,
+        glow::CLAMP_TO_EDGE as i32,
+    );
+    gl.tex_parameter_i32(
+        glow::TEXTURE_2D,
+        glow::TEXTURE_WRAP_T,
+        glow::CLAMP_TO_EDGE as i32,
+    );
+    texture
+}
+
+unsafe fn compile_program(gl: &glow::Context, vertex: &str, fragment: &str) -> glow::Program {
+    let shader_version = if cfg!(target_arch = "wasm32") {
+        "#version 300 es"
+    } else {
+        "#version 330"
+    };
+    let program = gl.create_program().expect("Cannot create program");
+    let shaders: Vec<_> = [
+        (glow::VERTEX_SHADER, vertex),
+        (glow::FRAGMENT_SHADER, fragment),
+    ]
+    .into_iter()
+    .map(|(shader_type, source)| {
+        let shader = gl.create_shader(shader_type).expect("Cannot create shader");
+        gl.shader_source(shader, &format!("{shader_version}\n{source}"));
+        gl.compile_shader(shader);
+        assert!(
+            gl.get_shader_compile_status(shader),
+            "Failed to compile {shader_type}: {}",
+            gl.get_shader_info_log(shader)
+        );
+        gl.attach_shader(program, shader);
+        shader
+    })
+    .collect();
+    gl.link_program(program);
+    assert!(
+        gl.get_program_link_status(program),
+        "{}",
+        gl.get_program_info_log(program)
+    );
+    for shader in shaders {
+        gl.detach_shader(program, shader);
+        gl.delete_shader(shader);
+    }
+    program
+}
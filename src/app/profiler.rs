@@ -0,0 +1,195 @@
+use std::collections::VecDeque;
+
+use super::save::save_files;
+use crate::export::ExportFile;
+use crate::trace;
+
+/// The stages a model goes through between being selected and being drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadStage {
+    /// Waiting for the packed group, from the provider or the network.
+    Fetch,
+    Decompress,
+    Decode,
+    Light,
+    /// Building and submitting the GL buffers, GPU work is not included.
+    Upload,
+}
+
+impl LoadStage {
+    pub const ALL: [LoadStage; 5] = [
+        LoadStage::Fetch,
+        LoadStage::Decompress,
+        LoadStage::Decode,
+        LoadStage::Light,
+        LoadStage::Upload,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            LoadStage::Fetch => "Fetch",
+            LoadStage::Decompress => "Decompress",
+            LoadStage::Decode => "Decode",
+            LoadStage::Light => "Light",
+            LoadStage::Upload => "Upload",
+        }
+    }
+}
+
+/// Stage timings of one model load, in milliseconds.
+#[derive(Debug, Clone)]
+pub struct ModelLoadTimings {
+    pub model_id: u32,
+    pub stages: [f64; LoadStage::ALL.len()],
+}
+
+impl ModelLoadTimings {
+    pub fn new(model_id: u32) -> Self {
+        Self {
+            model_id,
+            stages: [0.0; LoadStage::ALL.len()],
+        }
+    }
+
+    pub fn set(&mut self, stage: LoadStage, ms: f64) {
+        self.stages[stage as usize] = ms;
+    }
+
+    pub fn total(&self) -> f64 {
+        self.stages.iter().sum()
+    }
+}
+
+/// Keeps the timings of recent model loads and shows them with per-stage aggregates.
+pub struct ProfilerWindow {
+    records: VecDeque<ModelLoadTimings>,
+    saved_traces: u32,
+    status: Option<String>,
+}
+
+impl ProfilerWindow {
+    const MAX_RECORDS: usize = 256;
+    const SHOWN_RECORDS: usize = 20;
+
+    pub fn new() -> Self {
+        Self {
+            records: VecDeque::new(),
+            saved_traces: 0,
+            status: None,
+        }
+    }
+
+    pub fn record(&mut self, timings: ModelLoadTimings) {
+        if self.records.len() == Self::MAX_RECORDS {
+            self.records.pop_front();
+        }
+        self.records.push_back(timings);
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Load Profiler")
+            .default_open(false)
+            .resizable(true)
+            .show(ctx, |ui| {
+                self.ui(ui);
+            });
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        self.trace_ui(ui);
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            ui.label(format!("{} loads recorded", self.records.len()));
+            if ui.button("Clear").clicked() {
+                self.records.clear();
+            }
+        });
+        if self.records.is_empty() {
+            return;
+        }
+
+        ui.separator();
+        ui.strong("Aggregate (ms)");
+        egui::Grid::new("profiler_aggregate")
+            .num_columns(5)
+            .striped(true)
+            .show(ui, |ui| {
+                for header in ["Stage", "Mean", "Median", "Max", "Share"] {
+                    ui.strong(header);
+                }
+                ui.end_row();
+                let total: f64 = self.records.iter().map(ModelLoadTimings::total).sum();
+                for stage in LoadStage::ALL {
+                    let mut values: Vec<f64> = self
+                        .records
+                        .iter()
+                        .map(|record| record.stages[stage as usize])
+                        .collect();
+                    values.sort_by(f64::total_cmp);
+                    let sum: f64 = values.iter().sum();
+                    ui.label(stage.label());
+                    ui.monospace(format!("{:.2}", sum / values.len() as f64));
+                    ui.monospace(format!("{:.2}", values[values.len() / 2]));
+                    ui.monospace(format!("{:.2}", values[values.len() - 1]));
+                    ui.monospace(format!("{:.0}%", sum * 100.0 / total.max(f64::EPSILON)));
+                    ui.end_row();
+                }
+            });
+
+        ui.separator();
+        ui.strong("Recent loads (ms)");
+        egui::Grid::new("profiler_recent")
+            .num_columns(LoadStage::ALL.len() + 2)
+            .striped(true)
+            .show(ui, |ui| {
+                ui.strong("Model");
+                for stage in LoadStage::ALL {
+                    ui.strong(stage.label());
+                }
+                ui.strong("Total");
+                ui.end_row();
+                for record in self.records.iter().rev().take(Self::SHOWN_RECORDS) {
+                    ui.label(record.model_id.to_string());
+                    for value in record.stages {
+                        ui.monospace(format!("{value:.2}"));
+                    }
+                    ui.monospace(format!("{:.2}", record.total()));
+                    ui.end_row();
+                }
+            });
+    }
+
+    /// Controls for recording a chrome://tracing session of the instrumented spans.
+    fn trace_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let mut recording = trace::is_recording();
+            if ui.checkbox(&mut recording, "Record trace").changed() {
+                trace::set_recording(recording);
+            }
+            let event_count = trace::event_count();
+            ui.label(format!("{event_count} events"));
+            if ui.button("Clear trace").clicked() {
+                trace::clear();
+                self.status = None;
+            }
+            let save = ui.add_enabled(event_count > 0, egui::Button::new("Save trace"));
+            if save.clicked() {
+                let file = ExportFile {
+                    path: format!("trace_{}.json", self.saved_traces),
+                    data: trace::to_chrome_json().into_bytes(),
+                };
+                self.status = Some(match save_files("traces", vec![file]) {
+                    Ok(location) => {
+                        self.saved_traces += 1;
+                        format!("Saved to {location}, open it in chrome://tracing")
+                    }
+                    Err(err) => format!("Export failed: {err}"),
+                });
+            }
+        });
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+    }
+}
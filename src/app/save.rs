@@ -0,0 +1,149 @@
+use crate::export::ExportFile;
+
+/// Folder chosen with [`choose_export_root`], exports go to `./exports` until one is chosen.
+#[cfg(not(target_arch = "wasm32"))]
+static EXPORT_ROOT: std::sync::Mutex<Option<std::path::PathBuf>> = std::sync::Mutex::new(None);
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn export_root() -> std::path::PathBuf {
+    EXPORT_ROOT
+        .lock()
+        .unwrap()
+        .clone()
+        .unwrap_or_else(|| std::path::PathBuf::from("exports"))
+}
+
+/// Asks for the folder exports are saved to, keeping the current one if the dialog is cancelled.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn choose_export_root() {
+    if let Some(folder) = rfd::FileDialog::new()
+        .set_title("Export destination")
+        .set_directory(export_root())
+        .pick_folder()
+    {
+        *EXPORT_ROOT.lock().unwrap() = Some(folder);
+    }
+}
+
+/// Asks for the directory of a local cache, the one holding `main_file_cache.dat2`.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn pick_cache_dir() -> Option<std::path::PathBuf> {
+    rfd::FileDialog::new()
+        .set_title("Open cache folder")
+        .pick_folder()
+}
+
+/// Asks for a file to open, filtered to the given extensions.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn pick_file(
+    title: &str,
+    filter_name: &str,
+    extensions: &[&str],
+) -> Option<std::path::PathBuf> {
+    rfd::FileDialog::new()
+        .set_title(title)
+        .add_filter(filter_name, extensions)
+        .pick_file()
+}
+
+/// Saves exported files into a directory named `dir_name` below the export root, returning where
+/// they were saved.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_files(dir_name: &str, files: Vec<ExportFile>) -> Result<String, String> {
+    let root = export_root().join(dir_name);
+    match crate::export::write_files(&root, &files) {
+        Ok(()) => Ok(root.display().to_string()),
+        Err(err) => {
+            log::error!("Failed to write export {dir_name}: {err}");
+            Err(err.to_string())
+        }
+    }
+}
+
+/// Asks where to save a single file, starting in the export root, and writes it there. Returns
+/// None if the dialog is cancelled.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_file_as(
+    title: &str,
+    filter_name: &str,
+    file: ExportFile,
+) -> Option<Result<String, String>> {
+    let extension = file.path.rsplit('.').next().unwrap_or_default();
+    let path = rfd::FileDialog::new()
+        .set_title(title)
+        .set_directory(export_root())
+        .set_file_name(&file.path)
+        .add_filter(filter_name, &[extension])
+        .save_file()?;
+    Some(match std::fs::write(&path, &file.data) {
+        Ok(()) => Ok(path.display().to_string()),
+        Err(err) => {
+            log::error!("Failed to write {}: {err}", path.display());
+            Err(err.to_string())
+        }
+    })
+}
+
+/// Offers a single file as a browser download, the browser asks where to save it if it's set to.
+#[cfg(target_arch = "wasm32")]
+pub fn save_file_as(
+    _title: &str,
+    _filter_name: &str,
+    file: ExportFile,
+) -> Option<Result<String, String>> {
+    Some(save_files("download", vec![file]))
+}
+
+/// Offers the exported files as a browser download, packed into `{dir_name}.zip` unless there is
+/// only a single file.
+#[cfg(target_arch = "wasm32")]
+pub fn save_files(dir_name: &str, mut files: Vec<ExportFile>) -> Result<String, String> {
+    let (file_name, data, mime_type) = if files.len() == 1 {
+        let file = files.pop().unwrap();
+        let file_name = file
+            .path
+            .rsplit('/')
+            .next()
+            .unwrap_or(&file.path)
+            .to_owned();
+        (file_name, file.data, "application/octet-stream")
+    } else {
+        let data = crate::export::zip::encode_zip(&files)?;
+        (format!("{dir_name}.zip"), data, "application/zip")
+    };
+    match download(&file_name, &data, mime_type) {
+        Ok(()) => Ok(file_name),
+        Err(err) => {
+            log::error!("Failed to download export {dir_name}: {err:?}");
+            Err(format!("download failed: {err:?}"))
+        }
+    }
+}
+
+/// Triggers a download of `data` through a temporary object URL.
+#[cfg(target_arch = "wasm32")]
+fn download(file_name: &str, data: &[u8], mime_type: &str) -> Result<(), wasm_bindgen::JsValue> {
+    use wasm_bindgen::{closure::Closure, JsCast as _};
+    use web_sys::js_sys::{Array, Uint8Array};
+
+    let window = web_sys::window().ok_or("no window")?;
+    let document = window.document().ok_or("no document")?;
+
+    let parts = Array::of1(&Uint8Array::from(data));
+    let options = web_sys::BlobPropertyBag::new();
+    options.set_type(mime_type);
+    let blob = web_sys::Blob::new_with_u8_array_sequence_and_options(&parts, &options)?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob)?;
+
+    let anchor: web_sys::HtmlAnchorElement = document.create_element("a")?.dyn_into()?;
+    anchor.set_href(&url);
+    anchor.set_download(file_name);
+    anchor.click();
+
+    // Revoking right away can cancel the download in some browsers.
+    let revoke = Closure::once_into_js(move || {
+        let _ = web_sys::Url::revoke_object_url(&url);
+    });
+    window.set_timeout_with_callback_and_timeout_and_arguments_0(revoke.unchecked_ref(), 10_000)?;
+    Ok(())
+}
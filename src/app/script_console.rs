@@ -0,0 +1,488 @@
+//! Scripts automating the viewer, written in [Rhai](https://rhai.rs), e.g.
+//!
+//! ```text
+//! // Show model 123 with its red turned blue.
+//! show_model(123);
+//! recolour(933, 43968);
+//! ```
+//!
+//! Scripts read the model archive as `models` and queue commands for the app, which applies them
+//! once the script finished. A script failing part way queues nothing, so it can't leave the
+//! viewer half done.
+
+use std::{cell::RefCell, ops::Range, ops::RangeInclusive, rc::Rc, sync::Arc};
+
+use rhai::{Array, Blob, Dynamic, Engine, EvalAltResult, Scope, INT};
+
+use super::save::save_files;
+use crate::export::ExportFile;
+use crate::runetek5::graphics::model::ModelUnlit;
+use crate::runetek5::js5::Js5;
+
+/// A command queued by a script, applied by the app in script order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptCommand {
+    /// Opens an OpenRS2 cache by id.
+    Cache(u32),
+    /// Shows a model in the viewport.
+    Model(u32),
+    /// Merges the models into one and shows it in the viewport.
+    Merge(Vec<u32>),
+    /// Replaces an HSL colour in models shown in the viewport from now on.
+    Recolour { old_colour: u16, new_colour: u16 },
+    /// Clears the recolours.
+    Reset,
+    /// Lists the models in the selector.
+    Filter(Vec<u32>),
+    /// Exports the models as a dataset of thumbnails.
+    Export(Vec<u32>),
+}
+
+struct CommandHelp {
+    usage: &'static str,
+    description: &'static str,
+}
+
+const COMMANDS: [CommandHelp; 15] = [
+    CommandHelp {
+        usage: "open_cache(id)",
+        description: "Open an OpenRS2 cache, reloading the viewer",
+    },
+    CommandHelp {
+        usage: "show_model(id)",
+        description: "Show a model",
+    },
+    CommandHelp {
+        usage: "merge_models(ids)",
+        description: "Merge models into one and show it",
+    },
+    CommandHelp {
+        usage: "recolour(old, new)",
+        description: "Replace an HSL colour in the shown models",
+    },
+    CommandHelp {
+        usage: "reset_recolours()",
+        description: "Undo all recolours",
+    },
+    CommandHelp {
+        usage: "filter_models(ids)",
+        description: "List models in the selector",
+    },
+    CommandHelp {
+        usage: "export_dataset(ids)",
+        description: "Export models as a thumbnail dataset",
+    },
+    CommandHelp {
+        usage: "models.group_ids()",
+        description: "The ids of the model groups",
+    },
+    CommandHelp {
+        usage: "models.is_valid(id)",
+        description: "Whether the model exists",
+    },
+    CommandHelp {
+        usage: "models.model(id)",
+        description: "Decode a model, () if it isn't loaded or fails to decode",
+    },
+    CommandHelp {
+        usage: "model.colours",
+        description: "The distinct HSL colours of a model's triangles",
+    },
+    CommandHelp {
+        usage: "model.recolour(old, new)",
+        description: "Replace an HSL colour in the decoded model",
+    },
+    CommandHelp {
+        usage: "model.encode()",
+        description: "Encode the model in the cache format, as a blob",
+    },
+    CommandHelp {
+        usage: "export_file(path, blob)",
+        description: "A file to save, at a path relative to the export folder",
+    },
+    CommandHelp {
+        usage: "save_files(name, files)",
+        description: "Save the files into an export folder, or download them on the web",
+    },
+];
+
+const EXAMPLES: [(&str, &str); 5] = [
+    (
+        "Recolour a model",
+        "// Show model 0 with its colour 933 replaced.\nshow_model(0);\nrecolour(933, 43968);\n",
+    ),
+    (
+        "Merge a kit",
+        "// Merge a few models, like the client does for a player.\n\
+         merge_models([230, 249, 292, 151]);\n",
+    ),
+    (
+        "Export a range",
+        "// List the first hundred models and export them.\n\
+         filter_models(0..100);\nexport_dataset(0..100);\n",
+    ),
+    (
+        "Find a colour",
+        "// List the loaded models using colour 933.\n\
+         let found = [];\n\
+         for id in models.group_ids() {\n    \
+             let model = models.model(id);\n    \
+             if model != () && model.colours.contains(933) {\n        \
+                 found.push(id);\n    \
+             }\n\
+         }\n\
+         print(`${found.len()} models use colour 933`);\n\
+         filter_models(found);\n",
+    ),
+    (
+        "Save model data",
+        "// Save the encoded data of the first ten models.\n\
+         let files = [];\n\
+         for id in 0..10 {\n    \
+             let model = models.model(id);\n    \
+             if model != () {\n        \
+                 files.push(export_file(`${id}.dat`, model.encode()));\n    \
+             }\n\
+         }\n\
+         print(save_files(\"script\", files));\n",
+    ),
+];
+
+/// Ids lists and ranges are expanded, so cap them before they take all memory.
+const MAX_IDS: usize = 100_000;
+
+/// Operations a script may run, so an endless loop stops instead of freezing the viewer.
+const MAX_OPERATIONS: u64 = 50_000_000;
+
+fn parse_id(id: INT) -> Result<u32, String> {
+    u32::try_from(id).map_err(|_| format!("id {id} is out of range"))
+}
+
+fn parse_dynamic_id(value: Dynamic) -> Result<u32, String> {
+    let id = value
+        .as_int()
+        .map_err(|type_name| format!("expected an id, got {type_name}"))?;
+    parse_id(id)
+}
+
+fn parse_colour(colour: INT) -> Result<u16, String> {
+    u16::try_from(colour).map_err(|_| format!("colour {colour} is not 16 bit"))
+}
+
+/// Adds the inclusive range to the ids, failing if it's empty or the ids would be too many.
+fn push_range(ids: &mut Vec<u32>, start: u32, end: u32) -> Result<(), String> {
+    let Some(span) = end.checked_sub(start) else {
+        return Err(format!("empty range {start}..={end}"));
+    };
+    let count = span
+        .checked_add(1)
+        .and_then(|count| count.checked_add(ids.len() as u32));
+    if count.map_or(true, |count| count as usize > MAX_IDS) {
+        return Err(format!("more than {MAX_IDS} ids"));
+    }
+    ids.extend(start..=end);
+    Ok(())
+}
+
+/// Reads ids from an id, an array of ids or a range such as `0..100` or `0..=99`.
+fn parse_ids(value: Dynamic) -> Result<Vec<u32>, String> {
+    let mut ids = Vec::new();
+    if value.is_array() {
+        for item in value.into_array()? {
+            let id = parse_dynamic_id(item)?;
+            push_range(&mut ids, id, id)?;
+        }
+    } else if let Some(range) = value.clone().try_cast::<Range<INT>>() {
+        if range.is_empty() {
+            return Err(format!("empty range {range:?}"));
+        }
+        push_range(&mut ids, parse_id(range.start)?, parse_id(range.end - 1)?)?;
+    } else if let Some(range) = value.clone().try_cast::<RangeInclusive<INT>>() {
+        push_range(&mut ids, parse_id(*range.start())?, parse_id(*range.end())?)?;
+    } else if let Ok(id) = value.as_int() {
+        ids.push(parse_id(id)?);
+    } else {
+        return Err(format!("expected ids, got {}", value.type_name()));
+    }
+    if ids.is_empty() {
+        return Err("expected ids".to_owned());
+    }
+    Ok(ids)
+}
+
+/// The engine scripts run in, queueing their commands and printed lines.
+fn script_engine(
+    commands: &Rc<RefCell<Vec<ScriptCommand>>>,
+    printed: &Rc<RefCell<Vec<String>>>,
+) -> Engine {
+    type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+
+    let printed = printed.clone();
+    engine.on_print(move |text| printed.borrow_mut().push(text.to_owned()));
+
+    let queue = |parse: fn(Dynamic) -> Result<ScriptCommand, String>| {
+        let commands = commands.clone();
+        move |value: Dynamic| -> ScriptResult<()> {
+            commands.borrow_mut().push(parse(value)?);
+            Ok(())
+        }
+    };
+    engine
+        .register_fn(
+            "open_cache",
+            queue(|id| Ok(ScriptCommand::Cache(parse_dynamic_id(id)?))),
+        )
+        .register_fn(
+            "show_model",
+            queue(|id| Ok(ScriptCommand::Model(parse_dynamic_id(id)?))),
+        )
+        .register_fn(
+            "merge_models",
+            queue(|ids| Ok(ScriptCommand::Merge(parse_ids(ids)?))),
+        )
+        .register_fn(
+            "filter_models",
+            queue(|ids| Ok(ScriptCommand::Filter(parse_ids(ids)?))),
+        )
+        .register_fn(
+            "export_dataset",
+            queue(|ids| Ok(ScriptCommand::Export(parse_ids(ids)?))),
+        );
+    let recolour_commands = commands.clone();
+    engine.register_fn(
+        "recolour",
+        move |old_colour: INT, new_colour: INT| -> ScriptResult<()> {
+            recolour_commands
+                .borrow_mut()
+                .push(ScriptCommand::Recolour {
+                    old_colour: parse_colour(old_colour)?,
+                    new_colour: parse_colour(new_colour)?,
+                });
+            Ok(())
+        },
+    );
+    let reset_commands = commands.clone();
+    engine.register_fn("reset_recolours", move || {
+        reset_commands.borrow_mut().push(ScriptCommand::Reset);
+    });
+
+    engine
+        .register_type_with_name::<Arc<Js5>>("Js5")
+        .register_get("group_count", |js5: &mut Arc<Js5>| {
+            js5.get_group_count() as INT
+        })
+        .register_fn("group_ids", |js5: &mut Arc<Js5>| -> Array {
+            js5.index
+                .group_ids
+                .iter()
+                .map(|&id| Dynamic::from_int(id as INT))
+                .collect()
+        })
+        .register_fn("is_valid", |js5: &mut Arc<Js5>, id: INT| {
+            u32::try_from(id).is_ok_and(|id| js5.is_group_valid(id))
+        })
+        .register_fn(
+            "model",
+            |js5: &mut Arc<Js5>, id: INT| -> ScriptResult<Dynamic> {
+                let model = ModelUnlit::from_js5(js5, parse_id(id)?, 0);
+                Ok(model.map_or(Dynamic::UNIT, Dynamic::from))
+            },
+        );
+
+    engine
+        .register_type_with_name::<ModelUnlit>("Model")
+        .register_get("version", |model: &mut ModelUnlit| model.version as INT)
+        .register_get("vertex_count", |model: &mut ModelUnlit| {
+            model.vertex_count as INT
+        })
+        .register_get("triangle_count", |model: &mut ModelUnlit| {
+            model.triangle_count as INT
+        })
+        .register_get("colours", |model: &mut ModelUnlit| -> Array {
+            let mut colours = model.triangle_colour.clone();
+            colours.sort_unstable();
+            colours.dedup();
+            colours
+                .into_iter()
+                .map(|colour| Dynamic::from_int(colour as INT))
+                .collect()
+        })
+        .register_fn(
+            "recolour",
+            |model: &mut ModelUnlit, old_colour: INT, new_colour: INT| -> ScriptResult<()> {
+                model.recolour(parse_colour(old_colour)?, parse_colour(new_colour)?);
+                Ok(())
+            },
+        )
+        .register_fn("encode", |model: &mut ModelUnlit| -> Blob {
+            model.encode()
+        });
+
+    engine
+        .register_type_with_name::<ExportFile>("ExportFile")
+        .register_fn("export_file", |path: &str, data: Blob| ExportFile {
+            path: path.to_owned(),
+            data,
+        })
+        .register_get("path", |file: &mut ExportFile| file.path.clone())
+        .register_fn(
+            "save_files",
+            |name: &str, files: Array| -> ScriptResult<String> {
+                let files = files
+                    .into_iter()
+                    .map(|file| {
+                        let type_name = file.type_name();
+                        file.try_cast::<ExportFile>()
+                            .ok_or_else(|| format!("expected an export file, got {type_name}"))
+                    })
+                    .collect::<Result<_, _>>()?;
+                Ok(save_files(name, files)?)
+            },
+        );
+
+    engine
+}
+
+/// Edits and runs scripts, for repetitive tasks like recolouring or exporting many models.
+pub struct ScriptConsoleWindow {
+    script: String,
+    output: Vec<String>,
+    /// Commands of the last run, taken by the app.
+    pub commands: Vec<ScriptCommand>,
+}
+
+impl ScriptConsoleWindow {
+    /// Lines of output kept, older lines are dropped.
+    const MAX_OUTPUT_LINES: usize = 200;
+
+    pub fn new() -> Self {
+        Self {
+            script: EXAMPLES[0].1.to_owned(),
+            output: Vec::new(),
+            commands: Vec::new(),
+        }
+    }
+
+    /// Adds a line to the output, e.g. the result of a command.
+    pub fn log(&mut self, line: String) {
+        self.output.push(line);
+        if self.output.len() > Self::MAX_OUTPUT_LINES {
+            let excess = self.output.len() - Self::MAX_OUTPUT_LINES;
+            self.output.drain(..excess);
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, model_js5: &Arc<Js5>) {
+        egui::Window::new("Script Console")
+            .default_open(false)
+            .resizable(true)
+            .show(ctx, |ui| {
+                self.ui(ui, model_js5);
+            });
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, model_js5: &Arc<Js5>) {
+        ui.horizontal(|ui| {
+            let mut example = None;
+            egui::ComboBox::from_id_salt("script_example")
+                .selected_text("Examples")
+                .show_ui(ui, |ui| {
+                    for (name, script) in EXAMPLES {
+                        if ui.selectable_label(false, name).clicked() {
+                            example = Some(script);
+                        }
+                    }
+                });
+            if let Some(script) = example {
+                self.script = script.to_owned();
+            }
+            if ui
+                .button("Run")
+                .on_hover_text("Run the script, or ctrl+enter in the editor")
+                .clicked()
+            {
+                self.run(model_js5);
+            }
+            if ui.button("Clear output").clicked() {
+                self.output.clear();
+            }
+        });
+
+        let editor = ui.add(
+            egui::TextEdit::multiline(&mut self.script)
+                .code_editor()
+                .desired_rows(8)
+                .desired_width(f32::INFINITY),
+        );
+        if editor.has_focus()
+            && ui.input_mut(|input| input.consume_key(egui::Modifiers::COMMAND, egui::Key::Enter))
+        {
+            self.run(model_js5);
+        }
+
+        ui.collapsing("Functions", |ui| {
+            egui::Grid::new("script_commands").show(ui, |ui| {
+                for command in &COMMANDS {
+                    ui.monospace(command.usage);
+                    ui.label(command.description);
+                    ui.end_row();
+                }
+            });
+            ui.label("Ids are an id, an array of ids or a range such as 0..100 or 0..=99.");
+            ui.label("Colours are packed HSL. print() writes to the output below.");
+        });
+
+        ui.separator();
+        egui::ScrollArea::vertical()
+            .max_height(160.0)
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                for line in &self.output {
+                    ui.monospace(line);
+                }
+            });
+    }
+
+    fn run(&mut self, model_js5: &Arc<Js5>) {
+        let commands = Rc::new(RefCell::new(Vec::new()));
+        let printed = Rc::new(RefCell::new(Vec::new()));
+        let engine = script_engine(&commands, &printed);
+        let mut scope = Scope::new();
+        scope.push_constant("models", model_js5.clone());
+        let result = engine.run_with_scope(&mut scope, &self.script);
+
+        let printed = printed.take();
+        let printed_any = !printed.is_empty();
+        for line in printed {
+            self.log(line);
+        }
+        match result {
+            Ok(()) if commands.borrow().is_empty() && !printed_any => {
+                self.log("Nothing to run".to_owned())
+            }
+            Ok(()) => self.commands = commands.take(),
+            Err(err) => self.log(format!("Error: {err}")),
+        }
+    }
+}
+
+impl ScriptCommand {
+    /// Describes the command in the output, e.g. `merge (4 models)`.
+    pub fn describe(&self) -> String {
+        match self {
+            ScriptCommand::Cache(id) => format!("cache {id}"),
+            ScriptCommand::Model(id) => format!("model {id}"),
+            ScriptCommand::Merge(ids) => format!("merge ({} models)", ids.len()),
+            ScriptCommand::Recolour {
+                old_colour,
+                new_colour,
+            } => format!("recolour {old_colour} -> {new_colour}"),
+            ScriptCommand::Reset => "reset".to_owned(),
+            ScriptCommand::Filter(ids) => format!("filter ({} models)", ids.len()),
+            ScriptCommand::Export(ids) => format!("export ({} models)", ids.len()),
+        }
+    }
+}
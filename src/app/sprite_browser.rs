@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+
+use super::save::save_files;
+use crate::{
+    export::{png, ExportFile},
+    runetek5::{
+        error::Runetek5Error,
+        graphics::sprite::{MapIconSheet, Pix8, SpriteData},
+        js5::Js5,
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum SpriteViewMode {
+    Colour,
+    /// Raw palette indices drawn with a pseudo-colour map.
+    PaletteIndices,
+}
+
+impl SpriteViewMode {
+    fn label(&self) -> &'static str {
+        match self {
+            SpriteViewMode::Colour => "Colour",
+            SpriteViewMode::PaletteIndices => "Palette indices",
+        }
+    }
+}
+
+struct LoadedSprite {
+    group_id: u32,
+    frames: Result<Vec<Pix8>, Runetek5Error>,
+}
+
+/// Browses the sprite archive, showing each frame in colour or as palette indices with its
+/// palette strip.
+pub struct SpriteBrowserWindow {
+    search_text: String,
+    selected_group: Option<u32>,
+    frame: usize,
+    mode: SpriteViewMode,
+    loaded: Option<LoadedSprite>,
+    images: HashMap<(usize, SpriteViewMode), egui::TextureHandle>,
+    status: Option<String>,
+}
+
+impl SpriteBrowserWindow {
+    const ROW_HEIGHT: f32 = 18.0;
+    const PREVIEW_SIZE: f32 = 256.0;
+    const SWATCH_SIZE: f32 = 10.0;
+
+    pub fn new() -> Self {
+        Self {
+            search_text: String::new(),
+            selected_group: None,
+            frame: 0,
+            mode: SpriteViewMode::Colour,
+            loaded: None,
+            images: HashMap::new(),
+            status: None,
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, sprite_js5: &Js5) {
+        egui::Window::new("Sprite Browser")
+            .default_open(false)
+            .resizable(true)
+            .scroll(false)
+            .show(ctx, |ui| {
+                ui.horizontal_top(|ui| {
+                    ui.vertical(|ui| {
+                        ui.set_width(120.0);
+                        self.group_list(ui, sprite_js5);
+                    });
+                    ui.separator();
+                    ui.vertical(|ui| {
+                        self.sprite_details(ui, sprite_js5);
+                    });
+                });
+            });
+    }
+
+    fn group_list(&mut self, ui: &mut egui::Ui, sprite_js5: &Js5) {
+        ui.add(egui::TextEdit::singleline(&mut self.search_text).hint_text("Search sprites..."));
+        // The map icon sheets are only known by name, so offer them directly.
+        let map_icon_sheets: Vec<(MapIconSheet, u32)> = MapIconSheet::ALL
+            .into_iter()
+            .filter_map(|sheet| Some((sheet, sheet.group_id(sprite_js5)?)))
+            .collect();
+        for &(sheet, group_id) in &map_icon_sheets {
+            let selected = self.selected_group == Some(group_id);
+            if ui.selectable_label(selected, sheet.label()).clicked() {
+                self.selected_group = Some(group_id);
+                self.frame = 0;
+                self.status = None;
+            }
+        }
+        let search_text = self.search_text.trim();
+        let group_ids: Vec<u32> = sprite_js5
+            .index
+            .group_ids
+            .iter()
+            .copied()
+            .filter(|id| search_text.is_empty() || id.to_string().contains(search_text))
+            .collect();
+        ui.label(format!("{} sprites", group_ids.len()));
+        egui::ScrollArea::vertical()
+            .id_salt("sprite_groups")
+            .auto_shrink(false)
+            .show_rows(ui, Self::ROW_HEIGHT, group_ids.len(), |ui, row_range| {
+                for &group_id in &group_ids[row_range] {
+                    let selected = self.selected_group == Some(group_id);
+                    let label = match map_icon_sheets.iter().find(|(_, id)| *id == group_id) {
+                        Some((sheet, _)) => format!("Sprite {group_id} ({})", sheet.group_name()),
+                        None => format!("Sprite {group_id}"),
+                    };
+                    if ui.selectable_label(selected, label).clicked() {
+                        self.selected_group = Some(group_id);
+                        self.frame = 0;
+                        self.status = None;
+                    }
+                }
+            });
+    }
+
+    fn sprite_details(&mut self, ui: &mut egui::Ui, sprite_js5: &Js5) {
+        let Some(group_id) = self.selected_group else {
+            ui.label("Select a sprite");
+            return;
+        };
+        if !self
+            .loaded
+            .as_ref()
+            .is_some_and(|loaded| loaded.group_id == group_id)
+        {
+            let Some(data) = sprite_js5.get_file(group_id, 0) else {
+                ui.spinner();
+                return;
+            };
+            let mut frames = SpriteData::decode_into_pix8s(&data);
+            if let Ok(frames) = &mut frames {
+                frames.iter_mut().for_each(Pix8::normalize);
+            }
+            self.loaded = Some(LoadedSprite { group_id, frames });
+            self.images.clear();
+        }
+        let frame_count = match self.loaded.as_ref().map(|loaded| &loaded.frames) {
+            Some(Err(err)) => {
+                ui.colored_label(
+                    ui.visuals().error_fg_color,
+                    format!("Sprite couldn't be decoded: {err}"),
+                );
+                return;
+            }
+            Some(Ok(frames)) => frames.len(),
+            None => 0,
+        };
+        if frame_count == 0 {
+            ui.label("Sprite has no frames");
+            return;
+        }
+        self.frame = self.frame.min(frame_count - 1);
+
+        ui.horizontal(|ui| {
+            if frame_count > 1 {
+                ui.label("Frame");
+                ui.add(egui::DragValue::new(&mut self.frame).range(0..=frame_count - 1));
+            }
+            for mode in [SpriteViewMode::Colour, SpriteViewMode::PaletteIndices] {
+                ui.selectable_value(&mut self.mode, mode, mode.label());
+            }
+        });
+
+        let Some(Ok(frames)) = self.loaded.as_ref().map(|loaded| &loaded.frames) else {
+            return;
+        };
+        let frame = &frames[self.frame];
+        ui.label(format!(
+            "{}x{}, {} colours",
+            frame.width,
+            frame.height,
+            frame.palette.len() - 1
+        ));
+        Self::palette_strip(ui, &frame.palette);
+
+        if ui.button("Export indexed PNG").clicked() {
+            let file = ExportFile {
+                path: format!("{group_id}_{}.png", self.frame),
+                data: png::encode_indexed(
+                    frame.width as u32,
+                    frame.height as u32,
+                    &frame.palette,
+                    &frame.pixels,
+                ),
+            };
+            self.status = Some(match save_files("sprites", vec![file]) {
+                Ok(location) => format!("Saved to {location}"),
+                Err(err) => format!("Export failed: {err}"),
+            });
+        }
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+        ui.separator();
+
+        let key = (self.frame, self.mode);
+        if !self.images.contains_key(&key) {
+            let image = Self::frame_image(frame, self.mode);
+            let texture = ui.ctx().load_texture(
+                format!("sprite_{group_id}_{}_{:?}", self.frame, self.mode),
+                image,
+                egui::TextureOptions::NEAREST,
+            );
+            self.images.insert(key, texture);
+        }
+        let texture = &self.images[&key];
+        let [width, height] = texture.size().map(|size| size as f32);
+        let scale = (Self::PREVIEW_SIZE / width.max(height)).floor().max(1.0);
+        egui::ScrollArea::both()
+            .id_salt("sprite_preview")
+            .auto_shrink(false)
+            .show(ui, |ui| {
+                let response = ui.add(
+                    egui::Image::new((texture.id(), egui::vec2(width, height) * scale))
+                        .sense(egui::Sense::hover()),
+                );
+                if let Some(pos) = response.hover_pos() {
+                    let local = (pos - response.rect.min) / scale;
+                    let (x, y) = (local.x as usize, local.y as usize);
+                    if let Some(&index) = frame.pixels.get(x + y * frame.width as usize) {
+                        response.on_hover_text(format!(
+                            "({x}, {y}) index {index}, #{:06x}",
+                            frame.palette[index as usize]
+                        ));
+                    }
+                }
+            });
+    }
+
+    /// Draws each palette entry as a swatch, index 0 is the transparent colour.
+    fn palette_strip(ui: &mut egui::Ui, palette: &[u32]) {
+        ui.horizontal_wrapped(|ui| {
+            ui.spacing_mut().item_spacing = egui::vec2(1.0, 1.0);
+            for (index, &rgb) in palette.iter().enumerate() {
+                let (rect, response) = ui.allocate_exact_size(
+                    egui::Vec2::splat(Self::SWATCH_SIZE),
+                    egui::Sense::hover(),
+                );
+                let colour = if index == 0 {
+                    egui::Color32::TRANSPARENT
+                } else {
+                    rgb_to_colour(rgb)
+                };
+                ui.painter().rect_filled(rect, 0.0, colour);
+                ui.painter().rect_stroke(
+                    rect,
+                    0.0,
+                    egui::Stroke::new(1.0, index_colour(index as u8)),
+                    egui::StrokeKind::Inside,
+                );
+                response.on_hover_text(format!("Index {index}: #{rgb:06x}"));
+            }
+        });
+    }
+
+    fn frame_image(frame: &Pix8, mode: SpriteViewMode) -> egui::ColorImage {
+        let pixels = frame
+            .pixels
+            .iter()
+            .map(|&index| match (index, mode) {
+                (0, _) => egui::Color32::TRANSPARENT,
+                (_, SpriteViewMode::Colour) => rgb_to_colour(frame.palette[index as usize]),
+                (_, SpriteViewMode::PaletteIndices) => index_colour(index),
+            })
+            .collect();
+        egui::ColorImage {
+            size: [frame.width as usize, frame.height as usize],
+            pixels,
+        }
+    }
+}
+
+fn rgb_to_colour(rgb: u32) -> egui::Color32 {
+    egui::Color32::from_rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+}
+
+/// Pseudo-colour for a palette index, neighbouring indices get clearly different hues.
+fn index_colour(index: u8) -> egui::Color32 {
+    const GOLDEN_RATIO_CONJUGATE: f32 = 0.618_034;
+    let hue = (index as f32 * GOLDEN_RATIO_CONJUGATE).fract();
+    egui::ecolor::Hsva::new(hue, 0.75, 0.95, 1.0).into()
+}
@@ -0,0 +1,232 @@
+use super::save::save_files;
+use crate::{
+    export::{
+        png::{self, DecodedImage},
+        ExportFile,
+    },
+    runetek5::graphics::{
+        quantize::{quantize, QuantizeMethod, Quantized, MAX_SPRITE_COLOURS},
+        sprite::SpriteData,
+    },
+};
+
+struct SourceImage {
+    name: String,
+    image: DecodedImage,
+    texture: egui::TextureHandle,
+}
+
+struct QuantizedPreview {
+    max_colours: usize,
+    method: QuantizeMethod,
+    quantized: Quantized,
+    texture: egui::TextureHandle,
+}
+
+/// Imports a PNG dropped onto the app as a sprite, quantizing it to a sprite palette.
+pub struct SpriteImportWindow {
+    source: Option<SourceImage>,
+    max_colours: usize,
+    method: QuantizeMethod,
+    preview: Option<QuantizedPreview>,
+    status: Option<String>,
+}
+
+impl SpriteImportWindow {
+    const PREVIEW_SIZE: f32 = 192.0;
+
+    pub fn new() -> Self {
+        Self {
+            source: None,
+            max_colours: MAX_SPRITE_COLOURS,
+            method: QuantizeMethod::MedianCut,
+            preview: None,
+            status: None,
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) {
+        self.handle_dropped_files(ctx);
+
+        egui::Window::new("Sprite Import")
+            .default_open(false)
+            .resizable(true)
+            .show(ctx, |ui| {
+                self.ui(ui);
+            });
+    }
+
+    fn handle_dropped_files(&mut self, ctx: &egui::Context) {
+        let dropped_files = ctx.input(|input| input.raw.dropped_files.clone());
+        let Some(file) = dropped_files
+            .into_iter()
+            .find(|file| file_name(file).to_lowercase().ends_with(".png"))
+        else {
+            return;
+        };
+        let name = file_name(&file);
+        let data = match (&file.bytes, &file.path) {
+            (Some(bytes), _) => Ok(bytes.to_vec()),
+            #[cfg(not(target_arch = "wasm32"))]
+            (None, Some(path)) => std::fs::read(path).map_err(|err| err.to_string()),
+            _ => Err("no file contents".to_owned()),
+        };
+        self.load_source(ctx, name, data);
+    }
+
+    /// Asks for a PNG with the native file dialog.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn open_file(&mut self, ctx: &egui::Context) {
+        let Some(path) = super::save::pick_file("Import sprite", "PNG image", &["png"]) else {
+            return;
+        };
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let data = std::fs::read(&path).map_err(|err| err.to_string());
+        self.load_source(ctx, name, data);
+    }
+
+    fn load_source(&mut self, ctx: &egui::Context, name: String, data: Result<Vec<u8>, String>) {
+        match data.and_then(|data| png::decode_rgba(&data)) {
+            Ok(image) => {
+                let texture = ctx.load_texture(
+                    format!("sprite_import_{name}"),
+                    egui::ColorImage::from_rgba_unmultiplied(
+                        [image.width as usize, image.height as usize],
+                        &image.rgba,
+                    ),
+                    egui::TextureOptions::NEAREST,
+                );
+                self.source = Some(SourceImage {
+                    name: name.trim_end_matches(".png").to_owned(),
+                    image,
+                    texture,
+                });
+                self.preview = None;
+                self.status = None;
+            }
+            Err(err) => self.status = Some(format!("Failed to read {name}: {err}")),
+        }
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Drop a PNG onto the window to import it.");
+            #[cfg(not(target_arch = "wasm32"))]
+            if ui.button("Open…").clicked() {
+                self.open_file(ui.ctx());
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::Slider::new(&mut self.max_colours, 1..=MAX_SPRITE_COLOURS).text("Colours"),
+            );
+            egui::ComboBox::from_id_salt("quantize_method")
+                .selected_text(self.method.label())
+                .show_ui(ui, |ui| {
+                    for method in [QuantizeMethod::MedianCut, QuantizeMethod::Popularity] {
+                        ui.selectable_value(&mut self.method, method, method.label());
+                    }
+                });
+        });
+
+        if let Some(source) = &self.source {
+            let (width, height) = (source.image.width, source.image.height);
+            if width > u16::MAX as u32 || height > u16::MAX as u32 {
+                ui.label(format!("{width}x{height} is too large for a sprite"));
+                return;
+            }
+            let up_to_date = self.preview.as_ref().is_some_and(|preview| {
+                preview.max_colours == self.max_colours && preview.method == self.method
+            });
+            if !up_to_date {
+                let quantized = quantize(&source.image.rgba, self.max_colours, self.method);
+                let pixels = quantized
+                    .indices
+                    .iter()
+                    .map(|&index| match index {
+                        0 => egui::Color32::TRANSPARENT,
+                        _ => {
+                            let rgb = quantized.palette[index as usize];
+                            egui::Color32::from_rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+                        }
+                    })
+                    .collect();
+                let texture = ui.ctx().load_texture(
+                    "sprite_import_preview",
+                    egui::ColorImage {
+                        size: [width as usize, height as usize],
+                        pixels,
+                    },
+                    egui::TextureOptions::NEAREST,
+                );
+                self.preview = Some(QuantizedPreview {
+                    max_colours: self.max_colours,
+                    method: self.method,
+                    quantized,
+                    texture,
+                });
+            }
+            let preview = self.preview.as_ref().unwrap();
+
+            ui.label(format!(
+                "{}: {width}x{height}, {} colours",
+                source.name,
+                preview.quantized.colour_count()
+            ));
+            let scale = (Self::PREVIEW_SIZE / width.max(height) as f32).min(8.0);
+            let size = egui::vec2(width as f32, height as f32) * scale;
+            ui.horizontal(|ui| {
+                ui.vertical(|ui| {
+                    ui.add(egui::Image::new((source.texture.id(), size)));
+                    ui.label("Original");
+                });
+                ui.vertical(|ui| {
+                    ui.add(egui::Image::new((preview.texture.id(), size)));
+                    ui.label("Quantized");
+                });
+            });
+
+            if ui.button("Save sprite").clicked() {
+                let Quantized { palette, indices } = &preview.quantized;
+                let sprite = SpriteData::new_single(
+                    width as u16,
+                    height as u16,
+                    palette.clone(),
+                    indices.clone(),
+                );
+                let files = vec![
+                    ExportFile {
+                        path: format!("{}.dat", source.name),
+                        data: sprite.encode(),
+                    },
+                    ExportFile {
+                        path: format!("{}_indexed.png", source.name),
+                        data: png::encode_indexed(width, height, palette, indices),
+                    },
+                ];
+                self.status = Some(match save_files("sprites", files) {
+                    Ok(location) => format!("Saved to {location}"),
+                    Err(err) => format!("Export failed: {err}"),
+                });
+            }
+        }
+
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+    }
+}
+
+pub(super) fn file_name(file: &egui::DroppedFile) -> String {
+    if !file.name.is_empty() {
+        return file.name.clone();
+    }
+    file.path
+        .as_ref()
+        .and_then(|path| path.file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
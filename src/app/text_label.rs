@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+
+use crate::runetek5::{
+    graphics::font::{Font, FontMetrics, TextImage},
+    js5::Js5,
+};
+
+/// Converts rasterized text into an egui image.
+pub fn text_image_to_colour_image(image: &TextImage) -> egui::ColorImage {
+    egui::ColorImage {
+        size: [image.width, image.height],
+        pixels: image
+            .pixels
+            .iter()
+            .map(|&argb| {
+                let [a, r, g, b] = argb.to_be_bytes();
+                egui::Color32::from_rgba_unmultiplied(r, g, b, a)
+            })
+            .collect(),
+    }
+}
+
+pub fn colour_to_argb(colour: egui::Color32) -> u32 {
+    let [r, g, b, a] = colour.to_srgba_unmultiplied();
+    u32::from_be_bytes([a, r, g, b])
+}
+
+/// Draws text with a game font into cached egui textures, for labels over renders.
+pub struct TextLabelRenderer {
+    pub font_id: u32,
+    font: Font,
+    /// False if the metrics were approximated from the glyphs.
+    pub has_metrics: bool,
+    textures: HashMap<(String, egui::Color32, bool), egui::TextureHandle>,
+}
+
+impl TextLabelRenderer {
+    pub const SHADOW_ARGB: u32 = 0xff000000;
+    const MAX_CACHED_TEXTURES: usize = 64;
+
+    /// Loads the font from its glyph sprite group, None while the groups are still being fetched
+    /// or if the sprite isn't a font.
+    pub fn load(font_id: u32, sprite_js5: &Js5, fontmetrics_js5: &Js5) -> Option<Self> {
+        let sprite_data = sprite_js5.get_file(font_id, 0)?;
+        let metrics = if fontmetrics_js5.is_group_valid(font_id) {
+            Some(FontMetrics::decode(&fontmetrics_js5.get_file(font_id, 0)?)?)
+        } else {
+            None
+        };
+        Some(Self {
+            font_id,
+            has_metrics: metrics.is_some(),
+            font: Font::decode(&sprite_data, metrics)?,
+            textures: HashMap::new(),
+        })
+    }
+
+    pub fn font(&self) -> &Font {
+        &self.font
+    }
+
+    /// The texture for `text` drawn in `colour`, optionally with a drop shadow, rendered on
+    /// first use.
+    pub fn texture(
+        &mut self,
+        ctx: &egui::Context,
+        text: &str,
+        colour: egui::Color32,
+        shadow: bool,
+    ) -> &egui::TextureHandle {
+        let key = (text.to_owned(), colour, shadow);
+        if !self.textures.contains_key(&key) && self.textures.len() >= Self::MAX_CACHED_TEXTURES {
+            self.textures.clear();
+        }
+        let font = &self.font;
+        self.textures.entry(key).or_insert_with(|| {
+            let shadow_argb = shadow.then_some(Self::SHADOW_ARGB);
+            let image = font.render(text, colour_to_argb(colour), shadow_argb);
+            ctx.load_texture(
+                format!("text_label_{text}"),
+                text_image_to_colour_image(&image),
+                egui::TextureOptions::NEAREST,
+            )
+        })
+    }
+
+    /// Drops cached textures, e.g. when the label text changes every frame.
+    pub fn clear(&mut self) {
+        self.textures.clear();
+    }
+}
@@ -0,0 +1,502 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::runetek5::{
+    graphics::{model::ModelUnlit, texture::TextureProvider},
+    js5::Js5,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AlphaFilter {
+    All,
+    Opaque,
+    Transparent,
+}
+
+impl AlphaFilter {
+    fn label(&self) -> &'static str {
+        match self {
+            AlphaFilter::All => "All",
+            AlphaFilter::Opaque => "Opaque",
+            AlphaFilter::Transparent => "Transparent",
+        }
+    }
+}
+
+/// Scans the model archive a few groups per frame for models referencing a texture.
+struct TextureUsageScan {
+    texture_id: u32,
+    next_index: usize,
+    model_ids: Vec<usize>,
+}
+
+impl TextureUsageScan {
+    const GROUPS_PER_FRAME: usize = 256;
+
+    fn new(texture_id: u32) -> Self {
+        Self {
+            texture_id,
+            next_index: 0,
+            model_ids: Vec::new(),
+        }
+    }
+
+    /// Returns true once every model group has been checked.
+    fn step(&mut self, model_js5: &Js5) -> bool {
+        let group_ids = &model_js5.index.group_ids;
+        let end = (self.next_index + Self::GROUPS_PER_FRAME).min(group_ids.len());
+        let mut batch = Vec::with_capacity(end - self.next_index);
+        while self.next_index < end {
+            let group_id = group_ids[self.next_index];
+            match model_js5.try_get_file(group_id, 0) {
+                Ok(Some(data)) => batch.push((group_id, data)),
+                // Stop at groups that are still being fetched.
+                Ok(None) => break,
+                // Skip groups that never load, e.g. quarantined ones.
+                Err(_) => {}
+            }
+            self.next_index += 1;
+        }
+
+        let texture_id = self.texture_id;
+        let matches = crate::parallel::map(&batch, |(_, data)| {
+            let Ok(model) = ModelUnlit::from_data(data) else {
+                return false;
+            };
+            model.triangle_material.as_ref().is_some_and(|materials| {
+                materials
+                    .iter()
+                    .any(|&material| material as u16 as u32 == texture_id)
+            })
+        });
+        for ((group_id, _), uses_texture) in batch.iter().zip(matches) {
+            if uses_texture {
+                self.model_ids.push(*group_id as usize);
+            }
+        }
+        self.next_index == group_ids.len()
+    }
+}
+
+/// Alpha content of a decoded texture, compared against its `opaque` flag.
+#[derive(Debug, Clone, Copy)]
+struct AlphaStats {
+    transparent_pixels: usize,
+    pixel_count: usize,
+}
+
+impl AlphaStats {
+    fn from_argb(pixels: &[u32]) -> Self {
+        Self {
+            transparent_pixels: pixels.iter().filter(|&&argb| argb >> 24 == 0).count(),
+            pixel_count: pixels.len(),
+        }
+    }
+
+    /// Describes how the pixels disagree with the `opaque` flag, if they do.
+    fn mismatch(&self, opaque: bool) -> Option<&'static str> {
+        if opaque && self.transparent_pixels > 0 {
+            Some("Flagged opaque but has transparent pixels, cutouts will render solid")
+        } else if !opaque && self.transparent_pixels == 0 {
+            Some("Flagged transparent but every pixel is opaque")
+        } else {
+            None
+        }
+    }
+}
+
+/// The texture shown in the inspector, with its alpha channel as a separate greyscale image.
+struct InspectedTexture {
+    texture_id: u32,
+    colour: egui::TextureHandle,
+    alpha: egui::TextureHandle,
+    stats: AlphaStats,
+}
+
+pub struct TextureBrowserWindow {
+    alpha_filter: AlphaFilter,
+    animated_only: bool,
+    thumbnails: HashMap<u32, egui::TextureHandle>,
+    alpha_stats: HashMap<u32, AlphaStats>,
+    inspected: Option<InspectedTexture>,
+    active_thumbnail_ids: HashSet<u32>,
+    usage_scan: Option<TextureUsageScan>,
+    /// Models found by a finished usage scan, as (texture id, model ids).
+    pub found_models: Option<(u32, Vec<usize>)>,
+}
+
+impl TextureBrowserWindow {
+    const THUMBNAIL_SIZE: f32 = 64.0;
+    const CONTAINER_WIDTH: f32 = 76.0;
+    const CONTAINER_HEIGHT: f32 = 112.0;
+    const CONTAINER_WIDTH_WITH_SPACING: f32 = Self::CONTAINER_WIDTH + 6.0;
+
+    pub fn new() -> Self {
+        Self {
+            alpha_filter: AlphaFilter::All,
+            animated_only: false,
+            thumbnails: HashMap::new(),
+            alpha_stats: HashMap::new(),
+            inspected: None,
+            active_thumbnail_ids: HashSet::new(),
+            usage_scan: None,
+            found_models: None,
+        }
+    }
+
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        model_js5: &Js5,
+        texture_provider: &TextureProvider,
+        brightness: f32,
+    ) {
+        if let Some(scan) = self.usage_scan.as_mut() {
+            if scan.step(model_js5) {
+                let scan = self.usage_scan.take().unwrap();
+                self.found_models = Some((scan.texture_id, scan.model_ids));
+            }
+        }
+        if self.usage_scan.is_some() {
+            ctx.request_repaint();
+        }
+
+        egui::Window::new("Texture Browser")
+            .default_open(false)
+            .resizable(true)
+            .scroll(false)
+            .show(ctx, |ui| {
+                self.active_thumbnail_ids.clear();
+
+                self.ui(ui, model_js5, texture_provider, brightness);
+
+                self.thumbnails
+                    .retain(|id, _| self.active_thumbnail_ids.contains(id));
+            });
+    }
+
+    /// Drops all thumbnails so they are decoded again, e.g. after a brightness change.
+    pub fn clear_thumbnails(&mut self) {
+        self.thumbnails.clear();
+        self.inspected = None;
+    }
+
+    fn inspect(
+        &mut self,
+        ctx: &egui::Context,
+        texture_provider: &TextureProvider,
+        texture_id: u32,
+        brightness: f32,
+    ) {
+        let size = 128;
+        let Some(pixels) = texture_provider.get_pixels_argb(
+            texture_id,
+            size as u16,
+            size as u16,
+            false,
+            brightness as f64,
+        ) else {
+            return;
+        };
+        let colour = egui::ColorImage {
+            size: [size, size],
+            pixels: pixels.iter().map(|&argb| argb_to_colour(argb)).collect(),
+        };
+        let alpha = egui::ColorImage {
+            size: [size, size],
+            pixels: pixels
+                .iter()
+                .map(|&argb| egui::Color32::from_gray((argb >> 24) as u8))
+                .collect(),
+        };
+        self.inspected = Some(InspectedTexture {
+            texture_id,
+            colour: ctx.load_texture(
+                format!("inspected_texture_{texture_id}"),
+                colour,
+                egui::TextureOptions::NEAREST,
+            ),
+            alpha: ctx.load_texture(
+                format!("inspected_alpha_{texture_id}"),
+                alpha,
+                egui::TextureOptions::NEAREST,
+            ),
+            stats: AlphaStats::from_argb(&pixels),
+        });
+    }
+
+    /// Lists textures drawn with the placeholder because their sprite couldn't be loaded.
+    fn missing_ui(&mut self, ui: &mut egui::Ui, texture_provider: &TextureProvider) {
+        let missing = texture_provider.get_missing_sprites();
+        if missing.is_empty() {
+            return;
+        }
+        egui::CollapsingHeader::new(
+            egui::RichText::new(format!("{} missing textures", missing.len()))
+                .color(ui.visuals().warn_fg_color),
+        )
+        .id_salt("missing_textures")
+        .show(ui, |ui| {
+            ui.label("These are drawn with magenta checks.");
+            egui::Grid::new("missing_textures_grid")
+                .num_columns(3)
+                .striped(true)
+                .show(ui, |ui| {
+                    let scanning = self.usage_scan.is_some();
+                    for (texture_id, sprite_id) in missing {
+                        ui.label(format!("Texture {texture_id}"));
+                        ui.label(format!("Sprite {sprite_id}"));
+                        if ui
+                            .add_enabled(!scanning, egui::Button::new("Models").small())
+                            .on_hover_text("Find models using this texture")
+                            .clicked()
+                        {
+                            self.usage_scan = Some(TextureUsageScan::new(texture_id));
+                        }
+                        ui.end_row();
+                    }
+                });
+        });
+    }
+
+    fn inspector_ui(&mut self, ui: &mut egui::Ui, texture_provider: &TextureProvider) {
+        let Some(inspected) = &self.inspected else {
+            return;
+        };
+        let Some(texture) = texture_provider.textures[inspected.texture_id as usize].as_ref()
+        else {
+            return;
+        };
+        let mut close = false;
+        egui::Frame::group(ui.style()).show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.strong(format!("Texture {}", inspected.texture_id));
+                close = ui.small_button("Close").clicked();
+            });
+            ui.horizontal(|ui| {
+                let size = egui::Vec2::splat(128.0);
+                ui.vertical(|ui| {
+                    ui.add(egui::Image::new((inspected.colour.id(), size)));
+                    ui.label("Colour");
+                });
+                ui.vertical(|ui| {
+                    ui.add(egui::Image::new((inspected.alpha.id(), size)));
+                    ui.label("Alpha");
+                });
+                ui.vertical(|ui| {
+                    let stats = inspected.stats;
+                    ui.label(format!("Opaque flag: {}", texture.opaque));
+                    if let Some(info) = texture_provider.get_info(inspected.texture_id) {
+                        ui.label(format!(
+                            "Detail: {}",
+                            match (info.high_detail, info.standard_detail_only) {
+                                (false, false) => "any",
+                                (true, false) => "textured in high detail only",
+                                (true, true) => "standard detail only, untextured",
+                                (false, true) => "never drawn",
+                            }
+                        ));
+                        ui.label(format!(
+                            "Blend: {:?}, effect {} ({})",
+                            info.alpha_mode, info.effect_id, info.effect_config0
+                        ));
+                    }
+                    ui.label(format!(
+                        "Transparent pixels: {}/{} ({:.1}%)",
+                        stats.transparent_pixels,
+                        stats.pixel_count,
+                        stats.transparent_pixels as f32 * 100.0 / stats.pixel_count.max(1) as f32
+                    ));
+                    if let Some(mismatch) = stats.mismatch(texture.opaque) {
+                        ui.colored_label(ui.visuals().warn_fg_color, mismatch);
+                    }
+                });
+            });
+        });
+        if close {
+            self.inspected = None;
+        }
+    }
+
+    fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        model_js5: &Js5,
+        texture_provider: &TextureProvider,
+        brightness: f32,
+    ) {
+        ui.horizontal(|ui| {
+            for filter in [
+                AlphaFilter::All,
+                AlphaFilter::Opaque,
+                AlphaFilter::Transparent,
+            ] {
+                ui.selectable_value(&mut self.alpha_filter, filter, filter.label());
+            }
+            ui.separator();
+            ui.checkbox(&mut self.animated_only, "Animated");
+        });
+
+        if let Some(scan) = &self.usage_scan {
+            let mut cancel = false;
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label(format!(
+                    "Finding models using texture {}... {}/{}",
+                    scan.texture_id,
+                    scan.next_index,
+                    model_js5.get_group_count()
+                ));
+                cancel = ui.button("Cancel").clicked();
+            });
+            if cancel {
+                self.usage_scan = None;
+            }
+        }
+
+        let texture_ids: Vec<u32> = texture_provider
+            .textures
+            .iter()
+            .enumerate()
+            .filter_map(|(id, texture)| {
+                let texture = texture.as_ref()?;
+                let alpha_matches = match self.alpha_filter {
+                    AlphaFilter::All => true,
+                    AlphaFilter::Opaque => texture.opaque,
+                    AlphaFilter::Transparent => !texture.opaque,
+                };
+                let animated = texture.anim_speed != 0;
+                (alpha_matches && (animated || !self.animated_only)).then_some(id as u32)
+            })
+            .collect();
+
+        ui.label(format!("{} textures", texture_ids.len()));
+        self.missing_ui(ui, texture_provider);
+        self.inspector_ui(ui, texture_provider);
+        ui.separator();
+
+        let available_width = ui.available_width();
+        let items_per_row =
+            ((available_width / Self::CONTAINER_WIDTH_WITH_SPACING).floor() as usize).max(1);
+        let total_rows = texture_ids.len().div_ceil(items_per_row);
+
+        egui::ScrollArea::vertical().auto_shrink(false).show_rows(
+            ui,
+            Self::CONTAINER_HEIGHT,
+            total_rows,
+            |ui, row_range| {
+                for row in row_range {
+                    ui.horizontal(|ui| {
+                        let item_start = row * items_per_row;
+                        let item_end = (item_start + items_per_row).min(texture_ids.len());
+                        for &texture_id in &texture_ids[item_start..item_end] {
+                            self.add_item(ui, texture_provider, texture_id, brightness);
+                        }
+                    });
+                }
+            },
+        );
+    }
+
+    fn add_item(
+        &mut self,
+        ui: &mut egui::Ui,
+        texture_provider: &TextureProvider,
+        texture_id: u32,
+        brightness: f32,
+    ) {
+        self.active_thumbnail_ids.insert(texture_id);
+        ui.vertical(|ui| {
+            ui.set_width(Self::CONTAINER_WIDTH);
+            let size = egui::Vec2::splat(Self::THUMBNAIL_SIZE);
+            match self.get_or_load_thumbnail(ui.ctx(), texture_provider, texture_id, brightness) {
+                Some(thumbnail) => {
+                    let response = ui
+                        .add(egui::Image::new((thumbnail.id(), size)).sense(egui::Sense::click()))
+                        .on_hover_text("Inspect alpha");
+                    if response.clicked() {
+                        self.inspect(ui.ctx(), texture_provider, texture_id, brightness);
+                    }
+                }
+                None if texture_provider.is_sprite_missing(texture_id) => {
+                    let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+                    ui.put(
+                        rect,
+                        egui::Label::new(
+                            egui::RichText::new("Missing").color(ui.visuals().error_fg_color),
+                        ),
+                    )
+                    .on_hover_text("The texture's sprite couldn't be loaded");
+                }
+                None => {
+                    let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+                    ui.put(rect, egui::Spinner::new());
+                }
+            }
+            let mismatch = self.alpha_stats.get(&texture_id).and_then(|stats| {
+                let opaque = texture_provider.textures[texture_id as usize]
+                    .as_ref()?
+                    .opaque;
+                stats.mismatch(opaque)
+            });
+            match mismatch {
+                Some(mismatch) => {
+                    ui.colored_label(ui.visuals().warn_fg_color, format!("{texture_id} \u{26A0}"))
+                        .on_hover_text(mismatch);
+                }
+                None => {
+                    ui.label(texture_id.to_string());
+                }
+            }
+            let scanning = self.usage_scan.is_some();
+            if ui
+                .add_enabled(!scanning, egui::Button::new("Models").small())
+                .on_hover_text("Find models using this texture")
+                .clicked()
+            {
+                self.usage_scan = Some(TextureUsageScan::new(texture_id));
+            }
+        });
+    }
+
+    fn get_or_load_thumbnail(
+        &mut self,
+        ctx: &egui::Context,
+        texture_provider: &TextureProvider,
+        texture_id: u32,
+        brightness: f32,
+    ) -> Option<&egui::TextureHandle> {
+        if !self.thumbnails.contains_key(&texture_id) {
+            let size = 128;
+            let pixels = texture_provider.get_pixels_argb(
+                texture_id,
+                size as u16,
+                size as u16,
+                false,
+                brightness as f64,
+            )?;
+            self.alpha_stats
+                .insert(texture_id, AlphaStats::from_argb(&pixels));
+            let pixels = pixels.into_iter().map(argb_to_colour).collect();
+            let image = egui::ColorImage {
+                size: [size, size],
+                pixels,
+            };
+            let thumbnail = ctx.load_texture(
+                format!("texture_{texture_id}"),
+                image,
+                egui::TextureOptions::NEAREST,
+            );
+            self.thumbnails.insert(texture_id, thumbnail);
+        }
+        self.thumbnails.get(&texture_id)
+    }
+}
+
+fn argb_to_colour(argb: u32) -> egui::Color32 {
+    egui::Color32::from_rgba_unmultiplied(
+        (argb >> 16) as u8,
+        (argb >> 8) as u8,
+        argb as u8,
+        (argb >> 24) as u8,
+    )
+}
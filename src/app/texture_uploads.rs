@@ -0,0 +1,171 @@
+//! Updates of the texture array's layers. Layers marked dirty are decoded again and uploaded a
+//! few at a time within a byte budget per frame, and only the rows that changed since their last
+//! upload are sent, so updating every texture never stalls a frame.
+
+use std::{
+    collections::VecDeque,
+    hash::{DefaultHasher, Hash as _, Hasher as _},
+};
+
+use eframe::glow::{self, HasContext as _};
+
+use crate::runetek5::graphics::texture::TextureProvider;
+
+pub struct TextureUploads {
+    /// Width and height of each layer.
+    size: i32,
+    /// Hashes of each layer's rows as last uploaded, empty before its first upload.
+    row_hashes: Vec<Vec<u64>>,
+    /// Layers to decode and upload again, oldest first.
+    dirty: VecDeque<u32>,
+    /// Whether each layer is in `dirty`.
+    queued: Vec<bool>,
+}
+
+impl TextureUploads {
+    /// Bytes uploaded per frame at most, 16 full 128×128 layers. A layer isn't split across
+    /// frames, so at least one goes up each frame.
+    const FRAME_BYTE_BUDGET: usize = 1 << 20;
+    /// Layers decoded per frame at most, uploaded or not, since decoding costs time too.
+    const FRAME_LAYER_LIMIT: usize = 64;
+
+    pub fn new(layer_count: usize, size: i32) -> Self {
+        Self {
+            size,
+            row_hashes: vec![Vec::new(); layer_count],
+            dirty: VecDeque::new(),
+            queued: vec![false; layer_count],
+        }
+    }
+
+    /// Queues the layer to be decoded and uploaded again.
+    pub fn mark_dirty(&mut self, texture_id: u32) {
+        let Some(queued) = self.queued.get_mut(texture_id as usize) else {
+            return;
+        };
+        if !*queued {
+            *queued = true;
+            self.dirty.push_back(texture_id);
+        }
+    }
+
+    /// Queues every layer, e.g. once the brightness they are decoded with changed.
+    pub fn mark_all_dirty(&mut self) {
+        for texture_id in 0..self.queued.len() as u32 {
+            self.mark_dirty(texture_id);
+        }
+    }
+
+    /// Whether layers are still queued, so another frame is needed to upload them.
+    pub fn is_pending(&self) -> bool {
+        !self.dirty.is_empty()
+    }
+
+    /// Decodes and uploads every layer at once, for the first upload before anything is drawn.
+    pub fn upload_all(
+        &mut self,
+        gl: &glow::Context,
+        texture_array: glow::Texture,
+        texture_provider: &TextureProvider,
+        brightness: f32,
+    ) {
+        let size = self.size as u16;
+        // Every layer gets pixels so faces with a missing texture show the placeholder instead
+        // of black.
+        let texture_ids: Vec<u32> = (0..self.row_hashes.len() as u32).collect();
+        let decoded = crate::parallel::map(&texture_ids, |&texture_id| {
+            texture_provider.get_pixels_or_placeholder(
+                texture_id,
+                size,
+                size,
+                false,
+                brightness as f64,
+            )
+        });
+
+        Self::bind(gl, texture_array);
+        for (&texture_id, pixels) in texture_ids.iter().zip(decoded) {
+            self.upload_layer(gl, texture_id, &pixels);
+        }
+        self.dirty.clear();
+        self.queued.fill(false);
+    }
+
+    /// Decodes and uploads queued layers until this frame's budget is spent, returning the bytes
+    /// uploaded.
+    pub fn upload_pending(
+        &mut self,
+        gl: &glow::Context,
+        texture_array: glow::Texture,
+        texture_provider: &TextureProvider,
+        brightness: f32,
+    ) -> usize {
+        let size = self.size as u16;
+        let mut uploaded = 0;
+        let mut decoded = 0;
+        Self::bind(gl, texture_array);
+        while uploaded < Self::FRAME_BYTE_BUDGET && decoded < Self::FRAME_LAYER_LIMIT {
+            let Some(texture_id) = self.dirty.pop_front() else {
+                break;
+            };
+            self.queued[texture_id as usize] = false;
+            let pixels = texture_provider.get_pixels_or_placeholder(
+                texture_id,
+                size,
+                size,
+                false,
+                brightness as f64,
+            );
+            uploaded += self.upload_layer(gl, texture_id, &pixels);
+            decoded += 1;
+        }
+        uploaded
+    }
+
+    fn bind(gl: &glow::Context, texture_array: glow::Texture) {
+        unsafe {
+            gl.active_texture(glow::TEXTURE0);
+            gl.bind_texture(glow::TEXTURE_2D_ARRAY, Some(texture_array));
+        }
+    }
+
+    /// Uploads the rows of the layer that changed, returning the bytes uploaded. Changed rows are
+    /// sent as one span from the first to the last, since a span of whole rows is contiguous in
+    /// `pixels`.
+    fn upload_layer(&mut self, gl: &glow::Context, texture_id: u32, pixels: &[u32]) -> usize {
+        let width = self.size as usize;
+        let hashes: Vec<u64> = pixels
+            .chunks_exact(width)
+            .map(|row| {
+                let mut hasher = DefaultHasher::new();
+                row.hash(&mut hasher);
+                hasher.finish()
+            })
+            .collect();
+        let previous = &self.row_hashes[texture_id as usize];
+        let changed = |&y: &usize| previous.get(y) != Some(&hashes[y]);
+        let Some(first) = (0..hashes.len()).find(changed) else {
+            return 0;
+        };
+        let last = (0..hashes.len()).rfind(changed).unwrap_or(first);
+
+        let rows = &pixels[first * width..(last + 1) * width];
+        unsafe {
+            gl.tex_sub_image_3d(
+                glow::TEXTURE_2D_ARRAY,
+                0,
+                0,
+                first as i32,
+                texture_id as i32,
+                self.size,
+                (last + 1 - first) as i32,
+                1,
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                glow::PixelUnpackData::Slice(Some(bytemuck::cast_slice(rows))),
+            );
+        }
+        self.row_hashes[texture_id as usize] = hashes;
+        std::mem::size_of_val(rows)
+    }
+}
@@ -0,0 +1,133 @@
+use crate::runetek5::graphics::{
+    model::{ModelFlags, ModelLit, ModelUnlit, TriangleOrder, TriangleSortKey},
+    texture::TextureProvider,
+};
+
+/// Shows the key each triangle of the shown model is sorted by, in draw order, and lets the
+/// viewport draw them in another order to diagnose transparency and effect ordering.
+pub struct TriangleOrderWindow {
+    order: TriangleOrder,
+    changed: bool,
+    /// The shown model's drawn triangles, in file order.
+    keys: Vec<TriangleSortKey>,
+    /// Positions into `keys` in draw order.
+    draw_order: Vec<usize>,
+    transparent_only: bool,
+}
+
+impl TriangleOrderWindow {
+    const ROW_HEIGHT: f32 = 16.0;
+
+    pub fn new() -> Self {
+        Self {
+            order: TriangleOrder::default(),
+            changed: false,
+            keys: Vec::new(),
+            draw_order: Vec::new(),
+            transparent_only: false,
+        }
+    }
+
+    pub fn order(&self) -> TriangleOrder {
+        self.order
+    }
+
+    /// Whether the order changed since the last call, so the model has to be lit again.
+    pub fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+
+    /// Sets the model to show the keys of, as it is lit for the viewport.
+    pub fn set_model(&mut self, texture_provider: &TextureProvider, model: &ModelUnlit) {
+        let substituted = texture_provider
+            .material_rules()
+            .apply(texture_provider, model);
+        let model = substituted.as_ref().unwrap_or(model);
+        let triangles = ModelLit::drawn_triangles(texture_provider, model);
+        self.keys =
+            ModelLit::triangle_sort_keys(texture_provider, model, ModelFlags::empty(), &triangles);
+        self.sort();
+    }
+
+    fn sort(&mut self) {
+        let order = self.order;
+        let keys = &self.keys;
+        self.draw_order = (0..keys.len()).collect();
+        self.draw_order
+            .sort_unstable_by_key(|&i| keys[i].packed(order));
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Triangle Order")
+            .default_open(false)
+            .resizable(true)
+            .scroll(false)
+            .show(ctx, |ui| {
+                self.ui(ui);
+            });
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let previous = self.order;
+            egui::ComboBox::from_label("Order")
+                .selected_text(self.order.label())
+                .show_ui(ui, |ui| {
+                    for order in TriangleOrder::ALL {
+                        ui.selectable_value(&mut self.order, order, order.label());
+                    }
+                })
+                .response
+                .on_hover_text("The order the viewport draws triangles in");
+            if self.order != previous {
+                self.sort();
+                self.changed = true;
+            }
+            ui.checkbox(&mut self.transparent_only, "Transparent only");
+        });
+        let transparent_count = self.keys.iter().filter(|key| key.transparent).count();
+        ui.label(format!(
+            "{} drawn triangles, {transparent_count} transparent",
+            self.keys.len()
+        ));
+        ui.separator();
+
+        // Pairs of draw position and position into the keys.
+        let rows: Vec<(usize, usize)> = self
+            .draw_order
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|&(_, i)| !self.transparent_only || self.keys[i].transparent)
+            .collect();
+        ui.monospace(format!(
+            "{:>5} {:>5} {:>4} {:>5} {:>4} {:>4} {:>5} {:>16}",
+            "Draw", "Tri", "Pri", "Alpha", "Fx", "Cfg", "Mat", "Key"
+        ));
+        egui::ScrollArea::vertical()
+            .id_salt("triangle_order_keys")
+            .auto_shrink(false)
+            .show_rows(ui, Self::ROW_HEIGHT, rows.len(), |ui, row_range| {
+                for &(draw_index, i) in &rows[row_range] {
+                    let key = &self.keys[i];
+                    // Priorities the client order ignores are shown in brackets.
+                    let priority = if key.priority_applies {
+                        key.priority.to_string()
+                    } else {
+                        format!("({})", key.priority)
+                    };
+                    ui.monospace(format!(
+                        "{:>5} {:>5} {:>4} {:>5} {:>4} {:>4} {:>5} {:016x}",
+                        draw_index,
+                        key.triangle,
+                        priority,
+                        if key.transparent { "yes" } else { "" },
+                        key.effect_id,
+                        key.effect_config0,
+                        key.texture_id,
+                        key.packed(self.order),
+                    ));
+                }
+            });
+    }
+}
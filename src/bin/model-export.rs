@@ -0,0 +1,533 @@
+//! Exports models, NPCs and items from a cache without starting the viewer, for batch pipelines
+//! and extracting assets in CI. Run with `--help` for the arguments.
+
+#![warn(clippy::all, rust_2018_idioms)]
+
+#[cfg(not(target_arch = "wasm32"))]
+const USAGE: &str = "\
+Usage: model-export [options] <kind>:<ids>...
+
+Exports each id to <out>/<kind>s/<id>.glb (or .gltf and .bin), e.g. `model:0-99 npc:1,2`.
+
+Kinds:
+  model    a model of the model archive
+  npc      an NPC's models merged, recoloured and resized like the client
+  item     an item's ground model, recoloured and resized like the client
+
+Options:
+  --cache <dir>       the directory holding main_file_cache.dat2 [default: cache]
+  --openrs2 <id>      streams the cache with this id from OpenRS2 instead
+  --out <dir>         where to write the exports [default: export]
+  --preset <preset>   a preset name or key=value pairs, e.g. format=gltf,scale=512,up=z
+  --brightness <b>    the palette brightness, from 0.5 to 1.0 [default: 0.7]
+  --rig-skins         exports the vertex skin labels as joints
+  --definitions       also writes NPC and item definitions to <out>/<kind>s/<id>.json
+  --dump-schema <dir> writes the JSON Schemas of the JSON files exports contain to <dir>, ids
+                      are optional with this
+  -h, --help          shows this message";
+
+/// What an id refers to.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExportKind {
+    Model,
+    Npc,
+    Item,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ExportKind {
+    fn parse(text: &str) -> Result<Self, String> {
+        match text {
+            "model" | "models" => Ok(ExportKind::Model),
+            "npc" | "npcs" => Ok(ExportKind::Npc),
+            "item" | "items" | "obj" | "objs" => Ok(ExportKind::Item),
+            _ => Err(format!(
+                "unknown kind {text:?}, expected model, npc or item"
+            )),
+        }
+    }
+
+    fn dir(&self) -> &'static str {
+        match self {
+            ExportKind::Model => "models",
+            ExportKind::Npc => "npcs",
+            ExportKind::Item => "items",
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+struct Args {
+    cache_dir: std::path::PathBuf,
+    openrs2_cache_id: Option<u32>,
+    out_dir: std::path::PathBuf,
+    preset: rs_model_viewer::export::preset::ExportPreset,
+    brightness: f64,
+    rig_skins: bool,
+    definitions: bool,
+    dump_schema_dir: Option<std::path::PathBuf>,
+    ids: Vec<(ExportKind, u32)>,
+}
+
+/// Parses ids separated by commas, with ranges written as `first-last`.
+#[cfg(not(target_arch = "wasm32"))]
+fn parse_ids(text: &str) -> Result<Vec<u32>, String> {
+    let parse = |id: &str| {
+        id.trim()
+            .parse::<u32>()
+            .map_err(|_| format!("invalid id {id:?}"))
+    };
+    let mut ids = Vec::new();
+    for part in text.split(',').filter(|part| !part.trim().is_empty()) {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let (first, last) = (parse(first)?, parse(last)?);
+                if first > last {
+                    return Err(format!("empty range {part:?}"));
+                }
+                ids.extend(first..=last);
+            }
+            None => ids.push(parse(part)?),
+        }
+    }
+    Ok(ids)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        cache_dir: "cache".into(),
+        openrs2_cache_id: None,
+        out_dir: "export".into(),
+        preset: Default::default(),
+        brightness: 0.7,
+        rig_skins: false,
+        definitions: false,
+        dump_schema_dir: None,
+        ids: Vec::new(),
+    };
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let mut value = || iter.next().ok_or_else(|| format!("{arg} needs a value"));
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                std::process::exit(0);
+            }
+            "--cache" => args.cache_dir = value()?.into(),
+            "--openrs2" => {
+                let id = value()?;
+                let id = id.parse().map_err(|_| format!("invalid cache id {id:?}"))?;
+                args.openrs2_cache_id = Some(id);
+            }
+            "--out" => args.out_dir = value()?.into(),
+            "--preset" => args.preset = value()?.parse()?,
+            "--brightness" => {
+                let brightness = value()?;
+                args.brightness = brightness
+                    .parse()
+                    .ok()
+                    .filter(|brightness| (0.5..=1.0).contains(brightness))
+                    .ok_or_else(|| format!("invalid brightness {brightness:?}"))?;
+            }
+            "--rig-skins" => args.rig_skins = true,
+            "--definitions" => args.definitions = true,
+            "--dump-schema" => args.dump_schema_dir = Some(value()?.into()),
+            _ if arg.starts_with('-') => return Err(format!("unknown option {arg}")),
+            _ => {
+                let (kind, ids) = arg
+                    .split_once(':')
+                    .ok_or_else(|| format!("expected <kind>:<ids>, got {arg:?}"))?;
+                let kind = ExportKind::parse(kind)?;
+                args.ids
+                    .extend(parse_ids(ids)?.into_iter().map(|id| (kind, id)));
+            }
+        }
+    }
+    if args.ids.is_empty() && args.dump_schema_dir.is_none() {
+        return Err("nothing to export".to_owned());
+    }
+    Ok(args)
+}
+
+/// Where the archives are read from.
+#[cfg(not(target_arch = "wasm32"))]
+enum CacheSource {
+    Disk(std::sync::Arc<rs_model_viewer::runetek5::js5::disk::Js5DiskStore>),
+    Openrs2(std::sync::Arc<rs_model_viewer::runetek5::js5::net::Openrs2Js5NetClient>),
+}
+
+/// An opened archive, with its network provider when streamed to check for failed groups.
+#[cfg(not(target_arch = "wasm32"))]
+struct Archive {
+    js5: std::sync::Arc<rs_model_viewer::runetek5::js5::Js5>,
+    net: Option<std::sync::Arc<rs_model_viewer::runetek5::js5::net::Openrs2Js5ResourceProvider>>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl CacheSource {
+    /// Opens an archive, None if the cache doesn't have it.
+    fn open_archive(
+        &self,
+        archive_id: u8,
+        id_policy: rs_model_viewer::runetek5::js5::Js5IdPolicy,
+    ) -> Option<Archive> {
+        use std::sync::Arc;
+
+        use rs_model_viewer::{
+            jobs,
+            runetek5::js5::{
+                disk::Js5DiskResourceProvider, net::Openrs2Js5ResourceProvider, Js5,
+                Js5ResourceProvider as _,
+            },
+        };
+
+        match self {
+            CacheSource::Disk(store) => {
+                let provider = Arc::new(Js5DiskResourceProvider::new(archive_id, store.clone()));
+                let index = provider.fetch_index()?;
+                Some(Archive {
+                    js5: Arc::new(
+                        Js5::new(provider, index, false, false).with_id_policy(id_policy),
+                    ),
+                    net: None,
+                })
+            }
+            CacheSource::Openrs2(net_client) => {
+                let provider = Arc::new(Openrs2Js5ResourceProvider::new(
+                    archive_id,
+                    net_client.clone(),
+                ));
+                let index = jobs::block_on(provider.index_ready()).ok()?;
+                Some(Archive {
+                    js5: Arc::new(
+                        Js5::new(provider.clone(), index, false, false).with_id_policy(id_policy),
+                    ),
+                    net: Some(provider),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Archive {
+    /// Fetches every group, false if some never will be.
+    fn fetch_all(&self) -> bool {
+        use rs_model_viewer::jobs;
+
+        match &self.net {
+            Some(net) => jobs::block_on(net.ready(|| self.js5.fetch_all())).is_ok(),
+            None => self.js5.fetch_all(),
+        }
+    }
+
+    /// Waits until the group is fetched, false if it never will be.
+    fn wait_for_group(&self, group_id: u32) -> bool {
+        use rs_model_viewer::jobs;
+
+        if !self.js5.is_group_valid(group_id) {
+            return false;
+        }
+        let Some(net) = &self.net else {
+            return self.js5.is_group_ready(group_id);
+        };
+        jobs::block_on(net.wait_for(|| {
+            if self.js5.is_group_ready(group_id) {
+                return Some(true);
+            }
+            net.get_failed_groups()
+                .iter()
+                .any(|&(failed_group_id, _)| failed_group_id == group_id)
+                .then_some(false)
+        }))
+    }
+
+    /// Reads a file, waiting for its group. None if it doesn't exist or the group failed.
+    fn get_file(&self, group_id: u32, file_id: u32) -> Option<bytes::Bytes> {
+        if !self.wait_for_group(group_id) {
+            return None;
+        }
+        self.js5.get_file(group_id, file_id)
+    }
+}
+
+/// The archives an export reads from.
+#[cfg(not(target_arch = "wasm32"))]
+struct Exporter {
+    source: CacheSource,
+    models: Archive,
+    texture_provider: rs_model_viewer::runetek5::graphics::texture::TextureProvider,
+    /// Opened on first use, as (archive id, archive) with None when the cache lacks it.
+    archives: Vec<(u8, Option<Archive>)>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Exporter {
+    fn open(source: CacheSource) -> Result<Self, String> {
+        use rs_model_viewer::{
+            jobs,
+            runetek5::{graphics::texture::TextureProvider, js5::Js5IdPolicy},
+        };
+
+        let open = |archive_id, id_policy| {
+            source
+                .open_archive(archive_id, id_policy)
+                .ok_or_else(|| format!("The cache has no archive {archive_id}."))
+        };
+        let models = open(7, Js5IdPolicy::Auto)?;
+        // Sprite ids are always group ids.
+        let sprites = open(8, Js5IdPolicy::Group)?;
+        let textures = open(9, Js5IdPolicy::Auto)?;
+        if !textures.fetch_all() {
+            log::warn!("The cache is missing some texture groups");
+        }
+        let texture_provider = TextureProvider::new(sprites.js5.clone(), &textures.js5);
+        // The provider fetches the sprites of the textures as they are asked for.
+        let sprites_loaded = match &sprites.net {
+            Some(net) => {
+                jobs::block_on(net.ready(|| texture_provider.get_loaded_percentage() == 100))
+                    .is_ok()
+            }
+            None => texture_provider.get_loaded_percentage() == 100,
+        };
+        if !sprites_loaded {
+            let loaded_percentage = texture_provider.get_loaded_percentage();
+            log::warn!("Only {loaded_percentage}% of the texture sprites are in the cache");
+        }
+        Ok(Self {
+            source,
+            models,
+            texture_provider,
+            archives: Vec::new(),
+        })
+    }
+
+    fn archive(&mut self, archive_id: u8) -> Option<&Archive> {
+        let position = match self.archives.iter().position(|(id, _)| *id == archive_id) {
+            Some(position) => position,
+            None => {
+                let archive = self.source.open_archive(archive_id, Default::default());
+                self.archives.push((archive_id, archive));
+                self.archives.len() - 1
+            }
+        };
+        self.archives[position].1.as_ref()
+    }
+
+    /// Decodes a model as the client scales it.
+    fn model_unlit(
+        &self,
+        model_id: u32,
+    ) -> Result<rs_model_viewer::runetek5::graphics::model::ModelUnlit, String> {
+        use rs_model_viewer::runetek5::graphics::model::ModelUnlit;
+
+        let data = self
+            .models
+            .get_file(model_id, 0)
+            .ok_or_else(|| format!("model {model_id} is not in the cache"))?;
+        let mut model = ModelUnlit::from_data(&data)
+            .map_err(|err| format!("model {model_id} couldn't be decoded: {err}"))?;
+        if model.version < 13 {
+            model.scale_log2(2);
+        }
+        Ok(model)
+    }
+
+    /// The model of an id lit like the client lights it, with the unlit model for its skins and
+    /// the definition it was built from as JSON.
+    fn load(
+        &mut self,
+        kind: ExportKind,
+        id: u32,
+    ) -> Result<
+        (
+            rs_model_viewer::runetek5::graphics::model::ModelUnlit,
+            rs_model_viewer::runetek5::graphics::model::ModelLit,
+            Option<String>,
+        ),
+        String,
+    > {
+        use rs_model_viewer::{
+            export::schema::{item_json, npc_json},
+            runetek5::{
+                graphics::model::{ModelFlags, ModelLit, ModelUnlit},
+                npc::NpcArchive,
+                obj::ObjArchive,
+            },
+        };
+
+        let light = |exporter: &Self, model: &ModelUnlit, (ambient, contrast): (i16, i16)| {
+            ModelLit::from_unlit(
+                &exporter.texture_provider,
+                model,
+                ModelFlags::empty(),
+                ambient,
+                contrast,
+            )
+        };
+        match kind {
+            ExportKind::Model => {
+                let model_unlit = self.model_unlit(id)?;
+                let model = light(self, &model_unlit, (64, 768));
+                Ok((model_unlit, model, None))
+            }
+            ExportKind::Npc => {
+                let npc = [NpcArchive::Npcs, NpcArchive::Config]
+                    .into_iter()
+                    .find_map(|source| {
+                        let archive = self.archive(source.archive_id())?;
+                        let (group_id, file_id) = source.location(id);
+                        let data = archive.get_file(group_id, file_id)?;
+                        rs_model_viewer::runetek5::npc::NpcType::decode(id, &data)
+                    })
+                    .ok_or_else(|| format!("npc {id} is not in the cache"))?;
+                if npc.models.is_empty() {
+                    return Err(format!("npc {id} has no models"));
+                }
+                let models = npc
+                    .models
+                    .iter()
+                    .map(|&model_id| self.model_unlit(model_id))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let mut model_unlit = ModelUnlit::merge(&models)
+                    .map_err(|size| format!("npc {id}'s models are too large to merge: {size}"))?;
+                for &(old_colour, new_colour) in &npc.recolours {
+                    model_unlit.recolour(old_colour, new_colour);
+                }
+                let mut model = light(self, &model_unlit, npc.lighting());
+                for &(old_texture, new_texture) in &npc.retextures {
+                    model.replace_material(old_texture as i16, new_texture as i16);
+                }
+                let (h, v) = (npc.resize_h as i32, npc.resize_v as i32);
+                model.scale(h, v, h);
+                Ok((model_unlit, model, Some(npc_json(&npc))))
+            }
+            ExportKind::Item => {
+                let obj = [ObjArchive::Objs, ObjArchive::Config]
+                    .into_iter()
+                    .find_map(|source| {
+                        let archive = self.archive(source.archive_id())?;
+                        let (group_id, file_id) = source.location(id);
+                        let data = archive.get_file(group_id, file_id)?;
+                        rs_model_viewer::runetek5::obj::ObjType::decode(id, &data)
+                    })
+                    .ok_or_else(|| format!("item {id} is not in the cache"))?;
+                let model_id = obj.model.ok_or_else(|| format!("item {id} has no model"))?;
+                let mut model_unlit = self.model_unlit(model_id)?;
+                for &(old_colour, new_colour) in &obj.recolours {
+                    model_unlit.recolour(old_colour, new_colour);
+                }
+                let mut model = light(self, &model_unlit, obj.lighting());
+                for &(old_texture, new_texture) in &obj.retextures {
+                    model.replace_material(old_texture as i16, new_texture as i16);
+                }
+                let [x, y, z] = obj.resize.map(i32::from);
+                model.scale(x, y, z);
+                Ok((model_unlit, model, Some(item_json(&obj))))
+            }
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn run(args: Args) -> Result<(), String> {
+    use std::sync::Arc;
+
+    use rs_model_viewer::{
+        export::{schema::Schema, write_files, ExportFile},
+        runetek5::{
+            graphics::texture::TextureFilter,
+            js5::{disk::Js5DiskStore, net::Openrs2Js5NetClient},
+        },
+    };
+
+    if let Some(dir) = &args.dump_schema_dir {
+        let files: Vec<ExportFile> = Schema::ALL
+            .iter()
+            .map(|schema| ExportFile {
+                path: schema.file_name(),
+                data: schema.to_json().into_bytes(),
+            })
+            .collect();
+        write_files(dir, &files)
+            .map_err(|err| format!("Failed to write {}: {err}", dir.display()))?;
+        println!("Wrote {} schemas to {}", files.len(), dir.display());
+        if args.ids.is_empty() {
+            return Ok(());
+        }
+    }
+
+    let source = match args.openrs2_cache_id {
+        Some(cache_id) => CacheSource::Openrs2(Arc::new(Openrs2Js5NetClient::new(cache_id))),
+        None => {
+            let store = Js5DiskStore::open(&args.cache_dir).map_err(|err| {
+                format!(
+                    "Failed to open the cache in {}: {err}",
+                    args.cache_dir.display()
+                )
+            })?;
+            CacheSource::Disk(Arc::new(store))
+        }
+    };
+    let mut exporter = Exporter::open(source)?;
+    let options =
+        args.preset
+            .gltf_options(args.brightness, TextureFilter::default(), args.rig_skins);
+
+    let mut failed = 0;
+    for &(kind, id) in &args.ids {
+        let (model_unlit, model, definition) = match exporter.load(kind, id) {
+            Ok(loaded) => loaded,
+            Err(message) => {
+                eprintln!("Skipping {message}");
+                failed += 1;
+                continue;
+            }
+        };
+        let name = format!("{}/{id}", kind.dir());
+        let mut files = args.preset.export(
+            &exporter.texture_provider,
+            &model,
+            model_unlit.vertex_skins.as_deref(),
+            options,
+            &name,
+        );
+        if let Some(definition) = definition.filter(|_| args.definitions) {
+            files.push(ExportFile {
+                path: format!("{name}.json"),
+                data: definition.into_bytes(),
+            });
+        }
+        write_files(&args.out_dir, &files)
+            .map_err(|err| format!("Failed to write {}: {err}", args.out_dir.display()))?;
+    }
+    println!(
+        "Exported {} of {} to {}",
+        args.ids.len() - failed,
+        args.ids.len(),
+        args.out_dir.display()
+    );
+    if failed > 0 {
+        return Err(format!("{failed} could not be exported"));
+    }
+    Ok(())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() {
+    env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
+
+    let result = parse_args()
+        .map_err(|message| format!("{message}\n\n{USAGE}"))
+        .and_then(run);
+    if let Err(message) = result {
+        eprintln!("{message}");
+        std::process::exit(1);
+    }
+}
+
+// There is no file system to export to on the web.
+#[cfg(target_arch = "wasm32")]
+fn main() {}
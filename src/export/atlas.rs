@@ -0,0 +1,174 @@
+//! Packs textures into atlas pages, so exports of scenes using many textures need a single
+//! material per alpha mode instead of one per texture. Images are placed on shelves, tallest
+//! first, and surrounded by copies of their edge pixels so filtering doesn't pick up their
+//! neighbours.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AtlasOptions {
+    /// The largest width and height of a page in pixels, textures that don't fit on one page
+    /// start another.
+    pub size: u32,
+    /// Pixels of repeated edge around each texture.
+    pub padding: u32,
+}
+
+impl AtlasOptions {
+    pub const DEFAULT_SIZE: u32 = 2048;
+    pub const DEFAULT_PADDING: u32 = 2;
+}
+
+impl Default for AtlasOptions {
+    fn default() -> Self {
+        Self {
+            size: Self::DEFAULT_SIZE,
+            padding: Self::DEFAULT_PADDING,
+        }
+    }
+}
+
+/// An RGBA image to pack.
+pub struct AtlasImage<'a> {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: &'a [u8],
+}
+
+/// Where an image ended up, without its padding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AtlasPlacement {
+    pub page: usize,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+pub struct AtlasPage {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+pub struct Atlas {
+    pub pages: Vec<AtlasPage>,
+    /// One per packed image, None for empty images and images larger than a page.
+    pub placements: Vec<Option<AtlasPlacement>>,
+}
+
+impl Atlas {
+    /// Maps coordinates from 0 to 1 across a placed image to coordinates into its page.
+    pub fn texcoord(&self, placement: &AtlasPlacement, [u, v]: [f32; 2]) -> [f32; 2] {
+        let page = &self.pages[placement.page];
+        [
+            (placement.x as f32 + u * placement.width as f32) / page.width as f32,
+            (placement.y as f32 + v * placement.height as f32) / page.height as f32,
+        ]
+    }
+}
+
+/// A row of images as tall as the first placed on it.
+struct Shelf {
+    y: u32,
+    height: u32,
+    used_width: u32,
+}
+
+/// Packs the images, in pages no larger than `options.size` and as small as the images allow,
+/// rounded up to powers of two.
+pub fn pack(images: &[AtlasImage], options: AtlasOptions) -> Atlas {
+    let padding = options.padding;
+    let size = options.size.max(1);
+    let mut order: Vec<usize> = (0..images.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse((images[i].height, images[i].width)));
+
+    let mut placements = vec![None; images.len()];
+    // The shelves of each page.
+    let mut pages: Vec<Vec<Shelf>> = Vec::new();
+    for i in order {
+        let (width, height) = (
+            images[i].width + padding * 2,
+            images[i].height + padding * 2,
+        );
+        let empty = images[i].width == 0 || images[i].height == 0;
+        if empty || width > size || height > size {
+            continue;
+        }
+        let mut spot = None;
+        'pages: for (page, shelves) in pages.iter_mut().enumerate() {
+            for shelf in shelves.iter_mut() {
+                if height <= shelf.height && shelf.used_width + width <= size {
+                    spot = Some((page, shelf.used_width, shelf.y));
+                    shelf.used_width += width;
+                    break 'pages;
+                }
+            }
+            let top = shelves.last().map_or(0, |shelf| shelf.y + shelf.height);
+            if top + height <= size {
+                shelves.push(Shelf {
+                    y: top,
+                    height,
+                    used_width: width,
+                });
+                spot = Some((page, 0, top));
+                break;
+            }
+        }
+        let (page, x, y) = spot.unwrap_or_else(|| {
+            pages.push(vec![Shelf {
+                y: 0,
+                height,
+                used_width: width,
+            }]);
+            (pages.len() - 1, 0, 0)
+        });
+        placements[i] = Some(AtlasPlacement {
+            page,
+            x: x + padding,
+            y: y + padding,
+            width: images[i].width,
+            height: images[i].height,
+        });
+    }
+
+    let mut pages: Vec<AtlasPage> = pages
+        .iter()
+        .map(|shelves| {
+            let width = shelves
+                .iter()
+                .map(|shelf| shelf.used_width)
+                .max()
+                .unwrap_or(1);
+            let height = shelves.last().map_or(1, |shelf| shelf.y + shelf.height);
+            let (width, height) = (
+                width.next_power_of_two().min(size),
+                height.next_power_of_two().min(size),
+            );
+            AtlasPage {
+                width,
+                height,
+                rgba: vec![0; (width * height * 4) as usize],
+            }
+        })
+        .collect();
+    for (image, placement) in images.iter().zip(&placements) {
+        if let Some(placement) = placement {
+            blit_padded(&mut pages[placement.page], image, placement, padding);
+        }
+    }
+
+    Atlas { pages, placements }
+}
+
+/// Copies the image to its place on the page, extending its edge pixels into the padding.
+fn blit_padded(page: &mut AtlasPage, image: &AtlasImage, placement: &AtlasPlacement, padding: u32) {
+    let (left, top) = (placement.x - padding, placement.y - padding);
+    for y in 0..image.height + padding * 2 {
+        let source_y = y.saturating_sub(padding).min(image.height - 1);
+        for x in 0..image.width + padding * 2 {
+            let source_x = x.saturating_sub(padding).min(image.width - 1);
+            let source = ((source_y * image.width + source_x) * 4) as usize;
+            let target = (((top + y) * page.width + left + x) * 4) as usize;
+            page.rgba[target..target + 4].copy_from_slice(&image.rgba[source..source + 4]);
+        }
+    }
+}
@@ -0,0 +1,269 @@
+//! Bakes vertex colours into textures, for export targets that don't support vertex colours. Every
+//! triangle gets a cell of its own in an atlas, filled with its vertex colours interpolated across
+//! it and multiplied with its texture, and texture coordinates pointing into the cell. Shading
+//! normals are baked into normal maps the same way.
+
+use super::gltf::srgb_to_linear;
+
+/// Pixels along each side of a triangle's cell.
+const CELL_SIZE: usize = 8;
+
+/// Cells of `columns` triangles a row, the same for colours and normals of as many triangles.
+struct CellLayout {
+    columns: usize,
+    width: usize,
+    height: usize,
+}
+
+impl CellLayout {
+    fn new(triangle_count: usize) -> Self {
+        let columns = ((triangle_count as f64).sqrt().ceil() as usize).max(1);
+        let rows = triangle_count.div_ceil(columns).max(1);
+        Self {
+            columns,
+            width: columns * CELL_SIZE,
+            height: rows * CELL_SIZE,
+        }
+    }
+
+    /// The top left pixel of triangle `t`'s cell.
+    fn cell(&self, t: usize) -> (usize, usize) {
+        (
+            (t % self.columns) * CELL_SIZE,
+            (t / self.columns) * CELL_SIZE,
+        )
+    }
+
+    /// Coordinates of triangle `t`'s corners, on the centres of three of its cell's corner
+    /// pixels. U runs from the first corner to the second and V to the third.
+    fn texcoords(&self, t: usize) -> [[f32; 2]; 3] {
+        let (cell_x, cell_y) = self.cell(t);
+        let (low, high) = (0.5, CELL_SIZE as f32 - 0.5);
+        [[low, low], [high, low], [low, high]].map(|[x, y]| {
+            [
+                (cell_x as f32 + x) / self.width as f32,
+                (cell_y as f32 + y) / self.height as f32,
+            ]
+        })
+    }
+}
+
+pub struct BakedAtlas {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+    /// Coordinates into the atlas, one per vertex of the baked triangles.
+    pub texcoords: Vec<[f32; 2]>,
+}
+
+/// A square RGBA texture multiplied into the baked colours, with the triangles' coordinates into
+/// it. U is clamped and V repeats, like the client samples textures.
+pub struct BakeTexture<'a> {
+    pub size: usize,
+    pub rgba: &'a [u8],
+    pub texcoords: &'a [[f32; 2]],
+}
+
+impl BakeTexture<'_> {
+    fn sample(&self, [u, v]: [f32; 2]) -> [f32; 4] {
+        let x = ((u.clamp(0.0, 1.0) * self.size as f32) as usize).min(self.size - 1);
+        let y = ((v.rem_euclid(1.0) * self.size as f32) as usize).min(self.size - 1);
+        let texel = &self.rgba[(y * self.size + x) * 4..][..4];
+        [
+            srgb_to_linear(texel[0] as u32),
+            srgb_to_linear(texel[1] as u32),
+            srgb_to_linear(texel[2] as u32),
+            texel[3] as f32 / 255.0,
+        ]
+    }
+}
+
+fn linear_to_srgb(c: f32) -> u8 {
+    let c = c.clamp(0.0, 1.0);
+    let c = if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    };
+    (c * 255.0).round() as u8
+}
+
+/// Weights of a triangle's corners at each pixel of its cell. The triangle covers the lower half
+/// of the cell with its corners on pixel centres, pixels past its long edge take the colour of the
+/// nearest point on it so filtering never reaches the neighbouring cells.
+fn cell_weights() -> Vec<[f32; 3]> {
+    let span = CELL_SIZE as f32 - 1.0;
+    let mut weights = Vec::with_capacity(CELL_SIZE * CELL_SIZE);
+    for y in 0..CELL_SIZE {
+        for x in 0..CELL_SIZE {
+            let (mut u, mut v) = (x as f32 / span, y as f32 / span);
+            if u + v > 1.0 {
+                let sum = u + v;
+                u /= sum;
+                v /= sum;
+            }
+            weights.push([1.0 - u - v, u, v]);
+        }
+    }
+    weights
+}
+
+/// Bakes unindexed triangles, with three `colours` in linear RGBA per triangle.
+pub fn bake_triangles(colours: &[[f32; 4]], texture: Option<&BakeTexture>) -> BakedAtlas {
+    let triangle_count = colours.len() / 3;
+    let layout = CellLayout::new(triangle_count);
+    let width = layout.width;
+    let mut rgba = vec![0u8; width * layout.height * 4];
+    let mut texcoords = Vec::with_capacity(colours.len());
+
+    let weights = cell_weights();
+    for t in 0..triangle_count {
+        let (cell_x, cell_y) = layout.cell(t);
+        texcoords.extend_from_slice(&layout.texcoords(t));
+
+        let corners = &colours[t * 3..t * 3 + 3];
+        for (i, weight) in weights.iter().enumerate() {
+            let mut colour = [0.0f32; 4];
+            for (corner, &w) in corners.iter().zip(weight) {
+                for (channel, value) in colour.iter_mut().zip(corner) {
+                    *channel += value * w;
+                }
+            }
+            if let Some(texture) = texture {
+                let corners = &texture.texcoords[t * 3..t * 3 + 3];
+                let mut texcoord = [0.0f32; 2];
+                for (corner, &w) in corners.iter().zip(weight) {
+                    texcoord[0] += corner[0] * w;
+                    texcoord[1] += corner[1] * w;
+                }
+                let texel = texture.sample(texcoord);
+                for (channel, value) in colour.iter_mut().zip(texel) {
+                    *channel *= value;
+                }
+            }
+
+            let (x, y) = (cell_x + i % CELL_SIZE, cell_y + i / CELL_SIZE);
+            let pixel = &mut rgba[(y * width + x) * 4..][..4];
+            pixel[0] = linear_to_srgb(colour[0]);
+            pixel[1] = linear_to_srgb(colour[1]);
+            pixel[2] = linear_to_srgb(colour[2]);
+            pixel[3] = (colour[3].clamp(0.0, 1.0) * 255.0).round() as u8;
+        }
+    }
+
+    BakedAtlas {
+        width: width as u32,
+        height: layout.height as u32,
+        rgba,
+        texcoords,
+    }
+}
+
+pub struct BakedNormals {
+    pub atlas: BakedAtlas,
+    /// Tangents with the handedness of the bitangent in w, one per vertex.
+    pub tangents: Vec<[f32; 4]>,
+}
+
+fn normalize([x, y, z]: [f32; 3]) -> [f32; 3] {
+    let length = (x * x + y * y + z * z).sqrt();
+    if length > 0.0 {
+        [x / length, y / length, z / length]
+    } else {
+        [0.0, 0.0, 1.0]
+    }
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+/// The corners' values blended by a pixel's weights.
+fn blend(corners: &[[f32; 3]], weight: &[f32; 3]) -> [f32; 3] {
+    let mut value = [0.0f32; 3];
+    for (corner, &w) in corners.iter().zip(weight) {
+        for (channel, component) in value.iter_mut().zip(corner) {
+            *channel += component * w;
+        }
+    }
+    value
+}
+
+/// Bakes the `shading_normals` of unindexed triangles into a tangent-space normal map relative to
+/// their `normals`, which the map's users interpolate across each triangle. The cells and texture
+/// coordinates match [`bake_triangles`] for as many triangles.
+pub fn bake_normals(
+    positions: &[[f32; 3]],
+    normals: &[[f32; 3]],
+    shading_normals: &[[f32; 3]],
+) -> BakedNormals {
+    let triangle_count = positions.len() / 3;
+    let layout = CellLayout::new(triangle_count);
+    let width = layout.width;
+    let mut rgba = vec![0u8; width * layout.height * 4];
+    let mut texcoords = Vec::with_capacity(positions.len());
+    let mut tangents = Vec::with_capacity(positions.len());
+
+    let weights = cell_weights();
+    for t in 0..triangle_count {
+        let (cell_x, cell_y) = layout.cell(t);
+        texcoords.extend_from_slice(&layout.texcoords(t));
+
+        // U runs along the edge to the second corner.
+        let [a, b, _] = [0, 1, 2].map(|i| positions[t * 3 + i]);
+        let edge = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+        let corners = &normals[t * 3..t * 3 + 3];
+        let corner_tangents = corners.iter().map(|&normal| {
+            let along = dot(normal, edge);
+            normalize([
+                edge[0] - normal[0] * along,
+                edge[1] - normal[1] * along,
+                edge[2] - normal[2] * along,
+            ])
+        });
+        let corner_tangents: Vec<[f32; 3]> = corner_tangents.collect();
+        // V runs down the image towards the third corner, the bitangent points up it like glTF
+        // expects, which is a negative handedness for every cell.
+        tangents.extend(corner_tangents.iter().map(|&[x, y, z]| [x, y, z, -1.0]));
+
+        let shading = &shading_normals[t * 3..t * 3 + 3];
+        for (i, weight) in weights.iter().enumerate() {
+            let normal = normalize(blend(corners, weight));
+            let tangent = blend(&corner_tangents, weight);
+            let along = dot(normal, tangent);
+            let tangent = normalize([
+                tangent[0] - normal[0] * along,
+                tangent[1] - normal[1] * along,
+                tangent[2] - normal[2] * along,
+            ]);
+            let [x, y, z] = cross(normal, tangent);
+            let bitangent = [-x, -y, -z];
+            let shading = normalize(blend(shading, weight));
+
+            let (x, y) = (cell_x + i % CELL_SIZE, cell_y + i / CELL_SIZE);
+            let pixel = &mut rgba[(y * width + x) * 4..][..4];
+            for (channel, axis) in pixel.iter_mut().zip([tangent, bitangent, normal]) {
+                *channel = ((dot(shading, axis) * 0.5 + 0.5) * 255.0).round() as u8;
+            }
+            pixel[3] = 0xff;
+        }
+    }
+
+    BakedNormals {
+        atlas: BakedAtlas {
+            width: width as u32,
+            height: layout.height as u32,
+            rgba,
+            texcoords,
+        },
+        tangents,
+    }
+}
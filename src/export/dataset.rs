@@ -0,0 +1,201 @@
+use nalgebra_glm as glm;
+
+use super::{json, png, schema::SCHEMA_VERSION, ExportFile};
+use crate::runetek5::{
+    graphics::{
+        model::{ModelFlags, ModelLit, ModelUnlit},
+        rasterizer::Rasterizer,
+        texture::TextureProvider,
+    },
+    js5::Js5,
+};
+
+/// Builds a `models.json` manifest and one thumbnail PNG per model for a list of model ids.
+///
+/// Models are decoded a few per call to [`DatasetExport::step`] so the export can run alongside
+/// the UI while groups are still being fetched.
+pub struct DatasetExport {
+    pub start_id: u32,
+    pub end_id: u32,
+    ids: Vec<u32>,
+    next_index: usize,
+    thumbnail_size: usize,
+    rasterizer: Rasterizer,
+    entries: Vec<String>,
+    files: Vec<ExportFile>,
+}
+
+impl DatasetExport {
+    const MODELS_PER_STEP: usize = 8;
+
+    const YAW: f32 = 90.0;
+    const PITCH: f32 = 30.0;
+
+    /// Exports the models in `start_id..=end_id`, missing ids are skipped.
+    pub fn new(start_id: u32, end_id: u32, thumbnail_size: usize, brightness: f64) -> Self {
+        Self::from_ids((start_id..=end_id).collect(), thumbnail_size, brightness)
+    }
+
+    /// Exports the given models in ascending order, missing ids are skipped.
+    pub fn from_ids(mut ids: Vec<u32>, thumbnail_size: usize, brightness: f64) -> Self {
+        ids.sort_unstable();
+        ids.dedup();
+        let mut rasterizer = Rasterizer::new(brightness);
+        rasterizer.resize(thumbnail_size, thumbnail_size);
+        Self {
+            start_id: ids.first().copied().unwrap_or(0),
+            end_id: ids.last().copied().unwrap_or(0),
+            ids,
+            next_index: 0,
+            thumbnail_size,
+            rasterizer,
+            entries: Vec::new(),
+            files: Vec::new(),
+        }
+    }
+
+    pub fn exported_count(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// The number of ids processed so far and the total number of ids to export.
+    pub fn progress(&self) -> (u32, u32) {
+        (self.next_index as u32, self.ids.len() as u32)
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.next_index >= self.ids.len()
+    }
+
+    /// Returns true once every id in the range has been processed.
+    pub fn step(&mut self, model_js5: &Js5, texture_provider: &TextureProvider) -> bool {
+        let mut exported = 0;
+        while !self.is_done() && exported < Self::MODELS_PER_STEP {
+            let id = self.ids[self.next_index];
+            // Skip ids without a model and groups that never load, e.g. quarantined ones.
+            if model_js5.try_get_file(id, 0).is_ok() {
+                // Wait for groups that are still being fetched.
+                let Some(model_unlit) = ModelUnlit::from_js5(model_js5, id, 0) else {
+                    return false;
+                };
+                self.export_model(texture_provider, id, model_unlit);
+                exported += 1;
+            }
+            self.next_index += 1;
+        }
+        self.is_done()
+    }
+
+    fn export_model(
+        &mut self,
+        texture_provider: &TextureProvider,
+        id: u32,
+        mut model_unlit: ModelUnlit,
+    ) {
+        let version = model_unlit.version;
+        let fingerprint = model_unlit.fingerprint();
+        if version < 13 {
+            model_unlit.scale_log2(2);
+        }
+
+        let model =
+            ModelLit::from_unlit(texture_provider, &model_unlit, ModelFlags::empty(), 64, 768);
+        let mut model =
+            model.copy(ModelFlags::CHANGED_X | ModelFlags::CHANGED_Y | ModelFlags::CHANGED_Z);
+        let bounds = model.calculate_bounds();
+        let (center_x, center_y, center_z) = model.get_center();
+        model.translate(-center_x, -center_y, -center_z);
+
+        let thumbnail_path = format!("thumbnails/{id}.png");
+        let thumbnail = self.render_thumbnail(texture_provider, &mut model);
+        self.files.push(ExportFile {
+            path: thumbnail_path.clone(),
+            data: thumbnail,
+        });
+
+        let mut materials: Vec<i16> = model_unlit
+            .triangle_material
+            .iter()
+            .flatten()
+            .copied()
+            .filter(|&material| material != -1)
+            .collect();
+        materials.sort_unstable();
+        materials.dedup();
+
+        let box_ = &bounds.bounding_box;
+        let entry = json::JsonObject::new()
+            .number("id", id)
+            .number("version", version)
+            .number("vertexCount", model_unlit.vertex_count)
+            .number("triangleCount", model_unlit.triangle_count)
+            .number("texturedTriangleCount", model_unlit.textured_triangle_count)
+            .number("priority", model_unlit.priority)
+            .string("fingerprint", &format!("{fingerprint:016x}"))
+            .bool("transparent", model.is_transparent)
+            .bool("hasVertexSkins", model_unlit.vertex_skins.is_some())
+            .bool("hasTriangleSkins", model_unlit.triangle_skins.is_some())
+            .numbers("materials", &materials)
+            .numbers("boundsMin", &[box_.min_x, box_.min_y, box_.min_z])
+            .numbers("boundsMax", &[box_.max_x, box_.max_y, box_.max_z])
+            .number("xzRadius", bounds.xz_radius)
+            .number("xyzRadius", bounds.xyz_radius)
+            .string("thumbnail", &thumbnail_path)
+            .finish();
+        self.entries.push(entry);
+    }
+
+    fn render_thumbnail(
+        &mut self,
+        texture_provider: &TextureProvider,
+        model: &mut ModelLit,
+    ) -> Vec<u8> {
+        let radius = (model.get_xyz_radius() as f32 / 512.0 * 2.0).max(0.1);
+        let yaw = Self::YAW.to_radians();
+        let pitch = Self::PITCH.to_radians();
+        let camera_front = glm::vec3(
+            yaw.cos() * pitch.cos(),
+            pitch.sin(),
+            yaw.sin() * pitch.cos(),
+        );
+        let view = glm::look_at(
+            &(camera_front * radius),
+            &glm::vec3(0.0, 0.0, 0.0),
+            &glm::vec3(0.0, 1.0, 0.0),
+        );
+        let projection = glm::perspective(1.0, 60f32.to_radians(), 0.1, 100.0);
+
+        let (light_x, light_y, light_z) = ModelLit::DEFAULT_LIGHT_DIRECTION;
+        self.rasterizer.clear(0);
+        self.rasterizer.render_model(
+            texture_provider,
+            model,
+            &(projection * view),
+            light_x,
+            light_y,
+            light_z,
+        );
+
+        let size = self.thumbnail_size as u32;
+        png::encode_rgb(size, size, &self.rasterizer.pixels, Some(0))
+    }
+
+    /// Consumes the export, returning the manifest followed by the thumbnails.
+    pub fn finish(self) -> Vec<ExportFile> {
+        let manifest = json::JsonObject::new()
+            .number("schemaVersion", SCHEMA_VERSION)
+            .number("startId", self.start_id)
+            .number("endId", self.end_id)
+            .number("thumbnailSize", self.thumbnail_size as u32)
+            .raw("models", &json::array(&self.entries))
+            .finish();
+
+        let mut files = Vec::with_capacity(self.files.len() + 1);
+        files.push(ExportFile {
+            path: "models.json".to_owned(),
+            data: manifest.into_bytes(),
+        });
+        files.extend(self.files);
+        files
+    }
+}
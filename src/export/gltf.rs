@@ -0,0 +1,1048 @@
+use std::collections::BTreeMap;
+
+use super::{
+    atlas::{self, Atlas, AtlasImage, AtlasOptions},
+    bake::{bake_normals, bake_triangles, BakeTexture},
+    json, png,
+};
+use crate::runetek5::graphics::{
+    colour::hsl_to_rgb,
+    model::{Hsl, ModelLit, TriangleShading},
+    texture::{TextureFilter, TextureProvider},
+};
+
+const ARRAY_BUFFER: u32 = 34962;
+const UNSIGNED_BYTE: u32 = 5121;
+const SHORT: u32 = 5122;
+const UNSIGNED_SHORT: u32 = 5123;
+const FLOAT: u32 = 5126;
+const NEAREST: u32 = 9728;
+const LINEAR: u32 = 9729;
+const CLAMP_TO_EDGE: u32 = 33071;
+const REPEAT: u32 = 10497;
+
+/// Width and height the textures are embedded at.
+const TEXTURE_SIZE: usize = 128;
+/// Width and height of the white image untextured triangles sample in an atlas.
+const WHITE_SIZE: usize = 4;
+
+const GLB_MAGIC: u32 = 0x46546c67;
+const CHUNK_JSON: u32 = 0x4e4f534a;
+const CHUNK_BIN: u32 = 0x004e4942;
+
+/// Which way is up in the exported model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpAxis {
+    /// glTF's own convention.
+    Y,
+    /// For tools that import glTF without converting it to their Z up space.
+    Z,
+}
+
+impl UpAxis {
+    pub const ALL: [UpAxis; 2] = [UpAxis::Y, UpAxis::Z];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            UpAxis::Y => "Y up",
+            UpAxis::Z => "Z up",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct GltfOptions {
+    pub brightness: f64,
+    /// Turns the vertex skin labels into joints, one per label, with identity bind poses so the
+    /// model can be posed by the game's animation groups in external tools.
+    pub rig_skins: bool,
+    pub texture_filter: TextureFilter,
+    /// Output units per 512 model units, the size of a tile.
+    pub scale: f32,
+    pub up_axis: UpAxis,
+    /// Embeds the textures, textured triangles are exported with their shading only otherwise.
+    pub textures: bool,
+    /// Bakes the client's lighting into the vertex colours, exports flat colours otherwise.
+    pub baked_lighting: bool,
+    /// Bakes the vertex colours, multiplied with the textures, into one generated texture per
+    /// material with new texture coordinates, for targets that ignore vertex colours.
+    pub bake_colours: bool,
+    /// Stores positions as 16 bit integers in model units, scaled by the mesh node, and colours,
+    /// texture coordinates and weights as normalized integers, roughly halving the buffer. Needs
+    /// `KHR_mesh_quantization`, which three.js, Babylon.js and Blender support. Rigged models and
+    /// models outside the 16 bit range keep float positions, since skinned meshes ignore the
+    /// node's scale. Draco isn't offered, there's no pure Rust encoder for it.
+    pub quantize: bool,
+    /// Packs the textures into atlas pages with remapped coordinates, so a scene needs one
+    /// material per page and alpha mode instead of one per texture. Untextured triangles sample
+    /// a white patch, triangles repeating their texture keep materials of their own. Ignored when
+    /// baking colours, which already makes one texture per material.
+    pub atlas: Option<AtlasOptions>,
+    /// Exports normals smoothed across every face sharing a vertex, with tangents and a baked
+    /// tangent-space normal map per material putting back the client's shading, the face normals
+    /// of flat shaded triangles, for engines that want smoother looking low poly models. The map
+    /// uses the baked colours' coordinates, or a second set when colours aren't baked.
+    pub normal_map: bool,
+}
+
+impl GltfOptions {
+    /// Converts a model position to the exported axes, still in model units.
+    fn axes(&self, x: i32, y: i32, z: i32) -> [i32; 3] {
+        // Model y points down and z away from the viewer, glTF y points up and z towards it.
+        let (x, y, z) = (x, -y, -z);
+        match self.up_axis {
+            UpAxis::Y => [x, y, z],
+            UpAxis::Z => [x, -z, y],
+        }
+    }
+
+    /// Output units per model unit.
+    fn unit_scale(&self) -> f32 {
+        self.scale / 512.0
+    }
+}
+
+/// How a primitive's triangles are blended, for the material they share.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum AlphaMode {
+    Opaque,
+    /// Cut out where the texture is transparent.
+    Mask,
+    Blend,
+}
+
+impl AlphaMode {
+    fn name(&self) -> &'static str {
+        match self {
+            AlphaMode::Opaque => "opaque",
+            AlphaMode::Mask => "mask",
+            AlphaMode::Blend => "blend",
+        }
+    }
+}
+
+/// What a primitive's triangles share.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum PrimitiveKey {
+    /// The model's material, -1 for untextured triangles.
+    Material(i16),
+    /// A page of the texture atlas.
+    Atlas { page: usize, alpha: AlphaMode },
+}
+
+/// Vertices of the triangles sharing one material, stored unindexed.
+#[derive(Default)]
+struct Primitive {
+    /// In the exported axes but model units, see [`GltfOptions::axes`].
+    positions: Vec<[i32; 3]>,
+    colours: Vec<[f32; 4]>,
+    texcoords: Vec<[f32; 2]>,
+    joints: Vec<u16>,
+    /// Smoothed normals and the normals the client shades with, only kept for normal maps.
+    normals: Vec<[f32; 3]>,
+    shading_normals: Vec<[f32; 3]>,
+    transparent: bool,
+}
+
+impl Primitive {
+    /// Appends triangle `t` of `other` with new texture coordinates.
+    fn push_triangle(&mut self, other: &Primitive, t: usize, texcoords: [[f32; 2]; 3]) {
+        let corners = t * 3..t * 3 + 3;
+        self.positions
+            .extend_from_slice(&other.positions[corners.clone()]);
+        self.colours
+            .extend_from_slice(&other.colours[corners.clone()]);
+        self.texcoords.extend_from_slice(&texcoords);
+        if !other.joints.is_empty() {
+            self.joints
+                .extend_from_slice(&other.joints[corners.clone()]);
+        }
+        if !other.normals.is_empty() {
+            self.normals
+                .extend_from_slice(&other.normals[corners.clone()]);
+            self.shading_normals
+                .extend_from_slice(&other.shading_normals[corners]);
+        }
+    }
+}
+
+/// Packs accessor data into the binary chunk and records the glTF objects describing it.
+struct GltfBuilder {
+    bin: Vec<u8>,
+    buffer_views: Vec<String>,
+    accessors: Vec<String>,
+}
+
+impl GltfBuilder {
+    fn push_view(&mut self, data: &[u8], target: Option<u32>, byte_stride: Option<u32>) -> usize {
+        while self.bin.len() % 4 != 0 {
+            self.bin.push(0);
+        }
+        let mut view = json::JsonObject::new()
+            .number("buffer", 0)
+            .number("byteOffset", self.bin.len() as u32)
+            .number("byteLength", data.len() as u32);
+        if let Some(byte_stride) = byte_stride {
+            view = view.number("byteStride", byte_stride);
+        }
+        if let Some(target) = target {
+            view = view.number("target", target);
+        }
+        self.bin.extend_from_slice(data);
+        self.buffer_views.push(view.finish());
+        self.buffer_views.len() - 1
+    }
+
+    fn push_accessor(
+        &mut self,
+        data: &[u8],
+        component_type: u32,
+        count: usize,
+        kind: &str,
+        bounds: Option<([f32; 3], [f32; 3])>,
+    ) -> usize {
+        let view = self.push_view(data, Some(ARRAY_BUFFER), None);
+        self.push_accessor_json(view, component_type, false, count, kind, bounds)
+    }
+
+    fn push_accessor_json(
+        &mut self,
+        view: usize,
+        component_type: u32,
+        normalized: bool,
+        count: usize,
+        kind: &str,
+        bounds: Option<([f32; 3], [f32; 3])>,
+    ) -> usize {
+        let mut accessor = json::JsonObject::new()
+            .number("bufferView", view as u32)
+            .number("componentType", component_type)
+            .number("count", count as u32)
+            .string("type", kind);
+        if normalized {
+            accessor = accessor.bool("normalized", true);
+        }
+        if let Some((min, max)) = bounds {
+            accessor = accessor.numbers("min", &min).numbers("max", &max);
+        }
+        self.accessors.push(accessor.finish());
+        self.accessors.len() - 1
+    }
+
+    /// Pushes positions as 16 bit integers, padded to 8 bytes since vertex attributes have to
+    /// be 4 byte aligned.
+    fn push_positions_i16(&mut self, positions: &[[i32; 3]]) -> usize {
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        let mut data = Vec::with_capacity(positions.len() * 8);
+        for position in positions {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(position[axis] as f32);
+                max[axis] = max[axis].max(position[axis] as f32);
+                data.extend_from_slice(&(position[axis] as i16).to_le_bytes());
+            }
+            data.extend_from_slice(&[0, 0]);
+        }
+        let view = self.push_view(&data, Some(ARRAY_BUFFER), Some(8));
+        self.push_accessor_json(
+            view,
+            SHORT,
+            false,
+            positions.len(),
+            "VEC3",
+            Some((min, max)),
+        )
+    }
+
+    /// Pushes values in 0 to 1 as normalized unsigned integers of 1 or 2 bytes.
+    fn push_normalized<const N: usize>(
+        &mut self,
+        values: &[[f32; N]],
+        kind: &str,
+        component_type: u32,
+    ) -> usize {
+        let data: Vec<u8> = match component_type {
+            UNSIGNED_BYTE => values
+                .iter()
+                .flatten()
+                .map(|v| (v.clamp(0.0, 1.0) * 255.0).round() as u8)
+                .collect(),
+            _ => values
+                .iter()
+                .flatten()
+                .flat_map(|v| ((v.clamp(0.0, 1.0) * 65535.0).round() as u16).to_le_bytes())
+                .collect(),
+        };
+        let view = self.push_view(&data, Some(ARRAY_BUFFER), None);
+        self.push_accessor_json(view, component_type, true, values.len(), kind, None)
+    }
+
+    fn push_f32s<const N: usize>(&mut self, values: &[[f32; N]], kind: &str) -> usize {
+        let data: Vec<u8> = values
+            .iter()
+            .flatten()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        self.push_accessor(&data, FLOAT, values.len(), kind, None)
+    }
+}
+
+pub(super) fn srgb_to_linear(channel: u32) -> f32 {
+    let c = (channel & 0xff) as f32 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn rgb_to_linear(rgb: u32, alpha: u8) -> [f32; 4] {
+    [
+        srgb_to_linear(rgb >> 16),
+        srgb_to_linear(rgb >> 8),
+        srgb_to_linear(rgb),
+        alpha as f32 / 255.0,
+    ]
+}
+
+/// Exports the lit model as a binary glTF, with one primitive per texture and the used textures
+/// embedded as PNGs. `vertex_skins` are the skin labels of the unlit model's vertices.
+pub fn export_glb(
+    texture_provider: &TextureProvider,
+    model: &ModelLit,
+    vertex_skins: Option<&[i32]>,
+    options: GltfOptions,
+) -> Vec<u8> {
+    let (json, bin) = build(texture_provider, model, vertex_skins, options, None);
+    let mut json_chunk = json.into_bytes();
+    while json_chunk.len() % 4 != 0 {
+        json_chunk.push(b' ');
+    }
+
+    let total_length = 12 + 8 + json_chunk.len() + 8 + bin.len();
+    let mut glb = Vec::with_capacity(total_length);
+    glb.extend_from_slice(&GLB_MAGIC.to_le_bytes());
+    glb.extend_from_slice(&2u32.to_le_bytes());
+    glb.extend_from_slice(&(total_length as u32).to_le_bytes());
+    glb.extend_from_slice(&(json_chunk.len() as u32).to_le_bytes());
+    glb.extend_from_slice(&CHUNK_JSON.to_le_bytes());
+    glb.extend_from_slice(&json_chunk);
+    glb.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+    glb.extend_from_slice(&CHUNK_BIN.to_le_bytes());
+    glb.extend_from_slice(&bin);
+    glb
+}
+
+/// Exports the model like [`export_glb`] but as a JSON glTF document and the binary buffer it
+/// refers to as `bin_uri`, for tools that edit the document by hand.
+pub fn export_gltf(
+    texture_provider: &TextureProvider,
+    model: &ModelLit,
+    vertex_skins: Option<&[i32]>,
+    options: GltfOptions,
+    bin_uri: &str,
+) -> (String, Vec<u8>) {
+    build(
+        texture_provider,
+        model,
+        vertex_skins,
+        options,
+        Some(bin_uri),
+    )
+}
+
+/// Builds the glTF document and its binary buffer, which is embedded unless `bin_uri` is set.
+fn build(
+    texture_provider: &TextureProvider,
+    model: &ModelLit,
+    vertex_skins: Option<&[i32]>,
+    options: GltfOptions,
+    bin_uri: Option<&str>,
+) -> (String, Vec<u8>) {
+    let (colours_a, colours_b, colours_c) = if options.baked_lighting {
+        let (light_x, light_y, light_z) = ModelLit::DEFAULT_LIGHT_DIRECTION;
+        model.calc_lit_colours(light_x, light_y, light_z)
+    } else {
+        flat_colours(model)
+    };
+
+    // Maps render vertices back to model vertices for positions and skins.
+    let mut render_vertex_ids = vec![0usize; model.render_vertex_count as usize];
+    for i in 0..model.used_vertex_count as usize {
+        let v_start = model.vertex_unique_index[i] as usize;
+        let v_end = model.vertex_unique_index[i + 1] as usize;
+        for v in v_start..v_end {
+            let pos = model.vertex_stream_pos[v] as usize;
+            if pos == 0 {
+                break;
+            }
+            render_vertex_ids[pos - 1] = i;
+        }
+    }
+
+    let vertex_skins = vertex_skins.filter(|_| options.rig_skins);
+    // Joint 0 is the root for unlabelled vertices, labels get their own joints after it.
+    let mut skin_joints: BTreeMap<i32, u16> = BTreeMap::new();
+    if let Some(skins) = vertex_skins {
+        for &skin in skins.iter().filter(|&&skin| skin >= 0) {
+            skin_joints.insert(skin, 0);
+        }
+        for (i, joint) in skin_joints.values_mut().enumerate() {
+            *joint = i as u16 + 1;
+        }
+    }
+
+    let smooth_normals = options
+        .normal_map
+        .then(|| smooth_normals(model, &render_vertex_ids, &colours_c, options));
+
+    let mut primitives: BTreeMap<PrimitiveKey, Primitive> = BTreeMap::new();
+    for t in 0..model.render_triangle_count as usize {
+        let colour_a = colours_a[t];
+        let mut colour_b = colours_b[t];
+        let mut colour_c = colours_c[t];
+        if colour_c == -2 {
+            continue;
+        }
+        if colour_c == -1 {
+            colour_b = colour_a;
+            colour_c = colour_a;
+        }
+        let material = model.triangle_material[t];
+        let alpha = 0xff - model.triangle_transparency[t];
+        let primitive = primitives
+            .entry(PrimitiveKey::Material(material))
+            .or_default();
+        primitive.transparent |= alpha != 0xff;
+
+        let corners = [
+            model.triangle_render_a[t] as usize,
+            model.triangle_render_b[t] as usize,
+            model.triangle_render_c[t] as usize,
+        ];
+        if let Some(smooth_normals) = &smooth_normals {
+            let normals =
+                corners.map(|render_vertex| smooth_normals[render_vertex_ids[render_vertex]]);
+            let client_normal = |render_vertex: usize| {
+                let [x, y, z] = options.axes(
+                    model.normal_x[render_vertex] as i32,
+                    model.normal_y[render_vertex] as i32,
+                    model.normal_z[render_vertex] as i32,
+                );
+                [x as f32, y as f32, z as f32]
+            };
+            // Unlit triangles keep the smoothed normals, leaving the map flat.
+            let shading_normals = match model.shading(t) {
+                TriangleShading::Smooth => corners.map(client_normal),
+                TriangleShading::Flat => [client_normal(corners[0]); 3],
+                _ => normals,
+            };
+            primitive.normals.extend_from_slice(&normals);
+            primitive.shading_normals.extend(
+                shading_normals
+                    .iter()
+                    .zip(&normals)
+                    .map(|(&shading, &normal)| if shading == [0.0; 3] { normal } else { shading }),
+            );
+        }
+        for (render_vertex, colour) in corners.into_iter().zip([colour_a, colour_b, colour_c]) {
+            let vertex = render_vertex_ids[render_vertex];
+            primitive.positions.push(options.axes(
+                model.vertex_x[vertex],
+                model.vertex_y[vertex],
+                model.vertex_z[vertex],
+            ));
+            // Textured triangles only carry a lightness, which shades the texture.
+            let hsl = if material == -1 {
+                colour
+            } else {
+                colour & 0x7f
+            };
+            let rgb = hsl_to_rgb(hsl as Hsl, options.brightness);
+            primitive.colours.push(rgb_to_linear(rgb, alpha));
+            primitive.texcoords.push([
+                model.texcoord_u[render_vertex],
+                model.texcoord_v[render_vertex],
+            ]);
+            if let Some(skins) = vertex_skins {
+                primitive
+                    .joints
+                    .push(skin_joints.get(&skins[vertex]).copied().unwrap_or(0));
+            }
+        }
+    }
+
+    let atlas = options
+        .atlas
+        .filter(|_| options.textures && !options.bake_colours)
+        .and_then(|atlas_options| {
+            pack_atlas(
+                texture_provider,
+                &mut primitives,
+                options.brightness,
+                atlas_options,
+            )
+        });
+
+    // Skinned meshes ignore their node's transform, which scales quantized positions.
+    let quantize_positions = options.quantize
+        && vertex_skins.is_none()
+        && primitives
+            .values()
+            .flat_map(|primitive| primitive.positions.iter().flatten())
+            .all(|&value| i16::try_from(value).is_ok());
+
+    let mut builder = GltfBuilder {
+        bin: Vec::new(),
+        buffer_views: Vec::new(),
+        accessors: Vec::new(),
+    };
+    let mut images = Vec::new();
+    let mut textures = Vec::new();
+    let mut materials = Vec::new();
+    let mut mesh_primitives = Vec::new();
+
+    let mut push_image =
+        |builder: &mut GltfBuilder, width: u32, height: u32, rgba: &[u8], name: &str| {
+            let view = builder.push_view(&png::encode_rgba(width, height, rgba), None, None);
+            images.push(
+                json::JsonObject::new()
+                    .number("bufferView", view as u32)
+                    .string("mimeType", "image/png")
+                    .string("name", name)
+                    .finish(),
+            );
+            textures.push(
+                json::JsonObject::new()
+                    .number("sampler", 0)
+                    .number("source", images.len() as u32 - 1)
+                    .finish(),
+            );
+            textures.len() - 1
+        };
+
+    let atlas_textures: Vec<usize> = atlas
+        .iter()
+        .flat_map(|atlas| &atlas.pages)
+        .enumerate()
+        .map(|(page, pixels)| {
+            push_image(
+                &mut builder,
+                pixels.width,
+                pixels.height,
+                &pixels.rgba,
+                &format!("atlas_{page}"),
+            )
+        })
+        .collect();
+
+    for (&key, primitive) in &primitives {
+        let mut texture = None;
+        let mut texture_opaque = true;
+        let mut texture_index = None;
+        let name = match key {
+            PrimitiveKey::Material(-1) => "vertex_colour".to_owned(),
+            PrimitiveKey::Material(material) => {
+                if options.textures {
+                    let texture_id = material as u16 as u32;
+                    texture = texture_rgba(texture_provider, texture_id, options.brightness)
+                        .map(|rgba| (texture_id, rgba));
+                    if texture.is_some() {
+                        texture_opaque = texture_opaque_at(texture_provider, texture_id);
+                    }
+                }
+                format!("material_{material}")
+            }
+            PrimitiveKey::Atlas { page, alpha } => {
+                texture_index = Some(atlas_textures[page]);
+                texture_opaque = alpha != AlphaMode::Mask;
+                format!("atlas_{page}_{}", alpha.name())
+            }
+        };
+
+        let mut texcoords = &primitive.texcoords;
+        let baked;
+        if options.bake_colours {
+            let texture = texture.as_ref().map(|(_, rgba)| BakeTexture {
+                size: TEXTURE_SIZE,
+                rgba,
+                texcoords: &primitive.texcoords,
+            });
+            baked = bake_triangles(&primitive.colours, texture.as_ref());
+            texture_index = Some(push_image(
+                &mut builder,
+                baked.width,
+                baked.height,
+                &baked.rgba,
+                &format!("baked_{name}"),
+            ));
+            texcoords = &baked.texcoords;
+        } else if let Some((texture_id, rgba)) = &texture {
+            let size = TEXTURE_SIZE as u32;
+            texture_index = Some(push_image(
+                &mut builder,
+                size,
+                size,
+                rgba,
+                &format!("texture_{texture_id}"),
+            ));
+        }
+
+        let normals = options.normal_map.then(|| {
+            let positions: Vec<[f32; 3]> = primitive
+                .positions
+                .iter()
+                .map(|&position| position.map(|value| value as f32))
+                .collect();
+            let baked = bake_normals(&positions, &primitive.normals, &primitive.shading_normals);
+            let texture_index = push_image(
+                &mut builder,
+                baked.atlas.width,
+                baked.atlas.height,
+                &baked.atlas.rgba,
+                &format!("normals_{name}"),
+            );
+            (baked, texture_index)
+        });
+
+        let mut pbr = json::JsonObject::new()
+            .number("metallicFactor", 0)
+            .number("roughnessFactor", 1);
+        if let Some(texture_index) = texture_index {
+            pbr = pbr.raw(
+                "baseColorTexture",
+                &json::JsonObject::new()
+                    .number("index", texture_index as u32)
+                    .finish(),
+            );
+        }
+        let mut material_json = json::JsonObject::new()
+            .string("name", &name)
+            .raw("pbrMetallicRoughness", &pbr.finish());
+        // Baked colours share the normal map's coordinates, other textures have their own.
+        let normal_texcoord = u32::from(texture_index.is_some() && !options.bake_colours);
+        if let Some((_, normal_texture)) = &normals {
+            material_json = material_json.raw(
+                "normalTexture",
+                &json::JsonObject::new()
+                    .number("index", *normal_texture as u32)
+                    .number("texCoord", normal_texcoord)
+                    .finish(),
+            );
+        }
+        if primitive.transparent {
+            material_json = material_json.string("alphaMode", "BLEND");
+        } else if !texture_opaque {
+            material_json = material_json
+                .string("alphaMode", "MASK")
+                .number("alphaCutoff", 0.5);
+        }
+        materials.push(material_json.finish());
+
+        let count = primitive.positions.len();
+        let position = if quantize_positions {
+            builder.push_positions_i16(&primitive.positions)
+        } else {
+            let scale = options.unit_scale();
+            let mut min = [f32::MAX; 3];
+            let mut max = [f32::MIN; 3];
+            let mut position_data = Vec::with_capacity(count * 12);
+            for position in &primitive.positions {
+                for axis in 0..3 {
+                    let value = position[axis] as f32 * scale;
+                    min[axis] = min[axis].min(value);
+                    max[axis] = max[axis].max(value);
+                    position_data.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            builder.push_accessor(&position_data, FLOAT, count, "VEC3", Some((min, max)))
+        };
+        let mut attributes = json::JsonObject::new().number("POSITION", position as u32);
+        // Baked colours are already in the texture.
+        if !options.bake_colours {
+            let colour = if options.quantize {
+                // Colours are linear, 8 bits would band the dark shades.
+                builder.push_normalized(&primitive.colours, "VEC4", UNSIGNED_SHORT)
+            } else {
+                builder.push_f32s(&primitive.colours, "VEC4")
+            };
+            attributes = attributes.number("COLOR_0", colour as u32);
+        }
+        if texture_index.is_some() {
+            // Normalized texture coordinates can't repeat past 0 to 1.
+            let in_range = texcoords.iter().flatten().all(|v| (0.0..=1.0).contains(v));
+            let texcoord = if options.quantize && in_range {
+                builder.push_normalized(texcoords, "VEC2", UNSIGNED_SHORT)
+            } else {
+                builder.push_f32s(texcoords, "VEC2")
+            };
+            attributes = attributes.number("TEXCOORD_0", texcoord as u32);
+        }
+        if let Some((baked, _)) = &normals {
+            let normal = builder.push_f32s(&primitive.normals, "VEC3");
+            let tangent = builder.push_f32s(&baked.tangents, "VEC4");
+            attributes = attributes
+                .number("NORMAL", normal as u32)
+                .number("TANGENT", tangent as u32);
+            if normal_texcoord == 1 {
+                let texcoord = builder.push_f32s(&baked.atlas.texcoords, "VEC2");
+                attributes = attributes.number("TEXCOORD_1", texcoord as u32);
+            } else if texture_index.is_none() {
+                let texcoord = builder.push_f32s(&baked.atlas.texcoords, "VEC2");
+                attributes = attributes.number("TEXCOORD_0", texcoord as u32);
+            }
+        }
+        if vertex_skins.is_some() {
+            let joint_data: Vec<u8> = primitive
+                .joints
+                .iter()
+                .flat_map(|&joint| [joint, 0, 0, 0])
+                .flat_map(|joint| joint.to_le_bytes())
+                .collect();
+            let joints = builder.push_accessor(&joint_data, UNSIGNED_SHORT, count, "VEC4", None);
+            let weights = vec![[1.0f32, 0.0, 0.0, 0.0]; count];
+            let weights = if options.quantize {
+                builder.push_normalized(&weights, "VEC4", UNSIGNED_BYTE)
+            } else {
+                builder.push_f32s(&weights, "VEC4")
+            };
+            attributes = attributes
+                .number("JOINTS_0", joints as u32)
+                .number("WEIGHTS_0", weights as u32);
+        }
+        mesh_primitives.push(
+            json::JsonObject::new()
+                .raw("attributes", &attributes.finish())
+                .number("material", materials.len() as u32 - 1)
+                .finish(),
+        );
+    }
+
+    let mut mesh_node = json::JsonObject::new()
+        .string("name", "model")
+        .number("mesh", 0);
+    if quantize_positions {
+        mesh_node = mesh_node.numbers("scale", &[options.unit_scale(); 3]);
+    }
+    let mut nodes = Vec::new();
+    let mut scene_nodes = vec![0u32];
+    let mut skins = Vec::new();
+    if vertex_skins.is_some() {
+        mesh_node = mesh_node.number("skin", 0);
+        let joint_count = skin_joints.len() as u32 + 1;
+        // The mesh node comes first, then the root joint with one child per skin label.
+        let root = 1;
+        let children: Vec<u32> = (root + 1..root + joint_count).collect();
+        let joints: Vec<u32> = (root..root + joint_count).collect();
+        nodes.push(mesh_node.finish());
+        nodes.push(
+            json::JsonObject::new()
+                .string("name", "skin_root")
+                .numbers("children", &children)
+                .finish(),
+        );
+        for label in skin_joints.keys() {
+            nodes.push(
+                json::JsonObject::new()
+                    .string("name", &format!("skin_{label}"))
+                    .finish(),
+            );
+        }
+        skins.push(
+            json::JsonObject::new()
+                .number("skeleton", root)
+                .numbers("joints", &joints)
+                .finish(),
+        );
+        scene_nodes.push(root);
+    } else {
+        nodes.push(mesh_node.finish());
+    }
+
+    while builder.bin.len() % 4 != 0 {
+        builder.bin.push(0);
+    }
+
+    let mut document = json::JsonObject::new()
+        .raw(
+            "asset",
+            &json::JsonObject::new()
+                .string("version", "2.0")
+                .string("generator", "rs_model_viewer")
+                .finish(),
+        )
+        .number("scene", 0)
+        .raw(
+            "scenes",
+            &json::array(&[json::JsonObject::new()
+                .numbers("nodes", &scene_nodes)
+                .finish()]),
+        )
+        .raw("nodes", &json::array(&nodes))
+        .raw(
+            "meshes",
+            &json::array(&[json::JsonObject::new()
+                .raw("primitives", &json::array(&mesh_primitives))
+                .finish()]),
+        )
+        .raw("materials", &json::array(&materials))
+        .raw("accessors", &json::array(&builder.accessors))
+        .raw("bufferViews", &json::array(&builder.buffer_views))
+        .raw("buffers", &{
+            let mut buffer = json::JsonObject::new().number("byteLength", builder.bin.len() as u32);
+            if let Some(bin_uri) = bin_uri {
+                buffer = buffer.string("uri", bin_uri);
+            }
+            json::array(&[buffer.finish()])
+        });
+    if !skins.is_empty() {
+        document = document.raw("skins", &json::array(&skins));
+    }
+    if quantize_positions {
+        let extensions = json::array(&[r#""KHR_mesh_quantization""#]);
+        document = document
+            .raw("extensionsUsed", &extensions)
+            .raw("extensionsRequired", &extensions);
+    }
+    if !textures.is_empty() {
+        let filter = match options.texture_filter {
+            TextureFilter::Nearest => NEAREST,
+            TextureFilter::Linear => LINEAR,
+        };
+        let sampler = json::JsonObject::new()
+            .number("magFilter", filter)
+            .number("minFilter", filter)
+            .number("wrapS", CLAMP_TO_EDGE)
+            .number("wrapT", REPEAT)
+            .finish();
+        document = document
+            .raw("samplers", &json::array(&[sampler]))
+            .raw("images", &json::array(&images))
+            .raw("textures", &json::array(&textures));
+    }
+
+    (document.finish(), builder.bin)
+}
+
+/// Whether a texture has no transparent pixels, textures that aren't loaded count as opaque.
+fn texture_opaque_at(texture_provider: &TextureProvider, texture_id: u32) -> bool {
+    texture_provider
+        .textures
+        .get(texture_id as usize)
+        .and_then(|texture| texture.as_ref())
+        .map_or(true, |texture| texture.opaque)
+}
+
+/// The triangle's texture coordinates moved by whole repeats of V so they lie within one copy of
+/// the texture, None if they span more than one and need the texture to repeat. U is clamped by
+/// the client, so coordinates past its edges can't be mapped either.
+fn fit_texcoords(texcoords: &[[f32; 2]]) -> Option<[[f32; 2]; 3]> {
+    let min_v = texcoords.iter().map(|[_, v]| *v).fold(f32::MAX, f32::min);
+    let shift = min_v.floor();
+    let mut fitted = [[0.0; 2]; 3];
+    for (fitted, &[u, v]) in fitted.iter_mut().zip(texcoords) {
+        let v = v - shift;
+        if !(0.0..=1.0).contains(&u) || !(0.0..=1.0).contains(&v) {
+            return None;
+        }
+        *fitted = [u, v];
+    }
+    Some(fitted)
+}
+
+/// Moves the triangles that can sample an atlas of the textures to primitives per page and
+/// alpha mode, returns the atlas or None if the model has no textures to pack.
+fn pack_atlas(
+    texture_provider: &TextureProvider,
+    primitives: &mut BTreeMap<PrimitiveKey, Primitive>,
+    brightness: f64,
+    options: AtlasOptions,
+) -> Option<Atlas> {
+    // White comes first, for the untextured triangles.
+    let mut materials = vec![(-1i16, vec![0xffu8; WHITE_SIZE * WHITE_SIZE * 4], true)];
+    for &key in primitives.keys() {
+        if let PrimitiveKey::Material(material) = key {
+            if material == -1 {
+                continue;
+            }
+            let texture_id = material as u16 as u32;
+            if let Some(rgba) = texture_rgba(texture_provider, texture_id, brightness) {
+                let opaque = texture_opaque_at(texture_provider, texture_id);
+                materials.push((material, rgba, opaque));
+            }
+        }
+    }
+    if materials.len() == 1 {
+        return None;
+    }
+
+    let images: Vec<AtlasImage> = materials
+        .iter()
+        .map(|(material, rgba, _)| {
+            let size = if *material == -1 {
+                WHITE_SIZE
+            } else {
+                TEXTURE_SIZE
+            } as u32;
+            AtlasImage {
+                width: size,
+                height: size,
+                rgba,
+            }
+        })
+        .collect();
+    let atlas = atlas::pack(&images, options);
+
+    for (&(material, _, opaque), placement) in materials.iter().zip(&atlas.placements) {
+        let Some(placement) = placement else {
+            continue;
+        };
+        let Some(primitive) = primitives.remove(&PrimitiveKey::Material(material)) else {
+            continue;
+        };
+        let mut rest = Primitive {
+            transparent: primitive.transparent,
+            ..Primitive::default()
+        };
+        for t in 0..primitive.positions.len() / 3 {
+            let texcoords = &primitive.texcoords[t * 3..t * 3 + 3];
+            let fitted = if material == -1 {
+                Some([[0.5; 2]; 3])
+            } else {
+                fit_texcoords(texcoords)
+            };
+            let Some(fitted) = fitted else {
+                rest.push_triangle(&primitive, t, [texcoords[0], texcoords[1], texcoords[2]]);
+                continue;
+            };
+            let alpha = if primitive.colours[t * 3][3] < 1.0 {
+                AlphaMode::Blend
+            } else if !opaque {
+                AlphaMode::Mask
+            } else {
+                AlphaMode::Opaque
+            };
+            let target = primitives
+                .entry(PrimitiveKey::Atlas {
+                    page: placement.page,
+                    alpha,
+                })
+                .or_default();
+            target.transparent |= alpha == AlphaMode::Blend;
+            target.push_triangle(
+                &primitive,
+                t,
+                fitted.map(|texcoord| atlas.texcoord(placement, texcoord)),
+            );
+        }
+        if !rest.positions.is_empty() {
+            primitives.insert(PrimitiveKey::Material(material), rest);
+        }
+    }
+    Some(atlas)
+}
+
+/// A texture's pixels as RGBA, None if it can't be generated.
+fn texture_rgba(
+    texture_provider: &TextureProvider,
+    texture_id: u32,
+    brightness: f64,
+) -> Option<Vec<u8>> {
+    let size = TEXTURE_SIZE as u16;
+    let pixels = texture_provider.get_pixels_argb(texture_id, size, size, false, brightness)?;
+    Some(
+        pixels
+            .iter()
+            .flat_map(|&argb| {
+                [
+                    (argb >> 16) as u8,
+                    (argb >> 8) as u8,
+                    argb as u8,
+                    (argb >> 24) as u8,
+                ]
+            })
+            .collect(),
+    )
+}
+
+/// The triangles' own colours, flat across each triangle. Triangles the client hides stay
+/// hidden.
+/// Normals of the model's vertices averaged over the faces drawn around them, weighted by area,
+/// in the exported axes.
+fn smooth_normals(
+    model: &ModelLit,
+    render_vertex_ids: &[usize],
+    colours_c: &[i32],
+    options: GltfOptions,
+) -> Vec<[f32; 3]> {
+    let mut sums = vec![[0.0f64; 3]; model.used_vertex_count as usize];
+    for t in 0..model.render_triangle_count as usize {
+        if colours_c[t] == -2 {
+            continue;
+        }
+        let vertices = [
+            model.triangle_render_a[t],
+            model.triangle_render_b[t],
+            model.triangle_render_c[t],
+        ]
+        .map(|render_vertex| render_vertex_ids[render_vertex as usize]);
+        let [a, b, c] = vertices.map(|vertex| {
+            options
+                .axes(
+                    model.vertex_x[vertex],
+                    model.vertex_y[vertex],
+                    model.vertex_z[vertex],
+                )
+                .map(|value| value as f64)
+        });
+        let (u, v) = (
+            [b[0] - a[0], b[1] - a[1], b[2] - a[2]],
+            [c[0] - a[0], c[1] - a[1], c[2] - a[2]],
+        );
+        let normal = [
+            u[1] * v[2] - u[2] * v[1],
+            u[2] * v[0] - u[0] * v[2],
+            u[0] * v[1] - u[1] * v[0],
+        ];
+        for vertex in vertices {
+            for (sum, component) in sums[vertex].iter_mut().zip(normal) {
+                *sum += component;
+            }
+        }
+    }
+    sums.into_iter()
+        .map(|[x, y, z]| {
+            let length = (x * x + y * y + z * z).sqrt();
+            if length > 0.0 {
+                [
+                    (x / length) as f32,
+                    (y / length) as f32,
+                    (z / length) as f32,
+                ]
+            } else {
+                [0.0, 1.0, 0.0]
+            }
+        })
+        .collect()
+}
+
+fn flat_colours(model: &ModelLit) -> (Vec<i32>, Vec<i32>, Vec<i32>) {
+    let (light_x, light_y, light_z) = ModelLit::DEFAULT_LIGHT_DIRECTION;
+    let (_, _, lit_c) = model.calc_lit_colours(light_x, light_y, light_z);
+    let colours: Vec<i32> = (0..lit_c.len())
+        .map(|t| {
+            if model.triangle_material[t] != -1 {
+                // Textured triangles are shaded at full lightness.
+                0x7f
+            } else {
+                model.triangle_colour[t] as i32
+            }
+        })
+        .collect();
+    // A third colour of -1 marks a flat triangle, -2 a hidden one.
+    let flags = lit_c
+        .iter()
+        .map(|&colour| if colour == -2 { -2 } else { -1 })
+        .collect();
+    (colours.clone(), colours, flags)
+}
@@ -0,0 +1,29 @@
+pub mod atlas;
+pub mod bake;
+pub mod dataset;
+pub mod gltf;
+pub mod json;
+pub mod png;
+pub mod preset;
+pub mod schema;
+pub mod zip;
+
+/// A file produced by an export, `path` is relative to the export root.
+#[derive(Clone)]
+pub struct ExportFile {
+    pub path: String,
+    pub data: Vec<u8>,
+}
+
+/// Writes the files below `root`, creating directories as needed.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write_files(root: &std::path::Path, files: &[ExportFile]) -> std::io::Result<()> {
+    for file in files {
+        let path = root.join(&file.path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, &file.data)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,245 @@
+use std::io::Write;
+
+use libflate::zlib;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32fast::hash(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Compresses unfiltered scanlines of `stride` bytes and writes the PNG around them.
+fn encode(
+    width: u32,
+    height: u32,
+    colour_type: u8,
+    stride: usize,
+    data: &[u8],
+    chunks: &[(&[u8; 4], &[u8])],
+) -> Vec<u8> {
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // Bit depth 8, default compression, filter and no interlacing.
+    header.extend_from_slice(&[8, colour_type, 0, 0, 0]);
+
+    let mut scanlines = Vec::with_capacity((stride + 1) * height as usize);
+    for row in data.chunks_exact(stride.max(1)).take(height as usize) {
+        scanlines.push(0);
+        scanlines.extend_from_slice(row);
+    }
+    let mut encoder = zlib::Encoder::new(Vec::new()).unwrap();
+    encoder.write_all(&scanlines).unwrap();
+    let image_data = encoder.finish().into_result().unwrap();
+
+    let mut out = Vec::with_capacity(SIGNATURE.len() + image_data.len() + 64);
+    out.extend_from_slice(&SIGNATURE);
+    write_chunk(&mut out, b"IHDR", &header);
+    for (kind, data) in chunks {
+        write_chunk(&mut out, kind, data);
+    }
+    write_chunk(&mut out, b"IDAT", &image_data);
+    write_chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+/// Encodes 8-bit RGBA pixels as a PNG, scanlines are stored unfiltered.
+pub fn encode_rgba(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+    assert_eq!(rgba.len(), width as usize * height as usize * 4);
+    // Colour type 6 (RGBA).
+    encode(width, height, 6, width as usize * 4, rgba, &[])
+}
+
+/// Encodes palette indices as an indexed PNG keeping the 0xRRGGBB palette as is. Index 0 is
+/// written fully transparent, matching how sprites treat it.
+pub fn encode_indexed(width: u32, height: u32, palette: &[u32], indices: &[u8]) -> Vec<u8> {
+    assert_eq!(indices.len(), width as usize * height as usize);
+    assert!(!palette.is_empty() && palette.len() <= 256);
+
+    let plte: Vec<u8> = palette
+        .iter()
+        .flat_map(|&rgb| [(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8])
+        .collect();
+    // Entries after the tRNS chunk default to opaque.
+    let trns = [0u8];
+    // Colour type 3 (indexed).
+    encode(
+        width,
+        height,
+        3,
+        width as usize,
+        indices,
+        &[(b"PLTE", &plte), (b"tRNS", &trns)],
+    )
+}
+
+/// Encodes 0xRRGGBB pixels as a PNG, pixels equal to `transparent_rgb` are written fully
+/// transparent.
+pub fn encode_rgb(
+    width: u32,
+    height: u32,
+    pixels: &[u32],
+    transparent_rgb: Option<u32>,
+) -> Vec<u8> {
+    let rgba: Vec<u8> = pixels
+        .iter()
+        .flat_map(|&rgb| {
+            let alpha = if Some(rgb) == transparent_rgb {
+                0
+            } else {
+                0xff
+            };
+            [(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8, alpha]
+        })
+        .collect();
+    encode_rgba(width, height, &rgba)
+}
+
+/// A decoded PNG as 8-bit RGBA pixels.
+pub struct DecodedImage {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+/// Decodes a non-interlaced PNG with 8-bit channels or a 1, 2, 4 or 8-bit palette.
+pub fn decode_rgba(data: &[u8]) -> Result<DecodedImage, String> {
+    use std::io::Read;
+
+    if data.len() < SIGNATURE.len() || data[..SIGNATURE.len()] != SIGNATURE {
+        return Err("not a PNG file".to_owned());
+    }
+    let mut header = None;
+    let mut palette: &[u8] = &[];
+    let mut transparency: &[u8] = &[];
+    let mut image_data = Vec::new();
+    let mut offset = SIGNATURE.len();
+    while offset + 12 <= data.len() {
+        let length = u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap()) as usize;
+        let kind = &data[offset + 4..offset + 8];
+        let Some(chunk) = data.get(offset + 8..offset + 8 + length) else {
+            return Err("truncated chunk".to_owned());
+        };
+        match kind {
+            b"IHDR" if length >= 13 => header = Some(chunk),
+            b"PLTE" => palette = chunk,
+            b"tRNS" => transparency = chunk,
+            b"IDAT" => image_data.extend_from_slice(chunk),
+            b"IEND" => break,
+            _ => {}
+        }
+        offset += length + 12;
+    }
+    let header = header.ok_or("missing IHDR chunk")?;
+    let width = u32::from_be_bytes(header[0..4].try_into().unwrap());
+    let height = u32::from_be_bytes(header[4..8].try_into().unwrap());
+    let (bit_depth, colour_type, interlace) = (header[8], header[9], header[12]);
+    if interlace != 0 {
+        return Err("interlaced PNGs are not supported".to_owned());
+    }
+    let channels = match (colour_type, bit_depth) {
+        (0, 8) => 1,
+        (2, 8) => 3,
+        (3, 1 | 2 | 4 | 8) => 1,
+        (4, 8) => 2,
+        (6, 8) => 4,
+        _ => {
+            return Err(format!(
+                "unsupported colour type {colour_type} with bit depth {bit_depth}"
+            ))
+        }
+    };
+
+    let mut decoder = zlib::Decoder::new(image_data.as_slice()).map_err(|err| err.to_string())?;
+    let mut filtered = Vec::new();
+    decoder
+        .read_to_end(&mut filtered)
+        .map_err(|err| err.to_string())?;
+
+    let (width_px, height_px) = (width as usize, height as usize);
+    let stride = (width_px * channels * bit_depth as usize).div_ceil(8);
+    if filtered.len() < (stride + 1) * height_px {
+        return Err("image data is too short".to_owned());
+    }
+    let pixel_bytes = channels.max(1);
+    let mut scanlines = vec![0u8; stride * height_px];
+    for y in 0..height_px {
+        let filter = filtered[y * (stride + 1)];
+        let src = &filtered[y * (stride + 1) + 1..(y + 1) * (stride + 1)];
+        let (previous, current) = scanlines.split_at_mut(y * stride);
+        let above = if y == 0 {
+            None
+        } else {
+            Some(&previous[(y - 1) * stride..])
+        };
+        let row = &mut current[..stride];
+        for x in 0..stride {
+            let left = if x >= pixel_bytes {
+                row[x - pixel_bytes]
+            } else {
+                0
+            };
+            let up = above.map_or(0, |above| above[x]);
+            let up_left = match above {
+                Some(above) if x >= pixel_bytes => above[x - pixel_bytes],
+                _ => 0,
+            };
+            row[x] = src[x].wrapping_add(match filter {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => paeth(left, up, up_left),
+                _ => return Err(format!("invalid filter type {filter}")),
+            });
+        }
+    }
+
+    let mut rgba = Vec::with_capacity(width_px * height_px * 4);
+    for row in scanlines.chunks_exact(stride.max(1)).take(height_px) {
+        for x in 0..width_px {
+            let pixel = match colour_type {
+                0 => [row[x], row[x], row[x], 0xff],
+                2 => [row[x * 3], row[x * 3 + 1], row[x * 3 + 2], 0xff],
+                3 => {
+                    let bits = bit_depth as usize;
+                    let bit = x * bits;
+                    let index =
+                        ((row[bit / 8] >> (8 - bits - bit % 8)) & ((1 << bits) - 1) as u8) as usize;
+                    let Some(rgb) = palette.get(index * 3..index * 3 + 3) else {
+                        return Err(format!("palette index {index} out of range"));
+                    };
+                    let alpha = transparency.get(index).copied().unwrap_or(0xff);
+                    [rgb[0], rgb[1], rgb[2], alpha]
+                }
+                4 => [row[x * 2], row[x * 2], row[x * 2], row[x * 2 + 1]],
+                _ => [row[x * 4], row[x * 4 + 1], row[x * 4 + 2], row[x * 4 + 3]],
+            };
+            rgba.extend_from_slice(&pixel);
+        }
+    }
+    Ok(DecodedImage {
+        width,
+        height,
+        rgba,
+    })
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = left as i16 + up as i16 - up_left as i16;
+    let distance_left = (estimate - left as i16).abs();
+    let distance_up = (estimate - up as i16).abs();
+    let distance_up_left = (estimate - up_left as i16).abs();
+    if distance_left <= distance_up && distance_left <= distance_up_left {
+        left
+    } else if distance_up <= distance_up_left {
+        up
+    } else {
+        up_left
+    }
+}
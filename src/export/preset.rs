@@ -0,0 +1,321 @@
+//! Named sets of model export options, so repeated exports don't need reconfiguring. Presets are
+//! written as `key=value` pairs, e.g. `format=gltf,scale=512,up=z,textures=off,lighting=off`, for
+//! sharing them and passing them on the command line.
+
+use std::{fmt, str::FromStr};
+
+use super::{
+    atlas::AtlasOptions,
+    gltf::{export_glb, export_gltf, GltfOptions, UpAxis},
+    ExportFile,
+};
+use crate::runetek5::graphics::{
+    model::ModelLit,
+    texture::{TextureFilter, TextureProvider},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// A single binary glTF file.
+    Glb,
+    /// A JSON glTF document with its binary buffer next to it.
+    Gltf,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 2] = [ExportFormat::Glb, ExportFormat::Gltf];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ExportFormat::Glb => "glTF binary (.glb)",
+            ExportFormat::Gltf => "glTF JSON (.gltf + .bin)",
+        }
+    }
+
+    fn key(&self) -> &'static str {
+        match self {
+            ExportFormat::Glb => "glb",
+            ExportFormat::Gltf => "gltf",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportPreset {
+    pub name: String,
+    pub format: ExportFormat,
+    /// Output units per tile, see [`GltfOptions::scale`].
+    pub scale: f32,
+    pub up_axis: UpAxis,
+    pub textures: bool,
+    pub baked_lighting: bool,
+    /// See [`GltfOptions::bake_colours`].
+    pub bake_colours: bool,
+    /// See [`GltfOptions::quantize`].
+    pub quantize: bool,
+    /// See [`GltfOptions::atlas`].
+    pub atlas: Option<AtlasOptions>,
+    /// See [`GltfOptions::normal_map`].
+    pub normal_map: bool,
+}
+
+impl Default for ExportPreset {
+    fn default() -> Self {
+        Self {
+            name: "Default".to_owned(),
+            format: ExportFormat::Glb,
+            scale: 1.0,
+            up_axis: UpAxis::Y,
+            textures: true,
+            baked_lighting: true,
+            bake_colours: false,
+            quantize: false,
+            atlas: None,
+            normal_map: false,
+        }
+    }
+}
+
+impl ExportPreset {
+    /// The presets offered out of the box.
+    pub fn builtins() -> Vec<Self> {
+        vec![
+            Self::default(),
+            Self {
+                name: "Unlit".to_owned(),
+                baked_lighting: false,
+                ..Self::default()
+            },
+            Self {
+                name: "Client units".to_owned(),
+                format: ExportFormat::Gltf,
+                scale: 512.0,
+                up_axis: UpAxis::Z,
+                ..Self::default()
+            },
+            Self {
+                name: "Vertex colours".to_owned(),
+                textures: false,
+                ..Self::default()
+            },
+            Self {
+                name: "Baked textures".to_owned(),
+                bake_colours: true,
+                ..Self::default()
+            },
+            Self {
+                name: "Compact".to_owned(),
+                quantize: true,
+                ..Self::default()
+            },
+            Self {
+                name: "Texture atlas".to_owned(),
+                atlas: Some(AtlasOptions::default()),
+                ..Self::default()
+            },
+            Self {
+                name: "Normal map".to_owned(),
+                normal_map: true,
+                ..Self::default()
+            },
+        ]
+    }
+
+    /// Finds a built-in preset by name, ignoring case.
+    pub fn builtin(name: &str) -> Option<Self> {
+        Self::builtins()
+            .into_iter()
+            .find(|preset| preset.name.eq_ignore_ascii_case(name))
+    }
+
+    /// The glTF options of the preset, with the settings presets leave to the viewer.
+    pub fn gltf_options(
+        &self,
+        brightness: f64,
+        texture_filter: TextureFilter,
+        rig_skins: bool,
+    ) -> GltfOptions {
+        GltfOptions {
+            brightness,
+            rig_skins,
+            texture_filter,
+            scale: self.scale,
+            up_axis: self.up_axis,
+            textures: self.textures,
+            baked_lighting: self.baked_lighting,
+            bake_colours: self.bake_colours,
+            quantize: self.quantize,
+            atlas: self.atlas,
+            normal_map: self.normal_map,
+        }
+    }
+
+    /// Exports a model in the preset's format, naming the files after `name`.
+    pub fn export(
+        &self,
+        texture_provider: &TextureProvider,
+        model: &ModelLit,
+        vertex_skins: Option<&[i32]>,
+        options: GltfOptions,
+        name: &str,
+    ) -> Vec<ExportFile> {
+        match self.format {
+            ExportFormat::Glb => vec![ExportFile {
+                path: format!("{name}.glb"),
+                data: export_glb(texture_provider, model, vertex_skins, options),
+            }],
+            ExportFormat::Gltf => {
+                let bin_path = format!("{name}.bin");
+                let (json, bin) =
+                    export_gltf(texture_provider, model, vertex_skins, options, &bin_path);
+                vec![
+                    ExportFile {
+                        path: format!("{name}.gltf"),
+                        data: json.into_bytes(),
+                    },
+                    ExportFile {
+                        path: bin_path,
+                        data: bin,
+                    },
+                ]
+            }
+        }
+    }
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+impl fmt::Display for ExportPreset {
+    /// Writes the options as `key=value` pairs, the name is left out.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let up = match self.up_axis {
+            UpAxis::Y => "y",
+            UpAxis::Z => "z",
+        };
+        write!(
+            f,
+            "format={},scale={},up={up},textures={},lighting={},bake={},quantize={},normals={}",
+            self.format.key(),
+            self.scale,
+            on_off(self.textures),
+            on_off(self.baked_lighting),
+            on_off(self.bake_colours),
+            on_off(self.quantize),
+            on_off(self.normal_map)
+        )?;
+        match self.atlas {
+            Some(atlas) => write!(f, ",atlas={},padding={}", atlas.size, atlas.padding),
+            None => write!(f, ",atlas=off"),
+        }
+    }
+}
+
+impl FromStr for ExportPreset {
+    type Err = String;
+
+    /// Parses the name of a built-in preset, or `key=value` pairs changing the default preset.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.trim();
+        if let Some(preset) = Self::builtin(text) {
+            return Ok(preset);
+        }
+        if !text.contains('=') {
+            let names: Vec<String> = Self::builtins()
+                .into_iter()
+                .map(|preset| preset.name)
+                .collect();
+            return Err(format!(
+                "no preset named {text:?}, expected one of {} or key=value pairs",
+                names.join(", ")
+            ));
+        }
+        let mut preset = Self {
+            name: "Custom".to_owned(),
+            ..Self::default()
+        };
+        // Padding may come before the atlas size it applies to.
+        let mut padding = None;
+        let parse_bool = |key: &str, value: &str| match value {
+            "on" | "true" | "yes" | "1" => Ok(true),
+            "off" | "false" | "no" | "0" => Ok(false),
+            _ => Err(format!("{key} should be on or off, not {value:?}")),
+        };
+        for pair in text
+            .split(',')
+            .map(str::trim)
+            .filter(|pair| !pair.is_empty())
+        {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("expected key=value, got {pair:?}"))?;
+            let (key, value) = (key.trim(), value.trim());
+            match key {
+                "format" => {
+                    preset.format = ExportFormat::ALL
+                        .into_iter()
+                        .find(|format| format.key() == value)
+                        .ok_or_else(|| format!("unknown format {value:?}"))?;
+                }
+                "scale" => {
+                    preset.scale = value
+                        .parse()
+                        .ok()
+                        .filter(|scale: &f32| *scale > 0.0 && scale.is_finite())
+                        .ok_or_else(|| format!("invalid scale {value:?}"))?;
+                }
+                "up" => {
+                    preset.up_axis = match value {
+                        "y" | "Y" => UpAxis::Y,
+                        "z" | "Z" => UpAxis::Z,
+                        _ => return Err(format!("up should be y or z, not {value:?}")),
+                    };
+                }
+                "textures" => preset.textures = parse_bool(key, value)?,
+                "lighting" => preset.baked_lighting = parse_bool(key, value)?,
+                "bake" => preset.bake_colours = parse_bool(key, value)?,
+                "quantize" => preset.quantize = parse_bool(key, value)?,
+                "normals" => preset.normal_map = parse_bool(key, value)?,
+                "atlas" => {
+                    preset.atlas = match value {
+                        "off" | "false" | "no" | "0" => None,
+                        _ => {
+                            let size = value
+                                .parse()
+                                .ok()
+                                .filter(|size: &u32| (64..=16384).contains(size))
+                                .ok_or_else(|| format!("invalid atlas size {value:?}"))?;
+                            Some(AtlasOptions {
+                                size,
+                                ..AtlasOptions::default()
+                            })
+                        }
+                    };
+                }
+                "padding" => {
+                    padding = Some(
+                        value
+                            .parse::<u32>()
+                            .ok()
+                            .filter(|padding| *padding <= 64)
+                            .ok_or_else(|| format!("invalid padding {value:?}"))?,
+                    );
+                }
+                _ => return Err(format!("unknown option {key:?}")),
+            }
+        }
+        if let Some(padding) = padding {
+            preset
+                .atlas
+                .as_mut()
+                .ok_or("padding needs an atlas size, e.g. atlas=2048")?
+                .padding = padding;
+        }
+        Ok(preset)
+    }
+}
@@ -0,0 +1,433 @@
+//! JSON Schemas of the JSON files the exporters write, so tools reading them can validate against
+//! a versioned contract instead of the code. Every file carries the [`SCHEMA_VERSION`] it was
+//! written with, which changes whenever a field is removed, renamed or changes meaning; new
+//! fields may be added without a new version.
+//!
+//! The writers of the definition files live here next to their schemas so the two change
+//! together.
+
+use std::fmt;
+
+use super::json::{self, JsonObject};
+use crate::runetek5::{npc::NpcType, obj::ObjType};
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy)]
+enum FieldType {
+    Integer,
+    Bool,
+    String,
+    /// An integer or null when absent.
+    OptionalInteger,
+    Integers,
+    OptionalIntegers,
+    /// Pairs of integers, like (old, new) colours.
+    IntegerPairs,
+    Objects(&'static [Field]),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Field {
+    name: &'static str,
+    ty: FieldType,
+    description: &'static str,
+}
+
+const fn field(name: &'static str, ty: FieldType, description: &'static str) -> Field {
+    Field {
+        name,
+        ty,
+        description,
+    }
+}
+
+const VERSION_FIELD: Field = field(
+    "schemaVersion",
+    FieldType::Integer,
+    "The version of this schema the file was written with.",
+);
+
+const DATASET_MODEL_FIELDS: &[Field] = &[
+    field("id", FieldType::Integer, "The model id."),
+    field("version", FieldType::Integer, "The model format version."),
+    field("vertexCount", FieldType::Integer, ""),
+    field("triangleCount", FieldType::Integer, ""),
+    field("texturedTriangleCount", FieldType::Integer, ""),
+    field(
+        "priority",
+        FieldType::Integer,
+        "The model-wide draw priority, 255 if triangles have their own.",
+    ),
+    field(
+        "fingerprint",
+        FieldType::String,
+        "16 hex digits hashing the geometry, equal for duplicate models.",
+    ),
+    field("transparent", FieldType::Bool, ""),
+    field("hasVertexSkins", FieldType::Bool, ""),
+    field("hasTriangleSkins", FieldType::Bool, ""),
+    field(
+        "materials",
+        FieldType::Integers,
+        "The texture ids used, ascending.",
+    ),
+    field(
+        "boundsMin",
+        FieldType::Integers,
+        "The x, y and z minimum of the centred model, in model units.",
+    ),
+    field("boundsMax", FieldType::Integers, "Like boundsMin."),
+    field("xzRadius", FieldType::Integer, ""),
+    field("xyzRadius", FieldType::Integer, ""),
+    field(
+        "thumbnail",
+        FieldType::String,
+        "The path of the thumbnail PNG, relative to the manifest.",
+    ),
+];
+
+const DATASET_FIELDS: &[Field] = &[
+    VERSION_FIELD,
+    field(
+        "startId",
+        FieldType::Integer,
+        "The first model id of the range.",
+    ),
+    field("endId", FieldType::Integer, "The model id after the range."),
+    field(
+        "thumbnailSize",
+        FieldType::Integer,
+        "Thumbnail width and height in pixels.",
+    ),
+    field(
+        "models",
+        FieldType::Objects(DATASET_MODEL_FIELDS),
+        "The models that could be decoded.",
+    ),
+];
+
+const MODEL_FIELDS: &[Field] = &[
+    VERSION_FIELD,
+    field("id", FieldType::Integer, "The model id."),
+    field("version", FieldType::Integer, "The model format version."),
+    field("vertexCount", FieldType::Integer, ""),
+    field("triangleCount", FieldType::Integer, ""),
+    field("texturedTriangleCount", FieldType::Integer, ""),
+    field("priority", FieldType::Integer, ""),
+    field("hasSkins", FieldType::Bool, ""),
+    field(
+        "fingerprint",
+        FieldType::String,
+        "16 hex digits hashing the geometry.",
+    ),
+];
+
+const NPC_FIELDS: &[Field] = &[
+    VERSION_FIELD,
+    field("id", FieldType::Integer, "The NPC id."),
+    field("name", FieldType::String, ""),
+    field(
+        "models",
+        FieldType::Integers,
+        "The models merged into the body.",
+    ),
+    field("size", FieldType::Integer, "The size in tiles."),
+    field(
+        "combatLevel",
+        FieldType::OptionalInteger,
+        "Null for NPCs that can't be fought.",
+    ),
+    field(
+        "recolours",
+        FieldType::IntegerPairs,
+        "HSL colours replaced, as [old, new].",
+    ),
+    field(
+        "retextures",
+        FieldType::IntegerPairs,
+        "Textures replaced, as [old, new].",
+    ),
+    field(
+        "resizeH",
+        FieldType::Integer,
+        "Horizontal scale, 128 is the original size.",
+    ),
+    field(
+        "resizeV",
+        FieldType::Integer,
+        "Vertical scale, 128 is the original size.",
+    ),
+    field(
+        "ambient",
+        FieldType::Integer,
+        "Added to the default ambient light.",
+    ),
+    field(
+        "contrast",
+        FieldType::Integer,
+        "Added to the default contrast.",
+    ),
+    field(
+        "unknownOpcode",
+        FieldType::OptionalInteger,
+        "The first opcode that couldn't be decoded, fields after it keep their defaults.",
+    ),
+];
+
+const ITEM_FIELDS: &[Field] = &[
+    VERSION_FIELD,
+    field("id", FieldType::Integer, "The item id."),
+    field("name", FieldType::String, ""),
+    field(
+        "examine",
+        FieldType::String,
+        "Empty in caches that don't keep examine texts.",
+    ),
+    field(
+        "model",
+        FieldType::OptionalInteger,
+        "The ground and inventory model.",
+    ),
+    field("zoom2d", FieldType::Integer, "Inventory icon zoom."),
+    field("xAngle2d", FieldType::Integer, "Inventory icon rotations."),
+    field("yAngle2d", FieldType::Integer, ""),
+    field("zAngle2d", FieldType::Integer, ""),
+    field("xOffset2d", FieldType::Integer, "Inventory icon offsets."),
+    field("yOffset2d", FieldType::Integer, ""),
+    field("stackable", FieldType::Bool, ""),
+    field("cost", FieldType::Integer, ""),
+    field("members", FieldType::Bool, ""),
+    field(
+        "maleModels",
+        FieldType::OptionalIntegers,
+        "The three worn models on male players.",
+    ),
+    field(
+        "femaleModels",
+        FieldType::OptionalIntegers,
+        "The three worn models on female players.",
+    ),
+    field(
+        "recolours",
+        FieldType::IntegerPairs,
+        "HSL colours replaced, as [old, new].",
+    ),
+    field(
+        "retextures",
+        FieldType::IntegerPairs,
+        "Textures replaced, as [old, new].",
+    ),
+    field(
+        "certLink",
+        FieldType::OptionalInteger,
+        "The noted or unnoted item.",
+    ),
+    field(
+        "certTemplate",
+        FieldType::OptionalInteger,
+        "The note template item.",
+    ),
+    field(
+        "countObjs",
+        FieldType::IntegerPairs,
+        "Items shown for stack sizes, as [item, count].",
+    ),
+    field(
+        "resize",
+        FieldType::Integers,
+        "The x, y and z scale, 128 is the original size.",
+    ),
+    field(
+        "ambient",
+        FieldType::Integer,
+        "Added to the default ambient light.",
+    ),
+    field(
+        "contrast",
+        FieldType::Integer,
+        "Added to the default contrast.",
+    ),
+    field(
+        "unknownOpcode",
+        FieldType::OptionalInteger,
+        "The first opcode that couldn't be decoded, fields after it keep their defaults.",
+    ),
+];
+
+/// The schema of one kind of exported JSON file.
+#[derive(Debug, Clone, Copy)]
+pub struct Schema {
+    pub name: &'static str,
+    pub description: &'static str,
+    fields: &'static [Field],
+}
+
+impl Schema {
+    pub const ALL: [Schema; 4] = [
+        Schema {
+            name: "dataset",
+            description: "The models.json manifest of a dataset export.",
+            fields: DATASET_FIELDS,
+        },
+        Schema {
+            name: "model",
+            description: "The metadata of a model, as copied from the model info.",
+            fields: MODEL_FIELDS,
+        },
+        Schema {
+            name: "npc",
+            description: "A decoded NPC definition, as written by model-export.",
+            fields: NPC_FIELDS,
+        },
+        Schema {
+            name: "item",
+            description: "A decoded item definition, as written by model-export.",
+            fields: ITEM_FIELDS,
+        },
+    ];
+
+    pub fn find(name: &str) -> Option<Schema> {
+        Self::ALL.into_iter().find(|schema| schema.name == name)
+    }
+
+    /// The file name the schema is published under.
+    pub fn file_name(&self) -> String {
+        format!("{}.v{SCHEMA_VERSION}.schema.json", self.name)
+    }
+
+    /// The schema as a JSON Schema (draft 2020-12) document.
+    pub fn to_json(&self) -> String {
+        let id = format!("https://rs-model-viewer/schemas/{}", self.file_name());
+        object_schema(self.fields)
+            .string("$schema", "https://json-schema.org/draft/2020-12/schema")
+            .string("$id", &id)
+            .string("title", self.name)
+            .string("description", self.description)
+            .finish()
+    }
+}
+
+fn type_schema(ty: &str) -> JsonObject {
+    JsonObject::new().string("type", ty)
+}
+
+/// An integer or null.
+fn optional_integer_schema() -> JsonObject {
+    JsonObject::new().raw("type", r#"["integer","null"]"#)
+}
+
+fn array_schema(items: JsonObject) -> JsonObject {
+    type_schema("array").raw("items", &items.finish())
+}
+
+fn field_schema(ty: FieldType) -> JsonObject {
+    match ty {
+        FieldType::Integer => type_schema("integer"),
+        FieldType::Bool => type_schema("boolean"),
+        FieldType::String => type_schema("string"),
+        FieldType::OptionalInteger => optional_integer_schema(),
+        FieldType::Integers => array_schema(type_schema("integer")),
+        FieldType::OptionalIntegers => array_schema(optional_integer_schema()),
+        FieldType::IntegerPairs => array_schema(
+            array_schema(type_schema("integer"))
+                .number("minItems", 2)
+                .number("maxItems", 2),
+        ),
+        FieldType::Objects(fields) => array_schema(object_schema(fields)),
+    }
+}
+
+/// An object schema requiring every field, left open for the caller to add keywords.
+fn object_schema(fields: &[Field]) -> JsonObject {
+    let properties = fields
+        .iter()
+        .fold(JsonObject::new(), |properties, field| {
+            let mut schema = field_schema(field.ty);
+            if !field.description.is_empty() {
+                schema = schema.string("description", field.description);
+            }
+            properties.raw(field.name, &schema.finish())
+        })
+        .finish();
+    let required: Vec<String> = fields
+        .iter()
+        .map(|field| {
+            let mut name = String::new();
+            json::write_string(&mut name, field.name);
+            name
+        })
+        .collect();
+    type_schema("object")
+        .raw("properties", &properties)
+        .raw("required", &json::array(&required))
+}
+
+fn optional(value: Option<u32>) -> String {
+    value.map_or_else(|| "null".to_owned(), |value| value.to_string())
+}
+
+fn pairs<A: fmt::Display, B: fmt::Display>(pairs: impl IntoIterator<Item = (A, B)>) -> String {
+    let pairs: Vec<String> = pairs
+        .into_iter()
+        .map(|(a, b)| format!("[{a},{b}]"))
+        .collect();
+    json::array(&pairs)
+}
+
+/// Writes an NPC in the layout of the `npc` schema.
+pub fn npc_json(npc: &NpcType) -> String {
+    JsonObject::new()
+        .number("schemaVersion", SCHEMA_VERSION)
+        .number("id", npc.id)
+        .string("name", &npc.name)
+        .numbers("models", &npc.models)
+        .number("size", npc.size)
+        .raw("combatLevel", &optional(npc.combat_level.map(u32::from)))
+        .raw("recolours", &pairs(npc.recolours.iter().copied()))
+        .raw("retextures", &pairs(npc.retextures.iter().copied()))
+        .number("resizeH", npc.resize_h)
+        .number("resizeV", npc.resize_v)
+        .number("ambient", npc.ambient)
+        .number("contrast", npc.contrast)
+        .raw(
+            "unknownOpcode",
+            &optional(npc.unknown_opcode.map(u32::from)),
+        )
+        .finish()
+}
+
+/// Writes an item in the layout of the `item` schema.
+pub fn item_json(obj: &ObjType) -> String {
+    let models = |models: &[Option<u32>; 3]| json::array(&models.map(optional));
+    JsonObject::new()
+        .number("schemaVersion", SCHEMA_VERSION)
+        .number("id", obj.id)
+        .string("name", &obj.name)
+        .string("examine", &obj.examine)
+        .raw("model", &optional(obj.model))
+        .number("zoom2d", obj.zoom_2d)
+        .number("xAngle2d", obj.x_angle_2d)
+        .number("yAngle2d", obj.y_angle_2d)
+        .number("zAngle2d", obj.z_angle_2d)
+        .number("xOffset2d", obj.x_offset_2d)
+        .number("yOffset2d", obj.y_offset_2d)
+        .bool("stackable", obj.stackable)
+        .number("cost", obj.cost)
+        .bool("members", obj.members)
+        .raw("maleModels", &models(&obj.male_models))
+        .raw("femaleModels", &models(&obj.female_models))
+        .raw("recolours", &pairs(obj.recolours.iter().copied()))
+        .raw("retextures", &pairs(obj.retextures.iter().copied()))
+        .raw("certLink", &optional(obj.cert_link))
+        .raw("certTemplate", &optional(obj.cert_template))
+        .raw("countObjs", &pairs(obj.count_objs.iter().copied()))
+        .numbers("resize", &obj.resize)
+        .number("ambient", obj.ambient)
+        .number("contrast", obj.contrast)
+        .raw(
+            "unknownOpcode",
+            &optional(obj.unknown_opcode.map(u32::from)),
+        )
+        .finish()
+}
@@ -0,0 +1,115 @@
+use std::io::Write;
+
+use libflate::deflate;
+
+use super::ExportFile;
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x04034b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x02014b50;
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x06054b50;
+/// Version 2.0, the first with deflate and folders.
+const VERSION: u16 = 20;
+/// Entry names are UTF-8.
+const FLAG_UTF8: u16 = 1 << 11;
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+/// 1980-01-01 00:00, the earliest DOS date, as exports carry no timestamps.
+const DOS_TIME: u16 = 0;
+const DOS_DATE: u16 = (1 << 5) | 1;
+
+struct CentralEntry {
+    name: String,
+    method: u16,
+    crc: u32,
+    compressed_size: u32,
+    size: u32,
+    offset: u32,
+}
+
+fn put_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+/// Packs the files into a ZIP archive in memory. Files are deflated unless that doesn't make
+/// them smaller, e.g. PNGs. Without ZIP64 the archive is limited to 65535 files and 4 GiB.
+pub fn encode_zip(files: &[ExportFile]) -> Result<Vec<u8>, String> {
+    if files.len() > u16::MAX as usize {
+        return Err(format!("too many files for a zip: {}", files.len()));
+    }
+    let too_large = || "export is too large for a zip".to_owned();
+
+    let mut out = Vec::new();
+    let mut entries = Vec::with_capacity(files.len());
+    for file in files {
+        let size = u32::try_from(file.data.len()).map_err(|_| too_large())?;
+        let mut encoder = deflate::Encoder::new(Vec::new());
+        encoder.write_all(&file.data).unwrap();
+        let deflated = encoder.finish().into_result().unwrap();
+        let (method, data) = if deflated.len() < file.data.len() {
+            (METHOD_DEFLATED, &deflated[..])
+        } else {
+            (METHOD_STORED, &file.data[..])
+        };
+        let entry = CentralEntry {
+            name: file.path.replace('\\', "/"),
+            method,
+            crc: crc32fast::hash(&file.data),
+            compressed_size: data.len() as u32,
+            size,
+            offset: u32::try_from(out.len()).map_err(|_| too_large())?,
+        };
+
+        put_u32(&mut out, LOCAL_HEADER_SIGNATURE);
+        put_u16(&mut out, VERSION);
+        put_u16(&mut out, FLAG_UTF8);
+        put_u16(&mut out, entry.method);
+        put_u16(&mut out, DOS_TIME);
+        put_u16(&mut out, DOS_DATE);
+        put_u32(&mut out, entry.crc);
+        put_u32(&mut out, entry.compressed_size);
+        put_u32(&mut out, entry.size);
+        put_u16(&mut out, entry.name.len() as u16);
+        put_u16(&mut out, 0);
+        out.extend_from_slice(entry.name.as_bytes());
+        out.extend_from_slice(data);
+        entries.push(entry);
+    }
+
+    let directory_offset = u32::try_from(out.len()).map_err(|_| too_large())?;
+    for entry in &entries {
+        put_u32(&mut out, CENTRAL_HEADER_SIGNATURE);
+        put_u16(&mut out, VERSION);
+        put_u16(&mut out, VERSION);
+        put_u16(&mut out, FLAG_UTF8);
+        put_u16(&mut out, entry.method);
+        put_u16(&mut out, DOS_TIME);
+        put_u16(&mut out, DOS_DATE);
+        put_u32(&mut out, entry.crc);
+        put_u32(&mut out, entry.compressed_size);
+        put_u32(&mut out, entry.size);
+        put_u16(&mut out, entry.name.len() as u16);
+        // Extra field, comment, disk number, internal and external attributes.
+        put_u16(&mut out, 0);
+        put_u16(&mut out, 0);
+        put_u16(&mut out, 0);
+        put_u16(&mut out, 0);
+        put_u32(&mut out, 0);
+        put_u32(&mut out, entry.offset);
+        out.extend_from_slice(entry.name.as_bytes());
+    }
+    let directory_size = u32::try_from(out.len()).map_err(|_| too_large())? - directory_offset;
+
+    put_u32(&mut out, END_OF_CENTRAL_DIRECTORY_SIGNATURE);
+    put_u16(&mut out, 0);
+    put_u16(&mut out, 0);
+    put_u16(&mut out, entries.len() as u16);
+    put_u16(&mut out, entries.len() as u16);
+    put_u32(&mut out, directory_size);
+    put_u32(&mut out, directory_offset);
+    put_u16(&mut out, 0);
+    Ok(out)
+}
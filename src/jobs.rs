@@ -0,0 +1,141 @@
+//! Background jobs that run the same way natively and on the web.
+//!
+//! Natively each spawned job gets its own thread and runs there with [`block_on`]. On the web
+//! jobs are futures on the page's event loop, so waiting in them has to yield to it instead of
+//! blocking. Either way jobs wait on a [`Signal`] raised when what they wait for may be done.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Mutex,
+    task::{Context, Poll, Waker},
+};
+
+/// Runs the future in the background.
+#[cfg(target_arch = "wasm32")]
+pub fn spawn(future: impl Future<Output = ()> + 'static) {
+    wasm_bindgen_futures::spawn_local(future);
+}
+
+/// Runs the future in the background.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn spawn(future: impl Future<Output = ()> + Send + 'static) {
+    std::thread::spawn(move || block_on(future));
+}
+
+/// Runs the future to completion on the calling thread, parking it while the future waits.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    use std::{sync::Arc, task::Wake, thread::Thread};
+
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let mut future = std::pin::pin!(future);
+    let waker = Arc::new(Unpark(std::thread::current())).into();
+    let mut context = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return output;
+        }
+        std::thread::park();
+    }
+}
+
+/// Waits for the duration. Natively this blocks the job's thread, which it has to itself.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn sleep(duration: std::time::Duration) {
+    std::thread::sleep(duration);
+}
+
+/// Waits for the duration, yielding to the page's event loop meanwhile.
+#[cfg(target_arch = "wasm32")]
+pub async fn sleep(duration: std::time::Duration) {
+    let promise = web_sys::js_sys::Promise::new(&mut |resolve, _| {
+        let window = web_sys::window().unwrap();
+        window
+            .set_timeout_with_callback_and_timeout_and_arguments_0(
+                &resolve,
+                duration.as_millis().min(i32::MAX as u128) as i32,
+            )
+            .unwrap();
+    });
+    let _ = wasm_bindgen_futures::JsFuture::from(promise).await;
+}
+
+/// Wakes the jobs waiting on it each time it's raised, for jobs waiting on work that finishes
+/// on other threads or in other futures.
+#[derive(Debug, Default)]
+pub struct Signal {
+    state: Mutex<SignalState>,
+}
+
+#[derive(Debug, Default)]
+struct SignalState {
+    /// How many times the signal was raised, so waiters can tell if it was raised since they
+    /// last checked.
+    raised: u64,
+    wakers: Vec<Waker>,
+}
+
+impl Signal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wakes every job waiting on the signal.
+    pub fn raise(&self) {
+        let wakers = {
+            let mut state = self.state.lock().unwrap();
+            state.raised = state.raised.wrapping_add(1);
+            std::mem::take(&mut state.wakers)
+        };
+        wakers.into_iter().for_each(Waker::wake);
+    }
+
+    /// Waits until `check` returns something, checking again each time the signal is raised.
+    pub async fn wait_for<T>(&self, mut check: impl FnMut() -> Option<T>) -> T {
+        loop {
+            // Read before checking, so a raise during the check isn't missed.
+            let since = self.state.lock().unwrap().raised;
+            if let Some(value) = check() {
+                return value;
+            }
+            Raised {
+                signal: self,
+                since,
+            }
+            .await;
+        }
+    }
+}
+
+/// Resolves once the signal was raised after `since`.
+struct Raised<'a> {
+    signal: &'a Signal,
+    since: u64,
+}
+
+impl Future for Raised<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.signal.state.lock().unwrap();
+        if state.raised != self.since {
+            return Poll::Ready(());
+        }
+        if !state
+            .wakers
+            .iter()
+            .any(|waker| waker.will_wake(context.waker()))
+        {
+            state.wakers.push(context.waker().clone());
+        }
+        Poll::Pending
+    }
+}
@@ -1,6 +1,25 @@
+//! Decoders for RuneTek 5 caches and the model viewer built on them.
+//!
+//! [`runetek5`] reads JS5 archives from disk or OpenRS2 and decodes the models, textures,
+//! sprites, animations and definitions they hold, [`export`] writes models out as glTF, PNG and
+//! JSON. Neither needs egui: depend on the crate with `default-features = false` to leave out
+//! the viewer and eframe. The viewer itself, [`ModelViewerApp`], is behind the default `app`
+//! feature.
+//!
+//! Lookups into archives return `None` while a group is being fetched or if it doesn't exist,
+//! rather than panicking.
+
 #![warn(clippy::all, rust_2018_idioms)]
 
+#[cfg(feature = "app")]
 mod app;
+pub mod export;
+pub mod jobs;
+pub mod parallel;
 pub mod runetek5;
+pub mod trace;
 
-pub use app::ModelViewerApp;
+#[cfg(all(feature = "app", not(target_arch = "wasm32")))]
+pub use app::pick_cache_dir;
+#[cfg(feature = "app")]
+pub use app::{linked_cache_id, model_image, plugin, ModelViewerApp};
@@ -1,63 +1,286 @@
 #![warn(clippy::all, rust_2018_idioms)]
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
+/// The archives the viewer opens, ready to hand to the app.
+type LoadedCache = (
+    std::sync::Arc<rs_model_viewer::runetek5::js5::Js5>,
+    std::sync::Arc<rs_model_viewer::runetek5::js5::Js5>,
+    std::sync::Arc<rs_model_viewer::runetek5::js5::Js5>,
+    std::sync::Arc<rs_model_viewer::runetek5::js5::Js5>,
+    rs_model_viewer::runetek5::graphics::texture::TextureProvider,
+    rs_model_viewer::plugin::PluginRegistry,
+);
+
+/// Opens a local cache in the `main_file_cache.dat2` layout.
+#[cfg(not(target_arch = "wasm32"))]
+fn load_disk_cache(cache_dir: &std::path::Path) -> Result<LoadedCache, String> {
+    use std::sync::Arc;
+
+    use rs_model_viewer::{
+        plugin::PluginRegistry,
+        runetek5::{
+            graphics::texture::TextureProvider,
+            js5::{
+                disk::{Js5DiskResourceProvider, Js5DiskStore},
+                Js5, Js5IdPolicy, Js5ResourceProvider as _,
+            },
+        },
+    };
+
+    let store = match Js5DiskStore::open(cache_dir) {
+        Ok(store) => Arc::new(store),
+        Err(err) => {
+            return Err(format!(
+                "Failed to open the cache in {}: {err}. Pick the directory holding \
+                 main_file_cache.dat2, pass it as the first argument, or #cache=<id> to stream \
+                 a cache from OpenRS2.",
+                cache_dir.display()
+            ));
+        }
+    };
+    let open_archive = |archive_id| {
+        let provider = Arc::new(Js5DiskResourceProvider::new(archive_id, store.clone()));
+        let Some(index) = provider.fetch_index() else {
+            return Err(format!(
+                "The cache in {} has no archive {archive_id}.",
+                cache_dir.display()
+            ));
+        };
+        Ok(Js5::new(provider, index, false, false))
+    };
+
+    let frame_js5 = Arc::new(open_archive(0)?);
+    let framemap_js5 = Arc::new(open_archive(1)?);
+    let model_js5 = Arc::new(open_archive(7)?);
+    // Sprite ids are always group ids.
+    let sprite_js5 = Arc::new(open_archive(8)?.with_id_policy(Js5IdPolicy::Group));
+    let texture_js5 = open_archive(9)?;
+    let fontmetrics_js5 = Arc::new(open_archive(13)?);
+
+    // Groups are read as they are fetched, so one pass loads everything the cache has.
+    if !texture_js5.fetch_all() {
+        log::warn!("The cache is missing some texture groups");
+    }
+    let texture_provider = TextureProvider::new(sprite_js5, &texture_js5);
+    let loaded_percentage = texture_provider.get_loaded_percentage();
+    if loaded_percentage < 100 {
+        log::warn!("Only {loaded_percentage}% of the texture sprites are in the cache");
+    }
+
+    let mut plugins = PluginRegistry::with_builtins();
+    for archive_id in plugins.missing_archive_ids() {
+        if store.has_archive(archive_id) {
+            plugins.add_archive(archive_id, Arc::new(open_archive(archive_id)?));
+        }
+    }
+
+    Ok((
+        model_js5,
+        frame_js5,
+        framemap_js5,
+        fontmetrics_js5,
+        texture_provider,
+        plugins,
+    ))
+}
+
+/// Waits for the index of an archive, failing if the server refused it for good.
+async fn open_archive(
+    resource_provider: &std::sync::Arc<
+        rs_model_viewer::runetek5::js5::net::Openrs2Js5ResourceProvider,
+    >,
+) -> Result<rs_model_viewer::runetek5::js5::Js5, String> {
+    use rs_model_viewer::runetek5::js5::Js5;
+
+    let index = resource_provider.index_ready().await.map_err(|error| {
+        format!(
+            "The index of archive {} failed: {error}.",
+            resource_provider.get_archive_id()
+        )
+    })?;
+    Ok(Js5::new(resource_provider.clone(), index, false, false))
+}
+
+/// Streams a cache from OpenRS2, waiting for the groups needed on startup.
+async fn load_openrs2_cache(cache_id: u32) -> Result<LoadedCache, String> {
+    use std::sync::Arc;
+
+    use rs_model_viewer::{
+        plugin::PluginRegistry,
+        runetek5::{
+            graphics::texture::TextureProvider,
+            js5::{
+                net::{Openrs2Js5NetClient, Openrs2Js5ResourceProvider},
+                Js5IdPolicy,
+            },
+        },
+    };
+
+    let net_client = Arc::new(Openrs2Js5NetClient::new(cache_id));
+    let archive = |archive_id| {
+        Arc::new(Openrs2Js5ResourceProvider::new(
+            archive_id,
+            net_client.clone(),
+        ))
+    };
+
+    let frame_js5 = Arc::new(open_archive(&archive(0)).await?);
+    let framemap_js5 = Arc::new(open_archive(&archive(1)).await?);
+    let model_js5 = Arc::new(open_archive(&archive(7)).await?);
+    let sprite_resources = archive(8);
+    // Sprite ids are always group ids.
+    let sprite_js5 = Arc::new(
+        open_archive(&sprite_resources)
+            .await?
+            .with_id_policy(Js5IdPolicy::Group),
+    );
+    let texture_resources = archive(9);
+    let texture_js5 = open_archive(&texture_resources).await?;
+    let fontmetrics_js5 = Arc::new(open_archive(&archive(13)).await?);
+
+    texture_resources
+        .ready(|| texture_js5.fetch_all())
+        .await
+        .map_err(|failed| describe_failed_group(&texture_resources, failed))?;
+
+    let texture_provider = TextureProvider::new(sprite_js5, &texture_js5);
+
+    // Plugins do without archives the cache lacks rather than failing the whole viewer.
+    let mut plugins = PluginRegistry::with_builtins();
+    for archive_id in plugins.missing_archive_ids() {
+        match open_archive(&archive(archive_id)).await {
+            Ok(js5) => plugins.add_archive(archive_id, Arc::new(js5)),
+            Err(message) => log::warn!("Skipping plugin archive: {message}"),
+        }
+    }
+
+    sprite_resources
+        .ready(|| texture_provider.get_loaded_percentage() == 100)
+        .await
+        .map_err(|failed| describe_failed_group(&sprite_resources, failed))?;
+
+    Ok((
+        model_js5,
+        frame_js5,
+        framemap_js5,
+        fontmetrics_js5,
+        texture_provider,
+        plugins,
+    ))
+}
+
 // When compiling natively:
 #[cfg(not(target_arch = "wasm32"))]
 fn main() -> eframe::Result {
+    use std::path::PathBuf;
+
     env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
+    rs_model_viewer::trace::init();
+
+    // A `#cache=<id>` argument streams that cache from OpenRS2, otherwise the first argument
+    // not starting with `#` is the local cache directory. Without either the directory is picked
+    // in a dialog.
+    let loaded = match rs_model_viewer::linked_cache_id() {
+        Some(cache_id) => {
+            rs_model_viewer::jobs::block_on(load_openrs2_cache(cache_id)).map_err(|message| {
+                format!("Cache {cache_id} could not be opened from OpenRS2. {message}")
+            })
+        }
+        None => std::env::args()
+            .skip(1)
+            .find(|arg| !arg.starts_with('#'))
+            .map(PathBuf::from)
+            .or_else(rs_model_viewer::pick_cache_dir)
+            .ok_or_else(|| {
+                "No cache was opened. Pick the directory holding main_file_cache.dat2, pass it \
+                 as the first argument, or #cache=<id> to stream a cache from OpenRS2."
+                    .to_owned()
+            })
+            .and_then(|cache_dir| load_disk_cache(&cache_dir)),
+    };
+    let (model_js5, frame_js5, framemap_js5, fontmetrics_js5, texture_provider, plugins) =
+        match loaded {
+            Ok(loaded) => loaded,
+            Err(message) => {
+                eprintln!("{message}");
+                std::process::exit(1);
+            }
+        };
 
     let native_options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
-            .with_inner_size([400.0, 300.0])
+            .with_inner_size([1280.0, 800.0])
             .with_min_inner_size([300.0, 220.0])
             .with_icon(
                 // NOTE: Adding an icon is optional
                 eframe::icon_data::from_png_bytes(&include_bytes!("../assets/icon-256.png")[..])
                     .expect("Failed to load icon"),
             ),
+        depth_buffer: 24,
         ..Default::default()
     };
     eframe::run_native(
-        "eframe template",
+        "Model Viewer",
         native_options,
-        Box::new(|cc| Ok(Box::new(eframe_template::ModelViewerApp::new(cc)))),
+        Box::new(|cc| {
+            Ok(Box::new(rs_model_viewer::ModelViewerApp::new(
+                cc,
+                model_js5,
+                frame_js5,
+                framemap_js5,
+                fontmetrics_js5,
+                texture_provider,
+                plugins,
+            )))
+        }),
     )
 }
 
-pub async fn sleep(delay: i32) {
-    let mut cb = |resolve: web_sys::js_sys::Function, reject: web_sys::js_sys::Function| {
-        web_sys::window()
-            .unwrap()
-            .set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, delay);
-    };
-
-    let p = web_sys::js_sys::Promise::new(&mut cb);
+/// The message for a group that was refused for good while loading.
+fn describe_failed_group(
+    resource_provider: &rs_model_viewer::runetek5::js5::net::Openrs2Js5ResourceProvider,
+    (group_id, error): (u32, rs_model_viewer::runetek5::js5::net::Js5FetchError),
+) -> String {
+    format!(
+        "Group {group_id} of archive {} failed: {error}.",
+        resource_provider.get_archive_id()
+    )
+}
 
-    wasm_bindgen_futures::JsFuture::from(p).await.unwrap();
+/// Replaces the loading spinner with the error and a form to open another cache.
+#[cfg(target_arch = "wasm32")]
+fn show_cache_error(document: &web_sys::Document, cache_id: u32, message: &str) {
+    log::error!("Failed to open cache {cache_id}: {message}");
+    let Some(loading_text) = document.get_element_by_id("loading_text") else {
+        return;
+    };
+    loading_text.set_inner_html(&format!(
+        "<p>Cache {cache_id} could not be opened. {message}</p>\
+         <p style=\"font-size:16px\">It may not exist on OpenRS2 or may lack the model, sprite or \
+         texture archives. Pick another cache from \
+         <a href=\"https://archive.openrs2.org/caches\" style=\"color:#8cf\">archive.openrs2.org</a>:</p>\
+         <form onsubmit=\"location.hash = 'cache=' + this.cache.value; location.reload(); return false;\">\
+         <input name=\"cache\" type=\"number\" min=\"0\" value=\"{cache_id}\"> <button>Open</button>\
+         </form>"
+    ));
 }
 
 // When compiling to web using trunk:
 #[cfg(target_arch = "wasm32")]
 fn main() {
-    use std::sync::Arc;
-
     use eframe::wasm_bindgen::JsCast as _;
-    use rs_model_viewer::runetek5::{
-        graphics::texture::TextureProvider,
-        js5::{
-            net::{Openrs2Js5NetClient, Openrs2Js5ResourceProvider},
-            Js5, Js5ResourceProvider,
-        },
-    };
+    use rs_model_viewer::runetek5::js5::net::Openrs2Js5NetClient;
 
     // Redirect `log` message to `console.log` and friends:
     // let is_release = cfg!(debug_assertions);
     // eframe::WebLogger::init(log::LevelFilter::Debug).ok();
 
+    rs_model_viewer::trace::init();
+
     let mut web_options = eframe::WebOptions::default();
     web_options.depth_buffer = 24;
 
-    wasm_bindgen_futures::spawn_local(async {
+    rs_model_viewer::jobs::spawn(async {
         let document = web_sys::window()
             .expect("No window")
             .document()
@@ -69,51 +292,19 @@ fn main() {
             .dyn_into::<web_sys::HtmlCanvasElement>()
             .expect("the_canvas_id was not a HtmlCanvasElement");
 
-        let net_client = Arc::new(Openrs2Js5NetClient::new(2064));
-
-        let resource_provider = Arc::new(Openrs2Js5ResourceProvider::new(7, net_client.clone()));
-        let model_js5 = loop {
-            let index = resource_provider.fetch_index();
-            if let Some(index) = index {
-                break Arc::new(Js5::new(resource_provider.clone(), index, false, false));
-            }
-            sleep(20).await;
-        };
+        rs_model_viewer::parallel::init_web_pool().await;
 
-        let resource_provider = Arc::new(Openrs2Js5ResourceProvider::new(8, net_client.clone()));
-        let sprite_js5 = loop {
-            let index = resource_provider.fetch_index();
-            if let Some(index) = index {
-                break Arc::new(Js5::new(resource_provider.clone(), index, false, false));
-            }
-            sleep(20).await;
-        };
-        let resource_provider = Arc::new(Openrs2Js5ResourceProvider::new(9, net_client.clone()));
-        let texture_js5 = loop {
-            let index = resource_provider.fetch_index();
-            if let Some(index) = index {
-                break Arc::new(Js5::new(resource_provider.clone(), index, false, false));
-            }
-            sleep(20).await;
-        };
-
-        loop {
-            if texture_js5.fetch_all() {
-                break;
-            }
-            sleep(20).await;
-        }
-
-        let texture_provider = TextureProvider::new(sprite_js5.clone(), &texture_js5);
-
-        loop {
-            let loaded_percentage = texture_provider.get_loaded_percentage();
-            if loaded_percentage == 100 {
-                break;
-            }
-            println!("Loaded: {}%", loaded_percentage);
-            sleep(20).await;
-        }
+        rs_model_viewer::runetek5::js5::net::restore_offline_mode();
+        let cache_id =
+            rs_model_viewer::linked_cache_id().unwrap_or(Openrs2Js5NetClient::DEFAULT_CACHE_ID);
+        let (model_js5, frame_js5, framemap_js5, fontmetrics_js5, texture_provider, plugins) =
+            match load_openrs2_cache(cache_id).await {
+                Ok(loaded) => loaded,
+                Err(message) => {
+                    show_cache_error(&document, cache_id, &message);
+                    return;
+                }
+            };
 
         let start_result = eframe::WebRunner::new()
             .start(
@@ -123,7 +314,11 @@ fn main() {
                     Ok(Box::new(rs_model_viewer::ModelViewerApp::new(
                         cc,
                         model_js5,
+                        frame_js5,
+                        framemap_js5,
+                        fontmetrics_js5,
                         texture_provider,
+                        plugins,
                     )))
                 }),
             )
@@ -0,0 +1,59 @@
+//! Optional data parallelism for decoding work.
+//!
+//! With the `threads` feature the work is spread over a Rayon pool. On the web the pool lives in
+//! workers sharing the wasm memory, which needs a cross-origin isolated page (COOP/COEP headers)
+//! and a build with the `atomics` target feature, see the README. Without a pool everything runs
+//! on the calling thread.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool =
+    AtomicBool::new(cfg!(all(feature = "threads", not(target_arch = "wasm32"))));
+
+/// Whether work is currently spread over the thread pool.
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Starts the worker pool, resolving to false if the page is not cross-origin isolated or the
+/// build has no thread support.
+#[cfg(target_arch = "wasm32")]
+pub async fn init_web_pool() -> bool {
+    #[cfg(feature = "threads")]
+    {
+        let window = web_sys::window().expect("No window");
+        let isolated = web_sys::js_sys::Reflect::get(&window, &"crossOriginIsolated".into())
+            .map(|value| value.is_truthy())
+            .unwrap_or(false);
+        if !isolated {
+            log::warn!("Page is not cross-origin isolated, decoding on the main thread");
+            return false;
+        }
+        let threads = window.navigator().hardware_concurrency().max(1.0) as usize;
+        let promise = wasm_bindgen_rayon::init_thread_pool(threads);
+        if let Err(err) = wasm_bindgen_futures::JsFuture::from(promise).await {
+            log::warn!("Failed to start decode workers: {err:?}");
+            return false;
+        }
+        log::info!("Started {threads} decode workers");
+        ENABLED.store(true, Ordering::Relaxed);
+        true
+    }
+    #[cfg(not(feature = "threads"))]
+    false
+}
+
+/// Maps `f` over `items`, in parallel when the pool is enabled. The output keeps the input order.
+pub fn map<T, R, F>(items: &[T], f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync + Send,
+{
+    #[cfg(feature = "threads")]
+    if is_enabled() {
+        use rayon::prelude::*;
+        return items.par_iter().map(f).collect();
+    }
+    items.iter().map(f).collect()
+}
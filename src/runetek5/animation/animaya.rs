@@ -0,0 +1,205 @@
+//! Animaya animations: bone transforms keyframed on curves, as exported from Maya, stored in the
+//! frame archive and played by sequences in place of frames. They pose a [`Skeleton`] from a
+//! frame map, which moves the vertices bound to its bones by their model's Maya groups.
+
+use nalgebra_glm as glm;
+
+use super::skeleton::Skeleton;
+use crate::runetek5::io::packet::Packet;
+
+/// A key of a curve, with the tangents of the curve arriving at it and leaving it as offsets in
+/// frames and value.
+#[derive(Debug, Clone, Copy)]
+pub struct Keyframe {
+    pub frame: i16,
+    pub value: f32,
+    pub in_tangent: [f32; 2],
+    pub out_tangent: [f32; 2],
+}
+
+/// A value animated over time, as cubic Bézier segments between keyframes. Before the first key
+/// and after the last the curve holds its value.
+#[derive(Debug, Clone)]
+pub struct Curve {
+    pub keyframes: Vec<Keyframe>,
+}
+
+impl Curve {
+    fn decode(data: &mut &[u8]) -> Option<Self> {
+        // How the curve continues before and after its keys, only holding the value is played.
+        data.try_skip(2)?;
+        let count = data.try_g2()? as usize;
+        let keyframes = (0..count)
+            .map(|_| {
+                Some(Keyframe {
+                    frame: data.try_g2s()?,
+                    value: data.try_g4f()?,
+                    in_tangent: [data.try_g4f()?, data.try_g4f()?],
+                    out_tangent: [data.try_g4f()?, data.try_g4f()?],
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self { keyframes })
+    }
+
+    /// The value of the curve at a frame, None without keyframes.
+    pub fn value_at(&self, frame: f32) -> Option<f32> {
+        let first = self.keyframes.first()?;
+        let last = self.keyframes.last()?;
+        if frame <= first.frame as f32 {
+            return Some(first.value);
+        }
+        if frame >= last.frame as f32 {
+            return Some(last.value);
+        }
+        let end = self
+            .keyframes
+            .iter()
+            .position(|key| key.frame as f32 > frame)?;
+        let (start, end) = (&self.keyframes[end - 1], &self.keyframes[end]);
+        let p0 = [start.frame as f32, start.value];
+        let p3 = [end.frame as f32, end.value];
+        let p1 = [p0[0] + start.out_tangent[0], p0[1] + start.out_tangent[1]];
+        let p2 = [p3[0] - end.in_tangent[0], p3[1] - end.in_tangent[1]];
+        let bezier = |axis: usize, t: f32| {
+            let u = 1.0 - t;
+            u * u * u * p0[axis]
+                + 3.0 * u * u * t * p1[axis]
+                + 3.0 * u * t * t * p2[axis]
+                + t * t * t * p3[axis]
+        };
+        // Find where the segment reaches the frame, its frames only ever increase.
+        let (mut low, mut high) = (0.0, 1.0);
+        for _ in 0..24 {
+            let middle = (low + high) / 2.0;
+            if bezier(0, middle) < frame {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+        Some(bezier(1, (low + high) / 2.0))
+    }
+}
+
+/// What a curve animates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CurveTarget {
+    /// One of a bone's nine channels: translation, rotation in radians and scale, each along x,
+    /// y and z.
+    Bone { bone: usize, channel: u8 },
+    /// Targets other than bones, e.g. colours, which aren't played.
+    Other(u8),
+}
+
+/// A keyframed animation of a skeleton.
+#[derive(Debug, Clone)]
+pub struct AnimayaAnimation {
+    pub version: u8,
+    /// The frame map holding the skeleton the animation poses.
+    pub framemap_id: u16,
+    /// The length of the animation in client cycles.
+    pub duration: u16,
+    pub curves: Vec<(CurveTarget, Curve)>,
+}
+
+impl AnimayaAnimation {
+    const BONE_TARGET: u8 = 1;
+    const CHANNELS: usize = 9;
+
+    /// Decodes an animation, None if the data doesn't decode exactly.
+    pub fn decode(mut data: &[u8]) -> Option<Self> {
+        let version = data.try_g1()?;
+        let framemap_id = data.try_g2()?;
+        let duration = data.try_g2()?;
+        let count = data.try_g2()? as usize;
+        let mut curves = Vec::with_capacity(count);
+        for _ in 0..count {
+            let target = data.try_g1()?;
+            let bone = data.try_g2s()?;
+            let channel = data.try_g1()?;
+            let curve = Curve::decode(&mut data)?;
+            let target = match (target, usize::try_from(bone)) {
+                (Self::BONE_TARGET, Ok(bone)) if (channel as usize) < Self::CHANNELS => {
+                    CurveTarget::Bone { bone, channel }
+                }
+                _ => CurveTarget::Other(target),
+            };
+            curves.push((target, curve));
+        }
+        if !data.is_empty() {
+            return None;
+        }
+        Some(Self {
+            version,
+            framemap_id,
+            duration,
+            curves,
+        })
+    }
+
+    /// The skinning transforms of the skeleton's bones at a frame, see
+    /// [`Skeleton::skinning_transforms`]. Channels without a curve keep their bind pose value.
+    pub fn skinning_transforms(&self, skeleton: &Skeleton, frame: f32) -> Vec<glm::Mat4> {
+        let mut channels: Vec<[Option<f32>; Self::CHANNELS]> =
+            vec![[None; Self::CHANNELS]; skeleton.bones.len()];
+        for (target, curve) in &self.curves {
+            if let CurveTarget::Bone { bone, channel } = *target {
+                if let Some(bone_channels) = channels.get_mut(bone) {
+                    bone_channels[channel as usize] = curve.value_at(frame);
+                }
+            }
+        }
+        let locals: Vec<glm::Mat4> = channels
+            .iter()
+            .enumerate()
+            .map(|(bone, bone_channels)| {
+                let bind = skeleton.bind_local(bone);
+                if bone_channels.iter().all(Option::is_none) {
+                    return bind;
+                }
+                let values: Vec<f32> = bone_channels
+                    .iter()
+                    .zip(decompose(&bind))
+                    .map(|(value, bind_value)| value.unwrap_or(bind_value))
+                    .collect();
+                compose(&values)
+            })
+            .collect();
+        skeleton.skinning_transforms(&locals)
+    }
+}
+
+/// Builds a transform from translation, rotation and scale, rotating about x, then y, then z.
+fn compose(values: &[f32]) -> glm::Mat4 {
+    let translation = glm::translation(&glm::vec3(values[0], values[1], values[2]));
+    let rotation = glm::rotation(values[5], &glm::Vec3::z())
+        * glm::rotation(values[4], &glm::Vec3::y())
+        * glm::rotation(values[3], &glm::Vec3::x());
+    let scale = glm::scaling(&glm::vec3(values[6], values[7], values[8]));
+    translation * rotation * scale
+}
+
+/// Splits a transform into the channels [`compose`] builds it from.
+fn decompose(transform: &glm::Mat4) -> [f32; 9] {
+    let column = |index: usize| transform.column(index).xyz();
+    let scale = [column(0).norm(), column(1).norm(), column(2).norm()];
+    let x_axis = column(0) / scale[0].max(f32::EPSILON);
+    let y_axis = column(1) / scale[1].max(f32::EPSILON);
+    let z_axis = column(2) / scale[2].max(f32::EPSILON);
+    let rotation_y = (-x_axis.z).clamp(-1.0, 1.0).asin();
+    let rotation_x = y_axis.z.atan2(z_axis.z);
+    let rotation_z = x_axis.y.atan2(x_axis.x);
+    let translation = column(3);
+    [
+        translation.x,
+        translation.y,
+        translation.z,
+        rotation_x,
+        rotation_y,
+        rotation_z,
+        scale[0],
+        scale[1],
+        scale[2],
+    ]
+}
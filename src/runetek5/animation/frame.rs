@@ -0,0 +1,104 @@
+use crate::runetek5::io::packet::Packet;
+
+use super::framemap::{FrameMap, TransformType};
+
+/// A single transform of a frame, `slot` indexes the frame map's types and labels.
+#[derive(Debug, Clone, Copy)]
+pub struct FrameTransform {
+    pub slot: u8,
+    /// Which of the x, y and z values were encoded, the others use the type's default.
+    pub flags: u8,
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+/// An animation frame from archive 0, as the raw list of transforms it encodes.
+///
+/// The transforms are not resolved against the frame map, origin transforms the client inserts
+/// implicitly are not added.
+#[derive(Debug, Clone)]
+pub struct AnimFrame {
+    pub framemap_id: u16,
+    pub transforms: Vec<FrameTransform>,
+    /// Bytes left in the body after the last transform.
+    pub trailing_bytes: usize,
+}
+
+impl AnimFrame {
+    /// Reads the id of the frame map a frame uses without decoding the transforms.
+    pub fn peek_framemap_id(data: &[u8]) -> Option<u16> {
+        (data.len() >= 2).then(|| u16::from_be_bytes([data[0], data[1]]))
+    }
+
+    /// The transforms as the client applies them. Before a transform that isn't an origin, the
+    /// client inserts the nearest origin slot since the previous transform, if any, at zero.
+    pub fn resolve_transforms(&self, framemap: &FrameMap) -> Vec<FrameTransform> {
+        let mut resolved = Vec::with_capacity(self.transforms.len());
+        let mut next_slot = 0;
+        for transform in &self.transforms {
+            if framemap.types[transform.slot as usize] != TransformType::Origin {
+                let origin_slot = (next_slot..transform.slot as usize)
+                    .rev()
+                    .find(|&slot| framemap.types[slot] == TransformType::Origin);
+                if let Some(slot) = origin_slot {
+                    resolved.push(FrameTransform {
+                        slot: slot as u8,
+                        flags: 0,
+                        x: 0,
+                        y: 0,
+                        z: 0,
+                    });
+                }
+            }
+            resolved.push(*transform);
+            next_slot = transform.slot as usize + 1;
+        }
+        resolved
+    }
+
+    /// Decodes a frame, returning `None` if the data is truncated or references slots missing
+    /// from `framemap`.
+    pub fn decode(data: &[u8], framemap: &FrameMap) -> Option<Self> {
+        let _span = tracing::info_span!("frame_decode", len = data.len()).entered();
+        let framemap_id = Self::peek_framemap_id(data)?;
+        let mut header = &data[2..];
+        let count = header.try_g1()? as usize;
+        if header.len() < count || count > framemap.types.len() {
+            return None;
+        }
+        let mut body = &header[count..];
+
+        let mut transforms = Vec::new();
+        for slot in 0..count {
+            let flags = header.g1();
+            if flags == 0 {
+                continue;
+            }
+            let default = framemap.types[slot].default_value();
+            let mut value = |bit: u8| -> Option<i32> {
+                if flags & bit != 0 {
+                    body.try_get_smart_1_or_2s()
+                } else {
+                    Some(default)
+                }
+            };
+            let x = value(0x1)?;
+            let y = value(0x2)?;
+            let z = value(0x4)?;
+            transforms.push(FrameTransform {
+                slot: slot as u8,
+                flags,
+                x,
+                y,
+                z,
+            });
+        }
+
+        Some(Self {
+            framemap_id,
+            transforms,
+            trailing_bytes: body.len(),
+        })
+    }
+}
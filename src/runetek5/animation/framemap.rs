@@ -0,0 +1,84 @@
+use super::skeleton::Skeleton;
+use crate::runetek5::io::packet::Packet;
+
+/// The kind of transform a frame map slot applies to its label groups.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransformType {
+    Origin,
+    Translate,
+    Rotate,
+    Scale,
+    Transparency,
+    Unknown(u8),
+}
+
+impl TransformType {
+    pub fn from_id(id: u8) -> Self {
+        match id {
+            0 => TransformType::Origin,
+            1 => TransformType::Translate,
+            2 => TransformType::Rotate,
+            3 => TransformType::Scale,
+            5 => TransformType::Transparency,
+            id => TransformType::Unknown(id),
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TransformType::Origin => "Origin",
+            TransformType::Translate => "Translate",
+            TransformType::Rotate => "Rotate",
+            TransformType::Scale => "Scale",
+            TransformType::Transparency => "Transparency",
+            TransformType::Unknown(_) => "Unknown",
+        }
+    }
+
+    /// The value used for axes a frame does not encode.
+    pub fn default_value(&self) -> i32 {
+        match self {
+            TransformType::Scale => 128,
+            _ => 0,
+        }
+    }
+}
+
+/// A frame map (animation base) from archive 1, listing the transform slots frames refer to and
+/// the vertex/triangle skin labels each slot affects.
+#[derive(Debug, Clone)]
+pub struct FrameMap {
+    pub types: Vec<TransformType>,
+    pub labels: Vec<Vec<u8>>,
+    /// The bones animaya animations pose, newer caches append them after the labels.
+    pub skeleton: Option<Skeleton>,
+    /// Bytes left after the known fields that aren't a skeleton.
+    pub trailing_bytes: usize,
+}
+
+impl FrameMap {
+    /// Decodes a frame map, returning `None` if the data is truncated.
+    pub fn decode(mut data: &[u8]) -> Option<Self> {
+        let _span = tracing::info_span!("framemap_decode", len = data.len()).entered();
+        let count = data.try_g1()? as usize;
+        let types = (0..count)
+            .map(|_| data.try_g1().map(TransformType::from_id))
+            .collect::<Option<Vec<_>>>()?;
+        let label_counts = (0..count)
+            .map(|_| data.try_g1())
+            .collect::<Option<Vec<_>>>()?;
+        let mut labels = Vec::with_capacity(count);
+        for label_count in label_counts {
+            let mut label = vec![0; label_count as usize];
+            data.try_get_array(&mut label)?;
+            labels.push(label);
+        }
+        let skeleton = Skeleton::decode(data);
+        Some(Self {
+            types,
+            labels,
+            trailing_bytes: if skeleton.is_some() { 0 } else { data.len() },
+            skeleton,
+        })
+    }
+}
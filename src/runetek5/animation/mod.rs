@@ -0,0 +1,5 @@
+pub mod animaya;
+pub mod frame;
+pub mod framemap;
+pub mod seq;
+pub mod skeleton;
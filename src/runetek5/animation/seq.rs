@@ -0,0 +1,124 @@
+use crate::runetek5::{io::packet::Packet, js5::Js5};
+
+/// A sequence from the config archive: the frames an animation steps through and how long each
+/// is shown. Only the opcodes that time and loop the frames are kept, the rest are skipped.
+#[derive(Debug, Clone, Default)]
+pub struct SeqType {
+    pub id: u32,
+    /// Frame ids, the frame group in the high 16 bits and the file in the low 16 bits.
+    pub frame_ids: Vec<u32>,
+    /// Client cycles of 20 ms each frame is shown for.
+    pub frame_delays: Vec<u16>,
+    /// Frames from the end to loop back by, None to stop after the last frame.
+    pub replay_offset: Option<u16>,
+    pub replay_count: u8,
+    pub priority: u8,
+    /// Frames for the model's other layer, e.g. a held item.
+    pub iframe_ids: Vec<u32>,
+    /// Frame map slots this sequence animates when it is played over another one.
+    pub interleave_order: Vec<u8>,
+    /// Whether the client tweens between frames.
+    pub tweened: bool,
+    /// The animaya animation played instead of frames, stored like frame ids.
+    pub animaya_id: Option<u32>,
+    /// The first opcode that couldn't be decoded, fields after it are missing.
+    pub unknown_opcode: Option<u8>,
+}
+
+impl SeqType {
+    pub const ARCHIVE_ID: u8 = 2;
+    /// The group of the config archive holding one file per sequence.
+    pub const GROUP_ID: u32 = 12;
+    /// The length of a client cycle in milliseconds, the unit of frame delays.
+    pub const CYCLE_MS: u32 = 20;
+
+    /// Reads the sequence from the config archive, None while the group is being fetched or if
+    /// the sequence doesn't exist.
+    pub fn from_js5(config_js5: &Js5, id: u32) -> Option<Self> {
+        let data = config_js5.get_file(Self::GROUP_ID, id)?;
+        Self::decode(id, &data)
+    }
+
+    /// Decodes a sequence, None if the data ends early.
+    pub fn decode(id: u32, data: &[u8]) -> Option<Self> {
+        let mut seq = Self {
+            id,
+            ..Default::default()
+        };
+        let mut buf = data;
+        loop {
+            let opcode = buf.try_g1()?;
+            if opcode == 0 {
+                break;
+            }
+            if seq.decode_opcode(opcode, &mut buf).is_none() {
+                // Later opcodes can't be found without knowing the length of this one.
+                seq.unknown_opcode = Some(opcode);
+                break;
+            }
+        }
+        Some(seq)
+    }
+
+    fn decode_opcode(&mut self, opcode: u8, buf: &mut &[u8]) -> Option<()> {
+        match opcode {
+            1 => {
+                let count = buf.try_g2()? as usize;
+                self.frame_delays = (0..count).map(|_| buf.try_g2()).collect::<Option<_>>()?;
+                self.frame_ids = (0..count)
+                    .map(|_| buf.try_g2().map(u32::from))
+                    .collect::<Option<_>>()?;
+                for frame_id in &mut self.frame_ids {
+                    *frame_id |= (buf.try_g2()? as u32) << 16;
+                }
+            }
+            2 => self.replay_offset = Some(buf.try_g2()?),
+            3 => {
+                let count = buf.try_g1()? as usize;
+                self.interleave_order = (0..count).map(|_| buf.try_g1()).collect::<Option<_>>()?;
+            }
+            4 => {}
+            5 => self.priority = buf.try_g1()?,
+            // Right and left hand objects.
+            6 | 7 => buf.try_skip(2)?,
+            8 => self.replay_count = buf.try_g1()?,
+            // Movement before and after the animation, and how it replays.
+            9..=11 => buf.try_skip(1)?,
+            12 => {
+                let count = buf.try_g1()? as usize;
+                self.iframe_ids = (0..count)
+                    .map(|_| buf.try_g2().map(u32::from))
+                    .collect::<Option<_>>()?;
+                for frame_id in &mut self.iframe_ids {
+                    *frame_id |= (buf.try_g2()? as u32) << 16;
+                }
+            }
+            // Sounds by frame.
+            13 => {
+                let count = buf.try_g1()? as usize;
+                buf.try_skip(count * 3)?;
+            }
+            14 => self.animaya_id = Some(buf.try_g4()?),
+            // Two shorts the viewer has no use for.
+            16 => buf.try_skip(4)?,
+            18 => self.tweened = true,
+            _ => return None,
+        }
+        Some(())
+    }
+
+    /// The frame group of a frame id.
+    pub fn frame_group(frame_id: u32) -> u32 {
+        frame_id >> 16
+    }
+
+    /// The file of a frame id in its group.
+    pub fn frame_file(frame_id: u32) -> u32 {
+        frame_id & 0xffff
+    }
+
+    /// The length of one play through, in client cycles.
+    pub fn duration(&self) -> u32 {
+        self.frame_delays.iter().map(|&delay| delay as u32).sum()
+    }
+}
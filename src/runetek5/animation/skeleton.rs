@@ -0,0 +1,103 @@
+use nalgebra_glm as glm;
+
+use crate::runetek5::io::packet::Packet;
+
+/// A bone of a [`Skeleton`], with its transform relative to its parent in each stored pose.
+#[derive(Debug, Clone)]
+pub struct Bone {
+    pub parent: Option<usize>,
+    /// Local transforms by pose, the first is the bind pose models are rigged in.
+    pub poses: Vec<glm::Mat4>,
+}
+
+/// The bone hierarchy newer frame maps carry after their transform slots, used by animaya
+/// (Maya exported, keyframed) animations. Vertices are bound to bones by the Maya groups of their
+/// model.
+#[derive(Debug, Clone)]
+pub struct Skeleton {
+    pub bones: Vec<Bone>,
+    /// Model space transforms of the bind pose, inverted.
+    inverse_bind: Vec<glm::Mat4>,
+}
+
+impl Skeleton {
+    /// Decodes the skeleton from the bytes following a frame map's labels. None if there is no
+    /// skeleton, or the data doesn't decode exactly.
+    pub fn decode(mut data: &[u8]) -> Option<Self> {
+        let bone_count = data.try_g2()? as usize;
+        if bone_count == 0 {
+            return None;
+        }
+        let pose_count = data.try_g1()? as usize;
+        let mut bones = Vec::with_capacity(bone_count);
+        for _ in 0..bone_count {
+            let parent = data.try_g2s()?;
+            let poses = (0..pose_count)
+                .map(|_| read_matrix(&mut data))
+                .collect::<Option<Vec<_>>>()?;
+            bones.push(Bone {
+                // Parents come first, anything else would loop.
+                parent: usize::try_from(parent)
+                    .ok()
+                    .filter(|&parent| parent < bones.len()),
+                poses,
+            });
+        }
+        if !data.is_empty() {
+            return None;
+        }
+        let bind_locals: Vec<glm::Mat4> = bones
+            .iter()
+            .map(|bone| bone.poses.first().copied().unwrap_or_else(glm::identity))
+            .collect();
+        let inverse_bind = Self::model_transforms(&bones, &bind_locals)
+            .iter()
+            .map(|transform| transform.try_inverse().unwrap_or_else(glm::identity))
+            .collect();
+        Some(Self {
+            bones,
+            inverse_bind,
+        })
+    }
+
+    /// Chains local bone transforms from the root down into model space.
+    fn model_transforms(bones: &[Bone], locals: &[glm::Mat4]) -> Vec<glm::Mat4> {
+        let mut transforms: Vec<glm::Mat4> = Vec::with_capacity(bones.len());
+        for (bone, local) in bones.iter().zip(locals) {
+            let transform = match bone.parent {
+                Some(parent) => transforms[parent] * local,
+                None => *local,
+            };
+            transforms.push(transform);
+        }
+        transforms
+    }
+
+    /// The local transform of a bone in the bind pose.
+    pub fn bind_local(&self, bone: usize) -> glm::Mat4 {
+        self.bones[bone]
+            .poses
+            .first()
+            .copied()
+            .unwrap_or_else(glm::identity)
+    }
+
+    /// Turns local bone transforms into the transforms that move bound vertices from the bind
+    /// pose into this one.
+    pub fn skinning_transforms(&self, locals: &[glm::Mat4]) -> Vec<glm::Mat4> {
+        Self::model_transforms(&self.bones, locals)
+            .iter()
+            .zip(&self.inverse_bind)
+            .map(|(transform, inverse_bind)| transform * inverse_bind)
+            .collect()
+    }
+}
+
+/// Reads a 4x4 matrix stored as 16 floats in column major order.
+fn read_matrix(data: &mut &[u8]) -> Option<glm::Mat4> {
+    let mut values = [0.0; 16];
+    for value in &mut values {
+        *value = data.try_g4f()?;
+    }
+    Some(glm::Mat4::from_column_slice(&values))
+}
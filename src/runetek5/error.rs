@@ -0,0 +1,57 @@
+//! Why cache data couldn't be decoded. The decoders return these instead of panicking, since
+//! caches from archives and mirrors aren't always intact and a panic takes the whole wasm runtime
+//! down with it.
+
+use std::fmt;
+
+use super::js5::Js5CompressionType;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Runetek5Error {
+    /// The data ended before the named part of it could be read.
+    Truncated(&'static str),
+    UnknownCompressionType(u8),
+    UnsupportedCompressionType(Js5CompressionType),
+    /// The compressed stream is invalid, with the decompressor's reason.
+    Decompression(String),
+    /// The data couldn't be compressed, with the compressor's reason.
+    Compression(String),
+    /// The decrypted payload doesn't start like its compression type, the key is wrong or missing.
+    Decryption,
+    ChecksumMismatch {
+        expected: u32,
+        actual: u32,
+    },
+    UnknownIndexProtocol(u8),
+    /// The data is well formed but uses something that isn't supported.
+    Unsupported(&'static str),
+    /// The data contradicts itself, e.g. ids that overflow.
+    Invalid(&'static str),
+}
+
+impl fmt::Display for Runetek5Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Runetek5Error::Truncated(part) => write!(f, "the data ends in the {part}"),
+            Runetek5Error::UnknownCompressionType(value) => {
+                write!(f, "unknown compression type {value}")
+            }
+            Runetek5Error::UnsupportedCompressionType(compression_type) => {
+                write!(f, "{compression_type:?} compression isn't supported")
+            }
+            Runetek5Error::Decompression(reason) => write!(f, "decompression failed: {reason}"),
+            Runetek5Error::Compression(reason) => write!(f, "compression failed: {reason}"),
+            Runetek5Error::Decryption => write!(f, "the XTEA key is wrong or missing"),
+            Runetek5Error::ChecksumMismatch { expected, actual } => {
+                write!(f, "CRC {actual:08x} isn't the expected {expected:08x}")
+            }
+            Runetek5Error::UnknownIndexProtocol(value) => {
+                write!(f, "unknown index protocol {value}")
+            }
+            Runetek5Error::Unsupported(what) => write!(f, "unsupported {what}"),
+            Runetek5Error::Invalid(what) => write!(f, "invalid {what}"),
+        }
+    }
+}
+
+impl std::error::Error for Runetek5Error {}
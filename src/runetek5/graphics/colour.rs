@@ -0,0 +1,57 @@
+use super::{model::Hsl, texture::brighten_rgb};
+
+pub const HSL_PALETTE_SIZE: usize = 65536;
+
+fn hue_to_rgb(p: f64, q: f64, t: f64) -> f64 {
+    if 6.0 * t < 1.0 {
+        p + (q - p) * 6.0 * t
+    } else if 2.0 * t < 1.0 {
+        q
+    } else if 3.0 * t < 2.0 {
+        p + (q - p) * (2.0 / 3.0 - t) * 6.0
+    } else {
+        p
+    }
+}
+
+/// Converts a packed 16-bit HSL colour (6 bits hue, 3 bits saturation, 7 bits lightness) to RGB
+/// the same way the client builds its colour palette.
+pub fn hsl_to_rgb(hsl: Hsl, brightness: f64) -> u32 {
+    let hue = (hsl >> 10) as f64 / 64.0 + 0.0078125;
+    let saturation = ((hsl >> 7) & 0x7) as f64 / 8.0 + 0.0625;
+    let lightness = (hsl & 0x7f) as f64 / 128.0;
+
+    let q = if lightness < 0.5 {
+        lightness * (1.0 + saturation)
+    } else {
+        lightness + saturation - lightness * saturation
+    };
+    let p = 2.0 * lightness - q;
+
+    let mut hue_r = hue + 1.0 / 3.0;
+    if hue_r > 1.0 {
+        hue_r -= 1.0;
+    }
+    let hue_g = hue;
+    let mut hue_b = hue - 1.0 / 3.0;
+    if hue_b < 0.0 {
+        hue_b += 1.0;
+    }
+
+    let r = (hue_to_rgb(p, q, hue_r) * 256.0) as u32;
+    let g = (hue_to_rgb(p, q, hue_g) * 256.0) as u32;
+    let b = (hue_to_rgb(p, q, hue_b) * 256.0) as u32;
+
+    let rgb = brighten_rgb((r << 16) | (g << 8) | b, brightness);
+    if rgb == 0 {
+        1
+    } else {
+        rgb
+    }
+}
+
+pub fn build_hsl_palette(brightness: f64) -> Vec<u32> {
+    (0..HSL_PALETTE_SIZE)
+        .map(|hsl| hsl_to_rgb(hsl as Hsl, brightness))
+        .collect()
+}
@@ -0,0 +1,171 @@
+use crate::runetek5::io::packet::{char_to_cp1252, Packet};
+
+use super::sprite::{Pix8, SpriteData};
+
+/// Glyph advances and vertical metrics of a font, stored in the font metrics archive under the
+/// same group id as the font's glyph sprite.
+#[derive(Debug, Clone)]
+pub struct FontMetrics {
+    pub char_advances: [u8; 256],
+    /// Whether per-glyph bearing tables for kerning follow the advances. They are skipped.
+    pub has_kerning: bool,
+    pub ascent: u8,
+    pub trailing_bytes: usize,
+}
+
+impl FontMetrics {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let mut buf = data;
+        let version = buf.try_g1()?;
+        if version != 0 {
+            return None;
+        }
+        let has_kerning = buf.try_g1()? == 1;
+        let mut char_advances = [0; 256];
+        buf.try_get_array(&mut char_advances)?;
+
+        let ascent = if has_kerning {
+            let mut left_counts = [0; 256];
+            let mut right_counts = [0; 256];
+            buf.try_get_array(&mut left_counts)?;
+            buf.try_get_array(&mut right_counts)?;
+            let bearing_bytes: usize = left_counts
+                .iter()
+                .chain(right_counts.iter())
+                .map(|&count| count as usize)
+                .sum();
+            buf.try_skip(bearing_bytes)?;
+            // Derived from the bearings of the space glyph rather than stored.
+            left_counts[32].wrapping_add(right_counts[32])
+        } else {
+            buf.try_g1()?
+        };
+
+        Some(Self {
+            char_advances,
+            has_kerning,
+            ascent,
+            trailing_bytes: buf.remaining(),
+        })
+    }
+
+    /// Approximates metrics from the glyphs alone, for fonts without a metrics group.
+    pub fn from_glyphs(glyphs: &[Pix8]) -> Self {
+        let mut char_advances = [0; 256];
+        for (advance, glyph) in char_advances.iter_mut().zip(glyphs) {
+            *advance = if glyph.sub_width == 0 {
+                // Blank glyphs such as space are as wide as a quarter of the glyph canvas.
+                (glyph.width / 4).max(1) as u8
+            } else {
+                (glyph.offset_x + glyph.sub_width + 1).min(u8::MAX as u16) as u8
+            };
+        }
+        let ascent = glyphs.get(b'A' as usize).map_or(0, |glyph| {
+            (glyph.offset_y + glyph.sub_height).min(u8::MAX as u16) as u8
+        });
+        Self {
+            char_advances,
+            has_kerning: false,
+            ascent,
+            trailing_bytes: 0,
+        }
+    }
+}
+
+/// Text rasterized by [`Font::render`] as 0xAARRGGBB pixels, transparent where no glyph is.
+pub struct TextImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u32>,
+}
+
+/// A bitmap font: one glyph sprite frame per cp1252 byte.
+pub struct Font {
+    pub glyphs: Vec<Pix8>,
+    pub metrics: FontMetrics,
+}
+
+impl Font {
+    pub const GLYPH_COUNT: usize = 256;
+
+    /// Decodes the glyph sprite, using the given metrics or approximating them from the glyphs.
+    /// Returns None for sprites that aren't fonts or can't be decoded.
+    pub fn decode(sprite_data: &[u8], metrics: Option<FontMetrics>) -> Option<Self> {
+        let glyphs = SpriteData::decode_into_pix8s(sprite_data).ok()?;
+        if glyphs.len() != Self::GLYPH_COUNT {
+            return None;
+        }
+        let metrics = metrics.unwrap_or_else(|| FontMetrics::from_glyphs(&glyphs));
+        Some(Self { glyphs, metrics })
+    }
+
+    /// The height of the glyph canvas, which every line of text occupies.
+    pub fn line_height(&self) -> i32 {
+        self.glyphs[0].height as i32
+    }
+
+    fn glyph_index(c: char) -> u8 {
+        char_to_cp1252(c).unwrap_or(b'?')
+    }
+
+    pub fn text_width(&self, text: &str) -> i32 {
+        text.chars()
+            .map(|c| self.metrics.char_advances[Self::glyph_index(c) as usize] as i32)
+            .sum()
+    }
+
+    /// Draws `text` in a single colour into 0xAARRGGBB `pixels`, with (x, y) the top left of the
+    /// glyph canvas. Glyphs are clipped to the buffer.
+    pub fn draw(
+        &self,
+        pixels: &mut [u32],
+        width: usize,
+        text: &str,
+        mut x: i32,
+        y: i32,
+        argb: u32,
+    ) {
+        let height = (pixels.len() / width.max(1)) as i32;
+        for c in text.chars() {
+            let index = Self::glyph_index(c) as usize;
+            let glyph = &self.glyphs[index];
+            let glyph_x = x + glyph.offset_x as i32;
+            let glyph_y = y + glyph.offset_y as i32;
+            for gy in 0..glyph.sub_height as i32 {
+                let py = glyph_y + gy;
+                if py < 0 || py >= height {
+                    continue;
+                }
+                for gx in 0..glyph.sub_width as i32 {
+                    let px = glyph_x + gx;
+                    if px < 0 || px >= width as i32 {
+                        continue;
+                    }
+                    let palette_index = glyph.pixels[(gx + gy * glyph.sub_width as i32) as usize];
+                    if palette_index != 0 {
+                        pixels[px as usize + py as usize * width] = argb;
+                    }
+                }
+            }
+            x += self.metrics.char_advances[index] as i32;
+        }
+    }
+
+    /// Rasterizes a line of text into an image just large enough for it, optionally with the
+    /// one pixel drop shadow the client draws under most text.
+    pub fn render(&self, text: &str, argb: u32, shadow_argb: Option<u32>) -> TextImage {
+        let shadow_offset = shadow_argb.map_or(0, |_| 1);
+        let width = (self.text_width(text).max(1) + shadow_offset) as usize;
+        let height = (self.line_height().max(1) + shadow_offset) as usize;
+        let mut pixels = vec![0; width * height];
+        if let Some(shadow_argb) = shadow_argb {
+            self.draw(&mut pixels, width, text, 1, 1, shadow_argb);
+        }
+        self.draw(&mut pixels, width, text, 0, 0, argb);
+        TextImage {
+            width,
+            height,
+            pixels,
+        }
+    }
+}
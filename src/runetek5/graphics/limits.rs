@@ -0,0 +1,101 @@
+//! Limits of the model format, to tell whether an edited or merged model can still be encoded
+//! and loaded by the client.
+//!
+//! HSL colours are 16 bit in memory as in the format, so they can't leave the range and aren't
+//! checked.
+
+use super::model::ModelUnlit;
+
+/// The largest value of one property of a model against what the format can store.
+#[derive(Debug, Clone)]
+pub struct LimitCheck {
+    pub name: &'static str,
+    pub value: i64,
+    pub limit: i64,
+    /// What happens past the limit.
+    pub note: &'static str,
+}
+
+impl LimitCheck {
+    pub fn passed(&self) -> bool {
+        self.value <= self.limit
+    }
+}
+
+/// Vertex positions are stored as deltas in signed smarts.
+pub const MAX_VERTEX_DELTA: i64 = 16383;
+/// Texture mappings are counted in a byte.
+pub const MAX_TEXTURE_MAPPINGS: i64 = 255;
+/// Vertex skins are stored in a byte where 255 means no skin, unless extended.
+pub const MAX_VERTEX_SKIN: i64 = 254;
+pub const MAX_TRIANGLE_SKIN: i64 = 255;
+
+/// Checks the model against the limits of the format, skipping properties it doesn't have.
+pub fn check_limits(model: &ModelUnlit) -> Vec<LimitCheck> {
+    let mut checks = vec![
+        LimitCheck {
+            name: "Vertices",
+            value: model.vertex_x.len() as i64,
+            limit: u16::MAX as i64,
+            note: "Counts are 16 bit",
+        },
+        LimitCheck {
+            name: "Triangles",
+            value: model.triangle_a.len() as i64,
+            limit: u16::MAX as i64,
+            note: "Counts are 16 bit",
+        },
+        LimitCheck {
+            name: "Texture mappings",
+            value: model.textured_triangle_count as i64,
+            limit: MAX_TEXTURE_MAPPINGS,
+            note: "Counted in a byte",
+        },
+        LimitCheck {
+            name: "Vertex delta",
+            value: max_vertex_delta(model),
+            limit: MAX_VERTEX_DELTA,
+            note: "Positions are stored as deltas between vertices",
+        },
+    ];
+
+    if let Some(coords) = &model.triangle_texture_coords {
+        checks.push(LimitCheck {
+            name: "Texture mapping index",
+            value: coords.iter().copied().max().unwrap_or(-1) as i64,
+            limit: model.textured_triangle_count as i64 - 1,
+            note: "Triangles must use one of the model's texture mappings",
+        });
+    }
+    if let Some(skins) = &model.vertex_skins {
+        checks.push(LimitCheck {
+            name: "Vertex skin label",
+            value: skins.iter().copied().max().unwrap_or(-1) as i64,
+            limit: MAX_VERTEX_SKIN,
+            note: "Stored in a byte unless the model uses extended skins",
+        });
+    }
+    if let Some(skins) = &model.triangle_skins {
+        checks.push(LimitCheck {
+            name: "Triangle skin label",
+            value: skins.iter().copied().max().unwrap_or(-1) as i64,
+            limit: MAX_TRIANGLE_SKIN,
+            note: "Stored in a byte",
+        });
+    }
+    checks
+}
+
+fn max_vertex_delta(model: &ModelUnlit) -> i64 {
+    let mut max_delta = 0;
+    for axis in [&model.vertex_x, &model.vertex_y, &model.vertex_z] {
+        let mut last = 0i64;
+        for &position in axis.iter() {
+            let delta = position as i64 - last;
+            // Smarts go one further below zero, -16384 fits as well as 16383.
+            max_delta = max_delta.max(delta.max(-delta - 1));
+            last = position as i64;
+        }
+    }
+    max_delta
+}
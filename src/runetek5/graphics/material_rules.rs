@@ -0,0 +1,200 @@
+//! Global material substitutions applied when models are lit, for visual experiments and for
+//! comparing models with and without their textures. Rules are written one per line as
+//! `<materials> -> <target>`, the first rule matching a material applies:
+//!
+//! ```text
+//! # Swap a texture for another.
+//! 40 -> 41
+//! # Draw some textures as a flat colour.
+//! 1-3,24 -> colour 43968
+//! # Draw everything else like standard detail does.
+//! * -> average
+//! ```
+//!
+//! Materials are ids and ranges of ids separated by commas, or `*` for every material. Colours are
+//! packed HSL, in decimal or `0x` hex.
+
+use std::{fmt, str::FromStr};
+
+use super::{
+    model::{Hsl, ModelUnlit},
+    texture::{MaterialInfo, TextureProvider},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaterialTarget {
+    Material(u16),
+    /// Untextured, in the given colour.
+    Colour(Hsl),
+    /// Untextured, in the material's average colour.
+    AverageColour,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MaterialRule {
+    /// Inclusive ranges of material ids, empty for every material.
+    pub materials: Vec<(u16, u16)>,
+    pub target: MaterialTarget,
+}
+
+impl MaterialRule {
+    pub fn matches(&self, material: u16) -> bool {
+        self.materials.is_empty()
+            || self
+                .materials
+                .iter()
+                .any(|&(first, last)| (first..=last).contains(&material))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MaterialRules {
+    rules: Vec<MaterialRule>,
+}
+
+impl MaterialRules {
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn rules(&self) -> &[MaterialRule] {
+        &self.rules
+    }
+
+    /// What the first rule matching the material replaces it with.
+    pub fn target(&self, material: u16) -> Option<MaterialTarget> {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(material))
+            .map(|rule| rule.target)
+    }
+
+    /// The model with its materials substituted, None if no rule applies to it.
+    pub fn apply(
+        &self,
+        texture_provider: &TextureProvider,
+        model: &ModelUnlit,
+    ) -> Option<ModelUnlit> {
+        let materials = model.triangle_material.as_ref()?;
+        if self.is_empty() {
+            return None;
+        }
+        let mut substituted: Option<ModelUnlit> = None;
+        for (t, &material) in materials.iter().enumerate() {
+            if material == -1 {
+                continue;
+            }
+            let Some(target) = self.target(material as u16) else {
+                continue;
+            };
+            let model = substituted.get_or_insert_with(|| model.clone());
+            let (material, colour) = match target {
+                MaterialTarget::Material(new_material) => (new_material as i16, None),
+                MaterialTarget::Colour(colour) => (-1, Some(colour)),
+                MaterialTarget::AverageColour => {
+                    let info = texture_provider
+                        .get_info(material as u16 as u32)
+                        .unwrap_or(&MaterialInfo::DEFAULT);
+                    (-1, Some(info.average_colour))
+                }
+            };
+            model.triangle_material.as_mut().unwrap()[t] = material;
+            if let Some(colour) = colour {
+                model.triangle_colour[t] = colour;
+            }
+        }
+        substituted
+    }
+}
+
+impl fmt::Display for MaterialRules {
+    /// Writes the rules one per line, in the format they are parsed from.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for rule in &self.rules {
+            if rule.materials.is_empty() {
+                write!(f, "*")?;
+            }
+            for (i, &(first, last)) in rule.materials.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ",")?;
+                }
+                if first == last {
+                    write!(f, "{first}")?;
+                } else {
+                    write!(f, "{first}-{last}")?;
+                }
+            }
+            match rule.target {
+                MaterialTarget::Material(material) => writeln!(f, " -> {material}")?,
+                MaterialTarget::Colour(colour) => writeln!(f, " -> colour {colour}")?,
+                MaterialTarget::AverageColour => writeln!(f, " -> average")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_id(text: &str) -> Result<u16, String> {
+    text.trim()
+        .parse()
+        .map_err(|_| format!("invalid material {:?}", text.trim()))
+}
+
+/// Parses a packed HSL colour, in decimal or `0x` hex.
+fn parse_colour(text: &str) -> Result<Hsl, String> {
+    match text.strip_prefix("0x") {
+        Some(hex) => Hsl::from_str_radix(hex, 16),
+        None => text.parse(),
+    }
+    .map_err(|_| format!("invalid colour {text:?}"))
+}
+
+fn parse_rule(line: &str) -> Result<MaterialRule, String> {
+    let (materials, target) = line
+        .split_once("->")
+        .ok_or_else(|| "expected <materials> -> <target>".to_owned())?;
+    let materials = match materials.trim() {
+        "*" => Vec::new(),
+        materials => materials
+            .split(',')
+            .map(|part| match part.split_once('-') {
+                Some((first, last)) => {
+                    let (first, last) = (parse_id(first)?, parse_id(last)?);
+                    if first > last {
+                        return Err(format!("empty range {:?}", part.trim()));
+                    }
+                    Ok((first, last))
+                }
+                None => parse_id(part).map(|id| (id, id)),
+            })
+            .collect::<Result<_, _>>()?,
+    };
+    let target = match target.split_whitespace().collect::<Vec<_>>()[..] {
+        ["average"] => MaterialTarget::AverageColour,
+        ["colour" | "color", colour] => MaterialTarget::Colour(parse_colour(colour)?),
+        [material] => MaterialTarget::Material(parse_id(material)?),
+        _ => {
+            return Err(format!(
+                "invalid target {:?}, expected a material, colour <hsl> or average",
+                target.trim()
+            ))
+        }
+    };
+    Ok(MaterialRule { materials, target })
+}
+
+impl FromStr for MaterialRules {
+    type Err = String;
+
+    /// Parses rules one per line, skipping blank lines and `#` comments.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let rules = text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .map(|(i, line)| parse_rule(line).map_err(|err| format!("line {}: {err}", i + 1)))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { rules })
+    }
+}
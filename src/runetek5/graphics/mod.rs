@@ -1,3 +1,12 @@
+pub mod colour;
+pub mod font;
+pub mod limits;
+pub mod material_rules;
 pub mod model;
+pub mod model_builder;
+pub mod orientation;
+pub mod quantize;
+pub mod rasterizer;
+pub mod reencode;
 pub mod sprite;
 pub mod texture;
@@ -1,18 +1,25 @@
-use std::sync::Arc;
+use std::{fmt, sync::Arc};
 
 use bitflags::bitflags;
+use nalgebra_glm as glm;
 
 use crate::runetek5::{
-    io::packet::Packet,
+    animation::{
+        frame::AnimFrame,
+        framemap::{FrameMap, TransformType},
+    },
+    error::Runetek5Error,
+    io::packet::{Packet, PacketMut},
     js5::Js5,
     math::trig::{JagDegrees, COSINE, SINE},
 };
 
-use super::texture::{AlphaMode, TextureProvider};
+use super::texture::{AlphaMode, MaterialInfo, TextureProvider};
 
 pub type Hsl = u16;
 pub type Rgb = u32;
 
+#[derive(Debug, Clone)]
 pub struct ModelTextureMappingProps {
     render_types: Vec<u8>,
     mapping_p: Vec<u16>,
@@ -31,6 +38,7 @@ impl ModelTextureMappingProps {
     }
 }
 
+#[derive(Debug, Clone)]
 pub struct ModelComplexTextureMappingProps {
     scale_x: Vec<i32>,
     scale_y: Vec<i32>,
@@ -40,6 +48,20 @@ pub struct ModelComplexTextureMappingProps {
     speed: Vec<i8>,
 }
 
+impl ModelComplexTextureMappingProps {
+    fn new(textured_triangle_count: usize) -> Self {
+        Self {
+            scale_x: vec![0; textured_triangle_count],
+            scale_y: vec![0; textured_triangle_count],
+            scale_z: vec![0; textured_triangle_count],
+            rotation: vec![0; textured_triangle_count],
+            direction: vec![0; textured_triangle_count],
+            speed: vec![0; textured_triangle_count],
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct ModelAnimMayaProps {
     groups: Vec<Vec<u8>>,
     scales: Vec<Vec<u8>>,
@@ -55,7 +77,7 @@ impl ModelAnimMayaProps {
 }
 
 struct ModelMergeVertices {
-    vertex_count: u16,
+    vertex_count: u32,
     vertex_x: Vec<i32>,
     vertex_y: Vec<i32>,
     vertex_z: Vec<i32>,
@@ -79,6 +101,72 @@ struct ModelMergeMaterialTriangles {
     speed: Vec<i8>,
 }
 
+/// The counts of a merged model, summed wide so models too large for the format's 16 bit counts
+/// are caught instead of wrapping around.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MergeSize {
+    pub vertex_count: u32,
+    pub triangle_count: u32,
+    pub textured_triangle_count: u32,
+}
+
+impl MergeSize {
+    /// The most vertices or triangles a model can index.
+    pub const MAX_COUNT: u32 = u16::MAX as u32;
+    /// The most textured triangles, texture coordinates are signed and reserve 32766 and up.
+    pub const MAX_TEXTURED_COUNT: u32 = 32766;
+
+    pub fn of(models: &[ModelUnlit]) -> Self {
+        models
+            .iter()
+            .fold(Self::default(), |size, model| size.with(model))
+    }
+
+    /// The size with `model` merged in too.
+    pub fn with(self, model: &ModelUnlit) -> Self {
+        Self {
+            vertex_count: self.vertex_count + model.vertex_count as u32,
+            triangle_count: self.triangle_count + model.triangle_count as u32,
+            textured_triangle_count: self.textured_triangle_count
+                + model.textured_triangle_count as u32,
+        }
+    }
+
+    pub fn fits(&self) -> bool {
+        self.vertex_count <= Self::MAX_COUNT
+            && self.triangle_count <= Self::MAX_COUNT
+            && self.textured_triangle_count <= Self::MAX_TEXTURED_COUNT
+    }
+}
+
+impl fmt::Display for MergeSize {
+    /// The counts, or only those over their limits when it doesn't fit.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.fits() {
+            return write!(
+                f,
+                "{} vertices and {} triangles",
+                self.vertex_count, self.triangle_count
+            );
+        }
+        let over: Vec<String> = [
+            (self.vertex_count, Self::MAX_COUNT, "vertices"),
+            (self.triangle_count, Self::MAX_COUNT, "triangles"),
+            (
+                self.textured_triangle_count,
+                Self::MAX_TEXTURED_COUNT,
+                "textured triangles",
+            ),
+        ]
+        .into_iter()
+        .filter(|&(count, max, _)| count > max)
+        .map(|(count, max, what)| format!("{count} {what} (at most {max})"))
+        .collect();
+        write!(f, "{}", over.join(", "))
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct ModelUnlit {
     pub version: u8,
     pub vertex_count: u16,
@@ -103,6 +191,9 @@ pub struct ModelUnlit {
     pub vertex_skins: Option<Vec<i32>>,
     pub triangle_skins: Option<Vec<i32>>,
     pub anim_maya_props: Option<ModelAnimMayaProps>,
+    /// Cached by [`ModelUnlit::get_bounds`] and cleared by the transforms. Code moving the
+    /// vertices itself has to clear it too.
+    pub bounds: Option<ModelBounds>,
 }
 
 impl ModelUnlit {
@@ -133,13 +224,19 @@ impl ModelUnlit {
             vertex_skins: None,
             triangle_skins: None,
             anim_maya_props: None,
+            bounds: None,
         }
     }
 
-    pub fn merge(models: &[ModelUnlit]) -> Self {
-        let mut vertex_count = 0u16;
-        let mut triangle_count = 0u16;
-        let mut textured_triangle_count = 0u16;
+    /// Merges the models into one like the client does for players and NPCs. Fails with the
+    /// merged size when it's more than the format's counts can hold, see [`MergeSize::fits`].
+    pub fn merge(models: &[ModelUnlit]) -> Result<Self, MergeSize> {
+        let size = MergeSize::of(models);
+        if !size.fits() {
+            return Err(size);
+        }
+        let mut triangle_count = size.triangle_count;
+        let mut textured_triangle_count = size.textured_triangle_count;
 
         let mut priority: Option<u8> = None;
 
@@ -152,10 +249,6 @@ impl ModelUnlit {
         let mut has_maya_group = false;
 
         for model in models {
-            vertex_count += model.vertex_count;
-            triangle_count += model.triangle_count;
-            textured_triangle_count += model.textured_triangle_count;
-
             if *priority.get_or_insert(model.priority) != model.priority {
                 has_priority = true;
             }
@@ -168,13 +261,14 @@ impl ModelUnlit {
             has_maya_group |= model.anim_maya_props.is_some();
         }
 
+        let vertex_count = size.vertex_count as usize;
         let mut vertices = ModelMergeVertices {
             vertex_count: 0,
-            vertex_x: vec![0; vertex_count as usize],
-            vertex_y: vec![0; vertex_count as usize],
-            vertex_z: vec![0; vertex_count as usize],
-            vertex_model_index_flags: vec![0; vertex_count as usize],
-            vertex_skins: vec![0; vertex_count as usize],
+            vertex_x: vec![0; vertex_count],
+            vertex_y: vec![0; vertex_count],
+            vertex_z: vec![0; vertex_count],
+            vertex_model_index_flags: vec![0; vertex_count],
+            vertex_skins: vec![0; vertex_count],
         };
 
         let mut triangle_a = vec![0u16; triangle_count as usize];
@@ -216,7 +310,7 @@ impl ModelUnlit {
 
         triangle_count = 0;
         for (index, model) in models.iter().enumerate() {
-            let index_flag = 1 << index;
+            let index_flag = Self::model_index_flag(index);
             let start_triangle_count = triangle_count as usize;
             Self::copy_priorities(start_triangle_count, triangle_priority.as_mut(), model);
             Self::copy_render_types(start_triangle_count, triangle_render_type.as_mut(), model);
@@ -263,7 +357,7 @@ impl ModelUnlit {
         textured_triangle_count = 0;
 
         for (index, model) in models.iter().enumerate() {
-            let index_flag = 1 << index;
+            let index_flag = Self::model_index_flag(index);
             Self::copy_texture_coords(
                 textured_triangle_count,
                 &mut tex_coord_count,
@@ -297,7 +391,8 @@ impl ModelUnlit {
             }
         }
 
-        vertex_count = vertices.vertex_count;
+        // Every count fits in 16 bits, merging only ever removes duplicate vertices.
+        let vertex_count = vertices.vertex_count as u16;
 
         let texture_props = mat_triangles.map(|triangles| ModelTextureMappingProps {
             render_types: triangles.render_types,
@@ -306,12 +401,12 @@ impl ModelUnlit {
             mapping_n: triangles.mapping_n,
         });
 
-        Self {
+        Ok(Self {
             version: Self::VERSION,
             vertex_count,
-            used_vertex_count,
-            triangle_count,
-            textured_triangle_count,
+            used_vertex_count: used_vertex_count as u16,
+            triangle_count: triangle_count as u16,
+            textured_triangle_count: textured_triangle_count as u16,
             priority: priority.unwrap_or(0),
             vertex_x: Arc::new(vertices.vertex_x),
             vertex_y: Arc::new(vertices.vertex_y),
@@ -330,7 +425,13 @@ impl ModelUnlit {
             vertex_skins: Some(vertices.vertex_skins),
             triangle_skins,
             anim_maya_props: None,
-        }
+            bounds: None,
+        })
+    }
+
+    /// The bit marking what came from the model at `index`, only the first 16 models get one.
+    fn model_index_flag(index: usize) -> u16 {
+        1u16.checked_shl(index as u32).unwrap_or(0)
     }
 
     fn copy_priorities(
@@ -423,7 +524,7 @@ impl ModelUnlit {
     }
 
     fn copy_texture_coords(
-        textured_triangle_count: u16,
+        textured_triangle_count: u32,
         tex_coord_count: &mut usize,
         dst_coords: Option<&mut Vec<i16>>,
         model: &ModelUnlit,
@@ -437,7 +538,8 @@ impl ModelUnlit {
         for t in 0..model.triangle_count as usize {
             let coord = src_coords[t];
             if coord >= 0 && coord < 32766 {
-                dst_coords[*tex_coord_count] = textured_triangle_count as i16 + coord;
+                // Below 32766 as long as the merged size fits.
+                dst_coords[*tex_coord_count] = (textured_triangle_count + coord as u32) as i16;
             } else {
                 dst_coords[*tex_coord_count] = coord;
             }
@@ -478,57 +580,82 @@ impl ModelUnlit {
         dst_index as u16
     }
 
+    /// None while the group is still being fetched. Models that fail to decode are logged and
+    /// come back empty, so callers waiting for the fetch don't wait on them forever.
     pub fn from_js5(js5: &Js5, group_id: u32, file_id: u32) -> Option<Self> {
         let data = js5.get_file(group_id, file_id)?;
-        Some(Self::from_data(&data))
+        Some(Self::from_data(&data).unwrap_or_else(|err| {
+            log::warn!("Model {group_id} couldn't be decoded: {err}");
+            Self::new()
+        }))
     }
 
-    pub fn from_data(data: &[u8]) -> Self {
+    pub fn from_data(data: &[u8]) -> Result<Self, Runetek5Error> {
         let mut model = Self::new();
-        model.decode(data);
-        model
+        model.decode(data)?;
+        Ok(model)
     }
 
-    pub fn decode(&mut self, data: &[u8]) {
-        let mut version_buf = &data[data.len() - 2..];
-        let version = 65536 - version_buf.g2() as u32;
-        match version {
-            3 => {
-                self.decode_v1_maya(data);
-            }
-            2 => {
-                self.decode_v0_maya(data);
-            }
-            1 => {
-                self.decode_v1(data);
-            }
-            _ => {
-                self.decode_v0(data);
-            }
+    /// Fails with [`Runetek5Error::Invalid`] if no format's footer fits the data, and with
+    /// [`Runetek5Error::Truncated`] naming the first section that runs into the footer.
+    pub fn decode(&mut self, data: &[u8]) -> Result<(), Runetek5Error> {
+        let _span = tracing::info_span!("model_decode", len = data.len()).entered();
+        self.decode_with_sections(data, &mut SectionRecorder::disabled())
+    }
+
+    /// Decodes the model while recording the byte range of each section of the format, sorted
+    /// by offset. Bytes not covered by a known section are reported as unaccounted, which on
+    /// failure includes everything from the section that didn't fit on.
+    pub fn decode_annotated(
+        &mut self,
+        data: &[u8],
+    ) -> (Vec<ModelSection>, Result<(), Runetek5Error>) {
+        let mut sections = SectionRecorder::enabled();
+        let result = self.decode_with_sections(data, &mut sections);
+        (sections.finish(data.len()), result)
+    }
+
+    fn decode_with_sections(
+        &mut self,
+        data: &[u8],
+        sections: &mut SectionRecorder,
+    ) -> Result<(), Runetek5Error> {
+        self.bounds = None;
+        let format = ModelFormat::detect(data).ok_or(Runetek5Error::Invalid("model footer"))?;
+        match format {
+            ModelFormat::V1Maya => self.decode_v1_maya(data, sections),
+            ModelFormat::V0Maya => self.decode_v0_maya(data, sections),
+            ModelFormat::V1 => self.decode_v1(data, sections),
+            ModelFormat::V0 => self.decode_v0(data, sections),
         }
     }
 
-    fn decode_v0(&mut self, data: &[u8]) {
+    fn decode_v0(
+        &mut self,
+        data: &[u8],
+        sections: &mut SectionRecorder,
+    ) -> Result<(), Runetek5Error> {
+        const FOOTER: Runetek5Error = Runetek5Error::Truncated("model footer");
         // println!("v0");
         let mut buf1 = data;
         let mut buf2 = data;
         let mut buf3 = data;
         let mut buf4 = data;
         let mut buf5 = data;
-        buf1 = &data[(data.len() - 18)..];
-        let vertex_count = buf1.g2() as usize;
-        let triangle_count = buf1.g2() as usize;
-        let textured_triangle_count = buf1.g1() as usize;
-        let has_textures = buf1.g1() == 1;
-        let priority = buf1.g1();
+        buf1 = &data[data.len().saturating_sub(18)..];
+        let vertex_count = buf1.try_g2().ok_or(FOOTER)? as usize;
+        let triangle_count = buf1.try_g2().ok_or(FOOTER)? as usize;
+        let textured_triangle_count = buf1.try_g1().ok_or(FOOTER)? as usize;
+        let has_textures = buf1.try_g1().ok_or(FOOTER)? == 1;
+        let priority = buf1.try_g1().ok_or(FOOTER)?;
         let has_priorities = priority == 255;
-        let has_transparencies = buf1.g1() == 1;
-        let has_triangle_skins = buf1.g1() == 1;
-        let has_vertex_skins = buf1.g1() == 1;
-        let vertex_x_count = buf1.g2() as usize;
-        let vertex_y_count = buf1.g2() as usize;
-        let vertex_z_count = buf1.g2() as usize;
-        let index_count = buf1.g2() as usize;
+        let has_transparencies = buf1.try_g1().ok_or(FOOTER)? == 1;
+        let has_triangle_skins = buf1.try_g1().ok_or(FOOTER)? == 1;
+        let has_vertex_skins = buf1.try_g1().ok_or(FOOTER)? == 1;
+        let vertex_x_count = buf1.try_g2().ok_or(FOOTER)? as usize;
+        let vertex_y_count = buf1.try_g2().ok_or(FOOTER)? as usize;
+        let vertex_z_count = buf1.try_g2().ok_or(FOOTER)? as usize;
+        let index_count = buf1.try_g2().ok_or(FOOTER)? as usize;
         let vertex_flags_offset = 0;
         let mut offset = vertex_flags_offset + vertex_count;
         let index_types_offset = offset;
@@ -566,6 +693,26 @@ impl ModelUnlit {
         let vertex_z_offset = offset;
         offset += vertex_z_count;
 
+        sections.record_footer("Footer", data.len() - 18, data.len());
+        sections.limit(data.len() - 18);
+        sections.record("Vertex flags", vertex_flags_offset, index_types_offset)?;
+        sections.record("Index types", index_types_offset, priorities_offset)?;
+        sections.record("Priorities", priorities_offset, triangle_skins_offset)?;
+        sections.record(
+            "Triangle skins",
+            triangle_skins_offset,
+            texture_flags_offset,
+        )?;
+        sections.record("Texture flags", texture_flags_offset, vertex_skins_offset)?;
+        sections.record("Vertex skins", vertex_skins_offset, transparencies_offset)?;
+        sections.record("Transparencies", transparencies_offset, indices_offset)?;
+        sections.record("Indices", indices_offset, colours_offset)?;
+        sections.record("Colours", colours_offset, texture_mapping_offset)?;
+        sections.record("Texture mapping", texture_mapping_offset, vertex_x_offset)?;
+        sections.record("Vertex x", vertex_x_offset, vertex_y_offset)?;
+        sections.record("Vertex y", vertex_y_offset, vertex_z_offset)?;
+        sections.record("Vertex z", vertex_z_offset, offset)?;
+
         self.vertex_count = vertex_count as u16;
         self.triangle_count = triangle_count as u16;
         self.textured_triangle_count = textured_triangle_count as u16;
@@ -626,7 +773,7 @@ impl ModelUnlit {
             &mut buf3,
             &mut buf4,
             &mut buf5,
-        );
+        )?;
 
         buf1 = &data[colours_offset..];
         buf2 = &data[texture_flags_offset..];
@@ -645,53 +792,380 @@ impl ModelUnlit {
             &mut buf3,
             &mut buf4,
             &mut buf5,
-        );
+        )?;
 
         buf1 = &data[indices_offset..];
         buf2 = &data[index_types_offset..];
 
-        self.decode_indices(triangle_count, &mut buf1, &mut buf2);
+        self.decode_indices(triangle_count, &mut buf1, &mut buf2)?;
 
         buf1 = &data[texture_mapping_offset..];
 
-        self.decode_texture_mapping(textured_triangle_count, &mut buf1);
+        self.decode_texture_mapping(textured_triangle_count, &mut buf1)?;
+        Ok(())
     }
 
-    fn decode_v1(&mut self, data: &[u8]) {
-        // println!("v1");
-        let mut buf1 = data;
-        let buf2 = data;
-        let buf3 = data;
-        let buf4 = data;
-        let buf5 = data;
-        let buf6 = data;
-        let buf7 = data;
-        buf1.skip(data.len() - 23);
+    /// Decodes the newer format with a 23-byte footer. Its flags byte marks which optional
+    /// blocks are present: 0x1 triangle render types, 0x2 particle emitters and effectors, 0x4
+    /// billboards, 0x8 a version byte just before the footer and 0x10 vertex skins stored as
+    /// smarts. Particles and billboards follow the texture mapping tables and aren't decoded.
+    fn decode_v1(
+        &mut self,
+        data: &[u8],
+        sections: &mut SectionRecorder,
+    ) -> Result<(), Runetek5Error> {
+        const FOOTER: Runetek5Error = Runetek5Error::Truncated("model footer");
+        let mut buf1 = &data[data.len().saturating_sub(23)..];
+        let vertex_count = buf1.try_g2().ok_or(FOOTER)? as usize;
+        let triangle_count = buf1.try_g2().ok_or(FOOTER)? as usize;
+        let textured_triangle_count = buf1.try_g1().ok_or(FOOTER)? as usize;
+        let flags = buf1.try_g1().ok_or(FOOTER)?;
+        let has_triangle_render_types = flags & 0x1 != 0;
+        let has_version = flags & 0x8 != 0;
+        let has_extended_vertex_skins = flags & 0x10 != 0;
+        let priority = buf1.try_g1().ok_or(FOOTER)?;
+        let has_priorities = priority == 255;
+        let has_transparencies = buf1.try_g1().ok_or(FOOTER)? == 1;
+        let has_triangle_skins = buf1.try_g1().ok_or(FOOTER)? == 1;
+        let has_textures = buf1.try_g1().ok_or(FOOTER)? == 1;
+        let has_vertex_skins = buf1.try_g1().ok_or(FOOTER)? == 1;
+        let vertex_x_count = buf1.try_g2().ok_or(FOOTER)? as usize;
+        let vertex_y_count = buf1.try_g2().ok_or(FOOTER)? as usize;
+        let vertex_z_count = buf1.try_g2().ok_or(FOOTER)? as usize;
+        let index_count = buf1.try_g2().ok_or(FOOTER)? as usize;
+        let texture_coords_size = buf1.try_g2().ok_or(FOOTER)? as usize;
+        let footer_offset = if has_version {
+            let offset = data.len().checked_sub(24).ok_or(FOOTER)?;
+            self.version = data[offset];
+            offset
+        } else {
+            data.len() - 23
+        };
+
+        if textured_triangle_count > 0 {
+            self.texture_props = Some(ModelTextureMappingProps::new(textured_triangle_count));
+        }
+
+        let (
+            simple_texture_triangle_count,
+            complex_texture_triangle_count,
+            cube_texture_triangle_count,
+        ) = self.decode_texture_render_types(textured_triangle_count, data)?;
+
+        let mut offset = textured_triangle_count;
+        let vertex_flags_offset = offset;
+        offset += vertex_count;
+        let triangle_render_types_offset = offset;
+        if has_triangle_render_types {
+            offset += triangle_count;
+        }
+        let index_types_offset = offset;
+        offset += triangle_count;
+        let priorities_offset = offset;
+        if has_priorities {
+            offset += triangle_count;
+        }
+        let triangle_skins_offset = offset;
+        if has_triangle_skins {
+            offset += triangle_count;
+        }
+        let vertex_skins_offset = offset;
+        if has_vertex_skins {
+            offset += if has_extended_vertex_skins {
+                data.get(offset..)
+                    .map_or(0, |skins| smarts_size(skins, vertex_count))
+            } else {
+                vertex_count
+            };
+        }
+        let transparencies_offset = offset;
+        if has_transparencies {
+            offset += triangle_count;
+        }
+        let indices_offset = offset;
+        offset += index_count;
+        let textures_offset = offset;
+        if has_textures {
+            offset += triangle_count * 2;
+        }
+        let texture_coords_offset = offset;
+        offset += texture_coords_size;
+        let colours_offset = offset;
+        offset += triangle_count * 2;
+        let vertex_x_offset = offset;
+        offset += vertex_x_count;
+        let vertex_y_offset = offset;
+        offset += vertex_y_count;
+        let vertex_z_offset = offset;
+        offset += vertex_z_count;
+        let simple_textures_offset = offset;
+        offset += simple_texture_triangle_count * 6;
+        let complex_textures_offset = offset;
+        offset += complex_texture_triangle_count * 6;
+        let texture_scales_offset = offset;
+        offset += complex_texture_triangle_count * Self::texture_scales_size(self.version);
+        let texture_rotations_offset = offset;
+        offset += complex_texture_triangle_count;
+        let texture_directions_offset = offset;
+        offset += complex_texture_triangle_count;
+        let texture_translations_offset = offset;
+        offset += complex_texture_triangle_count + cube_texture_triangle_count * 2;
+
+        if has_version {
+            sections.record_footer("Version", footer_offset, data.len() - 23);
+        }
+        sections.record_footer("Footer", data.len() - 23, data.len());
+        sections.limit(footer_offset);
+        sections.record("Texture render types", 0, vertex_flags_offset)?;
+        sections.record(
+            "Vertex flags",
+            vertex_flags_offset,
+            triangle_render_types_offset,
+        )?;
+        sections.record(
+            "Triangle render types",
+            triangle_render_types_offset,
+            index_types_offset,
+        )?;
+        sections.record("Index types", index_types_offset, priorities_offset)?;
+        sections.record("Priorities", priorities_offset, triangle_skins_offset)?;
+        sections.record("Triangle skins", triangle_skins_offset, vertex_skins_offset)?;
+        sections.record("Vertex skins", vertex_skins_offset, transparencies_offset)?;
+        sections.record("Transparencies", transparencies_offset, indices_offset)?;
+        sections.record("Indices", indices_offset, textures_offset)?;
+        sections.record("Materials", textures_offset, texture_coords_offset)?;
+        sections.record("Texture coords", texture_coords_offset, colours_offset)?;
+        sections.record("Colours", colours_offset, vertex_x_offset)?;
+        sections.record("Vertex x", vertex_x_offset, vertex_y_offset)?;
+        sections.record("Vertex y", vertex_y_offset, vertex_z_offset)?;
+        sections.record("Vertex z", vertex_z_offset, simple_textures_offset)?;
+        sections.record(
+            "Simple textures",
+            simple_textures_offset,
+            complex_textures_offset,
+        )?;
+        sections.record(
+            "Complex textures",
+            complex_textures_offset,
+            texture_scales_offset,
+        )?;
+        sections.record(
+            "Texture scales",
+            texture_scales_offset,
+            texture_rotations_offset,
+        )?;
+        sections.record(
+            "Texture rotations",
+            texture_rotations_offset,
+            texture_directions_offset,
+        )?;
+        sections.record(
+            "Texture directions",
+            texture_directions_offset,
+            texture_translations_offset,
+        )?;
+        sections.record("Texture translations", texture_translations_offset, offset)?;
+        if flags & 0x6 != 0 {
+            sections.record("Particles and billboards", offset, footer_offset)?;
+        }
+
+        self.vertex_count = vertex_count as u16;
+        self.triangle_count = triangle_count as u16;
+        self.textured_triangle_count = textured_triangle_count as u16;
+        self.vertex_x = Arc::new(vec![0; vertex_count]);
+        self.vertex_y = Arc::new(vec![0; vertex_count]);
+        self.vertex_z = Arc::new(vec![0; vertex_count]);
+        self.triangle_a = vec![0; triangle_count];
+        self.triangle_b = vec![0; triangle_count];
+        self.triangle_c = vec![0; triangle_count];
+
+        self.triangle_colour = vec![0; triangle_count];
+
+        if has_vertex_skins {
+            self.vertex_skins = Some(vec![0; vertex_count]);
+        }
+        if has_triangle_render_types {
+            self.triangle_render_type = Some(vec![0; triangle_count]);
+        }
+        if has_priorities {
+            self.triangle_priority = Some(vec![0; triangle_count]);
+        } else {
+            self.priority = priority;
+        }
+        if has_transparencies {
+            self.triangle_transparency = Some(vec![0; triangle_count]);
+        }
+        if has_triangle_skins {
+            self.triangle_skins = Some(vec![0; triangle_count]);
+        }
+        if has_textures {
+            self.triangle_material = Some(vec![0; triangle_count]);
+            if textured_triangle_count > 0 {
+                self.triangle_texture_coords = Some(vec![0; triangle_count]);
+            }
+        }
+        if complex_texture_triangle_count > 0 {
+            self.texture_complex_props = Some(ModelComplexTextureMappingProps::new(
+                textured_triangle_count,
+            ));
+        }
+
+        buf1 = &data[vertex_flags_offset..];
+        let mut buf2 = &data[vertex_x_offset..];
+        let mut buf3 = &data[vertex_y_offset..];
+        let mut buf4 = &data[vertex_z_offset..];
+        let mut buf5 = &data[vertex_skins_offset..];
+
+        self.decode_vertices(
+            vertex_count,
+            has_vertex_skins,
+            has_extended_vertex_skins,
+            false,
+            &mut buf1,
+            &mut buf2,
+            &mut buf3,
+            &mut buf4,
+            &mut buf5,
+        )?;
+
+        buf1 = &data[colours_offset..];
+        buf2 = &data[triangle_render_types_offset..];
+        buf3 = &data[priorities_offset..];
+        buf4 = &data[transparencies_offset..];
+        buf5 = &data[triangle_skins_offset..];
+        let mut buf6 = &data[textures_offset..];
+        let mut buf7 = &data[texture_coords_offset..];
+
+        self.decode_triangles_v1(
+            triangle_count,
+            has_triangle_render_types,
+            has_priorities,
+            has_transparencies,
+            has_triangle_skins,
+            has_textures,
+            &mut buf1,
+            &mut buf2,
+            &mut buf3,
+            &mut buf4,
+            &mut buf5,
+            &mut buf6,
+            &mut buf7,
+        )?;
+
+        buf1 = &data[indices_offset..];
+        buf2 = &data[index_types_offset..];
+
+        self.decode_indices(triangle_count, &mut buf1, &mut buf2)?;
+
+        buf1 = &data[simple_textures_offset..];
+        buf2 = &data[complex_textures_offset..];
+        buf3 = &data[texture_scales_offset..];
+        buf4 = &data[texture_rotations_offset..];
+        buf5 = &data[texture_directions_offset..];
+        buf6 = &data[texture_translations_offset..];
+
+        self.decode_texture_mapping_v1(
+            textured_triangle_count,
+            &mut buf1,
+            &mut buf2,
+            &mut buf3,
+            &mut buf4,
+            &mut buf5,
+            &mut buf6,
+        )?;
+        self.decode_complex_texture_mapping(
+            textured_triangle_count,
+            &mut buf3,
+            &mut buf4,
+            &mut buf5,
+            &mut buf6,
+        )?;
+        Ok(())
+    }
+
+    /// Bytes per complex textured triangle in the texture scales table, which widened from three
+    /// shorts to medium ints in versions 14 and 15.
+    fn texture_scales_size(version: u8) -> usize {
+        match version {
+            ..=13 => 6,
+            14 => 7,
+            _ => 9,
+        }
+    }
+
+    /// Reads the scale, rotation, direction and speed of complex (planar, cylindrical and cube)
+    /// texture mappings. Cube mappings are followed by a texture translation, which isn't kept.
+    fn decode_complex_texture_mapping(
+        &mut self,
+        textured_triangle_count: usize,
+        scales_buf: &mut &[u8],
+        rotation_buf: &mut &[u8],
+        direction_buf: &mut &[u8],
+        translation_buf: &mut &[u8],
+    ) -> Result<(), Runetek5Error> {
+        const TRUNCATED: Runetek5Error = Runetek5Error::Truncated("model texture mapping");
+        let (Some(texture_props), Some(complex_props)) = (
+            self.texture_props.as_ref(),
+            self.texture_complex_props.as_mut(),
+        ) else {
+            return Ok(());
+        };
+        for i in 0..textured_triangle_count {
+            let texture_render_type = texture_props.render_types[i];
+            if !(1..=3).contains(&texture_render_type) {
+                continue;
+            }
+            if self.version < 15 {
+                complex_props.scale_x[i] = scales_buf.try_g2().ok_or(TRUNCATED)? as i32;
+                complex_props.scale_y[i] = if self.version < 14 {
+                    scales_buf.try_g2().ok_or(TRUNCATED)? as i32
+                } else {
+                    scales_buf.try_g3().ok_or(TRUNCATED)? as i32
+                };
+                complex_props.scale_z[i] = scales_buf.try_g2().ok_or(TRUNCATED)? as i32;
+            } else {
+                complex_props.scale_x[i] = scales_buf.try_g3().ok_or(TRUNCATED)? as i32;
+                complex_props.scale_y[i] = scales_buf.try_g3().ok_or(TRUNCATED)? as i32;
+                complex_props.scale_z[i] = scales_buf.try_g3().ok_or(TRUNCATED)? as i32;
+            }
+            complex_props.rotation[i] = rotation_buf.try_g1s().ok_or(TRUNCATED)?;
+            complex_props.direction[i] = direction_buf.try_g1s().ok_or(TRUNCATED)?;
+            complex_props.speed[i] = translation_buf.try_g1s().ok_or(TRUNCATED)?;
+            if texture_render_type == 2 {
+                translation_buf.try_skip(2).ok_or(TRUNCATED)?;
+            }
+        }
+        Ok(())
     }
 
-    fn decode_v0_maya(&mut self, data: &[u8]) {
+    fn decode_v0_maya(
+        &mut self,
+        data: &[u8],
+        sections: &mut SectionRecorder,
+    ) -> Result<(), Runetek5Error> {
+        const FOOTER: Runetek5Error = Runetek5Error::Truncated("model footer");
         // println!("v2");
         let mut buf1 = data;
         let mut buf2 = data;
         let mut buf3 = data;
         let mut buf4 = data;
         let mut buf5 = data;
-        buf1 = &data[(data.len() - 23)..];
-        let vertex_count = buf1.g2() as usize;
-        let triangle_count = buf1.g2() as usize;
-        let textured_triangle_count = buf1.g1() as usize;
-        let has_textures = buf1.g1() == 1;
-        let priority = buf1.g1();
+        buf1 = &data[data.len().saturating_sub(23)..];
+        let vertex_count = buf1.try_g2().ok_or(FOOTER)? as usize;
+        let triangle_count = buf1.try_g2().ok_or(FOOTER)? as usize;
+        let textured_triangle_count = buf1.try_g1().ok_or(FOOTER)? as usize;
+        let has_textures = buf1.try_g1().ok_or(FOOTER)? == 1;
+        let priority = buf1.try_g1().ok_or(FOOTER)?;
         let has_priorities = priority == 255;
-        let has_transparencies = buf1.g1() == 1;
-        let has_triangle_skins = buf1.g1() == 1;
-        let has_vertex_skins = buf1.g1() == 1;
-        let has_maya_groups = buf1.g1() == 1;
-        let vertex_x_count = buf1.g2() as usize;
-        let vertex_y_count = buf1.g2() as usize;
-        let vertex_z_count = buf1.g2() as usize;
-        let index_count = buf1.g2() as usize;
-        let vertex_skins_size = buf1.g2() as usize;
+        let has_transparencies = buf1.try_g1().ok_or(FOOTER)? == 1;
+        let has_triangle_skins = buf1.try_g1().ok_or(FOOTER)? == 1;
+        let has_vertex_skins = buf1.try_g1().ok_or(FOOTER)? == 1;
+        let has_maya_groups = buf1.try_g1().ok_or(FOOTER)? == 1;
+        let vertex_x_count = buf1.try_g2().ok_or(FOOTER)? as usize;
+        let vertex_y_count = buf1.try_g2().ok_or(FOOTER)? as usize;
+        let vertex_z_count = buf1.try_g2().ok_or(FOOTER)? as usize;
+        let index_count = buf1.try_g2().ok_or(FOOTER)? as usize;
+        let vertex_skins_size = buf1.try_g2().ok_or(FOOTER)? as usize;
         let vertex_flags_offset = 0;
         let mut offset = vertex_flags_offset + vertex_count;
         let index_types_offset = offset;
@@ -727,6 +1201,26 @@ impl ModelUnlit {
         let vertex_z_offset = offset;
         offset += vertex_z_count;
 
+        sections.record_footer("Footer", data.len() - 23, data.len());
+        sections.limit(data.len() - 23);
+        sections.record("Vertex flags", vertex_flags_offset, index_types_offset)?;
+        sections.record("Index types", index_types_offset, priorities_offset)?;
+        sections.record("Priorities", priorities_offset, triangle_skins_offset)?;
+        sections.record(
+            "Triangle skins",
+            triangle_skins_offset,
+            texture_flags_offset,
+        )?;
+        sections.record("Texture flags", texture_flags_offset, vertex_skins_offset)?;
+        sections.record("Vertex skins", vertex_skins_offset, transparencies_offset)?;
+        sections.record("Transparencies", transparencies_offset, indices_offset)?;
+        sections.record("Indices", indices_offset, colours_offset)?;
+        sections.record("Colours", colours_offset, texture_mapping_offset)?;
+        sections.record("Texture mapping", texture_mapping_offset, vertex_x_offset)?;
+        sections.record("Vertex x", vertex_x_offset, vertex_y_offset)?;
+        sections.record("Vertex y", vertex_y_offset, vertex_z_offset)?;
+        sections.record("Vertex z", vertex_z_offset, offset)?;
+
         self.vertex_count = vertex_count as u16;
         self.triangle_count = triangle_count as u16;
         self.textured_triangle_count = textured_triangle_count as u16;
@@ -785,7 +1279,7 @@ impl ModelUnlit {
             &mut buf3,
             &mut buf4,
             &mut buf5,
-        );
+        )?;
 
         buf1 = &data[colours_offset..];
         buf2 = &data[texture_flags_offset..];
@@ -804,16 +1298,17 @@ impl ModelUnlit {
             &mut buf3,
             &mut buf4,
             &mut buf5,
-        );
+        )?;
 
         buf1 = &data[indices_offset..];
         buf2 = &data[index_types_offset..];
 
-        self.decode_indices(triangle_count, &mut buf1, &mut buf2);
+        self.decode_indices(triangle_count, &mut buf1, &mut buf2)?;
 
         buf1 = &data[texture_mapping_offset..];
 
-        self.decode_texture_mapping(textured_triangle_count, &mut buf1);
+        self.decode_texture_mapping(textured_triangle_count, &mut buf1)?;
+        Ok(())
     }
 
     fn decode_vertices(
@@ -827,7 +1322,8 @@ impl ModelUnlit {
         vertex_y_buf: &mut &[u8],
         vertex_z_buf: &mut &[u8],
         vertex_skins_buf: &mut &[u8],
-    ) {
+    ) -> Result<(), Runetek5Error> {
+        const TRUNCATED: Runetek5Error = Runetek5Error::Truncated("model vertices");
         let vertex_x = Arc::get_mut(&mut self.vertex_x).unwrap();
         let vertex_y = Arc::get_mut(&mut self.vertex_y).unwrap();
         let vertex_z = Arc::get_mut(&mut self.vertex_z).unwrap();
@@ -836,19 +1332,19 @@ impl ModelUnlit {
         let mut last_y = 0;
         let mut last_z = 0;
         for i in 0..vertex_count {
-            let flags = vertex_flags_buf.g1();
+            let flags = vertex_flags_buf.try_g1().ok_or(TRUNCATED)?;
             let delta_x = if flags & 0x1 != 0 {
-                vertex_x_buf.get_smart_1_or_2s()
+                vertex_x_buf.try_get_smart_1_or_2s().ok_or(TRUNCATED)?
             } else {
                 0
             };
             let delta_y = if flags & 0x2 != 0 {
-                vertex_y_buf.get_smart_1_or_2s()
+                vertex_y_buf.try_get_smart_1_or_2s().ok_or(TRUNCATED)?
             } else {
                 0
             };
             let delta_z = if flags & 0x4 != 0 {
-                vertex_z_buf.get_smart_1_or_2s()
+                vertex_z_buf.try_get_smart_1_or_2s().ok_or(TRUNCATED)?
             } else {
                 0
             };
@@ -864,9 +1360,11 @@ impl ModelUnlit {
             let vertex_skins = self.vertex_skins.as_mut().unwrap();
             for i in 0..vertex_count {
                 let v = if has_extended_vertex_skins {
-                    vertex_skins_buf.get_smart_1_or_2_null()
+                    vertex_skins_buf
+                        .try_get_smart_1_or_2_null()
+                        .ok_or(TRUNCATED)?
                 } else {
-                    match vertex_skins_buf.g1() {
+                    match vertex_skins_buf.try_g1().ok_or(TRUNCATED)? {
                         255 => -1,
                         n => n as i32,
                     }
@@ -878,20 +1376,21 @@ impl ModelUnlit {
         if has_maya_groups {
             let anim_maya_props = self.anim_maya_props.as_mut().unwrap();
             for _ in 0..vertex_count {
-                let count = vertex_skins_buf.g1() as usize;
+                let count = vertex_skins_buf.try_g1().ok_or(TRUNCATED)? as usize;
 
                 let mut maya_groups = vec![0; count as usize];
                 let mut maya_scales = vec![0; count as usize];
 
                 for i in 0..count {
-                    maya_groups[i] = vertex_skins_buf.g1();
-                    maya_scales[i] = vertex_skins_buf.g1();
+                    maya_groups[i] = vertex_skins_buf.try_g1().ok_or(TRUNCATED)?;
+                    maya_scales[i] = vertex_skins_buf.try_g1().ok_or(TRUNCATED)?;
                 }
 
                 anim_maya_props.groups.push(maya_groups);
                 anim_maya_props.scales.push(maya_scales);
             }
         }
+        Ok(())
     }
 
     fn decode_triangles(
@@ -906,16 +1405,17 @@ impl ModelUnlit {
         priority_buf: &mut &[u8],
         transparency_buf: &mut &[u8],
         triangle_skin_buf: &mut &[u8],
-    ) {
+    ) -> Result<(), Runetek5Error> {
+        const TRUNCATED: Runetek5Error = Runetek5Error::Truncated("model triangles");
         for i in 0..triangle_count {
-            self.triangle_colour[i] = colour_buf.g2();
+            self.triangle_colour[i] = colour_buf.try_g2().ok_or(TRUNCATED)?;
         }
         if has_textures {
             let triangle_render_types = self.triangle_render_type.as_mut().unwrap();
             let triangle_textures = self.triangle_material.as_mut().unwrap();
             let triangle_texture_coords = self.triangle_texture_coords.as_mut().unwrap();
             for i in 0..triangle_count {
-                let flags = texture_flag_buf.g1();
+                let flags = texture_flag_buf.try_g1().ok_or(TRUNCATED)?;
                 if flags & 0x1 != 0 {
                     triangle_render_types[i] = 1;
                 } else {
@@ -935,21 +1435,22 @@ impl ModelUnlit {
         if has_priorities {
             let triangle_priorities = self.triangle_priority.as_mut().unwrap();
             for i in 0..triangle_count {
-                triangle_priorities[i] = priority_buf.g1();
+                triangle_priorities[i] = priority_buf.try_g1().ok_or(TRUNCATED)?;
             }
         }
         if has_transparencies {
             let triangle_transparencies = self.triangle_transparency.as_mut().unwrap();
             for i in 0..triangle_count {
-                triangle_transparencies[i] = transparency_buf.g1();
+                triangle_transparencies[i] = transparency_buf.try_g1().ok_or(TRUNCATED)?;
             }
         }
         if has_triangle_skins {
             let triangle_skins = self.triangle_skins.as_mut().unwrap();
             for i in 0..triangle_count {
-                triangle_skins[i] = triangle_skin_buf.g1() as i32;
+                triangle_skins[i] = triangle_skin_buf.try_g1().ok_or(TRUNCATED)? as i32;
             }
         }
+        Ok(())
     }
 
     fn decode_indices(
@@ -957,7 +1458,8 @@ impl ModelUnlit {
         triangle_count: usize,
         index_buf: &mut &[u8],
         index_type_buf: &mut &[u8],
-    ) {
+    ) -> Result<(), Runetek5Error> {
+        const TRUNCATED: Runetek5Error = Runetek5Error::Truncated("model indices");
         let mut a = 0;
         let mut b = 0;
         let mut c = 0;
@@ -965,12 +1467,12 @@ impl ModelUnlit {
 
         let mut used_vertex_count = -1;
         for i in 0..triangle_count {
-            let index_type = index_type_buf.g1();
+            let index_type = index_type_buf.try_g1().ok_or(TRUNCATED)?;
             match index_type {
                 1 => {
-                    a = index_buf.get_smart_1_or_2s() + last_index;
-                    b = index_buf.get_smart_1_or_2s() + a;
-                    c = index_buf.get_smart_1_or_2s() + b;
+                    a = index_buf.try_get_smart_1_or_2s().ok_or(TRUNCATED)? + last_index;
+                    b = index_buf.try_get_smart_1_or_2s().ok_or(TRUNCATED)? + a;
+                    c = index_buf.try_get_smart_1_or_2s().ok_or(TRUNCATED)? + b;
                     last_index = c;
                     self.triangle_a[i] = a as u16;
                     self.triangle_b[i] = b as u16;
@@ -987,7 +1489,7 @@ impl ModelUnlit {
                 }
                 2 => {
                     b = c;
-                    c = index_buf.get_smart_1_or_2s() + last_index;
+                    c = index_buf.try_get_smart_1_or_2s().ok_or(TRUNCATED)? + last_index;
                     last_index = c;
                     self.triangle_a[i] = a as u16;
                     self.triangle_b[i] = b as u16;
@@ -998,7 +1500,7 @@ impl ModelUnlit {
                 }
                 3 => {
                     a = c;
-                    c = index_buf.get_smart_1_or_2s() + last_index;
+                    c = index_buf.try_get_smart_1_or_2s().ok_or(TRUNCATED)? + last_index;
                     last_index = c;
                     self.triangle_a[i] = a as u16;
                     self.triangle_b[i] = b as u16;
@@ -1011,7 +1513,7 @@ impl ModelUnlit {
                     let temp = a;
                     a = b;
                     b = temp;
-                    c = index_buf.get_smart_1_or_2s() + last_index;
+                    c = index_buf.try_get_smart_1_or_2s().ok_or(TRUNCATED)? + last_index;
                     last_index = c;
                     self.triangle_a[i] = a as u16;
                     self.triangle_b[i] = temp as u16;
@@ -1026,25 +1528,33 @@ impl ModelUnlit {
         used_vertex_count += 1;
 
         self.used_vertex_count = used_vertex_count as u16;
+        Ok(())
     }
 
     fn decode_texture_mapping(
         &mut self,
         textured_triangle_count: usize,
         texture_mapping_buf: &mut &[u8],
-    ) {
+    ) -> Result<(), Runetek5Error> {
+        const TRUNCATED: Runetek5Error = Runetek5Error::Truncated("model texture mapping");
         if textured_triangle_count > 0 {
             let texture_props = self.texture_props.as_mut().unwrap();
             for i in 0..textured_triangle_count {
                 texture_props.render_types[i] = 0;
-                texture_props.mapping_p[i] = texture_mapping_buf.g2();
-                texture_props.mapping_m[i] = texture_mapping_buf.g2();
-                texture_props.mapping_n[i] = texture_mapping_buf.g2();
+                texture_props.mapping_p[i] = texture_mapping_buf.try_g2().ok_or(TRUNCATED)?;
+                texture_props.mapping_m[i] = texture_mapping_buf.try_g2().ok_or(TRUNCATED)?;
+                texture_props.mapping_n[i] = texture_mapping_buf.try_g2().ok_or(TRUNCATED)?;
             }
         }
+        Ok(())
     }
 
-    fn decode_v1_maya(&mut self, data: &[u8]) {
+    fn decode_v1_maya(
+        &mut self,
+        data: &[u8],
+        sections: &mut SectionRecorder,
+    ) -> Result<(), Runetek5Error> {
+        const FOOTER: Runetek5Error = Runetek5Error::Truncated("model footer");
         // println!("v3");
         let mut buf1 = data;
         let mut buf2 = data;
@@ -1053,25 +1563,25 @@ impl ModelUnlit {
         let mut buf5 = data;
         let mut buf6 = data;
         let mut buf7 = data;
-        buf1 = &data[(data.len() - 26)..];
-        let vertex_count = buf1.g2() as usize;
-        let triangle_count = buf1.g2() as usize;
-        let textured_triangle_count = buf1.g1() as usize;
-        let flags = buf1.g1();
+        buf1 = &data[data.len().saturating_sub(26)..];
+        let vertex_count = buf1.try_g2().ok_or(FOOTER)? as usize;
+        let triangle_count = buf1.try_g2().ok_or(FOOTER)? as usize;
+        let textured_triangle_count = buf1.try_g1().ok_or(FOOTER)? as usize;
+        let flags = buf1.try_g1().ok_or(FOOTER)?;
         let has_triangle_render_types = flags & 0x1 != 0;
-        let priority = buf1.g1();
+        let priority = buf1.try_g1().ok_or(FOOTER)?;
         let has_priorities = priority == 255;
-        let has_transparencies = buf1.g1() == 1;
-        let has_triangle_skins = buf1.g1() == 1;
-        let has_textures = buf1.g1() == 1;
-        let has_vertex_skins = buf1.g1() == 1;
-        let has_maya_groups = buf1.g1() == 1;
-        let vertex_x_count = buf1.g2() as usize;
-        let vertex_y_count = buf1.g2() as usize;
-        let vertex_z_count = buf1.g2() as usize;
-        let index_count = buf1.g2() as usize;
-        let texture_coords_size = buf1.g2() as usize;
-        let vertex_skins_size = buf1.g2() as usize;
+        let has_transparencies = buf1.try_g1().ok_or(FOOTER)? == 1;
+        let has_triangle_skins = buf1.try_g1().ok_or(FOOTER)? == 1;
+        let has_textures = buf1.try_g1().ok_or(FOOTER)? == 1;
+        let has_vertex_skins = buf1.try_g1().ok_or(FOOTER)? == 1;
+        let has_maya_groups = buf1.try_g1().ok_or(FOOTER)? == 1;
+        let vertex_x_count = buf1.try_g2().ok_or(FOOTER)? as usize;
+        let vertex_y_count = buf1.try_g2().ok_or(FOOTER)? as usize;
+        let vertex_z_count = buf1.try_g2().ok_or(FOOTER)? as usize;
+        let index_count = buf1.try_g2().ok_or(FOOTER)? as usize;
+        let texture_coords_size = buf1.try_g2().ok_or(FOOTER)? as usize;
+        let vertex_skins_size = buf1.try_g2().ok_or(FOOTER)? as usize;
 
         if textured_triangle_count > 0 {
             self.texture_props = Some(ModelTextureMappingProps::new(textured_triangle_count));
@@ -1081,7 +1591,7 @@ impl ModelUnlit {
             simple_texture_triangle_count,
             complex_texture_triangle_count,
             cube_texture_triangle_count,
-        ) = self.decode_texture_render_types(textured_triangle_count, &data);
+        ) = self.decode_texture_render_types(textured_triangle_count, &data)?;
 
         let mut offset = textured_triangle_count;
         let vertex_flags_offset = offset;
@@ -1135,6 +1645,58 @@ impl ModelUnlit {
         let texture_translations_offset = offset;
         offset += complex_texture_triangle_count * 2 + cube_texture_triangle_count * 2;
 
+        sections.record_footer("Footer", data.len() - 26, data.len());
+        sections.limit(data.len() - 26);
+        sections.record("Texture render types", 0, vertex_flags_offset)?;
+        sections.record(
+            "Vertex flags",
+            vertex_flags_offset,
+            triangle_render_types_offset,
+        )?;
+        sections.record(
+            "Triangle render types",
+            triangle_render_types_offset,
+            index_types_offset,
+        )?;
+        sections.record("Index types", index_types_offset, priorities_offset)?;
+        sections.record("Priorities", priorities_offset, triangle_skins_offset)?;
+        sections.record("Triangle skins", triangle_skins_offset, vertex_skins_offset)?;
+        sections.record("Vertex skins", vertex_skins_offset, transparencies_offset)?;
+        sections.record("Transparencies", transparencies_offset, indices_offset)?;
+        sections.record("Indices", indices_offset, textures_offset)?;
+        sections.record("Materials", textures_offset, texture_coords_offset)?;
+        sections.record("Texture coords", texture_coords_offset, colours_offset)?;
+        sections.record("Colours", colours_offset, vertex_x_offset)?;
+        sections.record("Vertex x", vertex_x_offset, vertex_y_offset)?;
+        sections.record("Vertex y", vertex_y_offset, vertex_z_offset)?;
+        sections.record("Vertex z", vertex_z_offset, simple_textures_offset)?;
+        sections.record(
+            "Simple textures",
+            simple_textures_offset,
+            complex_textures_offset,
+        )?;
+        sections.record(
+            "Complex textures",
+            complex_textures_offset,
+            texture_scales_offset,
+        )?;
+        sections.record(
+            "Texture scales",
+            texture_scales_offset,
+            texture_rotations_offset,
+        )?;
+        sections.record(
+            "Texture rotations",
+            texture_rotations_offset,
+            texture_directions_offset,
+        )?;
+        sections.record(
+            "Texture directions",
+            texture_directions_offset,
+            texture_translations_offset,
+        )?;
+        sections.record("Texture translations", texture_translations_offset, offset)?;
+
         self.vertex_count = vertex_count as u16;
         self.triangle_count = triangle_count as u16;
         self.textured_triangle_count = textured_triangle_count as u16;
@@ -1190,7 +1752,7 @@ impl ModelUnlit {
             &mut buf3,
             &mut buf4,
             &mut buf5,
-        );
+        )?;
 
         buf1 = &data[colours_offset..];
         buf2 = &data[triangle_render_types_offset..];
@@ -1214,12 +1776,12 @@ impl ModelUnlit {
             &mut buf5,
             &mut buf6,
             &mut buf7,
-        );
+        )?;
 
         buf1 = &data[indices_offset..];
         buf2 = &data[index_types_offset..];
 
-        self.decode_indices(triangle_count, &mut buf1, &mut buf2);
+        self.decode_indices(triangle_count, &mut buf1, &mut buf2)?;
 
         buf1 = &data[simple_textures_offset..];
         buf2 = &data[complex_textures_offset..];
@@ -1236,7 +1798,8 @@ impl ModelUnlit {
             &mut buf4,
             &mut buf5,
             &mut buf6,
-        );
+        )?;
+        Ok(())
     }
 
     pub fn decode_texture_mapping_v1(
@@ -1248,22 +1811,24 @@ impl ModelUnlit {
         rotation_buf: &mut &[u8],
         direction_buf: &mut &[u8],
         translation_buf: &mut &[u8],
-    ) {
+    ) -> Result<(), Runetek5Error> {
+        const TRUNCATED: Runetek5Error = Runetek5Error::Truncated("model texture mapping");
         if textured_triangle_count > 0 {
             let texture_props = self.texture_props.as_mut().unwrap();
             for i in 0..textured_triangle_count {
                 let texture_render_type = texture_props.render_types[i];
                 if texture_render_type == 0 {
-                    texture_props.mapping_p[i] = simple_buf.g2();
-                    texture_props.mapping_m[i] = simple_buf.g2();
-                    texture_props.mapping_n[i] = simple_buf.g2();
+                    texture_props.mapping_p[i] = simple_buf.try_g2().ok_or(TRUNCATED)?;
+                    texture_props.mapping_m[i] = simple_buf.try_g2().ok_or(TRUNCATED)?;
+                    texture_props.mapping_n[i] = simple_buf.try_g2().ok_or(TRUNCATED)?;
                 } else if texture_render_type >= 1 && texture_render_type <= 3 {
-                    texture_props.mapping_p[i] = complex_buf.g2();
-                    texture_props.mapping_m[i] = complex_buf.g2();
-                    texture_props.mapping_n[i] = complex_buf.g2();
+                    texture_props.mapping_p[i] = complex_buf.try_g2().ok_or(TRUNCATED)?;
+                    texture_props.mapping_m[i] = complex_buf.try_g2().ok_or(TRUNCATED)?;
+                    texture_props.mapping_n[i] = complex_buf.try_g2().ok_or(TRUNCATED)?;
                 }
             }
         }
+        Ok(())
     }
 
     pub fn decode_triangles_v1(
@@ -1281,63 +1846,68 @@ impl ModelUnlit {
         triangle_skin_buf: &mut &[u8],
         texture_buf: &mut &[u8],
         texture_coord_buf: &mut &[u8],
-    ) {
+    ) -> Result<(), Runetek5Error> {
+        const TRUNCATED: Runetek5Error = Runetek5Error::Truncated("model triangles");
         for i in 0..triangle_count {
-            self.triangle_colour[i] = colour_buf.g2();
+            self.triangle_colour[i] = colour_buf.try_g2().ok_or(TRUNCATED)?;
         }
         if has_triangle_render_types {
             let triangle_render_types = self.triangle_render_type.as_mut().unwrap();
             for i in 0..triangle_count {
-                triangle_render_types[i] = triangle_render_type_buf.g1();
+                triangle_render_types[i] = triangle_render_type_buf.try_g1().ok_or(TRUNCATED)?;
             }
         }
         if has_priorities {
             let triangle_priorities = self.triangle_priority.as_mut().unwrap();
             for i in 0..triangle_count {
-                triangle_priorities[i] = priority_buf.g1();
+                triangle_priorities[i] = priority_buf.try_g1().ok_or(TRUNCATED)?;
             }
         }
         if has_transparencies {
             let triangle_transparencies = self.triangle_transparency.as_mut().unwrap();
             for i in 0..triangle_count {
-                triangle_transparencies[i] = transparency_buf.g1();
+                triangle_transparencies[i] = transparency_buf.try_g1().ok_or(TRUNCATED)?;
             }
         }
         if has_triangle_skins {
             let triangle_skins = self.triangle_skins.as_mut().unwrap();
             for i in 0..triangle_count {
-                triangle_skins[i] = triangle_skin_buf.g1() as i32;
+                triangle_skins[i] = triangle_skin_buf.try_g1().ok_or(TRUNCATED)? as i32;
             }
         }
         if has_textures {
             let triangle_textures = self.triangle_material.as_mut().unwrap();
             for i in 0..triangle_count {
-                triangle_textures[i] = (texture_buf.g2() as i16) - 1;
+                triangle_textures[i] =
+                    (texture_buf.try_g2().ok_or(TRUNCATED)? as i16).wrapping_sub(1);
             }
             if let Some(triangle_texture_coords) = self.triangle_texture_coords.as_mut() {
                 for i in 0..triangle_count {
                     if triangle_textures[i] != -1 {
-                        triangle_texture_coords[i] = (texture_coord_buf.g1() as i16) - 1;
+                        triangle_texture_coords[i] =
+                            (texture_coord_buf.try_g1().ok_or(TRUNCATED)? as i16) - 1;
                     } else {
                         triangle_texture_coords[i] = -1;
                     }
                 }
             }
         }
+        Ok(())
     }
 
     pub fn decode_texture_render_types(
         &mut self,
         textured_triangle_count: usize,
         mut buf: &[u8],
-    ) -> (usize, usize, usize) {
+    ) -> Result<(usize, usize, usize), Runetek5Error> {
+        const TRUNCATED: Runetek5Error = Runetek5Error::Truncated("model texture render types");
         let mut simple_texture_triangle_count = 0;
         let mut complex_texture_triangle_count = 0;
         let mut cube_texture_triangle_count = 0;
         if textured_triangle_count > 0 {
             let texture_props = self.texture_props.as_mut().unwrap();
             for i in 0..textured_triangle_count {
-                let texture_render_type = buf.g1();
+                let texture_render_type = buf.try_g1().ok_or(TRUNCATED)?;
                 texture_props.render_types[i] = texture_render_type;
                 if texture_render_type == 0 {
                     simple_texture_triangle_count += 1;
@@ -1351,14 +1921,726 @@ impl ModelUnlit {
             }
         }
 
-        (
+        Ok((
             simple_texture_triangle_count,
             complex_texture_triangle_count,
             cube_texture_triangle_count,
-        )
+        ))
     }
 
-    pub fn translate(&mut self, x: i32, y: i32, z: i32) {
+    /// A stable 64-bit fingerprint of the model's geometry and face attributes.
+    ///
+    /// Optional attributes are canonicalized to their defaults first, so models that render the
+    /// same hash the same regardless of version or which optional blocks were encoded. Skins and
+    /// animation data are not part of the fingerprint.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = Fingerprinter::new();
+        hasher.write_u32(self.vertex_count as u32);
+        hasher.write_u32(self.triangle_count as u32);
+        for v in 0..self.vertex_count as usize {
+            hasher.write_u32(self.vertex_x[v] as u32);
+            hasher.write_u32(self.vertex_y[v] as u32);
+            hasher.write_u32(self.vertex_z[v] as u32);
+        }
+        for t in 0..self.triangle_count as usize {
+            hasher.write_u32(self.triangle_a[t] as u32);
+            hasher.write_u32(self.triangle_b[t] as u32);
+            hasher.write_u32(self.triangle_c[t] as u32);
+            hasher.write_u32(self.triangle_colour[t] as u32);
+            let render_type = self
+                .triangle_render_type
+                .as_ref()
+                .map_or(0, |render_types| render_types[t]);
+            let transparency = self
+                .triangle_transparency
+                .as_ref()
+                .map_or(0, |transparencies| transparencies[t]);
+            let priority = self
+                .triangle_priority
+                .as_ref()
+                .map_or(self.priority, |priorities| priorities[t]);
+            hasher.write_u32(render_type as u32);
+            hasher.write_u32(transparency as u32);
+            hasher.write_u32(priority as u32);
+
+            let material = self
+                .triangle_material
+                .as_ref()
+                .map_or(-1, |materials| materials[t]);
+            hasher.write_u32(material as u32);
+            if material == -1 {
+                continue;
+            }
+            // Hash the resolved texture mapping rather than the texture face index.
+            let coord = self
+                .triangle_texture_coords
+                .as_ref()
+                .map_or(-1, |coords| coords[t]);
+            match &self.texture_props {
+                Some(props) if coord != -1 => {
+                    let coord = coord as usize;
+                    hasher.write_u32(props.render_types[coord] as u32);
+                    hasher.write_u32(props.mapping_p[coord] as u32);
+                    hasher.write_u32(props.mapping_m[coord] as u32);
+                    hasher.write_u32(props.mapping_n[coord] as u32);
+                    if let Some(complex) = &self.texture_complex_props {
+                        if props.render_types[coord] != 0 {
+                            hasher.write_u32(complex.scale_x[coord] as u32);
+                            hasher.write_u32(complex.scale_y[coord] as u32);
+                            hasher.write_u32(complex.scale_z[coord] as u32);
+                            hasher.write_u32(complex.rotation[coord] as u32);
+                            hasher.write_u32(complex.direction[coord] as u32);
+                            hasher.write_u32(complex.speed[coord] as u32);
+                        }
+                    }
+                }
+                _ => {
+                    hasher.write_u32(0);
+                    hasher.write_u32(self.triangle_a[t] as u32);
+                    hasher.write_u32(self.triangle_b[t] as u32);
+                    hasher.write_u32(self.triangle_c[t] as u32);
+                }
+            }
+        }
+        hasher.finish()
+    }
+
+    /// Encodes the model in the format the client reads, see [`ModelUnlit::encode_with_losses`].
+    pub fn encode(&self) -> Vec<u8> {
+        self.encode_with_losses().0
+    }
+
+    /// Encodes the model in the format the client reads, listing what couldn't be stored as it is
+    /// in memory. Values out of range are clamped or dropped rather than refused, so the result
+    /// can always be decoded again.
+    ///
+    /// Models of version 13 on, and models with complex texture mappings, render types or vertex
+    /// skins the oldest format has no room for, are written in the newer format. Models with Maya groups use
+    /// the Maya variant of the oldest format, everything else the oldest format itself.
+    pub fn encode_with_losses(&self) -> (Vec<u8>, Vec<EncodeLoss>) {
+        let mut losses = Vec::new();
+        let data = if self.needs_newer_format() {
+            self.encode_v1(&mut losses)
+        } else {
+            self.encode_v0(&mut losses)
+        };
+        (data, losses)
+    }
+
+    /// Whether the oldest format would lose the model's version, texture mappings, render types
+    /// or vertex skins.
+    fn needs_newer_format(&self) -> bool {
+        if self.anim_maya_props.is_some() {
+            return false;
+        }
+        let has_complex_mappings = self.texture_props.as_ref().is_some_and(|props| {
+            props.render_types[..self.textured_triangle_count as usize]
+                .iter()
+                .any(|&render_type| render_type != 0)
+        });
+        let has_other_render_types = self
+            .triangle_render_type
+            .as_ref()
+            .is_some_and(|render_types| render_types.iter().any(|&render_type| render_type > 1));
+        let has_wide_vertex_skins = self
+            .vertex_skins
+            .as_ref()
+            .is_some_and(|skins| skins.iter().any(|&skin| skin > 254));
+        self.version > Self::VERSION
+            || has_complex_mappings
+            || has_other_render_types
+            || has_wide_vertex_skins
+    }
+
+    /// Encodes the model in the oldest format, or its Maya variant for models with Maya groups.
+    fn encode_v0(&self, losses: &mut Vec<EncodeLoss>) -> Vec<u8> {
+        let vertex_count = self.vertex_count as usize;
+        let triangle_count = self.triangle_count as usize;
+        let mapping_count = (self.textured_triangle_count as usize).min(u8::MAX as usize);
+        push_loss(
+            losses,
+            "Texture mappings",
+            self.textured_triangle_count as usize - mapping_count,
+            "Counted in a byte",
+        );
+        push_loss(
+            losses,
+            "Version",
+            (self.version > Self::VERSION) as usize,
+            "The Maya variant of the oldest format has no version",
+        );
+        let has_textures = self.triangle_render_type.is_some() || self.triangle_material.is_some();
+        // A priority of 255 marks per triangle priorities, so give every triangle its own.
+        let has_priorities = self.triangle_priority.is_some() || self.priority == 255;
+
+        let (vertex_flags, axis_bufs) = self.encode_positions(losses);
+
+        let mut vertex_skin_buf = Vec::new();
+        if let Some(vertex_skins) = &self.vertex_skins {
+            let mut dropped = 0;
+            for &skin in &vertex_skins[..vertex_count] {
+                vertex_skin_buf.p1(match skin {
+                    0..=254 => skin as u8,
+                    -1 => 255,
+                    _ => {
+                        dropped += 1;
+                        255
+                    }
+                });
+            }
+            push_loss(
+                losses,
+                "Vertex skins",
+                dropped,
+                "Labels are stored in a byte where 255 means none",
+            );
+        }
+        if let Some(maya_props) = &self.anim_maya_props {
+            let mut truncated = 0;
+            for v in 0..vertex_count {
+                let groups = maya_props.groups.get(v).map_or(&[][..], Vec::as_slice);
+                let scales = maya_props.scales.get(v).map_or(&[][..], Vec::as_slice);
+                let count = groups.len().min(scales.len()).min(u8::MAX as usize);
+                truncated += (count < groups.len()) as usize;
+                vertex_skin_buf.p1(count as u8);
+                for i in 0..count {
+                    vertex_skin_buf.p1(groups[i]);
+                    vertex_skin_buf.p1(scales[i]);
+                }
+            }
+            push_loss(
+                losses,
+                "Maya groups",
+                truncated,
+                "Counted per vertex in a byte",
+            );
+        }
+
+        let (index_types, index_buf) = self.encode_indices(losses);
+
+        let mut colour_buf = Vec::with_capacity(triangle_count * 2);
+        let mut texture_flags = Vec::new();
+        let (mut lost_render_types, mut lost_colours, mut lost_coords) = (0, 0, 0);
+        for t in 0..triangle_count {
+            let mut colour = self.triangle_colour[t];
+            if has_textures {
+                let render_type = self.triangle_render_type.as_ref().map_or(0, |rts| rts[t]);
+                lost_render_types += (render_type > 1) as usize;
+                let mut flags = render_type & 0x1;
+                let material = self.triangle_material.as_ref().map_or(-1, |mats| mats[t]);
+                if material != -1 {
+                    let coord = self
+                        .triangle_texture_coords
+                        .as_ref()
+                        .map_or(-1, |coords| coords[t]);
+                    // The mapping index shares the flags byte, and must point at a mapping.
+                    if !(0..64).contains(&coord) || coord as usize >= mapping_count {
+                        lost_coords += 1;
+                    }
+                    flags |= 0x2 | ((coord.clamp(0, 63) as u8) << 2);
+                    // The material is stored in place of the colour, which decodes as 127.
+                    lost_colours += (colour != 127) as usize;
+                    colour = material as u16;
+                }
+                texture_flags.push(flags);
+            }
+            colour_buf.p2(colour);
+        }
+        push_loss(
+            losses,
+            "Render types",
+            lost_render_types,
+            "Only flat and smooth shading can be stored",
+        );
+        push_loss(
+            losses,
+            "Textured triangle colours",
+            lost_colours,
+            "Textured triangles store their material instead of a colour",
+        );
+        push_loss(
+            losses,
+            "Texture mapping indices",
+            lost_coords,
+            "Textured triangles need one of the first 64 mappings",
+        );
+
+        let priority_buf = self.encode_priorities(has_priorities);
+        let mut transparency_buf = Vec::new();
+        if let Some(transparencies) = &self.triangle_transparency {
+            transparency_buf.extend_from_slice(&transparencies[..triangle_count]);
+        }
+        let triangle_skin_buf = self.encode_triangle_skins(losses);
+
+        let mut mapping_buf = Vec::with_capacity(mapping_count * 6);
+        if let Some(texture_props) = &self.texture_props {
+            let mut complex = 0;
+            for i in 0..mapping_count {
+                complex += (texture_props.render_types[i] != 0) as usize;
+                mapping_buf.p2(texture_props.mapping_p[i]);
+                mapping_buf.p2(texture_props.mapping_m[i]);
+                mapping_buf.p2(texture_props.mapping_n[i]);
+            }
+            push_loss(
+                losses,
+                "Complex texture mappings",
+                complex,
+                "Only simple mappings can be stored, the rest keep their P, M and N vertices",
+            );
+        }
+
+        // The last field of the old footer is also read as the version, which ends at 65532.
+        let max_last_size = if self.anim_maya_props.is_some() {
+            u16::MAX as usize
+        } else {
+            65532
+        };
+        let sizes = [
+            axis_bufs[0].len(),
+            axis_bufs[1].len(),
+            axis_bufs[2].len(),
+            index_buf.len(),
+            vertex_skin_buf.len(),
+        ];
+        let oversized = sizes
+            .iter()
+            .filter(|&&size| size > u16::MAX as usize)
+            .count()
+            + (index_buf.len() > max_last_size && self.anim_maya_props.is_none()) as usize;
+        push_loss(
+            losses,
+            "Section sizes",
+            oversized,
+            "Sections are sized in 16 bits, the model won't decode",
+        );
+
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&vertex_flags);
+        buf.extend_from_slice(&index_types);
+        buf.extend_from_slice(&priority_buf);
+        buf.extend_from_slice(&triangle_skin_buf);
+        buf.extend_from_slice(&texture_flags);
+        buf.extend_from_slice(&vertex_skin_buf);
+        buf.extend_from_slice(&transparency_buf);
+        buf.extend_from_slice(&index_buf);
+        buf.extend_from_slice(&colour_buf);
+        buf.extend_from_slice(&mapping_buf);
+        for axis_buf in &axis_bufs {
+            buf.extend_from_slice(axis_buf);
+        }
+
+        buf.p2(self.vertex_count);
+        buf.p2(self.triangle_count);
+        buf.p1(mapping_count as u8);
+        buf.p1(has_textures as u8);
+        buf.p1(if has_priorities { 255 } else { self.priority });
+        buf.p1(self.triangle_transparency.is_some() as u8);
+        buf.p1(self.triangle_skins.is_some() as u8);
+        buf.p1(self.vertex_skins.is_some() as u8);
+        if self.anim_maya_props.is_some() {
+            buf.p1(1);
+        }
+        for size in &sizes[..4] {
+            buf.p2(*size as u16);
+        }
+        if self.anim_maya_props.is_some() {
+            buf.p2(vertex_skin_buf.len() as u16);
+            // Read as version 2, the Maya variant of the old format.
+            buf.p2(65534);
+        }
+        buf
+    }
+
+    /// Encodes the model in the newer format read by [`ModelUnlit::decode_v1`], with a version
+    /// byte for models of version 13 on.
+    fn encode_v1(&self, losses: &mut Vec<EncodeLoss>) -> Vec<u8> {
+        let vertex_count = self.vertex_count as usize;
+        let triangle_count = self.triangle_count as usize;
+        let mapping_count = (self.textured_triangle_count as usize).min(u8::MAX as usize);
+        push_loss(
+            losses,
+            "Texture mappings",
+            self.textured_triangle_count as usize - mapping_count,
+            "Counted in a byte",
+        );
+        let has_version = self.version > Self::VERSION;
+        let has_priorities = self.triangle_priority.is_some() || self.priority == 255;
+
+        let (vertex_flags, axis_bufs) = self.encode_positions(losses);
+
+        let mut vertex_skin_buf = Vec::new();
+        let has_extended_vertex_skins = self
+            .vertex_skins
+            .as_ref()
+            .is_some_and(|skins| skins[..vertex_count].iter().any(|&skin| skin > 254));
+        if let Some(vertex_skins) = &self.vertex_skins {
+            let mut dropped = 0;
+            for &skin in &vertex_skins[..vertex_count] {
+                if has_extended_vertex_skins {
+                    let stored = skin.clamp(-1, 32766);
+                    dropped += (stored != skin) as usize;
+                    vertex_skin_buf.p_smart_1_or_2_null(stored);
+                } else {
+                    dropped += (skin < -1) as usize;
+                    vertex_skin_buf.p1(if skin < 0 { 255 } else { skin as u8 });
+                }
+            }
+            push_loss(
+                losses,
+                "Vertex skins",
+                dropped,
+                "Labels are stored as smarts of -1 to 32766",
+            );
+        }
+
+        let (index_types, index_buf) = self.encode_indices(losses);
+
+        let mut render_type_buf = Vec::new();
+        if let Some(render_types) = &self.triangle_render_type {
+            render_type_buf.extend_from_slice(&render_types[..triangle_count]);
+        }
+        let mut colour_buf = Vec::with_capacity(triangle_count * 2);
+        for &colour in &self.triangle_colour[..triangle_count] {
+            colour_buf.p2(colour);
+        }
+        let mut material_buf = Vec::new();
+        let mut texture_coord_buf = Vec::new();
+        if let Some(materials) = &self.triangle_material {
+            let mut lost_coords = 0;
+            for t in 0..triangle_count {
+                let material = materials[t];
+                // Wraps like the decoder, so every material id round trips.
+                material_buf.p2((material as u16).wrapping_add(1));
+                // Coordinates are only read when the model has texture mappings.
+                if material == -1 || mapping_count == 0 {
+                    continue;
+                }
+                let coord = self
+                    .triangle_texture_coords
+                    .as_ref()
+                    .map_or(-1, |coords| coords[t]);
+                let stored = if coord as usize >= mapping_count {
+                    -1
+                } else {
+                    coord.max(-1)
+                };
+                lost_coords += (stored != coord) as usize;
+                texture_coord_buf.p1((stored + 1) as u8);
+            }
+            push_loss(
+                losses,
+                "Texture mapping indices",
+                lost_coords,
+                "Textured triangles need one of the first 255 mappings",
+            );
+        }
+        let priority_buf = self.encode_priorities(has_priorities);
+        let mut transparency_buf = Vec::new();
+        if let Some(transparencies) = &self.triangle_transparency {
+            transparency_buf.extend_from_slice(&transparencies[..triangle_count]);
+        }
+        let triangle_skin_buf = self.encode_triangle_skins(losses);
+
+        let mut mapping_render_types = Vec::with_capacity(mapping_count);
+        let mut simple_buf = Vec::new();
+        let mut complex_buf = Vec::new();
+        let mut scales_buf = Vec::new();
+        let mut rotation_buf = Vec::new();
+        let mut direction_buf = Vec::new();
+        let mut translation_buf = Vec::new();
+        if let Some(texture_props) = &self.texture_props {
+            let (scale_x_max, scale_y_max, scale_z_max) = match self.version {
+                ..=13 => (0xffff, 0xffff, 0xffff),
+                14 => (0xffff, 0xffffff, 0xffff),
+                _ => (0xffffff, 0xffffff, 0xffffff),
+            };
+            let mut clamped_scales = 0;
+            let mut unknown_mappings = 0;
+            for i in 0..mapping_count {
+                let render_type = texture_props.render_types[i];
+                mapping_render_types.push(render_type);
+                let mapping_buf = match render_type {
+                    0 => &mut simple_buf,
+                    1..=3 => &mut complex_buf,
+                    _ => {
+                        unknown_mappings += 1;
+                        continue;
+                    }
+                };
+                mapping_buf.p2(texture_props.mapping_p[i]);
+                mapping_buf.p2(texture_props.mapping_m[i]);
+                mapping_buf.p2(texture_props.mapping_n[i]);
+                if render_type == 0 {
+                    continue;
+                }
+                let complex = self.texture_complex_props.as_ref();
+                let scale = |scales: Option<&Vec<i32>>, max: i32| {
+                    let scale = scales.map_or(0, |scales| scales[i]);
+                    (scale.clamp(0, max), scale)
+                };
+                let scales = [
+                    scale(complex.map(|props| &props.scale_x), scale_x_max),
+                    scale(complex.map(|props| &props.scale_y), scale_y_max),
+                    scale(complex.map(|props| &props.scale_z), scale_z_max),
+                ];
+                clamped_scales += scales.iter().any(|(stored, scale)| stored != scale) as usize;
+                for ((stored, _), max) in
+                    scales
+                        .into_iter()
+                        .zip([scale_x_max, scale_y_max, scale_z_max])
+                {
+                    if max > 0xffff {
+                        scales_buf.p3(stored as u32);
+                    } else {
+                        scales_buf.p2(stored as u16);
+                    }
+                }
+                let value = |values: Option<&Vec<i8>>| values.map_or(0, |values| values[i]) as u8;
+                rotation_buf.p1(value(complex.map(|props| &props.rotation)));
+                direction_buf.p1(value(complex.map(|props| &props.direction)));
+                translation_buf.p1(value(complex.map(|props| &props.speed)));
+                if render_type == 2 {
+                    // The translation of cube mappings isn't kept.
+                    translation_buf.p2(0);
+                }
+            }
+            push_loss(
+                losses,
+                "Texture scales",
+                clamped_scales,
+                "Scales are stored in 16 or 24 bits depending on the version",
+            );
+            push_loss(
+                losses,
+                "Texture mapping vertices",
+                unknown_mappings,
+                "Mappings of render types past 3 are stored without their P, M and N vertices",
+            );
+        }
+
+        let sizes = [
+            axis_bufs[0].len(),
+            axis_bufs[1].len(),
+            axis_bufs[2].len(),
+            index_buf.len(),
+            texture_coord_buf.len(),
+        ];
+        push_loss(
+            losses,
+            "Section sizes",
+            sizes
+                .iter()
+                .filter(|&&size| size > u16::MAX as usize)
+                .count(),
+            "Sections are sized in 16 bits, the model won't decode",
+        );
+
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&mapping_render_types);
+        buf.extend_from_slice(&vertex_flags);
+        buf.extend_from_slice(&render_type_buf);
+        buf.extend_from_slice(&index_types);
+        buf.extend_from_slice(&priority_buf);
+        buf.extend_from_slice(&triangle_skin_buf);
+        buf.extend_from_slice(&vertex_skin_buf);
+        buf.extend_from_slice(&transparency_buf);
+        buf.extend_from_slice(&index_buf);
+        buf.extend_from_slice(&material_buf);
+        buf.extend_from_slice(&texture_coord_buf);
+        buf.extend_from_slice(&colour_buf);
+        for axis_buf in &axis_bufs {
+            buf.extend_from_slice(axis_buf);
+        }
+        for table in [
+            &simple_buf,
+            &complex_buf,
+            &scales_buf,
+            &rotation_buf,
+            &direction_buf,
+            &translation_buf,
+        ] {
+            buf.extend_from_slice(table);
+        }
+        if has_version {
+            buf.p1(self.version);
+        }
+
+        let mut flags = 0;
+        if self.triangle_render_type.is_some() {
+            flags |= 0x1;
+        }
+        if has_version {
+            flags |= 0x8;
+        }
+        if has_extended_vertex_skins {
+            flags |= 0x10;
+        }
+        buf.p2(self.vertex_count);
+        buf.p2(self.triangle_count);
+        buf.p1(mapping_count as u8);
+        buf.p1(flags);
+        buf.p1(if has_priorities { 255 } else { self.priority });
+        buf.p1(self.triangle_transparency.is_some() as u8);
+        buf.p1(self.triangle_skins.is_some() as u8);
+        buf.p1(self.triangle_material.is_some() as u8);
+        buf.p1(self.vertex_skins.is_some() as u8);
+        for size in sizes {
+            buf.p2(size as u16);
+        }
+        // Read as version 1, the newer format.
+        buf.p2(65535);
+        buf
+    }
+
+    /// Encodes vertex positions as flags and smart deltas per axis.
+    fn encode_positions(&self, losses: &mut Vec<EncodeLoss>) -> (Vec<u8>, [Vec<u8>; 3]) {
+        let vertex_count = self.vertex_count as usize;
+        let mut vertex_flags = Vec::with_capacity(vertex_count);
+        let mut axis_bufs = [Vec::new(), Vec::new(), Vec::new()];
+        let mut last = [0; 3];
+        let mut clamped_vertices = 0;
+        for v in 0..vertex_count {
+            let position = [self.vertex_x[v], self.vertex_y[v], self.vertex_z[v]];
+            let mut flags = 0;
+            let mut clamped = false;
+            for axis in 0..3 {
+                let delta = position[axis] - last[axis];
+                let stored = clamp_smart_1_or_2s(delta);
+                clamped |= stored != delta;
+                if stored != 0 {
+                    flags |= 1 << axis;
+                    axis_bufs[axis].p_smart_1_or_2s(stored);
+                }
+                // Continue from where the decoder ends up, so one far vertex doesn't move the rest.
+                last[axis] += stored;
+            }
+            clamped_vertices += clamped as usize;
+            vertex_flags.push(flags);
+        }
+        push_loss(
+            losses,
+            "Vertex positions",
+            clamped_vertices,
+            "Deltas between vertices are signed smarts of -16384 to 16383",
+        );
+
+        (vertex_flags, axis_bufs)
+    }
+
+    /// A priority per triangle, only written when triangles have their own.
+    fn encode_priorities(&self, has_priorities: bool) -> Vec<u8> {
+        let triangle_count = self.triangle_count as usize;
+        let mut priority_buf = Vec::new();
+        if has_priorities {
+            for t in 0..triangle_count {
+                let priority = self
+                    .triangle_priority
+                    .as_ref()
+                    .map_or(self.priority, |priorities| priorities[t]);
+                priority_buf.p1(priority);
+            }
+        }
+        priority_buf
+    }
+
+    fn encode_triangle_skins(&self, losses: &mut Vec<EncodeLoss>) -> Vec<u8> {
+        let triangle_count = self.triangle_count as usize;
+        let mut triangle_skin_buf = Vec::new();
+        if let Some(triangle_skins) = &self.triangle_skins {
+            let mut dropped = 0;
+            for &skin in &triangle_skins[..triangle_count] {
+                let stored = skin.clamp(0, 255);
+                dropped += (stored != skin) as usize;
+                triangle_skin_buf.p1(stored as u8);
+            }
+            push_loss(
+                losses,
+                "Triangle skins",
+                dropped,
+                "Labels are stored in a byte",
+            );
+        }
+
+        triangle_skin_buf
+    }
+
+    /// Encodes the triangles as index types and smart index deltas, reusing two indices of the
+    /// last triangle where it shares an edge with this one, as in strips and fans.
+    fn encode_indices(&self, losses: &mut Vec<EncodeLoss>) -> (Vec<u8>, Vec<u8>) {
+        let triangle_count = self.triangle_count as usize;
+        let mut index_types = Vec::with_capacity(triangle_count);
+        let mut index_buf = Vec::with_capacity(triangle_count * 2);
+        // The decoder's state, as it will have decoded the indices written so far.
+        let (mut a, mut b, mut c, mut last_index) = (0, 0, 0, 0);
+        let mut clamped_triangles = 0;
+        let write_delta = |index_buf: &mut Vec<u8>, index: i32, from: i32| {
+            let delta = index - from;
+            let stored = clamp_smart_1_or_2s(delta);
+            index_buf.p_smart_1_or_2s(stored);
+            (from + stored, stored != delta)
+        };
+        for t in 0..triangle_count {
+            let (ta, tb, tc) = (
+                self.triangle_a[t] as i32,
+                self.triangle_b[t] as i32,
+                self.triangle_c[t] as i32,
+            );
+            // Types 2 to 4 keep two of the last indices in the order the decoder moves them to.
+            let index_type = if t == 0 {
+                1
+            } else if (ta, tb) == (a, c) {
+                2
+            } else if (ta, tb) == (c, b) {
+                3
+            } else if (ta, tb) == (b, a) {
+                4
+            } else {
+                1
+            };
+            index_types.push(index_type);
+            let clamped = if index_type == 1 {
+                let (decoded_a, clamped_a) = write_delta(&mut index_buf, ta, last_index);
+                let (decoded_b, clamped_b) = write_delta(&mut index_buf, tb, decoded_a);
+                let (decoded_c, clamped_c) = write_delta(&mut index_buf, tc, decoded_b);
+                (a, b, c) = (decoded_a, decoded_b, decoded_c);
+                clamped_a || clamped_b || clamped_c
+            } else {
+                let (decoded_c, clamped) = write_delta(&mut index_buf, tc, last_index);
+                (a, b, c) = (ta, tb, decoded_c);
+                clamped
+            };
+            last_index = c;
+            clamped_triangles += clamped as usize;
+        }
+        push_loss(
+            losses,
+            "Triangle indices",
+            clamped_triangles,
+            "Deltas between indices are signed smarts of -16384 to 16383",
+        );
+        (index_types, index_buf)
+    }
+
+    /// The render type and P, M and N vertices of a texture mapping.
+    pub fn texture_mapping(&self, index: usize) -> Option<(u8, [u16; 3])> {
+        let props = self.texture_props.as_ref()?;
+        Some((
+            *props.render_types.get(index)?,
+            [
+                props.mapping_p[index],
+                props.mapping_m[index],
+                props.mapping_n[index],
+            ],
+        ))
+    }
+
+    /// The Maya groups of a vertex and their scales.
+    pub fn maya_groups(&self, vertex: usize) -> Option<(&[u8], &[u8])> {
+        let props = self.anim_maya_props.as_ref()?;
+        Some((props.groups.get(vertex)?, props.scales.get(vertex)?))
+    }
+
+    pub fn translate(&mut self, x: i32, y: i32, z: i32) {
         let vertex_x = Arc::get_mut(&mut self.vertex_x).unwrap();
         let vertex_y = Arc::get_mut(&mut self.vertex_y).unwrap();
         let vertex_z = Arc::get_mut(&mut self.vertex_z).unwrap();
@@ -1367,6 +2649,161 @@ impl ModelUnlit {
             vertex_y[i] += y;
             vertex_z[i] += z;
         }
+
+        self.bounds = None;
+    }
+
+    pub fn recolour(&mut self, old_colour: u16, new_colour: u16) {
+        for colour in self.triangle_colour.iter_mut() {
+            if *colour == old_colour {
+                *colour = new_colour;
+            }
+        }
+    }
+
+    /// Poses the model with an animation frame, moving vertices by their skin labels and fading
+    /// triangles by theirs. Models without vertex skins are left as they are.
+    pub fn apply_frame(&mut self, frame: &AnimFrame, framemap: &FrameMap) {
+        let Some(vertex_skins) = &self.vertex_skins else {
+            return;
+        };
+        self.bounds = None;
+        let vertex_labels = group_by_label(vertex_skins);
+        let triangle_labels = self.triangle_skins.as_deref().map(group_by_label);
+        let vertex_x = Arc::make_mut(&mut self.vertex_x);
+        let vertex_y = Arc::make_mut(&mut self.vertex_y);
+        let vertex_z = Arc::make_mut(&mut self.vertex_z);
+
+        let (mut origin_x, mut origin_y, mut origin_z) = (0, 0, 0);
+        for transform in frame.resolve_transforms(framemap) {
+            let slot = transform.slot as usize;
+            let labels = &framemap.labels[slot];
+            let vertices = || {
+                labels
+                    .iter()
+                    .filter_map(|&label| vertex_labels.get(label as usize))
+                    .flatten()
+                    .copied()
+            };
+            let (x, y, z) = (transform.x, transform.y, transform.z);
+            match framemap.types[slot] {
+                TransformType::Origin => {
+                    let (mut sum_x, mut sum_y, mut sum_z, mut count) = (0, 0, 0, 0);
+                    for v in vertices() {
+                        sum_x += vertex_x[v];
+                        sum_y += vertex_y[v];
+                        sum_z += vertex_z[v];
+                        count += 1;
+                    }
+                    (origin_x, origin_y, origin_z) = if count > 0 {
+                        (sum_x / count + x, sum_y / count + y, sum_z / count + z)
+                    } else {
+                        (x, y, z)
+                    };
+                }
+                TransformType::Translate => {
+                    for v in vertices() {
+                        vertex_x[v] += x;
+                        vertex_y[v] += y;
+                        vertex_z[v] += z;
+                    }
+                }
+                TransformType::Rotate => {
+                    // Angles are in the units of the trig tables, which are scaled by 2^14.
+                    let pitch = (x & 0x3fff) as usize;
+                    let yaw = (y & 0x3fff) as usize;
+                    let roll = (z & 0x3fff) as usize;
+                    for v in vertices() {
+                        let mut dx = vertex_x[v] - origin_x;
+                        let mut dy = vertex_y[v] - origin_y;
+                        let mut dz = vertex_z[v] - origin_z;
+                        if roll != 0 {
+                            let (sin, cos) = (SINE[roll], COSINE[roll]);
+                            let rotated_x = (dy * sin + dx * cos) >> 14;
+                            dy = (dy * cos - dx * sin) >> 14;
+                            dx = rotated_x;
+                        }
+                        if pitch != 0 {
+                            let (sin, cos) = (SINE[pitch], COSINE[pitch]);
+                            let rotated_y = (dy * cos - dz * sin) >> 14;
+                            dz = (dy * sin + dz * cos) >> 14;
+                            dy = rotated_y;
+                        }
+                        if yaw != 0 {
+                            let (sin, cos) = (SINE[yaw], COSINE[yaw]);
+                            let rotated_x = (dz * sin + dx * cos) >> 14;
+                            dz = (dz * cos - dx * sin) >> 14;
+                            dx = rotated_x;
+                        }
+                        vertex_x[v] = dx + origin_x;
+                        vertex_y[v] = dy + origin_y;
+                        vertex_z[v] = dz + origin_z;
+                    }
+                }
+                TransformType::Scale => {
+                    for v in vertices() {
+                        vertex_x[v] = (vertex_x[v] - origin_x) * x / 128 + origin_x;
+                        vertex_y[v] = (vertex_y[v] - origin_y) * y / 128 + origin_y;
+                        vertex_z[v] = (vertex_z[v] - origin_z) * z / 128 + origin_z;
+                    }
+                }
+                TransformType::Transparency => {
+                    let Some(triangle_labels) = &triangle_labels else {
+                        continue;
+                    };
+                    let triangle_count = self.triangle_count as usize;
+                    let transparencies = self
+                        .triangle_transparency
+                        .get_or_insert_with(|| vec![0; triangle_count]);
+                    for t in labels
+                        .iter()
+                        .filter_map(|&label| triangle_labels.get(label as usize))
+                        .flatten()
+                    {
+                        transparencies[*t] =
+                            (transparencies[*t] as i32 + x * 8).clamp(0, 255) as u8;
+                    }
+                }
+                TransformType::Unknown(_) => {}
+            }
+        }
+    }
+
+    /// Poses the model with the skinning transforms of an animaya animation, moving each vertex
+    /// by the bones of its Maya groups, weighted by their scales out of 255. Models without Maya
+    /// groups are left as they are.
+    pub fn apply_skinning(&mut self, transforms: &[glm::Mat4]) {
+        let Some(maya_props) = &self.anim_maya_props else {
+            return;
+        };
+        self.bounds = None;
+        let vertex_x = Arc::make_mut(&mut self.vertex_x);
+        let vertex_y = Arc::make_mut(&mut self.vertex_y);
+        let vertex_z = Arc::make_mut(&mut self.vertex_z);
+        for (v, (groups, scales)) in maya_props.groups.iter().zip(&maya_props.scales).enumerate() {
+            let mut blended = glm::Mat4::zeros();
+            let mut weight = 0.0;
+            for (&bone, &scale) in groups.iter().zip(scales) {
+                if let Some(transform) = transforms.get(bone as usize) {
+                    let scale = scale as f32 / 255.0;
+                    blended += transform * scale;
+                    weight += scale;
+                }
+            }
+            if weight <= 0.0 {
+                continue;
+            }
+            let position = glm::vec4(
+                vertex_x[v] as f32,
+                vertex_y[v] as f32,
+                vertex_z[v] as f32,
+                1.0,
+            );
+            let posed = blended * position / weight;
+            vertex_x[v] = posed.x.round() as i32;
+            vertex_y[v] = posed.y.round() as i32;
+            vertex_z[v] = posed.z.round() as i32;
+        }
     }
 
     pub fn scale_log2(&mut self, scale: i32) {
@@ -1378,6 +2815,7 @@ impl ModelUnlit {
             vertex_y[i] <<= scale;
             vertex_z[i] <<= scale;
         }
+        self.bounds = None;
         if self.textured_triangle_count > 0 {
             if let (Some(props), Some(complex_props)) = (
                 self.texture_props.as_ref(),
@@ -1394,7 +2832,37 @@ impl ModelUnlit {
         }
     }
 
-    fn calculate_normals(&self) -> (Vec<VertexNormal>, Vec<TriangleNormal>) {
+    /// Bounds of the vertices faces use.
+    pub fn calculate_bounds(&self) -> ModelBounds {
+        let count = self.used_vertex_count as usize;
+        ModelBounds::of_vertices(
+            &self.vertex_x[..count],
+            &self.vertex_y[..count],
+            &self.vertex_z[..count],
+        )
+    }
+
+    /// Like [`ModelUnlit::calculate_bounds`], cached until the model is transformed. Models lit
+    /// from this one start with the cached bounds.
+    pub fn get_bounds(&mut self) -> ModelBounds {
+        if let Some(bounds) = self.bounds {
+            return bounds;
+        }
+
+        *self.bounds.insert(self.calculate_bounds())
+    }
+
+    pub fn get_xyz_radius(&mut self) -> i32 {
+        self.get_bounds().xyz_radius
+    }
+
+    pub fn get_center(&mut self) -> (i32, i32, i32) {
+        self.get_bounds().bounding_box.get_center()
+    }
+
+    /// The smooth normals of vertices and the normals of flat shaded triangles. With
+    /// `flat_shaded` every triangle gets a triangle normal and the vertex normals stay zero.
+    fn calculate_normals(&self, flat_shaded: bool) -> (Vec<VertexNormal>, Vec<TriangleNormal>) {
         let mut vertex_normals = vec![VertexNormal::default(); self.used_vertex_count as usize];
         let mut triangle_normals = vec![TriangleNormal::default(); self.triangle_count as usize];
 
@@ -1428,7 +2896,10 @@ impl ModelUnlit {
             ny = ny * 256 / nmag;
             nz = nz * 256 / nmag;
 
-            let render_type = self.triangle_render_type.as_ref().map_or(0, |rts| rts[t]);
+            let mut render_type = self.triangle_render_type.as_ref().map_or(0, |rts| rts[t]);
+            if flat_shaded && render_type == 0 {
+                render_type = 1;
+            }
             if render_type == 0 {
                 let mut normal = &mut vertex_normals[a];
                 normal.x += nx;
@@ -1457,6 +2928,272 @@ impl ModelUnlit {
     }
 }
 
+/// Something the model format couldn't store as it was in memory, see
+/// [`ModelUnlit::encode_with_losses`].
+#[derive(Debug, Clone)]
+pub struct EncodeLoss {
+    pub property: &'static str,
+    /// How many vertices, triangles or texture mappings were changed.
+    pub count: usize,
+    pub note: &'static str,
+}
+
+fn push_loss(
+    losses: &mut Vec<EncodeLoss>,
+    property: &'static str,
+    count: usize,
+    note: &'static str,
+) {
+    if count > 0 {
+        losses.push(EncodeLoss {
+            property,
+            count,
+            note,
+        });
+    }
+}
+
+/// Clamps a value to what `p_smart_1_or_2s` can write.
+fn clamp_smart_1_or_2s(value: i32) -> i32 {
+    value.clamp(-16384, 16383)
+}
+
+/// The layouts encoded models come in. Newer layouts end in a marker, old models end in their
+/// index count instead, which can look like a marker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModelFormat {
+    /// The original layout, with an 18-byte footer and no marker.
+    V0,
+    /// Texture render types and flags for optional blocks, marked 0xffff.
+    V1,
+    /// The original layout with maya skin groups, marked 0xfffe.
+    V0Maya,
+    /// The newer layout with maya skin groups, marked 0xfffd.
+    V1Maya,
+}
+
+impl ModelFormat {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ModelFormat::V0 => "v0",
+            ModelFormat::V1 => "v1",
+            ModelFormat::V0Maya => "v0 maya",
+            ModelFormat::V1Maya => "v1 maya",
+        }
+    }
+
+    /// The format the data is decoded as: the one its last two bytes mark if its footer fits the
+    /// data, otherwise the original layout if its footer does. None when neither fits.
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        let marked = match data.len().checked_sub(2).map(|end| &data[end..]) {
+            Some([0xff, 0xff]) => ModelFormat::V1,
+            Some([0xff, 0xfe]) => ModelFormat::V0Maya,
+            Some([0xff, 0xfd]) => ModelFormat::V1Maya,
+            _ => ModelFormat::V0,
+        };
+        [marked, ModelFormat::V0]
+            .into_iter()
+            .find(|format| format.fits(data))
+    }
+
+    fn footer_size(&self) -> usize {
+        match self {
+            ModelFormat::V0 => 18,
+            ModelFormat::V1 | ModelFormat::V0Maya => 23,
+            ModelFormat::V1Maya => 26,
+        }
+    }
+
+    /// Whether the sections the footer describes fit in the data, with no more bytes of vertex
+    /// deltas and indices than their encodings can take up.
+    pub fn fits(&self, data: &[u8]) -> bool {
+        self.min_size(data).is_some_and(|size| size <= data.len())
+    }
+
+    /// The fewest bytes the data can have by its footer, counting sections of unknown size at
+    /// their smallest. None if the footer doesn't fit or its counts are impossible.
+    fn min_size(&self, data: &[u8]) -> Option<usize> {
+        let mut footer = &data[data.len().checked_sub(self.footer_size())?..];
+        let newer = matches!(self, ModelFormat::V1 | ModelFormat::V1Maya);
+        let maya = matches!(self, ModelFormat::V0Maya | ModelFormat::V1Maya);
+        let vertex_count = footer.try_g2()? as usize;
+        let triangle_count = footer.try_g2()? as usize;
+        let textured_triangle_count = footer.try_g1()? as usize;
+        let flags = if newer { footer.try_g1()? } else { 0 };
+        let mut has_textures = !newer && footer.try_g1()? == 1;
+        let has_priorities = footer.try_g1()? == 255;
+        let has_transparencies = footer.try_g1()? == 1;
+        let has_triangle_skins = footer.try_g1()? == 1;
+        if newer {
+            has_textures = footer.try_g1()? == 1;
+        }
+        let has_vertex_skins = footer.try_g1()? == 1;
+        if maya {
+            footer.try_g1()?;
+        }
+        let vertex_x_count = footer.try_g2()? as usize;
+        let vertex_y_count = footer.try_g2()? as usize;
+        let vertex_z_count = footer.try_g2()? as usize;
+        let index_count = footer.try_g2()? as usize;
+        let texture_coords_size = if newer { footer.try_g2()? as usize } else { 0 };
+        let vertex_skins_size = if maya {
+            footer.try_g2()? as usize
+        } else if has_vertex_skins {
+            // Extended skins are smarts of at least a byte each.
+            vertex_count
+        } else {
+            0
+        };
+
+        // Deltas are one or two byte smarts, each triangle's indices at most three of them.
+        let max_delta_size = vertex_count * 2;
+        if vertex_x_count > max_delta_size
+            || vertex_y_count > max_delta_size
+            || vertex_z_count > max_delta_size
+            || index_count > triangle_count * 6
+        {
+            return None;
+        }
+
+        let optional = |present: bool, size: usize| if present { size } else { 0 };
+        let texture_size = if newer {
+            // A render type per textured triangle, then at least its three vertices.
+            textured_triangle_count * 7
+                + optional(has_textures, triangle_count * 2)
+                + texture_coords_size
+        } else {
+            textured_triangle_count * 6 + optional(has_textures, triangle_count)
+        };
+        Some(
+            self.footer_size()
+                + optional(*self == ModelFormat::V1 && flags & 0x8 != 0, 1)
+                + optional(newer && flags & 0x1 != 0, triangle_count)
+                + vertex_count
+                + triangle_count
+                + optional(has_priorities, triangle_count)
+                + optional(has_triangle_skins, triangle_count)
+                + vertex_skins_size
+                + optional(has_transparencies, triangle_count)
+                + index_count
+                + triangle_count * 2
+                + vertex_x_count
+                + vertex_y_count
+                + vertex_z_count
+                + texture_size,
+        )
+    }
+}
+
+/// A named byte range of an encoded model, see [`ModelUnlit::decode_annotated`].
+#[derive(Debug, Clone)]
+pub struct ModelSection {
+    pub name: &'static str,
+    pub range: std::ops::Range<usize>,
+}
+
+/// Collects [`ModelSection`]s while decoding, recording nothing unless enabled.
+/// Sections are checked against a limit either way, so decoding fails instead of slicing past
+/// the data.
+struct SectionRecorder {
+    sections: Option<Vec<ModelSection>>,
+    /// Where the footer starts, which sections have to end by.
+    limit: usize,
+}
+
+impl SectionRecorder {
+    const UNACCOUNTED: &'static str = "Unaccounted";
+
+    fn disabled() -> Self {
+        Self {
+            sections: None,
+            limit: usize::MAX,
+        }
+    }
+
+    fn enabled() -> Self {
+        Self {
+            sections: Some(Vec::new()),
+            limit: usize::MAX,
+        }
+    }
+
+    fn limit(&mut self, limit: usize) {
+        self.limit = limit;
+    }
+
+    fn record(
+        &mut self,
+        name: &'static str,
+        start: usize,
+        end: usize,
+    ) -> Result<(), Runetek5Error> {
+        if end > self.limit {
+            return Err(Runetek5Error::Truncated(name));
+        }
+        self.record_footer(name, start, end);
+        Ok(())
+    }
+
+    /// Records a part of the footer, which isn't checked against the limit.
+    fn record_footer(&mut self, name: &'static str, start: usize, end: usize) {
+        if let Some(sections) = self.sections.as_mut() {
+            if start < end {
+                sections.push(ModelSection {
+                    name,
+                    range: start..end,
+                });
+            }
+        }
+    }
+
+    fn finish(self, data_len: usize) -> Vec<ModelSection> {
+        let mut sections = self.sections.unwrap_or_default();
+        sections.sort_by_key(|section| section.range.start);
+        let mut result = Vec::with_capacity(sections.len());
+        let mut position = 0;
+        for section in sections {
+            if section.range.start > position {
+                result.push(ModelSection {
+                    name: Self::UNACCOUNTED,
+                    range: position..section.range.start,
+                });
+            }
+            position = position.max(section.range.end);
+            result.push(section);
+        }
+        if position < data_len {
+            result.push(ModelSection {
+                name: Self::UNACCOUNTED,
+                range: position..data_len,
+            });
+        }
+        result
+    }
+}
+
+/// 64-bit FNV-1a, used instead of `DefaultHasher` because fingerprints must not change between
+/// builds or platforms.
+struct Fingerprinter(u64);
+
+impl Fingerprinter {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    fn new() -> Self {
+        Self(Self::OFFSET_BASIS)
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        for byte in value.to_le_bytes() {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(Self::PRIME);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct VertexNormal {
     pub x: i32,
@@ -1472,6 +3209,32 @@ pub struct TriangleNormal {
     pub z: i32,
 }
 
+/// The size of `count` smarts read with `get_smart_1_or_2`, which take two bytes when the high
+/// bit of the first is set.
+fn smarts_size(mut buf: &[u8], count: usize) -> usize {
+    let start = buf.len();
+    for _ in 0..count {
+        if buf.is_empty() {
+            break;
+        }
+        let size = if buf[0] < 128 { 1 } else { 2 };
+        buf = &buf[size.min(buf.len())..];
+    }
+    start - buf.len()
+}
+
+/// Indices by skin label, like the client groups vertices and triangles when it loads a model.
+fn group_by_label(skins: &[i32]) -> Vec<Vec<usize>> {
+    let label_count = skins.iter().max().map_or(0, |&max| max.max(-1) + 1) as usize;
+    let mut groups = vec![Vec::new(); label_count];
+    for (index, &label) in skins.iter().enumerate() {
+        if label >= 0 {
+            groups[label as usize].push(index);
+        }
+    }
+    groups
+}
+
 fn adjust_lightness(hsl: u16, lightness: i32) -> u16 {
     let mut new_lightness = (hsl & 0x7f) as i32 * lightness >> 7;
     if new_lightness < 2 {
@@ -1609,6 +3372,220 @@ pub struct ModelBounds {
     pub xyz_radius: i32,
 }
 
+impl ModelBounds {
+    /// Bounds of the vertices, with radii measured from the model origin. No vertices give empty
+    /// bounds at the origin.
+    pub fn of_vertices(vertex_x: &[i32], vertex_y: &[i32], vertex_z: &[i32]) -> Self {
+        let mut min_x = i32::MAX;
+        let mut min_y = i32::MAX;
+        let mut min_z = i32::MAX;
+        let mut max_x = i32::MIN;
+        let mut max_y = i32::MIN;
+        let mut max_z = i32::MIN;
+        let mut max_xz_length = 0;
+        let mut max_xyz_length = 0;
+        for ((&vx, &vy), &vz) in vertex_x.iter().zip(vertex_y).zip(vertex_z) {
+            if vx < min_x {
+                min_x = vx;
+            }
+            if vx > max_x {
+                max_x = vx;
+            }
+            if vy < min_y {
+                min_y = vy;
+            }
+            if vy > max_y {
+                max_y = vy;
+            }
+            if vz < min_z {
+                min_z = vz;
+            }
+            if vz > max_z {
+                max_z = vz;
+            }
+            let xz_length = vx * vx + vz * vz;
+            if xz_length > max_xz_length {
+                max_xz_length = xz_length;
+            }
+            let xyz_length = xz_length + vy * vy;
+            if xyz_length > max_xyz_length {
+                max_xyz_length = xyz_length;
+            }
+        }
+        if min_x == i32::MAX {
+            min_x = 0;
+            min_y = 0;
+            min_z = 0;
+        }
+        if max_x == i32::MIN {
+            max_x = 0;
+            max_y = 0;
+            max_z = 0;
+        }
+
+        let bounding_box = BoundingBox {
+            min_x,
+            min_y,
+            min_z,
+            max_x,
+            max_y,
+            max_z,
+        };
+
+        Self {
+            bounding_box,
+            xz_radius: (f64::sqrt(max_xz_length as f64) + 0.99) as i32,
+            xyz_radius: (f64::sqrt(max_xyz_length as f64) + 0.99) as i32,
+        }
+    }
+}
+
+/// Per-triangle lit colours as produced by [`ModelLit::calc_lit_colours`].
+///
+/// For untextured triangles the values are HSL colours, for textured triangles they are lightness
+/// values. A `c` value of -1 means the triangle is flat shaded with `a`, -2 means it is hidden.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LitColours {
+    pub a: Vec<i32>,
+    pub b: Vec<i32>,
+    pub c: Vec<i32>,
+}
+
+impl LitColours {
+    /// A stable checksum of the colours, for comparing snapshots of the integer lighting math.
+    pub fn checksum(&self) -> u32 {
+        let mut hasher = crc32fast::Hasher::new();
+        for colours in [&self.a, &self.b, &self.c] {
+            hasher.update(&(colours.len() as u32).to_be_bytes());
+            for colour in colours.iter() {
+                hasher.update(&colour.to_be_bytes());
+            }
+        }
+        hasher.finalize()
+    }
+}
+
+/// How a triangle takes light, see [`ModelLit::shading`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriangleShading {
+    /// Lit at each corner from its vertex normal.
+    Smooth,
+    /// Lit once from the face normal, which is stored at the triangle's first render vertex.
+    Flat,
+    /// Drawn in a fixed colour whatever the light.
+    Unlit(Hsl),
+    Hidden,
+}
+
+/// Which faces are skipped when seen from behind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FaceCulling {
+    /// Every face is one-sided.
+    All,
+    /// Faces the render type makes double-sided are drawn from both sides, see
+    /// [`ModelLit::is_double_sided`].
+    #[default]
+    RenderType,
+    /// Every face is drawn from both sides.
+    None,
+}
+
+impl FaceCulling {
+    pub fn label(&self) -> &'static str {
+        match self {
+            FaceCulling::All => "All back faces",
+            FaceCulling::RenderType => "By render type",
+            FaceCulling::None => "None (double-sided)",
+        }
+    }
+}
+
+/// How much work lighting a model puts into its shading, see [`ModelLit::from_unlit_with_detail`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LightDetail {
+    /// Smooth shading and texture coordinates, for models looked at up close.
+    #[default]
+    Full,
+    /// Flat shades every face and leaves texture coordinates at zero, for small previews where
+    /// neither shows.
+    Preview,
+}
+
+/// The order lit models draw their triangles in, see [`TriangleSortKey::packed`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TriangleOrder {
+    /// Like the client: transparent triangles last, ordered by priority, then grouped by
+    /// effect and material.
+    #[default]
+    Client,
+    /// Priority orders every triangle, not only transparent ones.
+    PriorityAlways,
+    /// Ignores the priorities and orders transparent triangles by material only.
+    IgnorePriority,
+    /// Triangles in the order the model stores them.
+    FileOrder,
+}
+
+impl TriangleOrder {
+    pub const ALL: [TriangleOrder; 4] = [
+        TriangleOrder::Client,
+        TriangleOrder::PriorityAlways,
+        TriangleOrder::IgnorePriority,
+        TriangleOrder::FileOrder,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TriangleOrder::Client => "Client",
+            TriangleOrder::PriorityAlways => "Priority for all triangles",
+            TriangleOrder::IgnorePriority => "Ignore priority",
+            TriangleOrder::FileOrder => "File order",
+        }
+    }
+}
+
+/// What a triangle is sorted by when lighting a model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TriangleSortKey {
+    pub triangle: u16,
+    pub priority: u8,
+    /// Whether the client sorts the triangle by its priority, which it only does for transparent
+    /// triangles or models.
+    pub priority_applies: bool,
+    pub transparent: bool,
+    pub effect_id: u8,
+    pub effect_config0: u8,
+    pub texture_id: i16,
+    /// Position among the drawn triangles in file order, keeping the sort stable.
+    pub index: u16,
+}
+
+impl TriangleSortKey {
+    /// The key triangles are sorted by in the given order, smallest first. For the client order
+    /// the bits are, from the top: priority, transparency, effect id, effect config, material and
+    /// index.
+    pub fn packed(&self, order: TriangleOrder) -> u64 {
+        if order == TriangleOrder::FileOrder {
+            return self.index as u64;
+        }
+        ((self.draw_priority(order) as u64) << 49)
+            | ((self.transparent as u64) << 48)
+            | ((self.effect_id as u64) << 40)
+            | ((self.effect_config0 as u64) << 32)
+            | ((self.texture_id as u16 as u64) << 16)
+            | self.index as u64
+    }
+
+    /// The priority the order draws the triangle with, 0 where it ignores them.
+    pub fn draw_priority(&self, order: TriangleOrder) -> u8 {
+        match order {
+            TriangleOrder::Client if self.priority_applies => self.priority,
+            TriangleOrder::PriorityAlways => self.priority,
+            TriangleOrder::Client | TriangleOrder::IgnorePriority | TriangleOrder::FileOrder => 0,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ModelLit {
     pub flags: ModelFlags,
@@ -1635,6 +3612,12 @@ pub struct ModelLit {
     pub triangle_render_type: Arc<Vec<u8>>,
     pub triangle_colour: Arc<Vec<u16>>,
     pub triangle_transparency: Arc<Vec<u8>>,
+    /// Whether each triangle is blended, by its transparency or its material's alpha, see
+    /// [`TriangleSortKey::transparent`].
+    pub triangle_transparent: Arc<Vec<bool>>,
+    /// The priority each triangle was ordered by, see [`TriangleSortKey::draw_priority`]. Like
+    /// the client's software renderer, painters draw lower priorities first whatever their depth.
+    pub triangle_priority: Arc<Vec<u8>>,
     pub triangle_material: Arc<Vec<i16>>,
     pub triangle_render_a: Arc<Vec<u16>>,
     pub triangle_render_b: Arc<Vec<u16>>,
@@ -1644,6 +3627,9 @@ pub struct ModelLit {
 }
 
 impl ModelLit {
+    /// The light direction used when rendering models outside of a scene.
+    pub const DEFAULT_LIGHT_DIRECTION: (i32, i32, i32) = (-50, -10, -50);
+
     pub fn new() -> Self {
         Self {
             flags: ModelFlags::empty(),
@@ -1669,6 +3655,8 @@ impl ModelLit {
             triangle_render_type: Arc::new(Vec::new()),
             triangle_colour: Arc::new(Vec::new()),
             triangle_transparency: Arc::new(Vec::new()),
+            triangle_transparent: Arc::new(Vec::new()),
+            triangle_priority: Arc::new(Vec::new()),
             triangle_material: Arc::new(Vec::new()),
             triangle_render_a: Arc::new(Vec::new()),
             triangle_render_b: Arc::new(Vec::new()),
@@ -1677,86 +3665,83 @@ impl ModelLit {
         }
     }
 
-    pub fn from_unlit(
+    pub fn from_unlit(
+        texture_provider: &TextureProvider,
+        model: &ModelUnlit,
+        flags: ModelFlags,
+        ambient: i16,
+        contrast: i16,
+    ) -> Self {
+        Self::from_unlit_with_detail(
+            texture_provider,
+            model,
+            flags,
+            ambient,
+            contrast,
+            LightDetail::Full,
+        )
+    }
+
+    pub fn from_unlit_with_detail(
+        texture_provider: &TextureProvider,
+        model: &ModelUnlit,
+        flags: ModelFlags,
+        ambient: i16,
+        contrast: i16,
+        detail: LightDetail,
+    ) -> Self {
+        Self::from_unlit_with_order(
+            texture_provider,
+            model,
+            flags,
+            ambient,
+            contrast,
+            detail,
+            TriangleOrder::Client,
+        )
+    }
+
+    /// Lights the model with its triangles drawn in the given order, for diagnosing triangles
+    /// drawn in the wrong order.
+    pub fn from_unlit_with_order(
         texture_provider: &TextureProvider,
         model: &ModelUnlit,
         flags: ModelFlags,
         ambient: i16,
         contrast: i16,
+        detail: LightDetail,
+        order: TriangleOrder,
     ) -> Self {
-        let mut is_transparent = false;
-        let mut triangle_indices = Vec::with_capacity(model.triangle_count as usize);
+        let _span = tracing::info_span!("model_light", triangles = model.triangle_count).entered();
+        let substituted = texture_provider
+            .material_rules()
+            .apply(texture_provider, model);
+        let model = substituted.as_ref().unwrap_or(model);
+        let mut triangle_indices = Self::drawn_triangles(texture_provider, model);
         let mut vertex_unique_index = vec![0u32; model.used_vertex_count as usize + 1];
-        // TODO: get from render flags
-        let hd_textures_enabled = true;
-        for t in 0..model.triangle_count as usize {
-            let render_type = model.triangle_render_type.as_ref().map_or(0, |ts| ts[t]);
-            if render_type == 2 {
-                continue;
-            }
-            let material_id = model.triangle_material.as_ref().map_or(-1, |ts| ts[t]);
-            if material_id != -1 {
-                let info = texture_provider
-                    .get_info(material_id as u16 as u32)
-                    .unwrap_or_default();
-                if (hd_textures_enabled || !info.high_detail) && info.standard_detail_only {
-                    continue;
-                }
-            }
-            triangle_indices.push(t as u16);
+        for &t in &triangle_indices {
+            let t = t as usize;
             vertex_unique_index[model.triangle_a[t] as usize] += 1;
             vertex_unique_index[model.triangle_b[t] as usize] += 1;
             vertex_unique_index[model.triangle_c[t] as usize] += 1;
         }
         let triangle_count = triangle_indices.len();
         let render_triangle_count = triangle_count;
-        let mut sort_keys = vec![0u64; model.triangle_count as usize];
-        let is_model_transparent = flags.contains(ModelFlags::ANIMATED_TRANSPARENCY);
-        for i in 0..triangle_count {
-            let t = triangle_indices[i] as usize;
-            let mut key = 0u64;
-            let mut texture_id = model.triangle_material.as_ref().map_or(-1, |ts| ts[t]);
-            let mut material_info = None;
-            if texture_id != -1 {
-                let info = texture_provider
-                    .get_info(texture_id as u16 as u32)
-                    .unwrap_or_default();
-                if !hd_textures_enabled && info.high_detail {
-                    texture_id = -1;
-                } else {
-                    material_info = Some(info);
-                }
-            }
-            let (effect_id, effect_config0, is_material_transparent) =
-                material_info.as_ref().map_or((0, 0, false), |info| {
-                    (
-                        info.effect_id,
-                        info.effect_config0,
-                        info.alpha_mode != AlphaMode::Opaque,
-                    )
-                });
-            let is_triangle_transparent = model
-                .triangle_transparency
-                .as_ref()
-                .map_or(false, |ts| ts[t] != 0)
-                || is_material_transparent;
-            if is_model_transparent || is_triangle_transparent {
-                if let Some(priorities) = &model.triangle_priority {
-                    key |= (priorities[t] as u64) << 49;
-                }
-            }
-
-            if is_triangle_transparent {
-                key |= 1 << 48;
-            }
-            key |= (effect_id as u64) << 40;
-            key |= (effect_config0 as u64) << 32;
-            key |= (texture_id as u16 as u64) << 16;
-            key |= i as u64 & 0xffff;
-            sort_keys[t] = key;
-            is_transparent |= is_triangle_transparent;
+        let sort_keys = Self::triangle_sort_keys(texture_provider, model, flags, &triangle_indices);
+        let is_transparent = sort_keys.iter().any(|key| key.transparent);
+        let mut keyed: Vec<_> = triangle_indices
+            .iter()
+            .zip(&sort_keys)
+            .map(|(&t, key)| (key.packed(order), t, key))
+            .collect();
+        keyed.sort_unstable_by_key(|&(packed, t, _)| (packed, t));
+        let mut triangle_transparent = vec![false; triangle_count];
+        let mut triangle_priority = vec![0; triangle_count];
+        for (i, (_, t, key)) in keyed.into_iter().enumerate() {
+            triangle_indices[i] = t;
+            triangle_transparent[i] = key.transparent;
+            triangle_priority[i] = key.draw_priority(order);
         }
-        triangle_indices.sort_by_key(|i| sort_keys[*i as usize]);
 
         let render_vertex_capacity = triangle_count * 3;
         let mut render_vertices = ModelRenderVertices::new(render_vertex_capacity);
@@ -1777,7 +3762,8 @@ impl ModelLit {
         }
         vertex_unique_index[model.used_vertex_count as usize] = vertex_data_index;
 
-        let (vertex_normals, triangle_normals) = model.calculate_normals();
+        let flat_shaded = detail == LightDetail::Preview;
+        let (vertex_normals, triangle_normals) = model.calculate_normals(flat_shaded);
 
         for i in 0..triangle_count {
             let t = triangle_indices[i] as usize;
@@ -1800,7 +3786,7 @@ impl ModelLit {
             let mut v1 = 0f32;
             let mut u2 = 0f32;
             let mut v2 = 0f32;
-            if texture_id != -1 {
+            if texture_id != -1 && detail == LightDetail::Full {
                 if texture_coord == 32766 {
                 } else {
                     let mut mapping_type = 0;
@@ -1877,7 +3863,10 @@ impl ModelLit {
                 }
             }
 
-            let render_type = model.triangle_render_type.as_ref().map_or(0, |rts| rts[t]);
+            let mut render_type = model.triangle_render_type.as_ref().map_or(0, |rts| rts[t]);
+            if flat_shaded && render_type == 0 {
+                render_type = 1;
+            }
             if render_type == 0 {
                 let a = model.triangle_a[t];
                 let b = model.triangle_b[t];
@@ -1992,14 +3981,97 @@ impl ModelLit {
             triangle_render_type: Arc::new(triangle_render_type),
             triangle_colour: Arc::new(triangle_colour),
             triangle_transparency: Arc::new(triangle_transparency),
+            triangle_transparent: Arc::new(triangle_transparent),
+            triangle_priority: Arc::new(triangle_priority),
             triangle_material: Arc::new(triangle_material),
             triangle_render_a: Arc::new(triangle_render_a),
             triangle_render_b: Arc::new(triangle_render_b),
             triangle_render_c: Arc::new(triangle_render_c),
-            bounds: None,
+            bounds: model.bounds,
         }
     }
 
+    /// The triangles that are drawn, in file order. Hidden triangles and ones with materials only
+    /// shown in standard detail are left out.
+    pub fn drawn_triangles(texture_provider: &TextureProvider, model: &ModelUnlit) -> Vec<u16> {
+        // TODO: get from render flags
+        let hd_textures_enabled = true;
+        (0..model.triangle_count)
+            .filter(|&t| {
+                let t = t as usize;
+                let render_type = model.triangle_render_type.as_ref().map_or(0, |ts| ts[t]);
+                if render_type == 2 {
+                    return false;
+                }
+                let material_id = model.triangle_material.as_ref().map_or(-1, |ts| ts[t]);
+                if material_id != -1 {
+                    let info = texture_provider
+                        .get_info(material_id as u16 as u32)
+                        .unwrap_or(&MaterialInfo::DEFAULT);
+                    if (hd_textures_enabled || !info.high_detail) && info.standard_detail_only {
+                        return false;
+                    }
+                }
+                true
+            })
+            .collect()
+    }
+
+    /// The sort key components of each of the given triangles, in the same order.
+    /// `triangle_indices` are the triangles that are drawn, see [`ModelLit::drawn_triangles`].
+    pub fn triangle_sort_keys(
+        texture_provider: &TextureProvider,
+        model: &ModelUnlit,
+        flags: ModelFlags,
+        triangle_indices: &[u16],
+    ) -> Vec<TriangleSortKey> {
+        // TODO: get from render flags
+        let hd_textures_enabled = true;
+        let is_model_transparent = flags.contains(ModelFlags::ANIMATED_TRANSPARENCY);
+        triangle_indices
+            .iter()
+            .enumerate()
+            .map(|(i, &t)| {
+                let t = t as usize;
+                let mut texture_id = model.triangle_material.as_ref().map_or(-1, |ts| ts[t]);
+                let mut material_info = None;
+                if texture_id != -1 {
+                    let info = texture_provider
+                        .get_info(texture_id as u16 as u32)
+                        .unwrap_or(&MaterialInfo::DEFAULT);
+                    if !hd_textures_enabled && info.high_detail {
+                        texture_id = -1;
+                    } else {
+                        material_info = Some(info);
+                    }
+                }
+                let (effect_id, effect_config0, is_material_transparent) =
+                    material_info.as_ref().map_or((0, 0, false), |info| {
+                        (
+                            info.effect_id,
+                            info.effect_config0,
+                            info.alpha_mode != AlphaMode::Opaque,
+                        )
+                    });
+                let transparent = model
+                    .triangle_transparency
+                    .as_ref()
+                    .map_or(false, |ts| ts[t] != 0)
+                    || is_material_transparent;
+                TriangleSortKey {
+                    triangle: t as u16,
+                    priority: model.triangle_priority.as_ref().map_or(0, |ps| ps[t]),
+                    priority_applies: is_model_transparent || transparent,
+                    transparent,
+                    effect_id,
+                    effect_config0,
+                    texture_id,
+                    index: i as u16,
+                }
+            })
+            .collect()
+    }
+
     pub fn add_render_vertex(
         vertex_unique_index: &[u32],
         vertices: &mut ModelRenderVertices,
@@ -2206,6 +4278,8 @@ impl ModelLit {
         } else {
             copy.triangle_transparency = self.triangle_transparency.clone();
         }
+        copy.triangle_transparent = self.triangle_transparent.clone();
+        copy.triangle_priority = self.triangle_priority.clone();
         if flags.has_changed_material() {
             copy.triangle_material = Arc::new(Vec::clone(&self.triangle_material));
         } else {
@@ -2247,72 +4321,12 @@ impl ModelLit {
     }
 
     pub fn calculate_bounds(&self) -> ModelBounds {
-        let mut min_x = i32::MAX;
-        let mut min_y = i32::MAX;
-        let mut min_z = i32::MAX;
-        let mut max_x = i32::MIN;
-        let mut max_y = i32::MIN;
-        let mut max_z = i32::MIN;
-        let mut max_xz_length = 0;
-        let mut max_xyz_length = 0;
-        for v in 0..self.used_vertex_count as usize {
-            let vx = self.vertex_x[v];
-            let vy = self.vertex_y[v];
-            let vz = self.vertex_z[v];
-            if vx < min_x {
-                min_x = vx;
-            }
-            if vx > max_x {
-                max_x = vx;
-            }
-            if vy < min_y {
-                min_y = vy;
-            }
-            if vy > max_y {
-                max_y = vy;
-            }
-            if vz < min_z {
-                min_z = vz;
-            }
-            if vz > max_z {
-                max_z = vz;
-            }
-            let xz_length = vx * vx + vz * vz;
-            if xz_length > max_xz_length {
-                max_xz_length = xz_length;
-            }
-            let xyz_length = xz_length + vy * vy;
-            if xyz_length > max_xyz_length {
-                max_xyz_length = xyz_length;
-            }
-        }
-        if min_x == i32::MAX {
-            min_x = 0;
-            min_y = 0;
-            min_z = 0;
-        }
-        if max_x == i32::MIN {
-            max_x = 0;
-            max_y = 0;
-            max_z = 0;
-        }
-
-        let bounding_box = BoundingBox {
-            min_x,
-            min_y,
-            min_z,
-            max_x,
-            max_y,
-            max_z,
-        };
-
-        let bounds = ModelBounds {
-            bounding_box,
-            xz_radius: (f64::sqrt(max_xz_length as f64) + 0.99) as i32,
-            xyz_radius: (f64::sqrt(max_xyz_length as f64) + 0.99) as i32,
-        };
-
-        bounds
+        let count = self.used_vertex_count as usize;
+        ModelBounds::of_vertices(
+            &self.vertex_x[..count],
+            &self.vertex_y[..count],
+            &self.vertex_z[..count],
+        )
     }
 
     pub fn get_xyz_radius(&mut self) -> i32 {
@@ -2334,6 +4348,47 @@ impl ModelLit {
             .get_center()
     }
 
+    /// Whether the triangle is drawn from both sides. Unlit faces (render type 3, which a
+    /// transparency of 0xfe also implies) take no lighting from their normal, so unlike lit faces
+    /// they have no side they are meant to be seen from.
+    pub fn is_double_sided(&self, t: usize) -> bool {
+        self.triangle_render_type[t] == 3 || self.triangle_transparency[t] == 0xfe
+    }
+
+    /// The priority the triangle is drawn with, 0 for models lit without sort keys.
+    pub fn draw_priority(&self, t: usize) -> u8 {
+        self.triangle_priority.get(t).copied().unwrap_or(0)
+    }
+
+    pub fn is_culled(&self, t: usize, culling: FaceCulling) -> bool {
+        match culling {
+            FaceCulling::All => true,
+            FaceCulling::RenderType => !self.is_double_sided(t),
+            FaceCulling::None => false,
+        }
+    }
+
+    pub fn lit_colours(&self, light_x: i32, light_y: i32, light_z: i32) -> LitColours {
+        let (a, b, c) = self.calc_lit_colours(light_x, light_y, light_z);
+        LitColours { a, b, c }
+    }
+
+    /// How the triangle is lit, from its render type and transparency. Doesn't depend on the
+    /// light, so renderers lighting models themselves can upload it once.
+    pub fn shading(&self, t: usize) -> TriangleShading {
+        let render_type = match self.triangle_transparency[t] {
+            0xfe => 3,
+            0xff => 2,
+            _ => self.triangle_render_type[t],
+        };
+        match (render_type, self.triangle_material[t]) {
+            (0, _) => TriangleShading::Smooth,
+            (1, _) => TriangleShading::Flat,
+            (3, -1) => TriangleShading::Unlit(128),
+            _ => TriangleShading::Hidden,
+        }
+    }
+
     pub fn calc_lit_colours(
         &self,
         light_x: i32,
@@ -2352,115 +4407,605 @@ impl ModelLit {
         let mut triangle_colours_c = vec![0; self.triangle_count as usize];
 
         for t in 0..self.triangle_count as usize {
-            let mut render_type = self.triangle_render_type[t];
-
-            let texture_id = self.triangle_material[t];
-
-            let transparency = self.triangle_transparency[t];
-
-            if transparency == 0xfe {
-                render_type = 3;
-            }
-
-            if transparency == 0xff {
-                render_type = 2;
-            }
+            let colour = self.triangle_colour[t];
+            // Textured triangles only carry a lightness, which shades the texture.
+            let shade = |lightness: i32| {
+                if self.triangle_material[t] == -1 {
+                    adjust_lightness(colour, lightness) as i32
+                } else {
+                    clamp_lightness(lightness)
+                }
+            };
+            let dot = |index: usize| {
+                let nx = self.normal_x[index] as i32;
+                let ny = self.normal_y[index] as i32;
+                let nz = self.normal_z[index] as i32;
+                light_x * nx + light_z * nz + light_y * ny
+            };
 
-            if texture_id == -1 {
-                if render_type == 0 {
-                    let colour = self.triangle_colour[t];
-
-                    let mut index = self.triangle_render_a[t] as usize;
-                    let mut nx = self.normal_x[index] as i32;
-                    let mut ny = self.normal_y[index] as i32;
-                    let mut nz = self.normal_z[index] as i32;
-                    let mut nmag = self.normal_magnitude[index] as i32;
-                    let lightness = (light_x * nx + light_z * nz + light_y * ny)
-                        / (scaled_light_mag * nmag)
-                        + ambient;
-                    triangle_colours_a[t] = adjust_lightness(colour, lightness) as i32;
-
-                    index = self.triangle_render_b[t] as usize;
-                    nx = self.normal_x[index] as i32;
-                    ny = self.normal_y[index] as i32;
-                    nz = self.normal_z[index] as i32;
-                    nmag = self.normal_magnitude[index] as i32;
-                    let lightness = (light_x * nx + light_z * nz + light_y * ny)
-                        / (scaled_light_mag * nmag)
-                        + ambient;
-                    triangle_colours_b[t] = adjust_lightness(colour, lightness) as i32;
-
-                    index = self.triangle_render_c[t] as usize;
-                    nx = self.normal_x[index] as i32;
-                    ny = self.normal_y[index] as i32;
-                    nz = self.normal_z[index] as i32;
-                    nmag = self.normal_magnitude[index] as i32;
-                    let lightness = (light_x * nx + light_z * nz + light_y * ny)
-                        / (scaled_light_mag * nmag)
-                        + ambient;
-                    triangle_colours_c[t] = adjust_lightness(colour, lightness) as i32;
-                } else if render_type == 1 {
+            match self.shading(t) {
+                TriangleShading::Smooth => {
+                    let [a, b, c] = [
+                        self.triangle_render_a[t],
+                        self.triangle_render_b[t],
+                        self.triangle_render_c[t],
+                    ]
+                    .map(|index| {
+                        let index = index as usize;
+                        let nmag = self.normal_magnitude[index] as i32;
+                        shade(dot(index) / (scaled_light_mag * nmag) + ambient)
+                    });
+                    triangle_colours_a[t] = a;
+                    triangle_colours_b[t] = b;
+                    triangle_colours_c[t] = c;
+                }
+                TriangleShading::Flat => {
                     let a = self.triangle_render_a[t] as usize;
-                    let nx = self.normal_x[a] as i32;
-                    let ny = self.normal_y[a] as i32;
-                    let nz = self.normal_z[a] as i32;
-                    let lightness = (light_x * nx + light_z * nz + light_y * ny)
-                        / (scaled_light_mag / 2 + scaled_light_mag)
-                        + ambient;
-                    triangle_colours_a[t] =
-                        adjust_lightness(self.triangle_colour[t], lightness) as i32;
+                    let lightness = dot(a) / (scaled_light_mag / 2 + scaled_light_mag) + ambient;
+                    triangle_colours_a[t] = shade(lightness);
                     triangle_colours_c[t] = -1;
-                } else if render_type == 3 {
-                    triangle_colours_a[t] = 128;
+                }
+                TriangleShading::Unlit(colour) => {
+                    triangle_colours_a[t] = colour as i32;
                     triangle_colours_c[t] = -1;
-                } else {
+                }
+                TriangleShading::Hidden => {
                     triangle_colours_c[t] = -2;
                 }
-            } else if render_type == 0 {
-                let mut index = self.triangle_render_a[t] as usize;
-                let mut nx = self.normal_x[index] as i32;
-                let mut ny = self.normal_y[index] as i32;
-                let mut nz = self.normal_z[index] as i32;
-                let mut nmag = self.normal_magnitude[index] as i32;
-                let lightness = (light_x * nx + light_z * nz + light_y * ny)
-                    / (scaled_light_mag * nmag)
-                    + ambient;
-                triangle_colours_a[t] = clamp_lightness(lightness) as i32;
-
-                index = self.triangle_render_b[t] as usize;
-                nx = self.normal_x[index] as i32;
-                ny = self.normal_y[index] as i32;
-                nz = self.normal_z[index] as i32;
-                nmag = self.normal_magnitude[index] as i32;
-                let lightness = (light_x * nx + light_z * nz + light_y * ny)
-                    / (scaled_light_mag * nmag)
-                    + ambient;
-                triangle_colours_b[t] = clamp_lightness(lightness) as i32;
-
-                index = self.triangle_render_c[t] as usize;
-                nx = self.normal_x[index] as i32;
-                ny = self.normal_y[index] as i32;
-                nz = self.normal_z[index] as i32;
-                nmag = self.normal_magnitude[index] as i32;
-                let lightness = (light_x * nx + light_z * nz + light_y * ny)
-                    / (scaled_light_mag * nmag)
-                    + ambient;
-                triangle_colours_c[t] = clamp_lightness(lightness) as i32;
-            } else if render_type == 1 {
-                let a = self.triangle_render_a[t] as usize;
-                let nx = self.normal_x[a] as i32;
-                let ny = self.normal_y[a] as i32;
-                let nz = self.normal_z[a] as i32;
-                let lightness = (light_x * nx + light_z * nz + light_y * ny)
-                    / (scaled_light_mag / 2 + scaled_light_mag)
-                    + ambient;
-                triangle_colours_a[t] = clamp_lightness(lightness) as i32;
-                triangle_colours_c[t] = -1;
-            } else {
-                triangle_colours_c[t] = -2;
             }
         }
 
         (triangle_colours_a, triangle_colours_b, triangle_colours_c)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::runetek5::{
+        graphics::model_builder::{Face, ModelUnlitBuilder},
+        js5::{Js5Index, Js5IndexProtocol, Js5ResourceProvider},
+    };
+
+    struct EmptyProvider;
+
+    impl Js5ResourceProvider for EmptyProvider {
+        fn fetch_index(&self) -> Option<Arc<Js5Index>> {
+            None
+        }
+
+        fn fetch_group(&self, _group_id: u32) -> Option<Bytes> {
+            None
+        }
+    }
+
+    /// An archive with one empty group, so every material falls back to
+    /// [`MaterialInfo::DEFAULT`].
+    fn empty_js5() -> Js5 {
+        let index = Js5Index {
+            crc: 0,
+            protocol: Js5IndexProtocol::Original,
+            version: 0,
+            has_names: false,
+            has_whirlpool_hashes: false,
+            has_group_data_sizes: false,
+            has_uncompressed_checksums: false,
+            has_md5_hashes: false,
+            group_count: 1,
+            group_capacity: 1,
+            group_ids: vec![0],
+            group_name_hashes: None,
+            group_checksums: vec![0],
+            group_uncompressed_checksums: None,
+            group_whirlpool_hashes: None,
+            group_data_sizes: None,
+            group_uncompressed_data_sizes: None,
+            group_versions: vec![0],
+            group_file_counts: vec![0],
+            group_file_capacities: vec![0],
+            group_file_ids: vec![Some(Vec::new())],
+            group_file_name_hashes: None,
+            group_md5_hashes: None,
+        };
+        Js5::new(Arc::new(EmptyProvider), Arc::new(index), false, false)
+    }
+
+    fn texture_provider() -> TextureProvider {
+        TextureProvider::new(Arc::new(empty_js5()), &empty_js5())
+    }
+
+    /// A square pyramid with its apex up, the faces coloured from `face`.
+    fn pyramid(face: impl Fn(usize) -> Face) -> ModelUnlit {
+        let mut builder = ModelUnlitBuilder::new();
+        let apex = builder.add_vertex(0, -200, 0);
+        let base = [
+            builder.add_vertex(-100, 0, -100),
+            builder.add_vertex(100, 0, -100),
+            builder.add_vertex(100, 0, 100),
+            builder.add_vertex(-100, 0, 100),
+        ];
+        for i in 0..4 {
+            builder.add_face([apex, base[(i + 1) % 4], base[i]], face(i));
+        }
+        builder.add_face([base[0], base[1], base[2]], face(4));
+        builder.add_face([base[0], base[2], base[3]], face(5));
+        builder.build().unwrap()
+    }
+
+    fn colour(i: usize) -> Face {
+        Face::colour(0x1234 + i as u16 * 0x0911)
+    }
+
+    fn checksums(model: &ModelUnlit) -> [u32; 3] {
+        let lit = ModelLit::from_unlit(&texture_provider(), model, ModelFlags::empty(), 64, 768);
+        let (x, y, z) = ModelLit::DEFAULT_LIGHT_DIRECTION;
+        [
+            lit.lit_colours(x, y, z).checksum(),
+            lit.lit_colours(0, -100, 0).checksum(),
+            lit.lit_colours(70, -30, 20).checksum(),
+        ]
+    }
+
+    #[test]
+    fn smooth_lit_colours() {
+        assert_eq!(
+            checksums(&pyramid(colour)),
+            [1210228727, 1150663441, 2529517980]
+        );
+    }
+
+    #[test]
+    fn flat_lit_colours() {
+        let mut model = pyramid(colour);
+        model.triangle_render_type = Some(vec![1; model.triangle_count as usize]);
+        assert_eq!(checksums(&model), [3692693507, 415098669, 1786064151]);
+    }
+
+    #[test]
+    fn textured_lit_colours() {
+        let model = pyramid(|i| Face {
+            material: (i % 2 == 0).then_some(i as u16),
+            ..colour(i)
+        });
+        assert_eq!(checksums(&model), [1147525315, 3252242599, 2634984966]);
+    }
+
+    #[test]
+    fn transparent_lit_colours() {
+        let model = pyramid(|i| Face {
+            transparency: [0, 64, 128, 0xfe, 0xff, 200][i],
+            ..colour(i)
+        });
+        assert_eq!(checksums(&model), [1516755866, 1326816630, 1591549849]);
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Layout {
+        V0,
+        V0Maya,
+        V1,
+    }
+
+    /// The values [`arb_model`] can take past what the format stores, one kind at a time so the
+    /// loss it causes isn't hidden by another.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Wide {
+        Positions,
+        VertexSkins,
+        TriangleSkins,
+        Materials,
+        Coords,
+        RenderTypes,
+        Mappings,
+        Scales,
+        Version,
+    }
+
+    impl Wide {
+        const ALL: [Wide; 9] = [
+            Wide::Positions,
+            Wide::VertexSkins,
+            Wide::TriangleSkins,
+            Wide::Materials,
+            Wide::Coords,
+            Wide::RenderTypes,
+            Wide::Mappings,
+            Wide::Scales,
+            Wide::Version,
+        ];
+    }
+
+    /// A vertex's position, skin and Maya groups with their scales.
+    type RawVertex = (i32, i32, i32, i32, Vec<(u8, u8)>);
+    /// Which of the last triangle's indices a triangle reuses (see
+    /// [`ModelUnlit::encode_indices`]), its own indices, colour, render type, material, texture
+    /// mapping, priority, transparency and skin.
+    type RawTriangle = (u8, [u16; 3], Hsl, u8, i16, i16, u8, u8, i32);
+    /// A mapping's render type, P, M and N vertices, scales, rotation, direction and speed.
+    type RawMapping = (u8, [u16; 3], [i32; 3], i8, i8, i8);
+    /// Which optional arrays the model has, its priority and version.
+    type RawFlags = ([bool; 6], u8, u8);
+
+    /// Models the layout stores as they are, or with the `wide` kind of values past what it can
+    /// store. Wide models keep the arrays a layout decodes to, so whatever changes is a value.
+    fn arb_model(layout: Layout, wide: Option<Wide>) -> impl Strategy<Value = ModelUnlit> {
+        (1..24usize, 1..24usize, 0..6usize).prop_flat_map(
+            move |(vertex_count, triangle_count, mapping_count)| {
+                let position = if wide == Some(Wide::Positions) {
+                    -40000..40000
+                } else {
+                    -8000..8000
+                };
+                let vertex = (
+                    position.clone(),
+                    position.clone(),
+                    position,
+                    -3..40000i32,
+                    prop::collection::vec(any::<(u8, u8)>(), 0..4),
+                );
+                let index = 0..vertex_count as u16;
+                let triangle = (
+                    0..4u8,
+                    prop::array::uniform3(index),
+                    any::<Hsl>(),
+                    any::<u8>(),
+                    any::<i16>(),
+                    -3..300i16,
+                    any::<u8>(),
+                    any::<u8>(),
+                    -2..300i32,
+                );
+                let mapping = (
+                    any::<u8>(),
+                    any::<[u16; 3]>(),
+                    prop::array::uniform3(-5..0x1000005i32),
+                    any::<i8>(),
+                    any::<i8>(),
+                    any::<i8>(),
+                );
+                (
+                    prop::collection::vec(vertex, vertex_count),
+                    prop::collection::vec(triangle, triangle_count),
+                    prop::collection::vec(mapping, mapping_count),
+                    (any::<[bool; 6]>(), any::<u8>(), 12..17u8),
+                )
+                    .prop_map(move |(vertices, triangles, mappings, flags)| {
+                        build_model(layout, wide, &vertices, &triangles, &mappings, flags)
+                    })
+            },
+        )
+    }
+
+    fn build_model(
+        layout: Layout,
+        wide: Option<Wide>,
+        vertices: &[RawVertex],
+        triangles: &[RawTriangle],
+        mappings: &[RawMapping],
+        flags: RawFlags,
+    ) -> ModelUnlit {
+        let (
+            [has_render_types, has_materials, has_priorities, has_transparencies, has_triangle_skins, has_vertex_skins],
+            priority,
+            version,
+        ) = flags;
+        let newer = layout == Layout::V1;
+        // The oldest format keeps render types and materials together.
+        let has_textures = !newer && (has_render_types || has_materials);
+        let mapping_count = mappings.len();
+
+        let mut model = ModelUnlit::new();
+        let is_wide = |kind: Wide| wide == Some(kind);
+        if newer || (is_wide(Wide::Version) && layout == Layout::V0Maya) {
+            model.version = version;
+        }
+        model.vertex_count = vertices.len() as u16;
+        model.vertex_x = Arc::new(vertices.iter().map(|vertex| vertex.0).collect());
+        model.vertex_y = Arc::new(vertices.iter().map(|vertex| vertex.1).collect());
+        model.vertex_z = Arc::new(vertices.iter().map(|vertex| vertex.2).collect());
+        if has_vertex_skins {
+            let skin = |skin: i32| match (layout, is_wide(Wide::VertexSkins)) {
+                // Wider labels would move the model to the newer format.
+                (Layout::V0, true) => skin.min(254),
+                (_, true) => skin,
+                (Layout::V1, false) => skin.clamp(-1, 32766),
+                (_, false) => skin.clamp(-1, 254),
+            };
+            model.vertex_skins = Some(vertices.iter().map(|vertex| skin(vertex.3)).collect());
+        }
+        if layout == Layout::V0Maya {
+            model.anim_maya_props = Some(ModelAnimMayaProps {
+                groups: vertices
+                    .iter()
+                    .map(|vertex| vertex.4.iter().map(|&(group, _)| group).collect())
+                    .collect(),
+                scales: vertices
+                    .iter()
+                    .map(|vertex| vertex.4.iter().map(|&(_, scale)| scale).collect())
+                    .collect(),
+            });
+        }
+
+        let (mut a, mut b, mut c) = (0, 0, 0);
+        for (t, &(reuse, indices, ..)) in triangles.iter().enumerate() {
+            (a, b, c) = match reuse {
+                1 if t > 0 => (a, c, indices[2]),
+                2 if t > 0 => (c, b, indices[2]),
+                3 if t > 0 => (b, a, indices[2]),
+                _ => (indices[0], indices[1], indices[2]),
+            };
+            model.triangle_a.push(a);
+            model.triangle_b.push(b);
+            model.triangle_c.push(c);
+        }
+        model.triangle_count = triangles.len() as u16;
+        model.used_vertex_count = [&model.triangle_a, &model.triangle_b, &model.triangle_c]
+            .into_iter()
+            .flatten()
+            .map(|&index| index + 1)
+            .max()
+            .unwrap_or(0);
+
+        let render_type = |render_type: u8| match (layout, is_wide(Wide::RenderTypes)) {
+            (Layout::V1, _) => render_type,
+            (Layout::V0Maya, true) => render_type % 4,
+            _ => render_type % 2,
+        };
+        let material = |material: i16| {
+            // Without mappings the oldest format has nothing for textured triangles to use.
+            if !newer && !is_wide(Wide::Materials) && mapping_count == 0 {
+                -1
+            } else {
+                material
+            }
+        };
+        let coord = |material: i16, coord: i16| match (layout, is_wide(Wide::Coords)) {
+            _ if material == -1 => -1,
+            (_, true) => coord,
+            (Layout::V1, false) => coord.rem_euclid(mapping_count as i16 + 1) - 1,
+            // Only wide materials are textured without mappings, and lose their mapping anyway.
+            (_, false) if mapping_count == 0 => 0,
+            (_, false) => coord.rem_euclid(mapping_count.min(64) as i16),
+        };
+        if has_render_types || has_textures {
+            model.triangle_render_type = Some(triangles.iter().map(|t| render_type(t.3)).collect());
+        }
+        if has_materials || has_textures {
+            let materials: Vec<i16> = triangles.iter().map(|t| material(t.4)).collect();
+            if has_textures || mapping_count > 0 {
+                model.triangle_texture_coords = Some(
+                    triangles
+                        .iter()
+                        .zip(&materials)
+                        .map(|(t, &material)| coord(material, t.5))
+                        .collect(),
+                );
+            }
+            model.triangle_material = Some(materials);
+        }
+        model.triangle_colour = triangles
+            .iter()
+            .enumerate()
+            .map(|(t, triangle)| {
+                let textured = model.triangle_material.as_ref().is_some_and(|m| m[t] != -1);
+                // The oldest format stores the material in place of the colour.
+                if textured && !newer && !is_wide(Wide::Materials) {
+                    127
+                } else {
+                    triangle.2
+                }
+            })
+            .collect();
+        if has_priorities {
+            model.triangle_priority = Some(triangles.iter().map(|t| t.6).collect());
+        } else {
+            // 255 marks per triangle priorities.
+            model.priority = priority.min(254);
+        }
+        if has_transparencies {
+            model.triangle_transparency = Some(triangles.iter().map(|t| t.7).collect());
+        }
+        if has_triangle_skins {
+            let skin = |skin: i32| match is_wide(Wide::TriangleSkins) {
+                true => skin,
+                false => skin.clamp(0, 255),
+            };
+            model.triangle_skins = Some(triangles.iter().map(|t| skin(t.8)).collect());
+        }
+
+        if mapping_count > 0 {
+            let render_types: Vec<u8> = mappings
+                .iter()
+                .map(|mapping| match (layout, is_wide(Wide::Mappings)) {
+                    (Layout::V1, false) => mapping.0 % 4,
+                    (Layout::V1, true) => mapping.0 % 6,
+                    (Layout::V0Maya, true) => mapping.0 % 4,
+                    _ => 0,
+                })
+                .collect();
+            model.texture_props = Some(ModelTextureMappingProps {
+                render_types: render_types.clone(),
+                mapping_p: mappings.iter().map(|mapping| mapping.1[0]).collect(),
+                mapping_m: mappings.iter().map(|mapping| mapping.1[1]).collect(),
+                mapping_n: mappings.iter().map(|mapping| mapping.1[2]).collect(),
+            });
+            let is_complex = |i: usize| (1..=3).contains(&render_types[i]);
+            if (0..mapping_count).any(is_complex) {
+                let max = match model.version {
+                    ..=13 => [0xffff, 0xffff, 0xffff],
+                    14 => [0xffff, 0xffffff, 0xffff],
+                    _ => [0xffffff, 0xffffff, 0xffffff],
+                };
+                let scale = |i: usize, axis: usize| match is_complex(i) {
+                    false => 0,
+                    true if is_wide(Wide::Scales) => mappings[i].2[axis],
+                    true => mappings[i].2[axis].clamp(0, max[axis]),
+                };
+                let value = |i: usize, value: i8| if is_complex(i) { value } else { 0 };
+                model.texture_complex_props = Some(ModelComplexTextureMappingProps {
+                    scale_x: (0..mapping_count).map(|i| scale(i, 0)).collect(),
+                    scale_y: (0..mapping_count).map(|i| scale(i, 1)).collect(),
+                    scale_z: (0..mapping_count).map(|i| scale(i, 2)).collect(),
+                    rotation: (0..mapping_count)
+                        .map(|i| value(i, mappings[i].3))
+                        .collect(),
+                    direction: (0..mapping_count)
+                        .map(|i| value(i, mappings[i].4))
+                        .collect(),
+                    speed: (0..mapping_count)
+                        .map(|i| value(i, mappings[i].5))
+                        .collect(),
+                });
+            }
+        }
+        model.textured_triangle_count = mapping_count as u16;
+
+        if newer && model.version == ModelUnlit::VERSION {
+            // Version 12 models only take the newer format when the oldest can't store them.
+            let render_types = model
+                .triangle_render_type
+                .get_or_insert_with(|| vec![0; triangles.len()]);
+            render_types[0] = 2;
+        }
+        model
+    }
+
+    fn assert_same(decoded: &ModelUnlit, model: &ModelUnlit) -> Result<(), TestCaseError> {
+        let mappings = |model: &ModelUnlit| {
+            model.texture_props.clone().map(|props| {
+                (
+                    props.render_types,
+                    props.mapping_p,
+                    props.mapping_m,
+                    props.mapping_n,
+                )
+            })
+        };
+        let complex_mappings = |model: &ModelUnlit| {
+            model.texture_complex_props.clone().map(|props| {
+                (
+                    props.scale_x,
+                    props.scale_y,
+                    props.scale_z,
+                    props.rotation,
+                    props.direction,
+                    props.speed,
+                )
+            })
+        };
+        let maya_groups = |model: &ModelUnlit| {
+            model
+                .anim_maya_props
+                .clone()
+                .map(|props| (props.groups, props.scales))
+        };
+        prop_assert_eq!(decoded.version, model.version);
+        prop_assert_eq!(decoded.vertex_count, model.vertex_count);
+        prop_assert_eq!(decoded.triangle_count, model.triangle_count);
+        prop_assert_eq!(
+            decoded.textured_triangle_count,
+            model.textured_triangle_count
+        );
+        prop_assert_eq!(decoded.priority, model.priority);
+        prop_assert_eq!(decoded.used_vertex_count, model.used_vertex_count);
+        prop_assert_eq!(&decoded.vertex_x, &model.vertex_x);
+        prop_assert_eq!(&decoded.vertex_y, &model.vertex_y);
+        prop_assert_eq!(&decoded.vertex_z, &model.vertex_z);
+        prop_assert_eq!(&decoded.triangle_a, &model.triangle_a);
+        prop_assert_eq!(&decoded.triangle_b, &model.triangle_b);
+        prop_assert_eq!(&decoded.triangle_c, &model.triangle_c);
+        prop_assert_eq!(&decoded.triangle_render_type, &model.triangle_render_type);
+        prop_assert_eq!(&decoded.triangle_colour, &model.triangle_colour);
+        prop_assert_eq!(&decoded.triangle_transparency, &model.triangle_transparency);
+        prop_assert_eq!(&decoded.triangle_material, &model.triangle_material);
+        prop_assert_eq!(
+            &decoded.triangle_texture_coords,
+            &model.triangle_texture_coords
+        );
+        prop_assert_eq!(&decoded.triangle_priority, &model.triangle_priority);
+        prop_assert_eq!(mappings(decoded), mappings(model));
+        prop_assert_eq!(complex_mappings(decoded), complex_mappings(model));
+        prop_assert_eq!(&decoded.vertex_skins, &model.vertex_skins);
+        prop_assert_eq!(&decoded.triangle_skins, &model.triangle_skins);
+        prop_assert_eq!(maya_groups(decoded), maya_groups(model));
+        Ok(())
+    }
+
+    fn check_round_trip(model: &ModelUnlit) -> Result<(), TestCaseError> {
+        let (data, losses) = model.encode_with_losses();
+        prop_assert!(losses.is_empty(), "{:?}", losses);
+        assert_same(&ModelUnlit::from_data(&data).unwrap(), model)
+    }
+
+    proptest! {
+        #[test]
+        fn v0_round_trips(model in arb_model(Layout::V0, None)) {
+            check_round_trip(&model)?;
+        }
+
+        #[test]
+        fn v0_maya_round_trips(model in arb_model(Layout::V0Maya, None)) {
+            check_round_trip(&model)?;
+        }
+
+        #[test]
+        fn v1_round_trips(model in arb_model(Layout::V1, None)) {
+            check_round_trip(&model)?;
+        }
+
+        #[test]
+        fn encode_reports_what_it_changes(
+            model in (
+                prop::sample::select(vec![Layout::V0, Layout::V0Maya, Layout::V1]),
+                prop::sample::select(Wide::ALL.to_vec()),
+            )
+                .prop_flat_map(|(layout, wide)| arb_model(layout, Some(wide)))
+        ) {
+            let (data, losses) = model.encode_with_losses();
+            if losses.is_empty() {
+                assert_same(&ModelUnlit::from_data(&data).unwrap(), &model)?;
+            }
+        }
+
+        #[test]
+        fn corrupt_models_fail_to_decode_without_panicking(
+            model in prop::sample::select(vec![Layout::V0, Layout::V0Maya, Layout::V1])
+                .prop_flat_map(|layout| arb_model(layout, None)),
+            index in any::<prop::sample::Index>(),
+            byte in any::<u8>(),
+        ) {
+            let mut data = model.encode_with_losses().0;
+            let index = index.index(data.len());
+            data[index] = byte;
+            let _ = ModelUnlit::from_data(&data);
+        }
+    }
+
+    #[test]
+    fn encode_reports_far_indices() {
+        let mut builder = ModelUnlitBuilder::new();
+        for z in 0..20000 {
+            builder.add_vertex(0, 0, z);
+        }
+        builder.add_face([0, 19999, 1], Face::colour(0));
+        let (_, losses) = builder.build().unwrap().encode_with_losses();
+        assert!(losses
+            .iter()
+            .any(|loss| loss.property == "Triangle indices"));
+    }
+
+    #[test]
+    fn encode_reports_long_maya_groups() {
+        let mut model = pyramid(colour);
+        let vertex_count = model.vertex_count as usize;
+        model.anim_maya_props = Some(ModelAnimMayaProps {
+            groups: vec![vec![1; 300]; vertex_count],
+            scales: vec![vec![2; 300]; vertex_count],
+        });
+        let (_, losses) = model.encode_with_losses();
+        assert!(losses.iter().any(|loss| loss.property == "Maya groups"));
+    }
+}
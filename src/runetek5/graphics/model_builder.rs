@@ -0,0 +1,145 @@
+//! Builds [`ModelUnlit`]s from vertices and faces, for models that don't come from a cache.
+//! Like decoded models, the optional per-face arrays are only kept when a face needs them.
+
+use std::sync::Arc;
+
+use super::model::{Hsl, ModelUnlit};
+use crate::runetek5::error::Runetek5Error;
+
+/// How a face added with [`ModelUnlitBuilder::add_face`] is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Face {
+    pub colour: Hsl,
+    /// The texture drawn across the face, mapped by its own corners. None draws the colour.
+    pub material: Option<u16>,
+    /// Lower priorities are drawn first where the triangle order uses them.
+    pub priority: u8,
+    /// 0 is opaque and 255 hides the face.
+    pub transparency: u8,
+}
+
+impl Face {
+    /// An opaque face of the colour at priority 0.
+    pub fn colour(colour: Hsl) -> Self {
+        Self {
+            colour,
+            material: None,
+            priority: 0,
+            transparency: 0,
+        }
+    }
+}
+
+/// Collects vertices and faces, checking them when the model is built.
+#[derive(Debug, Clone, Default)]
+pub struct ModelUnlitBuilder {
+    vertices: Vec<[i32; 3]>,
+    faces: Vec<([usize; 3], Face)>,
+    vertex_skins: Vec<i32>,
+    triangle_skins: Vec<i32>,
+}
+
+impl ModelUnlitBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a vertex in model units, with y pointing down like the cache's, and returns its
+    /// index.
+    pub fn add_vertex(&mut self, x: i32, y: i32, z: i32) -> usize {
+        self.vertices.push([x, y, z]);
+        self.vertices.len() - 1
+    }
+
+    /// Adds a face between three vertices, wound like the cache's faces, and returns its index.
+    pub fn add_face(&mut self, vertices: [usize; 3], face: Face) -> usize {
+        self.faces.push((vertices, face));
+        self.faces.len() - 1
+    }
+
+    /// Labels the vertices and faces for animations to transform, one label per vertex and one
+    /// per face. Leaving either empty leaves them unlabelled.
+    pub fn set_skins(&mut self, vertex_skins: Vec<i32>, triangle_skins: Vec<i32>) {
+        self.vertex_skins = vertex_skins;
+        self.triangle_skins = triangle_skins;
+    }
+
+    /// Fails with [`Runetek5Error::Invalid`] if the model has more vertices or faces than the
+    /// format's counts hold, a face uses a vertex that wasn't added or a material id too large
+    /// for the format, or the skins don't have a label each.
+    pub fn build(self) -> Result<ModelUnlit, Runetek5Error> {
+        let vertex_count = self.vertices.len();
+        let triangle_count = self.faces.len();
+        if vertex_count > u16::MAX as usize {
+            return Err(Runetek5Error::Invalid("vertex count"));
+        }
+        if triangle_count > u16::MAX as usize {
+            return Err(Runetek5Error::Invalid("face count"));
+        }
+        if !self.vertex_skins.is_empty() && self.vertex_skins.len() != vertex_count {
+            return Err(Runetek5Error::Invalid("vertex skin count"));
+        }
+        if !self.triangle_skins.is_empty() && self.triangle_skins.len() != triangle_count {
+            return Err(Runetek5Error::Invalid("face skin count"));
+        }
+        if self
+            .faces
+            .iter()
+            .flat_map(|(vertices, _)| vertices)
+            .any(|&v| v >= vertex_count)
+        {
+            return Err(Runetek5Error::Invalid("face vertex"));
+        }
+        if self
+            .faces
+            .iter()
+            .any(|(_, face)| face.material.is_some_and(|id| id > i16::MAX as u16))
+        {
+            return Err(Runetek5Error::Invalid("face material"));
+        }
+
+        let mut model = ModelUnlit::new();
+        model.vertex_count = vertex_count as u16;
+        model.triangle_count = triangle_count as u16;
+        model.used_vertex_count = self
+            .faces
+            .iter()
+            .flat_map(|(vertices, _)| vertices)
+            .map(|&v| v as u16 + 1)
+            .max()
+            .unwrap_or(0);
+        model.vertex_x = Arc::new(self.vertices.iter().map(|vertex| vertex[0]).collect());
+        model.vertex_y = Arc::new(self.vertices.iter().map(|vertex| vertex[1]).collect());
+        model.vertex_z = Arc::new(self.vertices.iter().map(|vertex| vertex[2]).collect());
+        model.triangle_a = self.faces.iter().map(|(v, _)| v[0] as u16).collect();
+        model.triangle_b = self.faces.iter().map(|(v, _)| v[1] as u16).collect();
+        model.triangle_c = self.faces.iter().map(|(v, _)| v[2] as u16).collect();
+        model.triangle_colour = self.faces.iter().map(|(_, face)| face.colour).collect();
+
+        let faces = || self.faces.iter().map(|(_, face)| face);
+        if faces().any(|face| face.transparency != 0) {
+            model.triangle_transparency = Some(faces().map(|face| face.transparency).collect());
+        }
+        if faces().any(|face| face.material.is_some()) {
+            model.triangle_material = Some(
+                faces()
+                    .map(|face| face.material.map_or(-1, |id| id as i16))
+                    .collect(),
+            );
+        }
+        // A priority shared by every face is stored once for the model.
+        let priority = faces().next().map_or(0, |face| face.priority);
+        if faces().all(|face| face.priority == priority) {
+            model.priority = priority;
+        } else {
+            model.triangle_priority = Some(faces().map(|face| face.priority).collect());
+        }
+        if !self.vertex_skins.is_empty() {
+            model.vertex_skins = Some(self.vertex_skins);
+        }
+        if !self.triangle_skins.is_empty() {
+            model.triangle_skins = Some(self.triangle_skins);
+        }
+        Ok(model)
+    }
+}
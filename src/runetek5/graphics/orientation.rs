@@ -0,0 +1,182 @@
+//! Guesses how to turn a model so previews show it upright and from the front. Models lying flat
+//! are stood up on their thinnest axis, then turned so the side with the most face area faces the
+//! preview camera, which looks at models from -z.
+
+use std::sync::Arc;
+
+use super::model::ModelUnlit;
+
+/// Quarter turns applied to a model, about x first and then about y.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Orientation {
+    /// Quarter turns about the x axis, standing up models that lie flat.
+    pub pitch: u8,
+    /// Quarter turns about the y axis.
+    pub yaw: u8,
+}
+
+impl Orientation {
+    /// How much thinner than its other axes a model has to be along y to count as lying flat.
+    const FLAT_RATIO: f32 = 0.5;
+    /// How much more face area another side needs than the front to be turned to the camera.
+    const FRONT_MARGIN: f32 = 1.25;
+
+    pub fn is_identity(&self) -> bool {
+        self.pitch % 4 == 0 && self.yaw % 4 == 0
+    }
+
+    pub fn label(&self) -> String {
+        format!(
+            "pitch {}°, yaw {}°",
+            (self.pitch % 4) as u16 * 90,
+            (self.yaw % 4) as u16 * 90
+        )
+    }
+
+    /// Adds quarter turns to the pitch and yaw.
+    pub fn turned(&self, pitch: u8, yaw: u8) -> Self {
+        Self {
+            pitch: (self.pitch + pitch) % 4,
+            yaw: (self.yaw + yaw) % 4,
+        }
+    }
+
+    /// Turns a position or direction.
+    fn apply_to(&self, [mut x, mut y, mut z]: [i64; 3]) -> [i64; 3] {
+        for _ in 0..self.pitch % 4 {
+            (y, z) = (-z, y);
+        }
+        for _ in 0..self.yaw % 4 {
+            (x, z) = (z, -x);
+        }
+        [x, y, z]
+    }
+
+    /// Turns the model's vertices. Quarter turns keep coordinates exact and the winding of
+    /// triangles, so the model lights the same.
+    pub fn apply(&self, model: &mut ModelUnlit) {
+        if self.is_identity() {
+            return;
+        }
+        let vertex_x = Arc::make_mut(&mut model.vertex_x);
+        let vertex_y = Arc::make_mut(&mut model.vertex_y);
+        let vertex_z = Arc::make_mut(&mut model.vertex_z);
+        for v in 0..model.vertex_count as usize {
+            let [x, y, z] =
+                self.apply_to([vertex_x[v] as i64, vertex_y[v] as i64, vertex_z[v] as i64]);
+            (vertex_x[v], vertex_y[v], vertex_z[v]) = (x as i32, y as i32, z as i32);
+        }
+        model.bounds = None;
+    }
+
+    /// Guesses the turns that show the model upright and from the front.
+    pub fn estimate(model: &ModelUnlit) -> Self {
+        let vertex_count = model.vertex_count as usize;
+        if vertex_count == 0 {
+            return Self::default();
+        }
+        let extent = |coords: &[i32]| {
+            let coords = &coords[..vertex_count];
+            let min = coords.iter().min().unwrap();
+            let max = coords.iter().max().unwrap();
+            (max - min) as f32
+        };
+        let (extent_x, extent_y, extent_z) = (
+            extent(&model.vertex_x),
+            extent(&model.vertex_y),
+            extent(&model.vertex_z),
+        );
+        let areas = face_areas(model, Self::default());
+
+        // Stand up models lying flat, with the larger of their top and bottom to the camera.
+        let mut orientation = Self::default();
+        if extent_y < extent_x.min(extent_z) * Self::FLAT_RATIO {
+            // A quarter turn takes -y to -z, three take +y there.
+            orientation.pitch = if areas[Axis::NegY as usize] >= areas[Axis::PosY as usize] {
+                1
+            } else {
+                3
+            };
+        }
+
+        // Vote for the side facing the camera by face area.
+        let areas = face_areas(model, orientation);
+        let front = areas[Axis::NegZ as usize];
+        let (side, area) = [Axis::PosX, Axis::NegX, Axis::PosZ]
+            .into_iter()
+            .map(|axis| (axis, areas[axis as usize]))
+            .fold((Axis::NegZ, front), |best, side| {
+                if side.1 > best.1 {
+                    side
+                } else {
+                    best
+                }
+            });
+        if area > front * Self::FRONT_MARGIN {
+            orientation.yaw = match side {
+                Axis::PosX => 1,
+                Axis::PosZ => 2,
+                Axis::NegX => 3,
+                _ => 0,
+            };
+        }
+        orientation
+    }
+}
+
+/// The direction a face points in, by its largest normal component.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Axis {
+    PosX,
+    NegX,
+    PosY,
+    NegY,
+    PosZ,
+    NegZ,
+}
+
+/// Sums the area of the model's faces by the direction they point in once turned.
+fn face_areas(model: &ModelUnlit, orientation: Orientation) -> [f32; 6] {
+    let mut areas = [0.0; 6];
+    let position = |v: u16| {
+        let v = v as usize;
+        orientation.apply_to([
+            model.vertex_x[v] as i64,
+            model.vertex_y[v] as i64,
+            model.vertex_z[v] as i64,
+        ])
+    };
+    for t in 0..model.triangle_count as usize {
+        let a = position(model.triangle_a[t]);
+        let b = position(model.triangle_b[t]);
+        let c = position(model.triangle_c[t]);
+        let ab = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+        let ac = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+        let normal = [
+            (ab[1] * ac[2] - ab[2] * ac[1]) as f32,
+            (ab[2] * ac[0] - ab[0] * ac[2]) as f32,
+            (ab[0] * ac[1] - ab[1] * ac[0]) as f32,
+        ];
+        let area = (normal[0] * normal[0] + normal[1] * normal[1] + normal[2] * normal[2]).sqrt();
+        let [x, y, z] = normal.map(f32::abs);
+        let axis = if x >= y && x >= z {
+            if normal[0] > 0.0 {
+                Axis::PosX
+            } else {
+                Axis::NegX
+            }
+        } else if y >= z {
+            if normal[1] > 0.0 {
+                Axis::PosY
+            } else {
+                Axis::NegY
+            }
+        } else if normal[2] > 0.0 {
+            Axis::PosZ
+        } else {
+            Axis::NegZ
+        };
+        areas[axis as usize] += area;
+    }
+    areas
+}
@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+
+/// How colours are picked when reducing an image to a sprite palette.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuantizeMethod {
+    /// Recursively splits the colour space at the median of its widest channel.
+    MedianCut,
+    /// Keeps the most frequent colours.
+    Popularity,
+}
+
+impl QuantizeMethod {
+    pub fn label(&self) -> &'static str {
+        match self {
+            QuantizeMethod::MedianCut => "Median cut",
+            QuantizeMethod::Popularity => "Popularity",
+        }
+    }
+}
+
+/// An image reduced to palette indices, index 0 is transparent like in sprites.
+pub struct Quantized {
+    /// 0xRRGGBB colours, entry 0 is the transparent colour.
+    pub palette: Vec<u32>,
+    pub indices: Vec<u8>,
+}
+
+impl Quantized {
+    /// The number of colours used, not counting the transparent entry.
+    pub fn colour_count(&self) -> usize {
+        self.palette.len() - 1
+    }
+}
+
+/// The most colours a sprite palette can hold besides the transparent entry.
+pub const MAX_SPRITE_COLOURS: usize = 255;
+
+/// Reduces 8-bit RGBA pixels to at most `max_colours` colours. Pixels with less than half alpha
+/// become transparent.
+pub fn quantize(rgba: &[u8], max_colours: usize, method: QuantizeMethod) -> Quantized {
+    let max_colours = max_colours.clamp(1, MAX_SPRITE_COLOURS);
+
+    let mut histogram: HashMap<u32, u32> = HashMap::new();
+    for pixel in rgba.chunks_exact(4) {
+        if pixel[3] >= 0x80 {
+            *histogram.entry(rgb(pixel)).or_default() += 1;
+        }
+    }
+    let mut colours: Vec<(u32, u32)> = histogram.into_iter().collect();
+    // Sort so the result doesn't depend on the hash map's iteration order.
+    colours.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    let mut palette_colours = if colours.len() <= max_colours {
+        colours.iter().map(|&(colour, _)| colour).collect()
+    } else {
+        match method {
+            QuantizeMethod::Popularity => colours[..max_colours]
+                .iter()
+                .map(|&(colour, _)| colour)
+                .collect(),
+            QuantizeMethod::MedianCut => median_cut(colours, max_colours),
+        }
+    };
+    palette_colours.dedup();
+
+    let mut palette = Vec::with_capacity(palette_colours.len() + 1);
+    palette.push(0);
+    // Sprites reserve 0 for transparency, so black is stored as 1.
+    palette.extend(palette_colours.iter().map(|&colour| colour.max(1)));
+
+    let mut nearest: HashMap<u32, u8> = HashMap::new();
+    let indices = rgba
+        .chunks_exact(4)
+        .map(|pixel| {
+            if pixel[3] < 0x80 {
+                return 0;
+            }
+            let colour = rgb(pixel);
+            *nearest
+                .entry(colour)
+                .or_insert_with(|| nearest_index(&palette, colour))
+        })
+        .collect();
+
+    Quantized { palette, indices }
+}
+
+fn rgb(pixel: &[u8]) -> u32 {
+    (pixel[0] as u32) << 16 | (pixel[1] as u32) << 8 | pixel[2] as u32
+}
+
+fn channel(colour: u32, channel: usize) -> i32 {
+    (colour >> (16 - channel * 8) & 0xff) as i32
+}
+
+fn nearest_index(palette: &[u32], colour: u32) -> u8 {
+    let distance = |other: u32| {
+        (0..3)
+            .map(|c| {
+                let delta = channel(colour, c) - channel(other, c);
+                delta * delta
+            })
+            .sum::<i32>()
+    };
+    let (index, _) = palette
+        .iter()
+        .enumerate()
+        .skip(1)
+        .min_by_key(|&(_, &other)| distance(other))
+        .unwrap_or((0, &0));
+    index as u8
+}
+
+/// Splits the weighted colours into `max_colours` boxes, returning each box's average colour.
+fn median_cut(colours: Vec<(u32, u32)>, max_colours: usize) -> Vec<u32> {
+    let widest_channel = |colours: &[(u32, u32)]| {
+        (0..3)
+            .map(|c| {
+                let values = colours.iter().map(|&(colour, _)| channel(colour, c));
+                let min = values.clone().min().unwrap_or(0);
+                let max = values.max().unwrap_or(0);
+                (max - min, c)
+            })
+            .max()
+            .unwrap_or((0, 0))
+    };
+
+    let mut boxes = vec![colours];
+    while boxes.len() < max_colours {
+        let Some((index, channel_index)) = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, colours)| colours.len() > 1)
+            .map(|(index, colours)| (widest_channel(colours), index))
+            .max()
+            .map(|((_, channel_index), index)| (index, channel_index))
+        else {
+            break;
+        };
+        let mut colours = boxes.swap_remove(index);
+        colours.sort_unstable_by_key(|&(colour, _)| channel(colour, channel_index));
+        let total: u64 = colours.iter().map(|&(_, count)| count as u64).sum();
+        let mut running = 0;
+        let split = colours
+            .iter()
+            .position(|&(_, count)| {
+                running += count as u64;
+                running * 2 >= total
+            })
+            .unwrap_or(0)
+            .clamp(0, colours.len() - 2)
+            + 1;
+        let upper = colours.split_off(split);
+        boxes.push(colours);
+        boxes.push(upper);
+    }
+
+    boxes
+        .iter()
+        .map(|colours| {
+            let total: u64 = colours.iter().map(|&(_, count)| count as u64).sum();
+            let average = |c: usize| {
+                let sum: u64 = colours
+                    .iter()
+                    .map(|&(colour, count)| channel(colour, c) as u64 * count as u64)
+                    .sum();
+                (sum / total.max(1)) as u32
+            };
+            average(0) << 16 | average(1) << 8 | average(2)
+        })
+        .collect()
+}
@@ -0,0 +1,437 @@
+use std::collections::HashMap;
+
+use nalgebra_glm as glm;
+
+use super::{
+    colour::build_hsl_palette,
+    model::{FaceCulling, ModelLit},
+    texture::TextureProvider,
+};
+
+/// A screen space vertex after projection, `inv_w` is kept for perspective correct texturing.
+#[derive(Debug, Clone, Copy, Default)]
+struct ScreenVertex {
+    x: f32,
+    y: f32,
+    depth: f32,
+    inv_w: f32,
+}
+
+/// Where a triangle's edge crosses a scanline. x and the lightness are in 16.16 fixed point like
+/// the client's rasterizer, the texture coordinates are divided by w so they step linearly.
+#[derive(Debug, Clone, Copy, Default)]
+struct SpanEnd {
+    x: i64,
+    lightness: i64,
+    /// u / w, v / w and 1 / w.
+    uvw: [f32; 3],
+}
+
+/// A triangle edge stepped one scanline at a time from its top vertex.
+struct Edge {
+    y: i32,
+    start: SpanEnd,
+    x_step: i64,
+    lightness_step: i64,
+    uvw_step: [f32; 3],
+}
+
+impl Edge {
+    fn new((y0, start): (i32, SpanEnd), (y1, end): (i32, SpanEnd)) -> Self {
+        let dy = (y1 - y0).max(1);
+        Self {
+            y: y0,
+            start,
+            x_step: (end.x - start.x) / dy as i64,
+            lightness_step: (end.lightness - start.lightness) / dy as i64,
+            uvw_step: [0, 1, 2].map(|i| (end.uvw[i] - start.uvw[i]) / dy as f32),
+        }
+    }
+
+    fn at(&self, y: i32) -> SpanEnd {
+        let steps = y - self.y;
+        SpanEnd {
+            x: self.start.x + self.x_step * steps as i64,
+            lightness: self.start.lightness + self.lightness_step * steps as i64,
+            uvw: [0, 1, 2].map(|i| self.start.uvw[i] + self.uvw_step[i] * steps as f32),
+        }
+    }
+}
+
+/// CPU reference renderer shading triangles flat, gouraud or textured like the client. Triangles
+/// are filled a scanline at a time like the client does, with vertices snapped to whole pixels
+/// and edges stepped in fixed point. Textures are sampled from each vertex's texture coordinates
+/// rather than the client's texture space vectors.
+///
+/// Triangles are drawn by priority and back to front within each priority, without a depth
+/// buffer, like the original software renderer, so the output can be compared against the GL
+/// renderer.
+pub struct Rasterizer {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u32>,
+    brightness: f64,
+    palette: Vec<u32>,
+    textures: HashMap<u32, Vec<u32>>,
+    pub face_culling: FaceCulling,
+}
+
+impl Rasterizer {
+    pub const TEXTURE_SIZE: usize = 128;
+    /// Screen coordinates are clamped to this many pixels either way, so triangles reaching
+    /// towards the camera don't overflow the fixed point edges.
+    const MAX_COORDINATE: f32 = 65536.0;
+
+    pub fn new(brightness: f64) -> Self {
+        Self {
+            width: 0,
+            height: 0,
+            pixels: Vec::new(),
+            brightness,
+            palette: build_hsl_palette(brightness),
+            textures: HashMap::new(),
+            face_culling: FaceCulling::default(),
+        }
+    }
+
+    pub fn set_brightness(&mut self, brightness: f64) {
+        if self.brightness == brightness {
+            return;
+        }
+        self.brightness = brightness;
+        self.palette = build_hsl_palette(brightness);
+        self.textures.clear();
+    }
+
+    pub fn resize(&mut self, width: usize, height: usize) {
+        if self.width != width || self.height != height {
+            self.width = width;
+            self.height = height;
+            self.pixels = vec![0; width * height];
+        }
+    }
+
+    pub fn clear(&mut self, rgb: u32) {
+        self.pixels.fill(rgb);
+    }
+
+    /// Takes the cache rather than `self` so the pixels can be borrowed while the texture is.
+    fn get_texture<'a>(
+        textures: &'a mut HashMap<u32, Vec<u32>>,
+        texture_provider: &TextureProvider,
+        id: u32,
+        brightness: f64,
+    ) -> &'a [u32] {
+        textures.entry(id).or_insert_with(|| {
+            texture_provider.get_pixels_or_placeholder(
+                id,
+                Self::TEXTURE_SIZE as u16,
+                Self::TEXTURE_SIZE as u16,
+                false,
+                brightness,
+            )
+        })
+    }
+
+    /// Renders the model with the given view-projection matrix, model units are divided by 512
+    /// and the y/z axes flipped to match the GL renderer.
+    pub fn render_model(
+        &mut self,
+        texture_provider: &TextureProvider,
+        model: &ModelLit,
+        view_projection: &glm::Mat4,
+        light_x: i32,
+        light_y: i32,
+        light_z: i32,
+    ) {
+        let (colours_a, colours_b, colours_c) = model.calc_lit_colours(light_x, light_y, light_z);
+
+        let mut vertices = vec![None; model.render_vertex_count as usize];
+        for i in 0..model.used_vertex_count as usize {
+            let v_start = model.vertex_unique_index[i] as usize;
+            let v_end = model.vertex_unique_index[i + 1] as usize;
+            let position = glm::vec4(
+                model.vertex_x[i] as f32 / 512.0,
+                -model.vertex_y[i] as f32 / 512.0,
+                -model.vertex_z[i] as f32 / 512.0,
+                1.0,
+            );
+            let vertex = self.project(view_projection, &position);
+            for v in v_start..v_end {
+                let pos = model.vertex_stream_pos[v] as usize;
+                if pos == 0 {
+                    break;
+                }
+                vertices[pos - 1] = vertex;
+            }
+        }
+
+        let mut triangles: Vec<(u8, f32, usize)> =
+            Vec::with_capacity(model.render_triangle_count as usize);
+        for t in 0..model.render_triangle_count as usize {
+            if colours_c[t] == -2 {
+                continue;
+            }
+            let (Some(a), Some(b), Some(c)) = (
+                vertices[model.triangle_render_a[t] as usize],
+                vertices[model.triangle_render_b[t] as usize],
+                vertices[model.triangle_render_c[t] as usize],
+            ) else {
+                continue;
+            };
+            // Back-face culling, screen space y points down so front faces are clockwise.
+            let area = (b.x - a.x) * (c.y - a.y) - (c.x - a.x) * (b.y - a.y);
+            if area >= 0.0 && model.is_culled(t, self.face_culling) {
+                continue;
+            }
+            triangles.push((model.draw_priority(t), a.depth + b.depth + c.depth, t));
+        }
+        triangles.sort_by(|(priority0, depth0, _), (priority1, depth1, _)| {
+            priority0.cmp(priority1).then(depth1.total_cmp(depth0))
+        });
+
+        for (_, _, t) in triangles {
+            let ia = model.triangle_render_a[t] as usize;
+            let ib = model.triangle_render_b[t] as usize;
+            let ic = model.triangle_render_c[t] as usize;
+            let (Some(a), Some(b), Some(c)) = (vertices[ia], vertices[ib], vertices[ic]) else {
+                continue;
+            };
+            let alpha = 0xff - model.triangle_transparency[t] as u32;
+            let colour_a = colours_a[t];
+            let mut colour_b = colours_b[t];
+            let mut colour_c = colours_c[t];
+            if colour_c == -1 {
+                colour_b = colour_a;
+                colour_c = colour_a;
+            }
+            let material = model.triangle_material[t];
+            if material != -1 {
+                let texcoords = [
+                    (model.texcoord_u[ia], model.texcoord_v[ia]),
+                    (model.texcoord_u[ib], model.texcoord_v[ib]),
+                    (model.texcoord_u[ic], model.texcoord_v[ic]),
+                ];
+                self.fill_textured(
+                    texture_provider,
+                    [a, b, c],
+                    [colour_a, colour_b, colour_c],
+                    texcoords,
+                    material as u16 as u32,
+                    alpha,
+                );
+            } else if colour_a == colour_b && colour_b == colour_c {
+                self.fill_flat([a, b, c], colour_a, alpha);
+            } else {
+                self.fill_gouraud([a, b, c], [colour_a, colour_b, colour_c], alpha);
+            }
+        }
+    }
+
+    fn project(&self, view_projection: &glm::Mat4, position: &glm::Vec4) -> Option<ScreenVertex> {
+        let clip = view_projection * position;
+        if clip.w <= 0.0 || clip.z < -clip.w {
+            return None;
+        }
+        let inv_w = 1.0 / clip.w;
+        Some(ScreenVertex {
+            x: (clip.x * inv_w * 0.5 + 0.5) * self.width as f32,
+            y: (0.5 - clip.y * inv_w * 0.5) * self.height as f32,
+            depth: clip.z * inv_w,
+            inv_w,
+        })
+    }
+
+    /// Walks the triangle from its top vertex to its bottom one, passing the left and right ends
+    /// of each scanline it covers. Like the client, the long edge is walked against the upper
+    /// short edge and then the lower one, and rows off the screen are skipped.
+    fn for_each_span(
+        height: usize,
+        vertices: [ScreenVertex; 3],
+        ends: [SpanEnd; 3],
+        mut span: impl FnMut(usize, SpanEnd, SpanEnd),
+    ) {
+        let mut points = [0, 1, 2].map(|i| (Self::snap(vertices[i].y) as i32, ends[i]));
+        points.sort_by_key(|&(y, _)| y);
+        let [top, mid, bottom] = points;
+        if top.0 == bottom.0 {
+            return;
+        }
+        let long = Edge::new(top, bottom);
+        let upper = Edge::new(top, mid);
+        let lower = Edge::new(mid, bottom);
+        for y in top.0.max(0)..bottom.0.min(height as i32) {
+            let a = long.at(y);
+            let b = if y < mid.0 { upper.at(y) } else { lower.at(y) };
+            let (left, right) = if a.x <= b.x { (a, b) } else { (b, a) };
+            span(y as usize, left, right);
+        }
+    }
+
+    /// Snaps a screen coordinate to the pixel it's in.
+    fn snap(coordinate: f32) -> f32 {
+        coordinate
+            .clamp(-Self::MAX_COORDINATE, Self::MAX_COORDINATE)
+            .floor()
+    }
+
+    /// The ends of the triangle at each vertex, x snapped to a whole pixel.
+    fn span_ends(
+        vertices: [ScreenVertex; 3],
+        lightness: [i32; 3],
+        texcoords: [(f32, f32); 3],
+    ) -> [SpanEnd; 3] {
+        [0, 1, 2].map(|i| {
+            let vertex = vertices[i];
+            let (u, v) = texcoords[i];
+            SpanEnd {
+                x: (Self::snap(vertex.x) as i64) << 16,
+                lightness: ((lightness[i] & 0x7f) as i64) << 16,
+                uvw: [u * vertex.inv_w, v * vertex.inv_w, vertex.inv_w],
+            }
+        })
+    }
+
+    /// The pixels a span covers, clipped to the row, with how far its start was clipped.
+    fn span_pixels(width: usize, left: SpanEnd, right: SpanEnd) -> Option<(i64, i64, i64)> {
+        let x0 = left.x >> 16;
+        let x1 = right.x >> 16;
+        if x0 >= x1 {
+            return None;
+        }
+        let start = x0.max(0);
+        let end = x1.min(width as i64);
+        (start < end).then_some((start, end, start - x0))
+    }
+
+    fn blend(dst: u32, src: u32, alpha: u32) -> u32 {
+        if alpha >= 0xff {
+            return src;
+        }
+        let inv_alpha = 0xff - alpha;
+        let rb = ((src & 0xff00ff) * alpha + (dst & 0xff00ff) * inv_alpha) >> 8;
+        let g = ((src & 0xff00) * alpha + (dst & 0xff00) * inv_alpha) >> 8;
+        (rb & 0xff00ff) | (g & 0xff00)
+    }
+
+    fn fill_flat(&mut self, vertices: [ScreenVertex; 3], hsl: i32, alpha: u32) {
+        let rgb = self.palette[hsl as u16 as usize];
+        let width = self.width;
+        let pixels = &mut self.pixels;
+        let ends = Self::span_ends(vertices, [0; 3], [(0.0, 0.0); 3]);
+        Self::for_each_span(self.height, vertices, ends, |y, left, right| {
+            let Some((start, end, _)) = Self::span_pixels(width, left, right) else {
+                return;
+            };
+            let row = &mut pixels[y * width..(y + 1) * width];
+            for pixel in &mut row[start as usize..end as usize] {
+                *pixel = Self::blend(*pixel, rgb, alpha);
+            }
+        });
+    }
+
+    fn fill_gouraud(&mut self, vertices: [ScreenVertex; 3], hsl: [i32; 3], alpha: u32) {
+        let hs = hsl[0] & 0xff80;
+        let width = self.width;
+        let palette = &self.palette;
+        let pixels = &mut self.pixels;
+        let ends = Self::span_ends(vertices, hsl, [(0.0, 0.0); 3]);
+        Self::for_each_span(self.height, vertices, ends, |y, left, right| {
+            let Some((start, end, clipped)) = Self::span_pixels(width, left, right) else {
+                return;
+            };
+            let step = (right.lightness - left.lightness) / ((right.x >> 16) - (left.x >> 16));
+            let mut lightness = left.lightness + step * clipped;
+            let row = &mut pixels[y * width..(y + 1) * width];
+            for pixel in &mut row[start as usize..end as usize] {
+                let rgb = palette[(hs | ((lightness >> 16) as i32).clamp(0, 127)) as usize];
+                *pixel = Self::blend(*pixel, rgb, alpha);
+                lightness += step;
+            }
+        });
+    }
+
+    fn fill_textured(
+        &mut self,
+        texture_provider: &TextureProvider,
+        vertices: [ScreenVertex; 3],
+        lightness: [i32; 3],
+        texcoords: [(f32, f32); 3],
+        texture_id: u32,
+        alpha: u32,
+    ) {
+        let size = Self::TEXTURE_SIZE;
+        let texture = Self::get_texture(
+            &mut self.textures,
+            texture_provider,
+            texture_id,
+            self.brightness,
+        );
+        let width = self.width;
+        let palette = &self.palette;
+        let pixels = &mut self.pixels;
+        let ends = Self::span_ends(vertices, lightness, texcoords);
+        Self::for_each_span(self.height, vertices, ends, |y, left, right| {
+            let Some((start, end, clipped)) = Self::span_pixels(width, left, right) else {
+                return;
+            };
+            let length = (right.x >> 16) - (left.x >> 16);
+            let step = (right.lightness - left.lightness) / length;
+            let uvw_step = [0, 1, 2].map(|i| (right.uvw[i] - left.uvw[i]) / length as f32);
+            let mut lightness = left.lightness + step * clipped;
+            let mut uvw = [0, 1, 2].map(|i| left.uvw[i] + uvw_step[i] * clipped as f32);
+            let row = &mut pixels[y * width..(y + 1) * width];
+            for pixel in &mut row[start as usize..end as usize] {
+                let u = uvw[0] / uvw[2];
+                let v = uvw[1] / uvw[2];
+                let tx = ((u * size as f32) as i32).clamp(0, size as i32 - 1) as usize;
+                let ty = ((v * size as f32) as i32).rem_euclid(size as i32) as usize;
+                let texel = texture[tx + ty * size];
+                if texel >> 24 != 0 {
+                    let l = ((lightness >> 16) as i32).clamp(0, 127);
+                    let shade = palette[l as usize];
+                    let r = (((texel >> 16) & 0xff) * ((shade >> 16) & 0xff)) >> 8;
+                    let g = (((texel >> 8) & 0xff) * ((shade >> 8) & 0xff)) >> 8;
+                    let b = ((texel & 0xff) * (shade & 0xff)) >> 8;
+                    *pixel = Self::blend(*pixel, (r << 16) | (g << 8) | b, alpha);
+                }
+                lightness += step;
+                for (value, step) in uvw.iter_mut().zip(uvw_step) {
+                    *value += step;
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spans_follow_the_edges() {
+        let vertex = |x, y| ScreenVertex {
+            x,
+            y,
+            depth: 0.0,
+            inv_w: 1.0,
+        };
+        let vertices = [vertex(0.0, 0.0), vertex(8.0, 0.0), vertex(0.0, 8.0)];
+        let ends = Rasterizer::span_ends(vertices, [0; 3], [(0.0, 0.0); 3]);
+        let mut rows = Vec::new();
+        Rasterizer::for_each_span(6, vertices, ends, |y, left, right| {
+            rows.push((y, Rasterizer::span_pixels(6, left, right)));
+        });
+        assert_eq!(
+            rows,
+            [
+                (0, Some((0, 6, 0))),
+                (1, Some((0, 6, 0))),
+                (2, Some((0, 6, 0))),
+                (3, Some((0, 5, 0))),
+                (4, Some((0, 4, 0))),
+                (5, Some((0, 3, 0))),
+            ]
+        );
+    }
+}
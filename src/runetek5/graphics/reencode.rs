@@ -0,0 +1,182 @@
+//! Checks an edited or merged model survives being written out: re-encodes it, decodes the bytes
+//! again and compares the result with the model in memory, so lossy conversions are found before
+//! the model is exported.
+
+use super::model::{EncodeLoss, ModelUnlit};
+
+/// How many elements of one property differ after the round trip.
+#[derive(Debug, Clone)]
+pub struct PropertyDiff {
+    pub name: &'static str,
+    pub changed: usize,
+    /// The number of vertices, triangles or texture mappings compared.
+    pub total: usize,
+    /// The first element that changed.
+    pub first: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReencodeReport {
+    /// The size of the encoded model in bytes.
+    pub size: usize,
+    /// What the encoder had to clamp or drop.
+    pub losses: Vec<EncodeLoss>,
+    /// Properties that decode differently, including changes the encoder didn't report.
+    pub diffs: Vec<PropertyDiff>,
+}
+
+impl ReencodeReport {
+    pub fn is_lossless(&self) -> bool {
+        self.losses.is_empty() && self.diffs.is_empty()
+    }
+}
+
+/// Encodes the model, decodes it again and compares the two.
+pub fn check_reencode(model: &ModelUnlit) -> ReencodeReport {
+    let (data, losses) = model.encode_with_losses();
+    // An encoding that doesn't decode shows up as every property differing.
+    let decoded = ModelUnlit::from_data(&data).unwrap_or_else(|_| ModelUnlit::new());
+
+    let mut diffs = Vec::new();
+    let vertex_count = model.vertex_count as usize;
+    let decoded_vertex_count = decoded.vertex_count as usize;
+    let triangle_count = model.triangle_count as usize;
+    let decoded_triangle_count = decoded.triangle_count as usize;
+    let mut compare = |name, total, decoded_total, equal: &dyn Fn(usize) -> bool| {
+        diffs.extend(diff_property(name, total, decoded_total, equal));
+    };
+
+    compare(
+        "Vertex positions",
+        vertex_count,
+        decoded_vertex_count,
+        &|v| {
+            (model.vertex_x[v], model.vertex_y[v], model.vertex_z[v])
+                == (
+                    decoded.vertex_x[v],
+                    decoded.vertex_y[v],
+                    decoded.vertex_z[v],
+                )
+        },
+    );
+    let vertex_skin =
+        |model: &ModelUnlit, v: usize| model.vertex_skins.as_ref().map_or(-1, |skins| skins[v]);
+    compare("Vertex skins", vertex_count, decoded_vertex_count, &|v| {
+        vertex_skin(model, v) == vertex_skin(&decoded, v)
+    });
+    compare("Maya groups", vertex_count, decoded_vertex_count, &|v| {
+        model.maya_groups(v).unwrap_or_default() == decoded.maya_groups(v).unwrap_or_default()
+    });
+
+    compare(
+        "Triangle indices",
+        triangle_count,
+        decoded_triangle_count,
+        &|t| {
+            (
+                model.triangle_a[t],
+                model.triangle_b[t],
+                model.triangle_c[t],
+            ) == (
+                decoded.triangle_a[t],
+                decoded.triangle_b[t],
+                decoded.triangle_c[t],
+            )
+        },
+    );
+    compare("Colours", triangle_count, decoded_triangle_count, &|t| {
+        model.triangle_colour[t] == decoded.triangle_colour[t]
+    });
+    // Missing optional properties compare as the defaults the renderer uses.
+    let render_type =
+        |model: &ModelUnlit, t: usize| model.triangle_render_type.as_ref().map_or(0, |rts| rts[t]);
+    compare(
+        "Render types",
+        triangle_count,
+        decoded_triangle_count,
+        &|t| render_type(model, t) == render_type(&decoded, t),
+    );
+    let priority = |model: &ModelUnlit, t: usize| {
+        model
+            .triangle_priority
+            .as_ref()
+            .map_or(model.priority, |priorities| priorities[t])
+    };
+    compare("Priorities", triangle_count, decoded_triangle_count, &|t| {
+        priority(model, t) == priority(&decoded, t)
+    });
+    let transparency = |model: &ModelUnlit, t: usize| {
+        model
+            .triangle_transparency
+            .as_ref()
+            .map_or(0, |transparencies| transparencies[t])
+    };
+    compare(
+        "Transparencies",
+        triangle_count,
+        decoded_triangle_count,
+        &|t| transparency(model, t) == transparency(&decoded, t),
+    );
+    let triangle_skin =
+        |model: &ModelUnlit, t: usize| model.triangle_skins.as_ref().map_or(0, |skins| skins[t]);
+    compare(
+        "Triangle skins",
+        triangle_count,
+        decoded_triangle_count,
+        &|t| triangle_skin(model, t) == triangle_skin(&decoded, t),
+    );
+    let material =
+        |model: &ModelUnlit, t: usize| model.triangle_material.as_ref().map_or(-1, |mats| mats[t]);
+    compare("Materials", triangle_count, decoded_triangle_count, &|t| {
+        material(model, t) == material(&decoded, t)
+    });
+    // Textured triangles compare the mapping they resolve to, not the index of it.
+    let texture_mapping = |model: &ModelUnlit, t: usize| {
+        if material(model, t) == -1 {
+            return None;
+        }
+        let coord = model
+            .triangle_texture_coords
+            .as_ref()
+            .map_or(-1, |coords| coords[t]);
+        usize::try_from(coord)
+            .ok()
+            .and_then(|coord| model.texture_mapping(coord))
+    };
+    compare(
+        "Texture mappings",
+        triangle_count,
+        decoded_triangle_count,
+        &|t| texture_mapping(model, t) == texture_mapping(&decoded, t),
+    );
+
+    ReencodeReport {
+        size: data.len(),
+        losses,
+        diffs,
+    }
+}
+
+/// Counts the elements of a property that aren't equal, elements only one of the models has
+/// count as changed. None if nothing changed.
+fn diff_property(
+    name: &'static str,
+    total: usize,
+    decoded_total: usize,
+    equal: &dyn Fn(usize) -> bool,
+) -> Option<PropertyDiff> {
+    let compared = total.min(decoded_total);
+    let changed_indices = (0..compared).filter(|&i| !equal(i));
+    let mut first = None;
+    let mut changed = total.abs_diff(decoded_total);
+    for i in changed_indices {
+        first.get_or_insert(i);
+        changed += 1;
+    }
+    (changed > 0).then(|| PropertyDiff {
+        name,
+        changed,
+        total,
+        first: first.unwrap_or(compared),
+    })
+}
@@ -1,6 +1,10 @@
 use std::sync::Arc;
 
-use crate::runetek5::io::packet::Packet;
+use crate::runetek5::{
+    error::Runetek5Error,
+    io::packet::{Packet, PacketMut},
+    js5::Js5,
+};
 
 #[derive(Debug)]
 pub struct SpriteData {
@@ -16,10 +20,10 @@ pub struct SpriteData {
 }
 
 impl SpriteData {
-    pub fn decode(data: &[u8]) -> Self {
-        let mut buf = &data[data.len() - 2..];
-
-        let sprite_count = buf.g2() as usize;
+    pub fn decode(data: &[u8]) -> Result<Self, Runetek5Error> {
+        const TRUNCATED: Runetek5Error = Runetek5Error::Truncated("sprite");
+        let _span = tracing::info_span!("sprite_decode", len = data.len()).entered();
+        let sprite_count = Self::peek_sprite_count(data).ok_or(TRUNCATED)? as usize;
 
         let mut offsets_x = vec![0; sprite_count];
         let mut offsets_y = vec![0; sprite_count];
@@ -27,7 +31,12 @@ impl SpriteData {
         let mut heights = vec![0; sprite_count];
         let mut sprite_pixels: Vec<Vec<u8>> = Vec::with_capacity(sprite_count);
 
-        buf = &data[data.len() - 7 - sprite_count * 8..];
+        // The header sits at the end, after the palette and before the frame sizes.
+        let header_start = data
+            .len()
+            .checked_sub(7 + sprite_count * 8)
+            .ok_or(TRUNCATED)?;
+        let mut buf = &data[header_start..];
 
         let width = buf.g2();
         let height = buf.g2();
@@ -46,7 +55,10 @@ impl SpriteData {
             heights[i] = buf.g2();
         }
 
-        buf = &data[data.len() - 7 - sprite_count * 8 - (palette_size - 1) * 3..];
+        let palette_start = header_start
+            .checked_sub((palette_size - 1) * 3)
+            .ok_or(TRUNCATED)?;
+        buf = &data[palette_start..];
 
         let mut palette = vec![0; palette_size];
         for i in 1..palette_size {
@@ -56,14 +68,17 @@ impl SpriteData {
             }
         }
 
-        buf = &data;
+        buf = &data[..palette_start];
 
         for i in 0..sprite_count {
             let width = widths[i] as usize;
             let height = heights[i] as usize;
             let pixel_count = width * height;
+            let pixel_order = buf.try_g1().ok_or(TRUNCATED)?;
+            if buf.len() < pixel_count {
+                return Err(TRUNCATED);
+            }
             let mut pixels = vec![0; pixel_count];
-            let pixel_order = buf.g1();
             if pixel_order == 0 {
                 // row first
                 for j in 0..pixel_count {
@@ -80,7 +95,7 @@ impl SpriteData {
             sprite_pixels.push(pixels);
         }
 
-        Self {
+        Ok(Self {
             sprite_count: sprite_count as u16,
             width,
             height,
@@ -90,13 +105,69 @@ impl SpriteData {
             heights,
             palette: Arc::new(palette),
             pixels: sprite_pixels,
+        })
+    }
+
+    /// Reads the number of frames from the end of the data without decoding the sprite.
+    pub fn peek_sprite_count(data: &[u8]) -> Option<u16> {
+        let mut buf = data.get(data.len().checked_sub(2)?..)?;
+        buf.try_g2()
+    }
+
+    /// A sprite with one frame covering the whole canvas. Palette entry 0 is transparent and
+    /// at most 256 entries are allowed.
+    pub fn new_single(width: u16, height: u16, palette: Vec<u32>, pixels: Vec<u8>) -> Self {
+        assert!(!palette.is_empty() && palette.len() <= 256);
+        assert_eq!(pixels.len(), width as usize * height as usize);
+        Self {
+            sprite_count: 1,
+            width,
+            height,
+            offsets_x: vec![0],
+            offsets_y: vec![0],
+            widths: vec![width],
+            heights: vec![height],
+            palette: Arc::new(palette),
+            pixels: vec![pixels],
         }
     }
 
-    pub fn decode_into_pix8s(data: &[u8]) -> Vec<Pix8> {
-        let sprite_data = SpriteData::decode(data);
+    /// Encodes the sprite in the format read by [`SpriteData::decode`], with row first pixels.
+    pub fn encode(&self) -> Vec<u8> {
+        let sprite_count = self.sprite_count as usize;
+        let palette_size = self.palette.len();
+        let pixel_count: usize = self.pixels.iter().map(Vec::len).sum();
+        let mut buf = Vec::with_capacity(pixel_count + sprite_count * 9 + palette_size * 3 + 7);
+
+        for pixels in &self.pixels {
+            buf.p1(0);
+            buf.extend_from_slice(pixels);
+        }
+        for &rgb in &self.palette[1..] {
+            // 0 would decode as transparent, so black is stored as 1.
+            buf.p3(rgb.max(1));
+        }
+        buf.p2(self.width);
+        buf.p2(self.height);
+        buf.p1((palette_size - 1) as u8);
+        for values in [
+            &self.offsets_x,
+            &self.offsets_y,
+            &self.widths,
+            &self.heights,
+        ] {
+            for &value in values {
+                buf.p2(value);
+            }
+        }
+        buf.p2(self.sprite_count);
+        buf
+    }
 
-        sprite_data
+    pub fn decode_into_pix8s(data: &[u8]) -> Result<Vec<Pix8>, Runetek5Error> {
+        let sprite_data = SpriteData::decode(data)?;
+
+        Ok(sprite_data
             .pixels
             .into_iter()
             .zip(sprite_data.offsets_x.into_iter().zip(sprite_data.offsets_y))
@@ -113,13 +184,16 @@ impl SpriteData {
                     pixels,
                 )
             })
-            .collect()
+            .collect())
     }
 
-    pub fn decode_into_pix8(data: &[u8]) -> Pix8 {
-        let mut sprite_data = SpriteData::decode(data);
+    pub fn decode_into_pix8(data: &[u8]) -> Result<Pix8, Runetek5Error> {
+        let mut sprite_data = SpriteData::decode(data)?;
+        if sprite_data.sprite_count == 0 {
+            return Err(Runetek5Error::Invalid("sprite without frames"));
+        }
 
-        Pix8::from_data(
+        Ok(Pix8::from_data(
             sprite_data.width,
             sprite_data.height,
             sprite_data.offsets_x[0],
@@ -128,7 +202,50 @@ impl SpriteData {
             sprite_data.heights[0],
             sprite_data.palette.clone(),
             std::mem::take(&mut sprite_data.pixels[0]),
-        )
+        ))
+    }
+}
+
+/// A sprite group of world map icons, found by the name the client loads it with. Each frame is
+/// one icon, indexed by the map function or map scene id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapIconSheet {
+    /// Icons of map functions like banks, shops and quest starts.
+    Function,
+    /// Icons drawn for scenery on the map, like trees and rocks.
+    Scene,
+}
+
+impl MapIconSheet {
+    pub const ALL: [MapIconSheet; 2] = [MapIconSheet::Function, MapIconSheet::Scene];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            MapIconSheet::Function => "Map functions",
+            MapIconSheet::Scene => "Map scenes",
+        }
+    }
+
+    /// The name of the sprite group, the index only stores its hash.
+    pub fn group_name(&self) -> &'static str {
+        match self {
+            MapIconSheet::Function => "mapfunction",
+            MapIconSheet::Scene => "mapscene",
+        }
+    }
+
+    /// The sprite group holding the icons, None if the archive isn't named or lacks it.
+    pub fn group_id(&self, sprite_js5: &Js5) -> Option<u32> {
+        sprite_js5.index.get_group_id_by_name(self.group_name())
+    }
+
+    /// The icons, normalized to their full size. None while the group is being fetched or if the
+    /// archive lacks it or it can't be decoded.
+    pub fn load_icons(&self, sprite_js5: &Js5) -> Option<Vec<Pix8>> {
+        let data = sprite_js5.get_file(self.group_id(sprite_js5)?, 0)?;
+        let mut icons = SpriteData::decode_into_pix8s(&data).ok()?;
+        icons.iter_mut().for_each(Pix8::normalize);
+        Some(icons)
     }
 }
 
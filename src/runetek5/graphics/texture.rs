@@ -1,8 +1,11 @@
-use std::sync::Arc;
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
 
-use crate::runetek5::{io::packet::Packet, js5::Js5};
+use crate::runetek5::{error::Runetek5Error, io::packet::Packet, js5::Js5};
 
-use super::sprite::SpriteData;
+use super::{material_rules::MaterialRules, sprite::SpriteData};
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum AlphaMode {
@@ -11,6 +14,24 @@ pub enum AlphaMode {
     Blend,
 }
 
+/// How textures are sampled between texels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextureFilter {
+    /// Blocky texels like the software renderer.
+    Nearest,
+    #[default]
+    Linear,
+}
+
+impl TextureFilter {
+    pub fn label(&self) -> &'static str {
+        match self {
+            TextureFilter::Nearest => "Nearest (authentic)",
+            TextureFilter::Linear => "Linear (smooth)",
+        }
+    }
+}
+
 pub struct MaterialInfo {
     /// If true, triangles with this material will only render the texture in high detail mode.
     /// If false and standard_detail_only is true, triangles with this material will never render.
@@ -20,20 +41,49 @@ pub struct MaterialInfo {
     pub alpha_mode: AlphaMode,
     pub effect_id: u8,
     pub effect_config0: u8,
+    /// HSL colour used for the material when textures are disabled.
+    pub average_colour: u16,
+    pub anim_direction: u8,
+    pub anim_speed: u8,
 }
 
-impl Default for MaterialInfo {
-    fn default() -> Self {
+impl MaterialInfo {
+    pub const DEFAULT: MaterialInfo = MaterialInfo {
+        high_detail: false,
+        standard_detail_only: false,
+        alpha_mode: AlphaMode::Opaque,
+        effect_id: 0,
+        effect_config0: 0,
+        average_colour: 0,
+        anim_direction: 0,
+        anim_speed: 0,
+    };
+
+    fn from_texture(texture_data: &TextureData) -> Self {
+        let alpha_mode = match &texture_data.alpha_mode {
+            Some(alpha_mode) => alpha_mode.clone(),
+            None if texture_data.opaque => AlphaMode::Opaque,
+            None => AlphaMode::Blend,
+        };
         Self {
-            high_detail: false,
-            standard_detail_only: false,
-            alpha_mode: AlphaMode::Opaque,
-            effect_id: 0,
-            effect_config0: 0,
+            high_detail: texture_data.high_detail,
+            standard_detail_only: texture_data.standard_detail_only,
+            alpha_mode,
+            effect_id: texture_data.effect_id,
+            effect_config0: texture_data.effect_config0,
+            average_colour: texture_data.average_colour,
+            anim_direction: texture_data.anim_direction,
+            anim_speed: texture_data.anim_speed,
         }
     }
 }
 
+impl Default for MaterialInfo {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct TextureData {
     pub average_colour: u16,
@@ -42,34 +92,73 @@ pub struct TextureData {
     pub colour_mask: u32,
     pub anim_direction: u8,
     pub anim_speed: u8,
+    /// See [`MaterialInfo::high_detail`].
+    pub high_detail: bool,
+    /// See [`MaterialInfo::standard_detail_only`].
+    pub standard_detail_only: bool,
+    pub effect_id: u8,
+    pub effect_config0: u8,
+    /// How the HD config blends the texture. None leaves it to the opaque flag.
+    pub alpha_mode: Option<AlphaMode>,
 }
 
 impl TextureData {
-    pub fn decode(data: &[u8]) -> Self {
+    const FLAG_HIGH_DETAIL: u8 = 0x1;
+    const FLAG_STANDARD_DETAIL_ONLY: u8 = 0x2;
+
+    /// Decodes a texture definition. Textures made of more than one sprite aren't supported.
+    /// Caches with HD textures follow the definition with the HD config, older ones end there
+    /// and get the defaults.
+    pub fn decode(data: &[u8]) -> Result<Self, Runetek5Error> {
+        const TRUNCATED: Runetek5Error = Runetek5Error::Truncated("texture");
+        let _span = tracing::info_span!("texture_decode", len = data.len()).entered();
         let mut buf = data;
 
-        let average_colour = buf.g2();
-        let opaque = buf.g1() == 1;
+        let average_colour = buf.try_g2().ok_or(TRUNCATED)?;
+        let opaque = buf.try_g1().ok_or(TRUNCATED)? == 1;
 
-        let sprite_count = buf.g1();
+        let sprite_count = buf.try_g1().ok_or(TRUNCATED)?;
         if sprite_count != 1 {
-            panic!("Texture sprite_count != 1");
+            return Err(Runetek5Error::Unsupported("texture with several sprites"));
         }
 
-        let sprite_id = buf.g2();
-        let colour_mask = buf.g4();
+        let sprite_id = buf.try_g2().ok_or(TRUNCATED)?;
+        let colour_mask = buf.try_g4().ok_or(TRUNCATED)?;
 
-        let anim_direction = buf.g1();
-        let anim_speed = buf.g1();
+        let anim_direction = buf.try_g1().ok_or(TRUNCATED)?;
+        let anim_speed = buf.try_g1().ok_or(TRUNCATED)?;
 
-        Self {
+        let mut texture = Self {
             average_colour,
             opaque,
             sprite_id,
             colour_mask,
             anim_direction,
             anim_speed,
+            high_detail: false,
+            standard_detail_only: false,
+            effect_id: 0,
+            effect_config0: 0,
+            alpha_mode: None,
+        };
+        if buf.is_empty() {
+            return Ok(texture);
         }
+
+        const TRUNCATED_HD: Runetek5Error = Runetek5Error::Truncated("texture HD config");
+        let flags = buf.try_g1().ok_or(TRUNCATED_HD)?;
+        texture.high_detail = flags & Self::FLAG_HIGH_DETAIL != 0;
+        texture.standard_detail_only = flags & Self::FLAG_STANDARD_DETAIL_ONLY != 0;
+        texture.effect_id = buf.try_g1().ok_or(TRUNCATED_HD)?;
+        texture.effect_config0 = buf.try_g1().ok_or(TRUNCATED_HD)?;
+        texture.alpha_mode = match buf.try_g1().ok_or(TRUNCATED_HD)? {
+            0 => None,
+            1 => Some(AlphaMode::Opaque),
+            2 => Some(AlphaMode::Cutout),
+            3 => Some(AlphaMode::Blend),
+            _ => return Err(Runetek5Error::Invalid("texture blend mode")),
+        };
+        Ok(texture)
     }
 }
 
@@ -86,9 +175,29 @@ pub fn brighten_rgb(rgb: u32, brightness: f64) -> u32 {
     (new_r << 16) | (new_g << 8) | new_b
 }
 
+/// Magenta and black checks drawn in place of textures without pixels.
+pub fn placeholder_pixels(width: u16, height: u16) -> Vec<u32> {
+    const CHECK_SIZE: usize = 16;
+    let width = width as usize;
+    let mut pixels = vec![0xff000000; width * height as usize];
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        let (x, y) = (i % width, i / width);
+        if (x / CHECK_SIZE + y / CHECK_SIZE) % 2 == 0 {
+            *pixel = 0xffff00ff;
+        }
+    }
+    pixels
+}
+
 pub struct TextureProvider {
     pub sprite_js5: Arc<Js5>,
     pub textures: Vec<Option<TextureData>>,
+    /// Material info per texture id, built once since lighting looks it up per triangle.
+    infos: Vec<Option<MaterialInfo>>,
+    /// Sprite ids of defined textures whose sprite couldn't be loaded or decoded, by texture id.
+    missing_sprites: Mutex<BTreeMap<u32, u32>>,
+    /// Substitutions applied to the materials of every model as it is lit.
+    material_rules: MaterialRules,
 }
 
 impl TextureProvider {
@@ -97,15 +206,25 @@ impl TextureProvider {
         if let Some(texture_ids) = texture_js5.get_file_ids(0) {
             for &texture_id in texture_ids.iter() {
                 if let Some(data) = texture_js5.get_file(0, texture_id) {
-                    let texture_data = TextureData::decode(&data);
-                    textures[texture_id as usize] = Some(texture_data);
+                    match TextureData::decode(&data) {
+                        Ok(texture_data) => textures[texture_id as usize] = Some(texture_data),
+                        Err(err) => log::warn!("Failed to decode texture {texture_id}: {err}"),
+                    }
                 }
             }
         }
 
+        let infos = textures
+            .iter()
+            .map(|texture| texture.as_ref().map(MaterialInfo::from_texture))
+            .collect();
+
         Self {
             sprite_js5,
             textures,
+            infos,
+            missing_sprites: Mutex::new(BTreeMap::new()),
+            material_rules: MaterialRules::default(),
         }
     }
 
@@ -135,22 +254,48 @@ impl TextureProvider {
         loaded_sprite_count * 100 / total_sprite_count
     }
 
-    pub fn get_info(&self, id: u32) -> Option<MaterialInfo> {
-        let texture_data = self.textures[id as usize].as_ref()?;
-        let alpha_mode = if texture_data.opaque {
-            AlphaMode::Opaque
-        } else {
-            AlphaMode::Blend
-        };
-        Some(MaterialInfo {
-            standard_detail_only: false,
-            high_detail: false,
-            alpha_mode,
-            effect_id: 0,
-            effect_config0: 0,
-        })
+    pub fn get_info(&self, id: u32) -> Option<&MaterialInfo> {
+        self.infos.get(id as usize)?.as_ref()
+    }
+
+    pub fn material_rules(&self) -> &MaterialRules {
+        &self.material_rules
+    }
+
+    /// Sets the substitutions applied to models lit from now on, models lit before keep theirs.
+    pub fn set_material_rules(&mut self, material_rules: MaterialRules) {
+        self.material_rules = material_rules;
+    }
+
+    /// Textures whose sprite couldn't be loaded or decoded when their pixels were last asked for,
+    /// as (texture id, sprite id).
+    pub fn get_missing_sprites(&self) -> Vec<(u32, u32)> {
+        let missing_sprites = self.missing_sprites.lock().unwrap();
+        missing_sprites
+            .iter()
+            .map(|(&texture_id, &sprite_id)| (texture_id, sprite_id))
+            .collect()
+    }
+
+    pub fn is_sprite_missing(&self, id: u32) -> bool {
+        self.missing_sprites.lock().unwrap().contains_key(&id)
+    }
+
+    /// Like [`TextureProvider::get_pixels_argb`], falling back to [`placeholder_pixels`].
+    pub fn get_pixels_or_placeholder(
+        &self,
+        id: u32,
+        width: u16,
+        height: u16,
+        flip_h: bool,
+        brightness: f64,
+    ) -> Vec<u32> {
+        self.get_pixels_argb(id, width, height, flip_h, brightness)
+            .unwrap_or_else(|| placeholder_pixels(width, height))
     }
 
+    /// The texture's sprite as ARGB, resampled to `width` by `height` whatever its own size.
+    /// None if the texture or its sprite is missing.
     pub fn get_pixels_argb(
         &self,
         id: u32,
@@ -159,10 +304,27 @@ impl TextureProvider {
         flip_h: bool,
         brightness: f64,
     ) -> Option<Vec<u32>> {
-        let texture_data = self.textures[id as usize].as_ref()?;
+        let texture_data = self.textures.get(id as usize)?.as_ref()?;
 
-        let sprite_data = self.sprite_js5.get_file(texture_data.sprite_id as u32, 0)?;
-        let mut pix8 = SpriteData::decode_into_pix8(&sprite_data);
+        let sprite_id = texture_data.sprite_id as u32;
+        let Some(sprite_data) = self.sprite_js5.get_file(sprite_id, 0) else {
+            let mut missing_sprites = self.missing_sprites.lock().unwrap();
+            if missing_sprites.insert(id, sprite_id).is_none() {
+                log::warn!("Sprite {sprite_id} of texture {id} is missing");
+            }
+            return None;
+        };
+        let mut pix8 = match SpriteData::decode_into_pix8(&sprite_data) {
+            Ok(pix8) => pix8,
+            Err(err) => {
+                let mut missing_sprites = self.missing_sprites.lock().unwrap();
+                if missing_sprites.insert(id, sprite_id).is_none() {
+                    log::warn!("Sprite {sprite_id} of texture {id} couldn't be decoded: {err}");
+                }
+                return None;
+            }
+        };
+        self.missing_sprites.lock().unwrap().remove(&id);
         pix8.normalize();
 
         let pixel_count = width as usize * height as usize;
@@ -175,21 +337,19 @@ impl TextureProvider {
             *rgb = alpha << 24 | brighten_rgb(*rgb, brightness as f64);
         });
 
-        if width == pix8.sub_width {
-            pix8.pixels
-                .iter()
-                .enumerate()
-                .for_each(|(i, &palette_index)| {
-                    pixels[i] = palette[palette_index as usize];
-                });
-        } else if width == 128 && pix8.sub_width == 64 {
-            let mut pixel_index = 0;
-            for x in 0..width as usize {
-                for y in 0..height as usize {
-                    let src_index = ((x >> 1) << 6) + (y >> 1);
-                    pixels[pixel_index] = palette[pix8.pixels[src_index] as usize];
-                    pixel_index += 1;
-                }
+        // Nearest-neighbour resampling at pixel centres, so any sprite size fills the requested
+        // size. Doubling takes each sprite pixel twice, like the client's 64 to 128 upscale.
+        let (src_width, src_height) = (pix8.sub_width as usize, pix8.sub_height as usize);
+        if src_width == 0 || src_height == 0 {
+            return Some(pixels);
+        }
+        let (width, height) = (width as usize, height as usize);
+        for y in 0..height {
+            let src_y = (2 * y + 1) * src_height / (2 * height);
+            let src_row = &pix8.pixels[src_y * src_width..(src_y + 1) * src_width];
+            for x in 0..width {
+                let src_x = (2 * x + 1) * src_width / (2 * width);
+                pixels[y * width + x] = palette[src_row[src_x] as usize];
             }
         }
 
@@ -0,0 +1,387 @@
+//! Interface components from the interface archive, one group per interface and one file per
+//! component. Only the fields that place and draw a component are decoded; scripts, actions and
+//! hover states that follow them are left unread.
+
+use crate::runetek5::{io::packet::Packet, js5::Js5};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ComponentType {
+    /// Groups its children, optionally scrolling them.
+    #[default]
+    Layer,
+    /// A grid of item slots, only in the old format.
+    Inventory,
+    Rectangle,
+    Text,
+    Graphic,
+    Model,
+    /// Item names in a grid, only in the old format.
+    InventoryText,
+    /// Only in the old format.
+    Tooltip,
+    Line,
+    Unknown(u8),
+}
+
+impl ComponentType {
+    fn from_id(id: u8) -> Self {
+        match id {
+            0 => ComponentType::Layer,
+            2 => ComponentType::Inventory,
+            3 => ComponentType::Rectangle,
+            4 => ComponentType::Text,
+            5 => ComponentType::Graphic,
+            6 => ComponentType::Model,
+            7 => ComponentType::InventoryText,
+            8 => ComponentType::Tooltip,
+            9 => ComponentType::Line,
+            id => ComponentType::Unknown(id),
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ComponentType::Layer => "Layer",
+            ComponentType::Inventory => "Inventory",
+            ComponentType::Rectangle => "Rectangle",
+            ComponentType::Text => "Text",
+            ComponentType::Graphic => "Graphic",
+            ComponentType::Model => "Model",
+            ComponentType::InventoryText => "Inventory text",
+            ComponentType::Tooltip => "Tooltip",
+            ComponentType::Line => "Line",
+            ComponentType::Unknown(_) => "Unknown",
+        }
+    }
+}
+
+/// How a size or position of a new format component depends on its parent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LayoutModes {
+    pub width: i8,
+    pub height: i8,
+    pub x: i8,
+    pub y: i8,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Component {
+    /// The interface id in the high 16 bits and the component index in the low 16 bits.
+    pub id: u32,
+    /// Decoded from the new format rather than the old one.
+    pub if3: bool,
+    pub component_type: ComponentType,
+    pub content_type: u16,
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    pub modes: LayoutModes,
+    /// The id of the layer this component is in, None for top level components.
+    pub layer: Option<u32>,
+    pub hidden: bool,
+    pub scroll_width: i32,
+    pub scroll_height: i32,
+    /// 0xRRGGBB of rectangles, text and lines.
+    pub colour: u32,
+    pub filled: bool,
+    /// 0 is opaque, 255 fully transparent.
+    pub transparency: u8,
+    pub graphic: Option<u32>,
+    pub graphic_tiling: bool,
+    pub model: Option<u32>,
+    pub model_x_offset: i32,
+    pub model_y_offset: i32,
+    pub model_x_angle: u16,
+    pub model_y_angle: u16,
+    pub model_z_angle: u16,
+    pub model_zoom: u16,
+    pub font: Option<u32>,
+    pub text: String,
+    pub text_shadowed: bool,
+    /// 0 left or top, 1 centre, 2 right or bottom.
+    pub h_align: u8,
+    pub v_align: u8,
+    pub line_height: u8,
+    pub line_width: u8,
+    /// Lines go from the top left to the bottom right, or from the bottom left to the top right.
+    pub line_direction: bool,
+}
+
+/// Old format ids of 65535 and new format ids of -1 mean none.
+fn optional_id(id: u32) -> Option<u32> {
+    (id != u16::MAX as u32 && id != u32::MAX).then_some(id)
+}
+
+impl Component {
+    /// Decodes a component, None if the data ends early.
+    pub fn decode(id: u32, data: &[u8]) -> Option<Self> {
+        let mut component = Self {
+            id,
+            ..Default::default()
+        };
+        let mut buf = data;
+        if buf.first() == Some(&0xff) {
+            buf.skip(1);
+            component.decode_if3(&mut buf)?;
+        } else {
+            component.decode_if1(&mut buf)?;
+        }
+        Some(component)
+    }
+
+    fn decode_layer(&mut self, buf: &mut &[u8]) -> Option<()> {
+        let layer = buf.try_g2()? as u32;
+        self.layer = optional_id(layer).map(|layer| layer | (self.id & !0xffff));
+        Some(())
+    }
+
+    fn decode_if1(&mut self, buf: &mut &[u8]) -> Option<()> {
+        let type_id = buf.try_g1()?;
+        self.component_type = ComponentType::from_id(type_id);
+        let _button_type = buf.try_g1()?;
+        self.content_type = buf.try_g2()?;
+        self.x = buf.try_g2s()? as i32;
+        self.y = buf.try_g2s()? as i32;
+        self.width = buf.try_g2()? as i32;
+        self.height = buf.try_g2()? as i32;
+        self.transparency = buf.try_g1()?;
+        self.decode_layer(buf)?;
+        let _mouse_over_redirect = buf.try_g2()?;
+
+        let comparison_count = buf.try_g1()? as usize;
+        buf.try_skip(comparison_count * 3)?;
+        let script_count = buf.try_g1()?;
+        for _ in 0..script_count {
+            let length = buf.try_g2()? as usize;
+            buf.try_skip(length * 2)?;
+        }
+
+        match type_id {
+            0 => {
+                self.scroll_height = buf.try_g2()? as i32;
+                self.hidden = buf.try_g1()? == 1;
+            }
+            1 => buf.try_skip(3)?,
+            // Nothing that follows an inventory is drawn.
+            2 => return Some(()),
+            3 => self.filled = buf.try_g1()? == 1,
+            _ => {}
+        }
+        if type_id == 1 || type_id == 4 {
+            self.h_align = buf.try_g1()?;
+            self.v_align = buf.try_g1()?;
+            self.line_height = buf.try_g1()?;
+            self.font = optional_id(buf.try_g2()? as u32);
+            self.text_shadowed = buf.try_g1()? == 1;
+        }
+        if type_id == 4 {
+            self.text = buf.try_get_str_cp1252_to_utf8()?;
+            let _active_text = buf.try_get_str_cp1252_to_utf8()?;
+        }
+        if type_id == 1 || type_id == 3 || type_id == 4 {
+            self.colour = buf.try_g4()?;
+        }
+        if type_id == 3 || type_id == 4 {
+            // Active and mouse over colours.
+            buf.try_skip(12)?;
+        }
+        if type_id == 5 {
+            self.graphic = optional_id(buf.try_g4()?);
+            let _active_graphic = buf.try_g4()?;
+        }
+        if type_id == 6 {
+            self.model = optional_id(buf.try_g2()? as u32);
+            let _active_model = buf.try_g2()?;
+            let _animation = buf.try_g2()?;
+            let _active_animation = buf.try_g2()?;
+            self.model_zoom = buf.try_g2()?;
+            self.model_x_angle = buf.try_g2()?;
+            self.model_y_angle = buf.try_g2()?;
+        }
+        Some(())
+    }
+
+    fn decode_if3(&mut self, buf: &mut &[u8]) -> Option<()> {
+        self.if3 = true;
+        let mut type_id = buf.try_g1()?;
+        if type_id & 0x80 != 0 {
+            type_id &= 0x7f;
+            let _name = buf.try_get_str_cp1252_to_utf8()?;
+        }
+        self.component_type = ComponentType::from_id(type_id);
+        self.content_type = buf.try_g2()?;
+        self.x = buf.try_g2s()? as i32;
+        self.y = buf.try_g2s()? as i32;
+        self.width = buf.try_g2()? as i32;
+        // Lines may go up, so their height is signed.
+        self.height = if type_id == 9 {
+            buf.try_g2s()? as i32
+        } else {
+            buf.try_g2()? as i32
+        };
+        self.modes = LayoutModes {
+            width: buf.try_g1s()?,
+            height: buf.try_g1s()?,
+            x: buf.try_g1s()?,
+            y: buf.try_g1s()?,
+        };
+        self.decode_layer(buf)?;
+        self.hidden = buf.try_g1()? == 1;
+
+        match type_id {
+            0 => {
+                self.scroll_width = buf.try_g2()? as i32;
+                self.scroll_height = buf.try_g2()? as i32;
+                let _no_click_through = buf.try_g1()?;
+            }
+            3 => {
+                self.colour = buf.try_g4()?;
+                self.filled = buf.try_g1()? == 1;
+                self.transparency = buf.try_g1()?;
+            }
+            4 => {
+                self.font = optional_id(buf.try_g2()? as u32);
+                self.text = buf.try_get_str_cp1252_to_utf8()?;
+                self.line_height = buf.try_g1()?;
+                self.h_align = buf.try_g1()?;
+                self.v_align = buf.try_g1()?;
+                self.text_shadowed = buf.try_g1()? == 1;
+                self.colour = buf.try_g4()?;
+            }
+            5 => {
+                self.graphic = optional_id(buf.try_g4()?);
+                let _angle = buf.try_g2()?;
+                self.graphic_tiling = buf.try_g1()? == 1;
+                self.transparency = buf.try_g1()?;
+            }
+            6 => {
+                self.model = optional_id(buf.try_g2()? as u32);
+                self.model_x_offset = buf.try_g2s()? as i32;
+                self.model_y_offset = buf.try_g2s()? as i32;
+                self.model_x_angle = buf.try_g2()?;
+                self.model_z_angle = buf.try_g2()?;
+                self.model_y_angle = buf.try_g2()?;
+                self.model_zoom = buf.try_g2()?;
+            }
+            9 => {
+                self.line_width = buf.try_g1()?;
+                self.colour = buf.try_g4()?;
+                self.line_direction = buf.try_g1()? == 1;
+            }
+            _ => {}
+        }
+        Some(())
+    }
+}
+
+/// The components of an interface, None while its group is still being fetched. Components that
+/// fail to decode are left out.
+pub fn load_interface(interface_js5: &Js5, interface_id: u32) -> Option<Vec<Component>> {
+    let file_ids = interface_js5.get_file_ids(interface_id)?;
+    let mut components = Vec::with_capacity(file_ids.len());
+    for &file_id in file_ids.iter() {
+        let data = interface_js5.get_file(interface_id, file_id)?;
+        let id = (interface_id << 16) | file_id;
+        if let Some(component) = Component::decode(id, &data) {
+            components.push(component);
+        }
+    }
+    Some(components)
+}
+
+/// Where a component ends up on screen, relative to the root of the interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ComponentBounds {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    /// The area of the layers the component is in as min and max x and y, it is clipped to it.
+    pub clip: (i32, i32, i32, i32),
+}
+
+/// Resolves a new format size against the parent's size.
+fn resolve_size(size: i32, mode: i8, parent_size: i32) -> i32 {
+    match mode {
+        1 => parent_size - size,
+        2 => (parent_size * size) >> 14,
+        _ => size,
+    }
+}
+
+/// Resolves a new format position against the parent's size.
+fn resolve_position(position: i32, size: i32, mode: i8, parent_size: i32) -> i32 {
+    match mode {
+        1 => (parent_size - size) / 2 + position,
+        2 => parent_size - size - position,
+        3 => (parent_size * position) >> 14,
+        4 => (parent_size - size) / 2 + ((parent_size * position) >> 14),
+        5 => parent_size - size - ((parent_size * position) >> 14),
+        _ => position,
+    }
+}
+
+/// Lays out the components in a root of the given size, returning their bounds in drawing order,
+/// parents before children. Hidden components and their children are left out.
+pub fn layout(
+    components: &[Component],
+    root_width: i32,
+    root_height: i32,
+) -> Vec<(usize, ComponentBounds)> {
+    let mut bounds = Vec::with_capacity(components.len());
+    let root = (0, 0, root_width, root_height);
+    layout_children(components, None, root, root, &mut bounds);
+    bounds
+}
+
+fn layout_children(
+    components: &[Component],
+    layer: Option<u32>,
+    parent: (i32, i32, i32, i32),
+    clip: (i32, i32, i32, i32),
+    bounds: &mut Vec<(usize, ComponentBounds)>,
+) {
+    let (parent_x, parent_y, parent_width, parent_height) = parent;
+    for (index, component) in components.iter().enumerate() {
+        if component.layer != layer || component.hidden {
+            continue;
+        }
+        let width = resolve_size(component.width, component.modes.width, parent_width);
+        let height = resolve_size(component.height, component.modes.height, parent_height);
+        let x = parent_x + resolve_position(component.x, width, component.modes.x, parent_width);
+        let y = parent_y + resolve_position(component.y, height, component.modes.y, parent_height);
+        bounds.push((
+            index,
+            ComponentBounds {
+                x,
+                y,
+                width,
+                height,
+                clip,
+            },
+        ));
+        if component.component_type == ComponentType::Layer {
+            let child_clip = (
+                x.max(clip.0),
+                y.max(clip.1),
+                (x + width).min(clip.2),
+                (y + height).min(clip.3),
+            );
+            // Children are laid out in the scrolled area, scrolled to the top left.
+            let child_parent = (
+                x,
+                y,
+                component.scroll_width.max(width),
+                component.scroll_height.max(height),
+            );
+            layout_children(
+                components,
+                Some(component.id),
+                child_parent,
+                child_clip,
+                bounds,
+            );
+        }
+    }
+}
@@ -16,6 +16,17 @@ fn u8_to_cp1252_ascii(c: u8) -> char {
     }
 }
 
+/// Maps a char to its cp1252 byte, the inverse of how strings are read.
+pub fn char_to_cp1252(c: char) -> Option<u8> {
+    match c as u32 {
+        0..128 | 160..256 => Some(c as u8),
+        _ => CP1252_ASCII_EXTENSION_CHARS
+            .iter()
+            .position(|&extension| extension == c && c != '\u{0000}')
+            .map(|index| index as u8 + 128),
+    }
+}
+
 pub trait Packet: Buf + Sized {
     #[inline]
     fn skip(&mut self, n: usize) {
@@ -120,6 +131,113 @@ pub trait Packet: Buf + Sized {
         }
         chars.into_iter().collect()
     }
+
+    /// Returns true if at least `n` bytes are left to read.
+    #[inline]
+    fn can_read(&self, n: usize) -> bool {
+        Packet::remaining(self) >= n
+    }
+
+    #[inline]
+    fn try_skip(&mut self, n: usize) -> Option<()> {
+        self.can_read(n).then(|| self.skip(n))
+    }
+
+    #[inline]
+    fn try_g1(&mut self) -> Option<u8> {
+        self.can_read(1).then(|| self.g1())
+    }
+
+    #[inline]
+    fn try_g1s(&mut self) -> Option<i8> {
+        self.can_read(1).then(|| self.g1s())
+    }
+
+    #[inline]
+    fn try_g2(&mut self) -> Option<u16> {
+        self.can_read(2).then(|| self.g2())
+    }
+
+    #[inline]
+    fn try_g2s(&mut self) -> Option<i16> {
+        self.can_read(2).then(|| self.g2s())
+    }
+
+    #[inline]
+    fn try_g3(&mut self) -> Option<u32> {
+        self.can_read(3).then(|| self.g3())
+    }
+
+    #[inline]
+    fn try_g4(&mut self) -> Option<u32> {
+        self.can_read(4).then(|| self.g4())
+    }
+
+    #[inline]
+    fn try_g4s(&mut self) -> Option<i32> {
+        self.can_read(4).then(|| self.g4s())
+    }
+
+    /// Reads a big endian IEEE 754 float.
+    #[inline]
+    fn try_g4f(&mut self) -> Option<f32> {
+        self.try_g4().map(f32::from_bits)
+    }
+
+    #[inline]
+    fn try_g8(&mut self) -> Option<u64> {
+        self.can_read(8).then(|| self.g8())
+    }
+
+    #[inline]
+    fn try_g8s(&mut self) -> Option<i64> {
+        self.can_read(8).then(|| self.g8s())
+    }
+
+    #[inline]
+    fn try_get_smart_1_or_2(&mut self) -> Option<i32> {
+        let len = if *self.chunk().first()? < 128 { 1 } else { 2 };
+        self.can_read(len).then(|| self.get_smart_1_or_2())
+    }
+
+    #[inline]
+    fn try_get_smart_1_or_2s(&mut self) -> Option<i32> {
+        let len = if *self.chunk().first()? < 128 { 1 } else { 2 };
+        self.can_read(len).then(|| self.get_smart_1_or_2s())
+    }
+
+    #[inline]
+    fn try_get_smart_1_or_2_null(&mut self) -> Option<i32> {
+        Some(self.try_get_smart_1_or_2()? - 1)
+    }
+
+    #[inline]
+    fn try_get_smart_2_or_4(&mut self) -> Option<u32> {
+        let len = if *self.chunk().first()? & 0x80 == 0x80 {
+            4
+        } else {
+            2
+        };
+        self.can_read(len).then(|| self.get_smart_2_or_4())
+    }
+
+    #[inline]
+    fn try_get_array(&mut self, dst: &mut [u8]) -> Option<()> {
+        self.can_read(dst.len()).then(|| self.get_array(dst))
+    }
+
+    /// Reads a null terminated string, None if the data ends before the terminator.
+    fn try_get_str_cp1252_to_utf8(&mut self) -> Option<String> {
+        let mut chars: Vec<char> = Vec::new();
+        loop {
+            let c = self.try_g1()?;
+            if c == 0 {
+                break;
+            }
+            chars.push(u8_to_cp1252_ascii(c));
+        }
+        Some(chars.into_iter().collect())
+    }
 }
 
 impl<T: Buf + Sized> Packet for T {}
@@ -160,6 +278,190 @@ pub trait PacketMut: BufMut + Sized {
     fn p4s(&mut self, n: i32) {
         self.put_i32(n);
     }
+
+    /// Writes 0..=127 in one byte and up to 32767 in two, read by `get_smart_1_or_2`.
+    ///
+    /// # Panics
+    ///
+    /// If `n` is outside 0..=32767.
+    #[inline]
+    fn p_smart_1_or_2(&mut self, n: i32) {
+        assert!((0..32768).contains(&n), "smart value {n} out of range");
+        if n < 128 {
+            self.p1(n as u8);
+        } else {
+            self.p2((n + 32768) as u16);
+        }
+    }
+
+    /// Writes -64..=63 in one byte and -16384..=16383 in two, read by `get_smart_1_or_2s`.
+    ///
+    /// # Panics
+    ///
+    /// If `n` is outside -16384..=16383.
+    #[inline]
+    fn p_smart_1_or_2s(&mut self, n: i32) {
+        assert!(
+            (-16384..16384).contains(&n),
+            "signed smart value {n} out of range"
+        );
+        if (-64..64).contains(&n) {
+            self.p1((n + 64) as u8);
+        } else {
+            self.p2((n + 49152) as u16);
+        }
+    }
+
+    /// Writes -1..=32766 with -1 as null, read by `get_smart_1_or_2_null`.
+    ///
+    /// # Panics
+    ///
+    /// If `n` is outside -1..=32766.
+    #[inline]
+    fn p_smart_1_or_2_null(&mut self, n: i32) {
+        assert!(
+            (-1..32767).contains(&n),
+            "nullable smart value {n} out of range"
+        );
+        self.p_smart_1_or_2(n + 1);
+    }
+
+    /// Writes up to 32767 in two bytes and anything larger in four, read by `get_smart_2_or_4`.
+    ///
+    /// # Panics
+    ///
+    /// If `n` is larger than `i32::MAX`, which would set the length bit.
+    #[inline]
+    fn p_smart_2_or_4(&mut self, n: u32) {
+        assert!(n <= i32::MAX as u32, "smart value {n} out of range");
+        if n < 32768 {
+            self.p2(n as u16);
+        } else {
+            self.p4(n | 0x80000000);
+        }
+    }
 }
 
 impl<T: BufMut + Sized> PacketMut for T {}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    /// Writes `n` and reads it back, checking the reader takes exactly the bytes written.
+    fn round_trip<T: PartialEq + std::fmt::Debug>(
+        n: T,
+        write: impl FnOnce(&mut Vec<u8>, T),
+        read: impl FnOnce(&mut &[u8]) -> Option<T>,
+    ) -> (usize, Option<T>, usize) {
+        let mut buf = Vec::new();
+        write(&mut buf, n);
+        let mut data: &[u8] = &buf;
+        let value = read(&mut data);
+        (buf.len(), value, data.len())
+    }
+
+    #[test]
+    fn smart_boundaries_round_trip() {
+        for (n, len) in [
+            (0, 1),
+            (127, 1),
+            (128, 2),
+            (16383, 2),
+            (16384, 2),
+            (32767, 2),
+        ] {
+            let result = round_trip(
+                n,
+                |buf, n| buf.p_smart_1_or_2(n),
+                |data| data.try_get_smart_1_or_2(),
+            );
+            assert_eq!(result, (len, Some(n), 0), "{n}");
+        }
+        for (n, len) in [
+            (-16384, 2),
+            (-65, 2),
+            (-64, 1),
+            (-1, 1),
+            (0, 1),
+            (63, 1),
+            (64, 2),
+            (16383, 2),
+        ] {
+            let result = round_trip(
+                n,
+                |buf, n| buf.p_smart_1_or_2s(n),
+                |data| data.try_get_smart_1_or_2s(),
+            );
+            assert_eq!(result, (len, Some(n), 0), "{n}");
+        }
+        for (n, len) in [(-1, 1), (0, 1), (126, 1), (127, 2), (32766, 2)] {
+            let result = round_trip(
+                n,
+                |buf, n| buf.p_smart_1_or_2_null(n),
+                |data| data.try_get_smart_1_or_2_null(),
+            );
+            assert_eq!(result, (len, Some(n), 0), "{n}");
+        }
+        for (n, len) in [(0, 2), (32767, 2), (32768, 4), (i32::MAX as u32, 4)] {
+            let result = round_trip(
+                n,
+                |buf, n| buf.p_smart_2_or_4(n),
+                |data| data.try_get_smart_2_or_4(),
+            );
+            assert_eq!(result, (len, Some(n), 0), "{n}");
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn smart_1_or_2_rejects_out_of_range() {
+        Vec::new().p_smart_1_or_2(32768);
+    }
+
+    #[test]
+    #[should_panic]
+    fn smart_1_or_2s_rejects_out_of_range() {
+        Vec::new().p_smart_1_or_2s(-16385);
+    }
+
+    #[test]
+    #[should_panic]
+    fn smart_1_or_2_null_rejects_out_of_range() {
+        Vec::new().p_smart_1_or_2_null(-2);
+    }
+
+    #[test]
+    #[should_panic]
+    fn smart_2_or_4_rejects_out_of_range() {
+        Vec::new().p_smart_2_or_4(i32::MAX as u32 + 1);
+    }
+
+    proptest! {
+        #[test]
+        fn smart_1_or_2_round_trips(n in 0..=32767i32) {
+            let result = round_trip(n, |buf, n| buf.p_smart_1_or_2(n), |data| data.try_get_smart_1_or_2());
+            prop_assert_eq!(result, (if n < 128 { 1 } else { 2 }, Some(n), 0));
+        }
+
+        #[test]
+        fn smart_1_or_2s_round_trips(n in -16384..=16383i32) {
+            let result = round_trip(n, |buf, n| buf.p_smart_1_or_2s(n), |data| data.try_get_smart_1_or_2s());
+            prop_assert_eq!(result, (if (-64..64).contains(&n) { 1 } else { 2 }, Some(n), 0));
+        }
+
+        #[test]
+        fn smart_1_or_2_null_round_trips(n in -1..=32766i32) {
+            let result = round_trip(n, |buf, n| buf.p_smart_1_or_2_null(n), |data| data.try_get_smart_1_or_2_null());
+            prop_assert_eq!(result, (if n < 127 { 1 } else { 2 }, Some(n), 0));
+        }
+
+        #[test]
+        fn smart_2_or_4_round_trips(n in 0..=i32::MAX as u32) {
+            let result = round_trip(n, |buf, n| buf.p_smart_2_or_4(n), |data| data.try_get_smart_2_or_4());
+            prop_assert_eq!(result, (if n < 32768 { 2 } else { 4 }, Some(n), 0));
+        }
+    }
+}
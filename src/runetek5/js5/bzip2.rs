@@ -0,0 +1,297 @@
+//! A small bzip2 compressor for packing JS5 containers, which store bzip2 streams without their
+//! `BZh1` header. It favours being simple over ratio: each block uses one Huffman table (written
+//! twice, the format's minimum) and a plain prefix doubling sort for the Burrows-Wheeler
+//! transform.
+
+/// Bytes of run-length encoded data per block. Level 1 blocks hold 100000 bytes, less the slack
+/// bzip2 itself leaves for a run ending the block.
+const MAX_BLOCK_SIZE: usize = 100_000 - 19;
+const BLOCK_MAGIC: u64 = 0x3141_5926_5359;
+const END_MAGIC: u64 = 0x1772_4538_5090;
+const RUN_A: u16 = 0;
+const RUN_B: u16 = 1;
+/// Symbols coded with the same table before the next selector.
+const GROUP_SIZE: usize = 50;
+const TABLE_COUNT: usize = 2;
+/// Longest Huffman code the reference encoder makes, decoders accept up to 20.
+const MAX_CODE_LENGTH: u8 = 17;
+
+/// Compresses the data to a bzip2 stream without the `BZh1` header.
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = BitWriter::default();
+    let mut combined_crc = 0u32;
+    let mut remaining = data;
+    while !remaining.is_empty() {
+        let (block, consumed) = run_length_encode(remaining);
+        let crc = block_crc(&remaining[..consumed]);
+        combined_crc = combined_crc.rotate_left(1) ^ crc;
+        write_block(&mut out, &block, crc);
+        remaining = &remaining[consumed..];
+    }
+    out.write(48, END_MAGIC);
+    out.write(32, combined_crc as u64);
+    out.finish()
+}
+
+/// Run-length encodes as much of the data as fits a block: runs of 4 to 255 equal bytes become
+/// the first four and a count of the rest. Returns the block and how many bytes it covers.
+fn run_length_encode(data: &[u8]) -> (Vec<u8>, usize) {
+    let mut block = Vec::with_capacity(MAX_BLOCK_SIZE.min(data.len() + data.len() / 4));
+    let mut consumed = 0;
+    // A run adds at most five bytes.
+    while consumed < data.len() && block.len() + 5 <= MAX_BLOCK_SIZE {
+        let byte = data[consumed];
+        let run = data[consumed..]
+            .iter()
+            .take(255)
+            .take_while(|&&other| other == byte)
+            .count();
+        if run >= 4 {
+            block.extend_from_slice(&[byte; 4]);
+            block.push((run - 4) as u8);
+        } else {
+            block.extend(std::iter::repeat(byte).take(run));
+        }
+        consumed += run;
+    }
+    (block, consumed)
+}
+
+/// The big-endian CRC-32 bzip2 checks blocks with.
+fn block_crc(data: &[u8]) -> u32 {
+    let mut crc = u32::MAX;
+    for &byte in data {
+        crc ^= (byte as u32) << 24;
+        for _ in 0..8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04c1_1db7
+            } else {
+                crc << 1
+            };
+        }
+    }
+    !crc
+}
+
+fn write_block(out: &mut BitWriter, block: &[u8], crc: u32) {
+    let (last_column, orig_ptr) = burrows_wheeler(block);
+
+    let mut used = [false; 256];
+    for &byte in block {
+        used[byte as usize] = true;
+    }
+    let symbols = move_to_front(&last_column, &used);
+    let used_count = used.iter().filter(|&&used| used).count();
+    let alphabet_size = used_count + 2;
+
+    let mut frequencies = vec![0u32; alphabet_size];
+    for &symbol in &symbols {
+        frequencies[symbol as usize] += 1;
+    }
+    let lengths = code_lengths(&frequencies);
+    let codes = canonical_codes(&lengths);
+
+    out.write(48, BLOCK_MAGIC);
+    out.write(32, crc as u64);
+    // Not randomised.
+    out.write(1, 0);
+    out.write(24, orig_ptr as u64);
+
+    let ranges: Vec<bool> = used
+        .chunks(16)
+        .map(|range| range.iter().any(|&used| used))
+        .collect();
+    for &range_used in &ranges {
+        out.write(1, range_used as u64);
+    }
+    for (range, _) in used.chunks(16).zip(&ranges).filter(|(_, &used)| used) {
+        for &used in range {
+            out.write(1, used as u64);
+        }
+    }
+
+    // Every group uses table 0, whose move to front index is always 0.
+    let selector_count = symbols.len().div_ceil(GROUP_SIZE);
+    out.write(3, TABLE_COUNT as u64);
+    out.write(15, selector_count as u64);
+    for _ in 0..selector_count {
+        out.write(1, 0);
+    }
+
+    for _ in 0..TABLE_COUNT {
+        let mut current = lengths[0];
+        out.write(5, current as u64);
+        for &length in &lengths {
+            while current < length {
+                out.write(2, 0b10);
+                current += 1;
+            }
+            while current > length {
+                out.write(2, 0b11);
+                current -= 1;
+            }
+            out.write(1, 0);
+        }
+    }
+
+    for &symbol in &symbols {
+        let symbol = symbol as usize;
+        out.write(lengths[symbol] as u32, codes[symbol] as u64);
+    }
+}
+
+/// Sorts the rotations of the block, returning the last byte of each sorted rotation and where
+/// the unrotated block ended up.
+fn burrows_wheeler(block: &[u8]) -> (Vec<u8>, usize) {
+    let n = block.len();
+    let mut order: Vec<usize> = (0..n).collect();
+    let mut rank: Vec<usize> = block.iter().map(|&byte| byte as usize).collect();
+    let mut next_rank = vec![0; n];
+    let mut width = 1;
+    loop {
+        let key = |i: usize| (rank[i], rank[(i + width) % n]);
+        order.sort_unstable_by_key(|&i| key(i));
+        next_rank[order[0]] = 0;
+        for pair in 1..n {
+            let (previous, current) = (order[pair - 1], order[pair]);
+            next_rank[current] = next_rank[previous] + (key(previous) != key(current)) as usize;
+        }
+        std::mem::swap(&mut rank, &mut next_rank);
+        // Ranks stop changing once they are all distinct or the rotations repeat.
+        if rank[order[n - 1]] == n - 1 || width >= n {
+            break;
+        }
+        width *= 2;
+    }
+    let last_column = order.iter().map(|&i| block[(i + n - 1) % n]).collect();
+    let orig_ptr = order.iter().position(|&i| i == 0).unwrap();
+    (last_column, orig_ptr)
+}
+
+/// Move to front codes the bytes over the used byte values, coding runs of zeros with RUNA and
+/// RUNB as bijective base 2 and ending with the end of block symbol.
+fn move_to_front(bytes: &[u8], used: &[bool; 256]) -> Vec<u16> {
+    let mut list: Vec<u8> = (0..=255).filter(|&byte| used[byte as usize]).collect();
+    let end_of_block = list.len() as u16 + 1;
+    let mut symbols = Vec::with_capacity(bytes.len() + 1);
+    let mut zero_run = 0usize;
+    let flush_run = |symbols: &mut Vec<u16>, zero_run: &mut usize| {
+        if *zero_run == 0 {
+            return;
+        }
+        let mut run = *zero_run - 1;
+        loop {
+            symbols.push(if run & 1 == 1 { RUN_B } else { RUN_A });
+            if run < 2 {
+                break;
+            }
+            run = (run - 2) / 2;
+        }
+        *zero_run = 0;
+    };
+    for &byte in bytes {
+        let index = list.iter().position(|&other| other == byte).unwrap();
+        if index == 0 {
+            zero_run += 1;
+            continue;
+        }
+        flush_run(&mut symbols, &mut zero_run);
+        list[..=index].rotate_right(1);
+        symbols.push(index as u16 + 1);
+    }
+    flush_run(&mut symbols, &mut zero_run);
+    symbols.push(end_of_block);
+    symbols
+}
+
+/// Huffman code lengths of at most [`MAX_CODE_LENGTH`] bits. Every symbol gets a code, unused
+/// ones as if they occurred once. Frequencies are flattened until the longest code fits, like
+/// the reference encoder does.
+fn code_lengths(frequencies: &[u32]) -> Vec<u8> {
+    let mut weights: Vec<u64> = frequencies.iter().map(|&f| f.max(1) as u64).collect();
+    loop {
+        let lengths = huffman_lengths(&weights);
+        if lengths.iter().all(|&length| length <= MAX_CODE_LENGTH) {
+            return lengths;
+        }
+        for weight in &mut weights {
+            *weight = 1 + *weight / 2;
+        }
+    }
+}
+
+fn huffman_lengths(weights: &[u64]) -> Vec<u8> {
+    use std::cmp::Reverse;
+    use std::collections::BinaryHeap;
+
+    // Nodes are the symbols followed by the merged nodes, each with its parent.
+    let mut parents = vec![usize::MAX; weights.len()];
+    let mut heap: BinaryHeap<Reverse<(u64, usize)>> = weights
+        .iter()
+        .enumerate()
+        .map(|(node, &weight)| Reverse((weight, node)))
+        .collect();
+    while heap.len() > 1 {
+        let Reverse((weight_a, a)) = heap.pop().unwrap();
+        let Reverse((weight_b, b)) = heap.pop().unwrap();
+        let node = parents.len();
+        parents.push(usize::MAX);
+        parents[a] = node;
+        parents[b] = node;
+        heap.push(Reverse((weight_a + weight_b, node)));
+    }
+    (0..weights.len())
+        .map(|mut node| {
+            let mut length = 0;
+            while parents[node] != usize::MAX {
+                node = parents[node];
+                length += 1;
+            }
+            length
+        })
+        .collect()
+}
+
+/// Assigns codes in order of length and then symbol, as decoders rebuild them from the lengths.
+fn canonical_codes(lengths: &[u8]) -> Vec<u32> {
+    let mut codes = vec![0; lengths.len()];
+    let mut code = 0u32;
+    for length in 1..=MAX_CODE_LENGTH {
+        for (symbol, _) in lengths.iter().enumerate().filter(|(_, &l)| l == length) {
+            codes[symbol] = code;
+            code += 1;
+        }
+        code <<= 1;
+    }
+    codes
+}
+
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u64,
+    bit_count: u32,
+}
+
+impl BitWriter {
+    /// Writes the low `count` bits of the value, most significant first.
+    fn write(&mut self, count: u32, value: u64) {
+        for bit in (0..count).rev() {
+            self.buffer = (self.buffer << 1) | ((value >> bit) & 1);
+            self.bit_count += 1;
+            if self.bit_count == 8 {
+                self.bytes.push(self.buffer as u8);
+                self.buffer = 0;
+                self.bit_count = 0;
+            }
+        }
+    }
+
+    /// Pads the last byte with zero bits.
+    fn finish(mut self) -> Vec<u8> {
+        if self.bit_count > 0 {
+            self.bytes.push((self.buffer << (8 - self.bit_count)) as u8);
+        }
+        self.bytes
+    }
+}
@@ -0,0 +1,198 @@
+//! Reads a local cache in the client's `main_file_cache.dat2` and `main_file_cache.idx*` layout,
+//! as downloaded from OpenRS2 or written by the client.
+
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use bytes::Bytes;
+
+use super::{Js5Index, Js5ResourceProvider};
+
+/// The data file and one index file per archive. Index files hold the size and first sector of
+/// each group, the data file a chain of sectors per group.
+pub struct Js5DiskStore {
+    data: Mutex<File>,
+    indexes: Vec<Option<Mutex<File>>>,
+}
+
+impl Js5DiskStore {
+    const DATA_FILE_NAME: &'static str = "main_file_cache.dat2";
+    const INDEX_ENTRY_SIZE: u64 = 6;
+    const SECTOR_SIZE: usize = 520;
+    const HEADER_SIZE: usize = 8;
+    /// Groups past 65535 have 4 byte ids in their sector headers.
+    const EXTENDED_HEADER_SIZE: usize = 10;
+
+    /// Opens the cache in `dir`, failing if it has no data file. Archives without an index file
+    /// are left out.
+    pub fn open(dir: &Path) -> io::Result<Self> {
+        let data = File::open(dir.join(Self::DATA_FILE_NAME))?;
+        let indexes = (0..=Js5Index::ARCHIVE_ID)
+            .map(|archive_id| {
+                File::open(dir.join(format!("main_file_cache.idx{archive_id}")))
+                    .ok()
+                    .map(Mutex::new)
+            })
+            .collect();
+        Ok(Self {
+            data: Mutex::new(data),
+            indexes,
+        })
+    }
+
+    pub fn has_archive(&self, archive_id: u8) -> bool {
+        self.indexes[archive_id as usize].is_some()
+            && self.indexes[Js5Index::ARCHIVE_ID as usize].is_some()
+    }
+
+    /// Reads the packed container of a group, None if the store doesn't have it.
+    pub fn read(&self, archive_id: u8, group_id: u32) -> io::Result<Option<Vec<u8>>> {
+        let Some(index) = &self.indexes[archive_id as usize] else {
+            return Ok(None);
+        };
+        let mut entry = [0; Self::INDEX_ENTRY_SIZE as usize];
+        {
+            let mut index = index.lock().unwrap();
+            let offset = group_id as u64 * Self::INDEX_ENTRY_SIZE;
+            if offset + Self::INDEX_ENTRY_SIZE > index.metadata()?.len() {
+                return Ok(None);
+            }
+            index.seek(SeekFrom::Start(offset))?;
+            index.read_exact(&mut entry)?;
+        }
+        let size = u32::from_be_bytes([0, entry[0], entry[1], entry[2]]) as usize;
+        let mut sector = u32::from_be_bytes([0, entry[3], entry[4], entry[5]]);
+        if size == 0 || sector == 0 {
+            return Ok(None);
+        }
+
+        let extended = group_id > 0xffff;
+        let header_size = if extended {
+            Self::EXTENDED_HEADER_SIZE
+        } else {
+            Self::HEADER_SIZE
+        };
+        let corrupt = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let mut group = Vec::with_capacity(size);
+        let mut chunk = 0u16;
+        let mut data = self.data.lock().unwrap();
+        let data_len = data.metadata()?.len();
+        let mut buf = [0; Self::SECTOR_SIZE];
+        while group.len() < size {
+            let offset = sector as u64 * Self::SECTOR_SIZE as u64;
+            if sector == 0 || offset >= data_len {
+                return Err(corrupt(format!(
+                    "group {group_id} of archive {archive_id} points past the data file"
+                )));
+            }
+            let payload_size = (size - group.len()).min(Self::SECTOR_SIZE - header_size);
+            let sector_data = &mut buf[..header_size + payload_size];
+            data.seek(SeekFrom::Start(offset))?;
+            data.read_exact(sector_data)?;
+
+            let (header, payload) = sector_data.split_at(header_size);
+            let (sector_group_id, rest) = if extended {
+                let (id, rest) = header.split_at(4);
+                (u32::from_be_bytes([id[0], id[1], id[2], id[3]]), rest)
+            } else {
+                let (id, rest) = header.split_at(2);
+                (u16::from_be_bytes([id[0], id[1]]) as u32, rest)
+            };
+            let sector_chunk = u16::from_be_bytes([rest[0], rest[1]]);
+            let next_sector = u32::from_be_bytes([0, rest[2], rest[3], rest[4]]);
+            let sector_archive_id = rest[5];
+            if sector_group_id != group_id
+                || sector_chunk != chunk
+                || sector_archive_id != archive_id
+            {
+                return Err(corrupt(format!(
+                    "sector {sector} doesn't continue group {group_id} of archive {archive_id}"
+                )));
+            }
+
+            group.extend_from_slice(payload);
+            sector = next_sector;
+            chunk = chunk.wrapping_add(1);
+        }
+        Ok(Some(group))
+    }
+}
+
+/// Serves an archive from a [`Js5DiskStore`]. Reads are synchronous, so every group is ready on
+/// the first fetch.
+pub struct Js5DiskResourceProvider {
+    archive_id: u8,
+    store: Arc<Js5DiskStore>,
+    index: Mutex<Option<Arc<Js5Index>>>,
+    /// Groups that failed to read, logged once rather than on every fetch.
+    failed_groups: Mutex<HashSet<u32>>,
+}
+
+impl Js5DiskResourceProvider {
+    pub fn new(archive_id: u8, store: Arc<Js5DiskStore>) -> Self {
+        Self {
+            archive_id,
+            store,
+            index: Mutex::new(None),
+            failed_groups: Mutex::new(HashSet::new()),
+        }
+    }
+
+    pub fn get_archive_id(&self) -> u8 {
+        self.archive_id
+    }
+}
+
+impl Js5ResourceProvider for Js5DiskResourceProvider {
+    fn fetch_index(&self) -> Option<Arc<Js5Index>> {
+        let mut index = self.index.lock().unwrap();
+        if let Some(index) = &*index {
+            return Some(index.clone());
+        }
+        let data = match self
+            .store
+            .read(Js5Index::ARCHIVE_ID, self.archive_id as u32)
+        {
+            Ok(data) => data?,
+            Err(err) => {
+                log::error!("The index of archive {} failed: {err}", self.archive_id);
+                return None;
+            }
+        };
+        let mut decoded = match Js5Index::decode(&data, None) {
+            Ok(decoded) => decoded,
+            Err(err) => {
+                log::error!("The index of archive {} is invalid: {err}", self.archive_id);
+                return None;
+            }
+        };
+        decoded.clear_data_sizes();
+        let decoded = Arc::new(decoded);
+        *index = Some(decoded.clone());
+        Some(decoded)
+    }
+
+    fn fetch_group(&self, group_id: u32) -> Option<Bytes> {
+        let mut failed_groups = self.failed_groups.lock().unwrap();
+        if failed_groups.contains(&group_id) {
+            return None;
+        }
+        match self.store.read(self.archive_id, group_id) {
+            Ok(data) => data.map(Bytes::from),
+            Err(err) => {
+                log::error!(
+                    "Group {group_id} of archive {} failed: {err}",
+                    self.archive_id
+                );
+                failed_groups.insert(group_id);
+                None
+            }
+        }
+    }
+}
@@ -1,7 +1,11 @@
 use std::{
     borrow::Cow,
-    io::Read,
-    sync::{Arc, Mutex},
+    collections::HashMap,
+    io::{Read, Write},
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc, Mutex,
+    },
 };
 
 use bitflags::bitflags;
@@ -9,10 +13,14 @@ use bitflags::bitflags;
 use bytes::Bytes;
 use libflate::gzip;
 
-use crate::runetek5::io::packet::Packet;
+use super::{bzip2, xtea::XteaKey};
+use crate::runetek5::{
+    error::Runetek5Error,
+    io::packet::{Packet, PacketMut},
+};
 
-#[derive(Debug)]
-enum Js5CompressionType {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Js5CompressionType {
     None,
     Bzip2,
     Gzip,
@@ -34,37 +42,186 @@ impl TryFrom<u8> for Js5CompressionType {
 }
 
 const BZIP2_HEADER: &[u8] = b"BZh1";
+/// Bzip2 streams start with a block or, when empty, the end of stream marker.
+const BZIP2_BLOCK_MAGIC: &[u8] = &[0x31, 0x41, 0x59, 0x26, 0x53, 0x59];
+const BZIP2_END_MAGIC: &[u8] = &[0x17, 0x72, 0x45, 0x38, 0x50, 0x90];
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+/// Decrypts the container with the key, if any, and decompresses it. Fails with
+/// [`Runetek5Error::Decryption`] if the decrypted payload doesn't start like its compression type,
+/// which is what a wrong or missing key gives.
+pub fn decompress_with_key(data: &[u8], key: Option<&XteaKey>) -> Result<Vec<u8>, Runetek5Error> {
+    let compression_type = *data
+        .first()
+        .ok_or(Runetek5Error::Truncated("container header"))?;
+    let compression_type = Js5CompressionType::try_from(compression_type)
+        .map_err(|_| Runetek5Error::UnknownCompressionType(compression_type))?;
+    let mut data = Cow::Borrowed(data);
+    if let Some(key) = key.filter(|key| !key.is_zero()) {
+        if data.len() < 5 {
+            return Err(Runetek5Error::Truncated("container header"));
+        }
+        // Everything after the compression type and compressed size is encrypted, up to the
+        // version trailer.
+        let compressed_size = u32::from_be_bytes([data[1], data[2], data[3], data[4]]) as usize;
+        let encrypted_size = match compression_type {
+            Js5CompressionType::None => compressed_size,
+            _ => compressed_size + 4,
+        };
+        let end = (5 + encrypted_size).min(data.len());
+        key.decrypt(&mut data.to_mut()[5..end]);
+    }
+    let payload = data.get(9..).unwrap_or_default();
+    let looks_valid = match compression_type {
+        Js5CompressionType::None => true,
+        Js5CompressionType::Bzip2 => {
+            payload.starts_with(BZIP2_BLOCK_MAGIC) || payload.starts_with(BZIP2_END_MAGIC)
+        }
+        Js5CompressionType::Gzip => payload.starts_with(GZIP_MAGIC),
+        Js5CompressionType::Lzma => {
+            return Err(Runetek5Error::UnsupportedCompressionType(compression_type))
+        }
+    };
+    if !looks_valid {
+        return Err(Runetek5Error::Decryption);
+    }
+    decompress(&data)
+}
 
-pub fn decompress(mut data: &[u8]) -> Vec<u8> {
+pub fn decompress(mut data: &[u8]) -> Result<Vec<u8>, Runetek5Error> {
     use bytes::Buf;
-    let compression_type: Js5CompressionType = data.g1().try_into().unwrap();
-    let compressed_size = data.g4();
-    // println!("{:?}, {:?}", compression_type, compressed_size);
+    const HEADER: Runetek5Error = Runetek5Error::Truncated("container header");
+    let compression_type = data.try_g1().ok_or(HEADER)?;
+    let compression_type = Js5CompressionType::try_from(compression_type)
+        .map_err(|_| Runetek5Error::UnknownCompressionType(compression_type))?;
+    let compressed_size = data.try_g4().ok_or(HEADER)? as usize;
+    let decompression_error = |err: std::io::Error| Runetek5Error::Decompression(err.to_string());
     match compression_type {
-        Js5CompressionType::None => {
-            let mut decompressed = Vec::with_capacity(compressed_size as usize);
-            decompressed.extend_from_slice(&data[..compressed_size as usize]);
-            decompressed
-        }
+        Js5CompressionType::None => data
+            .get(..compressed_size)
+            .map(<[u8]>::to_vec)
+            .ok_or(Runetek5Error::Truncated("container payload")),
         Js5CompressionType::Bzip2 => {
-            let decompressed_size = data.g4();
+            let decompressed_size = data.try_g4().ok_or(HEADER)?;
             let buf_with_header = Buf::chain(BZIP2_HEADER, data);
             let mut decoder = bzip2_rs::DecoderReader::new(buf_with_header.reader());
-            let mut decompressed = Vec::with_capacity(decompressed_size as usize);
-            decoder.read_to_end(&mut decompressed).unwrap();
-            decompressed
+            // The size comes from the data, so it only hints at the capacity.
+            let mut decompressed = Vec::with_capacity((decompressed_size as usize).min(1 << 24));
+            decoder
+                .read_to_end(&mut decompressed)
+                .map_err(decompression_error)?;
+            Ok(decompressed)
+        }
+        Js5CompressionType::Gzip => {
+            let decompressed_size = data.try_g4().ok_or(HEADER)?;
+            let mut decoder = gzip::Decoder::new(data.reader()).map_err(decompression_error)?;
+            let mut decompressed = Vec::with_capacity((decompressed_size as usize).min(1 << 24));
+            decoder
+                .read_to_end(&mut decompressed)
+                .map_err(decompression_error)?;
+            Ok(decompressed)
+        }
+        Js5CompressionType::Lzma => {
+            Err(Runetek5Error::UnsupportedCompressionType(compression_type))
         }
+    }
+}
+
+/// Packs the data into a container that [`decompress`] reads back. Fails with
+/// [`Runetek5Error::UnsupportedCompressionType`] for LZMA.
+pub fn compress(
+    data: &[u8],
+    compression_type: Js5CompressionType,
+) -> Result<Vec<u8>, Runetek5Error> {
+    let compression_error = |err: std::io::Error| Runetek5Error::Compression(err.to_string());
+    let compressed = match compression_type {
+        Js5CompressionType::None => {
+            let mut container = Vec::with_capacity(5 + data.len());
+            container.p1(0);
+            container.p4(data.len() as u32);
+            container.extend_from_slice(data);
+            return Ok(container);
+        }
+        Js5CompressionType::Bzip2 => bzip2::compress(data),
         Js5CompressionType::Gzip => {
-            let decompressed_size = data.g4();
-            let mut decoder = gzip::Decoder::new(data.reader()).unwrap();
-            let mut decompressed = Vec::with_capacity(decompressed_size as usize);
-            decoder.read_to_end(&mut decompressed).unwrap();
-            decompressed
+            let mut encoder = gzip::Encoder::new(Vec::new()).map_err(compression_error)?;
+            encoder.write_all(data).map_err(compression_error)?;
+            encoder.finish().into_result().map_err(compression_error)?
         }
         Js5CompressionType::Lzma => {
-            unimplemented!();
+            return Err(Runetek5Error::UnsupportedCompressionType(compression_type))
         }
+    };
+    let mut container = Vec::with_capacity(9 + compressed.len());
+    container.p1(compression_type as u8);
+    container.p4(compressed.len() as u32);
+    container.p4(data.len() as u32);
+    container.extend_from_slice(&compressed);
+    Ok(container)
+}
+
+/// Joins the files of a group the way the client splits them when unpacking: the files one after
+/// another, followed by their sizes as deltas in a single chunk. Groups of one file are the file
+/// itself.
+pub fn pack_group(files: &[&[u8]]) -> Vec<u8> {
+    if let [file] = files {
+        return file.to_vec();
     }
+    let data_size: usize = files.iter().map(|file| file.len()).sum();
+    let mut group = Vec::with_capacity(data_size + files.len() * 4 + 1);
+    for file in files {
+        group.extend_from_slice(file);
+    }
+    let mut last_size = 0;
+    for file in files {
+        group.p4s(file.len() as i32 - last_size);
+        last_size = file.len() as i32;
+    }
+    group.p1(1);
+    group
+}
+
+/// Splits a group of several files packed by [`pack_group`], or by the client's tools which may
+/// store the files in several chunks.
+pub fn unpack_files(group: &[u8], file_count: usize) -> Result<Vec<Vec<u8>>, Runetek5Error> {
+    const TRUNCATED: Runetek5Error = Runetek5Error::Truncated("group file sizes");
+    let chunks = *group.last().ok_or(TRUNCATED)? as usize;
+    let meta_start = (group.len() - 1)
+        .checked_sub(file_count * chunks * 4)
+        .ok_or(TRUNCATED)?;
+    let (data, meta) = group.split_at(meta_start);
+
+    let mut file_sizes = vec![0usize; file_count];
+    let mut meta_buf = meta;
+    for _ in 0..chunks {
+        let mut file_size = 0i32;
+        for size in file_sizes.iter_mut() {
+            file_size = file_size.wrapping_add(meta_buf.try_g4s().ok_or(TRUNCATED)?);
+            let chunk_size =
+                usize::try_from(file_size).map_err(|_| Runetek5Error::Invalid("file size"))?;
+            *size = size.saturating_add(chunk_size);
+        }
+    }
+    let total_size = file_sizes
+        .iter()
+        .fold(0usize, |total, &size| total.saturating_add(size));
+    if total_size > data.len() {
+        return Err(Runetek5Error::Truncated("group files"));
+    }
+
+    let mut files: Vec<Vec<u8>> = file_sizes.into_iter().map(Vec::with_capacity).collect();
+    let mut meta_buf = meta;
+    let mut data_buf = data;
+    for _ in 0..chunks {
+        let mut file_size = 0i32;
+        for file in files.iter_mut() {
+            file_size = file_size.wrapping_add(meta_buf.try_g4s().ok_or(TRUNCATED)?);
+            let (chunk, rest) = data_buf.split_at(file_size as usize);
+            file.extend_from_slice(chunk);
+            data_buf = rest;
+        }
+    }
+    Ok(files)
 }
 
 const WHIRLPOOL_HASH_SIZE: usize = 64;
@@ -135,38 +292,65 @@ pub struct Js5Index {
 impl Js5Index {
     pub const ARCHIVE_ID: u8 = 255;
 
-    pub fn decode(data: &[u8], expected_crc: Option<u32>) -> Js5Index {
+    pub fn decode(data: &[u8], expected_crc: Option<u32>) -> Result<Js5Index, Runetek5Error> {
+        const TRUNCATED: Runetek5Error = Runetek5Error::Truncated("index");
         let crc = crc32fast::hash(data);
         if let Some(expected_crc) = expected_crc {
-            assert_eq!(crc, expected_crc);
+            if crc != expected_crc {
+                return Err(Runetek5Error::ChecksumMismatch {
+                    expected: expected_crc,
+                    actual: crc,
+                });
+            }
         }
 
-        let mut buffer = Bytes::from(decompress(data));
-        let protocol: Js5IndexProtocol = buffer.g1().try_into().unwrap();
+        let decompressed = decompress(data)?;
+        let mut buffer: &[u8] = &decompressed;
+        let protocol = buffer.try_g1().ok_or(TRUNCATED)?;
+        let protocol = Js5IndexProtocol::try_from(protocol)
+            .map_err(|_| Runetek5Error::UnknownIndexProtocol(protocol))?;
         let mut version = 0;
         if protocol >= Js5IndexProtocol::Versioned {
-            version = buffer.g4();
+            version = buffer.try_g4().ok_or(TRUNCATED)?;
         }
-        let flags = Js5IndexFlags::from_bits_retain(buffer.g1());
+        let flags = Js5IndexFlags::from_bits_retain(buffer.try_g1().ok_or(TRUNCATED)?);
         let has_names = flags.contains(Js5IndexFlags::NAMES);
         let has_whirlpool_hashes = flags.contains(Js5IndexFlags::WHIRLPOOL_HASHES);
         let has_group_data_sizes = flags.contains(Js5IndexFlags::GROUP_DATA_SIZES);
         let has_uncompressed_checksums = flags.contains(Js5IndexFlags::UNCOMPRESSED_CHECKSUMS);
         let has_md5_hashes = flags.contains(Js5IndexFlags::MD5_HASHES);
 
-        let read = if protocol == Js5IndexProtocol::Smart {
-            |buffer: &mut Bytes| buffer.get_smart_2_or_4()
-        } else {
-            |buffer: &mut Bytes| buffer.g2() as u32
+        let read = |buffer: &mut &[u8]| {
+            let value = if protocol == Js5IndexProtocol::Smart {
+                buffer.try_get_smart_2_or_4()
+            } else {
+                buffer.try_g2().map(u32::from)
+            };
+            value.ok_or(TRUNCATED)
+        };
+        // Every id takes at least a byte, so counts past the remaining data can't be right and
+        // would only allocate.
+        let read_count = |buffer: &mut &[u8]| {
+            let count = read(buffer)?;
+            if count as usize > buffer.len() {
+                return Err(TRUNCATED);
+            }
+            Ok(count)
+        };
+        let read_delta = |buffer: &mut &[u8], last_id: u32| {
+            last_id
+                .checked_add(read(buffer)?)
+                .filter(|&id| (id as usize) < (1 << 24))
+                .ok_or(Runetek5Error::Invalid("index id"))
         };
 
-        let group_count = read(&mut buffer);
+        let group_count = read_count(&mut buffer)?;
 
         let mut group_ids = vec![0; group_count as usize];
 
         let mut last_group_id = 0;
         for i in 0..group_count {
-            last_group_id += read(&mut buffer);
+            last_group_id = read_delta(&mut buffer, last_group_id)?;
             group_ids[i as usize] = last_group_id;
         }
 
@@ -180,21 +364,21 @@ impl Js5Index {
         if has_names {
             let mut hashes = vec![-1; group_capacity as usize];
             for i in 0..group_count {
-                hashes[group_ids[i as usize] as usize] = buffer.g4s();
+                hashes[group_ids[i as usize] as usize] = buffer.try_g4s().ok_or(TRUNCATED)?;
             }
             group_name_hashes = Some(hashes);
         }
 
         let mut group_checksums = vec![0; group_capacity as usize];
         for i in 0..group_count {
-            group_checksums[group_ids[i as usize] as usize] = buffer.g4();
+            group_checksums[group_ids[i as usize] as usize] = buffer.try_g4().ok_or(TRUNCATED)?;
         }
 
         let mut group_uncompressed_checksums: Option<Vec<u32>> = None;
         if has_uncompressed_checksums {
             let mut checksums = vec![0; group_capacity as usize];
             for i in 0..group_count {
-                checksums[group_ids[i as usize] as usize] = buffer.g4();
+                checksums[group_ids[i as usize] as usize] = buffer.try_g4().ok_or(TRUNCATED)?;
             }
             group_uncompressed_checksums = Some(checksums);
         }
@@ -203,7 +387,9 @@ impl Js5Index {
         if has_whirlpool_hashes {
             let mut hashes = vec![[0; WHIRLPOOL_HASH_SIZE]; group_capacity as usize];
             for i in 0..group_count {
-                buffer.get_array(&mut hashes[group_ids[i as usize] as usize]);
+                buffer
+                    .try_get_array(&mut hashes[group_ids[i as usize] as usize])
+                    .ok_or(TRUNCATED)?;
             }
             group_whirlpool_hashes = Some(hashes);
         }
@@ -215,8 +401,8 @@ impl Js5Index {
             let mut uncompressed_lengths = vec![0; group_capacity as usize];
             for i in 0..group_count {
                 let group_id = group_ids[i as usize] as usize;
-                lengths[group_id] = buffer.g4();
-                uncompressed_lengths[group_id] = buffer.g4();
+                lengths[group_id] = buffer.try_g4().ok_or(TRUNCATED)?;
+                uncompressed_lengths[group_id] = buffer.try_g4().ok_or(TRUNCATED)?;
             }
             group_data_sizes = Some(lengths);
             group_uncompressed_data_sizes = Some(uncompressed_lengths);
@@ -224,12 +410,12 @@ impl Js5Index {
 
         let mut group_versions = vec![0; group_capacity as usize];
         for i in 0..group_count {
-            group_versions[group_ids[i as usize] as usize] = buffer.g4();
+            group_versions[group_ids[i as usize] as usize] = buffer.try_g4().ok_or(TRUNCATED)?;
         }
 
         let mut group_file_counts = vec![0; group_capacity as usize];
         for i in 0..group_count {
-            group_file_counts[group_ids[i as usize] as usize] = read(&mut buffer);
+            group_file_counts[group_ids[i as usize] as usize] = read_count(&mut buffer)?;
         }
 
         let mut group_file_capacities = vec![0; group_capacity as usize];
@@ -243,7 +429,7 @@ impl Js5Index {
 
             let mut last_file_id = 0;
             for j in 0..file_count {
-                last_file_id += read(&mut buffer);
+                last_file_id = read_delta(&mut buffer, last_file_id)?;
                 file_ids[j as usize] = last_file_id;
             }
 
@@ -264,7 +450,7 @@ impl Js5Index {
                 let file_count = group_file_counts[group_id];
                 let mut hashes = vec![-1; file_count as usize];
                 for j in 0..file_count {
-                    hashes[j as usize] = buffer.g4s();
+                    hashes[j as usize] = buffer.try_g4s().ok_or(TRUNCATED)?;
                 }
                 file_name_hashes[group_id] = hashes;
             }
@@ -275,12 +461,14 @@ impl Js5Index {
         if has_md5_hashes {
             let mut hashes = vec![[0; MD5_HASH_SIZE]; group_capacity as usize];
             for i in 0..group_count {
-                buffer.get_array(&mut hashes[group_ids[i as usize] as usize]);
+                buffer
+                    .try_get_array(&mut hashes[group_ids[i as usize] as usize])
+                    .ok_or(TRUNCATED)?;
             }
             group_md5_hashes = Some(hashes);
         }
 
-        Js5Index {
+        Ok(Js5Index {
             crc,
             protocol,
             version,
@@ -304,7 +492,144 @@ impl Js5Index {
             group_file_ids,
             group_file_name_hashes,
             group_md5_hashes,
+        })
+    }
+
+    /// Encodes the index into a container that [`Js5Index::decode`] reads back. The index keeps
+    /// its decoded CRC, the CRC of the new container is that of the returned bytes.
+    ///
+    /// Fails with [`Runetek5Error::Invalid`] if an id or count is too large for the protocol,
+    /// which stores them in 16 bits unless it is [`Js5IndexProtocol::Smart`], and like
+    /// [`compress`] for LZMA.
+    pub fn encode(&self, compression_type: Js5CompressionType) -> Result<Vec<u8>, Runetek5Error> {
+        let mut buffer = Vec::new();
+        buffer.p1(self.protocol as u8);
+        if self.protocol >= Js5IndexProtocol::Versioned {
+            buffer.p4(self.version);
+        }
+        let mut flags = Js5IndexFlags::empty();
+        flags.set(Js5IndexFlags::NAMES, self.has_names);
+        flags.set(Js5IndexFlags::WHIRLPOOL_HASHES, self.has_whirlpool_hashes);
+        flags.set(Js5IndexFlags::GROUP_DATA_SIZES, self.has_group_data_sizes);
+        flags.set(
+            Js5IndexFlags::UNCOMPRESSED_CHECKSUMS,
+            self.has_uncompressed_checksums,
+        );
+        flags.set(Js5IndexFlags::MD5_HASHES, self.has_md5_hashes);
+        buffer.p1(flags.bits());
+
+        let smart = self.protocol == Js5IndexProtocol::Smart;
+        let write = |buffer: &mut Vec<u8>, n: u32| {
+            if smart && n <= i32::MAX as u32 {
+                buffer.p_smart_2_or_4(n);
+            } else if !smart && n <= u16::MAX as u32 {
+                buffer.p2(n as u16);
+            } else {
+                return Err(Runetek5Error::Invalid("index id or count for the protocol"));
+            }
+            Ok(())
+        };
+
+        let group_ids = &self.group_ids[..self.group_count as usize];
+        write(&mut buffer, self.group_count)?;
+        let mut last_group_id = 0;
+        for &group_id in group_ids {
+            write(&mut buffer, group_id - last_group_id)?;
+            last_group_id = group_id;
+        }
+
+        if let Some(hashes) = self.group_name_hashes.as_ref().filter(|_| self.has_names) {
+            for &group_id in group_ids {
+                buffer.p4s(hashes[group_id as usize]);
+            }
+        }
+
+        for &group_id in group_ids {
+            buffer.p4(self.group_checksums[group_id as usize]);
+        }
+
+        if let Some(checksums) = self
+            .group_uncompressed_checksums
+            .as_ref()
+            .filter(|_| self.has_uncompressed_checksums)
+        {
+            for &group_id in group_ids {
+                buffer.p4(checksums[group_id as usize]);
+            }
+        }
+
+        if let Some(hashes) = self
+            .group_whirlpool_hashes
+            .as_ref()
+            .filter(|_| self.has_whirlpool_hashes)
+        {
+            for &group_id in group_ids {
+                buffer.extend_from_slice(&hashes[group_id as usize]);
+            }
+        }
+
+        if self.has_group_data_sizes {
+            for &group_id in group_ids {
+                let size = |sizes: &Option<Vec<u32>>| {
+                    sizes.as_ref().map_or(0, |sizes| sizes[group_id as usize])
+                };
+                buffer.p4(size(&self.group_data_sizes));
+                buffer.p4(size(&self.group_uncompressed_data_sizes));
+            }
+        }
+
+        for &group_id in group_ids {
+            buffer.p4(self.group_versions[group_id as usize]);
+        }
+
+        for &group_id in group_ids {
+            write(&mut buffer, self.group_file_counts[group_id as usize])?;
+        }
+
+        for &group_id in group_ids {
+            let file_count = self.group_file_counts[group_id as usize];
+            match self.get_file_ids(group_id) {
+                Some(file_ids) => {
+                    let mut last_file_id = 0;
+                    for &file_id in &file_ids[..file_count as usize] {
+                        write(&mut buffer, file_id - last_file_id)?;
+                        last_file_id = file_id;
+                    }
+                }
+                // Files numbered from zero without gaps, each one more than the last.
+                None => {
+                    for file_id in 0..file_count {
+                        write(&mut buffer, (file_id > 0) as u32)?;
+                    }
+                }
+            }
+        }
+
+        if let Some(hashes) = self
+            .group_file_name_hashes
+            .as_ref()
+            .filter(|_| self.has_names)
+        {
+            for &group_id in group_ids {
+                let file_count = self.group_file_counts[group_id as usize] as usize;
+                let group_hashes = &hashes[group_id as usize];
+                for file in 0..file_count {
+                    buffer.p4s(group_hashes.get(file).copied().unwrap_or(-1));
+                }
+            }
         }
+
+        if let Some(hashes) = self
+            .group_md5_hashes
+            .as_ref()
+            .filter(|_| self.has_md5_hashes)
+        {
+            for &group_id in group_ids {
+                buffer.extend_from_slice(&hashes[group_id as usize]);
+            }
+        }
+
+        compress(&buffer, compression_type)
     }
 
     pub fn clear_data_sizes(&mut self) {
@@ -331,17 +656,69 @@ impl Js5Index {
     pub fn get_file_ids(&self, group_id: u32) -> Option<&Vec<u32>> {
         self.group_file_ids[group_id as usize].as_ref()
     }
+
+    /// The hash of the group's name, None if the archive has no names.
+    pub fn get_group_name_hash(&self, group_id: u32) -> Option<i32> {
+        self.group_name_hashes
+            .as_ref()?
+            .get(group_id as usize)
+            .copied()
+    }
+
+    /// Looks a group up by name like the client does, by the hash of the name.
+    pub fn get_group_id_by_name(&self, name: &str) -> Option<u32> {
+        let hashes = self.group_name_hashes.as_ref()?;
+        let hash = name_hash(name);
+        self.group_ids
+            .iter()
+            .copied()
+            .find(|&group_id| hashes[group_id as usize] == hash)
+    }
+}
+
+/// Hashes a group or file name like the client, ignoring case. Names are ASCII in practice, other
+/// characters are hashed by their code point.
+pub fn name_hash(name: &str) -> i32 {
+    name.chars()
+        .flat_map(char::to_lowercase)
+        .fold(0i32, |hash, c| hash.wrapping_mul(31).wrapping_add(c as i32))
 }
 
 pub trait Js5ResourceProvider {
     fn fetch_index(&self) -> Option<Arc<Js5Index>>;
 
     fn fetch_group(&self, group_id: u32) -> Option<Bytes>;
+
+    /// Starts fetching the group in the background, for providers where fetching takes a while.
+    /// Prefetches give way to the groups [`Js5ResourceProvider::fetch_group`] is waiting for.
+    fn prefetch_group(&self, _group_id: u32) {}
+
+    /// The key the group is encrypted with, for providers that know their archive's keys. Keys
+    /// set with [`Js5::set_xtea_key`] take precedence.
+    fn get_xtea_key(&self, _group_id: u32) -> Option<XteaKey> {
+        None
+    }
+}
+
+/// How the single ids given to [`Js5::is_valid`] and [`Js5::is_ready`] map to a group and file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Js5IdPolicy {
+    /// Ids are file ids if the archive has a single group, otherwise group ids of groups with a
+    /// single file. Ids of groups with several files can't be resolved.
+    #[default]
+    Auto,
+    /// Ids are group ids, addressing file 0 of the group.
+    Group,
+    /// Ids are file ids in group 0.
+    File,
 }
 
 pub struct Js5GroupData {
     packed: Option<Bytes>,
     unpacked: Option<Vec<Option<Bytes>>>,
+    /// The packed group failed its CRC check or couldn't be unpacked. Corrupt groups are never
+    /// fetched or unpacked again, so a bad group can't be decoded over and over.
+    corrupt: bool,
 }
 
 pub struct Js5 {
@@ -350,6 +727,10 @@ pub struct Js5 {
     discard_packed: bool,
     discard_unpacked: bool,
     groups: Vec<Mutex<Js5GroupData>>,
+    id_policy: Js5IdPolicy,
+    verify_groups: AtomicBool,
+    corrupt_group_count: AtomicU32,
+    xtea_keys: Mutex<HashMap<u32, XteaKey>>,
 }
 
 impl Js5 {
@@ -364,6 +745,7 @@ impl Js5 {
                 Mutex::new(Js5GroupData {
                     packed: None,
                     unpacked: None,
+                    corrupt: false,
                 })
             })
             .collect::<Vec<_>>();
@@ -373,6 +755,57 @@ impl Js5 {
             discard_packed,
             discard_unpacked,
             groups,
+            id_policy: Js5IdPolicy::default(),
+            verify_groups: AtomicBool::new(false),
+            corrupt_group_count: AtomicU32::new(0),
+            xtea_keys: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether groups are checked against the index CRC as they are fetched, see
+    /// [`Js5::verify_group`].
+    pub fn set_verify_groups(&self, verify: bool) {
+        self.verify_groups.store(verify, Ordering::Relaxed);
+    }
+
+    pub fn verifies_groups(&self) -> bool {
+        self.verify_groups.load(Ordering::Relaxed)
+    }
+
+    /// Sets the key to decrypt the group with, unpacking it again with the new key.
+    pub fn set_xtea_key(&self, group_id: u32, key: XteaKey) {
+        if !self.is_group_valid(group_id) {
+            return;
+        }
+        self.xtea_keys.lock().unwrap().insert(group_id, key);
+        self.groups[group_id as usize].lock().unwrap().unpacked = None;
+    }
+
+    /// The key the group is decrypted with, None for unencrypted groups.
+    pub fn get_xtea_key(&self, group_id: u32) -> Option<XteaKey> {
+        let key = self.xtea_keys.lock().unwrap().get(&group_id).copied();
+        key.or_else(|| self.provider.get_xtea_key(group_id))
+    }
+
+    pub fn with_id_policy(mut self, id_policy: Js5IdPolicy) -> Self {
+        self.id_policy = id_policy;
+        self
+    }
+
+    /// Resolves a single id to `(group_id, file_id)` using the archive's [`Js5IdPolicy`].
+    pub fn resolve_id(&self, id: u32) -> Option<(u32, u32)> {
+        match self.id_policy {
+            Js5IdPolicy::Group => Some((id, 0)),
+            Js5IdPolicy::File => Some((0, id)),
+            Js5IdPolicy::Auto => {
+                if self.index.group_count == 1 {
+                    Some((0, id))
+                } else if self.is_group_valid(id) && self.index.get_file_count(id) == 1 {
+                    Some((id, 0))
+                } else {
+                    None
+                }
+            }
         }
     }
 
@@ -425,19 +858,108 @@ impl Js5 {
     }
 
     pub fn is_valid(&self, id: u32) -> bool {
-        if self.index.group_count == 1 {
-            self.is_file_valid(0, id)
-        } else if !self.is_group_valid(id) {
-            false
-        } else if self.index.get_file_count(id) == 1 {
-            self.is_file_valid(id, 0)
-        } else {
-            panic!("Unable to determine if id is group_id or file_id");
-        }
+        self.resolve_id(id)
+            .is_some_and(|(group_id, file_id)| self.is_file_valid(group_id, file_id))
     }
 
     pub fn fetch_group(&self, group_data: &mut Js5GroupData, group_id: u32) {
+        if group_data.corrupt {
+            return;
+        }
+        let _span = tracing::info_span!("js5_fetch_group", group_id).entered();
         group_data.packed = self.provider.fetch_group(group_id);
+        if self.verifies_groups() {
+            self.check_group(group_data, group_id);
+        }
+    }
+
+    /// Whether the packed container matches the CRC in the index. Containers may still carry the
+    /// two byte version trailer, which the CRC doesn't cover.
+    pub fn is_group_crc_valid(&self, group_id: u32, packed: &[u8]) -> bool {
+        let expected = self.index.get_group_crc(group_id);
+        crc32fast::hash(packed) == expected
+            || (packed.len() >= 2 && crc32fast::hash(&packed[..packed.len() - 2]) == expected)
+    }
+
+    /// Quarantines the group if its packed container fails the CRC check.
+    fn check_group(&self, group_data: &mut Js5GroupData, group_id: u32) {
+        let Some(packed) = &group_data.packed else {
+            return;
+        };
+        if !self.is_group_crc_valid(group_id, packed) {
+            log::warn!("Group {group_id} failed its CRC check");
+            self.quarantine_group(group_data);
+        }
+    }
+
+    /// Drops the group's data and stops it from being fetched or unpacked again.
+    fn quarantine_group(&self, group_data: &mut Js5GroupData) {
+        group_data.packed = None;
+        group_data.unpacked = None;
+        group_data.corrupt = true;
+        self.corrupt_group_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Checks the group against the index CRC, fetching it if needed, and quarantines it if the
+    /// check fails. Returns None while the group is still being fetched.
+    pub fn verify_group(&self, group_id: u32) -> Option<bool> {
+        if !self.is_group_valid(group_id) {
+            return None;
+        }
+        let mut group_data = self.groups[group_id as usize].lock().unwrap();
+        if group_data.packed.is_none() {
+            self.fetch_group(&mut group_data, group_id);
+        }
+        self.check_group(&mut group_data, group_id);
+        if group_data.corrupt {
+            Some(false)
+        } else {
+            group_data.packed.as_ref().map(|_| true)
+        }
+    }
+
+    pub fn is_group_corrupt(&self, group_id: u32) -> bool {
+        self.is_group_valid(group_id) && self.groups[group_id as usize].lock().unwrap().corrupt
+    }
+
+    pub fn corrupt_group_count(&self) -> u32 {
+        self.corrupt_group_count.load(Ordering::Relaxed)
+    }
+
+    /// The groups quarantined by a failed CRC check or unpacking, in ascending order.
+    pub fn corrupt_group_ids(&self) -> Vec<u32> {
+        self.index
+            .group_ids
+            .iter()
+            .copied()
+            .filter(|&group_id| self.groups[group_id as usize].lock().unwrap().corrupt)
+            .collect()
+    }
+
+    /// Starts fetching the group in the background, so it is ready by the time it is shown.
+    pub fn prefetch_group(&self, group_id: u32) {
+        if !self.is_group_valid(group_id) {
+            return;
+        }
+        {
+            let group_data = self.groups[group_id as usize].lock().unwrap();
+            if group_data.packed.is_some() || group_data.unpacked.is_some() || group_data.corrupt {
+                return;
+            }
+        }
+        self.provider.prefetch_group(group_id);
+    }
+
+    /// Returns the group's packed (still compressed) container, fetching it if needed.
+    pub fn get_packed_group(&self, group_id: u32) -> Option<Bytes> {
+        if !self.is_group_valid(group_id) {
+            return None;
+        }
+        let mut group_data = self.groups[group_id as usize].lock().unwrap();
+        if group_data.packed.is_none() {
+            self.fetch_group(&mut group_data, group_id);
+        }
+        group_data.packed.clone()
     }
 
     pub fn fetch_all(&self) -> bool {
@@ -474,7 +996,9 @@ impl Js5 {
         }
         let mut group_data = self.groups[group_id as usize].lock().unwrap();
         if let Some(unpacked) = &group_data.unpacked {
-            unpacked[file_id as usize].is_some()
+            unpacked
+                .get(file_id as usize)
+                .is_some_and(|file| file.is_some())
         } else if group_data.packed.is_none() {
             self.fetch_group(&mut group_data, group_id);
             group_data.packed.is_some()
@@ -484,22 +1008,21 @@ impl Js5 {
     }
 
     pub fn is_ready(&self, id: u32) -> bool {
-        if self.index.group_count == 1 {
-            self.is_file_ready(0, id)
-        } else if !self.is_group_valid(id) {
-            false
-        } else if self.index.get_file_count(id) == 1 {
-            self.is_file_ready(id, 0)
-        } else {
-            panic!("Unable to determine if id is group_id or file_id");
-        }
+        self.resolve_id(id)
+            .is_some_and(|(group_id, file_id)| self.is_file_ready(group_id, file_id))
+    }
+
+    /// Gets the file a single id addresses, see [`Js5::resolve_id`].
+    pub fn get(&self, id: u32) -> Option<Bytes> {
+        let (group_id, file_id) = self.resolve_id(id)?;
+        self.get_file(group_id, file_id)
     }
 
     fn unpack_group(&self, group_data: &mut Js5GroupData, group_id: u32, file_id: u32) -> bool {
         if !self.is_group_valid(group_id) {
             return false;
         }
-        if group_data.packed.is_none() {
+        if group_data.packed.is_none() || group_data.corrupt {
             return false;
         }
 
@@ -521,9 +1044,25 @@ impl Js5 {
             return true;
         }
 
+        let _span = tracing::info_span!("js5_unpack_group", group_id, file_count).entered();
         let decompressed = {
             let packed = group_data.packed.as_ref().unwrap();
-            decompress(packed)
+            let _span = tracing::info_span!("js5_decompress", len = packed.len()).entered();
+            let key = self.get_xtea_key(group_id);
+            match decompress_with_key(packed, key.as_ref()) {
+                Ok(decompressed) => decompressed,
+                Err(Runetek5Error::Decryption) => {
+                    log::debug!(
+                        "Group {group_id} couldn't be decrypted, the key is wrong or missing"
+                    );
+                    return false;
+                }
+                Err(err) => {
+                    log::warn!("Group {group_id} couldn't be decompressed: {err}");
+                    self.quarantine_group(group_data);
+                    return false;
+                }
+            }
         };
 
         if self.discard_packed {
@@ -537,40 +1076,14 @@ impl Js5 {
             };
             unpacked[id] = Some(Bytes::from(decompressed));
         } else {
-            let length = decompressed.len();
-            let chunks = decompressed[length - 1] as usize;
-            let mut file_sizes = vec![0; file_count];
-            let mut meta_buf: &[u8] = &decompressed;
-            meta_buf.skip(length - 1 - file_count * chunks * 4);
-
-            for _ in 0..chunks {
-                let mut file_size = 0;
-                for j in 0..file_count {
-                    file_size += meta_buf.g4s();
-                    file_sizes[j] += file_size;
-                }
-            }
-
-            meta_buf = &decompressed;
-            meta_buf.skip(length - 1 - file_count * chunks * 4);
-
-            let mut files: Vec<Vec<u8>> = file_sizes
-                .into_iter()
-                .map(|file_size| Vec::with_capacity(file_size as usize))
-                .collect();
-
-            let mut data_buf: &[u8] = &decompressed;
-
-            for _ in 0..chunks {
-                let mut file_size = 0;
-                for j in 0..file_count {
-                    file_size += meta_buf.g4s();
-
-                    files[j].extend_from_slice(&data_buf[..file_size as usize]);
-                    data_buf.skip(file_size as usize);
+            let files = match unpack_files(&decompressed, file_count) {
+                Ok(files) => files,
+                Err(err) => {
+                    log::warn!("Group {group_id} couldn't be split into files: {err}");
+                    self.quarantine_group(group_data);
+                    return false;
                 }
-            }
-
+            };
             files.into_iter().enumerate().for_each(|(i, file)| {
                 let file_id = match file_ids {
                     Some(ids) => ids[i] as usize,
@@ -583,9 +1096,22 @@ impl Js5 {
         true
     }
 
+    /// Gets the file, None while its group is still being fetched or if it never will be. Scans
+    /// that wait for files use [`Js5::try_get_file`] instead, to skip the ones that never load.
     pub fn get_file(&self, group_id: u32, file_id: u32) -> Option<Bytes> {
+        self.try_get_file(group_id, file_id).ok().flatten()
+    }
+
+    /// Gets the file, Ok(None) while its group is still being fetched. Fails with
+    /// [`Runetek5Error::Invalid`] if the file doesn't exist or its group is quarantined, and with
+    /// [`Runetek5Error::Decryption`] if its group can't be decrypted, since those never load.
+    pub fn try_get_file(
+        &self,
+        group_id: u32,
+        file_id: u32,
+    ) -> Result<Option<Bytes>, Runetek5Error> {
         if !self.is_file_valid(group_id, file_id) {
-            return None;
+            return Err(Runetek5Error::Invalid("file id"));
         }
 
         let mut group_data = self.groups[group_id as usize].lock().unwrap();
@@ -593,12 +1119,13 @@ impl Js5 {
             Some(ref unpacked) => unpacked[file_id as usize].is_some(),
             None => false,
         };
-        if !is_unpacked_file_ready {
+        if !is_unpacked_file_ready && !self.unpack_group(&mut group_data, group_id, file_id) {
+            if let Some(err) = Self::unpack_error(&group_data) {
+                return Err(err);
+            }
+            self.fetch_group(&mut group_data, group_id);
             if !self.unpack_group(&mut group_data, group_id, file_id) {
-                self.fetch_group(&mut group_data, group_id);
-                if !self.unpack_group(&mut group_data, group_id, file_id) {
-                    return None;
-                }
+                return Self::unpack_error(&group_data).map_or(Ok(None), Err);
             }
         }
 
@@ -614,6 +1141,115 @@ impl Js5 {
             }
         }
 
-        file
+        Ok(file)
+    }
+
+    /// Why a group that didn't unpack never will, None if it's still being fetched.
+    fn unpack_error(group_data: &Js5GroupData) -> Option<Runetek5Error> {
+        if group_data.corrupt {
+            Some(Runetek5Error::Invalid("group, it's quarantined"))
+        } else if group_data.packed.is_some() {
+            // Groups that are fetched but can't be decrypted aren't fetched again.
+            Some(Runetek5Error::Decryption)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An index of one group with one file, under the given ids.
+    fn index(protocol: Js5IndexProtocol, group_id: u32, file_id: u32) -> Js5Index {
+        let group_capacity = group_id + 1;
+        let slots = group_capacity as usize;
+        let mut group_file_ids = vec![None; slots];
+        group_file_ids[group_id as usize] = Some(vec![file_id]);
+        let mut group_file_counts = vec![0; slots];
+        group_file_counts[group_id as usize] = 1;
+        let mut group_file_capacities = vec![0; slots];
+        group_file_capacities[group_id as usize] = file_id + 1;
+        Js5Index {
+            crc: 0,
+            protocol,
+            version: 0,
+            has_names: false,
+            has_whirlpool_hashes: false,
+            has_group_data_sizes: false,
+            has_uncompressed_checksums: false,
+            has_md5_hashes: false,
+            group_count: 1,
+            group_capacity,
+            group_ids: vec![group_id],
+            group_name_hashes: None,
+            group_checksums: vec![0x1234; slots],
+            group_uncompressed_checksums: None,
+            group_whirlpool_hashes: None,
+            group_data_sizes: None,
+            group_uncompressed_data_sizes: None,
+            group_versions: vec![7; slots],
+            group_file_counts,
+            group_file_capacities,
+            group_file_ids,
+            group_file_name_hashes: None,
+            group_md5_hashes: None,
+        }
+    }
+
+    #[test]
+    fn compress_round_trips() {
+        let data = b"model-viewer".repeat(10);
+        for compression_type in [
+            Js5CompressionType::None,
+            Js5CompressionType::Bzip2,
+            Js5CompressionType::Gzip,
+        ] {
+            let container = compress(&data, compression_type).unwrap();
+            assert_eq!(
+                decompress(&container).unwrap(),
+                data,
+                "{compression_type:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn compress_refuses_lzma() {
+        assert_eq!(
+            compress(b"data", Js5CompressionType::Lzma),
+            Err(Runetek5Error::UnsupportedCompressionType(
+                Js5CompressionType::Lzma
+            ))
+        );
+    }
+
+    #[test]
+    fn encode_refuses_ids_past_the_protocol() {
+        for protocol in [Js5IndexProtocol::Original, Js5IndexProtocol::Versioned] {
+            let encoded = index(protocol, 70000, 0).encode(Js5CompressionType::None);
+            assert!(
+                matches!(encoded, Err(Runetek5Error::Invalid(_))),
+                "{protocol:?}"
+            );
+            let encoded = index(protocol, 0, 70000).encode(Js5CompressionType::None);
+            assert!(
+                matches!(encoded, Err(Runetek5Error::Invalid(_))),
+                "{protocol:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn encode_round_trips_large_ids_with_smart_protocol() {
+        let encoded = index(Js5IndexProtocol::Smart, 70000, 80000)
+            .encode(Js5CompressionType::Gzip)
+            .unwrap();
+        let decoded = Js5Index::decode(&encoded, None).unwrap();
+        assert_eq!(decoded.group_ids, vec![70000]);
+        assert_eq!(decoded.get_group_version(70000), 7);
+        assert_eq!(decoded.get_group_crc(70000), 0x1234);
+        assert_eq!(decoded.get_file_ids(70000), Some(&vec![80000]));
     }
 }
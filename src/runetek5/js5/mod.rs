@@ -1,4 +1,8 @@
+pub mod bzip2;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod disk;
 pub mod js5;
 pub mod net;
+pub mod xtea;
 
 pub use js5::*;
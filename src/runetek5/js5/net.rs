@@ -1,5 +1,8 @@
+#[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
+#[cfg(target_arch = "wasm32")]
 use wasm_bindgen_futures::JsFuture;
+#[cfg(target_arch = "wasm32")]
 use web_sys::{
     js_sys::{ArrayBuffer, Uint8Array},
     Request, RequestInit, RequestMode, Response,
@@ -7,13 +10,19 @@ use web_sys::{
 
 use std::{
     collections::{hash_map::Entry, HashMap},
+    fmt,
     sync::{
         atomic::{AtomicBool, AtomicU32, Ordering},
         Arc, Mutex,
     },
+    time::Duration,
 };
 
 use super::{Js5Index, Js5ResourceProvider};
+use crate::export::json::JsonObject;
+use crate::jobs;
+use crate::runetek5::error::Runetek5Error;
+use crate::trace;
 use bytes::{Bytes, BytesMut};
 
 enum Js5RequestDataState {
@@ -28,6 +37,49 @@ impl Default for Js5RequestDataState {
     }
 }
 
+/// Why a request completed without data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Js5FetchError {
+    /// The server answered with an error status.
+    Http(u16),
+    /// The server couldn't be reached.
+    Network(String),
+    /// The data arrived but couldn't be decoded.
+    Decode(Runetek5Error),
+    /// Offline mode is on and the group wasn't stored by an earlier visit.
+    Offline,
+}
+
+impl Js5FetchError {
+    /// Whether retrying can't help, e.g. the cache or group doesn't exist or access is denied.
+    pub fn is_permanent(&self) -> bool {
+        match self {
+            // Timeouts and rate limiting go away by themselves.
+            Js5FetchError::Http(408 | 429) => false,
+            Js5FetchError::Http(status) => (400..500).contains(status),
+            Js5FetchError::Network(_) => false,
+            Js5FetchError::Decode(_) => true,
+            // Failing at once instead of retrying is the point of offline mode.
+            Js5FetchError::Offline => true,
+        }
+    }
+}
+
+impl fmt::Display for Js5FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Js5FetchError::Http(404) => write!(f, "not found (HTTP 404)"),
+            Js5FetchError::Http(status @ (401 | 403)) => {
+                write!(f, "access denied (HTTP {status})")
+            }
+            Js5FetchError::Http(status) => write!(f, "HTTP {status}"),
+            Js5FetchError::Network(message) => write!(f, "network error: {message}"),
+            Js5FetchError::Decode(err) => write!(f, "invalid data: {err}"),
+            Js5FetchError::Offline => write!(f, "not stored for offline use"),
+        }
+    }
+}
+
 pub struct Js5Request {
     pub archive_id: u8,
     pub group_id: u32,
@@ -36,6 +88,7 @@ pub struct Js5Request {
     completed: AtomicBool,
     orphaned: AtomicBool,
     data: Mutex<Js5RequestDataState>,
+    error: Mutex<Option<Js5FetchError>>,
 }
 
 impl Js5Request {
@@ -48,6 +101,7 @@ impl Js5Request {
             completed: AtomicBool::new(false),
             orphaned: AtomicBool::new(false),
             data: Mutex::new(Js5RequestDataState::NotLoaded),
+            error: Mutex::new(None),
         }
     }
 
@@ -91,6 +145,14 @@ impl Js5Request {
         *req_data = Js5RequestDataState::Loaded(data);
     }
 
+    pub fn fail(&self, error: Js5FetchError) {
+        *self.error.lock().unwrap() = Some(error);
+    }
+
+    pub fn get_error(&self) -> Option<Js5FetchError> {
+        self.error.lock().unwrap().clone()
+    }
+
     pub fn get_data(&self) -> Option<Bytes> {
         let mut req_data = self.data.lock().unwrap();
         match &*req_data {
@@ -109,10 +171,31 @@ impl Js5Request {
     }
 }
 
+/// Failed attempts at a request that can still succeed, and when it may be requested again.
+#[derive(Debug, Clone, Copy)]
+struct Js5Retry {
+    attempts: u32,
+    /// [`trace::timestamp`] after which the request is made again.
+    retry_at: f64,
+}
+
+impl Js5Retry {
+    fn is_waiting(&self) -> bool {
+        trace::timestamp() < self.retry_at
+    }
+}
+
 pub struct Openrs2Js5ResourceProviderState {
     index: Option<Arc<Js5Index>>,
     index_request: Option<Arc<Js5Request>>,
     requests: HashMap<u32, Arc<Js5Request>>,
+    /// Set once the index failed for good, it isn't requested again.
+    index_error: Option<Js5FetchError>,
+    index_retry: Option<Js5Retry>,
+    /// Groups that failed for good and aren't requested again.
+    failed_groups: HashMap<u32, Js5FetchError>,
+    /// Groups that failed in a way that may go away, waiting before they're requested again.
+    group_retries: HashMap<u32, Js5Retry>,
 }
 
 impl Openrs2Js5ResourceProviderState {
@@ -121,6 +204,10 @@ impl Openrs2Js5ResourceProviderState {
             index: None,
             index_request,
             requests: HashMap::new(),
+            index_error: None,
+            index_retry: None,
+            failed_groups: HashMap::new(),
+            group_retries: HashMap::new(),
         }
     }
 }
@@ -132,6 +219,11 @@ pub struct Openrs2Js5ResourceProvider {
 }
 
 impl Openrs2Js5ResourceProvider {
+    /// Attempts at a request before a failure that may go away counts as one that won't.
+    const MAX_ATTEMPTS: u32 = 5;
+    /// The wait before the first retry, doubled after each further failure.
+    const RETRY_DELAY: Duration = Duration::from_secs(1);
+
     pub fn new(archive_id: u8, net_client: Arc<Openrs2Js5NetClient>) -> Self {
         let index_request = Self::request_index(&net_client, archive_id);
         Self {
@@ -144,6 +236,74 @@ impl Openrs2Js5ResourceProvider {
     fn request_index(net_client: &Openrs2Js5NetClient, archive_id: u8) -> Option<Arc<Js5Request>> {
         net_client.queue_request(Js5Index::ARCHIVE_ID, archive_id as u32, true)
     }
+
+    /// Counts a failed attempt at a request. Returns when to retry, or the error once it failed
+    /// for good, either because retrying can't help or because it ran out of attempts.
+    fn retry_after(
+        &self,
+        retry: Option<Js5Retry>,
+        error: Js5FetchError,
+    ) -> Result<Js5Retry, Js5FetchError> {
+        let attempts = retry.map_or(0, |retry| retry.attempts) + 1;
+        if error.is_permanent() || attempts >= Self::MAX_ATTEMPTS {
+            return Err(error);
+        }
+        let delay = Self::RETRY_DELAY * 2u32.pow(attempts - 1);
+        self.net_client.raise_after(delay);
+        Ok(Js5Retry {
+            attempts,
+            retry_at: trace::timestamp() + delay.as_secs_f64() * 1_000_000.0,
+        })
+    }
+
+    pub fn get_archive_id(&self) -> u8 {
+        self.archive_id
+    }
+
+    /// Why the index can't be loaded, once it failed for good or ran out of retries.
+    pub fn get_index_error(&self) -> Option<Js5FetchError> {
+        self.state.lock().unwrap().index_error.clone()
+    }
+
+    /// The groups that failed for good or ran out of retries, with the reasons.
+    pub fn get_failed_groups(&self) -> Vec<(u32, Js5FetchError)> {
+        let state = self.state.lock().unwrap();
+        let mut failed: Vec<_> = state
+            .failed_groups
+            .iter()
+            .map(|(&group_id, error)| (group_id, error.clone()))
+            .collect();
+        failed.sort_by_key(|(group_id, _)| *group_id);
+        failed
+    }
+
+    /// Waits until `check` returns something, checking again each time one of the client's
+    /// requests completes or a failed one is due to be retried.
+    pub async fn wait_for<T>(&self, check: impl FnMut() -> Option<T>) -> T {
+        self.net_client.wait_for(check).await
+    }
+
+    /// Waits for the index, failing once it failed for good or ran out of retries.
+    pub async fn index_ready(&self) -> Result<Arc<Js5Index>, Js5FetchError> {
+        self.wait_for(|| match self.fetch_index() {
+            Some(index) => Some(Ok(index)),
+            None => self.get_index_error().map(Err),
+        })
+        .await
+    }
+
+    /// Waits until `ready` returns true, e.g. once the groups it fetches arrived. Fails with the
+    /// first of the archive's groups that failed for good, since that group is never requested
+    /// again.
+    pub async fn ready(&self, mut ready: impl FnMut() -> bool) -> Result<(), (u32, Js5FetchError)> {
+        self.wait_for(|| {
+            if ready() {
+                return Some(Ok(()));
+            }
+            self.get_failed_groups().into_iter().next().map(Err)
+        })
+        .await
+    }
 }
 
 impl Js5ResourceProvider for Openrs2Js5ResourceProvider {
@@ -152,9 +312,15 @@ impl Js5ResourceProvider for Openrs2Js5ResourceProvider {
         if let Some(index) = &state.index {
             return Some(index.clone());
         }
+        if state.index_error.is_some() {
+            return None;
+        }
         let request = if let Some(request) = &state.index_request {
             request.clone()
         } else {
+            if state.index_retry.is_some_and(|retry| retry.is_waiting()) {
+                return None;
+            }
             let request = Self::request_index(&self.net_client, self.archive_id)?;
             state.index_request = Some(request.clone());
             request
@@ -165,7 +331,14 @@ impl Js5ResourceProvider for Openrs2Js5ResourceProvider {
         }
 
         if let Some(data) = request.get_data() {
-            let mut index = Js5Index::decode(&data, None);
+            let mut index = match Js5Index::decode(&data, None) {
+                Ok(index) => index,
+                Err(err) => {
+                    state.index_request = None;
+                    state.index_error = Some(Js5FetchError::Decode(err));
+                    return None;
+                }
+            };
             index.clear_data_sizes();
 
             // if !request.is_cached() {
@@ -180,6 +353,7 @@ impl Js5ResourceProvider for Openrs2Js5ResourceProvider {
             let index = Arc::new(index);
 
             state.index = Some(index.clone());
+            state.index_retry = None;
             // state.group_status = vec![Js5GroupStatus::NotLoaded; index.group_capacity as usize];
             // state.verified_groups = 0;
 
@@ -188,6 +362,12 @@ impl Js5ResourceProvider for Openrs2Js5ResourceProvider {
             Some(index)
         } else {
             state.index_request = None;
+            if let Some(error) = request.get_error() {
+                match self.retry_after(state.index_retry, error) {
+                    Ok(retry) => state.index_retry = Some(retry),
+                    Err(error) => state.index_error = Some(error),
+                }
+            }
 
             None
         }
@@ -195,9 +375,17 @@ impl Js5ResourceProvider for Openrs2Js5ResourceProvider {
 
     fn fetch_group(&self, group_id: u32) -> Option<Bytes> {
         let mut state = self.state.lock().unwrap();
+        if state.failed_groups.contains_key(&group_id) {
+            return None;
+        }
 
+        let waiting = state
+            .group_retries
+            .get(&group_id)
+            .is_some_and(Js5Retry::is_waiting);
         let request = match state.requests.entry(group_id) {
             Entry::Occupied(entry) => entry.get().clone(),
+            Entry::Vacant(_) if waiting => return None,
             Entry::Vacant(entry) => {
                 let request = self
                     .net_client
@@ -215,26 +403,125 @@ impl Js5ResourceProvider for Openrs2Js5ResourceProvider {
 
         state.requests.remove(&group_id);
 
-        request.get_data()
+        let data = request.get_data();
+        if data.is_some() {
+            state.group_retries.remove(&group_id);
+        } else if let Some(error) = request.get_error() {
+            let retry = state.group_retries.remove(&group_id);
+            match self.retry_after(retry, error) {
+                Ok(retry) => {
+                    state.group_retries.insert(group_id, retry);
+                }
+                Err(error) => {
+                    log::error!(
+                        "Group {} of archive {} failed: {error}",
+                        group_id,
+                        self.archive_id
+                    );
+                    state.failed_groups.insert(group_id, error);
+                }
+            }
+        }
+        data
     }
+
+    fn prefetch_group(&self, group_id: u32) {
+        let mut state = self.state.lock().unwrap();
+        if state.failed_groups.contains_key(&group_id)
+            || state.requests.contains_key(&group_id)
+            || state
+                .group_retries
+                .get(&group_id)
+                .is_some_and(Js5Retry::is_waiting)
+        {
+            return;
+        }
+        // Picked up by fetch_group once it completes.
+        if let Some(request) = self
+            .net_client
+            .queue_request(self.archive_id, group_id, false)
+        {
+            state.requests.insert(group_id, request);
+        }
+    }
+}
+
+/// Requests queued by every client that haven't completed yet, and failed ones waiting to be
+/// retried.
+static IN_FLIGHT_REQUEST_COUNT: AtomicU32 = AtomicU32::new(0);
+
+pub fn in_flight_request_count() -> u32 {
+    IN_FLIGHT_REQUEST_COUNT.load(Ordering::Acquire)
+}
+
+static OFFLINE_MODE: AtomicBool = AtomicBool::new(false);
+
+/// Whether groups are only served from the copies earlier visits stored.
+pub fn is_offline_mode() -> bool {
+    OFFLINE_MODE.load(Ordering::Acquire)
+}
+
+/// Serves groups only from the browser's copies of earlier fetches, groups it doesn't have fail at
+/// once with [`Js5FetchError::Offline`]. Remembered across visits on the web. The desktop build
+/// keeps no copies of streamed groups, so every request fails there.
+pub fn set_offline_mode(offline: bool) {
+    OFFLINE_MODE.store(offline, Ordering::Release);
+    #[cfg(target_arch = "wasm32")]
+    store_offline_mode(offline);
 }
 
+/// Turns offline mode back on if the previous visit left it on.
+#[cfg(target_arch = "wasm32")]
+pub fn restore_offline_mode() {
+    OFFLINE_MODE.store(load_offline_mode(), Ordering::Release);
+}
+
+#[cfg(target_arch = "wasm32")]
 #[wasm_bindgen(module = "/src/test.js")]
 extern "C" {
     #[wasm_bindgen(catch)]
-    async fn fetch_group(archive_id: u8, group_id: u32) -> Result<JsValue, JsValue>;
+    async fn fetch_group(
+        cache_id: u32,
+        archive_id: u8,
+        group_id: u32,
+        offline: bool,
+    ) -> Result<JsValue, JsValue>;
+    fn load_offline_mode() -> bool;
+    fn store_offline_mode(offline: bool);
 }
 
+/// Fetches groups from the OpenRS2 archive, through the browser's fetch on the web and on a
+/// thread per request on the desktop.
 pub struct Openrs2Js5NetClient {
     cache_id: u32,
     queued_request_count: Arc<AtomicU32>,
+    /// Raised each time a request completes.
+    completions: Arc<jobs::Signal>,
+    #[cfg(not(target_arch = "wasm32"))]
+    agent: ureq::Agent,
 }
 
 impl Openrs2Js5NetClient {
+    /// The OpenRS2 cache opened unless another one is picked.
+    pub const DEFAULT_CACHE_ID: u32 = 2064;
+
+    /// Requests in flight at once.
+    const REQUEST_LIMIT: u32 = 20;
+    /// Prefetches only take this many of the slots, so they never hold up a request something is
+    /// waiting for.
+    const PREFETCH_REQUEST_LIMIT: u32 = 12;
+
+    /// How long a desktop request may take before it fails and is retried.
+    #[cfg(not(target_arch = "wasm32"))]
+    const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
     pub fn new(cache_id: u32) -> Self {
         Self {
             cache_id,
             queued_request_count: Arc::new(AtomicU32::new(0)),
+            completions: Arc::new(jobs::Signal::new()),
+            #[cfg(not(target_arch = "wasm32"))]
+            agent: ureq::AgentBuilder::new().timeout(Self::TIMEOUT).build(),
         }
     }
 
@@ -244,38 +531,140 @@ impl Openrs2Js5NetClient {
         group_id: u32,
         urgent: bool,
     ) -> Option<Arc<Js5Request>> {
-        if self.queued_request_count.load(Ordering::Acquire) >= 20 {
+        let limit = if urgent {
+            Self::REQUEST_LIMIT
+        } else {
+            Self::PREFETCH_REQUEST_LIMIT
+        };
+        // Checked and taken at once, so racing callers can't both take the last slot.
+        if self
+            .queued_request_count
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| {
+                (count < limit).then_some(count + 1)
+            })
+            .is_err()
+        {
             return None;
         }
-
-        self.queued_request_count.fetch_add(1, Ordering::Release);
+        IN_FLIGHT_REQUEST_COUNT.fetch_add(1, Ordering::Release);
 
         let request = Arc::new(Js5Request::new(archive_id, group_id, urgent, false));
 
-        wasm_bindgen_futures::spawn_local({
-            let cache_id = self.cache_id;
-            let request = request.clone();
-            let queued_request_count = self.queued_request_count.clone();
-            async move {
-                match Self::fetch(cache_id, archive_id, group_id).await {
-                    Ok(data) => {
-                        request.complete_data(data);
-                        request.mark_complete();
-                    }
-                    Err(e) => {
-                        log::error!("Failed to fetch group: {:?}", e);
-                        request.mark_complete();
-                    }
-                }
-                queued_request_count.fetch_sub(1, Ordering::Release);
-            }
+        let cache_id = self.cache_id;
+        let queued_request = request.clone();
+        let queued_request_count = self.queued_request_count.clone();
+        let completions = self.completions.clone();
+        let started = trace::timestamp();
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let agent = self.agent.clone();
+        jobs::spawn(async move {
+            #[cfg(target_arch = "wasm32")]
+            let result = Self::fetch(cache_id, archive_id, group_id).await;
+            // Blocking is fine natively, where the job has its own thread.
+            #[cfg(not(target_arch = "wasm32"))]
+            let result = Self::fetch(&agent, cache_id, archive_id, group_id);
+            Self::complete(&queued_request, result, started);
+            queued_request_count.fetch_sub(1, Ordering::Release);
+            // After freeing the slot, so waiters can queue their next request.
+            completions.raise();
         });
 
         Some(request)
     }
 
-    pub async fn fetch(cache_id: u32, archive_id: u8, group_id: u32) -> Result<Bytes, JsValue> {
-        let array_buffer = fetch_group(archive_id, group_id).await?;
+    fn complete(request: &Js5Request, result: Result<Bytes, Js5FetchError>, started: f64) {
+        match result {
+            Ok(data) => {
+                trace::record_interval(
+                    "js5_net_fetch",
+                    module_path!(),
+                    started,
+                    JsonObject::new()
+                        .number("archive_id", request.archive_id)
+                        .number("group_id", request.group_id)
+                        .number("len", data.len() as f64),
+                );
+                request.complete_data(data);
+                request.mark_complete();
+            }
+            Err(e) => {
+                log::error!("Failed to fetch group: {e}");
+                request.fail(e);
+                request.mark_complete();
+            }
+        }
+        IN_FLIGHT_REQUEST_COUNT.fetch_sub(1, Ordering::Release);
+    }
+
+    /// Raises the completion signal once the delay passed, so waiters check again for requests
+    /// that wait to be retried.
+    fn raise_after(&self, delay: Duration) {
+        let completions = self.completions.clone();
+        IN_FLIGHT_REQUEST_COUNT.fetch_add(1, Ordering::Release);
+        jobs::spawn(async move {
+            jobs::sleep(delay).await;
+            IN_FLIGHT_REQUEST_COUNT.fetch_sub(1, Ordering::Release);
+            completions.raise();
+        });
+    }
+
+    pub fn get_cache_id(&self) -> u32 {
+        self.cache_id
+    }
+
+    /// Waits until `check` returns something, checking again each time a request completes.
+    pub async fn wait_for<T>(&self, check: impl FnMut() -> Option<T>) -> T {
+        self.completions.wait_for(check).await
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn fetch(
+        agent: &ureq::Agent,
+        cache_id: u32,
+        archive_id: u8,
+        group_id: u32,
+    ) -> Result<Bytes, Js5FetchError> {
+        use std::io::Read as _;
+
+        if is_offline_mode() {
+            return Err(Js5FetchError::Offline);
+        }
+        let url = format!(
+            "https://archive.openrs2.org/caches/runescape/{cache_id}/archives/{archive_id}/groups/{group_id}.dat"
+        );
+        let response = agent.get(&url).call().map_err(|e| match e {
+            ureq::Error::Status(status, _) => Js5FetchError::Http(status),
+            ureq::Error::Transport(transport) => Js5FetchError::Network(transport.to_string()),
+        })?;
+        let mut data = Vec::new();
+        response
+            .into_reader()
+            .read_to_end(&mut data)
+            .map_err(|e| Js5FetchError::Network(e.to_string()))?;
+
+        Ok(Bytes::from(data))
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub async fn fetch(
+        cache_id: u32,
+        archive_id: u8,
+        group_id: u32,
+    ) -> Result<Bytes, Js5FetchError> {
+        let array_buffer = fetch_group(cache_id, archive_id, group_id, is_offline_mode())
+            .await
+            .map_err(|e| {
+                // The script rejects with the response status for HTTP errors, and "offline"
+                // for groups it has no copy of in offline mode.
+                if let Some(status) = e.as_f64() {
+                    Js5FetchError::Http(status as u16)
+                } else if e.as_string().as_deref() == Some("offline") {
+                    Js5FetchError::Offline
+                } else {
+                    Js5FetchError::Network(format!("{e:?}"))
+                }
+            })?;
         assert!(array_buffer.is_instance_of::<ArrayBuffer>());
         let typed_array = Uint8Array::new(&array_buffer);
         let mut data = vec![0; typed_array.length() as usize];
@@ -285,6 +674,7 @@ impl Openrs2Js5NetClient {
     }
 }
 
+#[cfg(target_arch = "wasm32")]
 async fn run(repo: String) -> Result<JsValue, JsValue> {
     let opts = RequestInit::new();
     opts.set_method("GET");
@@ -0,0 +1,79 @@
+use std::{fmt, str::FromStr};
+
+/// A 128 bit XTEA key, as used to encrypt map squares and a few other groups. Keys are usually
+/// published as four signed 32 bit integers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct XteaKey(pub [u32; 4]);
+
+impl XteaKey {
+    /// The all zero key, which the client treats as no encryption.
+    pub const ZERO: XteaKey = XteaKey([0; 4]);
+
+    const GOLDEN_RATIO: u32 = 0x9e37_79b9;
+    const ROUNDS: u32 = 32;
+    const BLOCK_SIZE: usize = 8;
+
+    pub fn from_ints(key: [i32; 4]) -> Self {
+        Self(key.map(|k| k as u32))
+    }
+
+    pub fn is_zero(&self) -> bool {
+        *self == Self::ZERO
+    }
+
+    /// Decrypts the data in place. A trailing partial block is left as is, like the client does.
+    pub fn decrypt(&self, data: &mut [u8]) {
+        if self.is_zero() {
+            return;
+        }
+        let key = &self.0;
+        for block in data.chunks_exact_mut(Self::BLOCK_SIZE) {
+            let mut v0 = u32::from_be_bytes([block[0], block[1], block[2], block[3]]);
+            let mut v1 = u32::from_be_bytes([block[4], block[5], block[6], block[7]]);
+            let mut sum = Self::GOLDEN_RATIO.wrapping_mul(Self::ROUNDS);
+            for _ in 0..Self::ROUNDS {
+                v1 = v1.wrapping_sub(
+                    (((v0 << 4) ^ (v0 >> 5)).wrapping_add(v0))
+                        ^ sum.wrapping_add(key[((sum >> 11) & 3) as usize]),
+                );
+                sum = sum.wrapping_sub(Self::GOLDEN_RATIO);
+                v0 = v0.wrapping_sub(
+                    (((v1 << 4) ^ (v1 >> 5)).wrapping_add(v1))
+                        ^ sum.wrapping_add(key[(sum & 3) as usize]),
+                );
+            }
+            block[..4].copy_from_slice(&v0.to_be_bytes());
+            block[4..].copy_from_slice(&v1.to_be_bytes());
+        }
+    }
+}
+
+impl fmt::Display for XteaKey {
+    /// Formats the key as four signed integers, the way keys are published.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [k0, k1, k2, k3] = self.0.map(|k| k as i32);
+        write!(f, "{k0}, {k1}, {k2}, {k3}")
+    }
+}
+
+impl FromStr for XteaKey {
+    type Err = String;
+
+    /// Parses four integers separated by commas or whitespace, signed or unsigned.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = text
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|part| !part.is_empty())
+            .collect();
+        let [k0, k1, k2, k3] = parts[..] else {
+            return Err(format!("expected 4 integers, got {}", parts.len()));
+        };
+        let parse = |part: &str| {
+            part.parse::<i32>()
+                .map(|k| k as u32)
+                .or_else(|_| part.parse::<u32>())
+                .map_err(|_| format!("{part:?} is not a 32 bit integer"))
+        };
+        Ok(Self([parse(k0)?, parse(k1)?, parse(k2)?, parse(k3)?]))
+    }
+}
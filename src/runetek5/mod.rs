@@ -1,4 +1,24 @@
+//! The RuneTek 5 formats: JS5 archives in [`js5`], the byte buffers everything is read from in
+//! [`io`], and the models, textures and sprites in [`graphics`]. The types most uses start from
+//! are re-exported here.
+
+pub mod animation;
+pub mod error;
 pub mod graphics;
+pub mod interface;
 pub mod io;
 pub mod js5;
 pub mod math;
+pub mod model_users;
+pub mod npc;
+pub mod obj;
+pub mod scene;
+
+pub use error::Runetek5Error;
+pub use graphics::{
+    model::{ModelLit, ModelUnlit},
+    sprite::SpriteData,
+    texture::TextureProvider,
+};
+pub use io::packet::{Packet, PacketMut};
+pub use js5::{Js5, Js5Index, Js5ResourceProvider};
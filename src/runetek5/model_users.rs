@@ -0,0 +1,93 @@
+//! Which NPCs and items use each model, indexed as their definitions are decoded so a model can be
+//! traced back to what shows it in game.
+
+use std::{collections::HashMap, fmt};
+
+use super::{npc::NpcType, obj::ObjType};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DefinitionKind {
+    Npc,
+    Item,
+}
+
+impl DefinitionKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DefinitionKind::Npc => "NPC",
+            DefinitionKind::Item => "Item",
+        }
+    }
+}
+
+/// A definition using a model.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelUser {
+    pub kind: DefinitionKind,
+    pub id: u32,
+    pub name: String,
+}
+
+impl fmt::Display for ModelUser {
+    /// Writes e.g. `NPC 50 (Zezima clone)`, leaving out the name if the definition has none.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.kind.name(), self.id)?;
+        if !self.name.is_empty() && self.name != "null" {
+            write!(f, " ({})", self.name)?;
+        }
+        Ok(())
+    }
+}
+
+/// The definitions using each model, in the order they were added.
+#[derive(Debug, Default)]
+pub struct ModelUserIndex {
+    users: HashMap<u32, Vec<ModelUser>>,
+}
+
+impl ModelUserIndex {
+    pub fn users(&self, model_id: u32) -> &[ModelUser] {
+        self.users.get(&model_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// How many models are used by at least one definition.
+    pub fn model_count(&self) -> usize {
+        self.users.len()
+    }
+
+    fn add(&mut self, model_ids: impl IntoIterator<Item = u32>, user: ModelUser) {
+        let mut added = Vec::new();
+        for model_id in model_ids {
+            // Definitions can use a model more than once, e.g. as both of an item's wearables.
+            if !added.contains(&model_id) {
+                added.push(model_id);
+                self.users.entry(model_id).or_default().push(user.clone());
+            }
+        }
+    }
+
+    pub fn add_npc(&mut self, npc: &NpcType) {
+        let user = ModelUser {
+            kind: DefinitionKind::Npc,
+            id: npc.id,
+            name: npc.name.clone(),
+        };
+        self.add(npc.models.iter().copied(), user);
+    }
+
+    /// Adds an item's ground model and the models worn by either body type.
+    pub fn add_obj(&mut self, obj: &ObjType) {
+        let user = ModelUser {
+            kind: DefinitionKind::Item,
+            id: obj.id,
+            name: obj.name.clone(),
+        };
+        let model_ids = obj
+            .model
+            .iter()
+            .chain(obj.male_models.iter().flatten())
+            .chain(obj.female_models.iter().flatten())
+            .copied();
+        self.add(model_ids, user);
+    }
+}
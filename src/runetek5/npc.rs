@@ -0,0 +1,239 @@
+//! NPC definitions. Newer caches keep them in their own archive, 128 to a group, older ones in a
+//! group of the config archive. Only the fields that pick, colour and size the NPC's models are
+//! kept; opcodes are decoded until one isn't known.
+
+use crate::runetek5::{io::packet::Packet, js5::Js5};
+
+/// Where a cache keeps its NPC definitions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NpcArchive {
+    /// The NPC archive, with the NPC id split into a group and a file.
+    Npcs,
+    /// One file per NPC in the NPC group of the config archive.
+    Config,
+}
+
+impl NpcArchive {
+    pub const NPC_ARCHIVE_ID: u8 = 18;
+    pub const CONFIG_ARCHIVE_ID: u8 = 2;
+    pub const CONFIG_GROUP_ID: u32 = 9;
+    const FILES_PER_GROUP: u32 = 128;
+
+    pub fn archive_id(&self) -> u8 {
+        match self {
+            NpcArchive::Npcs => Self::NPC_ARCHIVE_ID,
+            NpcArchive::Config => Self::CONFIG_ARCHIVE_ID,
+        }
+    }
+
+    /// The group and file an NPC is stored in.
+    pub fn location(&self, npc_id: u32) -> (u32, u32) {
+        match self {
+            NpcArchive::Npcs => (
+                npc_id / Self::FILES_PER_GROUP,
+                npc_id % Self::FILES_PER_GROUP,
+            ),
+            NpcArchive::Config => (Self::CONFIG_GROUP_ID, npc_id),
+        }
+    }
+
+    /// The NPC stored in a file of a group, the inverse of [`NpcArchive::location`].
+    pub fn npc_id(&self, group_id: u32, file_id: u32) -> u32 {
+        match self {
+            NpcArchive::Npcs => group_id * Self::FILES_PER_GROUP + file_id,
+            NpcArchive::Config => file_id,
+        }
+    }
+
+    /// The groups holding NPCs.
+    pub fn group_ids(&self, js5: &Js5) -> Vec<u32> {
+        match self {
+            NpcArchive::Npcs => js5.index.group_ids.clone(),
+            NpcArchive::Config => vec![Self::CONFIG_GROUP_ID],
+        }
+    }
+
+    /// Reads every NPC of a group, None while it is being fetched. Corrupt and missing groups have
+    /// none.
+    pub fn load_group(&self, js5: &Js5, group_id: u32) -> Option<Vec<NpcType>> {
+        if js5.is_group_corrupt(group_id) {
+            return Some(Vec::new());
+        }
+        let Some(file_ids) = js5.get_file_ids(group_id) else {
+            return Some(Vec::new());
+        };
+        let mut npcs = Vec::with_capacity(file_ids.len());
+        for &file_id in file_ids.iter() {
+            let data = js5.get_file(group_id, file_id)?;
+            npcs.extend(NpcType::decode(self.npc_id(group_id, file_id), &data));
+        }
+        Some(npcs)
+    }
+
+    /// Reads an NPC, None while its group is being fetched or if it doesn't exist.
+    pub fn load(&self, js5: &Js5, npc_id: u32) -> Option<NpcType> {
+        let (group_id, file_id) = self.location(npc_id);
+        let data = js5.get_file(group_id, file_id)?;
+        NpcType::decode(npc_id, &data)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NpcType {
+    pub id: u32,
+    pub name: String,
+    /// The models merged into the NPC's body.
+    pub models: Vec<u32>,
+    pub size: u8,
+    /// None for NPCs that can't be fought.
+    pub combat_level: Option<u16>,
+    /// HSL colours of the models replaced by others, as (old, new).
+    pub recolours: Vec<(u16, u16)>,
+    /// Textures of the models replaced by others, as (old, new).
+    pub retextures: Vec<(u16, u16)>,
+    /// Horizontal and vertical scale of the models, 128 is the original size.
+    pub resize_h: u16,
+    pub resize_v: u16,
+    pub ambient: i8,
+    pub contrast: i16,
+    /// The first opcode that couldn't be decoded, fields after it are missing.
+    pub unknown_opcode: Option<u8>,
+}
+
+impl Default for NpcType {
+    fn default() -> Self {
+        Self {
+            id: 0,
+            name: "null".to_owned(),
+            models: Vec::new(),
+            size: 1,
+            combat_level: None,
+            recolours: Vec::new(),
+            retextures: Vec::new(),
+            resize_h: 128,
+            resize_v: 128,
+            ambient: 0,
+            contrast: 0,
+            unknown_opcode: None,
+        }
+    }
+}
+
+fn read_pairs(buf: &mut &[u8]) -> Option<Vec<(u16, u16)>> {
+    let count = buf.try_g1()? as usize;
+    (0..count)
+        .map(|_| Some((buf.try_g2()?, buf.try_g2()?)))
+        .collect()
+}
+
+impl NpcType {
+    /// Decodes an NPC, None if the data ends early.
+    pub fn decode(id: u32, data: &[u8]) -> Option<Self> {
+        let mut npc = Self {
+            id,
+            ..Default::default()
+        };
+        let mut buf = data;
+        loop {
+            let opcode = buf.try_g1()?;
+            if opcode == 0 {
+                break;
+            }
+            if npc.decode_opcode(opcode, &mut buf).is_none() {
+                // Later opcodes can't be found without knowing the length of this one.
+                npc.unknown_opcode = Some(opcode);
+                break;
+            }
+        }
+        Some(npc)
+    }
+
+    fn decode_opcode(&mut self, opcode: u8, buf: &mut &[u8]) -> Option<()> {
+        match opcode {
+            1 => {
+                let count = buf.try_g1()? as usize;
+                self.models = (0..count)
+                    .map(|_| buf.try_g2().map(u32::from))
+                    .collect::<Option<_>>()?;
+            }
+            2 => self.name = buf.try_get_str_cp1252_to_utf8()?,
+            12 => self.size = buf.try_g1()?,
+            // Stand, walk and turn animations.
+            13..=16 | 18 => buf.try_skip(2)?,
+            17 => buf.try_skip(8)?,
+            // Options.
+            30..=34 => {
+                buf.try_get_str_cp1252_to_utf8()?;
+            }
+            40 => self.recolours = read_pairs(buf)?,
+            41 => self.retextures = read_pairs(buf)?,
+            // Recolour palette indices.
+            42 => {
+                let count = buf.try_g1()? as usize;
+                buf.try_skip(count)?;
+            }
+            // Chat head models.
+            60 => {
+                let count = buf.try_g1()? as usize;
+                buf.try_skip(count * 2)?;
+            }
+            // Hidden on the minimap, drawn above players, not interactable and not rotating.
+            93 | 99 | 107 | 109 | 111 => {}
+            95 => self.combat_level = Some(buf.try_g2()?),
+            // Head icon and turn speed.
+            102 | 103 => buf.try_skip(2)?,
+            97 => self.resize_h = buf.try_g2()?,
+            98 => self.resize_v = buf.try_g2()?,
+            100 => self.ambient = buf.try_g1s()?,
+            // Stored divided by 5.
+            101 => self.contrast = buf.try_g1s()? as i16 * 5,
+            // NPCs shown instead depending on a varbit or varp.
+            106 | 118 => {
+                buf.try_skip(4)?;
+                if opcode == 118 {
+                    buf.try_skip(2)?;
+                }
+                let count = buf.try_g1()? as usize;
+                buf.try_skip((count + 1) * 2)?;
+            }
+            // Shadow colours and modifiers.
+            113 => buf.try_skip(4)?,
+            114 => buf.try_skip(2)?,
+            // Walk mask and respawn direction.
+            119 | 125 => buf.try_skip(1)?,
+            // Per model translations.
+            121 => {
+                let count = buf.try_g1()? as usize;
+                buf.try_skip(count * 4)?;
+            }
+            // Hitbar sprite, render (bas) id and movement speed.
+            122 | 123 | 127 => buf.try_skip(2)?,
+            128 => buf.try_skip(1)?,
+            // Idle, crawl, walk and run sounds with their range.
+            134 => buf.try_skip(9)?,
+            // Op and cursor pairs.
+            135 | 136 => buf.try_skip(3)?,
+            // Attack cursor.
+            137 => buf.try_skip(2)?,
+            249 => {
+                let count = buf.try_g1()?;
+                for _ in 0..count {
+                    let is_string = buf.try_g1()? == 1;
+                    buf.try_skip(3)?;
+                    if is_string {
+                        buf.try_get_str_cp1252_to_utf8()?;
+                    } else {
+                        buf.try_skip(4)?;
+                    }
+                }
+            }
+            _ => return None,
+        }
+        Some(())
+    }
+
+    /// The lighting of the NPC's models, relative to the client's defaults for NPC models.
+    pub fn lighting(&self) -> (i16, i16) {
+        (64 + self.ambient as i16, 850 + self.contrast)
+    }
+}
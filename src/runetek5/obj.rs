@@ -0,0 +1,285 @@
+//! Item (obj) definitions. Newer caches keep them in their own archive, 256 to a group, older ones
+//! in a group of the config archive. Only the fields that pick, colour and place the item's
+//! models are kept; opcodes are decoded until one isn't known.
+
+use crate::runetek5::{io::packet::Packet, js5::Js5};
+
+/// Where a cache keeps its item definitions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjArchive {
+    /// The obj archive, with the item id split into a group and a file.
+    Objs,
+    /// One file per item in the obj group of the config archive.
+    Config,
+}
+
+impl ObjArchive {
+    pub const OBJ_ARCHIVE_ID: u8 = 19;
+    pub const CONFIG_ARCHIVE_ID: u8 = 2;
+    pub const CONFIG_GROUP_ID: u32 = 10;
+    const FILES_PER_GROUP: u32 = 256;
+
+    pub fn archive_id(&self) -> u8 {
+        match self {
+            ObjArchive::Objs => Self::OBJ_ARCHIVE_ID,
+            ObjArchive::Config => Self::CONFIG_ARCHIVE_ID,
+        }
+    }
+
+    /// The group and file an item is stored in.
+    pub fn location(&self, obj_id: u32) -> (u32, u32) {
+        match self {
+            ObjArchive::Objs => (
+                obj_id / Self::FILES_PER_GROUP,
+                obj_id % Self::FILES_PER_GROUP,
+            ),
+            ObjArchive::Config => (Self::CONFIG_GROUP_ID, obj_id),
+        }
+    }
+
+    /// The item stored in a file of a group, the inverse of [`ObjArchive::location`].
+    pub fn obj_id(&self, group_id: u32, file_id: u32) -> u32 {
+        match self {
+            ObjArchive::Objs => group_id * Self::FILES_PER_GROUP + file_id,
+            ObjArchive::Config => file_id,
+        }
+    }
+
+    /// The groups holding items.
+    pub fn group_ids(&self, js5: &Js5) -> Vec<u32> {
+        match self {
+            ObjArchive::Objs => js5.index.group_ids.clone(),
+            ObjArchive::Config => vec![Self::CONFIG_GROUP_ID],
+        }
+    }
+
+    /// One past the highest item id in the archive.
+    pub fn obj_count(&self, js5: &Js5) -> u32 {
+        match self {
+            ObjArchive::Objs => {
+                let last_group_id = js5.get_last_group_id();
+                let last_file_id = js5
+                    .get_file_ids(last_group_id)
+                    .and_then(|file_ids| file_ids.last().copied());
+                match last_file_id {
+                    Some(file_id) => last_group_id * Self::FILES_PER_GROUP + file_id + 1,
+                    None => 0,
+                }
+            }
+            ObjArchive::Config => js5
+                .get_file_ids(Self::CONFIG_GROUP_ID)
+                .and_then(|file_ids| file_ids.last().map(|&file_id| file_id + 1))
+                .unwrap_or(0),
+        }
+    }
+
+    /// Reads every item of a group, None while it is being fetched. Corrupt and missing groups have
+    /// none.
+    pub fn load_group(&self, js5: &Js5, group_id: u32) -> Option<Vec<ObjType>> {
+        if js5.is_group_corrupt(group_id) {
+            return Some(Vec::new());
+        }
+        let Some(file_ids) = js5.get_file_ids(group_id) else {
+            return Some(Vec::new());
+        };
+        let mut objs = Vec::with_capacity(file_ids.len());
+        for &file_id in file_ids.iter() {
+            let data = js5.get_file(group_id, file_id)?;
+            objs.extend(ObjType::decode(self.obj_id(group_id, file_id), &data));
+        }
+        Some(objs)
+    }
+
+    /// Reads an item, None while its group is being fetched or if it doesn't exist.
+    pub fn load(&self, js5: &Js5, obj_id: u32) -> Option<ObjType> {
+        let (group_id, file_id) = self.location(obj_id);
+        let data = js5.get_file(group_id, file_id)?;
+        ObjType::decode(obj_id, &data)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ObjType {
+    pub id: u32,
+    pub name: String,
+    /// Empty in caches that don't keep examine texts.
+    pub examine: String,
+    /// The model shown on the ground and in inventory icons.
+    pub model: Option<u32>,
+    pub zoom_2d: u16,
+    pub x_angle_2d: u16,
+    pub y_angle_2d: u16,
+    pub z_angle_2d: u16,
+    pub x_offset_2d: i32,
+    pub y_offset_2d: i32,
+    pub stackable: bool,
+    pub cost: i32,
+    pub members: bool,
+    /// Models worn by male and female characters, up to three each.
+    pub male_models: [Option<u32>; 3],
+    pub female_models: [Option<u32>; 3],
+    /// HSL colours of the model replaced by others, as (old, new).
+    pub recolours: Vec<(u16, u16)>,
+    /// Textures of the model replaced by others, as (old, new).
+    pub retextures: Vec<(u16, u16)>,
+    /// The item this is the noted version of, and the item drawn as the note.
+    pub cert_link: Option<u32>,
+    pub cert_template: Option<u32>,
+    /// Items shown instead once the stack reaches a count, as (item, count).
+    pub count_objs: Vec<(u32, u16)>,
+    /// Scale of the model along each axis, 128 is the original size.
+    pub resize: [u16; 3],
+    pub ambient: i8,
+    pub contrast: i16,
+    /// The first opcode that couldn't be decoded, fields after it are missing.
+    pub unknown_opcode: Option<u8>,
+}
+
+impl Default for ObjType {
+    fn default() -> Self {
+        Self {
+            id: 0,
+            name: "null".to_owned(),
+            examine: String::new(),
+            model: None,
+            zoom_2d: 2000,
+            x_angle_2d: 0,
+            y_angle_2d: 0,
+            z_angle_2d: 0,
+            x_offset_2d: 0,
+            y_offset_2d: 0,
+            stackable: false,
+            cost: 1,
+            members: false,
+            male_models: [None; 3],
+            female_models: [None; 3],
+            recolours: Vec::new(),
+            retextures: Vec::new(),
+            cert_link: None,
+            cert_template: None,
+            count_objs: Vec::new(),
+            resize: [128; 3],
+            ambient: 0,
+            contrast: 0,
+            unknown_opcode: None,
+        }
+    }
+}
+
+/// Ids of 65535 mean none.
+fn optional_id(id: u16) -> Option<u32> {
+    (id != u16::MAX).then_some(id as u32)
+}
+
+/// Offsets are stored unsigned and wrap at 32768.
+fn signed_offset(offset: u16) -> i32 {
+    offset as i16 as i32
+}
+
+fn read_pairs(buf: &mut &[u8]) -> Option<Vec<(u16, u16)>> {
+    let count = buf.try_g1()? as usize;
+    (0..count)
+        .map(|_| Some((buf.try_g2()?, buf.try_g2()?)))
+        .collect()
+}
+
+impl ObjType {
+    /// Decodes an item, None if the data ends early.
+    pub fn decode(id: u32, data: &[u8]) -> Option<Self> {
+        let mut obj = Self {
+            id,
+            ..Default::default()
+        };
+        let mut buf = data;
+        loop {
+            let opcode = buf.try_g1()?;
+            if opcode == 0 {
+                break;
+            }
+            if obj.decode_opcode(opcode, &mut buf).is_none() {
+                // Later opcodes can't be found without knowing the length of this one.
+                obj.unknown_opcode = Some(opcode);
+                break;
+            }
+        }
+        Some(obj)
+    }
+
+    fn decode_opcode(&mut self, opcode: u8, buf: &mut &[u8]) -> Option<()> {
+        match opcode {
+            1 => self.model = optional_id(buf.try_g2()?),
+            2 => self.name = buf.try_get_str_cp1252_to_utf8()?,
+            3 => self.examine = buf.try_get_str_cp1252_to_utf8()?,
+            4 => self.zoom_2d = buf.try_g2()?,
+            5 => self.x_angle_2d = buf.try_g2()?,
+            6 => self.y_angle_2d = buf.try_g2()?,
+            7 => self.x_offset_2d = signed_offset(buf.try_g2()?),
+            8 => self.y_offset_2d = signed_offset(buf.try_g2()?),
+            11 => self.stackable = true,
+            12 => self.cost = buf.try_g4s()?,
+            16 => self.members = true,
+            23 => self.male_models[0] = optional_id(buf.try_g2()?),
+            24 => self.male_models[1] = optional_id(buf.try_g2()?),
+            25 => self.female_models[0] = optional_id(buf.try_g2()?),
+            26 => self.female_models[1] = optional_id(buf.try_g2()?),
+            // Ground and inventory options.
+            30..=39 => {
+                buf.try_get_str_cp1252_to_utf8()?;
+            }
+            40 => self.recolours = read_pairs(buf)?,
+            41 => self.retextures = read_pairs(buf)?,
+            // Recolour palette indices.
+            42 => {
+                let count = buf.try_g1()? as usize;
+                buf.try_skip(count)?;
+            }
+            // Tradeable on the Grand Exchange.
+            65 => {}
+            78 => self.male_models[2] = optional_id(buf.try_g2()?),
+            79 => self.female_models[2] = optional_id(buf.try_g2()?),
+            // Chat head models.
+            90..=93 => buf.try_skip(2)?,
+            95 => self.z_angle_2d = buf.try_g2()?,
+            // Dummy item.
+            96 => buf.try_skip(1)?,
+            97 => self.cert_link = optional_id(buf.try_g2()?),
+            98 => self.cert_template = optional_id(buf.try_g2()?),
+            100..=109 => {
+                let obj = buf.try_g2()? as u32;
+                let count = buf.try_g2()?;
+                self.count_objs.push((obj, count));
+            }
+            110..=112 => self.resize[(opcode - 110) as usize] = buf.try_g2()?,
+            113 => self.ambient = buf.try_g1s()?,
+            // Stored divided by 5.
+            114 => self.contrast = buf.try_g1s()? as i16 * 5,
+            // Team.
+            115 => buf.try_skip(1)?,
+            // Lent item link and template.
+            121 | 122 => buf.try_skip(2)?,
+            // Worn model offsets.
+            125 | 126 => buf.try_skip(3)?,
+            // Cursors for options.
+            127..=130 => buf.try_skip(3)?,
+            249 => {
+                let count = buf.try_g1()?;
+                for _ in 0..count {
+                    let is_string = buf.try_g1()? == 1;
+                    buf.try_skip(3)?;
+                    if is_string {
+                        buf.try_get_str_cp1252_to_utf8()?;
+                    } else {
+                        buf.try_skip(4)?;
+                    }
+                }
+            }
+            _ => return None,
+        }
+        Some(())
+    }
+
+    /// The lighting of the item's models, relative to the client's defaults for item models.
+    pub fn lighting(&self) -> (i16, i16) {
+        (64 + self.ambient as i16, 768 + self.contrast)
+    }
+}
@@ -0,0 +1,74 @@
+use crate::runetek5::{
+    graphics::model::ModelLit,
+    math::trig::{JAG_180_DEGREES, JAG_270_DEGREES, JAG_90_DEGREES},
+};
+
+pub const TILE_SIZE: i32 = 128;
+
+/// Where a snapped origin lands within a tile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TileSnap {
+    /// The middle of the tile, how centred locs are placed.
+    Centre,
+    /// The tile corner, for walls and locs spanning an even number of tiles.
+    Edge,
+}
+
+/// Snaps a world coordinate in model units to the nearest tile centre or edge.
+pub fn snap_to_tile(value: i32, snap: TileSnap) -> i32 {
+    match snap {
+        TileSnap::Centre => value.div_euclid(TILE_SIZE) * TILE_SIZE + TILE_SIZE / 2,
+        TileSnap::Edge => (value + TILE_SIZE / 2).div_euclid(TILE_SIZE) * TILE_SIZE,
+    }
+}
+
+/// A loc placed on the tile grid, rotated in 90 degree steps like locs in game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TilePlacement {
+    pub tile_x: i32,
+    pub tile_z: i32,
+    /// Quarter turns, 0 to 3.
+    pub rotation: u8,
+    pub snap: TileSnap,
+}
+
+impl TilePlacement {
+    /// Places an origin given in model units at the nearest tile.
+    pub fn from_world(x: i32, z: i32, rotation: u8, snap: TileSnap) -> Self {
+        let (x, z) = (snap_to_tile(x, snap), snap_to_tile(z, snap));
+        Self {
+            tile_x: x.div_euclid(TILE_SIZE),
+            tile_z: z.div_euclid(TILE_SIZE),
+            rotation: rotation & 0x3,
+            snap,
+        }
+    }
+
+    pub fn rotate_clockwise(&mut self) {
+        self.rotation = (self.rotation + 1) & 0x3;
+    }
+
+    /// The model origin in model units.
+    pub fn world_origin(&self) -> (i32, i32) {
+        let offset = match self.snap {
+            TileSnap::Centre => TILE_SIZE / 2,
+            TileSnap::Edge => 0,
+        };
+        (
+            self.tile_x * TILE_SIZE + offset,
+            self.tile_z * TILE_SIZE + offset,
+        )
+    }
+
+    /// Rotates and moves a model centred on its origin into place.
+    pub fn apply(&self, model: &mut ModelLit) {
+        match self.rotation & 0x3 {
+            1 => model.rotate_y(JAG_90_DEGREES),
+            2 => model.rotate_y(JAG_180_DEGREES),
+            3 => model.rotate_y(JAG_270_DEGREES),
+            _ => {}
+        }
+        let (x, z) = self.world_origin();
+        model.translate(x, 0, z);
+    }
+}
@@ -1,9 +1,64 @@
-export async function fetch_group(archive, group) {
-    const response = await fetch("https://archive.openrs2.org/caches/runescape/2064/archives/" + archive + "/groups/" + group + ".dat");
+// Groups of an OpenRS2 cache never change, so they are kept in the browser's Cache API under
+// their URLs once fetched. The service worker serves them from there too, see assets/sw.js.
+const GROUP_CACHE_PREFIX = "openrs2-";
+const OFFLINE_MODE_KEY = "offline_mode";
+
+function group_url(cache, archive, group) {
+    return "https://archive.openrs2.org/caches/runescape/" + cache + "/archives/" + archive + "/groups/" + group + ".dat";
+}
+
+async function open_group_cache(cache) {
+    // The Cache API only exists in secure contexts.
+    if (!("caches" in self)) {
+        return null;
+    }
+    try {
+        return await caches.open(GROUP_CACHE_PREFIX + cache);
+    } catch {
+        return null;
+    }
+}
+
+// Rejects with the status for HTTP errors, and with "offline" for groups that weren't stored
+// while `offline` is set.
+export async function fetch_group(cache, archive, group, offline) {
+    const url = group_url(cache, archive, group);
+    const store = await open_group_cache(cache);
+    const stored = store && await store.match(url);
+    if (stored) {
+        return await stored.arrayBuffer();
+    }
+    if (offline) {
+        throw "offline";
+    }
+    const response = await fetch(url);
+    if (!response.ok) {
+        throw response.status;
+    }
+    if (store) {
+        // Running out of quota only costs the offline copy.
+        store.put(url, response.clone()).catch(() => {});
+    }
     const data = await response.arrayBuffer();
     return data;
 }
 
+export function load_offline_mode() {
+    try {
+        return localStorage.getItem(OFFLINE_MODE_KEY) === "1";
+    } catch {
+        return false;
+    }
+}
+
+export function store_offline_mode(offline) {
+    try {
+        localStorage.setItem(OFFLINE_MODE_KEY, offline ? "1" : "0");
+    } catch {
+        // Storage is disabled, offline mode lasts until the page is closed.
+    }
+}
+
 // export function fetch_test() {
 //     async_test();
 // }
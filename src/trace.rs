@@ -0,0 +1,261 @@
+//! Session tracing with chrome://tracing export.
+//!
+//! Hot paths (JS5 fetch and unpack, the decoders, lighting and uploads) are wrapped in `tracing`
+//! spans. [`init`] installs a subscriber that, while recording is switched on, turns every entered
+//! span into a complete ("X") event of the Chrome trace event format. The buffer can then be dumped
+//! with [`to_chrome_json`] and loaded into chrome://tracing or Perfetto. While recording is off
+//! the subscriber rejects spans before they are created, so the instrumentation is cheap.
+
+use std::cell::Cell;
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::subscriber::Interest;
+use tracing::{Metadata, Subscriber};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt as _};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::export::json::{self, JsonObject};
+
+/// Recording stops once this many events are buffered, about 100 MB of JSON.
+pub const MAX_EVENTS: usize = 1 << 20;
+
+static RECORDING: AtomicBool = AtomicBool::new(false);
+static EVENTS: Mutex<Vec<TraceEvent>> = Mutex::new(Vec::new());
+static NEXT_THREAD_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    static THREAD_ID: Cell<u64> = const { Cell::new(0) };
+}
+
+struct TraceEvent {
+    name: &'static str,
+    category: &'static str,
+    /// Microseconds, see [`timestamp`].
+    start: f64,
+    duration: f64,
+    thread_id: u64,
+    /// Serialized JSON object of the span's fields.
+    args: String,
+}
+
+/// Installs the recording subscriber as the global default.
+pub fn init() {
+    let subscriber = tracing_subscriber::registry().with(ChromeTraceLayer);
+    if let Err(err) = tracing::subscriber::set_global_default(subscriber) {
+        log::warn!("Failed to install the trace subscriber: {err}");
+    }
+}
+
+pub fn is_recording() -> bool {
+    RECORDING.load(Ordering::Relaxed)
+}
+
+pub fn set_recording(recording: bool) {
+    RECORDING.store(recording, Ordering::Relaxed);
+}
+
+pub fn event_count() -> usize {
+    EVENTS.lock().unwrap().len()
+}
+
+pub fn clear() {
+    EVENTS.lock().unwrap().clear();
+}
+
+/// Microseconds since an arbitrary origin, the unit of the trace timestamps.
+#[cfg(target_arch = "wasm32")]
+pub fn timestamp() -> f64 {
+    #[wasm_bindgen::prelude::wasm_bindgen]
+    extern "C" {
+        #[wasm_bindgen(js_namespace = performance)]
+        fn now() -> f64;
+    }
+
+    now() * 1000.0
+}
+
+/// Microseconds since an arbitrary origin, the unit of the trace timestamps.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn timestamp() -> f64 {
+    static ORIGIN: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();
+    ORIGIN
+        .get_or_init(std::time::Instant::now)
+        .elapsed()
+        .as_secs_f64()
+        * 1_000_000.0
+}
+
+/// Records an interval that started at `start` (from [`timestamp`]) and ends now, for work that
+/// can't be wrapped in a span such as a network request completing in a later frame.
+pub fn record_interval(name: &'static str, category: &'static str, start: f64, args: JsonObject) {
+    if !is_recording() {
+        return;
+    }
+    push(TraceEvent {
+        name,
+        category,
+        start,
+        duration: timestamp() - start,
+        thread_id: thread_id(),
+        args: args.finish(),
+    });
+}
+
+/// Serializes the recorded events in the Chrome trace event format. Events with a timestamp or
+/// duration that isn't finite are left out, JSON can't hold them and the trace wouldn't load.
+pub fn to_chrome_json() -> String {
+    let events = EVENTS.lock().unwrap();
+    let events: Vec<String> = events
+        .iter()
+        .filter(|event| event.start.is_finite() && event.duration.is_finite())
+        .map(|event| {
+            JsonObject::new()
+                .string("name", event.name)
+                .string("cat", event.category)
+                .string("ph", "X")
+                .number("ts", event.start)
+                .number("dur", event.duration)
+                .number("pid", 1)
+                .number("tid", event.thread_id as f64)
+                .raw("args", &event.args)
+                .finish()
+        })
+        .collect();
+    JsonObject::new()
+        .raw("traceEvents", &json::array(&events))
+        .string("displayTimeUnit", "ms")
+        .finish()
+}
+
+fn thread_id() -> u64 {
+    THREAD_ID.with(|id| {
+        if id.get() == 0 {
+            id.set(NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed));
+        }
+        id.get()
+    })
+}
+
+fn push(event: TraceEvent) {
+    let mut events = EVENTS.lock().unwrap();
+    if events.len() >= MAX_EVENTS {
+        if is_recording() {
+            log::warn!("Trace buffer is full, stopping the recording");
+            set_recording(false);
+        }
+        return;
+    }
+    events.push(event);
+}
+
+struct ChromeTraceLayer;
+
+/// When the span was last entered.
+struct SpanStart(f64);
+
+struct SpanArgs(String);
+
+#[derive(Default)]
+struct ArgsVisitor {
+    fields: Vec<(&'static str, String)>,
+}
+
+impl ArgsVisitor {
+    fn finish(self) -> String {
+        self.fields
+            .iter()
+            .fold(JsonObject::new(), |object, (name, value)| {
+                object.raw(name, value)
+            })
+            .finish()
+    }
+}
+
+impl Visit for ArgsVisitor {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        // JSON has no NaN or infinity.
+        let value = if value.is_finite() {
+            value.to_string()
+        } else {
+            "null".to_owned()
+        };
+        self.fields.push((field.name(), value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.fields.push((field.name(), value.to_string()));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.fields.push((field.name(), value.to_string()));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.fields.push((field.name(), value.to_string()));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        let mut out = String::new();
+        json::write_string(&mut out, value);
+        self.fields.push((field.name(), out));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        let mut debug = String::new();
+        let _ = write!(debug, "{value:?}");
+        self.record_str(field, &debug);
+    }
+}
+
+impl<S> Layer<S> for ChromeTraceLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    /// Recording is toggled at runtime, so callsites must not cache being disabled.
+    fn register_callsite(&self, _metadata: &'static Metadata<'static>) -> Interest {
+        Interest::sometimes()
+    }
+
+    fn enabled(&self, _metadata: &Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        is_recording()
+    }
+
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut visitor = ArgsVisitor::default();
+        attrs.record(&mut visitor);
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(SpanArgs(visitor.finish()));
+        }
+    }
+
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().replace(SpanStart(timestamp()));
+        }
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let extensions = span.extensions();
+        let Some(SpanStart(start)) = extensions.get::<SpanStart>() else {
+            return;
+        };
+        let args = extensions
+            .get::<SpanArgs>()
+            .map_or_else(|| "{}".to_owned(), |args| args.0.clone());
+        push(TraceEvent {
+            name: span.name(),
+            category: span.metadata().target(),
+            start: *start,
+            duration: timestamp() - start,
+            thread_id: thread_id(),
+            args,
+        });
+    }
+}